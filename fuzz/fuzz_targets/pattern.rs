@@ -0,0 +1,14 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use pepper::pattern::check_match_invariants;
+
+// input is the pattern and the text separated by the first '\0'
+fuzz_target!(|data: &[u8]| {
+    let data = match std::str::from_utf8(data) {
+        Ok(data) => data,
+        Err(_) => return,
+    };
+    let (pattern, text) = data.split_once('\0').unwrap_or((data, ""));
+    check_match_invariants(pattern, text);
+});
@@ -9,9 +9,17 @@ map-normal ci cvcCglccgii
 map-normal o dgli<enter>
 map-normal O dgii<enter><up>
 map-normal J djgivkgli<space><esc>
+map-normal gx :<space>open-url<enter>
+map-normal ga :<space>buffer-last<enter>
+map-normal gn :<space>buffer-next<enter>
+map-normal gp :<space>buffer-prev<enter>
+map-normal gY :<space>yank-history<enter>
 
 map-normal K :<space>lsp-hover<enter>
 map-normal gd :<space>lsp-definition<enter>
+map-normal gD :<space>lsp-declaration<enter>
+map-normal gy :<space>lsp-type-definition<enter>
+map-normal gI :<space>lsp-implementation<enter>
 map-normal gr :<space>lsp-references<enter>
 map-normal gs :<space>lsp-document-symbols<enter>
 map-normal rr :<space>lsp-rename<enter>
@@ -24,6 +32,8 @@ alias qa quit-all
 alias o open
 alias s save
 alias sa save-all
+alias sq save-quit
+alias saq save-all-quit
 alias r reopen
 alias ra reopen-all
 alias c close
@@ -35,6 +45,27 @@ syntax-symbols ","
 syntax-literals "%d"
 syntax-texts "{%w-_}"
 
+syntax "**/*.blame"
+syntax-keywords "^{%^}%w{%w}"
+syntax-comments "%({[!%)]}%)"
+
+syntax "**/*.diff"
+syntax-keywords "^@@{.}"
+syntax-strings "^+{.}"
+syntax-literals "^-{.}"
+
+syntax "**/COMMIT_EDITMSG"
+syntax-keywords "^# @@{.}"
+syntax-strings "^# +{.}"
+syntax-literals "^# -{.}"
+syntax-comments "#{.}"
+
+syntax "**/MERGE_MSG"
+syntax-keywords "^# @@{.}"
+syntax-strings "^# +{.}"
+syntax-literals "^# -{.}"
+syntax-comments "#{.}"
+
 syntax "**/*.pp"
 syntax-keywords ""
 syntax-strings [["{!".}|'{!'.}|%[%[{!(%]%]).}]]
@@ -84,7 +115,7 @@ syntax-strings [['{(\\)(\')!'.}|"{(\\)(\")!".}]]
 syntax-comments [[#{.}]]
 
 # https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Lexical_grammar#keywords
-syntax "**/*.{js,ts}"
+syntax "**/*.{js,ts,json}"
 syntax-keywords break|case|catch|class|const|continue|debugger|default|delete|do|else|export|extends|finally|for|function|if|import|in|instanceof|new|return|super|switch|this|throw|try|typeof|var|void|while|witch|yield|enum|implements|interface|let|package|private|protected|public|static|yield|await
 syntax-types %u{%w_}
 syntax-symbols %(|%)|%[|%]|%{|%}|%.|:|;|,|=|<|>|+|-|/|*|%%|%.|%!|?|&|%||@
@@ -99,10 +130,69 @@ syntax-literals %[{!%].}%({!%).}
 syntax-strings [[```{!(```).$}|`{!`.}]]
 syntax-texts {%w-_}
 
-syntax "**/*.html"
+syntax "**/*.{html,xml}"
 syntax-keywords "%!DOCTYPE"
 syntax-symbols =
 syntax-strings [['{(\\)(\')!'.}|"{(\\)(\")!".}]]
 syntax-comments <%!--{!(-->).$}
 syntax-texts {%w-_}
 
+syntax "**/*.{sh,bash,zsh}"
+syntax-keywords if|then|else|elif|fi|case|esac|for|while|until|do|done|in|function|select|return|local|export|readonly|declare|break|continue|exit
+syntax-symbols %(|%)|%[|%]|%{|%}|;|=|<|>|&|%||%!
+syntax-literals %$%{{!%}.}|%${%w_}|%$.|%d{%d}
+syntax-strings [['{!'.}|"{(\\)(\")!".}]]
+syntax-comments [[#{.}]]
+
+syntax "**/*.mk"
+syntax-keywords ifeq|ifneq|ifdef|ifndef|else|endif|include|define|endef|export|override|%.PHONY
+syntax-symbols :|=|?|+|%(|%)|@|%%
+syntax-literals %$%({!%).}|%$%{{!%}.}|%$.
+syntax-strings [['{!'.}|"{(\\)(\")!".}]]
+syntax-comments [[#{.}]]
+
+syntax "**/*.dockerfile"
+syntax-keywords FROM|RUN|CMD|LABEL|MAINTAINER|EXPOSE|ENV|ADD|COPY|ENTRYPOINT|VOLUME|USER|WORKDIR|ARG|ONBUILD|STOPSIGNAL|HEALTHCHECK|SHELL|AS
+syntax-symbols %[|%]|,|=|&|%|
+syntax-literals %$%{{!%}.}|%${%w_}|%d{%d}
+syntax-strings [['{!'.}|"{(\\)(\")!".}]]
+syntax-comments [[#{.}]]
+
+syntax "**/*.cmake"
+syntax-keywords if|elseif|else|endif|foreach|endforeach|while|endwhile|function|endfunction|macro|endmacro|return|break|continue|set|unset|option|project|include|AND|OR|NOT
+syntax-types %u{%u%d_}
+syntax-symbols %(|%)
+syntax-literals %$%{{!%}.}|%d{%d%.}
+syntax-strings [["{(\\)(\")!".}]]
+syntax-comments [[#{.}]]
+
+detect-syntax "#!sh" "detect.sh"
+detect-syntax "#!bash" "detect.sh"
+detect-syntax "#!zsh" "detect.sh"
+detect-syntax "#!python" "detect.py"
+detect-syntax "#!lua" "detect.lua"
+detect-syntax "#!node" "detect.js"
+detect-syntax "**/{Makefile,makefile,GNUmakefile}" "detect.mk"
+detect-syntax "**/{Dockerfile,Containerfile}" "detect.dockerfile"
+detect-syntax "**/CMakeLists.txt" "detect.cmake"
+detect-syntax "=rust" "detect.rs"
+detect-syntax "=python" "detect.py"
+detect-syntax "=javascript" "detect.js"
+detect-syntax "=typescript" "detect.ts"
+detect-syntax "=markdown" "detect.md"
+detect-syntax "=make" "detect.mk"
+detect-syntax "=csharp" "detect.cs"
+detect-syntax "=cpp" "detect.cpp"
+detect-syntax "=c++" "detect.cpp"
+detect-syntax "=shell-script" "detect.sh"
+detect-syntax "^{" "detect.json"
+
+workspace-marker "**/*.rs" "Cargo.toml"
+workspace-marker "**/*.go" "go.mod"
+workspace-marker "**/*.{js,ts}" "package.json"
+workspace-marker "**/*.py" "pyproject.toml setup.py"
+
+make-error-format "**/*.rs" "--> %f:%l:%c"
+make-error-format "**/*.py" 'File "%f", line %l, %m'
+
+formatter "**/*.rs" "rustfmt --edition 2021"
@@ -1,20 +1,41 @@
-use std::{env, fs, io, panic, path::Path, time::Duration};
+use std::{
+    env, fmt, fs, io, panic,
+    path::Path,
+    time::{Duration, Instant},
+};
 
 use crate::{
     client::ClientManager,
+    command::push_escaped_arg,
+    compression,
+    config::ServerExitPolicy,
     editor::{Editor, EditorControlFlow},
-    editor_utils::{load_config, MessageKind},
-    events::{ClientEvent, ClientEventReceiver, ServerEvent, TargetClient},
+    editor_utils::{load_config, show_text_in_buffer, MessageKind},
+    events::{
+        ClientEvent, ClientEventReceiver, CursorShape, ServerEvent, TargetClient, PROTOCOL_VERSION,
+    },
     platform::{Key, Platform, PlatformEvent, PlatformRequest},
-    serialization::{DeserializeError, Serialize},
+    plugin,
+    profile::{begin_startup_item, end_startup_item, FrameStage, StartupProfile},
+    serialization::{DeserializeError, Serialize, Serializer},
+    theme::Color,
     ui, Args,
 };
 
+const DISPLAY_COMPRESSION_THRESHOLD: usize = 1024;
+const INCOMPATIBLE_PROTOCOL_ERROR: &str =
+    "incompatible client and server protocol versions. are they from the same pepper version?";
+const INVALID_SERVER_DATA_ERROR: &str = "client received invalid data from server";
+const INVALID_COMPRESSED_DISPLAY_ERROR: &str =
+    "client received invalid compressed display from server";
+
 pub struct ServerApplication {
-    editor: Editor,
+    pub editor: Editor,
     pub platform: Platform,
-    clients: ClientManager,
+    pub clients: ClientManager,
     client_event_receiver: ClientEventReceiver,
+    last_client_left_at: Option<Instant>,
+    compression_buf: Vec<u8>,
 }
 impl ServerApplication {
     pub const fn connection_buffer_len() -> usize {
@@ -31,8 +52,16 @@ impl ServerApplication {
         let mut platform = Platform::default();
         let mut clients = ClientManager::default();
 
+        if args.startup_profile {
+            let mut profile = StartupProfile::default();
+            profile.add("parse args", args.parse_duration);
+            editor.startup_profile = Some(profile);
+        }
+
         if !args.no_default_config {
             let source = include_str!("../rc/default_config.pp");
+            let profile_index =
+                begin_startup_item(&mut editor.startup_profile, "default_config.pp");
             load_config(
                 &mut editor,
                 &mut platform,
@@ -40,6 +69,7 @@ impl ServerApplication {
                 "default_config.pp",
                 source,
             );
+            end_startup_item(&mut editor.startup_profile, profile_index);
         }
 
         for config in args.configs {
@@ -47,6 +77,7 @@ impl ServerApplication {
             if config.suppress_file_not_found && !path.exists() {
                 continue;
             }
+            let profile_index = begin_startup_item(&mut editor.startup_profile, &config.path);
             match fs::read_to_string(path) {
                 Ok(source) => match load_config(
                     &mut editor,
@@ -63,6 +94,21 @@ impl ServerApplication {
                     .write(MessageKind::Error)
                     .fmt(format_args!("could not load config '{}'", config.path)),
             }
+            end_startup_item(&mut editor.startup_profile, profile_index);
+        }
+
+        let profile_index = begin_startup_item(&mut editor.startup_profile, "plugins");
+        match plugin::manager::load_all(&mut editor, &mut platform, &mut clients) {
+            EditorControlFlow::Continue => (),
+            _ => return None,
+        }
+        end_startup_item(&mut editor.startup_profile, profile_index);
+
+        if let Some(profile) = editor.startup_profile.take() {
+            editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("{}", profile));
         }
 
         Some(Self {
@@ -70,6 +116,8 @@ impl ServerApplication {
             platform,
             clients,
             client_event_receiver: ClientEventReceiver::default(),
+            last_client_left_at: None,
+            compression_buf: Vec::new(),
         })
     }
 
@@ -77,15 +125,41 @@ impl ServerApplication {
     where
         I: Iterator<Item = PlatformEvent>,
     {
+        let events_start = Instant::now();
         for event in events {
             match event {
-                PlatformEvent::Idle => self.editor.on_idle(&mut self.clients, &mut self.platform),
-                PlatformEvent::ConnectionOpen { handle } => self.clients.on_client_joined(handle),
+                PlatformEvent::Idle => {
+                    self.editor.on_idle(&mut self.clients, &mut self.platform);
+                    if let Some(instant) = self.last_client_left_at {
+                        if let ServerExitPolicy::AfterIdleSeconds(seconds) =
+                            self.editor.config.server_exit_policy
+                        {
+                            if instant.elapsed() >= Duration::from_secs(seconds as _) {
+                                self.platform.requests.enqueue(PlatformRequest::Quit);
+                                break;
+                            }
+                            self.platform.requests.enqueue(PlatformRequest::Redraw);
+                        }
+                    }
+                }
+                PlatformEvent::ConnectionOpen { handle } => {
+                    self.last_client_left_at = None;
+                    self.clients.on_client_joined(handle);
+                }
                 PlatformEvent::ConnectionClose { handle } => {
                     self.clients.on_client_left(handle);
                     if self.clients.iter().next().is_none() {
-                        self.platform.requests.enqueue(PlatformRequest::Quit);
-                        break;
+                        match self.editor.config.server_exit_policy {
+                            ServerExitPolicy::KeepRunning => (),
+                            ServerExitPolicy::Immediately => {
+                                self.platform.requests.enqueue(PlatformRequest::Quit);
+                                break;
+                            }
+                            ServerExitPolicy::AfterIdleSeconds(_) => {
+                                self.last_client_left_at = Some(Instant::now());
+                                self.platform.requests.enqueue(PlatformRequest::Redraw);
+                            }
+                        }
                     }
                 }
                 PlatformEvent::ConnectionOutput { handle, buf } => {
@@ -95,6 +169,32 @@ impl ServerApplication {
                     self.platform.buf_pool.release(buf);
 
                     while let Some(event) = events.next(&self.client_event_receiver) {
+                        let client = self.clients.get_mut(handle);
+                        if !client.handshake_done {
+                            let mut buf = self.platform.buf_pool.acquire();
+                            if let ClientEvent::Handshake(PROTOCOL_VERSION, compress_display) =
+                                event
+                            {
+                                client.handshake_done = true;
+                                client.compress_display = compress_display;
+                                ServerEvent::Handshake(PROTOCOL_VERSION).serialize(buf.write());
+                                self.platform
+                                    .requests
+                                    .enqueue(PlatformRequest::WriteToClient { handle, buf });
+                                continue;
+                            }
+
+                            ServerEvent::CommandError(INCOMPATIBLE_PROTOCOL_ERROR)
+                                .serialize(buf.write());
+                            self.platform
+                                .requests
+                                .enqueue(PlatformRequest::WriteToClient { handle, buf });
+                            self.platform
+                                .requests
+                                .enqueue(PlatformRequest::CloseClient { handle });
+                            break;
+                        }
+
                         match self.editor.on_client_event(
                             &mut self.platform,
                             &mut self.clients,
@@ -116,6 +216,20 @@ impl ServerApplication {
                                 break;
                             }
                             EditorControlFlow::QuitAll => {
+                                match self.editor.config.server_exit_policy {
+                                    ServerExitPolicy::KeepRunning => {
+                                        for c in self.clients.iter() {
+                                            let handle = c.handle();
+                                            self.platform
+                                                .requests
+                                                .enqueue(PlatformRequest::CloseClient { handle });
+                                        }
+                                    }
+                                    _ => self.platform.requests.enqueue(PlatformRequest::Quit),
+                                }
+                                break;
+                            }
+                            EditorControlFlow::QuitServer => {
                                 self.platform.requests.enqueue(PlatformRequest::Quit);
                                 break;
                             }
@@ -136,51 +250,115 @@ impl ServerApplication {
                     );
                     self.platform.buf_pool.release(buf);
                 }
+                PlatformEvent::ProcessErrorOutput { tag, buf } => {
+                    self.editor.on_process_error_output(tag, buf.as_bytes());
+                    self.platform.buf_pool.release(buf);
+                }
                 PlatformEvent::ProcessExit { tag } => {
                     self.editor
                         .on_process_exit(&mut self.platform, &mut self.clients, tag)
                 }
+                PlatformEvent::JobFinished { tag, buf } => {
+                    self.editor.on_job_finished(
+                        &mut self.platform,
+                        &mut self.clients,
+                        tag,
+                        buf.as_bytes(),
+                    );
+                    self.platform.buf_pool.release(buf);
+                }
             }
         }
 
+        self.editor
+            .frame_profiler
+            .record(FrameStage::Events, events_start.elapsed());
+
+        let mut report = self.editor.string_pool.acquire();
+        if let Some(handle) = self.editor.frame_profiler.poll_report(&mut report) {
+            if self.clients.try_get(handle).is_some() {
+                show_text_in_buffer(
+                    &mut self.editor,
+                    &mut self.clients,
+                    handle,
+                    Path::new("profile.log"),
+                    &report,
+                );
+            }
+        }
+        self.editor.string_pool.release(report);
+
+        let highlighting_start = Instant::now();
+        plugin::on_pre_render(&mut self.editor, &mut self.platform, &mut self.clients);
         let needs_redraw = self.editor.on_pre_render(&mut self.clients);
         if needs_redraw {
             self.platform.requests.enqueue(PlatformRequest::Redraw);
         }
+        self.editor
+            .frame_profiler
+            .record(FrameStage::Highlighting, highlighting_start.elapsed());
 
         let focused_client_handle = self.clients.focused_client();
+        let mut rendering_duration = Duration::ZERO;
+        let mut serialization_duration = Duration::ZERO;
         for c in self.clients.iter() {
             if !c.has_ui() {
                 continue;
             }
 
+            let rendering_start = Instant::now();
             let mut buf = self.platform.buf_pool.acquire();
             let write = buf.write_with_len(ServerEvent::display_header_len());
             let ctx = ui::RenderContext {
                 editor: &self.editor,
+                display_config: c.display_config(&self.editor),
                 clients: &self.clients,
                 viewport_size: c.viewport_size,
                 scroll: c.scroll,
                 draw_height: c.height,
                 has_focus: focused_client_handle == Some(c.handle()),
             };
-            ui::render(&ctx, c.buffer_view_handle(), write);
-            ServerEvent::serialize_display_header(write);
+            let (cursor_shape, cursor_color) = ui::render(&ctx, c.buffer_view_handle(), write);
+            rendering_duration += rendering_start.elapsed();
+
+            let serialization_start = Instant::now();
+            let header_len = ServerEvent::display_header_len();
+            if c.compress_display && write.len() > header_len + DISPLAY_COMPRESSION_THRESHOLD {
+                self.compression_buf.clear();
+                compression::compress(&write[header_len..], &mut self.compression_buf);
+                write.clear();
+                ServerEvent::CompressedDisplay(&self.compression_buf).serialize(write);
+            } else {
+                ServerEvent::serialize_display_header(write);
+            }
+            ServerEvent::CursorStyle(cursor_shape, cursor_color).serialize(write);
+            serialization_duration += serialization_start.elapsed();
 
             let handle = c.handle();
             self.platform
                 .requests
                 .enqueue(PlatformRequest::WriteToClient { handle, buf });
         }
+
+        let frame_profiler = &mut self.editor.frame_profiler;
+        frame_profiler.record(FrameStage::Rendering, rendering_duration);
+        frame_profiler.record(FrameStage::Serialization, serialization_duration);
+        frame_profiler.end_frame();
     }
 }
 
 pub struct ClientApplication {
     is_pipped: bool,
+    is_headless: bool,
+    has_command_errors: bool,
+    has_protocol_error: bool,
+    handshake_done: bool,
     target_client: TargetClient,
-    stdin_read_buf: Vec<u8>, // TODO: do something with it
     server_read_buf: Vec<u8>,
     server_write_buf: Vec<u8>,
+    display_buf: Vec<u8>,
+    cursor_style: Option<(CursorShape, Color)>,
+    input_record: Option<InputRecord>,
     stdout: io::StdoutLock<'static>,
 }
 impl ClientApplication {
@@ -199,10 +377,16 @@ impl ClientApplication {
 
         Self {
             is_pipped,
+            is_headless: false,
+            has_command_errors: false,
+            has_protocol_error: false,
+            handshake_done: false,
             target_client: TargetClient::Sender,
-            stdin_read_buf: Vec::new(),
             server_read_buf: Vec::new(),
             server_write_buf: Vec::new(),
+            display_buf: Vec::new(),
+            cursor_style: None,
+            input_record: None,
             stdout,
         }
     }
@@ -216,7 +400,43 @@ impl ClientApplication {
             self.is_pipped = true;
         }
 
+        if !args.send.is_empty() || args.wait {
+            self.is_pipped = true;
+            self.is_headless = true;
+            self.target_client = TargetClient::FocusedOrSender;
+        }
+
         self.server_write_buf.clear();
+        ClientEvent::Handshake(PROTOCOL_VERSION, args.compress)
+            .serialize(&mut self.server_write_buf);
+        let events_start = self.server_write_buf.len();
+
+        if let Some(path) = &args.record_input {
+            match InputRecord::create(path) {
+                Ok(record) => self.input_record = Some(record),
+                Err(error) => {
+                    self.has_command_errors = true;
+                    eprintln!("could not create input record '{}': {}", path, error);
+                }
+            }
+        }
+
+        if let Some(path) = &args.replay_input {
+            match InputRecord::replay(path, &mut self.server_write_buf) {
+                Ok(()) => {
+                    self.reinit_screen();
+                    if let Some(record) = &mut self.input_record {
+                        record.write(&self.server_write_buf[events_start..]);
+                    }
+                    return self.server_write_buf.as_slice();
+                }
+                Err(error) => {
+                    self.has_command_errors = true;
+                    self.server_write_buf.truncate(events_start);
+                    eprintln!("could not replay input record '{}': {}", path, error);
+                }
+            }
+        }
 
         self.reinit_screen();
         if !self.is_pipped && !args.as_focused_client {
@@ -233,14 +453,60 @@ impl ClientApplication {
                 .serialize(&mut self.server_write_buf);
         }
 
-        if args.quit {
+        for command in &args.commands {
+            ClientEvent::Command(self.target_client, command).serialize(&mut self.server_write_buf);
+        }
+
+        for command in &args.send {
+            if command == "-" {
+                use io::Read;
+                let mut stdin_commands = String::new();
+                let _ = io::stdin().read_to_string(&mut stdin_commands);
+                for command in stdin_commands.lines() {
+                    if !command.trim().is_empty() {
+                        ClientEvent::Command(self.target_client, command)
+                            .serialize(&mut self.server_write_buf);
+                    }
+                }
+            } else {
+                ClientEvent::Command(self.target_client, command)
+                    .serialize(&mut self.server_write_buf);
+            }
+        }
+
+        let wait_path = match args.files.last() {
+            Some(path) if args.wait => Some(path),
+            _ => None,
+        };
+        if let Some(path) = wait_path {
+            commands.clear();
+            commands.push_str("wait-buffer ");
+            push_escaped_arg(&mut commands, path);
+            ClientEvent::Command(TargetClient::Sender, &commands)
+                .serialize(&mut self.server_write_buf);
+        } else if self.is_headless {
+            ClientEvent::Command(TargetClient::Sender, "quit!")
+                .serialize(&mut self.server_write_buf);
+        } else if args.quit {
             ClientEvent::Command(TargetClient::Sender, "quit")
                 .serialize(&mut self.server_write_buf);
         }
 
+        if let Some(record) = &mut self.input_record {
+            record.write(&self.server_write_buf[events_start..]);
+        }
+
         self.server_write_buf.as_slice()
     }
 
+    pub fn has_command_errors(&self) -> bool {
+        self.has_command_errors
+    }
+
+    pub fn has_protocol_error(&self) -> bool {
+        self.has_protocol_error
+    }
+
     pub fn reinit_screen(&mut self) {
         if self.is_pipped {
             return;
@@ -259,6 +525,9 @@ impl ClientApplication {
         }
 
         use io::Write;
+        if self.cursor_style.take().is_some() {
+            let _ = self.stdout.write_all(ui::RESET_CURSOR_STYLE_CODE);
+        }
         let _ = self.stdout.write_all(ui::EXIT_ALTERNATE_BUFFER_CODE);
         let _ = self.stdout.write_all(ui::SHOW_CURSOR_CODE);
         let _ = self.stdout.write_all(ui::RESET_STYLE_CODE);
@@ -276,6 +545,10 @@ impl ClientApplication {
 
         self.server_write_buf.clear();
 
+        if self.is_headless {
+            return self.receive_command_outputs(server_bytes);
+        }
+
         if let Some((width, height)) = resize {
             ClientEvent::Resize(width as _, height as _).serialize(&mut self.server_write_buf);
         }
@@ -285,10 +558,12 @@ impl ClientApplication {
         }
 
         if !stdin_bytes.is_empty() {
-            self.stdin_read_buf.extend_from_slice(stdin_bytes);
+            ClientEvent::StdinInput(self.target_client, stdin_bytes)
+                .serialize(&mut self.server_write_buf);
         }
 
         let mut suspend = false;
+        let mut protocol_error = None;
         if !server_bytes.is_empty() {
             self.server_read_buf.extend_from_slice(server_bytes);
             let mut read_slice = &self.server_read_buf[..];
@@ -296,20 +571,69 @@ impl ClientApplication {
             loop {
                 let previous_slice = read_slice;
                 match ServerEvent::deserialize(&mut read_slice) {
-                    Ok(ServerEvent::Display(display)) => self.stdout.write_all(display).unwrap(),
+                    Ok(ServerEvent::Display(display)) => {
+                        if self.cursor_style.is_some() {
+                            self.stdout.write_all(ui::HIDE_CURSOR_CODE).unwrap();
+                        }
+                        self.stdout.write_all(display).unwrap();
+                    }
+                    Ok(ServerEvent::CompressedDisplay(display)) => {
+                        self.display_buf.clear();
+                        if compression::decompress(display, &mut self.display_buf).is_err() {
+                            self.server_read_buf.clear();
+                            self.has_protocol_error = true;
+                            protocol_error = Some(INVALID_COMPRESSED_DISPLAY_ERROR.into());
+                            break;
+                        }
+                        if self.cursor_style.is_some() {
+                            self.stdout.write_all(ui::HIDE_CURSOR_CODE).unwrap();
+                        }
+                        self.stdout.write_all(&self.display_buf).unwrap();
+                    }
+                    Ok(ServerEvent::CursorStyle(CursorShape::Hidden, _)) => (),
+                    Ok(ServerEvent::CursorStyle(shape, color)) => {
+                        if self.cursor_style != Some((shape, color)) {
+                            self.cursor_style = Some((shape, color));
+                            self.display_buf.clear();
+                            ui::set_cursor_style(&mut self.display_buf, shape, color);
+                            self.stdout.write_all(&self.display_buf).unwrap();
+                        }
+                        self.stdout.write_all(ui::SHOW_CURSOR_CODE).unwrap();
+                    }
                     Ok(ServerEvent::Suspend) => suspend = true,
                     Ok(ServerEvent::CommandOutput(output)) => {
-                        self.stdout.write_all(output.as_bytes()).unwrap();
-                        self.stdout.write_all(b"\0").unwrap();
+                        if self.is_pipped {
+                            self.stdout.write_all(output.as_bytes()).unwrap();
+                            self.stdout.write_all(b"\0").unwrap();
+                        }
+                    }
+                    Ok(ServerEvent::CommandError(error)) => {
+                        if self.is_pipped {
+                            let mut stderr = io::stderr();
+                            let _ = stderr.write_all(error.as_bytes());
+                            let _ = stderr.write_all(b"\0");
+                        } else if !self.handshake_done {
+                            protocol_error = Some(error.to_string());
+                        }
                     }
                     Ok(ServerEvent::Request(_)) => (),
+                    Ok(ServerEvent::Handshake(version)) => {
+                        if version == PROTOCOL_VERSION {
+                            self.handshake_done = true;
+                        } else {
+                            protocol_error = Some(INCOMPATIBLE_PROTOCOL_ERROR.into());
+                        }
+                    }
                     Err(DeserializeError::InsufficientData) => {
                         let read_len = self.server_read_buf.len() - previous_slice.len();
                         self.server_read_buf.drain(..read_len);
                         break;
                     }
                     Err(DeserializeError::InvalidData) => {
-                        panic!("client received invalid data from server")
+                        self.server_read_buf.clear();
+                        self.has_protocol_error = true;
+                        protocol_error = Some(INVALID_SERVER_DATA_ERROR.into());
+                        break;
                     }
                 }
             }
@@ -317,9 +641,149 @@ impl ClientApplication {
             self.stdout.flush().unwrap();
         }
 
+        if let Some(error) = protocol_error {
+            self.restore_screen();
+            self.is_pipped = true;
+            self.has_command_errors = true;
+            let mut stderr = io::stderr();
+            let _ = stderr.write_all(error.as_bytes());
+            let _ = stderr.write_all(b"\n");
+        }
+
+        if let Some(record) = &mut self.input_record {
+            record.write(&self.server_write_buf);
+        }
+
         (suspend, self.server_write_buf.as_slice())
     }
+
+    fn receive_command_outputs(&mut self, server_bytes: &[u8]) -> (bool, &[u8]) {
+        use io::Write;
+
+        self.server_read_buf.extend_from_slice(server_bytes);
+        let mut read_slice = &self.server_read_buf[..];
+
+        loop {
+            let previous_slice = read_slice;
+            match ServerEvent::deserialize(&mut read_slice) {
+                Ok(ServerEvent::CommandOutput(output)) => {
+                    self.stdout.write_all(output.as_bytes()).unwrap();
+                    self.stdout.write_all(b"\n").unwrap();
+                }
+                Ok(ServerEvent::CommandError(error)) => {
+                    self.has_command_errors = true;
+                    let mut stderr = io::stderr();
+                    let _ = stderr.write_all(error.as_bytes());
+                    let _ = stderr.write_all(b"\n");
+                }
+                Ok(ServerEvent::Handshake(version)) => {
+                    if version != PROTOCOL_VERSION {
+                        self.has_command_errors = true;
+                        let mut stderr = io::stderr();
+                        let _ = stderr.write_all(INCOMPATIBLE_PROTOCOL_ERROR.as_bytes());
+                        let _ = stderr.write_all(b"\n");
+                    }
+                }
+                Ok(_) => (),
+                Err(DeserializeError::InsufficientData) => {
+                    let read_len = self.server_read_buf.len() - previous_slice.len();
+                    self.server_read_buf.drain(..read_len);
+                    break;
+                }
+                Err(DeserializeError::InvalidData) => {
+                    panic!("client received invalid data from server")
+                }
+            }
+        }
+
+        self.stdout.flush().unwrap();
+        (false, &[])
+    }
+}
+enum InputRecordError {
+    Io(io::Error),
+    InvalidData,
+    IncompatibleVersion,
+}
+impl fmt::Display for InputRecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::InvalidData => f.write_str("invalid record data"),
+            Self::IncompatibleVersion => {
+                f.write_str("record was made with an incompatible protocol version")
+            }
+        }
+    }
+}
+impl From<DeserializeError> for InputRecordError {
+    fn from(_: DeserializeError) -> Self {
+        Self::InvalidData
+    }
+}
+
+// record layout: protocol version followed by entries of
+// (milliseconds since record start, serialized client events)
+struct InputRecord {
+    file: fs::File,
+    start: Instant,
+    buf: Vec<u8>,
 }
+impl InputRecord {
+    pub fn create(path: &str) -> io::Result<Self> {
+        use io::Write;
+
+        let mut file = fs::File::create(path)?;
+        let mut buf = Vec::new();
+        PROTOCOL_VERSION.serialize(&mut buf);
+        file.write_all(&buf)?;
+
+        Ok(Self {
+            file,
+            start: Instant::now(),
+            buf,
+        })
+    }
+
+    pub fn write(&mut self, events: &[u8]) {
+        use io::Write;
+
+        if events.is_empty() {
+            return;
+        }
+
+        // flushing every entry keeps the record useful even if the session crashes
+        self.buf.clear();
+        let timestamp = self.start.elapsed().as_millis() as u32;
+        timestamp.serialize(&mut self.buf);
+        events.serialize(&mut self.buf);
+        let _ = self.file.write_all(&self.buf);
+    }
+
+    pub fn replay(path: &str, events_buf: &mut Vec<u8>) -> Result<(), InputRecordError> {
+        let bytes = fs::read(path).map_err(InputRecordError::Io)?;
+        let mut slice = &bytes[..];
+        if u32::deserialize(&mut slice)? != PROTOCOL_VERSION {
+            return Err(InputRecordError::IncompatibleVersion);
+        }
+
+        while !slice.is_empty() {
+            let _timestamp = u32::deserialize(&mut slice)?;
+            let events: &[u8] = Serialize::deserialize(&mut slice)?;
+
+            let mut events_slice = events;
+            while !events_slice.is_empty() {
+                if let ClientEvent::Handshake(_, _) = ClientEvent::deserialize(&mut events_slice)? {
+                    return Err(InputRecordError::InvalidData);
+                }
+            }
+            events_buf.write(events);
+        }
+
+        Ok(())
+    }
+}
+
 impl Drop for ClientApplication {
     fn drop(&mut self) {
         self.restore_screen();
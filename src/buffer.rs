@@ -7,19 +7,29 @@ use std::{
     path::{Path, PathBuf},
     process::{Command, Stdio},
     str::CharIndices,
+    time::Duration,
 };
 
 use crate::{
-    buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
+    buffer_position::{
+        byte_index_to_char_index, char_display_len, char_index_to_byte_index, floor_char_boundary,
+        BufferPosition, BufferPositionIndex, BufferRange,
+    },
+    change_list::ChangeList,
+    config::{ClientDisplayConfig, Config},
     events::{EditorEvent, EditorEventQueue},
     help,
     history::{Edit, EditKind, History},
+    modeline::Modeline,
     pattern::Pattern,
     platform::{Platform, PlatformRequest, PooledBuf, ProcessHandle, ProcessTag},
     syntax::{HighlightResult, HighlightedBuffer, SyntaxCollection, SyntaxHandle},
     word_database::{WordDatabase, WordIter, WordKind},
 };
 
+const WORD_DATABASE_DEFERRED_LINE_COUNT: usize = 4 * 1024;
+const CLOSED_BUFFERS_CAPACITY: usize = 16;
+
 pub fn find_delimiter_pair_at(text: &str, index: usize, delimiter: char) -> Option<(usize, usize)> {
     let mut is_right_delim = false;
     let mut last_i = 0;
@@ -47,12 +57,65 @@ pub fn find_delimiter_pair_at(text: &str, index: usize, delimiter: char) -> Opti
 
 pub fn parse_path_and_position(text: &str) -> (&str, Option<BufferPosition>) {
     let text = text.trim();
-    match text.rfind(':') {
-        Some(i) => match text[i + 1..].parse() {
-            Ok(position) => (&text[..i], Some(position)),
-            Err(_) => (text, None),
-        },
-        None => (text, None),
+    for (i, _) in text.match_indices(':') {
+        if let Ok(position) = text[i + 1..].parse() {
+            return (&text[..i], Some(position));
+        }
+    }
+    (text, None)
+}
+
+pub fn reindent_text(
+    text: &str,
+    indentation: &str,
+    tab_size: NonZeroU8,
+    indent_with_tabs: bool,
+    output: &mut String,
+) {
+    fn indentation_len(line: &str, tab_size: NonZeroU8) -> (usize, usize) {
+        let mut columns = 0;
+        for (i, c) in line.char_indices() {
+            match c {
+                ' ' | '\t' => columns += char_display_len(c, tab_size),
+                _ => return (i, columns),
+            }
+        }
+        (line.len(), columns)
+    }
+
+    let base_columns = text
+        .split('\n')
+        .filter_map(|l| match indentation_len(l, tab_size) {
+            (len, columns) if len < l.len() => Some(columns),
+            _ => None,
+        })
+        .min()
+        .unwrap_or(0);
+
+    let tab_len = tab_size.get() as usize;
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            output.push('\n');
+        }
+        let (len, columns) = indentation_len(line, tab_size);
+        if len == line.len() {
+            continue;
+        }
+
+        output.push_str(indentation);
+        let extra_columns = columns.saturating_sub(base_columns);
+        let (tab_count, space_count) = if indent_with_tabs {
+            (extra_columns / tab_len, extra_columns % tab_len)
+        } else {
+            (0, extra_columns)
+        };
+        for _ in 0..tab_count {
+            output.push('\t');
+        }
+        for _ in 0..space_count {
+            output.push(' ');
+        }
+        output.push_str(&line[len..]);
     }
 }
 
@@ -79,8 +142,12 @@ pub fn find_path_and_position_at(text: &str, index: usize) -> (&str, Option<Buff
             (path, position)
         }
         Some(i) => {
-            let position = path[i + 1..].parse().ok();
-            (&path[..i], position)
+            for (j, _) in path.match_indices(':').filter(|&(j, _)| j > 1) {
+                if let Ok(position) = text[from + j + 1..].parse() {
+                    return (&path[..j], Some(position));
+                }
+            }
+            (&path[..i], None)
         }
     }
 }
@@ -105,10 +172,7 @@ impl<'a> CharDisplayDistances<'a> {
 }
 impl<'a> CharDisplayDistances<'a> {
     fn calc_next(&mut self, char_index: usize, c: char) -> CharDisplayDistance {
-        self.len += match c {
-            '\t' => self.tab_size.get() as _,
-            _ => 1,
-        };
+        self.len += char_display_len(c, self.tab_size);
         CharDisplayDistance {
             distance: self.len,
             char_index,
@@ -334,6 +398,18 @@ impl BufferContent {
         &self.lines[index]
     }
 
+    pub fn is_large(&self, max_len: usize, max_line_len: usize) -> bool {
+        let mut len = 0;
+        for line in &self.lines {
+            let line_len = line.as_str().len();
+            len += line_len + 1;
+            if (max_line_len > 0 && line_len > max_line_len) || (max_len > 0 && len > max_len) {
+                return true;
+            }
+        }
+        false
+    }
+
     pub fn end(&self) -> BufferPosition {
         let last_line_index = self.lines.len() - 1;
         BufferPosition::line_col(
@@ -401,10 +477,24 @@ impl BufferContent {
     pub fn saturate_position(&self, mut position: BufferPosition) -> BufferPosition {
         position.line_index = position.line_index.min((self.line_count() - 1) as _);
         let line = self.line_at(position.line_index as _).as_str();
-        position.column_byte_index = position.column_byte_index.min(line.len() as _);
+        position.column_byte_index =
+            floor_char_boundary(line, position.column_byte_index as _) as _;
         position
     }
 
+    pub fn char_column(&self, position: BufferPosition) -> usize {
+        let position = self.saturate_position(position);
+        let line = self.line_at(position.line_index as _).as_str();
+        byte_index_to_char_index(line, position.column_byte_index as _)
+    }
+
+    pub fn position_from_char_column(&self, position: BufferPosition) -> BufferPosition {
+        let line_index = position.line_index.min((self.line_count() - 1) as _);
+        let line = self.line_at(line_index as _).as_str();
+        let column_byte_index = char_index_to_byte_index(line, position.column_byte_index as _);
+        BufferPosition::line_col(line_index, column_byte_index as _)
+    }
+
     pub fn append_range_text_to_string(&self, range: BufferRange, text: &mut String) {
         let from = self.saturate_position(range.from);
         let to = self.saturate_position(range.to);
@@ -754,6 +844,55 @@ impl BufferCapabilities {
     }
 }
 
+fn is_word_char(c: char) -> bool {
+    WordKind::from_char(c) == WordKind::Identifier
+}
+
+fn for_each_word_around<F>(content: &BufferContent, range: BufferRange, mut f: F)
+where
+    F: FnMut(&str),
+{
+    let from_line = content.line_at(range.from.line_index as _).as_str();
+    let from = from_line[..range.from.column_byte_index as usize]
+        .trim_end_matches(is_word_char)
+        .len();
+
+    let to_line = content.line_at(range.to.line_index as _).as_str();
+    let to_rest = &to_line[range.to.column_byte_index as usize..];
+    let to = to_line.len() - to_rest.trim_start_matches(is_word_char).len();
+
+    if range.from.line_index == range.to.line_index {
+        WordIter(&from_line[from..to])
+            .of_kind(WordKind::Identifier)
+            .for_each(&mut f);
+        return;
+    }
+
+    WordIter(&from_line[from..])
+        .of_kind(WordKind::Identifier)
+        .for_each(&mut f);
+    let middle_line_count = range.to.line_index - range.from.line_index - 1;
+    for line in content
+        .lines()
+        .skip(range.from.line_index as usize + 1)
+        .take(middle_line_count as _)
+    {
+        WordIter(line.as_str())
+            .of_kind(WordKind::Identifier)
+            .for_each(&mut f);
+    }
+    WordIter(&to_line[..to])
+        .of_kind(WordKind::Identifier)
+        .for_each(&mut f);
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct BufferSettings {
+    pub tab_size: Option<NonZeroU8>,
+    pub indent_with_tabs: Option<bool>,
+    pub soft_wrap: Option<bool>,
+}
+
 pub struct Buffer {
     alive: bool,
     handle: BufferHandle,
@@ -762,9 +901,13 @@ pub struct Buffer {
     syntax_handle: SyntaxHandle,
     highlighted: HighlightedBuffer,
     history: History,
+    pub change_list: ChangeList,
     search_ranges: Vec<BufferRange>,
     needs_save: bool,
+    large_file: bool,
+    words_pending: bool,
     pub capabilities: BufferCapabilities,
+    pub settings: BufferSettings,
 }
 
 impl Buffer {
@@ -777,9 +920,13 @@ impl Buffer {
             syntax_handle: SyntaxHandle::default(),
             highlighted: HighlightedBuffer::new(),
             history: History::new(),
+            change_list: ChangeList::default(),
             search_ranges: Vec::new(),
             needs_save: false,
+            large_file: false,
+            words_pending: false,
             capabilities: BufferCapabilities::default(),
+            settings: BufferSettings::default(),
         }
     }
 
@@ -792,13 +939,31 @@ impl Buffer {
         self.syntax_handle = SyntaxHandle::default();
         self.highlighted.clear();
         self.history.clear();
+        self.change_list.clear();
         self.search_ranges.clear();
         self.needs_save = false;
+        self.large_file = false;
+        self.words_pending = false;
         self.capabilities = BufferCapabilities::default();
+        self.settings = BufferSettings::default();
+    }
+
+    fn uses_word_database(&self) -> bool {
+        self.capabilities.uses_word_database && !self.large_file && !self.words_pending
+    }
+
+    fn add_all_words_to_database(&mut self, word_database: &mut WordDatabase) {
+        if self.uses_word_database() {
+            for line in &self.content.lines {
+                for word in WordIter(line.as_str()).of_kind(WordKind::Identifier) {
+                    word_database.add(word);
+                }
+            }
+        }
     }
 
     fn remove_all_words_from_database(&mut self, word_database: &mut WordDatabase) {
-        if self.capabilities.uses_word_database {
+        if self.uses_word_database() {
             for line in &self.content.lines {
                 for word in WordIter(line.as_str()).of_kind(WordKind::Identifier) {
                     word_database.remove(word);
@@ -815,7 +980,25 @@ impl Buffer {
         &self.highlighted
     }
 
+    pub fn syntax_handle(&self) -> SyntaxHandle {
+        self.syntax_handle
+    }
+
+    pub fn is_large_file(&self) -> bool {
+        self.large_file
+    }
+
+    pub fn index_pending_words(&mut self, word_database: &mut WordDatabase) {
+        if self.words_pending {
+            self.words_pending = false;
+            self.add_all_words_to_database(word_database);
+        }
+    }
+
     pub fn update_highlighting(&mut self, syntaxes: &SyntaxCollection) -> HighlightResult {
+        if self.large_file {
+            return HighlightResult::Complete;
+        }
         self.highlighted
             .highlight_dirty_lines(syntaxes.get(self.syntax_handle), &self.content)
     }
@@ -826,7 +1009,9 @@ impl Buffer {
             return;
         }
 
-        let syntax_handle = syntaxes.find_handle_by_path(path).unwrap_or_default();
+        let syntax_handle = syntaxes
+            .detect_handle(path, &self.content)
+            .unwrap_or_default();
 
         if self.syntax_handle != syntax_handle {
             self.syntax_handle = syntax_handle;
@@ -842,6 +1027,24 @@ impl Buffer {
         &self.content
     }
 
+    pub fn tab_size(&self, config: &Config) -> NonZeroU8 {
+        self.settings.tab_size.unwrap_or(config.tab_size)
+    }
+
+    pub fn indent_with_tabs(&self, config: &Config) -> bool {
+        self.settings
+            .indent_with_tabs
+            .unwrap_or(config.indent_with_tabs)
+    }
+
+    pub fn soft_wrap(&self, config: ClientDisplayConfig) -> bool {
+        config
+            .overrides()
+            .soft_wrap
+            .or(self.settings.soft_wrap)
+            .unwrap_or(config.soft_wrap())
+    }
+
     pub fn needs_save(&self) -> bool {
         self.capabilities.can_save && self.needs_save
     }
@@ -861,16 +1064,19 @@ impl Buffer {
         }
         self.needs_save = true;
 
+        let uses_word_database = self.uses_word_database();
         let range = Self::insert_text_no_history(
             &mut self.content,
             &mut self.highlighted,
-            self.capabilities.uses_word_database,
+            uses_word_database,
             word_database,
             position,
             text,
         );
 
         events.enqueue_buffer_insert(self.handle, range, text);
+        self.change_list.on_insert(range);
+        self.change_list.add(range.to);
 
         if self.capabilities.has_history {
             self.history.add_edit(Edit {
@@ -892,27 +1098,15 @@ impl Buffer {
         text: &str,
     ) -> BufferRange {
         if uses_word_database {
-            for word in WordIter(content.line_at(position.line_index as _).as_str())
-                .of_kind(WordKind::Identifier)
-            {
-                word_database.remove(word);
-            }
+            let range = BufferRange::between(position, position);
+            for_each_word_around(content, range, |w| word_database.remove(w));
         }
 
         let range = content.insert_text(position, text);
         highlighted.on_insert(range);
 
         if uses_word_database {
-            let line_count = range.to.line_index - range.from.line_index + 1;
-            for line in content
-                .lines()
-                .skip(range.from.line_index as _)
-                .take(line_count as _)
-            {
-                for word in WordIter(line.as_str()).of_kind(WordKind::Identifier) {
-                    word_database.add(word);
-                }
-            }
+            for_each_word_around(content, range, |w| word_database.add(w));
         }
 
         range
@@ -937,6 +1131,8 @@ impl Buffer {
             handle: self.handle,
             range,
         });
+        self.change_list.on_delete(range);
+        self.change_list.add(range.from);
 
         let from = range.from;
         let to = range.to;
@@ -983,10 +1179,11 @@ impl Buffer {
             }
         }
 
+        let uses_word_database = self.uses_word_database();
         Self::delete_range_no_history(
             &mut self.content,
             &mut self.highlighted,
-            self.capabilities.uses_word_database,
+            uses_word_database,
             word_database,
             range,
         );
@@ -1000,24 +1197,10 @@ impl Buffer {
         range: BufferRange,
     ) {
         if uses_word_database {
-            let line_count = range.to.line_index - range.from.line_index + 1;
-            for line in content
-                .lines()
-                .skip(range.from.line_index as _)
-                .take(line_count as _)
-            {
-                for word in WordIter(line.as_str()).of_kind(WordKind::Identifier) {
-                    word_database.remove(word);
-                }
-            }
-
+            for_each_word_around(content, range, |w| word_database.remove(w));
             content.delete_range(range);
-
-            for word in WordIter(content.line_at(range.from.line_index as _).as_str())
-                .of_kind(WordKind::Identifier)
-            {
-                word_database.add(word);
-            }
+            let range = BufferRange::between(range.from, range.from);
+            for_each_word_around(content, range, |w| word_database.add(w));
         } else {
             content.delete_range(range);
         }
@@ -1025,6 +1208,10 @@ impl Buffer {
         highlighted.on_delete(range);
     }
 
+    pub fn commit_edits_after_pause(&mut self, pause: Duration) {
+        self.history.commit_edits_after_pause(pause);
+    }
+
     pub fn commit_edits(&mut self) {
         self.history.commit_edits();
     }
@@ -1058,9 +1245,10 @@ impl Buffer {
         self.search_ranges.clear();
         self.needs_save = true;
 
+        let uses_word_database = self.uses_word_database();
         let content = &mut self.content;
         let highlighted = &mut self.highlighted;
-        let uses_word_database = self.capabilities.uses_word_database;
+        let change_list = &mut self.change_list;
 
         let edits = selector(&mut self.history);
         for edit in edits.clone() {
@@ -1075,6 +1263,7 @@ impl Buffer {
                         edit.text,
                     );
                     events.enqueue_buffer_insert(self.handle, edit.range, edit.text);
+                    change_list.on_insert(edit.range);
                 }
                 EditKind::Delete => {
                     Self::delete_range_no_history(
@@ -1088,6 +1277,7 @@ impl Buffer {
                         handle: self.handle,
                         range: edit.range,
                     });
+                    change_list.on_delete(edit.range);
                 }
             }
         }
@@ -1108,9 +1298,11 @@ impl Buffer {
     pub fn read_from_file(
         &mut self,
         word_database: &mut WordDatabase,
+        config: &Config,
         events: &mut EditorEventQueue,
     ) -> Result<(), BufferReadError> {
         self.history.clear();
+        self.change_list.clear();
         self.search_ranges.clear();
         self.needs_save = false;
 
@@ -1129,18 +1321,19 @@ impl Buffer {
             self.content.read(&mut reader)?;
         }
 
+        self.large_file = self
+            .content
+            .is_large(config.large_file_size as _, config.large_file_line_len as _);
+        let modeline = Modeline::find(&self.content).unwrap_or_default();
+        self.settings.tab_size = modeline.tab_size;
+        self.settings.indent_with_tabs = modeline.indent_with_tabs;
         self.highlighted.on_insert(BufferRange::between(
             BufferPosition::zero(),
             BufferPosition::line_col((self.content.line_count() - 1) as _, 0),
         ));
 
-        if self.capabilities.uses_word_database {
-            for line in &self.content.lines {
-                for word in WordIter(line.as_str()).of_kind(WordKind::Identifier) {
-                    word_database.add(word);
-                }
-            }
-        }
+        self.words_pending = self.content.line_count() > WORD_DATABASE_DEFERRED_LINE_COUNT;
+        self.add_all_words_to_database(word_database);
 
         Ok(())
     }
@@ -1165,7 +1358,9 @@ impl Buffer {
         }
 
         let file = File::create(&self.path)?;
-        self.content.write(&mut io::BufWriter::new(file))?;
+        let mut writer = io::BufWriter::new(file);
+        self.content.write(&mut writer)?;
+        io::Write::flush(&mut writer)?;
 
         self.capabilities.can_save = true;
         self.needs_save = false;
@@ -1189,10 +1384,16 @@ pub struct InsertProcess {
     pub output: Vec<u8>,
 }
 
+pub struct ClosedBuffer {
+    pub path: PathBuf,
+    pub position: BufferPosition,
+}
+
 #[derive(Default)]
 pub struct BufferCollection {
     buffers: Vec<Buffer>,
     insert_processes: Vec<InsertProcess>,
+    closed_buffers: Vec<ClosedBuffer>,
 }
 
 impl BufferCollection {
@@ -1249,10 +1450,48 @@ impl BufferCollection {
         self.buffers.iter().filter(|b| b.alive)
     }
 
+    pub fn rename_paths(
+        &mut self,
+        buffers_root: &Path,
+        old_path: &Path,
+        new_path: &Path,
+        syntaxes: &SyntaxCollection,
+    ) -> Vec<(BufferHandle, PathBuf)> {
+        let mut renamed = Vec::new();
+        for buffer in self.iter_mut() {
+            if buffer.path.as_os_str().is_empty() {
+                continue;
+            }
+
+            let path = buffers_root.join(&buffer.path);
+            let rest = match path.strip_prefix(old_path) {
+                Ok(rest) => rest,
+                Err(_) => continue,
+            };
+            let path = if rest.as_os_str().is_empty() {
+                new_path.to_path_buf()
+            } else {
+                new_path.join(rest)
+            };
+            let path = path.strip_prefix(buffers_root).unwrap_or(&path);
+
+            let old_buffer_path = std::mem::replace(&mut buffer.path, path.into());
+            buffer.refresh_syntax(syntaxes);
+            renamed.push((buffer.handle, old_buffer_path));
+        }
+        renamed
+    }
+
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Buffer> {
         self.buffers.iter_mut().filter(|b| b.alive)
     }
 
+    pub fn index_pending_words(&mut self, word_database: &mut WordDatabase) {
+        for buffer in self.iter_mut() {
+            buffer.index_pending_words(word_database);
+        }
+    }
+
     pub fn defer_remove(&self, handle: BufferHandle, events: &mut EditorEventQueue) {
         let buffer = &self.buffers[handle.0 as usize];
         if buffer.alive {
@@ -1263,12 +1502,31 @@ impl BufferCollection {
     pub fn remove_from_editor_event_handler(
         &mut self,
         handle: BufferHandle,
+        position: BufferPosition,
         word_database: &mut WordDatabase,
     ) {
         let buffer = &mut self.buffers[handle.0 as usize];
-        if buffer.alive {
-            buffer.dispose(word_database);
+        if !buffer.alive {
+            return;
+        }
+
+        if buffer.capabilities.can_save && !buffer.path.as_os_str().is_empty() {
+            let path = &buffer.path;
+            self.closed_buffers.retain(|b| b.path != *path);
+            if self.closed_buffers.len() == CLOSED_BUFFERS_CAPACITY {
+                self.closed_buffers.remove(0);
+            }
+            self.closed_buffers.push(ClosedBuffer {
+                path: path.clone(),
+                position,
+            });
         }
+
+        buffer.dispose(word_database);
+    }
+
+    pub fn pop_closed(&mut self) -> Option<ClosedBuffer> {
+        self.closed_buffers.pop()
     }
 
     pub fn spawn_insert_process(
@@ -1388,6 +1646,9 @@ impl BufferCollection {
             if let Ok(text) = std::str::from_utf8(&process.output) {
                 buffer.insert_text(word_database, process.position, text, events);
             }
+            events.enqueue(EditorEvent::BufferProcessExit {
+                handle: process.buffer_handle,
+            });
         }
     }
 }
@@ -1408,6 +1669,22 @@ mod tests {
         assert_eq!(None, find_delimiter_pair_at(text, 11, '|'));
     }
 
+    #[test]
+    fn test_reindent_text() {
+        fn reindent(text: &str, indentation: &str, indent_with_tabs: bool) -> String {
+            let mut output = String::new();
+            let tab_size = NonZeroU8::new(4).unwrap();
+            reindent_text(text, indentation, tab_size, indent_with_tabs, &mut output);
+            output
+        }
+
+        let text = "    if a {\n        b\n\n    }";
+        assert_eq!("if a {\n    b\n\n}", reindent(text, "", false));
+        assert_eq!("\tif a {\n\t\tb\n\n\t}", reindent(text, "\t", true));
+        assert_eq!("  if a {\n      b\n\n  }", reindent(text, "  ", false));
+        assert_eq!("  a\n  \t  b", reindent("\ta\n\t\t  b", "  ", true));
+    }
+
     #[test]
     fn test_find_path_at() {
         let text = "/path/file:45";
@@ -1538,6 +1815,49 @@ mod tests {
             (path, Some(BufferPosition::line_col(3, 4))),
             find_path_and_position_at(text, 27)
         );
+
+        let text = "src/main.rs:12:5: error";
+        let path = "src/main.rs";
+        assert_eq!(
+            (path, Some(BufferPosition::line_col(11, 4))),
+            find_path_and_position_at(text, 0)
+        );
+        assert_eq!(
+            (path, Some(BufferPosition::line_col(11, 4))),
+            find_path_and_position_at(text, 13)
+        );
+        assert_eq!(
+            (path, Some(BufferPosition::line_col(11, 4))),
+            find_path_and_position_at(text, 15)
+        );
+    }
+
+    #[test]
+    fn test_parse_path_and_position() {
+        assert_eq!(
+            ("/path/file", None),
+            parse_path_and_position(" /path/file ")
+        );
+        assert_eq!(
+            ("/path/file", Some(BufferPosition::line_col(44, 0))),
+            parse_path_and_position("/path/file:45")
+        );
+        assert_eq!(
+            ("/path/file", Some(BufferPosition::line_col(44, 2))),
+            parse_path_and_position("/path/file:45,3")
+        );
+        assert_eq!(
+            ("/path/file", Some(BufferPosition::line_col(44, 2))),
+            parse_path_and_position("/path/file:45:3")
+        );
+        assert_eq!(
+            ("c:/path/file", Some(BufferPosition::line_col(44, 2))),
+            parse_path_and_position("c:/path/file:45:3")
+        );
+        assert_eq!(
+            ("c:/path/file", None),
+            parse_path_and_position("c:/path/file")
+        );
     }
 
     #[test]
@@ -1781,6 +2101,83 @@ mod tests {
         assert_eq!("me\ncontent", buffer.content.to_string());
     }
 
+    #[test]
+    fn buffer_word_database_incremental_update() {
+        fn words(word_database: &WordDatabase) -> Vec<(&str, usize)> {
+            let mut words: Vec<_> = word_database
+                .word_indices()
+                .map(|(_, word, count)| (word, count))
+                .collect();
+            words.sort_unstable();
+            words
+        }
+
+        fn assert_words(buffer: &Buffer, word_database: &WordDatabase) {
+            let mut expected = WordDatabase::new();
+            for line in buffer.content.lines() {
+                for word in WordIter(line.as_str()).of_kind(WordKind::Identifier) {
+                    expected.add(word);
+                }
+            }
+            assert_eq!(words(&expected), words(word_database));
+        }
+
+        fn range(from: (u32, u32), to: (u32, u32)) -> BufferRange {
+            BufferRange::between(
+                BufferPosition::line_col(from.0 as _, from.1 as _),
+                BufferPosition::line_col(to.0 as _, to.1 as _),
+            )
+        }
+
+        let mut word_database = WordDatabase::new();
+        let mut events = EditorEventQueue::default();
+
+        let mut buffer = Buffer::new(BufferHandle(0));
+        buffer.capabilities = BufferCapabilities::text();
+        let text = "first second\nthird line";
+        buffer.insert_text(
+            &mut word_database,
+            BufferPosition::zero(),
+            text,
+            &mut events,
+        );
+        assert_words(&buffer, &word_database);
+
+        let position = BufferPosition::line_col(0, 5);
+        buffer.insert_text(&mut word_database, position, "x y", &mut events);
+        assert_eq!("firstx y second\nthird line", buffer.content.to_string());
+        assert_words(&buffer, &word_database);
+
+        let position = BufferPosition::line_col(1, 5);
+        buffer.insert_text(&mut word_database, position, "\nnew words\n", &mut events);
+        assert_words(&buffer, &word_database);
+
+        buffer.delete_range(&mut word_database, range((0, 3), (3, 2)), &mut events);
+        assert_eq!("firine", buffer.content.to_string());
+        assert_words(&buffer, &word_database);
+
+        buffer.delete_range(&mut word_database, range((0, 1), (0, 3)), &mut events);
+        assert_words(&buffer, &word_database);
+
+        buffer.commit_edits();
+        buffer.undo(&mut word_database, &mut events).for_each(drop);
+        assert_eq!("", buffer.content.to_string());
+        assert_words(&buffer, &word_database);
+        buffer.redo(&mut word_database, &mut events).for_each(drop);
+        assert_eq!("fine", buffer.content.to_string());
+        assert_words(&buffer, &word_database);
+    }
+
+    #[test]
+    fn buffer_content_is_large() {
+        let buffer = buffer_from_str("abc\ndefgh\ni");
+        assert!(!buffer.is_large(0, 0));
+        assert!(!buffer.is_large(12, 5));
+        assert!(buffer.is_large(11, 0));
+        assert!(buffer.is_large(0, 4));
+        assert!(!buffer.is_large(0, 5));
+    }
+
     #[test]
     fn buffer_content_range_text() {
         let buffer = buffer_from_str("abc\ndef\nghi");
@@ -1906,4 +2303,72 @@ mod tests {
             buffer.find_balanced_chars_at(BufferPosition::line_col(4, 2), '(', ')')
         );
     }
+
+    #[test]
+    fn buffer_collection_rename_paths() {
+        let syntaxes = SyntaxCollection::new();
+        let root = Path::new("/project");
+        let mut buffers = BufferCollection::default();
+        let mut add = |path: &str| {
+            let buffer = buffers.add_new();
+            buffer.path.push(path);
+            buffer.handle()
+        };
+        let main = add("src/main.rs");
+        let module = add("src/module/mod.rs");
+        let other = add("/other/src/lib.rs");
+        let scratch = add("");
+
+        let renamed =
+            buffers.rename_paths(root, &root.join("src"), &root.join("source"), &syntaxes);
+        assert_eq!(
+            vec![
+                (main, PathBuf::from("src/main.rs")),
+                (module, PathBuf::from("src/module/mod.rs")),
+            ],
+            renamed
+        );
+        assert_eq!(Path::new("source/main.rs"), buffers.get(main).path);
+        assert_eq!(Path::new("source/module/mod.rs"), buffers.get(module).path);
+        assert_eq!(Path::new("/other/src/lib.rs"), buffers.get(other).path);
+        assert!(buffers.get(scratch).path.as_os_str().is_empty());
+
+        let renamed = buffers.rename_paths(
+            root,
+            &root.join("source/main.rs"),
+            Path::new("/other/main.rs"),
+            &syntaxes,
+        );
+        assert_eq!(vec![(main, PathBuf::from("source/main.rs"))], renamed);
+        assert_eq!(Path::new("/other/main.rs"), buffers.get(main).path);
+    }
+
+    #[test]
+    fn buffer_collection_closed_buffers() {
+        let mut word_database = WordDatabase::new();
+        let mut buffers = BufferCollection::default();
+        let mut add = |path: &str, capabilities: BufferCapabilities| {
+            let buffer = buffers.add_new();
+            buffer.path.push(path);
+            buffer.capabilities = capabilities;
+            buffer.handle()
+        };
+        let a = add("a.txt", BufferCapabilities::text());
+        let b = add("b.txt", BufferCapabilities::text());
+        let log = add("editor.log", BufferCapabilities::log());
+        let a_again = add("a.txt", BufferCapabilities::text());
+
+        let position = BufferPosition::line_col(2, 3);
+        buffers.remove_from_editor_event_handler(a, position, &mut word_database);
+        buffers.remove_from_editor_event_handler(b, BufferPosition::zero(), &mut word_database);
+        buffers.remove_from_editor_event_handler(log, BufferPosition::zero(), &mut word_database);
+        buffers.remove_from_editor_event_handler(a_again, position, &mut word_database);
+
+        let closed = buffers.pop_closed().unwrap();
+        assert_eq!(Path::new("a.txt"), closed.path);
+        assert_eq!(position, closed.position);
+        let closed = buffers.pop_closed().unwrap();
+        assert_eq!(Path::new("b.txt"), closed.path);
+        assert!(buffers.pop_closed().is_none());
+    }
 }
@@ -1,11 +1,149 @@
 use std::{
     cmp::{Ord, Ordering, PartialOrd},
     fmt,
+    num::NonZeroU8,
     str::FromStr,
 };
 
 pub type BufferPositionIndex = u32;
 
+pub fn floor_char_boundary(text: &str, byte_index: usize) -> usize {
+    if byte_index >= text.len() {
+        return text.len();
+    }
+    let mut index = byte_index;
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+pub fn byte_index_to_char_index(text: &str, byte_index: usize) -> usize {
+    let byte_index = floor_char_boundary(text, byte_index);
+    text[..byte_index].chars().count()
+}
+
+pub fn char_index_to_byte_index(text: &str, char_index: usize) -> usize {
+    match text.char_indices().nth(char_index) {
+        Some((i, _)) => i,
+        None => text.len(),
+    }
+}
+
+pub fn byte_index_to_utf16_index(text: &str, byte_index: usize) -> usize {
+    let byte_index = floor_char_boundary(text, byte_index);
+    text[..byte_index].chars().map(char::len_utf16).sum()
+}
+
+pub fn utf16_index_to_byte_index(text: &str, utf16_index: usize) -> usize {
+    let mut len = 0;
+    for (i, c) in text.char_indices() {
+        if len >= utf16_index {
+            return i;
+        }
+        len += c.len_utf16();
+    }
+    text.len()
+}
+
+pub fn char_display_len(c: char, tab_size: NonZeroU8) -> usize {
+    match c {
+        '\t' => tab_size.get() as _,
+        _ => 1,
+    }
+}
+
+pub fn byte_index_to_display_column(text: &str, byte_index: usize, tab_size: NonZeroU8) -> usize {
+    let byte_index = floor_char_boundary(text, byte_index);
+    text[..byte_index]
+        .chars()
+        .map(|c| char_display_len(c, tab_size))
+        .sum()
+}
+
+pub fn display_column_to_byte_index(text: &str, column: usize, tab_size: NonZeroU8) -> usize {
+    let mut len = 0;
+    for (i, c) in text.char_indices() {
+        len += char_display_len(c, tab_size);
+        if len > column {
+            return i;
+        }
+    }
+    text.len()
+}
+
+#[derive(Clone, Copy)]
+pub struct SoftWrapCell {
+    pub byte_index: usize,
+    pub row: usize,
+    pub column: usize,
+}
+
+// visual cells of `text` (followed by its end of line cell) when wrapped at `width`
+pub fn soft_wrap_cells(
+    text: &str,
+    width: usize,
+    tab_size: NonZeroU8,
+) -> impl '_ + Iterator<Item = SoftWrapCell> {
+    let mut row = 0;
+    let mut x = 0;
+    text.char_indices()
+        .chain(std::iter::once((text.len(), '\n')))
+        .map(move |(byte_index, c)| {
+            let len = char_display_len(c, tab_size);
+            if x > 0 && x + len > width {
+                row += 1;
+                x = 0;
+            }
+            let cell = SoftWrapCell {
+                byte_index,
+                row,
+                column: x,
+            };
+            x += len;
+            cell
+        })
+}
+
+pub fn soft_wrap_row_count(text: &str, width: usize, tab_size: NonZeroU8) -> usize {
+    match soft_wrap_cells(text, width, tab_size).last() {
+        Some(cell) => cell.row + 1,
+        None => 1,
+    }
+}
+
+pub fn byte_index_to_soft_wrap_cell(
+    text: &str,
+    byte_index: usize,
+    width: usize,
+    tab_size: NonZeroU8,
+) -> SoftWrapCell {
+    let mut cells = soft_wrap_cells(text, width, tab_size);
+    let mut last = cells.next().unwrap();
+    for cell in cells {
+        if cell.byte_index > byte_index {
+            break;
+        }
+        last = cell;
+    }
+    last
+}
+
+pub fn soft_wrap_cell_to_byte_index(
+    text: &str,
+    row: usize,
+    column: usize,
+    width: usize,
+    tab_size: NonZeroU8,
+) -> usize {
+    soft_wrap_cells(text, width, tab_size)
+        .skip_while(|c| c.row < row)
+        .take_while(|c| c.row == row && c.column <= column)
+        .last()
+        .map(|c| c.byte_index)
+        .unwrap_or(text.len())
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct BufferPosition {
     pub line_index: BufferPositionIndex,
@@ -128,7 +266,7 @@ impl FromStr for BufferPosition {
         };
 
         let mut chars = s.chars();
-        if !matches!(chars.next(), Some(',')) {
+        if !matches!(chars.next(), Some(',' | ':')) {
             return Ok(BufferPosition::line_col(line, 0));
         }
         let s = chars.as_str();
@@ -188,6 +326,40 @@ mod tests {
         BufferPosition::line_col(line_index, column_byte_index)
     }
 
+    #[test]
+    fn column_conversions() {
+        let tab_size = NonZeroU8::new(4).unwrap();
+        let text = "a\u{e9}\t\u{1f600}b";
+
+        assert_eq!(0, floor_char_boundary(text, 0));
+        assert_eq!(1, floor_char_boundary(text, 2));
+        assert_eq!(4, floor_char_boundary(text, 6));
+        assert_eq!(text.len(), floor_char_boundary(text, 99));
+
+        assert_eq!(0, byte_index_to_char_index(text, 0));
+        assert_eq!(2, byte_index_to_char_index(text, 3));
+        assert_eq!(4, byte_index_to_char_index(text, 8));
+        assert_eq!(5, byte_index_to_char_index(text, 99));
+        assert_eq!(3, char_index_to_byte_index(text, 2));
+        assert_eq!(8, char_index_to_byte_index(text, 4));
+        assert_eq!(text.len(), char_index_to_byte_index(text, 9));
+
+        assert_eq!(3, byte_index_to_utf16_index(text, 4));
+        assert_eq!(5, byte_index_to_utf16_index(text, 8));
+        assert_eq!(4, utf16_index_to_byte_index(text, 3));
+        assert_eq!(8, utf16_index_to_byte_index(text, 5));
+        assert_eq!(8, utf16_index_to_byte_index(text, 4));
+        assert_eq!(text.len(), utf16_index_to_byte_index(text, 99));
+
+        assert_eq!(2, byte_index_to_display_column(text, 3, tab_size));
+        assert_eq!(6, byte_index_to_display_column(text, 4, tab_size));
+        assert_eq!(7, byte_index_to_display_column(text, 8, tab_size));
+        assert_eq!(3, display_column_to_byte_index(text, 2, tab_size));
+        assert_eq!(3, display_column_to_byte_index(text, 5, tab_size));
+        assert_eq!(4, display_column_to_byte_index(text, 6, tab_size));
+        assert_eq!(text.len(), display_column_to_byte_index(text, 99, tab_size));
+    }
+
     #[test]
     fn buffer_position_comparison() {
         assert!(pos(0, 0) < pos(0, 9));
@@ -249,6 +421,37 @@ mod tests {
         assert_eq!(pos(4, 6), pos66.delete(range33_51));
     }
 
+    #[test]
+    fn soft_wrap_conversions() {
+        let tab_size = NonZeroU8::new(4).unwrap();
+        let text = "abcde\tfg";
+
+        assert_eq!(1, soft_wrap_row_count("", 4, tab_size));
+        assert_eq!(1, soft_wrap_row_count("abc", 4, tab_size));
+        assert_eq!(2, soft_wrap_row_count("abcd", 4, tab_size));
+        assert_eq!(4, soft_wrap_row_count(text, 4, tab_size));
+
+        let cell = |byte_index| {
+            let cell = byte_index_to_soft_wrap_cell(text, byte_index, 4, tab_size);
+            (cell.row, cell.column)
+        };
+        assert_eq!((0, 0), cell(0));
+        assert_eq!((0, 3), cell(3));
+        assert_eq!((1, 0), cell(4));
+        assert_eq!((2, 0), cell(5));
+        assert_eq!((3, 0), cell(6));
+        assert_eq!((3, 2), cell(8));
+        assert_eq!((3, 2), cell(99));
+
+        assert_eq!(0, soft_wrap_cell_to_byte_index(text, 0, 0, 4, tab_size));
+        assert_eq!(3, soft_wrap_cell_to_byte_index(text, 0, 9, 4, tab_size));
+        assert_eq!(4, soft_wrap_cell_to_byte_index(text, 1, 2, 4, tab_size));
+        assert_eq!(5, soft_wrap_cell_to_byte_index(text, 2, 1, 4, tab_size));
+        assert_eq!(7, soft_wrap_cell_to_byte_index(text, 3, 1, 4, tab_size));
+        assert_eq!(8, soft_wrap_cell_to_byte_index(text, 3, 9, 4, tab_size));
+        assert_eq!(8, soft_wrap_cell_to_byte_index(text, 5, 0, 4, tab_size));
+    }
+
     #[test]
     fn buffer_position_parsing() {
         assert_eq!(Err(()), "".parse::<BufferPosition>());
@@ -271,5 +474,8 @@ mod tests {
         assert_eq!(Ok(pos(3, 0)), "4,x".parse());
         assert_eq!(Ok(pos(3, 8)), "4,9xx".parse());
         assert_eq!(Ok(pos(3, 8)), "4,9,xx".parse());
+
+        assert_eq!(Ok(pos(3, 8)), "4:9".parse());
+        assert_eq!(Ok(pos(3, 0)), "4: error".parse());
     }
 }
@@ -1,8 +1,11 @@
 use std::num::NonZeroU8;
 
 use crate::{
-    buffer::{Buffer, BufferCollection, BufferHandle, CharDisplayDistances},
-    buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
+    buffer::{Buffer, BufferCollection, BufferHandle},
+    buffer_position::{
+        byte_index_to_soft_wrap_cell, display_column_to_byte_index, soft_wrap_cell_to_byte_index,
+        soft_wrap_row_count, BufferPosition, BufferPositionIndex, BufferRange,
+    },
     client::ClientHandle,
     cursor::{Cursor, CursorCollection},
     events::EditorEventQueue,
@@ -15,6 +18,8 @@ pub enum CursorMovement {
     ColumnsBackward(usize),
     LinesForward(usize),
     LinesBackward(usize),
+    RowsForward { count: usize, width: usize },
+    RowsBackward { count: usize, width: usize },
     WordsForward(usize),
     WordsBackward(usize),
     Home,
@@ -66,7 +71,9 @@ impl BufferView {
             Err(n)
         }
 
-        let buffer = buffers.get(self.buffer_handle).content();
+        let buffer = buffers.get(self.buffer_handle);
+        let tab_size = buffer.settings.tab_size.unwrap_or(tab_size);
+        let buffer = buffer.content();
 
         let mut cursors = self.cursors.mut_guard();
         match movement {
@@ -178,11 +185,8 @@ impl BufferView {
                         as _;
                     if let Some(distance) = saved_display_distance {
                         let line = buffer.line_at(c.position.line_index as _).as_str();
-                        c.position.column_byte_index = CharDisplayDistances::new(line, tab_size)
-                            .find(|d| d.distance > distance as _)
-                            .map(|d| d.char_index)
-                            .unwrap_or(line.len())
-                            as _;
+                        c.position.column_byte_index =
+                            display_column_to_byte_index(line, distance as _, tab_size) as _;
                     }
                     c.position = buffer.saturate_position(c.position);
                 }
@@ -195,12 +199,86 @@ impl BufferView {
                     c.position.line_index = c.position.line_index.saturating_sub(n as _);
                     if let Some(distance) = saved_display_distance {
                         let line = buffer.line_at(c.position.line_index as _).as_str();
-                        c.position.column_byte_index = CharDisplayDistances::new(line, tab_size)
-                            .find(|d| d.distance > distance as _)
-                            .map(|d| d.char_index)
-                            .unwrap_or(line.len())
-                            as _;
+                        c.position.column_byte_index =
+                            display_column_to_byte_index(line, distance as _, tab_size) as _;
+                    }
+                    c.position = buffer.saturate_position(c.position);
+                }
+            }
+            CursorMovement::RowsForward { count, width } => {
+                let width = width.max(1);
+                let last_line_index = buffer.line_count() - 1;
+                cursors.save_display_distances(buffer, tab_size);
+                for i in 0..cursors[..].len() {
+                    let saved_display_distance = cursors.get_saved_display_distance(i);
+                    let c = &mut cursors[i];
+                    let mut line_index = c.position.line_index as usize;
+                    let mut line = buffer.line_at(line_index).as_str();
+                    let cell = byte_index_to_soft_wrap_cell(
+                        line,
+                        c.position.column_byte_index as _,
+                        width,
+                        tab_size,
+                    );
+                    let column = match saved_display_distance {
+                        Some(distance) => distance as usize % width,
+                        None => cell.column,
+                    };
+
+                    let mut row = cell.row;
+                    for _ in 0..count {
+                        if row + 1 < soft_wrap_row_count(line, width, tab_size) {
+                            row += 1;
+                        } else if line_index < last_line_index {
+                            line_index += 1;
+                            line = buffer.line_at(line_index).as_str();
+                            row = 0;
+                        } else {
+                            break;
+                        }
+                    }
+
+                    c.position.line_index = line_index as _;
+                    c.position.column_byte_index =
+                        soft_wrap_cell_to_byte_index(line, row, column, width, tab_size) as _;
+                    c.position = buffer.saturate_position(c.position);
+                }
+            }
+            CursorMovement::RowsBackward { count, width } => {
+                let width = width.max(1);
+                cursors.save_display_distances(buffer, tab_size);
+                for i in 0..cursors[..].len() {
+                    let saved_display_distance = cursors.get_saved_display_distance(i);
+                    let c = &mut cursors[i];
+                    let mut line_index = c.position.line_index as usize;
+                    let mut line = buffer.line_at(line_index).as_str();
+                    let cell = byte_index_to_soft_wrap_cell(
+                        line,
+                        c.position.column_byte_index as _,
+                        width,
+                        tab_size,
+                    );
+                    let column = match saved_display_distance {
+                        Some(distance) => distance as usize % width,
+                        None => cell.column,
+                    };
+
+                    let mut row = cell.row;
+                    for _ in 0..count {
+                        if row > 0 {
+                            row -= 1;
+                        } else if line_index > 0 {
+                            line_index -= 1;
+                            line = buffer.line_at(line_index).as_str();
+                            row = soft_wrap_row_count(line, width, tab_size) - 1;
+                        } else {
+                            break;
+                        }
                     }
+
+                    c.position.line_index = line_index as _;
+                    c.position.column_byte_index =
+                        soft_wrap_cell_to_byte_index(line, row, column, width, tab_size) as _;
                     c.position = buffer.saturate_position(c.position);
                 }
             }
@@ -409,13 +487,34 @@ impl BufferView {
         word_database: &mut WordDatabase,
         completion: &str,
         positions: &[BufferPosition],
+        main_range: Option<BufferRange>,
         events: &mut EditorEventQueue,
     ) {
+        let main_position = self.cursors.main_cursor().position;
         let buffer = buffers.get_mut(self.buffer_handle);
         for (cursor, &position) in self.cursors[..].iter().zip(positions.iter()).rev() {
-            let range = BufferRange::between(position, cursor.position);
+            let range = match main_range {
+                Some(main_range) => {
+                    let before_len =
+                        main_position.column_byte_index - main_range.from.column_byte_index;
+                    let after_len =
+                        main_range.to.column_byte_index - main_position.column_byte_index;
+                    let line_len = buffer
+                        .content()
+                        .line_at(cursor.position.line_index as _)
+                        .as_str()
+                        .len() as BufferPositionIndex;
+
+                    let mut from = cursor.position;
+                    from.column_byte_index = from.column_byte_index.saturating_sub(before_len);
+                    let mut to = cursor.position;
+                    to.column_byte_index = (to.column_byte_index + after_len).min(line_len);
+                    BufferRange::between(from, to)
+                }
+                None => BufferRange::between(position, cursor.position),
+            };
             buffer.delete_range(word_database, range, events);
-            buffer.insert_text(word_database, position, completion, events);
+            buffer.insert_text(word_database, range.from, completion, events);
         }
     }
 
@@ -529,6 +628,10 @@ impl BufferViewCollection {
         }
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &BufferView> {
+        self.buffer_views.iter().filter(|v| v.alive)
+    }
+
     pub fn get(&self, handle: BufferViewHandle) -> &BufferView {
         &self.buffer_views[handle.0 as usize]
     }
@@ -712,10 +815,74 @@ mod tests {
         assert_movement(&mut ctx, 2..2, 0..0, CursorMovement::WordsBackward(6));
         assert_movement(&mut ctx, 2..2, 0..0, CursorMovement::WordsBackward(999));
 
+        let rows_forward = |count| CursorMovement::RowsForward { count, width: 3 };
+        let rows_backward = |count| CursorMovement::RowsBackward { count, width: 3 };
+        assert_movement(&mut ctx, 2..1, 2..4, rows_forward(1));
+        assert_movement(&mut ctx, 2..4, 3..1, rows_forward(1));
+        assert_movement(&mut ctx, 0..1, 1..3, rows_forward(2));
+        assert_movement(&mut ctx, 4..1, 4..1, rows_forward(999));
+        assert_movement(&mut ctx, 3..0, 2..3, rows_backward(1));
+        assert_movement(&mut ctx, 2..3, 1..3, rows_backward(2));
+        assert_movement(&mut ctx, 0..0, 0..0, rows_backward(1));
+
         let mut ctx = TestContext::with_buffer("123\n  abc def\nghi");
         assert_movement(&mut ctx, 1..0, 1..2, CursorMovement::WordsForward(1));
         assert_movement(&mut ctx, 1..9, 2..0, CursorMovement::WordsForward(1));
         assert_movement(&mut ctx, 1..2, 1..0, CursorMovement::WordsBackward(1));
         assert_movement(&mut ctx, 2..0, 1..9, CursorMovement::WordsBackward(1));
     }
+
+    #[test]
+    fn buffer_view_apply_completion() {
+        fn apply(main_range: Option<BufferRange>) -> String {
+            let mut ctx = TestContext::with_buffer("foo(ba)xy\nfoo(ba)xy");
+            let mut word_database = WordDatabase::new();
+            let mut events = EditorEventQueue::default();
+
+            let buffer_view = ctx.buffer_views.get_mut(ctx.buffer_view_handle);
+            let mut cursors = buffer_view.cursors.mut_guard();
+            cursors.clear();
+            for line_index in 0..2 {
+                let position = BufferPosition::line_col(line_index, 6);
+                cursors.add(Cursor {
+                    anchor: position,
+                    position,
+                });
+            }
+            drop(cursors);
+
+            let positions = [
+                BufferPosition::line_col(0, 4),
+                BufferPosition::line_col(1, 4),
+            ];
+            ctx.buffer_views
+                .get(ctx.buffer_view_handle)
+                .apply_completion(
+                    &mut ctx.buffers,
+                    &mut word_database,
+                    "[bar]",
+                    &positions,
+                    main_range,
+                    &mut events,
+                );
+
+            let mut text = String::new();
+            let content = ctx
+                .buffers
+                .get(ctx.buffer_views.get(ctx.buffer_view_handle).buffer_handle)
+                .content();
+            content.append_range_text_to_string(
+                BufferRange::between(BufferPosition::zero(), content.end()),
+                &mut text,
+            );
+            text
+        }
+
+        assert_eq!("foo([bar])xy\nfoo([bar])xy", apply(None));
+        let main_range = BufferRange::between(
+            BufferPosition::line_col(1, 3),
+            BufferPosition::line_col(1, 7),
+        );
+        assert_eq!("foo[bar]xy\nfoo[bar]xy", apply(Some(main_range)));
+    }
 }
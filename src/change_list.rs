@@ -0,0 +1,117 @@
+use crate::buffer_position::{BufferPosition, BufferRange};
+
+const MAX_CHANGE_COUNT: usize = 100;
+
+#[derive(Default)]
+pub struct ChangeList {
+    positions: Vec<BufferPosition>,
+    current_index: usize,
+    last_insert_position: Option<BufferPosition>,
+}
+
+impl ChangeList {
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.current_index = 0;
+        self.last_insert_position = None;
+    }
+
+    pub fn add(&mut self, position: BufferPosition) {
+        match self.positions.last_mut() {
+            Some(last) if last.line_index == position.line_index => *last = position,
+            _ => {
+                if self.positions.len() == MAX_CHANGE_COUNT {
+                    self.positions.remove(0);
+                }
+                self.positions.push(position);
+            }
+        }
+        self.current_index = self.positions.len();
+    }
+
+    pub fn on_insert(&mut self, range: BufferRange) {
+        for position in &mut self.positions {
+            *position = position.insert(range);
+        }
+        if let Some(position) = &mut self.last_insert_position {
+            *position = position.insert(range);
+        }
+    }
+
+    pub fn on_delete(&mut self, range: BufferRange) {
+        for position in &mut self.positions {
+            *position = position.delete(range);
+        }
+        if let Some(position) = &mut self.last_insert_position {
+            *position = position.delete(range);
+        }
+    }
+
+    pub fn older(&mut self) -> Option<BufferPosition> {
+        self.current_index = self.current_index.checked_sub(1)?;
+        Some(self.positions[self.current_index])
+    }
+
+    pub fn newer(&mut self) -> Option<BufferPosition> {
+        if self.current_index + 1 >= self.positions.len() {
+            return None;
+        }
+        self.current_index += 1;
+        Some(self.positions[self.current_index])
+    }
+
+    pub fn last_insert_position(&self) -> Option<BufferPosition> {
+        self.last_insert_position
+    }
+
+    pub fn set_last_insert_position(&mut self, position: BufferPosition) {
+        self.last_insert_position = Some(position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(line_index: u32, column_byte_index: u32) -> BufferPosition {
+        BufferPosition::line_col(line_index as _, column_byte_index as _)
+    }
+
+    #[test]
+    fn navigate_changes() {
+        let mut changes = ChangeList::default();
+        assert_eq!(None, changes.older());
+        assert_eq!(None, changes.newer());
+
+        changes.add(pos(1, 0));
+        changes.add(pos(1, 4));
+        changes.add(pos(5, 2));
+        changes.add(pos(3, 1));
+
+        assert_eq!(Some(pos(3, 1)), changes.older());
+        assert_eq!(Some(pos(5, 2)), changes.older());
+        assert_eq!(Some(pos(1, 4)), changes.older());
+        assert_eq!(None, changes.older());
+        assert_eq!(Some(pos(5, 2)), changes.newer());
+        assert_eq!(Some(pos(3, 1)), changes.newer());
+        assert_eq!(None, changes.newer());
+
+        changes.add(pos(8, 0));
+        assert_eq!(Some(pos(8, 0)), changes.older());
+    }
+
+    #[test]
+    fn positions_follow_edits() {
+        let mut changes = ChangeList::default();
+        changes.add(pos(2, 3));
+        changes.add(pos(4, 0));
+        changes.set_last_insert_position(pos(4, 5));
+
+        changes.on_insert(BufferRange::between(pos(0, 0), pos(2, 0)));
+        changes.on_delete(BufferRange::between(pos(6, 1), pos(6, 3)));
+
+        assert_eq!(Some(pos(6, 3)), changes.last_insert_position());
+        assert_eq!(Some(pos(6, 0)), changes.older());
+        assert_eq!(Some(pos(4, 3)), changes.older());
+    }
+}
@@ -2,12 +2,17 @@ use std::{fmt, str::FromStr};
 
 use crate::{
     buffer::{BufferHandle, CharDisplayDistances},
-    buffer_position::BufferPositionIndex,
+    buffer_position::{
+        byte_index_to_soft_wrap_cell, soft_wrap_row_count, BufferPositionIndex, BufferRange,
+    },
     buffer_view::{BufferViewCollection, BufferViewHandle},
+    config::{ClientConfig, ClientDisplayConfig},
+    cursor::CursorCollection,
     editor::Editor,
     events::{EditorEvent, EditorEventQueue},
-    navigation_history::{NavigationHistory, NavigationMovement},
+    navigation_history::NavigationHistory,
     serialization::{DeserializeError, Deserializer, Serialize, Serializer},
+    ui::gutter_width,
 };
 
 #[derive(Default, Clone, Copy, Eq, PartialEq)]
@@ -60,6 +65,11 @@ impl FromStr for ClientHandle {
     }
 }
 
+struct ClientView {
+    buffer: Option<(BufferHandle, CursorCollection)>,
+    scroll: (BufferPositionIndex, BufferPositionIndex),
+}
+
 #[derive(Default)]
 pub struct Client {
     active: bool,
@@ -69,6 +79,15 @@ pub struct Client {
     pub scroll: (BufferPositionIndex, BufferPositionIndex),
     pub height: u16,
     pub navigation_history: NavigationHistory,
+    pub config: ClientConfig,
+    pub following: Option<ClientHandle>,
+    pub wait_buffer_handle: Option<BufferHandle>,
+    pub wait_buffer_saved: bool,
+    pub stdin_buffer_handle: Option<BufferHandle>,
+    pub stdin_utf8_tail: Vec<u8>,
+    pub alternate_buffer_handle: Option<BufferHandle>,
+    pub handshake_done: bool,
+    pub compress_display: bool,
 
     buffer_view_handle: Option<BufferViewHandle>,
 }
@@ -81,6 +100,15 @@ impl Client {
         self.scroll = (0, 0);
         self.height = 0;
         self.navigation_history.clear();
+        self.config = ClientConfig::default();
+        self.following = None;
+        self.wait_buffer_handle = None;
+        self.wait_buffer_saved = false;
+        self.stdin_buffer_handle = None;
+        self.stdin_utf8_tail.clear();
+        self.alternate_buffer_handle = None;
+        self.handshake_done = false;
+        self.compress_display = false;
 
         self.buffer_view_handle = None;
     }
@@ -95,17 +123,57 @@ impl Client {
 
     pub fn on_buffer_close(&mut self, editor: &mut Editor, buffer_handle: BufferHandle) {
         self.navigation_history
-            .remove_snapshots_with_buffer_handle(buffer_handle);
+            .on_buffer_close(editor.buffers.get(buffer_handle));
+        if self.stdin_buffer_handle == Some(buffer_handle) {
+            self.stdin_buffer_handle = None;
+            self.stdin_utf8_tail.clear();
+        }
+        if self.alternate_buffer_handle == Some(buffer_handle) {
+            self.alternate_buffer_handle = None;
+        }
 
         if let Some(handle) = self.buffer_view_handle {
             let buffer_view = editor.buffer_views.get(handle);
             if buffer_view.buffer_handle == buffer_handle {
                 self.buffer_view_handle = None;
-                NavigationHistory::move_in_history(self, editor, NavigationMovement::Backward);
+                NavigationHistory::move_to_last_open_buffer(self, editor);
             }
         }
     }
 
+    pub fn on_buffer_insert_text(
+        &mut self,
+        buffer_views: &BufferViewCollection,
+        buffer_handle: BufferHandle,
+        range: BufferRange,
+    ) {
+        if self.shows_buffer(buffer_views, buffer_handle) && range.from.line_index < self.scroll.1 {
+            self.scroll.1 += range.to.line_index - range.from.line_index;
+        }
+    }
+
+    pub fn on_buffer_delete_text(
+        &mut self,
+        buffer_views: &BufferViewCollection,
+        buffer_handle: BufferHandle,
+        range: BufferRange,
+    ) {
+        if self.shows_buffer(buffer_views, buffer_handle) && range.from.line_index < self.scroll.1 {
+            self.scroll.1 -= range.to.line_index.min(self.scroll.1) - range.from.line_index;
+        }
+    }
+
+    fn shows_buffer(
+        &self,
+        buffer_views: &BufferViewCollection,
+        buffer_handle: BufferHandle,
+    ) -> bool {
+        match self.buffer_view_handle {
+            Some(handle) => buffer_views.get(handle).buffer_handle == buffer_handle,
+            None => false,
+        }
+    }
+
     pub fn set_buffer_view_handle_no_history(
         &mut self,
         handle: Option<BufferViewHandle>,
@@ -129,14 +197,33 @@ impl Client {
         self.set_buffer_view_handle_no_history(handle, events);
     }
 
+    pub fn display_config<'a>(&'a self, editor: &'a Editor) -> ClientDisplayConfig<'a> {
+        ClientDisplayConfig::new(&self.config, &editor.config)
+    }
+
     pub fn has_ui(&self) -> bool {
         self.viewport_size.0 != 0 && self.viewport_size.1 != 0
     }
 
+    pub fn buffer_view_width(&self, editor: &Editor) -> u16 {
+        let file_tree_width = editor
+            .file_tree
+            .width(editor.config.file_tree_width, self.viewport_size.0);
+        let width = self.viewport_size.0 - file_tree_width;
+
+        match self.buffer_view_handle() {
+            Some(handle) => {
+                let buffer_handle = editor.buffer_views.get(handle).buffer_handle;
+                width - gutter_width(editor, self.display_config(editor), buffer_handle, width)
+            }
+            None => width,
+        }
+    }
+
     pub fn update_view(&mut self, editor: &Editor, picker_height: u16) {
         self.height = self.viewport_size.1.saturating_sub(1 + picker_height);
 
-        let width = self.viewport_size.0 as BufferPositionIndex;
+        let width = self.buffer_view_width(editor) as BufferPositionIndex;
         if width == 0 {
             return;
         }
@@ -150,7 +237,10 @@ impl Client {
         };
 
         let buffer_view = editor.buffer_views.get(buffer_view_handle);
-        let buffer = editor.buffers.get(buffer_view.buffer_handle).content();
+        let buffer = editor.buffers.get(buffer_view.buffer_handle);
+        let buffer_tab_size = buffer.tab_size(&editor.config);
+        let soft_wrap = buffer.soft_wrap(self.display_config(editor));
+        let buffer = buffer.content();
 
         let position = buffer_view.cursors.main_cursor().position;
 
@@ -163,6 +253,33 @@ impl Client {
 
         let (mut scroll_x, mut scroll_y) = self.scroll;
 
+        if soft_wrap {
+            if line_index < scroll_y.saturating_sub(quarter_height) {
+                scroll_y = line_index.saturating_sub(half_height);
+            } else if line_index < scroll_y {
+                scroll_y = line_index;
+            } else if line_index >= scroll_y + height {
+                scroll_y = line_index + 1 - height;
+            }
+
+            let width = width as usize;
+            let cursor_row =
+                byte_index_to_soft_wrap_cell(line, column_index as _, width, buffer_tab_size).row;
+            let mut rows = cursor_row + 1;
+            for i in scroll_y..line_index {
+                let line = buffer.line_at(i as _).as_str();
+                rows += soft_wrap_row_count(line, width, buffer_tab_size);
+            }
+            while rows > height as usize && scroll_y < line_index {
+                let line = buffer.line_at(scroll_y as _).as_str();
+                rows -= soft_wrap_row_count(line, width, buffer_tab_size);
+                scroll_y += 1;
+            }
+
+            self.scroll = (0, scroll_y);
+            return;
+        }
+
         if column_index < scroll_x {
             scroll_x = column_index
         } else {
@@ -172,7 +289,7 @@ impl Client {
                 None => (width - 1, line),
             };
 
-            if let Some(d) = CharDisplayDistances::new(text, editor.config.tab_size)
+            if let Some(d) = CharDisplayDistances::new(text, buffer_tab_size)
                 .rev()
                 .take_while(|d| d.distance <= width as _)
                 .last()
@@ -243,6 +360,13 @@ impl ClientManager {
         }
     }
 
+    pub fn try_get(&self, handle: ClientHandle) -> Option<&Client> {
+        match self.clients.get(handle.into_index()) {
+            Some(client) if client.active => Some(client),
+            _ => None,
+        }
+    }
+
     pub fn get(&self, handle: ClientHandle) -> &Client {
         &self.clients[handle.into_index()]
     }
@@ -251,6 +375,79 @@ impl ClientManager {
         &mut self.clients[handle.into_index()]
     }
 
+    pub fn update_followers(
+        &mut self,
+        buffer_views: &mut BufferViewCollection,
+        events: &mut EditorEventQueue,
+    ) {
+        for i in 0..self.clients.len() {
+            let leader_handle = match self.clients[i].following {
+                Some(handle) if self.clients[i].active => handle,
+                _ => continue,
+            };
+            let leader = &self.clients[leader_handle.into_index()];
+            if !leader.active {
+                self.clients[i].following = None;
+                continue;
+            }
+
+            let view = Self::view_of(leader, buffer_views);
+            Self::set_view(&mut self.clients[i], view, buffer_views, events);
+        }
+    }
+
+    pub fn copy_view(
+        &mut self,
+        from: ClientHandle,
+        to: ClientHandle,
+        buffer_views: &mut BufferViewCollection,
+        events: &mut EditorEventQueue,
+    ) {
+        let view = Self::view_of(self.get(from), buffer_views);
+        Self::set_view(self.get_mut(to), view, buffer_views, events);
+    }
+
+    pub fn swap_views(
+        &mut self,
+        a: ClientHandle,
+        b: ClientHandle,
+        buffer_views: &mut BufferViewCollection,
+        events: &mut EditorEventQueue,
+    ) {
+        let a_view = Self::view_of(self.get(a), buffer_views);
+        let b_view = Self::view_of(self.get(b), buffer_views);
+        Self::set_view(self.get_mut(a), b_view, buffer_views, events);
+        Self::set_view(self.get_mut(b), a_view, buffer_views, events);
+    }
+
+    fn view_of(client: &Client, buffer_views: &BufferViewCollection) -> ClientView {
+        ClientView {
+            buffer: client.buffer_view_handle.map(|handle| {
+                let buffer_view = buffer_views.get(handle);
+                (buffer_view.buffer_handle, buffer_view.cursors.clone())
+            }),
+            scroll: client.scroll,
+        }
+    }
+
+    fn set_view(
+        client: &mut Client,
+        view: ClientView,
+        buffer_views: &mut BufferViewCollection,
+        events: &mut EditorEventQueue,
+    ) {
+        match view.buffer {
+            Some((buffer_handle, cursors)) => {
+                let handle = buffer_views
+                    .buffer_view_handle_from_buffer_handle(client.handle, buffer_handle);
+                buffer_views.get_mut(handle).cursors = cursors;
+                client.set_buffer_view_handle_no_history(Some(handle), events);
+            }
+            None => client.set_buffer_view_handle_no_history(None, events),
+        }
+        client.scroll = view.scroll;
+    }
+
     pub fn iter(&self) -> impl Clone + Iterator<Item = &Client> {
         self.clients.iter().filter(|c| c.active)
     }
@@ -259,3 +456,108 @@ impl ClientManager {
         self.clients.iter_mut().filter(|c| c.active)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{buffer::BufferCollection, buffer_position::BufferPosition, cursor::Cursor};
+
+    fn cursor_at(line_index: BufferPositionIndex) -> Cursor {
+        let position = BufferPosition::line_col(line_index, 0);
+        Cursor {
+            anchor: position,
+            position,
+        }
+    }
+
+    fn view(
+        clients: &ClientManager,
+        buffer_views: &BufferViewCollection,
+        handle: ClientHandle,
+    ) -> (BufferHandle, BufferPositionIndex, BufferPositionIndex) {
+        let client = clients.get(handle);
+        let buffer_view = buffer_views.get(client.buffer_view_handle().unwrap());
+        assert_eq!(handle.0, buffer_view.client_handle.0);
+        (
+            buffer_view.buffer_handle,
+            buffer_view.cursors.main_cursor().position.line_index,
+            client.scroll.1,
+        )
+    }
+
+    #[test]
+    fn swap_and_copy_views() {
+        let mut buffers = BufferCollection::default();
+        let buffer_a = buffers.add_new().handle();
+        let buffer_b = buffers.add_new().handle();
+
+        let mut buffer_views = BufferViewCollection::default();
+        let mut events = EditorEventQueue::default();
+        let mut clients = ClientManager::default();
+        let client_0 = ClientHandle(0);
+        let client_1 = ClientHandle(1);
+        clients.on_client_joined(client_0);
+        clients.on_client_joined(client_1);
+
+        for (client_handle, buffer_handle, line_index) in
+            [(client_0, buffer_a, 3), (client_1, buffer_b, 7)]
+        {
+            let handle = buffer_views.add_new(client_handle, buffer_handle);
+            let mut cursors = buffer_views.get_mut(handle).cursors.mut_guard();
+            cursors.clear();
+            cursors.add(cursor_at(line_index));
+            drop(cursors);
+            let client = clients.get_mut(client_handle);
+            client.set_buffer_view_handle_no_history(Some(handle), &mut events);
+            client.scroll = (0, line_index);
+        }
+
+        clients.swap_views(client_0, client_1, &mut buffer_views, &mut events);
+        let (buffer, line, scroll) = view(&clients, &buffer_views, client_0);
+        assert!(buffer == buffer_b && line == 7 && scroll == 7);
+        let (buffer, line, scroll) = view(&clients, &buffer_views, client_1);
+        assert!(buffer == buffer_a && line == 3 && scroll == 3);
+
+        clients.copy_view(client_1, client_0, &mut buffer_views, &mut events);
+        let (buffer, line, scroll) = view(&clients, &buffer_views, client_0);
+        assert!(buffer == buffer_a && line == 3 && scroll == 3);
+        assert!(
+            clients.get(client_0).buffer_view_handle()
+                != clients.get(client_1).buffer_view_handle()
+        );
+    }
+
+    #[test]
+    fn edits_above_viewport_keep_scroll_stable() {
+        let mut buffers = BufferCollection::default();
+        let buffer_handle = buffers.add_new().handle();
+        let mut buffer_views = BufferViewCollection::default();
+        let mut events = EditorEventQueue::default();
+
+        let mut client = Client::default();
+        let handle = buffer_views.add_new(client.handle(), buffer_handle);
+        client.set_buffer_view_handle_no_history(Some(handle), &mut events);
+        client.scroll = (0, 10);
+
+        let range = |from, to| {
+            BufferRange::between(
+                BufferPosition::line_col(from, 0),
+                BufferPosition::line_col(to, 0),
+            )
+        };
+
+        client.on_buffer_insert_text(&buffer_views, buffer_handle, range(2, 5));
+        assert_eq!(13, client.scroll.1);
+        client.on_buffer_insert_text(&buffer_views, buffer_handle, range(20, 25));
+        assert_eq!(13, client.scroll.1);
+        client.on_buffer_delete_text(&buffer_views, buffer_handle, range(1, 4));
+        assert_eq!(10, client.scroll.1);
+        client.on_buffer_delete_text(&buffer_views, buffer_handle, range(8, 30));
+        assert_eq!(8, client.scroll.1);
+
+        let other_buffer_handle = buffers.add_new().handle();
+        client.on_buffer_insert_text(&buffer_views, other_buffer_handle, range(0, 5));
+        assert_eq!(8, client.scroll.1);
+    }
+}
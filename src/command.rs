@@ -1,39 +1,78 @@
-use std::{collections::VecDeque, fmt};
+use std::{collections::VecDeque, fmt, io};
 
 use crate::{
     buffer::{Buffer, BufferHandle, BufferReadError, BufferWriteError},
     buffer_view::BufferViewHandle,
     client::{ClientHandle, ClientManager},
     config::ParseConfigError,
+    dap::DebuggerError,
     editor::{Editor, EditorControlFlow},
     editor_utils::MessageKind,
+    git::GitError,
     glob::InvalidGlobError,
     keymap::ParseKeyMapError,
+    lsp::LspConfigError,
     pattern::PatternError,
     platform::Platform,
+    plugin::manager::PluginManagerError,
+    register::RegisterKey,
+    script::ScriptError,
 };
 
 mod builtin;
 
 pub const HISTORY_CAPACITY: usize = 10;
+const MACRO_RECURSION_LIMIT: u8 = 16;
 
 pub enum CommandError {
     NoSuchCommand,
     TooManyArguments,
     TooFewArguments,
     NoTargetClient,
+    NoSuchClient,
     NoBufferOpened,
+    NoAlternateBuffer,
+    NoClosedBuffer,
     UnsavedChanges,
     BufferReadError(BufferReadError),
     BufferWriteError(BufferWriteError),
     ConfigError(ParseConfigError),
     NoSuchColor,
     InvalidColorValue,
+    InvalidSignGlyph,
     KeyMapError(ParseKeyMapError),
+    NoSuchKeyMap,
+    InvalidDuration,
     PatternError(PatternError),
     InvalidGlob(InvalidGlobError),
     LspServerNotRunning,
     LspServerNotLogging,
+    LspConfigError(LspConfigError),
+    GitError(GitError),
+    ScriptError(ScriptError),
+    NoSuchMode,
+    NoSuchPanel,
+    NoSuchPlugin,
+    PluginManagerError(PluginManagerError),
+    DebuggerError(DebuggerError),
+    InvalidWorkspaceRoot,
+    NoSuchWorkspaceRoot,
+    FileOperationError(io::Error),
+    FileAlreadyExists,
+    DeleteDirectoryWithoutRecursive,
+    DeleteCurrentDirectory,
+    NoUrlUnderCursor,
+    MakeAlreadyRunning,
+    NoMakeResults,
+    NoFormatter,
+    NoLinter,
+    InvalidEnvironmentVariable,
+    InvalidLogLevel,
+    InvalidMacroName,
+    InvalidProcessCommand,
+    InvalidProcessOutput,
+    InvalidMacroParam,
+    MacroRecursionLimit,
 }
 impl fmt::Display for CommandError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -42,23 +81,59 @@ impl fmt::Display for CommandError {
             Self::TooManyArguments => f.write_str("too many arguments"),
             Self::TooFewArguments => f.write_str("too few arguments"),
             Self::NoTargetClient => f.write_str("no target client"),
+            Self::NoSuchClient => f.write_str("no such client"),
             Self::NoBufferOpened => f.write_str("no buffer opened"),
+            Self::NoAlternateBuffer => f.write_str("no alternate buffer"),
+            Self::NoClosedBuffer => f.write_str("no closed buffer to reopen"),
             Self::UnsavedChanges => f.write_str("unsaved changes"),
             Self::BufferReadError(error) => error.fmt(f),
             Self::BufferWriteError(error) => error.fmt(f),
             Self::ConfigError(error) => error.fmt(f),
             Self::NoSuchColor => f.write_str("no such color"),
             Self::InvalidColorValue => f.write_str("invalid color value"),
+            Self::InvalidSignGlyph => f.write_str("sign glyph must be a single character"),
             Self::KeyMapError(error) => error.fmt(f),
+            Self::NoSuchKeyMap => f.write_str("no such keymap"),
+            Self::InvalidDuration => f.write_str("invalid duration"),
             Self::PatternError(error) => write!(f, "pattern error: {}", error),
             Self::InvalidGlob(InvalidGlobError) => InvalidGlobError.fmt(f),
             Self::LspServerNotRunning => f.write_str("no lsp server running"),
             Self::LspServerNotLogging => f.write_str("lsp server is not logging"),
+            Self::LspConfigError(error) => error.fmt(f),
+            Self::GitError(error) => error.fmt(f),
+            Self::ScriptError(error) => error.fmt(f),
+            Self::NoSuchMode => f.write_str("no such mode"),
+            Self::NoSuchPanel => f.write_str("no such panel"),
+            Self::NoSuchPlugin => f.write_str("no such plugin"),
+            Self::PluginManagerError(error) => error.fmt(f),
+            Self::DebuggerError(error) => error.fmt(f),
+            Self::InvalidWorkspaceRoot => f.write_str("invalid workspace root"),
+            Self::NoSuchWorkspaceRoot => f.write_str("no such workspace root"),
+            Self::FileOperationError(error) => error.fmt(f),
+            Self::FileAlreadyExists => f.write_str("file already exists"),
+            Self::DeleteDirectoryWithoutRecursive => {
+                f.write_str("can not delete a directory without -recursive")
+            }
+            Self::DeleteCurrentDirectory => {
+                f.write_str("can not delete the current directory or its parents")
+            }
+            Self::NoUrlUnderCursor => f.write_str("no url under cursor"),
+            Self::MakeAlreadyRunning => f.write_str("make is already running"),
+            Self::NoMakeResults => f.write_str("no make results"),
+            Self::NoFormatter => f.write_str("no formatter for buffer"),
+            Self::NoLinter => f.write_str("no linter for buffer"),
+            Self::InvalidEnvironmentVariable => f.write_str("invalid environment variable"),
+            Self::InvalidLogLevel => f.write_str("invalid log level"),
+            Self::InvalidMacroName => f.write_str("invalid macro name"),
+            Self::InvalidProcessCommand => f.write_str("invalid process command"),
+            Self::InvalidProcessOutput => f.write_str("process output is not valid utf-8"),
+            Self::InvalidMacroParam => f.write_str("invalid macro param"),
+            Self::MacroRecursionLimit => f.write_str("macro recursion limit reached"),
         }
     }
 }
 
-type CommandFn = fn(&mut CommandContext) -> Result<EditorControlFlow, CommandError>;
+pub type CommandFn = fn(&mut CommandContext) -> Result<EditorControlFlow, CommandError>;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompletionSource {
@@ -74,6 +149,10 @@ impl<'command> CommandArgs<'command> {
         self.0.next()
     }
 
+    pub fn peek(&self) -> Option<&'command str> {
+        self.0.clone().next()
+    }
+
     pub fn next(&mut self) -> Result<&'command str, CommandError> {
         match self.try_next() {
             Some(value) => Ok(value),
@@ -143,6 +222,24 @@ impl<'state, 'command> CommandContext<'state, 'command> {
 
 #[derive(Clone)]
 pub struct CommandTokenizer<'a>(pub &'a str);
+impl<'a> CommandTokenizer<'a> {
+    pub fn has_unfinished_balanced_token(command: &str) -> bool {
+        let mut tokens = CommandTokenizer(command);
+        loop {
+            let rest = tokens.0.trim_start_matches(&[' ', '\t'][..]);
+            let token = match tokens.next() {
+                Some(token) => token,
+                None => return false,
+            };
+            if rest.starts_with('[')
+                && rest[1..].trim_start_matches('=').starts_with('[')
+                && token.as_ptr() == rest.as_ptr()
+            {
+                return true;
+            }
+        }
+    }
+}
 impl<'a> Iterator for CommandTokenizer<'a> {
     type Item = &'a str;
     fn next(&mut self) -> Option<Self::Item> {
@@ -227,6 +324,7 @@ impl<'a> Iterator for CommandTokenizer<'a> {
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct BuiltinCommand {
     pub name: &'static str,
     pub completions: &'static [CompletionSource],
@@ -298,18 +396,298 @@ impl AliasCollection {
     }
 }
 
+fn is_valid_macro_param(param: &str) -> bool {
+    let mut chars = param.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => (),
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub fn push_escaped_arg(command: &mut String, arg: &str) {
+    fn push_balanced_delim(command: &mut String, delim: char, depth: usize) {
+        command.push(delim);
+        for _ in 0..depth {
+            command.push('=');
+        }
+        command.push(delim);
+    }
+
+    let is_multiline = arg.contains('\n');
+    let is_literal = !arg.is_empty()
+        && !arg.contains(&[' ', '\t', '\n'][..])
+        && !arg.starts_with(&['"', '\'', '['][..]);
+    if is_literal {
+        command.push_str(arg);
+    } else if !is_multiline && !arg.contains('"') {
+        command.push('"');
+        command.push_str(arg);
+        command.push('"');
+    } else if !is_multiline && !arg.contains('\'') {
+        command.push('\'');
+        command.push_str(arg);
+        command.push('\'');
+    } else {
+        let mut closing = String::from("]");
+        while arg.contains(&closing[..]) {
+            closing.push('=');
+        }
+        let depth = closing.len() - 1;
+        push_balanced_delim(command, '[', depth);
+        command.push_str(arg);
+        push_balanced_delim(command, ']', depth);
+    }
+}
+
+// writes `body` with every `$<param>` or `$<index>` replaced by its escaped value.
+// unknown `$` references are kept as they are
+pub fn expand_params<P>(
+    body: &str,
+    params: &[P],
+    values: &[Option<&str>],
+    command: &mut String,
+) -> Result<(), CommandError>
+where
+    P: AsRef<str>,
+{
+    let mut body = body;
+    while let Some(i) = body.find('$') {
+        command.push_str(&body[..i]);
+        body = &body[i + 1..];
+
+        let reference_len = body
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(body.len());
+        let reference = &body[..reference_len];
+        let index = match reference.parse::<usize>() {
+            Ok(index) if index > 0 => Some(index - 1),
+            Ok(_) => None,
+            Err(_) => params.iter().position(|p| p.as_ref() == reference),
+        };
+        match index.and_then(|i| values.get(i)) {
+            Some(Some(value)) => {
+                push_escaped_arg(command, value);
+                body = &body[reference_len..];
+            }
+            Some(None) => return Err(CommandError::TooFewArguments),
+            None => command.push('$'),
+        }
+    }
+    command.push_str(body);
+
+    Ok(())
+}
+
+// writes `command` with every `%{<name>}` outside of balanced tokens replaced by
+// what `write_variable` writes for `<name>`. if `<name>` is not a variable, it's kept as it is
+// (patterns use `%{` too). balanced tokens are also kept as they are so their variables
+// are only expanded when they are evaluated themselves
+pub fn expand_variables<F>(
+    command: &str,
+    output: &mut String,
+    mut write_variable: F,
+) -> Result<(), CommandError>
+where
+    F: FnMut(&str, &mut String) -> Result<bool, CommandError>,
+{
+    let mut tokens = CommandTokenizer(command);
+    loop {
+        let rest = tokens.0;
+        let token = match tokens.next() {
+            Some(token) => token,
+            None => {
+                output.push_str(rest);
+                break;
+            }
+        };
+        let mut raw = &rest[..rest.len() - tokens.0.len()];
+
+        let trimmed = rest.trim_start_matches(&[' ', '\t'][..]);
+        if trimmed.starts_with('[') && token.as_ptr() != trimmed.as_ptr() {
+            output.push_str(raw);
+            continue;
+        }
+
+        while let Some(i) = raw.find("%{") {
+            output.push_str(&raw[..i]);
+            let variable = &raw[i + 2..];
+            match variable.find('}') {
+                Some(end) if write_variable(&variable[..end], output)? => {
+                    raw = &variable[end + 1..];
+                }
+                Some(_) => {
+                    output.push_str("%{");
+                    raw = variable;
+                }
+                None => {
+                    output.push_str(&raw[i..]);
+                    raw = "";
+                }
+            }
+        }
+        output.push_str(raw);
+    }
+
+    Ok(())
+}
+
+fn write_editor_variable(
+    editor: &Editor,
+    clients: &ClientManager,
+    client_handle: Option<ClientHandle>,
+    name: &str,
+    output: &mut String,
+) -> Result<bool, CommandError> {
+    use fmt::Write;
+
+    if let Some(key) = name.strip_prefix("register.") {
+        let mut chars = key.chars();
+        return match (chars.next().and_then(RegisterKey::from_char), chars.next()) {
+            (Some(key), None) => {
+                output.push_str(editor.registers.get(key));
+                Ok(true)
+            }
+            _ => Ok(false),
+        };
+    }
+
+    let current_buffer_view = || {
+        let client_handle = client_handle.ok_or(CommandError::NoTargetClient)?;
+        match clients.get(client_handle).buffer_view_handle() {
+            Some(handle) => Ok(editor.buffer_views.get(handle)),
+            None => Err(CommandError::NoBufferOpened),
+        }
+    };
+
+    match name {
+        "buffer.path" => {
+            let buffer_handle = current_buffer_view()?.buffer_handle;
+            let buffer = editor.buffers.get(buffer_handle);
+            output.push_str(&buffer.path.to_string_lossy());
+        }
+        "cursor.line" => {
+            let position = current_buffer_view()?.cursors.main_cursor().position;
+            let _ = write!(output, "{}", position.line_index + 1);
+        }
+        "cursor.column" => {
+            let position = current_buffer_view()?.cursors.main_cursor().position;
+            let _ = write!(output, "{}", position.column_byte_index + 1);
+        }
+        _ => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+pub struct MacroCommand {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: String,
+}
+impl MacroCommand {
+    // binds `args` to `params` before expanding them into `body`
+    pub fn expand(&self, mut args: CommandArgs, command: &mut String) -> Result<(), CommandError> {
+        let mut values = Vec::with_capacity(self.params.len());
+        values.resize(self.params.len(), None);
+
+        let mut positional = Vec::new();
+        while let Some(arg) = args.try_next() {
+            let named = arg.strip_prefix('-').and_then(|arg| {
+                let (name, value) = arg.split_at(arg.find('=')?);
+                let index = self.params.iter().position(|p| p == name)?;
+                Some((index, &value[1..]))
+            });
+            match named {
+                Some((index, value)) => values[index] = Some(value),
+                None => positional.push(arg),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        for value in &mut values {
+            if value.is_none() {
+                *value = positional.next();
+            }
+        }
+        if positional.next().is_some() {
+            return Err(CommandError::TooManyArguments);
+        }
+
+        expand_params(&self.body, &self.params, &values, command)
+    }
+}
+
+#[derive(Default)]
+pub struct MacroCollection {
+    macros: Vec<MacroCommand>,
+}
+impl MacroCollection {
+    pub fn add(&mut self, name: &str, params: &[&str], body: &str) -> Result<(), CommandError> {
+        let is_valid_name = !name.is_empty()
+            && !name.ends_with('!')
+            && !name.contains(&[' ', '\t', '\n', '"', '\''][..]);
+        if !is_valid_name {
+            return Err(CommandError::InvalidMacroName);
+        }
+        for (i, param) in params.iter().enumerate() {
+            if !is_valid_macro_param(param) || params[..i].contains(param) {
+                return Err(CommandError::InvalidMacroParam);
+            }
+        }
+
+        let macro_command = MacroCommand {
+            name: name.into(),
+            params: params.iter().map(|&p| p.into()).collect(),
+            body: body.into(),
+        };
+        match self.macros.iter_mut().find(|m| m.name == name) {
+            Some(m) => *m = macro_command,
+            None => self.macros.push(macro_command),
+        }
+        Ok(())
+    }
+
+    pub fn find(&self, name: &str) -> Option<&MacroCommand> {
+        self.macros.iter().find(|m| m.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &MacroCommand> {
+        self.macros.iter()
+    }
+}
+
 pub struct CommandManager {
-    builtin_commands: &'static [BuiltinCommand],
+    builtin_commands: Vec<BuiltinCommand>,
     history: VecDeque<String>,
     pub aliases: AliasCollection,
+    pub macros: MacroCollection,
+    macro_depth: u8,
 }
 
 impl CommandManager {
     pub fn new() -> Self {
-        Self {
-            builtin_commands: builtin::COMMANDS,
+        let mut commands = Self {
+            builtin_commands: Vec::with_capacity(builtin::COMMANDS.len()),
             history: VecDeque::with_capacity(HISTORY_CAPACITY),
             aliases: AliasCollection::default(),
+            macros: MacroCollection::default(),
+            macro_depth: 0,
+        };
+        for &command in builtin::COMMANDS {
+            commands.register_builtin(command);
+        }
+        commands
+    }
+
+    pub fn register_builtin(&mut self, command: BuiltinCommand) {
+        match self
+            .builtin_commands
+            .iter_mut()
+            .find(|c| c.name == command.name)
+        {
+            Some(c) => *c = command,
+            None => self.builtin_commands.push(command),
         }
     }
 
@@ -388,6 +766,18 @@ impl CommandManager {
             }
         }
 
+        if command.contains("%{") {
+            let mut expanded = editor.string_pool.acquire();
+            let result = expand_variables(command, &mut expanded, |name, output| {
+                write_editor_variable(editor, clients, client_handle, name, output)
+            });
+            if result.is_ok() {
+                std::mem::swap(command, &mut expanded);
+            }
+            editor.string_pool.release(expanded);
+            result?;
+        }
+
         Self::do_eval(editor, platform, clients, client_handle, command)
     }
 
@@ -409,7 +799,20 @@ impl CommandManager {
         };
         let command_func = match editor.commands.find_command(command) {
             Some(command) => command.func,
-            None => return Err(CommandError::NoSuchCommand),
+            None => {
+                let macro_command = match editor.commands.macros.find(command) {
+                    Some(macro_command) => macro_command,
+                    None => return Err(CommandError::NoSuchCommand),
+                };
+                let mut body = editor.string_pool.acquire();
+                let result = macro_command.expand(CommandArgs(tokenizer), &mut body);
+                let result = match result {
+                    Ok(()) => Self::eval_commands(editor, platform, clients, client_handle, &body),
+                    Err(error) => Err(error),
+                };
+                editor.string_pool.release(body);
+                return result;
+            }
         };
 
         let mut ctx = CommandContext {
@@ -422,6 +825,48 @@ impl CommandManager {
         };
         (command_func)(&mut ctx)
     }
+
+    pub fn eval_commands(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        clients: &mut ClientManager,
+        client_handle: Option<ClientHandle>,
+        body: &str,
+    ) -> Result<EditorControlFlow, CommandError> {
+        if editor.commands.macro_depth >= MACRO_RECURSION_LIMIT {
+            return Err(CommandError::MacroRecursionLimit);
+        }
+        editor.commands.macro_depth += 1;
+
+        let mut result = Ok(EditorControlFlow::Continue);
+        let mut lines = body.lines();
+        while let Some(line) = lines.next() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut command = editor.string_pool.acquire_with(line);
+            while CommandTokenizer::has_unfinished_balanced_token(&command) {
+                match lines.next() {
+                    Some(line) => {
+                        command.push('\n');
+                        command.push_str(line);
+                    }
+                    None => break,
+                }
+            }
+
+            result = Self::try_eval(editor, platform, clients, client_handle, &mut command);
+            editor.string_pool.release(command);
+            match result {
+                Ok(EditorControlFlow::Continue) => (),
+                _ => break,
+            }
+        }
+
+        editor.commands.macro_depth -= 1;
+        result
+    }
 }
 
 #[cfg(test)]
@@ -472,4 +917,141 @@ mod tests {
         assert_eq!(Some("arg]]=]"), tokens.next());
         assert_eq!(None, tokens.next());
     }
+
+    #[test]
+    fn unfinished_balanced_tokens() {
+        assert!(!CommandTokenizer::has_unfinished_balanced_token("cmd arg"));
+        assert!(!CommandTokenizer::has_unfinished_balanced_token(
+            "cmd [[arg]]"
+        ));
+        assert!(!CommandTokenizer::has_unfinished_balanced_token("cmd [arg"));
+        assert!(CommandTokenizer::has_unfinished_balanced_token("cmd [[arg"));
+        assert!(CommandTokenizer::has_unfinished_balanced_token(
+            "cmd [==[arg]]"
+        ));
+        assert!(CommandTokenizer::has_unfinished_balanced_token(
+            "cmd [[arg]] [[{\n\t\"key\": 0"
+        ));
+    }
+
+    #[test]
+    fn escaped_args() {
+        fn escaped(arg: &str) -> String {
+            let mut command = String::new();
+            push_escaped_arg(&mut command, arg);
+            command
+        }
+
+        for arg in &[
+            "arg",
+            "",
+            "two words",
+            "\"quoted\" words",
+            "'single' \"double\"",
+            "[[bracket]] 'single' \"double\"",
+            "ends with ]=",
+            "multi\nline",
+        ] {
+            let command = escaped(arg);
+            let mut tokens = CommandTokenizer(&command);
+            assert_eq!(Some(*arg), tokens.next());
+            assert_eq!(None, tokens.next());
+        }
+    }
+
+    #[test]
+    fn macro_expansion() {
+        let mut macros = MacroCollection::default();
+        assert!(macros.add("m", &["a", "b"], "cmd $a $2 $HOME $").is_ok());
+        assert!(macros.add("bad", &["1a"], "cmd").is_err());
+        assert!(macros.add("bad", &["a", "a"], "cmd").is_err());
+        let macro_command = macros.find("m").unwrap();
+
+        let mut command = String::new();
+        let args = CommandArgs(CommandTokenizer("x y"));
+        assert!(macro_command.expand(args, &mut command).is_ok());
+        assert_eq!("cmd x y $HOME $", command);
+
+        let mut command = String::new();
+        let args = CommandArgs(CommandTokenizer("-a=x 'y z'"));
+        assert!(macro_command.expand(args, &mut command).is_ok());
+        assert_eq!("cmd x \"y z\" $HOME $", command);
+
+        let mut command = String::new();
+        let args = CommandArgs(CommandTokenizer("-b=y x"));
+        assert!(macro_command.expand(args, &mut command).is_ok());
+        assert_eq!("cmd x y $HOME $", command);
+
+        let mut command = String::new();
+        let args = CommandArgs(CommandTokenizer("x"));
+        assert!(macro_command.expand(args, &mut command).is_err());
+
+        let mut command = String::new();
+        let args = CommandArgs(CommandTokenizer("x y z"));
+        assert!(macro_command.expand(args, &mut command).is_err());
+    }
+
+    #[test]
+    fn register_builtin_commands() {
+        let mut commands = CommandManager::new();
+        let builtin_count = commands.builtin_commands().len();
+        assert!(commands.find_command("quit").is_some());
+        assert!(commands.find_command("custom").is_none());
+
+        commands.register_builtin(BuiltinCommand {
+            name: "custom",
+            completions: &[],
+            func: |_| Ok(EditorControlFlow::Quit),
+        });
+        assert_eq!(builtin_count + 1, commands.builtin_commands().len());
+        assert!(commands.find_command("custom").is_some());
+
+        commands.register_builtin(BuiltinCommand {
+            name: "quit",
+            completions: &[CompletionSource::Files],
+            func: |_| Ok(EditorControlFlow::Continue),
+        });
+        assert_eq!(builtin_count + 1, commands.builtin_commands().len());
+        let quit = commands.find_command("quit").unwrap();
+        assert!(matches!(quit.completions, [CompletionSource::Files]));
+    }
+
+    #[test]
+    fn variable_expansion() {
+        fn expand(command: &str) -> Result<String, CommandError> {
+            let mut output = String::new();
+            expand_variables(command, &mut output, |name, output| match name {
+                "a" => {
+                    output.push_str("x y");
+                    Ok(true)
+                }
+                "b" => {
+                    output.push('z');
+                    Ok(true)
+                }
+                "error" => Err(CommandError::NoBufferOpened),
+                _ => Ok(false),
+            })?;
+            Ok(output)
+        }
+
+        assert_eq!(Some("cmd"), expand("cmd").ok().as_deref());
+        assert_eq!(Some("cmd x y"), expand("cmd %{a}").ok().as_deref());
+        assert_eq!(
+            Some("cmd  'x y-z'"),
+            expand("cmd  '%{a}-%{b}'").ok().as_deref()
+        );
+        assert_eq!(
+            Some("cmd \"x y\" %{a"),
+            expand("cmd \"%{a}\" %{a").ok().as_deref()
+        );
+        assert_eq!(
+            Some("cmd [[%{a}]] z"),
+            expand("cmd [[%{a}]] %{b}").ok().as_deref()
+        );
+        assert_eq!(Some("cmd [x y]"), expand("cmd [%{a}]").ok().as_deref());
+        assert_eq!(Some("cmd %{c} z"), expand("cmd %{c} %{b}").ok().as_deref());
+        assert_eq!(Some("a|%{|%}|b"), expand("a|%{|%}|b").ok().as_deref());
+        assert!(expand("cmd %{error}").is_err());
+    }
 }
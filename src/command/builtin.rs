@@ -1,21 +1,45 @@
-use std::path::Path;
+use std::{
+    fmt::Write,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use crate::{
-    buffer::{parse_path_and_position, BufferCapabilities, BufferHandle},
-    buffer_position::BufferPosition,
-    client::ClientManager,
-    command::{BuiltinCommand, CommandContext, CommandError, CompletionSource},
-    config::{ParseConfigError, CONFIG_NAMES},
+    buffer::{
+        parse_path_and_position, BufferCapabilities, BufferHandle, BufferLine, BufferReadError,
+    },
+    buffer_position::{BufferPosition, BufferRange},
+    client::{ClientHandle, ClientManager},
+    command::{BuiltinCommand, CommandArgs, CommandContext, CommandError, CompletionSource},
+    config::{ParseConfigError, CLIENT_CONFIG_NAMES, CONFIG_NAMES},
     cursor::Cursor,
+    dap::{self, Debugger, DebuggerError, StepKind},
     editor::{Editor, EditorControlFlow},
-    editor_utils::MessageKind,
-    help, lsp,
-    mode::{picker, ModeContext, ModeKind},
+    editor_utils::{
+        is_valid_env_var_name, parse_process_command, show_text_in_buffer, LogLevel, MessageKind,
+        BUFFER_LIST_PATH,
+    },
+    formatter::FormatterCollection,
+    git,
+    glob::IgnoreList,
+    help,
+    linter::LinterCollection,
+    lsp,
+    make::Make,
+    mode::{picker, Mode, ModeContext, ModeKind},
+    navigation_history::NavigationHistory,
     platform::Platform,
+    plugin::{self, manager, remote::RemotePluginCollection},
+    spawn::{SpawnCollection, SpawnOptions},
     syntax::TokenKind,
     theme::{Color, THEME_COLOR_NAMES},
+    url,
 };
 
+const DEFAULT_PROFILE_SECONDS: u64 = 5;
+
 pub static COMMANDS: &[BuiltinCommand] = &[
     BuiltinCommand {
         name: "help",
@@ -82,6 +106,15 @@ pub static COMMANDS: &[BuiltinCommand] = &[
             Ok(EditorControlFlow::QuitAll)
         },
     },
+    BuiltinCommand {
+        name: "server-quit",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            ctx.assert_can_discard_all_buffers()?;
+            Ok(EditorControlFlow::QuitServer)
+        },
+    },
     BuiltinCommand {
         name: "open",
         completions: &[CompletionSource::Files],
@@ -107,8 +140,14 @@ pub static COMMANDS: &[BuiltinCommand] = &[
                     );
 
                     if let Some(position) = position {
-                        let mut cursors =
-                            ctx.editor.buffer_views.get_mut(handle).cursors.mut_guard();
+                        let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+                        let position = ctx
+                            .editor
+                            .buffers
+                            .get(buffer_view.buffer_handle)
+                            .content()
+                            .position_from_char_column(position);
+                        let mut cursors = buffer_view.cursors.mut_guard();
                         cursors.clear();
                         cursors.add(Cursor {
                             anchor: position,
@@ -127,24 +166,55 @@ pub static COMMANDS: &[BuiltinCommand] = &[
             Ok(EditorControlFlow::Continue)
         },
     },
+    BuiltinCommand {
+        name: "wait-buffer",
+        completions: &[CompletionSource::Buffers],
+        func: |ctx| {
+            let path = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            let client_handle = ctx.client_handle()?;
+            let (path, _) = parse_path_and_position(path);
+            let buffer_handle = ctx
+                .editor
+                .buffers
+                .find_with_path(&ctx.editor.current_directory, Path::new(path));
+
+            let has_other_focused_client = match ctx.clients.focused_client() {
+                Some(handle) => handle != client_handle,
+                None => false,
+            };
+            match buffer_handle {
+                Some(handle) if has_other_focused_client => {
+                    let client = ctx.clients.get_mut(client_handle);
+                    client.wait_buffer_handle = Some(handle);
+                    client.wait_buffer_saved = false;
+                    Ok(EditorControlFlow::Continue)
+                }
+                Some(_) => {
+                    ctx.editor
+                        .status_bar
+                        .write(MessageKind::Error)
+                        .str("no focused client to edit the buffer");
+                    Ok(EditorControlFlow::Quit)
+                }
+                None => {
+                    ctx.editor
+                        .status_bar
+                        .write(MessageKind::Error)
+                        .fmt(format_args!("no buffer opened at '{}'", path));
+                    Ok(EditorControlFlow::Quit)
+                }
+            }
+        },
+    },
     BuiltinCommand {
         name: "save",
         completions: &[],
         func: |ctx| {
             let path = ctx.args.try_next().map(|p| Path::new(p));
             ctx.args.assert_empty()?;
-
-            let buffer_handle = ctx.current_buffer_handle()?;
-            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
-
-            buffer
-                .write_to_file(path, &mut ctx.editor.events)
-                .map_err(CommandError::BufferWriteError)?;
-
-            ctx.editor
-                .status_bar
-                .write(MessageKind::Info)
-                .fmt(format_args!("buffer saved to {:?}", &buffer.path));
+            save_current_buffer(ctx, path)?;
             Ok(EditorControlFlow::Continue)
         },
     },
@@ -153,24 +223,34 @@ pub static COMMANDS: &[BuiltinCommand] = &[
         completions: &[],
         func: |ctx| {
             ctx.args.assert_empty()?;
-
-            let mut count = 0;
-            for buffer in ctx.editor.buffers.iter_mut() {
-                if buffer.capabilities.can_save {
-                    buffer
-                        .write_to_file(None, &mut ctx.editor.events)
-                        .map_err(CommandError::BufferWriteError)?;
-                    count += 1;
-                }
-            }
-
-            ctx.editor
-                .status_bar
-                .write(MessageKind::Info)
-                .fmt(format_args!("{} buffers saved", count));
+            save_all_buffers(ctx)?;
             Ok(EditorControlFlow::Continue)
         },
     },
+    BuiltinCommand {
+        name: "save-quit",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            save_current_buffer(ctx, None)?;
+            ctx.editor.trigger_event_handlers(ctx.platform, ctx.clients);
+            if ctx.clients.iter().count() == 1 {
+                ctx.assert_can_discard_all_buffers()?;
+            }
+            Ok(EditorControlFlow::Quit)
+        },
+    },
+    BuiltinCommand {
+        name: "save-all-quit",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            save_all_buffers(ctx)?;
+            ctx.editor.trigger_event_handlers(ctx.platform, ctx.clients);
+            ctx.assert_can_discard_all_buffers()?;
+            Ok(EditorControlFlow::QuitAll)
+        },
+    },
     BuiltinCommand {
         name: "reopen",
         completions: &[],
@@ -182,7 +262,11 @@ pub static COMMANDS: &[BuiltinCommand] = &[
             let buffer = ctx.editor.buffers.get_mut(buffer_handle);
 
             buffer
-                .read_from_file(&mut ctx.editor.word_database, &mut ctx.editor.events)
+                .read_from_file(
+                    &mut ctx.editor.word_database,
+                    &ctx.editor.config,
+                    &mut ctx.editor.events,
+                )
                 .map_err(CommandError::BufferReadError)?;
 
             ctx.editor
@@ -192,6 +276,49 @@ pub static COMMANDS: &[BuiltinCommand] = &[
             Ok(EditorControlFlow::Continue)
         },
     },
+    BuiltinCommand {
+        name: "reopen-closed",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let client_handle = ctx.client_handle()?;
+            let closed = ctx
+                .editor
+                .buffers
+                .pop_closed()
+                .ok_or(CommandError::NoClosedBuffer)?;
+
+            let handle = ctx
+                .editor
+                .buffer_view_handle_from_path(
+                    client_handle,
+                    &closed.path,
+                    BufferCapabilities::text(),
+                )
+                .map_err(CommandError::BufferReadError)?;
+            let client = ctx.clients.get_mut(client_handle);
+            client.set_buffer_view_handle(
+                Some(handle),
+                &ctx.editor.buffer_views,
+                &mut ctx.editor.events,
+            );
+
+            let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+            let position = ctx
+                .editor
+                .buffers
+                .get(buffer_view.buffer_handle)
+                .content()
+                .saturate_position(closed.position);
+            let mut cursors = buffer_view.cursors.mut_guard();
+            cursors.clear();
+            cursors.add(Cursor {
+                anchor: position,
+                position,
+            });
+            Ok(EditorControlFlow::Continue)
+        },
+    },
     BuiltinCommand {
         name: "reopen-all",
         completions: &[],
@@ -202,7 +329,11 @@ pub static COMMANDS: &[BuiltinCommand] = &[
             let mut count = 0;
             for buffer in ctx.editor.buffers.iter_mut() {
                 buffer
-                    .read_from_file(&mut ctx.editor.word_database, &mut ctx.editor.events)
+                    .read_from_file(
+                        &mut ctx.editor.word_database,
+                        &ctx.editor.config,
+                        &mut ctx.editor.events,
+                    )
                     .map_err(CommandError::BufferReadError)?;
                 count += 1;
             }
@@ -271,149 +402,156 @@ pub static COMMANDS: &[BuiltinCommand] = &[
         },
     },
     BuiltinCommand {
-        name: "config",
-        completions: &[(CompletionSource::Custom(CONFIG_NAMES))],
+        name: "undo-checkpoint",
+        completions: &[],
         func: |ctx| {
-            let key = ctx.args.next()?;
-            let value = ctx.args.try_next();
             ctx.args.assert_empty()?;
-
-            match value {
-                Some(value) => match ctx.editor.config.parse_config(key, value) {
-                    Ok(()) => Ok(EditorControlFlow::Continue),
-                    Err(error) => Err(CommandError::ConfigError(error)),
-                },
-                None => match ctx.editor.config.display_config(key) {
-                    Some(display) => {
-                        ctx.editor
-                            .status_bar
-                            .write(MessageKind::Info)
-                            .fmt(format_args!("{}", display));
-                        Ok(EditorControlFlow::Continue)
-                    }
-                    None => Err(CommandError::ConfigError(ParseConfigError::NoSuchConfig)),
-                },
-            }
+            let buffer_handle = ctx.current_buffer_handle()?;
+            ctx.editor.buffers.get_mut(buffer_handle).commit_edits();
+            Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
-        name: "color",
-        completions: &[CompletionSource::Custom(THEME_COLOR_NAMES)],
+        name: "messages",
+        completions: &[],
         func: |ctx| {
-            let key = ctx.args.next()?;
-            let value = ctx.args.try_next();
             ctx.args.assert_empty()?;
+            let client_handle = ctx.client_handle()?;
 
-            let color = ctx
-                .editor
-                .theme
-                .color_from_name(key)
-                .ok_or(CommandError::NoSuchColor)?;
-
-            match value {
-                Some(value) => {
-                    let encoded =
-                        u32::from_str_radix(value, 16).map_err(|_| CommandError::NoSuchColor)?;
-                    *color = Color::from_u32(encoded);
-                }
-                None => ctx
-                    .editor
-                    .status_bar
-                    .write(MessageKind::Info)
-                    .fmt(format_args!("0x{:0<6x}", color.into_u32())),
+            let mut text = ctx.editor.string_pool.acquire();
+            for (kind, message) in ctx.editor.status_bar.history() {
+                let kind = match kind {
+                    MessageKind::Info => "info",
+                    MessageKind::Error => "error",
+                };
+                text.push_str(kind);
+                text.push_str(": ");
+                text.push_str(message);
+                text.push('\n');
             }
-
+            show_text_in_buffer(
+                ctx.editor,
+                ctx.clients,
+                client_handle,
+                Path::new("messages.log"),
+                &text,
+            );
+            ctx.editor.string_pool.release(text);
             Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
-        name: "map-normal",
-        completions: &[],
-        func: |ctx| map(ctx, ModeKind::Normal),
-    },
-    BuiltinCommand {
-        name: "map-insert",
-        completions: &[],
-        func: |ctx| map(ctx, ModeKind::Insert),
-    },
-    BuiltinCommand {
-        name: "map-command",
-        completions: &[],
-        func: |ctx| map(ctx, ModeKind::Command),
-    },
-    BuiltinCommand {
-        name: "map-readline",
-        completions: &[],
-        func: |ctx| map(ctx, ModeKind::Command),
-    },
-    BuiltinCommand {
-        name: "map-picker",
+        name: "buffers",
         completions: &[],
-        func: |ctx| map(ctx, ModeKind::Picker),
-    },
-    BuiltinCommand {
-        name: "alias",
-        completions: &[CompletionSource::Custom(&[]), CompletionSource::Commands],
         func: |ctx| {
-            let from = ctx.args.next()?;
-            let to = ctx.args.next()?;
             ctx.args.assert_empty()?;
-            ctx.editor.commands.aliases.add(from, to);
+            let client_handle = ctx.client_handle()?;
+
+            let mut text = ctx.editor.string_pool.acquire();
+            let editor = &*ctx.editor;
+            let buffers: Vec<_> = editor
+                .buffers
+                .iter()
+                .filter(|b| b.path != Path::new(BUFFER_LIST_PATH))
+                .collect();
+            let path_width = buffers
+                .iter()
+                .map(|b| b.path.to_str().unwrap_or("").len())
+                .chain(std::iter::once("path".len()))
+                .max()
+                .unwrap_or(0);
+            let syntax_width = buffers
+                .iter()
+                .map(|b| editor.syntaxes.get(b.syntax_handle()).glob_pattern().len())
+                .chain(std::iter::once("syntax".len()))
+                .max()
+                .unwrap_or(0);
+
+            let _ = writeln!(
+                text,
+                "{:<6} {:<path_width$} {:<syntax_width$} {:<8} {:>6} {:>9} lsp",
+                "handle",
+                "path",
+                "syntax",
+                "modified",
+                "lines",
+                "bytes",
+                path_width = path_width,
+                syntax_width = syntax_width,
+            );
+            for buffer in buffers {
+                let path = buffer.path.to_str().unwrap_or("");
+                let syntax = editor.syntaxes.get(buffer.syntax_handle()).glob_pattern();
+                let modified = if buffer.needs_save() { "yes" } else { "no" };
+                let content = buffer.content();
+                let line_count = content.line_count();
+                let byte_count =
+                    content.lines().map(|l| l.as_str().len()).sum::<usize>() + line_count - 1;
+
+                let _ = write!(
+                    text,
+                    "{:<6} {:<path_width$} {:<syntax_width$} {:<8} {:>6} {:>9} ",
+                    buffer.handle().0,
+                    path,
+                    syntax,
+                    modified,
+                    line_count,
+                    byte_count,
+                    path_width = path_width,
+                    syntax_width = syntax_width,
+                );
+                match editor.lsp.clients().find(|c| c.handles_path(path)) {
+                    Some(client) if !client.server_name().is_empty() => {
+                        text.push_str(client.server_name())
+                    }
+                    Some(client) => {
+                        let _ = write!(text, "#{}", client.handle());
+                    }
+                    None => text.push('-'),
+                }
+                text.push('\n');
+            }
+
+            show_text_in_buffer(
+                ctx.editor,
+                ctx.clients,
+                client_handle,
+                Path::new(BUFFER_LIST_PATH),
+                &text,
+            );
+            ctx.editor.string_pool.release(text);
             Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
-        name: "syntax",
+        name: "buffer-last",
         completions: &[],
         func: |ctx| {
-            let glob = ctx.args.next()?;
             ctx.args.assert_empty()?;
-            match ctx.editor.syntaxes.set_current_from_glob(glob) {
-                Ok(()) => Ok(EditorControlFlow::Continue),
-                Err(error) => Err(CommandError::InvalidGlob(error)),
-            }
+            let client_handle = ctx.client_handle()?;
+            let buffer_handle = ctx
+                .clients
+                .get(client_handle)
+                .alternate_buffer_handle
+                .ok_or(CommandError::NoAlternateBuffer)?;
+            focus_buffer(ctx, client_handle, buffer_handle);
+            Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
-        name: "syntax-keywords",
-        completions: &[],
-        func: |ctx| syntax_pattern(ctx, TokenKind::Keyword),
-    },
-    BuiltinCommand {
-        name: "syntax-types",
-        completions: &[],
-        func: |ctx| syntax_pattern(ctx, TokenKind::Type),
-    },
-    BuiltinCommand {
-        name: "syntax-symbols",
-        completions: &[],
-        func: |ctx| syntax_pattern(ctx, TokenKind::Symbol),
-    },
-    BuiltinCommand {
-        name: "syntax-literals",
-        completions: &[],
-        func: |ctx| syntax_pattern(ctx, TokenKind::Literal),
-    },
-    BuiltinCommand {
-        name: "syntax-strings",
-        completions: &[],
-        func: |ctx| syntax_pattern(ctx, TokenKind::String),
-    },
-    BuiltinCommand {
-        name: "syntax-comments",
+        name: "buffer-next",
         completions: &[],
-        func: |ctx| syntax_pattern(ctx, TokenKind::Comment),
+        func: |ctx| move_to_buffer_in_open_order(ctx, true),
     },
     BuiltinCommand {
-        name: "syntax-texts",
+        name: "buffer-prev",
         completions: &[],
-        func: |ctx| syntax_pattern(ctx, TokenKind::Text),
+        func: |ctx| move_to_buffer_in_open_order(ctx, false),
     },
     BuiltinCommand {
-        name: "find-file",
+        name: "yank-history",
         completions: &[],
         func: |ctx| {
-            let command = ctx.args.next()?;
             ctx.args.assert_empty()?;
             if let Some(client_handle) = ctx.client_handle {
                 let mut ctx = ModeContext {
@@ -422,38 +560,1610 @@ pub static COMMANDS: &[BuiltinCommand] = &[
                     clients: ctx.clients,
                     client_handle,
                 };
-                picker::find_file::enter_mode(&mut ctx, command);
+                picker::yank_history::enter_mode(&mut ctx);
             }
             Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
-        name: "pid",
+        name: "log",
+        completions: &[CompletionSource::Custom(&[
+            "error", "warning", "info", "debug",
+        ])],
+        func: |ctx| {
+            let level = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let level = match level {
+                Some(level) => level.parse().map_err(|_| CommandError::InvalidLogLevel)?,
+                None => LogLevel::Debug,
+            };
+            let client_handle = ctx.client_handle()?;
+
+            let mut text = ctx.editor.string_pool.acquire();
+            for (entry_level, message) in ctx.platform.logger.entries() {
+                if entry_level <= level {
+                    text.push_str(entry_level.name());
+                    text.push_str(": ");
+                    text.push_str(message);
+                    text.push('\n');
+                }
+            }
+            show_text_in_buffer(
+                ctx.editor,
+                ctx.clients,
+                client_handle,
+                Path::new("editor.log"),
+                &text,
+            );
+            ctx.editor.string_pool.release(text);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "log-file",
+        completions: &[CompletionSource::Files],
+        func: |ctx| {
+            let path = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            match path {
+                Some(path) => ctx
+                    .platform
+                    .logger
+                    .set_file_path(path)
+                    .map_err(CommandError::FileOperationError)?,
+                None => {
+                    let path = ctx.platform.logger.file_path().unwrap_or("");
+                    ctx.editor.status_bar.write(MessageKind::Info).str(path);
+                }
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "profile",
         completions: &[],
         func: |ctx| {
+            let seconds = ctx.args.try_next();
             ctx.args.assert_empty()?;
+
+            let seconds = match seconds {
+                Some(seconds) => seconds.parse().map_err(|_| CommandError::InvalidDuration)?,
+                None => DEFAULT_PROFILE_SECONDS,
+            };
+            let client_handle = ctx.client_handle()?;
+            ctx.editor
+                .frame_profiler
+                .start(client_handle, Duration::from_secs(seconds));
             ctx.editor
                 .status_bar
                 .write(MessageKind::Info)
-                .fmt(format_args!("{}", std::process::id()));
+                .fmt(format_args!("profiling for {} seconds", seconds));
             Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
-        name: "lsp",
-        completions: &[],
+        name: "config",
+        completions: &[(CompletionSource::Custom(CONFIG_NAMES))],
         func: |ctx| {
-            let command = ctx.args.next()?;
-            let glob = ctx.args.next()?;
-            let log_path = ctx.args.try_next();
+            let key = ctx.args.next()?;
+            let value = ctx.args.try_next();
             ctx.args.assert_empty()?;
 
-            match ctx.editor.lsp.add_recipe(glob, command, None, log_path) {
-                Ok(()) => Ok(EditorControlFlow::Continue),
+            match value {
+                Some(value) => match ctx.editor.config.parse_config(key, value) {
+                    Ok(()) => Ok(EditorControlFlow::Continue),
+                    Err(error) => Err(CommandError::ConfigError(error)),
+                },
+                None => match ctx.editor.config.display_config(key) {
+                    Some(display) => {
+                        ctx.editor
+                            .status_bar
+                            .write(MessageKind::Info)
+                            .fmt(format_args!("{}", display));
+                        Ok(EditorControlFlow::Continue)
+                    }
+                    None => Err(CommandError::ConfigError(ParseConfigError::NoSuchConfig)),
+                },
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "soft-wrap",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let client_handle = ctx.client_handle()?;
+            let view_handle = ctx.current_buffer_view_handle()?;
+            let buffer_handle = ctx.editor.buffer_views.get(view_handle).buffer_handle;
+            let client = ctx.clients.get_mut(client_handle);
+            let soft_wrap = !ctx
+                .editor
+                .buffers
+                .get(buffer_handle)
+                .soft_wrap(client.display_config(ctx.editor));
+            client.config.soft_wrap = Some(soft_wrap);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "follow",
+        completions: &[],
+        func: |ctx| {
+            let (client_handle, leader_handle) = other_client_handle(ctx)?;
+            ctx.clients.get_mut(client_handle).following = Some(leader_handle);
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .str("following client. press <esc> to stop");
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "view-sync",
+        completions: &[],
+        func: |ctx| {
+            let (client_handle, other_handle) = other_client_handle(ctx)?;
+            NavigationHistory::save_snapshot(
+                ctx.clients.get_mut(client_handle),
+                &ctx.editor.buffer_views,
+            );
+            ctx.clients.copy_view(
+                other_handle,
+                client_handle,
+                &mut ctx.editor.buffer_views,
+                &mut ctx.editor.events,
+            );
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "view-swap",
+        completions: &[],
+        func: |ctx| {
+            let (client_handle, other_handle) = other_client_handle(ctx)?;
+            for handle in [client_handle, other_handle] {
+                NavigationHistory::save_snapshot(
+                    ctx.clients.get_mut(handle),
+                    &ctx.editor.buffer_views,
+                );
+            }
+            ctx.clients.swap_views(
+                client_handle,
+                other_handle,
+                &mut ctx.editor.buffer_views,
+                &mut ctx.editor.events,
+            );
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "client-config",
+        completions: &[(CompletionSource::Custom(CLIENT_CONFIG_NAMES))],
+        func: |ctx| {
+            let key = ctx.args.next()?;
+            let value = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            if !CLIENT_CONFIG_NAMES.contains(&key) {
+                return Err(CommandError::ConfigError(ParseConfigError::NoSuchConfig));
+            }
+
+            let client_handle = ctx.client_handle()?;
+            let client = ctx.clients.get_mut(client_handle);
+            match value {
+                Some(value) => match client.config.parse_config(key, value) {
+                    Ok(()) => Ok(EditorControlFlow::Continue),
+                    Err(error) => Err(CommandError::ConfigError(error)),
+                },
+                None => {
+                    let mut write = ctx.editor.status_bar.write(MessageKind::Info);
+                    match client.config.display_override(key) {
+                        Some(display) => write.fmt(format_args!("{}", display)),
+                        None => match ctx.editor.config.display_config(key) {
+                            Some(display) => write.fmt(format_args!("{}", display)),
+                            None => {
+                                return Err(CommandError::ConfigError(
+                                    ParseConfigError::NoSuchConfig,
+                                ))
+                            }
+                        },
+                    }
+                    Ok(EditorControlFlow::Continue)
+                }
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "color",
+        completions: &[CompletionSource::Custom(THEME_COLOR_NAMES)],
+        func: |ctx| {
+            let key = ctx.args.next()?;
+            let value = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let color = ctx
+                .editor
+                .theme
+                .color_from_name(key)
+                .ok_or(CommandError::NoSuchColor)?;
+
+            match value {
+                Some(value) => {
+                    let encoded =
+                        u32::from_str_radix(value, 16).map_err(|_| CommandError::NoSuchColor)?;
+                    *color = Color::from_u32(encoded);
+                }
+                None => ctx
+                    .editor
+                    .status_bar
+                    .write(MessageKind::Info)
+                    .fmt(format_args!("0x{:0<6x}", color.into_u32())),
+            }
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "sign-define",
+        completions: &[
+            CompletionSource::Custom(&[]),
+            CompletionSource::Custom(&[]),
+            CompletionSource::Custom(THEME_COLOR_NAMES),
+        ],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            let glyph = ctx.args.next()?;
+            let color = ctx.args.try_next().unwrap_or("gutter_sign");
+            ctx.args.assert_empty()?;
+
+            let mut chars = glyph.chars();
+            let glyph = match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(CommandError::InvalidSignGlyph),
+            };
+            if ctx.editor.theme.color(color).is_none() {
+                return Err(CommandError::NoSuchColor);
+            }
+
+            ctx.editor.signs.define(name, glyph, color);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "map",
+        completions: &[],
+        func: |ctx| {
+            let modes = parse_mode_flags(&mut ctx.args)?;
+            let from = ctx.args.next()?;
+            let to = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            for &mode in modes.iter().flatten() {
+                ctx.editor
+                    .keymaps
+                    .parse_and_map(mode, from, to, &ctx.editor.config.leader)
+                    .map_err(CommandError::KeyMapError)?;
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "unmap",
+        completions: &[],
+        func: |ctx| {
+            let modes = parse_mode_flags(&mut ctx.args)?;
+            let from = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            let mut removed = false;
+            for &mode in modes.iter().flatten() {
+                removed |= ctx
+                    .editor
+                    .keymaps
+                    .parse_and_unmap(mode, from, &ctx.editor.config.leader)
+                    .map_err(CommandError::KeyMapError)?;
+            }
+            if removed {
+                Ok(EditorControlFlow::Continue)
+            } else {
+                Err(CommandError::NoSuchKeyMap)
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "map-list",
+        completions: &[],
+        func: |ctx| {
+            let mut modes = [None; ModeKind::ALL.len()];
+            if ctx.args.peek().is_some() {
+                modes = parse_mode_flags(&mut ctx.args)?;
+            } else {
+                for (mode, &kind) in modes.iter_mut().zip(ModeKind::ALL.iter()) {
+                    *mode = Some(kind);
+                }
+            }
+            ctx.args.assert_empty()?;
+
+            let mut write = ctx.editor.status_bar.write(MessageKind::Info);
+            write.str("keymaps:");
+            for &mode in modes.iter().flatten() {
+                for (from, to) in ctx.editor.keymaps.iter(mode) {
+                    write.fmt(format_args!("\n{} ", mode.name()));
+                    for key in from {
+                        write.fmt(format_args!("{}", key));
+                    }
+                    write.str(" ");
+                    for key in to {
+                        write.fmt(format_args!("{}", key));
+                    }
+                }
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "keymap-list",
+        completions: &[],
+        func: |ctx| {
+            let mut modes = [None; ModeKind::ALL.len()];
+            if ctx.args.peek().is_some() {
+                modes = parse_mode_flags(&mut ctx.args)?;
+            } else {
+                for (mode, &kind) in modes.iter_mut().zip(ModeKind::ALL.iter()) {
+                    *mode = Some(kind);
+                }
+            }
+            ctx.args.assert_empty()?;
+            let client_handle = ctx.client_handle()?;
+
+            let mut text = ctx.editor.string_pool.acquire();
+            for &mode in modes.iter().flatten() {
+                let _ = writeln!(text, "# {}", mode.name());
+                for (from, to) in ctx.editor.keymaps.iter(mode) {
+                    let _ = write!(text, "map -{} ", mode.name());
+                    for key in from {
+                        let _ = write!(text, "{}", key);
+                    }
+                    text.push(' ');
+                    for key in to {
+                        let _ = write!(text, "{}", key);
+                    }
+                    text.push('\n');
+                }
+                text.push('\n');
+            }
+
+            if modes[ModeKind::Plugin as usize].is_some() {
+                for (name, mode) in ctx.editor.script.definitions("mode") {
+                    let _ = writeln!(text, "# plugin mode '{}'", name);
+                    for (key, handler) in mode.field("keys").fields() {
+                        match handler.as_str() {
+                            Some(command) => {
+                                let _ = writeln!(text, "# {} : {}", key, command);
+                            }
+                            None => {
+                                let _ = writeln!(text, "# {} : <{}>", key, handler.type_name());
+                            }
+                        }
+                    }
+                    text.push('\n');
+                }
+            }
+
+            show_text_in_buffer(
+                ctx.editor,
+                ctx.clients,
+                client_handle,
+                Path::new("keymaps.pp"),
+                &text,
+            );
+            ctx.editor.string_pool.release(text);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "map-normal",
+        completions: &[],
+        func: |ctx| map(ctx, ModeKind::Normal),
+    },
+    BuiltinCommand {
+        name: "map-insert",
+        completions: &[],
+        func: |ctx| map(ctx, ModeKind::Insert),
+    },
+    BuiltinCommand {
+        name: "map-command",
+        completions: &[],
+        func: |ctx| map(ctx, ModeKind::Command),
+    },
+    BuiltinCommand {
+        name: "map-readline",
+        completions: &[],
+        func: |ctx| map(ctx, ModeKind::ReadLine),
+    },
+    BuiltinCommand {
+        name: "map-picker",
+        completions: &[],
+        func: |ctx| map(ctx, ModeKind::Picker),
+    },
+    BuiltinCommand {
+        name: "map-plugin",
+        completions: &[],
+        func: |ctx| map(ctx, ModeKind::Plugin),
+    },
+    BuiltinCommand {
+        name: "alias",
+        completions: &[CompletionSource::Custom(&[]), CompletionSource::Commands],
+        func: |ctx| {
+            let from = ctx.args.next()?;
+            let to = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            ctx.editor.commands.aliases.add(from, to);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "macro",
+        completions: &[],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            let mut params = Vec::new();
+            while let Some(arg) = ctx.args.try_next() {
+                params.push(arg);
+            }
+            let body = match params.pop() {
+                Some(body) => body,
+                None => return Err(CommandError::TooFewArguments),
+            };
+            if ctx.editor.commands.find_command(name).is_some() {
+                return Err(CommandError::InvalidMacroName);
+            }
+            ctx.editor.commands.macros.add(name, &params, body)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "syntax",
+        completions: &[],
+        func: |ctx| {
+            let glob = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            match ctx.editor.syntaxes.set_current_from_glob(glob) {
+                Ok(()) => Ok(EditorControlFlow::Continue),
+                Err(error) => Err(CommandError::InvalidGlob(error)),
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "detect-syntax",
+        completions: &[],
+        func: |ctx| {
+            let detector = ctx.args.next()?;
+            let path = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            match ctx.editor.syntaxes.add_detection(detector, path) {
+                Ok(()) => Ok(EditorControlFlow::Continue),
+                Err(error) => Err(CommandError::InvalidGlob(error)),
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "syntax-keywords",
+        completions: &[],
+        func: |ctx| syntax_pattern(ctx, TokenKind::Keyword),
+    },
+    BuiltinCommand {
+        name: "syntax-types",
+        completions: &[],
+        func: |ctx| syntax_pattern(ctx, TokenKind::Type),
+    },
+    BuiltinCommand {
+        name: "syntax-symbols",
+        completions: &[],
+        func: |ctx| syntax_pattern(ctx, TokenKind::Symbol),
+    },
+    BuiltinCommand {
+        name: "syntax-literals",
+        completions: &[],
+        func: |ctx| syntax_pattern(ctx, TokenKind::Literal),
+    },
+    BuiltinCommand {
+        name: "syntax-strings",
+        completions: &[],
+        func: |ctx| syntax_pattern(ctx, TokenKind::String),
+    },
+    BuiltinCommand {
+        name: "syntax-comments",
+        completions: &[],
+        func: |ctx| syntax_pattern(ctx, TokenKind::Comment),
+    },
+    BuiltinCommand {
+        name: "syntax-texts",
+        completions: &[],
+        func: |ctx| syntax_pattern(ctx, TokenKind::Text),
+    },
+    BuiltinCommand {
+        name: "syntax-completion-triggers",
+        completions: &[],
+        func: |ctx| {
+            let triggers = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            ctx.editor
+                .syntaxes
+                .get_current()
+                .set_completion_triggers(triggers);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "find-file",
+        completions: &[],
+        func: |ctx| {
+            let command = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            if let Some(client_handle) = ctx.client_handle {
+                let mut ctx = ModeContext {
+                    editor: ctx.editor,
+                    platform: ctx.platform,
+                    clients: ctx.clients,
+                    client_handle,
+                };
+                picker::find_file::enter_mode(&mut ctx, command);
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "file-tree-toggle",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            if !ctx.editor.file_tree.is_visible() {
+                let root = ctx.editor.current_directory.clone();
+                ctx.editor.file_tree.show(&root);
+                return Ok(EditorControlFlow::Continue);
+            }
+
+            ctx.editor.file_tree.hide();
+            if let Some(client_handle) = ctx.client_handle {
+                if ctx.editor.mode.kind() == ModeKind::FileTree {
+                    let mut ctx = ModeContext {
+                        editor: ctx.editor,
+                        platform: ctx.platform,
+                        clients: ctx.clients,
+                        client_handle,
+                    };
+                    Mode::change_to(&mut ctx, ModeKind::default());
+                }
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "file-tree-focus",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let client_handle = ctx.client_handle()?;
+            let mut ctx = ModeContext {
+                editor: ctx.editor,
+                platform: ctx.platform,
+                clients: ctx.clients,
+                client_handle,
+            };
+            Mode::change_to(&mut ctx, ModeKind::FileTree);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "file-tree-reveal",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let client_handle = ctx.client_handle()?;
+            let buffer_handle = ctx.current_buffer_handle()?;
+            let path = ctx
+                .editor
+                .current_directory
+                .join(&ctx.editor.buffers.get(buffer_handle).path);
+
+            let mut ctx = ModeContext {
+                editor: ctx.editor,
+                platform: ctx.platform,
+                clients: ctx.clients,
+                client_handle,
+            };
+            Mode::change_to(&mut ctx, ModeKind::FileTree);
+            if !ctx.editor.file_tree.reveal(&path) {
+                ctx.editor
+                    .status_bar
+                    .write(MessageKind::Error)
+                    .fmt(format_args!("'{}' is not in the file tree", path.display()));
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "workspace-root",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let buffer_handle = ctx.current_buffer_handle().ok();
+            let root = ctx.editor.workspace_root(buffer_handle);
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("{}", root.display()));
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "workspace-list",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let mut write = ctx.editor.status_bar.write(MessageKind::Info);
+            for (i, root) in ctx.editor.workspace.roots().iter().enumerate() {
+                if i > 0 {
+                    write.str("\n");
+                }
+                write.fmt(format_args!("{}", root.display()));
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "workspace-add",
+        completions: &[CompletionSource::Files],
+        func: |ctx| {
+            let path = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            let path = ctx.editor.current_directory.join(path);
+            match path.canonicalize() {
+                Ok(path) if path.is_dir() => {
+                    ctx.editor.workspace.add_root(&path);
+                    Ok(EditorControlFlow::Continue)
+                }
+                _ => Err(CommandError::InvalidWorkspaceRoot),
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "workspace-remove",
+        completions: &[CompletionSource::Files],
+        func: |ctx| {
+            let path = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            let path = ctx.editor.current_directory.join(path);
+            let path = path.canonicalize().unwrap_or(path);
+            if ctx.editor.workspace.remove_root(&path) {
+                Ok(EditorControlFlow::Continue)
+            } else {
+                Err(CommandError::NoSuchWorkspaceRoot)
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "workspace-marker",
+        completions: &[],
+        func: |ctx| {
+            let glob = ctx.args.next()?;
+            let markers = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            match ctx.editor.workspace.add_markers(glob, markers) {
+                Ok(()) => Ok(EditorControlFlow::Continue),
+                Err(error) => Err(CommandError::InvalidGlob(error)),
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "file-rename",
+        completions: &[CompletionSource::Files],
+        func: |ctx| {
+            let (old_path, new_path) = file_operation_paths(ctx)?;
+            if new_path.exists() {
+                return Err(CommandError::FileAlreadyExists);
+            }
+            lsp::ClientManager::rename_file(ctx.editor, ctx.platform, old_path, new_path)
+                .map_err(CommandError::FileOperationError)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "file-delete",
+        completions: &[CompletionSource::Files],
+        func: |ctx| {
+            let mut path = ctx.args.try_next();
+            let recursive = path == Some("-recursive");
+            if recursive {
+                path = ctx.args.try_next();
+            }
+            ctx.args.assert_empty()?;
+
+            let path = match path {
+                Some(path) => Path::new(path),
+                None => {
+                    let handle = ctx.current_buffer_handle()?;
+                    &ctx.editor.buffers.get(handle).path
+                }
+            };
+            if path.as_os_str().is_empty() {
+                return Err(CommandError::DeleteCurrentDirectory);
+            }
+            let path = ctx.editor.current_directory.join(path);
+            let canonical_path = path.canonicalize().unwrap_or_else(|_| path.clone());
+            let current_directory = ctx.editor.current_directory.canonicalize();
+            let current_directory = current_directory
+                .as_ref()
+                .unwrap_or(&ctx.editor.current_directory);
+            if current_directory.starts_with(&canonical_path) {
+                return Err(CommandError::DeleteCurrentDirectory);
+            }
+            let is_dir = path.is_dir();
+            if is_dir && !recursive {
+                return Err(CommandError::DeleteDirectoryWithoutRecursive);
+            }
+
+            let mut handles = Vec::new();
+            for buffer in ctx.editor.buffers.iter() {
+                if !buffer.path.as_os_str().is_empty()
+                    && ctx
+                        .editor
+                        .current_directory
+                        .join(&buffer.path)
+                        .starts_with(&path)
+                {
+                    ctx.assert_can_discard_buffer(buffer.handle())?;
+                    handles.push(buffer.handle());
+                }
+            }
+
+            let result = if is_dir {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            };
+            result.map_err(CommandError::FileOperationError)?;
+
+            for handle in handles {
+                ctx.editor
+                    .buffers
+                    .defer_remove(handle, &mut ctx.editor.events);
+            }
+            if ctx.editor.file_tree.is_visible() {
+                ctx.editor.file_tree.refresh();
+            }
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "file-copy",
+        completions: &[CompletionSource::Files],
+        func: |ctx| {
+            let (old_path, new_path) = file_operation_paths(ctx)?;
+            if new_path.exists() {
+                return Err(CommandError::FileAlreadyExists);
+            }
+            if let Some(parent) = new_path.parent() {
+                fs::create_dir_all(parent).map_err(CommandError::FileOperationError)?;
+            }
+            fs::copy(&old_path, &new_path).map_err(CommandError::FileOperationError)?;
+            if ctx.editor.file_tree.is_visible() {
+                ctx.editor.file_tree.refresh();
+            }
+
+            if let Some(client_handle) = ctx.client_handle {
+                let path = new_path
+                    .strip_prefix(&ctx.editor.current_directory)
+                    .unwrap_or(&new_path);
+                let handle = ctx
+                    .editor
+                    .buffer_view_handle_from_path(client_handle, path, BufferCapabilities::text())
+                    .map_err(CommandError::BufferReadError)?;
+                let client = ctx.clients.get_mut(client_handle);
+                client.set_buffer_view_handle(
+                    Some(handle),
+                    &ctx.editor.buffer_views,
+                    &mut ctx.editor.events,
+                );
+            }
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "open-url",
+        completions: &[],
+        func: |ctx| {
+            let url = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let url = match url {
+                Some(url) => url,
+                None => {
+                    let buffer_view_handle = ctx.current_buffer_view_handle()?;
+                    let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+                    let position = buffer_view.cursors.main_cursor().position;
+                    let line = ctx
+                        .editor
+                        .buffers
+                        .get(buffer_view.buffer_handle)
+                        .content()
+                        .line_at(position.line_index as _)
+                        .as_str();
+                    url::find_url_at(line, position.column_byte_index as _)
+                        .ok_or(CommandError::NoUrlUnderCursor)?
+                }
+            };
+
+            ctx.platform
+                .open_url(&ctx.editor.config.open_url_command, url);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "make",
+        completions: &[],
+        func: |ctx| {
+            let command = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+            if ctx.editor.make.is_running() {
+                return Err(CommandError::MakeAlreadyRunning);
+            }
+
+            let mut command_buf = ctx.editor.string_pool.acquire();
+            command_buf.push_str(command.unwrap_or(&ctx.editor.config.make_command));
+            let buffer_handle = ctx.current_buffer_handle().ok();
+            Make::spawn(
+                ctx.editor,
+                ctx.platform,
+                ctx.client_handle,
+                buffer_handle,
+                &command_buf,
+            );
+            ctx.editor.string_pool.release(command_buf);
+
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .str("make started");
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "spawn",
+        completions: &[],
+        func: |ctx| {
+            let mut command = ctx.args.next()?;
+            let stderr_to_output = command == "-stderr-to-output";
+            if stderr_to_output {
+                command = ctx.args.next()?;
+            }
+            let on_output = ctx.args.try_next();
+            let on_error = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let options = SpawnOptions {
+                stderr_to_output,
+                on_output,
+                on_error,
+            };
+            if !SpawnCollection::spawn(
+                ctx.editor,
+                ctx.platform,
+                ctx.client_handle,
+                command,
+                options,
+            ) {
+                return Err(CommandError::InvalidProcessCommand);
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "make-next",
+        completions: &[],
+        func: |ctx| move_to_make_result(ctx, true),
+    },
+    BuiltinCommand {
+        name: "make-prev",
+        completions: &[],
+        func: |ctx| move_to_make_result(ctx, false),
+    },
+    BuiltinCommand {
+        name: "make-error-format",
+        completions: &[],
+        func: |ctx| {
+            let glob = ctx.args.next()?;
+            let format = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            match ctx.editor.make.add_error_format(glob, format) {
+                Ok(()) => Ok(EditorControlFlow::Continue),
+                Err(error) => Err(CommandError::InvalidGlob(error)),
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "linter",
+        completions: &[],
+        func: |ctx| {
+            let glob = ctx.args.next()?;
+            let command = ctx.args.next()?;
+            let format = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+            match ctx.editor.linters.add(glob, command, format) {
+                Ok(()) => Ok(EditorControlFlow::Continue),
+                Err(error) => Err(CommandError::InvalidGlob(error)),
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "lint",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let buffer_handle = ctx.current_buffer_handle()?;
+            if LinterCollection::lint(ctx.editor, ctx.platform, buffer_handle) {
+                Ok(EditorControlFlow::Continue)
+            } else {
+                Err(CommandError::NoLinter)
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "formatter",
+        completions: &[],
+        func: |ctx| {
+            let glob = ctx.args.next()?;
+            let command = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            match ctx.editor.formatters.add(glob, command) {
+                Ok(()) => Ok(EditorControlFlow::Continue),
+                Err(error) => Err(CommandError::InvalidGlob(error)),
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "format",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let buffer_handle = ctx.current_buffer_handle()?;
+            if FormatterCollection::format(ctx.editor, ctx.platform, buffer_handle, false) {
+                Ok(EditorControlFlow::Continue)
+            } else {
+                Err(CommandError::NoFormatter)
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "format-on-save",
+        completions: &[],
+        func: |ctx| {
+            let glob = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            match ctx.editor.formatters.add_format_on_save_glob(glob) {
+                Ok(()) => Ok(EditorControlFlow::Continue),
+                Err(error) => Err(CommandError::InvalidGlob(error)),
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "todo",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            enter_todo_picker(ctx, None);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "todo-workspace",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            let root = ctx.editor.workspace_root(ctx.current_buffer_handle().ok());
+            let mut ignore = IgnoreList::default();
+            if let Ok(gitignore) = fs::read_to_string(root.join(".gitignore")) {
+                let _ = ignore.add_lines(&gitignore);
+            }
+            for pattern in ctx.editor.config.find_file_ignore.split_whitespace() {
+                ignore.add(pattern).map_err(CommandError::InvalidGlob)?;
+            }
+            enter_todo_picker(ctx, Some((root, ignore)));
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "pid",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("{}", std::process::id()));
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "setenv",
+        completions: &[],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            let value = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            if !is_valid_env_var_name(name) {
+                return Err(CommandError::InvalidEnvironmentVariable);
+            }
+            match value {
+                Some(value) if !value.contains('\0') => std::env::set_var(name, value),
+                Some(_) => return Err(CommandError::InvalidEnvironmentVariable),
+                None => std::env::remove_var(name),
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "script",
+        completions: &[],
+        func: |ctx| {
+            let source = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            plugin::eval_script(
+                ctx.editor,
+                ctx.platform,
+                ctx.clients,
+                ctx.client_handle,
+                source,
+            )
+            .map_err(CommandError::ScriptError)
+        },
+    },
+    BuiltinCommand {
+        name: "plugin-mode",
+        completions: &[],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            let client_handle = ctx.client_handle()?;
+            let mut ctx = ModeContext {
+                editor: ctx.editor,
+                platform: ctx.platform,
+                clients: ctx.clients,
+                client_handle,
+            };
+            if plugin::enter_mode(&mut ctx, name) {
+                Ok(EditorControlFlow::Continue)
+            } else {
+                Err(CommandError::NoSuchMode)
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "panel-toggle",
+        completions: &[],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            if plugin::toggle_panel(ctx.editor, name) {
+                Ok(EditorControlFlow::Continue)
+            } else {
+                Err(CommandError::NoSuchPanel)
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "panel-focus",
+        completions: &[],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            let client_handle = ctx.client_handle()?;
+            let mode = match ctx.editor.panels.find(name) {
+                Some(panel) => ctx.editor.string_pool.acquire_with(&panel.mode),
+                None => return Err(CommandError::NoSuchPanel),
+            };
+            let mut ctx = ModeContext {
+                editor: ctx.editor,
+                platform: ctx.platform,
+                clients: ctx.clients,
+                client_handle,
+            };
+            let entered = plugin::enter_mode(&mut ctx, &mode);
+            ctx.editor.string_pool.release(mode);
+            if entered {
+                Ok(EditorControlFlow::Continue)
+            } else {
+                Err(CommandError::NoSuchMode)
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "plugin-start",
+        completions: &[],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            let command = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            let mut command = match parse_process_command(command) {
+                Some(command) => command,
+                None => return Err(CommandError::TooFewArguments),
+            };
+            if let Some(directory) = ctx.editor.plugin_manager.loading_directory() {
+                command.current_dir(directory);
+            }
+            ctx.editor.remote_plugins.start(ctx.platform, name, command);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "plugin-stop",
+        completions: &[],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            match ctx.editor.remote_plugins.find(name) {
+                Some(handle) => {
+                    ctx.editor.remote_plugins.stop(ctx.platform, handle);
+                    Ok(EditorControlFlow::Continue)
+                }
+                None => Err(CommandError::NoSuchPlugin),
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "plugin-list",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            let entries = manager::list(ctx.editor).map_err(CommandError::PluginManagerError)?;
+            let mut write = ctx.editor.status_bar.write(MessageKind::Info);
+            write.str("plugins:");
+            for entry in &entries {
+                let state = if !entry.enabled {
+                    "disabled"
+                } else if ctx.editor.plugin_manager.is_loaded(&entry.name) {
+                    "loaded"
+                } else {
+                    "enabled"
+                };
+                let version = if entry.version.is_empty() {
+                    "-"
+                } else {
+                    &entry.version
+                };
+                write.fmt(format_args!("\n{} {} {}", entry.name, version, state));
+            }
+            for plugin in ctx.editor.remote_plugins.iter() {
+                if !entries.iter().any(|e| e.name == plugin.name()) {
+                    write.fmt(format_args!("\n{} - running", plugin.name()));
+                }
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "plugin-enable",
+        completions: &[],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            manager::set_enabled(ctx.editor, ctx.platform, ctx.clients, name, true)
+                .map_err(CommandError::PluginManagerError)
+        },
+    },
+    BuiltinCommand {
+        name: "plugin-disable",
+        completions: &[],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            manager::set_enabled(ctx.editor, ctx.platform, ctx.clients, name, false)
+                .map_err(CommandError::PluginManagerError)
+        },
+    },
+    BuiltinCommand {
+        name: "plugin-install",
+        completions: &[CompletionSource::Files],
+        func: |ctx| {
+            let source = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            manager::install(ctx.editor, ctx.platform, ctx.clients, source)
+                .map_err(CommandError::PluginManagerError)
+        },
+    },
+    BuiltinCommand {
+        name: "plugin-command",
+        completions: &[],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            let command = ctx.args.next()?;
+
+            let handle = match ctx.editor.remote_plugins.find(name) {
+                Some(handle) => handle,
+                None => return Err(CommandError::NoSuchPlugin),
+            };
+            RemotePluginCollection::send_command(
+                ctx.editor,
+                ctx.platform,
+                handle,
+                command,
+                &mut ctx.args,
+            );
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "blame",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let client_handle = ctx.client_handle()?;
+            let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx)?;
+
+            let mut content = ctx.editor.string_pool.acquire();
+            let mut blame = ctx.editor.string_pool.acquire();
+            let mut blame_path = ctx.editor.string_pool.acquire();
+
+            let buffer = ctx.editor.buffers.get(buffer_handle);
+            let path = buffer.path.to_str().unwrap_or("");
+            blame_path.push_str(path);
+            blame_path.push_str(".blame");
+            let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+            buffer
+                .content()
+                .append_range_text_to_string(range, &mut content);
+
+            let result = git::run(
+                &ctx.editor.current_directory,
+                &["blame", "--date=short", "--contents", "-", "--", path],
+                Some(&content),
+                &mut blame,
+            );
+            ctx.editor.string_pool.release(content);
+
+            if let Err(error) = result {
+                ctx.editor.string_pool.release(blame);
+                ctx.editor.string_pool.release(blame_path);
+                return Err(CommandError::GitError(error));
+            }
+
+            let buffer_view_handle = show_text_in_buffer(
+                ctx.editor,
+                ctx.clients,
+                client_handle,
+                Path::new(&blame_path),
+                &blame,
+            );
+            ctx.editor.string_pool.release(blame);
+            ctx.editor.string_pool.release(blame_path);
+
+            if let Some(handle) = buffer_view_handle {
+                let position = BufferPosition::line_col(cursor.position.line_index, 0);
+                let mut cursors = ctx.editor.buffer_views.get_mut(handle).cursors.mut_guard();
+                cursors.clear();
+                cursors.add(Cursor {
+                    anchor: position,
+                    position,
+                });
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "blame-commit",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx)?;
+            let line_index = cursor.position.line_index as usize;
+
+            let buffer = ctx.editor.buffers.get(buffer_handle);
+            let mut blame = ctx.editor.string_pool.acquire();
+            if buffer
+                .path
+                .extension()
+                .map(|e| e == "blame")
+                .unwrap_or(false)
+            {
+                blame.push_str(buffer.content().line_at(line_index).as_str());
+            } else {
+                let mut content = ctx.editor.string_pool.acquire();
+                let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+                buffer
+                    .content()
+                    .append_range_text_to_string(range, &mut content);
+                let line_range = format!("{},{}", line_index + 1, line_index + 1);
+                let path = buffer.path.to_str().unwrap_or("");
+                let result = git::run(
+                    &ctx.editor.current_directory,
+                    &[
+                        "blame",
+                        "-s",
+                        "-L",
+                        &line_range,
+                        "--contents",
+                        "-",
+                        "--",
+                        path,
+                    ],
+                    Some(&content),
+                    &mut blame,
+                );
+                ctx.editor.string_pool.release(content);
+                if let Err(error) = result {
+                    ctx.editor.string_pool.release(blame);
+                    return Err(CommandError::GitError(error));
+                }
+            }
+
+            let mut commit = ctx.editor.string_pool.acquire();
+            let result = match git::blame_line_commit(&blame) {
+                Some(hash) if git::is_uncommitted(hash) => {
+                    commit.push_str("not committed yet");
+                    Ok(())
+                }
+                Some(hash) => git::run(
+                    &ctx.editor.current_directory,
+                    &["show", "--no-patch", "--date=short", hash],
+                    None,
+                    &mut commit,
+                ),
+                None => {
+                    commit.push_str("no commit found at line");
+                    Ok(())
+                }
+            };
+            ctx.editor.string_pool.release(blame);
+
+            let result = match result {
+                Ok(()) => {
+                    ctx.editor
+                        .status_bar
+                        .write(MessageKind::Info)
+                        .str(commit.trim_end());
+                    Ok(EditorControlFlow::Continue)
+                }
+                Err(error) => Err(CommandError::GitError(error)),
+            };
+            ctx.editor.string_pool.release(commit);
+            result
+        },
+    },
+    BuiltinCommand {
+        name: "git-status",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            if let Some(client_handle) = ctx.client_handle {
+                let mut ctx = ModeContext {
+                    editor: ctx.editor,
+                    platform: ctx.platform,
+                    clients: ctx.clients,
+                    client_handle,
+                };
+                picker::git_status::enter_mode(&mut ctx);
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "hunk-next",
+        completions: &[],
+        func: |ctx| move_to_hunk(ctx, true),
+    },
+    BuiltinCommand {
+        name: "hunk-prev",
+        completions: &[],
+        func: |ctx| move_to_hunk(ctx, false),
+    },
+    BuiltinCommand {
+        name: "hunk-revert",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx)?;
+            let line_index = cursor.position.line_index as usize;
+
+            let mut index_text = ctx.editor.string_pool.acquire();
+            let mut hunks = Vec::new();
+            if let Err(error) =
+                compute_git_hunks(ctx.editor, buffer_handle, &mut index_text, &mut hunks)
+            {
+                ctx.editor.string_pool.release(index_text);
+                return Err(error);
+            }
+
+            let hunk = hunks.into_iter().find(|h| {
+                h.new_lines.contains(&line_index)
+                    || (h.new_lines.is_empty() && h.new_lines.start == line_index)
+            });
+            let hunk = match hunk {
+                Some(hunk) => hunk,
+                None => {
+                    ctx.editor.string_pool.release(index_text);
+                    ctx.editor
+                        .status_bar
+                        .write(MessageKind::Error)
+                        .str("no hunk at cursor");
+                    return Ok(EditorControlFlow::Continue);
+                }
+            };
+
+            let mut text = ctx.editor.string_pool.acquire();
+            for line in index_text
+                .lines()
+                .skip(hunk.old_lines.start)
+                .take(hunk.old_lines.len())
+            {
+                text.push_str(line);
+                text.push('\n');
+            }
+            ctx.editor.string_pool.release(index_text);
+
+            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            let from = BufferPosition::line_col(hunk.new_lines.start as _, 0);
+            let to = if hunk.new_lines.end < buffer.content().line_count() {
+                BufferPosition::line_col(hunk.new_lines.end as _, 0)
+            } else {
+                buffer.content().end()
+            };
+
+            buffer.commit_edits();
+            buffer.delete_range(
+                &mut ctx.editor.word_database,
+                BufferRange::between(from, to),
+                &mut ctx.editor.events,
+            );
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                from,
+                &text,
+                &mut ctx.editor.events,
+            );
+            buffer.commit_edits();
+            ctx.editor.string_pool.release(text);
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "conflict-next",
+        completions: &[],
+        func: |ctx| move_to_conflict(ctx, true),
+    },
+    BuiltinCommand {
+        name: "conflict-prev",
+        completions: &[],
+        func: |ctx| move_to_conflict(ctx, false),
+    },
+    BuiltinCommand {
+        name: "conflict-ours",
+        completions: &[],
+        func: |ctx| resolve_conflict(ctx, true, false),
+    },
+    BuiltinCommand {
+        name: "conflict-theirs",
+        completions: &[],
+        func: |ctx| resolve_conflict(ctx, false, true),
+    },
+    BuiltinCommand {
+        name: "conflict-both",
+        completions: &[],
+        func: |ctx| resolve_conflict(ctx, true, true),
+    },
+    BuiltinCommand {
+        name: "diff-buffers",
+        completions: &[CompletionSource::Buffers, CompletionSource::Buffers],
+        func: |ctx| {
+            let old_path = ctx.args.next()?;
+            let new_path = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            let client_handle = ctx.client_handle()?;
+
+            let mut old_text = ctx.editor.string_pool.acquire();
+            let mut new_text = ctx.editor.string_pool.acquire();
+            let result = read_buffer_or_file(ctx.editor, Path::new(old_path), &mut old_text)
+                .and_then(|_| read_buffer_or_file(ctx.editor, Path::new(new_path), &mut new_text));
+            if let Err(error) = result {
+                ctx.editor.string_pool.release(old_text);
+                ctx.editor.string_pool.release(new_text);
+                return Err(CommandError::BufferReadError(error));
+            }
+
+            let old_lines: Vec<_> = old_text.lines().collect();
+            let new_lines: Vec<_> = new_text.lines().collect();
+            let mut hunks = Vec::new();
+            git::diff_lines(&old_lines, &new_lines, &mut hunks);
+
+            let mut diff = ctx.editor.string_pool.acquire();
+            diff.push_str("--- ");
+            diff.push_str(old_path);
+            diff.push_str("\n+++ ");
+            diff.push_str(new_path);
+            diff.push('\n');
+            git::write_unified_diff(&old_lines, &new_lines, &hunks, 3, &mut diff);
+
+            let mut diff_path = ctx.editor.string_pool.acquire_with(new_path);
+            diff_path.push_str(".diff");
+            show_text_in_buffer(
+                ctx.editor,
+                ctx.clients,
+                client_handle,
+                Path::new(&diff_path),
+                &diff,
+            );
+
+            ctx.editor.string_pool.release(diff_path);
+            ctx.editor.string_pool.release(diff);
+            ctx.editor.string_pool.release(old_text);
+            ctx.editor.string_pool.release(new_text);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "lsp",
+        completions: &[],
+        func: |ctx| {
+            let command = ctx.args.next()?;
+            let glob = ctx.args.next()?;
+            let log_path = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            match ctx.editor.lsp.add_recipe(glob, command, None, log_path) {
+                Ok(()) => Ok(EditorControlFlow::Continue),
                 Err(error) => Err(CommandError::InvalidGlob(error)),
             }
         },
     },
+    BuiltinCommand {
+        name: "lsp-initialization-options",
+        completions: &[],
+        func: |ctx| {
+            let glob = ctx.args.next()?;
+            let options = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            match ctx
+                .editor
+                .lsp
+                .set_recipe_initialization_options(glob, options)
+            {
+                Ok(()) => Ok(EditorControlFlow::Continue),
+                Err(error) => Err(CommandError::LspConfigError(error)),
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "lsp-settings",
+        completions: &[],
+        func: |ctx| {
+            let glob = ctx.args.next()?;
+            let settings = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            match ctx
+                .editor
+                .lsp
+                .set_recipe_settings(ctx.platform, glob, settings)
+            {
+                Ok(()) => Ok(EditorControlFlow::Continue),
+                Err(error) => Err(CommandError::LspConfigError(error)),
+            }
+        },
+    },
     BuiltinCommand {
         name: "lsp-open-log",
         completions: &[],
@@ -492,6 +2202,22 @@ pub static COMMANDS: &[BuiltinCommand] = &[
             Ok(EditorControlFlow::Continue)
         },
     },
+    BuiltinCommand {
+        name: "lsp-log",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let client_handle = ctx.client_handle()?;
+            let buffer_handle = ctx.current_buffer_handle()?;
+            access_lsp(ctx, buffer_handle, |editor, _, clients, client| {
+                let mut text = editor.string_pool.acquire();
+                client.write_log(&mut text);
+                show_text_in_buffer(editor, clients, client_handle, Path::new("lsp.log"), &text);
+                editor.string_pool.release(text);
+            })?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
     BuiltinCommand {
         name: "lsp-stop",
         completions: &[],
@@ -564,6 +2290,25 @@ pub static COMMANDS: &[BuiltinCommand] = &[
             Ok(EditorControlFlow::Continue)
         },
     },
+    BuiltinCommand {
+        name: "lsp-type-definition",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let client_handle = ctx.client_handle()?;
+            let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx)?;
+            access_lsp(ctx, buffer_handle, |editor, platform, _, client| {
+                client.type_definition(
+                    editor,
+                    platform,
+                    buffer_handle,
+                    cursor.position,
+                    client_handle,
+                )
+            })?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
     BuiltinCommand {
         name: "lsp-implementation",
         completions: &[],
@@ -694,19 +2439,304 @@ pub static COMMANDS: &[BuiltinCommand] = &[
             Ok(EditorControlFlow::Continue)
         },
     },
+    BuiltinCommand {
+        name: "lsp-format-range",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx)?;
+            access_lsp(ctx, buffer_handle, |editor, platform, _, client| {
+                client.range_formatting(editor, platform, buffer_handle, cursor.to_range())
+            })?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "lsp-format-on-save",
+        completions: &[],
+        func: |ctx| {
+            let glob = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            match ctx.editor.lsp.add_format_on_save_glob(glob) {
+                Ok(()) => Ok(EditorControlFlow::Continue),
+                Err(error) => Err(CommandError::InvalidGlob(error)),
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "lsp-inlay-hints",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            let enabled = !ctx.editor.config.lsp_inlay_hints;
+            ctx.editor.config.lsp_inlay_hints = enabled;
+            if !enabled {
+                for client in ctx.editor.lsp.clients_mut() {
+                    client.clear_inlay_hints();
+                }
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "dap-start",
+        completions: &[],
+        func: |ctx| {
+            let mut command = ctx.args.next()?;
+            let attach = command == "-attach";
+            if attach {
+                command = ctx.args.next()?;
+            }
+            let arguments = ctx.args.try_next().unwrap_or("{}");
+            ctx.args.assert_empty()?;
+
+            let buffer_handle = ctx.current_buffer_handle().ok();
+            Debugger::start(
+                ctx.editor,
+                ctx.platform,
+                buffer_handle,
+                command,
+                attach,
+                arguments,
+            )
+            .map_err(CommandError::DebuggerError)?;
+
+            if let Some(client_handle) = ctx.client_handle {
+                let mut ctx = ModeContext {
+                    editor: ctx.editor,
+                    platform: ctx.platform,
+                    clients: ctx.clients,
+                    client_handle,
+                };
+                Mode::change_to(&mut ctx, ModeKind::Debug);
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "dap-mode",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let client_handle = ctx.client_handle()?;
+            if !ctx.editor.debugger.is_running() {
+                return Err(CommandError::DebuggerError(DebuggerError::NoSession));
+            }
+            let mut ctx = ModeContext {
+                editor: ctx.editor,
+                platform: ctx.platform,
+                clients: ctx.clients,
+                client_handle,
+            };
+            Mode::change_to(&mut ctx, ModeKind::Debug);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "dap-continue",
+        completions: &[],
+        func: |ctx| dap_step(ctx, StepKind::Continue),
+    },
+    BuiltinCommand {
+        name: "dap-next",
+        completions: &[],
+        func: |ctx| dap_step(ctx, StepKind::Next),
+    },
+    BuiltinCommand {
+        name: "dap-step-in",
+        completions: &[],
+        func: |ctx| dap_step(ctx, StepKind::StepIn),
+    },
+    BuiltinCommand {
+        name: "dap-step-out",
+        completions: &[],
+        func: |ctx| dap_step(ctx, StepKind::StepOut),
+    },
+    BuiltinCommand {
+        name: "dap-frame",
+        completions: &[],
+        func: |ctx| {
+            let index = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let index = match index {
+                Some(index) => index
+                    .parse()
+                    .map_err(|_| CommandError::DebuggerError(DebuggerError::NoSuchFrame))?,
+                None => {
+                    let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx)?;
+                    let buffer_path = &ctx.editor.buffers.get(buffer_handle).path;
+                    if buffer_path != Path::new(dap::STACK_BUFFER_PATH) {
+                        return Err(CommandError::DebuggerError(DebuggerError::NoSuchFrame));
+                    }
+                    cursor.position.line_index as _
+                }
+            };
+            Debugger::select_frame(ctx.editor, ctx.platform, ctx.clients, index)
+                .map_err(CommandError::DebuggerError)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "dap-stop",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            Debugger::stop(ctx.editor, ctx.platform).map_err(CommandError::DebuggerError)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "dap-toggle-breakpoint",
+        completions: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx)?;
+            Debugger::toggle_breakpoint(
+                ctx.editor,
+                ctx.platform,
+                buffer_handle,
+                cursor.position.line_index as _,
+            );
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "dap-watch",
+        completions: &[],
+        func: |ctx| {
+            let expression = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            Debugger::add_watch(ctx.editor, ctx.platform, expression);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "dap-unwatch",
+        completions: &[],
+        func: |ctx| {
+            let expression = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            if !Debugger::remove_watch(ctx.editor, expression) {
+                return Err(CommandError::DebuggerError(DebuggerError::NoSuchWatch));
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "dap-expand",
+        completions: &[],
+        func: |ctx| {
+            let path = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let mut path_buf = ctx.editor.string_pool.acquire();
+            match path {
+                Some(path) => path_buf.push_str(path),
+                None => {
+                    let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx)?;
+                    let buffer_path = &ctx.editor.buffers.get(buffer_handle).path;
+                    if buffer_path == Path::new(dap::VARIABLES_BUFFER_PATH) {
+                        let index = cursor.position.line_index as usize;
+                        if let Some(path) = ctx.editor.debugger.variable_path_at(index) {
+                            path_buf.push_str(path);
+                        }
+                    }
+                }
+            }
+            if path_buf.is_empty() {
+                ctx.editor.string_pool.release(path_buf);
+                return Err(CommandError::DebuggerError(DebuggerError::NoSuchVariable));
+            }
+
+            Debugger::toggle_variable(ctx.editor, ctx.platform, &path_buf);
+            ctx.editor.string_pool.release(path_buf);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
 ];
 
+fn save_current_buffer(ctx: &mut CommandContext, path: Option<&Path>) -> Result<(), CommandError> {
+    let buffer_handle = ctx.current_buffer_handle()?;
+    let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+
+    buffer
+        .write_to_file(path, &mut ctx.editor.events)
+        .map_err(CommandError::BufferWriteError)?;
+
+    ctx.editor
+        .status_bar
+        .write(MessageKind::Info)
+        .fmt(format_args!("buffer saved to {:?}", &buffer.path));
+    Ok(())
+}
+
+fn save_all_buffers(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let mut count = 0;
+    for buffer in ctx.editor.buffers.iter_mut() {
+        if buffer.capabilities.can_save {
+            buffer
+                .write_to_file(None, &mut ctx.editor.events)
+                .map_err(CommandError::BufferWriteError)?;
+            count += 1;
+        }
+    }
+
+    ctx.editor
+        .status_bar
+        .write(MessageKind::Info)
+        .fmt(format_args!("{} buffers saved", count));
+    Ok(())
+}
+
+fn enter_todo_picker(ctx: &mut CommandContext, workspace: Option<(PathBuf, IgnoreList)>) {
+    if let Some(client_handle) = ctx.client_handle {
+        let mut ctx = ModeContext {
+            editor: ctx.editor,
+            platform: ctx.platform,
+            clients: ctx.clients,
+            client_handle,
+        };
+        picker::todo::enter_mode(&mut ctx, workspace);
+    }
+}
+
 fn map(ctx: &mut CommandContext, mode: ModeKind) -> Result<EditorControlFlow, CommandError> {
     let from = ctx.args.next()?;
     let to = ctx.args.next()?;
     ctx.args.assert_empty()?;
 
-    match ctx.editor.keymaps.parse_and_map(mode, from, to) {
+    let leader = &ctx.editor.config.leader;
+    match ctx.editor.keymaps.parse_and_map(mode, from, to, leader) {
         Ok(()) => Ok(EditorControlFlow::Continue),
         Err(error) => Err(CommandError::KeyMapError(error)),
     }
 }
 
+fn parse_mode_flags(
+    args: &mut CommandArgs,
+) -> Result<[Option<ModeKind>; ModeKind::ALL.len()], CommandError> {
+    let mut modes = [None; ModeKind::ALL.len()];
+    let mut has_mode = false;
+    while let Some(flag) = args.peek() {
+        let kind = match flag.strip_prefix('-').and_then(ModeKind::from_name) {
+            Some(kind) => kind,
+            None => break,
+        };
+        args.try_next();
+        modes[kind as usize] = Some(kind);
+        has_mode = true;
+    }
+
+    if has_mode {
+        Ok(modes)
+    } else {
+        Err(CommandError::NoSuchMode)
+    }
+}
+
 fn syntax_pattern(
     ctx: &mut CommandContext,
     token_kind: TokenKind,
@@ -724,6 +2754,12 @@ fn syntax_pattern(
     }
 }
 
+fn dap_step(ctx: &mut CommandContext, kind: StepKind) -> Result<EditorControlFlow, CommandError> {
+    ctx.args.assert_empty()?;
+    Debugger::step(ctx.editor, ctx.platform, kind).map_err(CommandError::DebuggerError)?;
+    Ok(EditorControlFlow::Continue)
+}
+
 fn current_buffer_and_main_cursor(
     ctx: &CommandContext,
 ) -> Result<(BufferHandle, Cursor), CommandError> {
@@ -735,6 +2771,222 @@ fn current_buffer_and_main_cursor(
     Ok((buffer_handle, cursor))
 }
 
+fn compute_git_hunks(
+    editor: &mut Editor,
+    buffer_handle: BufferHandle,
+    index_text: &mut String,
+    hunks: &mut Vec<git::Hunk>,
+) -> Result<(), CommandError> {
+    let buffer = editor.buffers.get(buffer_handle);
+    git::show_index_file(&editor.current_directory, &buffer.path, index_text)
+        .map_err(CommandError::GitError)?;
+
+    let mut text = editor.string_pool.acquire();
+    let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+    buffer
+        .content()
+        .append_range_text_to_string(range, &mut text);
+
+    let old_lines: Vec<_> = index_text.lines().collect();
+    let new_lines: Vec<_> = text.lines().collect();
+    git::diff_lines(&old_lines, &new_lines, hunks);
+
+    editor.string_pool.release(text);
+    Ok(())
+}
+
+fn move_to_hunk(
+    ctx: &mut CommandContext,
+    forward: bool,
+) -> Result<EditorControlFlow, CommandError> {
+    ctx.args.assert_empty()?;
+    let view_handle = ctx.current_buffer_view_handle()?;
+    let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx)?;
+    let line_index = cursor.position.line_index as usize;
+
+    let mut index_text = ctx.editor.string_pool.acquire();
+    let mut hunks = Vec::new();
+    let result = compute_git_hunks(ctx.editor, buffer_handle, &mut index_text, &mut hunks);
+    ctx.editor.string_pool.release(index_text);
+    result?;
+
+    let hunk = if forward {
+        hunks.iter().find(|h| h.new_lines.start > line_index)
+    } else {
+        hunks.iter().rev().find(|h| h.new_lines.start < line_index)
+    };
+    let line_index = match hunk {
+        Some(hunk) => hunk.new_lines.start,
+        None => {
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Error)
+                .str("no more hunks");
+            return Ok(EditorControlFlow::Continue);
+        }
+    };
+
+    NavigationHistory::save_snapshot(
+        ctx.clients.get_mut(ctx.client_handle()?),
+        &ctx.editor.buffer_views,
+    );
+
+    let last_line_index = ctx.editor.buffers.get(buffer_handle).content().line_count() - 1;
+    let position = BufferPosition::line_col(line_index.min(last_line_index) as _, 0);
+    let mut cursors = ctx
+        .editor
+        .buffer_views
+        .get_mut(view_handle)
+        .cursors
+        .mut_guard();
+    cursors.clear();
+    cursors.add(Cursor {
+        anchor: position,
+        position,
+    });
+    Ok(EditorControlFlow::Continue)
+}
+
+fn move_to_conflict(
+    ctx: &mut CommandContext,
+    forward: bool,
+) -> Result<EditorControlFlow, CommandError> {
+    ctx.args.assert_empty()?;
+    let view_handle = ctx.current_buffer_view_handle()?;
+    let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx)?;
+    let line_index = cursor.position.line_index as usize;
+
+    let is_conflict_start = |(_, line): &(usize, &BufferLine)| {
+        git::ConflictMarker::parse(line.as_str()) == Some(git::ConflictMarker::Ours)
+    };
+    let lines = ctx
+        .editor
+        .buffers
+        .get(buffer_handle)
+        .content()
+        .lines()
+        .enumerate();
+    let conflict_line_index = if forward {
+        lines.skip(line_index + 1).find(is_conflict_start)
+    } else {
+        lines.take(line_index).rev().find(is_conflict_start)
+    };
+    let conflict_line_index = match conflict_line_index {
+        Some((i, _)) => i,
+        None => {
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Error)
+                .str("no more conflicts");
+            return Ok(EditorControlFlow::Continue);
+        }
+    };
+
+    NavigationHistory::save_snapshot(
+        ctx.clients.get_mut(ctx.client_handle()?),
+        &ctx.editor.buffer_views,
+    );
+
+    let position = BufferPosition::line_col(conflict_line_index as _, 0);
+    let mut cursors = ctx
+        .editor
+        .buffer_views
+        .get_mut(view_handle)
+        .cursors
+        .mut_guard();
+    cursors.clear();
+    cursors.add(Cursor {
+        anchor: position,
+        position,
+    });
+    Ok(EditorControlFlow::Continue)
+}
+
+fn resolve_conflict(
+    ctx: &mut CommandContext,
+    keep_ours: bool,
+    keep_theirs: bool,
+) -> Result<EditorControlFlow, CommandError> {
+    ctx.args.assert_empty()?;
+    let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx)?;
+    let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+
+    let lines: Vec<_> = buffer.content().lines().map(|l| l.as_str()).collect();
+    let conflict = match git::find_conflict_at(&lines, cursor.position.line_index as _) {
+        Some(conflict) => conflict,
+        None => {
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Error)
+                .str("no conflict at cursor");
+            return Ok(EditorControlFlow::Continue);
+        }
+    };
+
+    let mut text = ctx.editor.string_pool.acquire();
+    if keep_ours {
+        for line in &lines[conflict.ours.clone()] {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+    if keep_theirs {
+        for line in &lines[conflict.theirs.clone()] {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+
+    let from = BufferPosition::line_col(conflict.start as _, 0);
+    let to = if conflict.end + 1 < lines.len() {
+        BufferPosition::line_col((conflict.end + 1) as _, 0)
+    } else {
+        if text.ends_with('\n') {
+            text.pop();
+        }
+        buffer.content().end()
+    };
+
+    buffer.commit_edits();
+    buffer.delete_range(
+        &mut ctx.editor.word_database,
+        BufferRange::between(from, to),
+        &mut ctx.editor.events,
+    );
+    buffer.insert_text(
+        &mut ctx.editor.word_database,
+        from,
+        &text,
+        &mut ctx.editor.events,
+    );
+    buffer.commit_edits();
+    ctx.editor.string_pool.release(text);
+
+    Ok(EditorControlFlow::Continue)
+}
+
+fn read_buffer_or_file(
+    editor: &Editor,
+    path: &Path,
+    text: &mut String,
+) -> Result<(), BufferReadError> {
+    match editor
+        .buffers
+        .find_with_path(&editor.current_directory, path)
+    {
+        Some(handle) => {
+            let content = editor.buffers.get(handle).content();
+            let range = BufferRange::between(BufferPosition::zero(), content.end());
+            content.append_range_text_to_string(range, text);
+        }
+        None => {
+            let mut file = File::open(editor.current_directory.join(path))?;
+            file.read_to_string(text)?;
+        }
+    }
+    Ok(())
+}
+
 fn find_lsp_client_for_buffer(
     editor: &Editor,
     buffer_handle: BufferHandle,
@@ -762,3 +3014,99 @@ where
         None => Err(CommandError::LspServerNotRunning),
     }
 }
+
+fn file_operation_paths(ctx: &mut CommandContext) -> Result<(PathBuf, PathBuf), CommandError> {
+    let path = ctx.args.next()?;
+    let (old_path, new_path) = match ctx.args.try_next() {
+        Some(new_path) => (ctx.editor.current_directory.join(path), new_path),
+        None => {
+            let handle = ctx.current_buffer_handle()?;
+            let old_path = &ctx.editor.buffers.get(handle).path;
+            (ctx.editor.current_directory.join(old_path), path)
+        }
+    };
+    ctx.args.assert_empty()?;
+
+    let new_path = ctx.editor.current_directory.join(new_path);
+    Ok((old_path, new_path))
+}
+
+fn other_client_handle(
+    ctx: &mut CommandContext,
+) -> Result<(ClientHandle, ClientHandle), CommandError> {
+    let other_handle = ctx.args.try_next();
+    ctx.args.assert_empty()?;
+
+    let client_handle = ctx.client_handle()?;
+    let other_handle = match other_handle {
+        Some(handle) => handle.parse().map_err(|_| CommandError::NoSuchClient)?,
+        None => match ctx.clients.previous_focused_client() {
+            Some(handle) => handle,
+            None => return Err(CommandError::NoSuchClient),
+        },
+    };
+
+    if other_handle == client_handle || ctx.clients.try_get(other_handle).is_none() {
+        return Err(CommandError::NoSuchClient);
+    }
+    Ok((client_handle, other_handle))
+}
+
+fn focus_buffer(
+    ctx: &mut CommandContext,
+    client_handle: ClientHandle,
+    buffer_handle: BufferHandle,
+) {
+    let buffer_view_handle = ctx
+        .editor
+        .buffer_views
+        .buffer_view_handle_from_buffer_handle(client_handle, buffer_handle);
+    ctx.clients.get_mut(client_handle).set_buffer_view_handle(
+        Some(buffer_view_handle),
+        &ctx.editor.buffer_views,
+        &mut ctx.editor.events,
+    );
+}
+
+fn move_to_buffer_in_open_order(
+    ctx: &mut CommandContext,
+    forward: bool,
+) -> Result<EditorControlFlow, CommandError> {
+    ctx.args.assert_empty()?;
+    let client_handle = ctx.client_handle()?;
+    let current_buffer_handle = ctx
+        .clients
+        .get(client_handle)
+        .buffer_view_handle()
+        .map(|h| ctx.editor.buffer_views.get(h).buffer_handle);
+
+    let handles: Vec<_> = ctx.editor.buffers.iter().map(|b| b.handle()).collect();
+    let len = handles.len();
+    if len == 0 {
+        return Err(CommandError::NoBufferOpened);
+    }
+
+    let index = match current_buffer_handle.and_then(|h| handles.iter().position(|&b| b == h)) {
+        Some(i) if forward => (i + 1) % len,
+        Some(i) => (i + len - 1) % len,
+        None if forward => 0,
+        None => len - 1,
+    };
+    focus_buffer(ctx, client_handle, handles[index]);
+    Ok(EditorControlFlow::Continue)
+}
+
+fn move_to_make_result(
+    ctx: &mut CommandContext,
+    forward: bool,
+) -> Result<EditorControlFlow, CommandError> {
+    ctx.args.assert_empty()?;
+    let client_handle = ctx.client_handle()?;
+    let index = ctx
+        .editor
+        .make
+        .next_result_index(forward)
+        .ok_or(CommandError::NoMakeResults)?;
+    Make::jump_to_result(ctx.editor, ctx.clients, client_handle, index);
+    Ok(EditorControlFlow::Continue)
+}
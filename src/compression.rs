@@ -0,0 +1,221 @@
+use std::{convert::TryInto, fmt};
+
+const MIN_MATCH_LEN: usize = 4;
+const MAX_OFFSET: usize = u16::MAX as _;
+const HASH_BITS: u32 = 12;
+const RUN_MASK: usize = 0xf;
+const LEN_HEADER_SIZE: usize = std::mem::size_of::<u32>();
+pub const MAX_DECOMPRESSED_LEN: usize = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub struct InvalidCompressedDataError;
+impl fmt::Display for InvalidCompressedDataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid compressed data")
+    }
+}
+
+fn write_length(output: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        output.push(255);
+        len -= 255;
+    }
+    output.push(len as _);
+}
+
+fn read_length(input: &[u8], index: &mut usize) -> Result<usize, InvalidCompressedDataError> {
+    let mut len = 0;
+    loop {
+        let byte = *input.get(*index).ok_or(InvalidCompressedDataError)?;
+        *index += 1;
+        len += byte as usize;
+        if byte != 255 {
+            return Ok(len);
+        }
+    }
+}
+
+fn write_sequence(output: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let literal_token = literals.len().min(RUN_MASK);
+    let match_token = match matched {
+        Some((_, len)) => (len - MIN_MATCH_LEN).min(RUN_MASK),
+        None => 0,
+    };
+    output.push(((literal_token << 4) | match_token) as _);
+
+    if literal_token == RUN_MASK {
+        write_length(output, literals.len() - RUN_MASK);
+    }
+    output.extend_from_slice(literals);
+
+    if let Some((offset, len)) = matched {
+        output.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_token == RUN_MASK {
+            write_length(output, len - MIN_MATCH_LEN - RUN_MASK);
+        }
+    }
+}
+
+pub fn compress(input: &[u8], output: &mut Vec<u8>) {
+    output.extend_from_slice(&(input.len() as u32).to_le_bytes());
+
+    let mut table = [0u32; 1 << HASH_BITS];
+    let mut literal_start = 0;
+    let mut i = 0;
+
+    while i + MIN_MATCH_LEN <= input.len() {
+        let sequence = u32::from_le_bytes(input[i..i + MIN_MATCH_LEN].try_into().unwrap());
+        let hash = (sequence.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize;
+        let candidate = table[hash] as usize;
+        table[hash] = (i + 1) as _;
+
+        if candidate > 0 {
+            let candidate = candidate - 1;
+            let offset = i - candidate;
+            if offset <= MAX_OFFSET
+                && input[candidate..candidate + MIN_MATCH_LEN] == input[i..i + MIN_MATCH_LEN]
+            {
+                let mut len = MIN_MATCH_LEN;
+                while i + len < input.len() && input[candidate + len] == input[i + len] {
+                    len += 1;
+                }
+
+                write_sequence(output, &input[literal_start..i], Some((offset, len)));
+                i += len;
+                literal_start = i;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    write_sequence(output, &input[literal_start..], None);
+}
+
+pub fn decompress(input: &[u8], output: &mut Vec<u8>) -> Result<(), InvalidCompressedDataError> {
+    let len = input
+        .get(..LEN_HEADER_SIZE)
+        .ok_or(InvalidCompressedDataError)?;
+    let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+    if len > MAX_DECOMPRESSED_LEN {
+        return Err(InvalidCompressedDataError);
+    }
+    let output_start = output.len();
+    let output_end = output_start + len;
+
+    let mut i = LEN_HEADER_SIZE;
+    while i < input.len() {
+        let token = input[i] as usize;
+        i += 1;
+
+        let mut literal_len = token >> 4;
+        if literal_len == RUN_MASK {
+            literal_len += read_length(input, &mut i)?;
+        }
+        let literals = input
+            .get(i..i + literal_len)
+            .ok_or(InvalidCompressedDataError)?;
+        if output.len() + literal_len > output_end {
+            return Err(InvalidCompressedDataError);
+        }
+        output.extend_from_slice(literals);
+        i += literal_len;
+
+        if i == input.len() {
+            break;
+        }
+
+        let offset = input.get(i..i + 2).ok_or(InvalidCompressedDataError)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        i += 2;
+
+        let mut len = (token & RUN_MASK) + MIN_MATCH_LEN;
+        if token & RUN_MASK == RUN_MASK {
+            len += read_length(input, &mut i)?;
+        }
+
+        if offset == 0 || offset > output.len() - output_start || output.len() + len > output_end {
+            return Err(InvalidCompressedDataError);
+        }
+        let start = output.len() - offset;
+        for j in start..start + len {
+            let byte = output[j];
+            output.push(byte);
+        }
+    }
+
+    if output.len() != output_end {
+        return Err(InvalidCompressedDataError);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roundtrip(input: &[u8]) -> usize {
+        let mut compressed = Vec::new();
+        compress(input, &mut compressed);
+        let mut decompressed = Vec::new();
+        assert!(decompress(&compressed, &mut decompressed).is_ok());
+        assert_eq!(input, &decompressed[..]);
+        compressed.len()
+    }
+
+    #[test]
+    fn roundtrip() {
+        assert_roundtrip(b"");
+        assert_roundtrip(b"a");
+        assert_roundtrip(b"abcd");
+        assert_roundtrip(b"abcdabcd");
+        assert_roundtrip(b"the quick brown fox jumps over the lazy dog");
+
+        let repetitive = b"\x1b[38;5;15m    let x = 0;\n".repeat(200);
+        let compressed_len = assert_roundtrip(&repetitive);
+        assert!(compressed_len < repetitive.len() / 10);
+
+        let long_run = vec![b'x'; 70000];
+        assert_roundtrip(&long_run);
+
+        let mut seed = 17u32;
+        let noise: Vec<u8> = (0..5000)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed as u8
+            })
+            .collect();
+        assert_roundtrip(&noise);
+    }
+
+    #[test]
+    fn invalid_data() {
+        fn decompress_with_len(len: u32, data: &[u8]) -> Result<(), InvalidCompressedDataError> {
+            let mut input = len.to_le_bytes().to_vec();
+            input.extend_from_slice(data);
+            let mut output = Vec::new();
+            decompress(&input, &mut output)
+        }
+
+        let mut output = Vec::new();
+        assert!(decompress(&[], &mut output).is_err());
+        assert!(decompress(&[1, 0], &mut output).is_err());
+        assert!(decompress_with_len(1, &[0x0f, 1]).is_err());
+        assert!(decompress_with_len(5, &[0x10, b'a', 2, 0]).is_err());
+        assert!(decompress_with_len(15, &[0xf0]).is_err());
+
+        assert!(decompress_with_len(1, &[0x10, b'a']).is_ok());
+        assert!(decompress_with_len(0, &[0x10, b'a']).is_err());
+        assert!(decompress_with_len(2, &[0x10, b'a']).is_err());
+
+        // a single byte repeated by a long match must not grow past the expected length
+        let mut bomb = vec![0x1f, b'a', 1, 0];
+        bomb.extend(std::iter::repeat_n(255, 1000));
+        bomb.push(0);
+        assert!(decompress_with_len(8, &bomb).is_err());
+        assert!(decompress_with_len(u32::MAX, &bomb).is_err());
+    }
+}
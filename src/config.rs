@@ -1,4 +1,4 @@
-use std::{fmt, num::NonZeroU8};
+use std::{fmt, num::NonZeroU8, str::FromStr};
 
 pub enum ParseConfigError {
     NoSuchConfig,
@@ -13,10 +13,147 @@ impl fmt::Display for ParseConfigError {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ServerExitPolicy {
+    KeepRunning,
+    Immediately,
+    AfterIdleSeconds(u32),
+}
+impl FromStr for ServerExitPolicy {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::KeepRunning),
+            "immediately" => Ok(Self::Immediately),
+            _ => match s.parse() {
+                Ok(seconds) => Ok(Self::AfterIdleSeconds(seconds)),
+                Err(_) => Err(()),
+            },
+        }
+    }
+}
+impl fmt::Display for ServerExitPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::KeepRunning => f.write_str("keep"),
+            Self::Immediately => f.write_str("immediately"),
+            Self::AfterIdleSeconds(seconds) => seconds.fmt(f),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum UndoGrouping {
+    InsertSession,
+    AfterPauseMillis(u32),
+}
+impl FromStr for UndoGrouping {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "insert" => Ok(Self::InsertSession),
+            _ => match s.parse() {
+                Ok(millis) => Ok(Self::AfterPauseMillis(millis)),
+                Err(_) => Err(()),
+            },
+        }
+    }
+}
+impl fmt::Display for UndoGrouping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InsertSession => f.write_str("insert"),
+            Self::AfterPauseMillis(millis) => millis.fmt(f),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineNumbers {
+    None,
+    Absolute,
+    Relative,
+    Hybrid,
+}
+impl FromStr for LineNumbers {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "absolute" => Ok(Self::Absolute),
+            "relative" => Ok(Self::Relative),
+            "hybrid" => Ok(Self::Hybrid),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for LineNumbers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::None => f.write_str("none"),
+            Self::Absolute => f.write_str("absolute"),
+            Self::Relative => f.write_str("relative"),
+            Self::Hybrid => f.write_str("hybrid"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CompletionTrigger {
+    Auto,
+    Manual,
+}
+impl FromStr for CompletionTrigger {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "manual" => Ok(Self::Manual),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for CompletionTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Auto => f.write_str("auto"),
+            Self::Manual => f.write_str("manual"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LspTrace {
+    Off,
+    Messages,
+    Verbose,
+}
+impl FromStr for LspTrace {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "messages" => Ok(Self::Messages),
+            "verbose" => Ok(Self::Verbose),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for LspTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Off => f.write_str("off"),
+            Self::Messages => f.write_str("messages"),
+            Self::Verbose => f.write_str("verbose"),
+        }
+    }
+}
+
 macro_rules! config_values {
     ($($name:ident: $type:ty = $default:expr,)*) => {
         pub static CONFIG_NAMES: &[&str] = &[$(stringify!($name),)*];
 
+        #[derive(Clone)]
         pub struct Config {
             $(pub $name: $type,)*
         }
@@ -65,15 +202,99 @@ macro_rules! config_values {
     }
 }
 
+macro_rules! client_config_values {
+    ($($name:ident: $type:ty,)*) => {
+        pub static CLIENT_CONFIG_NAMES: &[&str] = &[$(stringify!($name),)*];
+
+        #[derive(Default)]
+        pub struct ClientConfig {
+            $(pub $name: Option<$type>,)*
+        }
+
+        impl ClientConfig {
+            pub fn parse_config(&mut self, key: &str, value: &str) -> Result<(), ParseConfigError> {
+                match key {
+                    $(stringify!($name) => match value.parse() {
+                        Ok(value) => self.$name = Some(value),
+                        Err(_) => return Err(ParseConfigError::InvalidValue),
+                    },)*
+                    _ => return Err(ParseConfigError::NoSuchConfig),
+                }
+                Ok(())
+            }
+
+            pub fn display_override(&self, key: &str) -> Option<&dyn fmt::Display> {
+                match key {
+                    $(stringify!($name) => self.$name.as_ref().map(|v| v as _),)*
+                    _ => None,
+                }
+            }
+        }
+
+        #[derive(Clone, Copy)]
+        pub struct ClientDisplayConfig<'a> {
+            overrides: &'a ClientConfig,
+            config: &'a Config,
+        }
+
+        impl<'a> ClientDisplayConfig<'a> {
+            pub fn new(overrides: &'a ClientConfig, config: &'a Config) -> Self {
+                Self { overrides, config }
+            }
+
+            pub fn overrides(&self) -> &'a ClientConfig {
+                self.overrides
+            }
+
+            $(pub fn $name(&self) -> $type {
+                self.overrides.$name.unwrap_or(self.config.$name)
+            })*
+        }
+    }
+}
+
+client_config_values! {
+    visual_empty: char,
+    visual_space: char,
+    visual_tab_first: char,
+    visual_tab_repeat: char,
+    soft_wrap: bool,
+    line_numbers: LineNumbers,
+    terminal_cursor: bool,
+}
+
 config_values! {
     tab_size: NonZeroU8 = NonZeroU8::new(4).unwrap(),
     indent_with_tabs: bool = true,
+    undo_grouping: UndoGrouping = UndoGrouping::InsertSession,
 
     visual_empty: char = '~',
     visual_space: char = '.',
     visual_tab_first: char = '|',
     visual_tab_repeat: char = ' ',
+    soft_wrap: bool = false,
+    line_numbers: LineNumbers = LineNumbers::None,
+    terminal_cursor: bool = false,
 
     completion_min_len: u8 = 3,
+    completion_trigger: CompletionTrigger = CompletionTrigger::Auto,
     picker_max_height: u8 = 8,
+    find_file_ignore: String = String::new(),
+    file_tree_width: u8 = 30,
+    open_url_command: String = String::new(),
+    make_command: String = "make".into(),
+    todo_keywords: String = "TODO FIXME NOTE".into(),
+    large_file_size: u32 = 8 * 1024 * 1024,
+    large_file_line_len: u32 = 10 * 1024,
+    stdin_follow: bool = false,
+    leader: String = "<space>".into(),
+
+    lsp_rename_preview: bool = false,
+    lsp_inlay_hints: bool = false,
+    lsp_trace: LspTrace = LspTrace::Messages,
+
+    server_exit_policy: ServerExitPolicy = ServerExitPolicy::Immediately,
+
+    status_segments: String = String::new(),
+    plugin_directory: String = String::new(),
 }
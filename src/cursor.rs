@@ -4,8 +4,8 @@ use std::{
 };
 
 use crate::{
-    buffer::{BufferContent, CharDisplayDistances},
-    buffer_position::{BufferPosition, BufferRange},
+    buffer::BufferContent,
+    buffer_position::{byte_index_to_display_column, BufferPosition, BufferRange},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -160,12 +160,9 @@ impl<'a> CursorCollectionMutGuard<'a> {
         self.clear_display_distances = false;
         if self.inner.saved_display_distances_len == 0 {
             for c in &self.inner.cursors[..self.inner.len as usize] {
-                let line = &buffer.line_at(c.position.line_index as _).as_str()
-                    [..c.position.column_byte_index as usize];
-                let distance = CharDisplayDistances::new(line, tab_size)
-                    .last()
-                    .map(|d| d.distance)
-                    .unwrap_or(0);
+                let line = buffer.line_at(c.position.line_index as _).as_str();
+                let distance =
+                    byte_index_to_display_column(line, c.position.column_byte_index as _, tab_size);
 
                 self.inner.saved_display_distances
                     [self.inner.saved_display_distances_len as usize] = distance as _;
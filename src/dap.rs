@@ -0,0 +1,1285 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use crate::{
+    buffer::{BufferCapabilities, BufferHandle},
+    buffer_position::{BufferPosition, BufferRange},
+    client::ClientManager,
+    cursor::Cursor,
+    editor::Editor,
+    editor_utils::{parse_process_command, MessageKind},
+    json::{FromJson, Json, JsonArray, JsonInteger, JsonObject, JsonValue},
+    lsp,
+    mode::{Mode, ModeContext, ModeKind},
+    navigation_history::NavigationHistory,
+    platform::{Platform, PlatformRequest, ProcessHandle, ProcessTag},
+};
+
+pub const VARIABLES_BUFFER_PATH: &str = "dap.variables";
+pub const WATCH_BUFFER_PATH: &str = "dap.watch";
+pub const STACK_BUFFER_PATH: &str = "dap.stack";
+
+const STACK_TRACE_LEVELS: JsonInteger = 64;
+
+const DAP_NAMESPACE: &str = "dap";
+const BREAKPOINT_SIGN_NAME: &str = "breakpoint";
+
+pub enum DebuggerError {
+    SessionAlreadyRunning,
+    NoSession,
+    NotStopped,
+    NoSuchFrame,
+    InvalidCommand,
+    InvalidArguments,
+    NoSuchWatch,
+    NoSuchVariable,
+}
+impl fmt::Display for DebuggerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::SessionAlreadyRunning => f.write_str("debug session already running"),
+            Self::NoSession => f.write_str("no debug session running"),
+            Self::NotStopped => f.write_str("debuggee is not stopped"),
+            Self::NoSuchFrame => f.write_str("no such stack frame"),
+            Self::InvalidCommand => f.write_str("invalid debug adapter command"),
+            Self::InvalidArguments => f.write_str("debug arguments must be a json object"),
+            Self::NoSuchWatch => f.write_str("no such watch expression"),
+            Self::NoSuchVariable => f.write_str("no such variable"),
+        }
+    }
+}
+
+pub struct Variable {
+    pub depth: u32,
+    pub path: String,
+    pub name: String,
+    pub value: String,
+    pub reference: JsonInteger,
+    pub expanded_by_default: bool,
+}
+impl Variable {
+    pub fn is_expanded(&self, toggled: &[String]) -> bool {
+        self.reference > 0 && self.expanded_by_default != toggled.contains(&self.path)
+    }
+}
+
+pub fn write_variables(variables: &[Variable], toggled: &[String], text: &mut String) {
+    for variable in variables {
+        for _ in 0..variable.depth {
+            text.push_str("  ");
+        }
+        let marker = if variable.reference <= 0 {
+            ' '
+        } else if variable.is_expanded(toggled) {
+            '-'
+        } else {
+            '+'
+        };
+        text.push(marker);
+        text.push(' ');
+        text.push_str(&variable.name);
+        if !variable.value.is_empty() {
+            text.push_str(" = ");
+            text.push_str(&variable.value);
+        }
+        text.push('\n');
+    }
+}
+
+pub struct StackFrame {
+    pub id: JsonInteger,
+    pub name: String,
+    pub path: Option<PathBuf>,
+    pub position: BufferPosition,
+}
+
+pub fn write_stack_frames(
+    frames: &[StackFrame],
+    current_frame: usize,
+    root: &Path,
+    text: &mut String,
+) {
+    use std::fmt::Write;
+
+    for (i, frame) in frames.iter().enumerate() {
+        let marker = if i == current_frame { '>' } else { ' ' };
+        let _ = write!(text, "{} #{} {}", marker, i, frame.name);
+        if let Some(path) = &frame.path {
+            let path = path.strip_prefix(root).unwrap_or(path);
+            let _ = write!(
+                text,
+                " {}:{},{}",
+                path.display(),
+                frame.position.line_index + 1,
+                frame.position.column_byte_index + 1,
+            );
+        }
+        text.push('\n');
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum StepKind {
+    Continue,
+    Next,
+    StepIn,
+    StepOut,
+}
+impl StepKind {
+    fn command(self) -> &'static str {
+        match self {
+            Self::Continue => "continue",
+            Self::Next => "next",
+            Self::StepIn => "stepIn",
+            Self::StepOut => "stepOut",
+        }
+    }
+}
+
+pub struct Watch {
+    pub expression: String,
+    pub value: Option<String>,
+}
+
+struct SourceBreakpoints {
+    path: PathBuf,
+    line_indexes: Vec<u32>,
+}
+
+enum PendingRequest {
+    Initialize,
+    Launch,
+    SetBreakpoints,
+    ConfigurationDone,
+    Step,
+    Threads(u32),
+    StackTrace(u32),
+    Scopes(u32),
+    Variables(u32, String),
+    Evaluate(u32, String),
+    Disconnect,
+}
+
+struct Session {
+    process_handle: Option<ProcessHandle>,
+    json: Json,
+    read_buf: Vec<u8>,
+    body_buf: Vec<u8>,
+    next_seq: JsonInteger,
+    pending_requests: Vec<(JsonInteger, PendingRequest)>,
+    launch_command: &'static str,
+    launch_arguments: String,
+    initialized: bool,
+    disconnecting: bool,
+    stopped: bool,
+    generation: u32,
+    thread_id: Option<JsonInteger>,
+    frame_id: Option<JsonInteger>,
+    frames: Vec<StackFrame>,
+    current_frame: usize,
+    variables: Vec<Variable>,
+}
+
+impl Session {
+    fn send(&mut self, platform: &mut Platform, body: JsonObject) {
+        let handle = match self.process_handle {
+            Some(handle) => handle,
+            None => return,
+        };
+
+        use io::Write;
+        let mut buf = platform.buf_pool.acquire();
+        let write = buf.write();
+        let _ = self.json.write(&mut self.body_buf, &body.into());
+        let _ = write!(write, "Content-Length: {}\r\n\r\n", self.body_buf.len());
+        write.append(&mut self.body_buf);
+
+        platform
+            .requests
+            .enqueue(PlatformRequest::WriteToProcess { handle, buf });
+    }
+
+    fn request(
+        &mut self,
+        platform: &mut Platform,
+        command: &'static str,
+        arguments: JsonValue,
+        pending: PendingRequest,
+    ) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        let mut body = JsonObject::default();
+        body.set("seq".into(), seq.into(), &mut self.json);
+        body.set("type".into(), "request".into(), &mut self.json);
+        body.set("command".into(), command.into(), &mut self.json);
+        body.set("arguments".into(), arguments, &mut self.json);
+        self.send(platform, body);
+
+        self.pending_requests.push((seq, pending));
+    }
+
+    fn respond_unsupported(
+        &mut self,
+        platform: &mut Platform,
+        request_seq: JsonValue,
+        command: JsonValue,
+    ) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        let mut body = JsonObject::default();
+        body.set("seq".into(), seq.into(), &mut self.json);
+        body.set("type".into(), "response".into(), &mut self.json);
+        body.set("request_seq".into(), request_seq, &mut self.json);
+        body.set("success".into(), false.into(), &mut self.json);
+        body.set("command".into(), command, &mut self.json);
+        body.set("message".into(), "unsupported".into(), &mut self.json);
+        self.send(platform, body);
+    }
+
+    fn set_breakpoints(&mut self, platform: &mut Platform, breakpoints: &SourceBreakpoints) {
+        let json = &mut self.json;
+        let mut source = JsonObject::default();
+        let path = json.create_string(breakpoints.path.to_str().unwrap_or(""));
+        source.set("path".into(), path.into(), json);
+
+        let mut lines = JsonArray::default();
+        let mut source_breakpoints = JsonArray::default();
+        for &line_index in &breakpoints.line_indexes {
+            let line = JsonValue::Integer(line_index as JsonInteger + 1);
+            let mut breakpoint = JsonObject::default();
+            breakpoint.set("line".into(), line.clone(), json);
+            source_breakpoints.push(breakpoint.into(), json);
+            lines.push(line, json);
+        }
+
+        let mut arguments = JsonObject::default();
+        arguments.set("source".into(), source.into(), json);
+        arguments.set("breakpoints".into(), source_breakpoints.into(), json);
+        arguments.set("lines".into(), lines.into(), json);
+        self.request(
+            platform,
+            "setBreakpoints",
+            arguments.into(),
+            PendingRequest::SetBreakpoints,
+        );
+    }
+
+    fn request_stack_trace(&mut self, platform: &mut Platform, thread_id: JsonInteger) {
+        let mut arguments = JsonObject::default();
+        arguments.set("threadId".into(), thread_id.into(), &mut self.json);
+        arguments.set("startFrame".into(), JsonValue::Integer(0), &mut self.json);
+        arguments.set("levels".into(), STACK_TRACE_LEVELS.into(), &mut self.json);
+        self.request(
+            platform,
+            "stackTrace",
+            arguments.into(),
+            PendingRequest::StackTrace(self.generation),
+        );
+    }
+
+    fn request_variables(&mut self, platform: &mut Platform, reference: JsonInteger, path: String) {
+        let mut arguments = JsonObject::default();
+        arguments.set(
+            "variablesReference".into(),
+            reference.into(),
+            &mut self.json,
+        );
+        self.request(
+            platform,
+            "variables",
+            arguments.into(),
+            PendingRequest::Variables(self.generation, path),
+        );
+    }
+
+    fn evaluate(&mut self, platform: &mut Platform, expression: &str) {
+        let json = &mut self.json;
+        let mut arguments = JsonObject::default();
+        let value = json.create_string(expression);
+        arguments.set("expression".into(), value.into(), json);
+        if let Some(frame_id) = self.frame_id {
+            arguments.set("frameId".into(), frame_id.into(), json);
+        }
+        arguments.set("context".into(), "watch".into(), json);
+        self.request(
+            platform,
+            "evaluate",
+            arguments.into(),
+            PendingRequest::Evaluate(self.generation, expression.into()),
+        );
+    }
+
+    fn disconnect(&mut self, platform: &mut Platform) {
+        self.disconnecting = true;
+        let mut arguments = JsonObject::default();
+        arguments.set("terminateDebuggee".into(), true.into(), &mut self.json);
+        self.request(
+            platform,
+            "disconnect",
+            arguments.into(),
+            PendingRequest::Disconnect,
+        );
+    }
+
+    fn kill(&mut self, platform: &mut Platform) {
+        if let Some(handle) = self.process_handle.take() {
+            platform
+                .requests
+                .enqueue(PlatformRequest::KillProcess { handle });
+        }
+    }
+}
+
+fn get<'json, T>(object: &JsonObject, key: &str, json: &'json Json) -> Option<T>
+where
+    T: FromJson<'json>,
+{
+    T::from_json(object.clone().get(key, json), json).ok()
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    session: Option<Session>,
+    breakpoints: Vec<SourceBreakpoints>,
+    watches: Vec<Watch>,
+    toggled_variables: Vec<String>,
+}
+
+impl Debugger {
+    pub fn is_running(&self) -> bool {
+        self.session.is_some()
+    }
+
+    pub fn start(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        current_buffer_handle: Option<BufferHandle>,
+        command: &str,
+        attach: bool,
+        arguments: &str,
+    ) -> Result<(), DebuggerError> {
+        if editor.debugger.session.is_some() {
+            return Err(DebuggerError::SessionAlreadyRunning);
+        }
+
+        let mut json = Json::new();
+        let mut reader = io::Cursor::new(arguments.as_bytes());
+        match json.read(&mut reader) {
+            Ok(JsonValue::Object(_)) => (),
+            _ => return Err(DebuggerError::InvalidArguments),
+        }
+
+        let mut process = match parse_process_command(command) {
+            Some(process) => process,
+            None => return Err(DebuggerError::InvalidCommand),
+        };
+        process.current_dir(editor.workspace_root(current_buffer_handle));
+        process.stdin(Stdio::piped());
+        process.stdout(Stdio::piped());
+        process.stderr(Stdio::null());
+
+        editor.debugger.session = Some(Session {
+            process_handle: None,
+            json,
+            read_buf: Vec::new(),
+            body_buf: Vec::new(),
+            next_seq: 1,
+            pending_requests: Vec::new(),
+            launch_command: if attach { "attach" } else { "launch" },
+            launch_arguments: arguments.into(),
+            initialized: false,
+            disconnecting: false,
+            stopped: false,
+            generation: 0,
+            thread_id: None,
+            frame_id: None,
+            frames: Vec::new(),
+            current_frame: 0,
+            variables: Vec::new(),
+        });
+        for watch in &mut editor.debugger.watches {
+            watch.value = None;
+        }
+        Self::refresh_variables_buffer(editor);
+        Self::refresh_watch_buffer(editor);
+        Self::refresh_stack_buffer(editor);
+
+        platform.requests.enqueue(PlatformRequest::SpawnProcess {
+            tag: ProcessTag::Dap,
+            command: process,
+            buf_len: 4 * 1024,
+        });
+        Ok(())
+    }
+
+    pub fn stop(editor: &mut Editor, platform: &mut Platform) -> Result<(), DebuggerError> {
+        let session = match &mut editor.debugger.session {
+            Some(session) => session,
+            None => return Err(DebuggerError::NoSession),
+        };
+        if session.disconnecting || session.process_handle.is_none() {
+            session.disconnecting = true;
+            session.kill(platform);
+        } else {
+            session.disconnect(platform);
+        }
+        Ok(())
+    }
+
+    pub fn step(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        kind: StepKind,
+    ) -> Result<(), DebuggerError> {
+        let session = match &mut editor.debugger.session {
+            Some(session) => session,
+            None => return Err(DebuggerError::NoSession),
+        };
+        let thread_id = match session.thread_id {
+            Some(thread_id) if session.stopped => thread_id,
+            _ => return Err(DebuggerError::NotStopped),
+        };
+
+        let mut arguments = JsonObject::default();
+        arguments.set("threadId".into(), thread_id.into(), &mut session.json);
+        session.request(
+            platform,
+            kind.command(),
+            arguments.into(),
+            PendingRequest::Step,
+        );
+        session.stopped = false;
+        session.frame_id = None;
+        editor.decorations.clear(None, DAP_NAMESPACE);
+        Ok(())
+    }
+
+    pub fn current_frame(&self) -> Option<usize> {
+        match &self.session {
+            Some(session) if session.stopped && !session.frames.is_empty() => {
+                Some(session.current_frame)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn select_frame(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        clients: &mut ClientManager,
+        index: usize,
+    ) -> Result<(), DebuggerError> {
+        let mut session = match editor.debugger.session.take() {
+            Some(session) => session,
+            None => return Err(DebuggerError::NoSession),
+        };
+        let result = if !session.stopped {
+            Err(DebuggerError::NotStopped)
+        } else if index >= session.frames.len() {
+            Err(DebuggerError::NoSuchFrame)
+        } else {
+            Self::focus_frame(editor, platform, clients, &mut session, index);
+            Ok(())
+        };
+        editor.debugger.session = Some(session);
+        result
+    }
+
+    pub fn toggle_breakpoint(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        line_index: u32,
+    ) {
+        let path = editor
+            .current_directory
+            .join(&editor.buffers.get(buffer_handle).path);
+        let debugger = &mut editor.debugger;
+        let index = match debugger.breakpoints.iter().position(|b| b.path == path) {
+            Some(index) => index,
+            None => {
+                debugger.breakpoints.push(SourceBreakpoints {
+                    path,
+                    line_indexes: Vec::new(),
+                });
+                debugger.breakpoints.len() - 1
+            }
+        };
+
+        let breakpoints = &mut debugger.breakpoints[index];
+        match breakpoints
+            .line_indexes
+            .iter()
+            .position(|&l| l == line_index)
+        {
+            Some(i) => {
+                breakpoints.line_indexes.remove(i);
+                editor
+                    .signs
+                    .remove(buffer_handle, DAP_NAMESPACE, line_index as _);
+            }
+            None => {
+                breakpoints.line_indexes.push(line_index);
+                editor.signs.add(
+                    buffer_handle,
+                    DAP_NAMESPACE,
+                    line_index as _,
+                    BREAKPOINT_SIGN_NAME,
+                );
+            }
+        }
+
+        if let Some(session) = &mut debugger.session {
+            if session.initialized {
+                session.set_breakpoints(platform, &debugger.breakpoints[index]);
+            }
+        }
+    }
+
+    pub fn add_watch(editor: &mut Editor, platform: &mut Platform, expression: &str) {
+        let debugger = &mut editor.debugger;
+        if debugger.watches.iter().any(|w| w.expression == expression) {
+            return;
+        }
+        debugger.watches.push(Watch {
+            expression: expression.into(),
+            value: None,
+        });
+        if let Some(session) = &mut debugger.session {
+            if session.stopped {
+                session.evaluate(platform, expression);
+            }
+        }
+        Self::refresh_watch_buffer(editor);
+    }
+
+    pub fn remove_watch(editor: &mut Editor, expression: &str) -> bool {
+        let len = editor.debugger.watches.len();
+        editor
+            .debugger
+            .watches
+            .retain(|w| w.expression != expression);
+        if editor.debugger.watches.len() == len {
+            return false;
+        }
+        Self::refresh_watch_buffer(editor);
+        true
+    }
+
+    pub fn variable_path_at(&self, index: usize) -> Option<&str> {
+        let session = self.session.as_ref()?;
+        let variable = session.variables.get(index)?;
+        Some(&variable.path)
+    }
+
+    pub fn toggle_variable(editor: &mut Editor, platform: &mut Platform, path: &str) {
+        let debugger = &mut editor.debugger;
+        match debugger.toggled_variables.iter().position(|p| p == path) {
+            Some(i) => {
+                debugger.toggled_variables.swap_remove(i);
+            }
+            None => debugger.toggled_variables.push(path.into()),
+        }
+
+        if let Some(session) = &mut debugger.session {
+            if let Some(index) = session.variables.iter().position(|v| v.path == path) {
+                let variable = &session.variables[index];
+                let depth = variable.depth;
+                let children_len = session.variables[index + 1..]
+                    .iter()
+                    .take_while(|v| v.depth > depth)
+                    .count();
+                session.variables.drain(index + 1..index + 1 + children_len);
+
+                let variable = &session.variables[index];
+                if session.stopped && variable.is_expanded(&debugger.toggled_variables) {
+                    let reference = variable.reference;
+                    session.request_variables(platform, reference, path.into());
+                }
+            }
+        }
+        Self::refresh_variables_buffer(editor);
+    }
+
+    pub fn on_process_spawned(editor: &mut Editor, platform: &mut Platform, handle: ProcessHandle) {
+        let session = match &mut editor.debugger.session {
+            Some(session) => session,
+            None => {
+                platform
+                    .requests
+                    .enqueue(PlatformRequest::KillProcess { handle });
+                return;
+            }
+        };
+        session.process_handle = Some(handle);
+        if session.disconnecting {
+            session.kill(platform);
+            return;
+        }
+
+        let json = &mut session.json;
+        let mut arguments = JsonObject::default();
+        arguments.set("clientID".into(), "pepper".into(), json);
+        arguments.set("clientName".into(), "pepper".into(), json);
+        arguments.set("adapterID".into(), "pepper".into(), json);
+        arguments.set("linesStartAt1".into(), true.into(), json);
+        arguments.set("columnsStartAt1".into(), true.into(), json);
+        arguments.set("pathFormat".into(), "path".into(), json);
+        session.request(
+            platform,
+            "initialize",
+            arguments.into(),
+            PendingRequest::Initialize,
+        );
+    }
+
+    pub fn on_process_output(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        clients: &mut ClientManager,
+        bytes: &[u8],
+    ) {
+        let mut session = match editor.debugger.session.take() {
+            Some(session) => session,
+            None => return,
+        };
+
+        session.read_buf.extend_from_slice(bytes);
+        let mut read_len = 0;
+        while let Some(range) = lsp::try_get_content_range(&session.read_buf[read_len..]) {
+            let start = read_len + range.start;
+            let end = read_len + range.end;
+            read_len = end;
+
+            session.json.clear();
+            let mut reader = io::Cursor::new(&session.read_buf[start..end]);
+            if let Ok(JsonValue::Object(body)) = session.json.read(&mut reader) {
+                Self::on_message(editor, platform, clients, &mut session, body);
+            }
+        }
+        session.read_buf.drain(..read_len);
+
+        editor.debugger.session = Some(session);
+    }
+
+    pub fn on_process_exit(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        clients: &mut ClientManager,
+    ) {
+        if editor.debugger.session.take().is_none() {
+            return;
+        }
+        editor
+            .status_bar
+            .write(MessageKind::Info)
+            .str("debug session ended");
+        editor.decorations.clear(None, DAP_NAMESPACE);
+
+        if editor.mode.kind() == ModeKind::Debug {
+            if let Some(client_handle) = clients.focused_client() {
+                let mut ctx = ModeContext {
+                    editor,
+                    platform,
+                    clients,
+                    client_handle,
+                };
+                Mode::change_to(&mut ctx, ModeKind::default());
+            }
+        }
+    }
+
+    fn on_message(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        clients: &mut ClientManager,
+        session: &mut Session,
+        body: JsonObject,
+    ) {
+        let json = &session.json;
+        match get(&body, "type", json) {
+            Some("response") => {
+                let request_seq: JsonInteger = get(&body, "request_seq", json).unwrap_or(0);
+                let index = match session
+                    .pending_requests
+                    .iter()
+                    .position(|(seq, _)| *seq == request_seq)
+                {
+                    Some(index) => index,
+                    None => return,
+                };
+                let (_, request) = session.pending_requests.swap_remove(index);
+                if get(&body, "success", json).unwrap_or(false) {
+                    let response_body = body.get("body", json);
+                    Self::on_response(editor, platform, clients, session, request, response_body);
+                } else {
+                    let message: &str = get(&body, "message", json).unwrap_or("");
+                    let message = message.to_owned();
+                    Self::on_response_error(editor, platform, session, request, &message);
+                }
+            }
+            Some("event") => {
+                let event: &str = get(&body, "event", json).unwrap_or("");
+                let event_body = match body.get("body", json) {
+                    JsonValue::Object(event_body) => event_body,
+                    _ => JsonObject::default(),
+                };
+                let event = match event {
+                    "initialized" => Event::Initialized,
+                    "stopped" => Event::Stopped,
+                    "continued" => Event::Continued,
+                    "exited" => Event::Exited,
+                    "terminated" => Event::Terminated,
+                    _ => return,
+                };
+                Self::on_event(editor, platform, session, event, event_body);
+            }
+            Some("request") => {
+                let request_seq = body.clone().get("seq", json);
+                let command = body.get("command", json);
+                session.respond_unsupported(platform, request_seq, command);
+            }
+            _ => (),
+        }
+    }
+
+    fn on_event(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        session: &mut Session,
+        event: Event,
+        body: JsonObject,
+    ) {
+        let json = &session.json;
+        match event {
+            Event::Initialized => {
+                session.initialized = true;
+                for breakpoints in &editor.debugger.breakpoints {
+                    session.set_breakpoints(platform, breakpoints);
+                }
+                session.request(
+                    platform,
+                    "configurationDone",
+                    JsonObject::default().into(),
+                    PendingRequest::ConfigurationDone,
+                );
+            }
+            Event::Stopped => {
+                let reason: &str = get(&body, "reason", json).unwrap_or("");
+                editor
+                    .status_bar
+                    .write(MessageKind::Info)
+                    .fmt(format_args!("debugger stopped: {}", reason));
+
+                if let Some(thread_id) = get(&body, "threadId", json) {
+                    session.thread_id = Some(thread_id);
+                }
+                session.stopped = true;
+                session.generation = session.generation.wrapping_add(1);
+                match session.thread_id {
+                    Some(thread_id) => session.request_stack_trace(platform, thread_id),
+                    None => session.request(
+                        platform,
+                        "threads",
+                        JsonObject::default().into(),
+                        PendingRequest::Threads(session.generation),
+                    ),
+                }
+            }
+            Event::Continued => {
+                session.stopped = false;
+                session.frame_id = None;
+                editor.decorations.clear(None, DAP_NAMESPACE);
+            }
+            Event::Exited => {
+                let exit_code: JsonInteger = get(&body, "exitCode", json).unwrap_or(0);
+                editor
+                    .status_bar
+                    .write(MessageKind::Info)
+                    .fmt(format_args!("debuggee exited with code {}", exit_code));
+            }
+            Event::Terminated => {
+                session.stopped = false;
+                editor.decorations.clear(None, DAP_NAMESPACE);
+                if !session.disconnecting {
+                    session.disconnect(platform);
+                }
+            }
+        }
+    }
+
+    fn on_response(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        clients: &mut ClientManager,
+        session: &mut Session,
+        request: PendingRequest,
+        body: JsonValue,
+    ) {
+        let json = &session.json;
+        match request {
+            PendingRequest::Initialize => {
+                let mut reader = io::Cursor::new(session.launch_arguments.as_bytes());
+                let arguments = session.json.read(&mut reader).unwrap_or_default();
+                let command = session.launch_command;
+                session.request(platform, command, arguments, PendingRequest::Launch);
+            }
+            PendingRequest::Launch
+            | PendingRequest::SetBreakpoints
+            | PendingRequest::ConfigurationDone
+            | PendingRequest::Step => (),
+            PendingRequest::Threads(generation) => {
+                if generation != session.generation {
+                    return;
+                }
+                let thread_id = body
+                    .get("threads", json)
+                    .elements(json)
+                    .find_map(|t| get(&JsonObject::from_json(t, json).ok()?, "id", json));
+                if let Some(thread_id) = thread_id {
+                    session.thread_id = Some(thread_id);
+                    session.request_stack_trace(platform, thread_id);
+                }
+            }
+            PendingRequest::StackTrace(generation) => {
+                if generation != session.generation {
+                    return;
+                }
+                session.frames.clear();
+                for frame in body.get("stackFrames", json).elements(json) {
+                    let frame = match frame {
+                        JsonValue::Object(frame) => frame,
+                        _ => continue,
+                    };
+                    let name: &str = get(&frame, "name", json).unwrap_or("");
+                    let source = frame.clone().get("source", json);
+                    let path: Option<&str> = FromJson::from_json(source.get("path", json), json)
+                        .ok()
+                        .flatten();
+                    let line: u32 = get(&frame, "line", json).unwrap_or(1);
+                    let column: u32 = get(&frame, "column", json).unwrap_or(1);
+                    session.frames.push(StackFrame {
+                        id: get(&frame, "id", json).unwrap_or(0),
+                        name: name.into(),
+                        path: path.map(PathBuf::from),
+                        position: BufferPosition::line_col(
+                            line.saturating_sub(1) as _,
+                            column.saturating_sub(1) as _,
+                        ),
+                    });
+                }
+                Self::focus_frame(editor, platform, clients, session, 0);
+            }
+            PendingRequest::Scopes(generation) => {
+                if generation != session.generation {
+                    return;
+                }
+                session.variables.clear();
+                for scope in body.get("scopes", json).elements(json) {
+                    let scope = match scope {
+                        JsonValue::Object(scope) => scope,
+                        _ => continue,
+                    };
+                    let name: &str = get(&scope, "name", json).unwrap_or("");
+                    session.variables.push(Variable {
+                        depth: 0,
+                        path: name.into(),
+                        name: name.into(),
+                        value: String::new(),
+                        reference: get(&scope, "variablesReference", json).unwrap_or(0),
+                        expanded_by_default: !get(&scope, "expensive", json).unwrap_or(false),
+                    });
+                }
+                Self::request_expanded_children(editor, platform, session, 0);
+                Self::refresh_variables_buffer_with(editor, session);
+            }
+            PendingRequest::Variables(generation, parent_path) => {
+                if generation != session.generation {
+                    return;
+                }
+                let parent_index = match session.variables.iter().position(|v| {
+                    v.path == parent_path && v.is_expanded(&editor.debugger.toggled_variables)
+                }) {
+                    Some(index) => index,
+                    None => return,
+                };
+                let depth = session.variables[parent_index].depth + 1;
+                let mut children = Vec::new();
+                for variable in body.get("variables", json).elements(json) {
+                    let variable = match variable {
+                        JsonValue::Object(variable) => variable,
+                        _ => continue,
+                    };
+                    let name: &str = get(&variable, "name", json).unwrap_or("");
+                    let value: &str = get(&variable, "value", json).unwrap_or("");
+                    children.push(Variable {
+                        depth,
+                        path: format!("{}.{}", parent_path, name),
+                        name: name.into(),
+                        value: value.replace('\n', " "),
+                        reference: get(&variable, "variablesReference", json).unwrap_or(0),
+                        expanded_by_default: false,
+                    });
+                }
+                let index = parent_index + 1;
+                session.variables.splice(index..index, children);
+                Self::request_expanded_children(editor, platform, session, index);
+                Self::refresh_variables_buffer_with(editor, session);
+            }
+            PendingRequest::Evaluate(generation, expression) => {
+                if generation != session.generation {
+                    return;
+                }
+                let value = body.get("result", json);
+                let value = <&str>::from_json(value, json).unwrap_or("");
+                let value = value.replace('\n', " ");
+                Self::set_watch_value(editor, &expression, value);
+            }
+            PendingRequest::Disconnect => session.kill(platform),
+        }
+    }
+
+    fn on_response_error(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        session: &mut Session,
+        request: PendingRequest,
+        message: &str,
+    ) {
+        match request {
+            PendingRequest::Initialize | PendingRequest::Launch => {
+                editor
+                    .status_bar
+                    .write(MessageKind::Error)
+                    .fmt(format_args!("could not start debug session: {}", message));
+                session.kill(platform);
+            }
+            PendingRequest::Evaluate(generation, expression) => {
+                if generation == session.generation {
+                    Self::set_watch_value(editor, &expression, format!("<{}>", message));
+                }
+            }
+            PendingRequest::Disconnect => session.kill(platform),
+            _ => editor
+                .status_bar
+                .write(MessageKind::Error)
+                .fmt(format_args!("debug adapter error: {}", message)),
+        }
+    }
+
+    fn focus_frame(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        clients: &mut ClientManager,
+        session: &mut Session,
+        index: usize,
+    ) {
+        session.generation = session.generation.wrapping_add(1);
+        session.current_frame = index;
+        session.frame_id = None;
+        session.variables.clear();
+        editor.decorations.clear(None, DAP_NAMESPACE);
+
+        if let Some(frame) = session.frames.get(index) {
+            session.frame_id = Some(frame.id);
+            if let Some(path) = &frame.path {
+                Self::show_location(editor, clients, path, frame.position);
+            }
+
+            let mut arguments = JsonObject::default();
+            arguments.set("frameId".into(), frame.id.into(), &mut session.json);
+            session.request(
+                platform,
+                "scopes",
+                arguments.into(),
+                PendingRequest::Scopes(session.generation),
+            );
+        }
+        for watch in &editor.debugger.watches {
+            session.evaluate(platform, &watch.expression);
+        }
+
+        Self::refresh_variables_buffer_with(editor, session);
+        Self::refresh_stack_buffer_with(editor, session);
+    }
+
+    fn show_location(
+        editor: &mut Editor,
+        clients: &mut ClientManager,
+        path: &Path,
+        position: BufferPosition,
+    ) {
+        editor.decorations.clear(None, DAP_NAMESPACE);
+        let path = path.strip_prefix(&editor.current_directory).unwrap_or(path);
+
+        let buffer_handle = match clients.focused_client() {
+            Some(client_handle) => {
+                NavigationHistory::save_snapshot(
+                    clients.get_mut(client_handle),
+                    &editor.buffer_views,
+                );
+                match editor.buffer_view_handle_from_path(
+                    client_handle,
+                    path,
+                    BufferCapabilities::text(),
+                ) {
+                    Ok(buffer_view_handle) => {
+                        clients.get_mut(client_handle).set_buffer_view_handle(
+                            Some(buffer_view_handle),
+                            &editor.buffer_views,
+                            &mut editor.events,
+                        );
+
+                        let buffer_view = editor.buffer_views.get_mut(buffer_view_handle);
+                        let position = editor
+                            .buffers
+                            .get(buffer_view.buffer_handle)
+                            .content()
+                            .position_from_char_column(position);
+                        let mut cursors = buffer_view.cursors.mut_guard();
+                        cursors.clear();
+                        cursors.add(Cursor {
+                            anchor: position,
+                            position,
+                        });
+                        Some(buffer_view.buffer_handle)
+                    }
+                    Err(error) => {
+                        editor
+                            .status_bar
+                            .write(MessageKind::Error)
+                            .fmt(format_args!("{}", error));
+                        None
+                    }
+                }
+            }
+            None => editor
+                .buffers
+                .find_with_path(&editor.current_directory, path),
+        };
+
+        if let Some(buffer_handle) = buffer_handle {
+            let line_index = position.line_index;
+            let range = BufferRange::between(
+                BufferPosition::line_col(line_index, 0),
+                BufferPosition::line_col(line_index + 1, 0),
+            );
+            let color = editor.theme.debug_line_background;
+            editor
+                .decorations
+                .add(buffer_handle, DAP_NAMESPACE, range, Some(color), false, "");
+        }
+    }
+
+    fn request_expanded_children(
+        editor: &Editor,
+        platform: &mut Platform,
+        session: &mut Session,
+        start_index: usize,
+    ) {
+        let depth = match start_index.checked_sub(1) {
+            Some(parent_index) => session.variables[parent_index].depth + 1,
+            None => 0,
+        };
+        let mut index = start_index;
+        while let Some(variable) = session.variables.get(index) {
+            if variable.depth != depth {
+                break;
+            }
+            index += 1;
+            if variable.is_expanded(&editor.debugger.toggled_variables) {
+                let reference = variable.reference;
+                let path = variable.path.clone();
+                session.request_variables(platform, reference, path);
+            }
+        }
+    }
+
+    fn set_watch_value(editor: &mut Editor, expression: &str, value: String) {
+        if let Some(watch) = editor
+            .debugger
+            .watches
+            .iter_mut()
+            .find(|w| w.expression == expression)
+        {
+            watch.value = Some(value);
+            Self::refresh_watch_buffer(editor);
+        }
+    }
+
+    fn refresh_variables_buffer(editor: &mut Editor) {
+        if let Some(session) = editor.debugger.session.take() {
+            Self::refresh_variables_buffer_with(editor, &session);
+            editor.debugger.session = Some(session);
+        }
+    }
+
+    fn refresh_variables_buffer_with(editor: &mut Editor, session: &Session) {
+        let mut text = editor.string_pool.acquire();
+        write_variables(
+            &session.variables,
+            &editor.debugger.toggled_variables,
+            &mut text,
+        );
+        update_buffer(editor, VARIABLES_BUFFER_PATH, &text);
+        editor.string_pool.release(text);
+    }
+
+    fn refresh_stack_buffer(editor: &mut Editor) {
+        if let Some(session) = editor.debugger.session.take() {
+            Self::refresh_stack_buffer_with(editor, &session);
+            editor.debugger.session = Some(session);
+        }
+    }
+
+    fn refresh_stack_buffer_with(editor: &mut Editor, session: &Session) {
+        let mut text = editor.string_pool.acquire();
+        write_stack_frames(
+            &session.frames,
+            session.current_frame,
+            &editor.current_directory,
+            &mut text,
+        );
+        update_buffer(editor, STACK_BUFFER_PATH, &text);
+        editor.string_pool.release(text);
+    }
+
+    fn refresh_watch_buffer(editor: &mut Editor) {
+        let mut text = editor.string_pool.acquire();
+        for watch in &editor.debugger.watches {
+            text.push_str(&watch.expression);
+            text.push_str(" = ");
+            text.push_str(watch.value.as_deref().unwrap_or("<not available>"));
+            text.push('\n');
+        }
+        update_buffer(editor, WATCH_BUFFER_PATH, &text);
+        editor.string_pool.release(text);
+    }
+}
+
+enum Event {
+    Initialized,
+    Stopped,
+    Continued,
+    Exited,
+    Terminated,
+}
+
+// only replaces the lines after the unchanged prefix so cursors on those lines stay put
+fn update_buffer(editor: &mut Editor, path: &str, text: &str) {
+    let buffer_handle = match editor
+        .buffers
+        .find_with_path(&editor.current_directory, Path::new(path))
+    {
+        Some(handle) => handle,
+        None => {
+            let buffer = editor.buffers.add_new();
+            buffer.path.push(path);
+            buffer.handle()
+        }
+    };
+    let buffer = editor.buffers.get_mut(buffer_handle);
+    buffer.capabilities = BufferCapabilities::log();
+
+    let content = buffer.content();
+    let end = content.end();
+    let max_line_count = content.line_count().min(text.split('\n').count()) - 1;
+    let mut line_count = 0;
+    let mut offset = 0;
+    for (line, new_line) in content.lines().zip(text.split('\n')) {
+        if line_count == max_line_count || line.as_str() != new_line {
+            break;
+        }
+        line_count += 1;
+        offset += new_line.len() + 1;
+    }
+
+    let from = BufferPosition::line_col(line_count as _, 0);
+    let range = BufferRange::between(from, end);
+    buffer.delete_range(&mut editor.word_database, range, &mut editor.events);
+    if offset < text.len() {
+        buffer.insert_text(
+            &mut editor.word_database,
+            from,
+            &text[offset..],
+            &mut editor.events,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(depth: u32, name: &str, value: &str, reference: JsonInteger) -> Variable {
+        Variable {
+            depth,
+            path: name.into(),
+            name: name.into(),
+            value: value.into(),
+            reference,
+            expanded_by_default: depth == 0,
+        }
+    }
+
+    #[test]
+    fn stack_frames_rendering() {
+        let root = Path::new("/code");
+        let frame = |name: &str, path: Option<&str>, line, column| StackFrame {
+            id: 0,
+            name: name.into(),
+            path: path.map(PathBuf::from),
+            position: BufferPosition::line_col(line, column),
+        };
+        let frames = [
+            frame("parse", Some("/code/src/parser.rs"), 11, 4),
+            frame("main", Some("/code/src/main.rs"), 2, 0),
+            frame("[external]", None, 0, 0),
+            frame("start", Some("/lib/start.c"), 0, 0),
+        ];
+
+        let mut text = String::new();
+        write_stack_frames(&frames, 1, root, &mut text);
+        assert_eq!(
+            concat!(
+                "  #0 parse src/parser.rs:12,5\n",
+                "> #1 main src/main.rs:3,1\n",
+                "  #2 [external]\n",
+                "  #3 start /lib/start.c:1,1\n",
+            ),
+            text
+        );
+    }
+
+    #[test]
+    fn variables_rendering() {
+        let variables = [
+            variable(0, "Locals", "", 1),
+            variable(1, "count", "3", 0),
+            variable(1, "point", "{x, y}", 2),
+            variable(2, "x", "1", 0),
+            variable(1, "list", "[2]", 3),
+            variable(0, "Globals", "", 4),
+        ];
+        let toggled = ["point".into(), "Globals".into()];
+
+        let mut text = String::new();
+        write_variables(&variables, &toggled, &mut text);
+        assert_eq!(
+            concat!(
+                "- Locals\n",
+                "    count = 3\n",
+                "  - point = {x, y}\n",
+                "      x = 1\n",
+                "  + list = [2]\n",
+                "+ Globals\n",
+            ),
+            text
+        );
+    }
+}
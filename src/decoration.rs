@@ -0,0 +1,223 @@
+use crate::{
+    buffer::BufferHandle,
+    buffer_position::{BufferPositionIndex, BufferRange},
+    theme::Color,
+};
+
+pub struct Decoration {
+    pub range: BufferRange,
+    pub color: Option<Color>,
+    pub underlined: bool,
+    pub text: String,
+    namespace: u32,
+}
+
+struct BufferDecorations {
+    buffer_handle: BufferHandle,
+    decorations: Vec<Decoration>,
+}
+
+#[derive(Default)]
+pub struct DecorationCollection {
+    namespaces: Vec<String>,
+    buffers: Vec<BufferDecorations>,
+}
+
+impl DecorationCollection {
+    fn namespace_index(&mut self, namespace: &str) -> u32 {
+        match self.namespaces.iter().position(|n| n == namespace) {
+            Some(index) => index as _,
+            None => {
+                self.namespaces.push(namespace.into());
+                (self.namespaces.len() - 1) as _
+            }
+        }
+    }
+
+    fn buffer_mut(&mut self, buffer_handle: BufferHandle) -> Option<&mut BufferDecorations> {
+        self.buffers
+            .iter_mut()
+            .find(|b| b.buffer_handle == buffer_handle)
+    }
+
+    pub fn add(
+        &mut self,
+        buffer_handle: BufferHandle,
+        namespace: &str,
+        range: BufferRange,
+        color: Option<Color>,
+        underlined: bool,
+        text: &str,
+    ) {
+        let namespace = self.namespace_index(namespace);
+        let index = match self
+            .buffers
+            .iter()
+            .position(|b| b.buffer_handle == buffer_handle)
+        {
+            Some(index) => index,
+            None => {
+                self.buffers.push(BufferDecorations {
+                    buffer_handle,
+                    decorations: Vec::new(),
+                });
+                self.buffers.len() - 1
+            }
+        };
+
+        let decorations = &mut self.buffers[index].decorations;
+        let index = decorations
+            .iter()
+            .position(|d| range.from < d.range.from)
+            .unwrap_or(decorations.len());
+        decorations.insert(
+            index,
+            Decoration {
+                range,
+                color,
+                underlined,
+                text: text.into(),
+                namespace,
+            },
+        );
+    }
+
+    pub fn clear(&mut self, buffer_handle: Option<BufferHandle>, namespace: &str) {
+        let namespace = match self.namespaces.iter().position(|n| n == namespace) {
+            Some(index) => index as u32,
+            None => return,
+        };
+        for buffer in &mut self.buffers {
+            if buffer_handle
+                .map(|h| h == buffer.buffer_handle)
+                .unwrap_or(true)
+            {
+                buffer.decorations.retain(|d| d.namespace != namespace);
+            }
+        }
+    }
+
+    pub fn clear_lines(
+        &mut self,
+        buffer_handle: BufferHandle,
+        namespace: &str,
+        from_line_index: BufferPositionIndex,
+        to_line_index: BufferPositionIndex,
+    ) {
+        let namespace = match self.namespaces.iter().position(|n| n == namespace) {
+            Some(index) => index as u32,
+            None => return,
+        };
+        if let Some(buffer) = self.buffer_mut(buffer_handle) {
+            buffer.decorations.retain(|d| {
+                d.namespace != namespace
+                    || d.range.to.line_index < from_line_index
+                    || d.range.from.line_index >= to_line_index
+            });
+        }
+    }
+
+    pub fn buffer_decorations(&self, buffer_handle: BufferHandle) -> &[Decoration] {
+        match self
+            .buffers
+            .iter()
+            .find(|b| b.buffer_handle == buffer_handle)
+        {
+            Some(buffer) => &buffer.decorations,
+            None => &[],
+        }
+    }
+
+    pub fn on_buffer_insert_text(&mut self, buffer_handle: BufferHandle, range: BufferRange) {
+        if let Some(buffer) = self.buffer_mut(buffer_handle) {
+            for decoration in &mut buffer.decorations {
+                decoration.range.from = decoration.range.from.insert(range);
+                decoration.range.to = decoration.range.to.insert(range);
+            }
+        }
+    }
+
+    pub fn on_buffer_delete_text(&mut self, buffer_handle: BufferHandle, range: BufferRange) {
+        if let Some(buffer) = self.buffer_mut(buffer_handle) {
+            for decoration in &mut buffer.decorations {
+                decoration.range.from = decoration.range.from.delete(range);
+                decoration.range.to = decoration.range.to.delete(range);
+            }
+        }
+    }
+
+    pub fn on_buffer_close(&mut self, buffer_handle: BufferHandle) {
+        self.buffers.retain(|b| b.buffer_handle != buffer_handle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer_position::BufferPosition;
+
+    fn range(from_line: u32, to_line: u32) -> BufferRange {
+        BufferRange::between(
+            BufferPosition::line_col(from_line as _, 0),
+            BufferPosition::line_col(to_line as _, 0),
+        )
+    }
+
+    #[test]
+    fn add_and_clear() {
+        let buffer_a = BufferHandle(0);
+        let buffer_b = BufferHandle(1);
+
+        let mut decorations = DecorationCollection::default();
+        decorations.add(buffer_a, "lint", range(4, 5), None, false, "b");
+        decorations.add(buffer_a, "coverage", range(1, 2), None, false, "a");
+        decorations.add(buffer_a, "lint", range(8, 9), None, false, "c");
+        decorations.add(buffer_b, "lint", range(0, 1), None, false, "d");
+
+        let texts = |decorations: &DecorationCollection, handle| {
+            decorations
+                .buffer_decorations(handle)
+                .iter()
+                .map(|d| d.text.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        assert_eq!("a,b,c", texts(&decorations, buffer_a));
+        assert_eq!("d", texts(&decorations, buffer_b));
+
+        decorations.clear(Some(buffer_a), "lint");
+        assert_eq!("a", texts(&decorations, buffer_a));
+        assert_eq!("d", texts(&decorations, buffer_b));
+
+        decorations.clear(None, "lint");
+        assert_eq!("", texts(&decorations, buffer_b));
+
+        decorations.on_buffer_close(buffer_a);
+        assert_eq!("", texts(&decorations, buffer_a));
+    }
+
+    #[test]
+    fn follow_edits() {
+        let buffer = BufferHandle(0);
+        let mut decorations = DecorationCollection::default();
+        decorations.add(buffer, "url", range(1, 1), None, true, "a");
+        decorations.add(buffer, "url", range(3, 3), None, true, "b");
+        decorations.add(buffer, "lint", range(3, 4), None, false, "c");
+
+        decorations.on_buffer_insert_text(buffer, range(0, 2));
+        decorations.on_buffer_delete_text(buffer, range(2, 3));
+        let lines = |decorations: &DecorationCollection| {
+            decorations
+                .buffer_decorations(buffer)
+                .iter()
+                .map(|d| (d.range.from.line_index, d.range.to.line_index))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![(2, 2), (4, 4), (4, 5)], lines(&decorations));
+
+        decorations.clear_lines(buffer, "url", 3, 5);
+        assert_eq!(vec![(2, 2), (4, 5)], lines(&decorations));
+        decorations.clear_lines(buffer, "url", 0, 2);
+        assert_eq!(vec![(2, 2), (4, 5)], lines(&decorations));
+    }
+}
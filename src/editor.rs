@@ -4,26 +4,46 @@ use std::{
 };
 
 use crate::{
-    buffer::{BufferCapabilities, BufferCollection, BufferReadError},
+    buffer::{BufferCapabilities, BufferCollection, BufferHandle, BufferReadError},
+    buffer_position::BufferPosition,
     buffer_view::{BufferViewCollection, BufferViewHandle},
     client::{Client, ClientHandle, ClientManager},
     command::CommandManager,
     config::Config,
-    editor_utils::{ReadLine, StatusBar, StringPool},
+    cursor::Cursor,
+    dap::Debugger,
+    decoration::DecorationCollection,
+    editor_utils::{MessageKind, ReadLine, StatusBar, StringPool},
     events::{
         ClientEvent, EditorEvent, EditorEventIter, EditorEventQueue, KeyParseAllError, KeyParser,
-        TargetClient,
+        ServerEvent, TargetClient,
     },
+    file_tree::FileTree,
+    formatter::FormatterCollection,
+    git,
     keymap::{KeyMapCollection, MatchResult},
+    linter::LinterCollection,
     lsp,
+    make::Make,
     mode::{Mode, ModeContext, ModeKind},
     pattern::Pattern,
     picker::Picker,
-    platform::{Key, Platform, ProcessHandle, ProcessTag},
-    register::{RegisterCollection, RegisterKey},
+    platform::{JobTag, Key, Platform, PlatformRequest, ProcessHandle, ProcessTag},
+    plugin::{
+        self, manager::PluginManager, remote::RemotePluginCollection, PanelCollection,
+        StatusSegments,
+    },
+    profile::{FrameProfiler, StartupProfile},
+    register::{RegisterCollection, RegisterKey, YankHistory},
+    script::ScriptEngine,
+    serialization::Serialize,
+    sign::SignCollection,
+    spawn::SpawnCollection,
     syntax::{HighlightResult, SyntaxCollection},
     theme::Theme,
+    url,
     word_database::WordDatabase,
+    workspace::Workspace,
 };
 
 #[derive(Clone, Copy)]
@@ -32,6 +52,7 @@ pub enum EditorControlFlow {
     Suspend,
     Quit,
     QuitAll,
+    QuitServer,
 }
 
 pub struct KeysIterator {
@@ -84,6 +105,7 @@ impl BufferedKeys {
 
 pub struct Editor {
     pub current_directory: PathBuf,
+    pub workspace: Workspace,
     pub config: Config,
     pub theme: Theme,
     pub syntaxes: SyntaxCollection,
@@ -97,20 +119,38 @@ pub struct Editor {
     pub buffered_keys: BufferedKeys,
     pub recording_macro: Option<RegisterKey>,
     pub registers: RegisterCollection,
+    pub yank_history: YankHistory,
     pub read_line: ReadLine,
     pub picker: Picker,
+    pub file_tree: FileTree,
     pub string_pool: StringPool,
 
     pub status_bar: StatusBar,
     pub aux_pattern: Pattern,
+    pub startup_profile: Option<StartupProfile>,
+    pub frame_profiler: FrameProfiler,
 
     pub commands: CommandManager,
     pub lsp: lsp::ClientManager,
+    pub git: git::RepoState,
+    pub formatters: FormatterCollection,
+    pub linters: LinterCollection,
+    pub make: Make,
+    pub debugger: Debugger,
+    pub spawns: SpawnCollection,
+    pub script: ScriptEngine,
+    pub remote_plugins: RemotePluginCollection,
+    pub plugin_manager: PluginManager,
+    pub panels: PanelCollection,
+    pub status_segments: StatusSegments,
+    pub decorations: DecorationCollection,
+    pub signs: SignCollection,
     pub events: EditorEventQueue,
 }
 impl Editor {
     pub fn new(current_directory: PathBuf) -> Self {
         Self {
+            workspace: Workspace::new(&current_directory),
             current_directory,
             config: Config::default(),
             theme: Theme::default(),
@@ -126,15 +166,32 @@ impl Editor {
             buffered_keys: BufferedKeys::default(),
             recording_macro: None,
             registers: RegisterCollection::new(),
+            yank_history: YankHistory::default(),
             read_line: ReadLine::default(),
             picker: Picker::default(),
+            file_tree: FileTree::default(),
             string_pool: StringPool::default(),
 
             status_bar: StatusBar::new(),
             aux_pattern: Pattern::new(),
+            startup_profile: None,
+            frame_profiler: FrameProfiler::default(),
 
             commands: CommandManager::new(),
             lsp: lsp::ClientManager::new(),
+            git: git::RepoState::default(),
+            formatters: FormatterCollection::default(),
+            linters: LinterCollection::default(),
+            make: Make::default(),
+            debugger: Debugger::default(),
+            spawns: SpawnCollection::default(),
+            script: ScriptEngine::default(),
+            remote_plugins: RemotePluginCollection::default(),
+            plugin_manager: PluginManager::default(),
+            panels: PanelCollection::default(),
+            status_segments: StatusSegments::default(),
+            decorations: DecorationCollection::default(),
+            signs: SignCollection::default(),
             events: EditorEventQueue::default(),
         }
     }
@@ -157,7 +214,7 @@ impl Editor {
             buffer.path.push(path);
             buffer.capabilities = capabilities;
 
-            match buffer.read_from_file(&mut self.word_database, &mut self.events) {
+            match buffer.read_from_file(&mut self.word_database, &self.config, &mut self.events) {
                 Ok(()) => {
                     let handle = self.buffer_views.add_new(client_handle, buffer.handle());
                     Ok(handle)
@@ -171,6 +228,14 @@ impl Editor {
         }
     }
 
+    pub fn workspace_root(&self, buffer_handle: Option<BufferHandle>) -> PathBuf {
+        let path = match buffer_handle {
+            Some(handle) => &self.buffers.get(handle).path,
+            None => Path::new(""),
+        };
+        self.workspace.root_for(&self.current_directory, path)
+    }
+
     pub fn execute_keys(
         &mut self,
         platform: &mut Platform,
@@ -243,6 +308,7 @@ impl Editor {
             } else {
                 0
             };
+            let panels_height = self.panels.bottom_height(c.viewport_size.1);
 
             if let Some(handle) = c.buffer_view_handle() {
                 let buffer_view = self.buffer_views.get(handle);
@@ -252,9 +318,15 @@ impl Editor {
                 }
             }
 
-            c.update_view(self, picker_height);
+            c.update_view(self, picker_height + panels_height);
+            if focused_handle == Some(c.handle()) {
+                self.file_tree
+                    .update_scroll(c.height.saturating_sub(1) as _);
+            }
         }
 
+        clients.update_followers(&mut self.buffer_views, &mut self.events);
+
         needs_redraw
     }
 
@@ -273,9 +345,23 @@ impl Editor {
                         Some(handle) => handle,
                         None => return EditorControlFlow::Continue,
                     },
+                    TargetClient::FocusedOrSender => {
+                        clients.focused_client().unwrap_or(client_handle)
+                    }
                 };
 
+                let client = clients.get_mut(client_handle);
+                if client.following.is_some() {
+                    if key == Key::Esc {
+                        client.following = None;
+                    }
+                    return EditorControlFlow::Continue;
+                }
+
                 if clients.focus_client(client_handle) {
+                    self.events.enqueue(EditorEvent::ClientFocus {
+                        handle: client_handle,
+                    });
                     self.recording_macro = None;
                     self.buffered_keys.0.clear();
 
@@ -302,14 +388,28 @@ impl Editor {
                 EditorControlFlow::Continue
             }
             ClientEvent::Command(target, command) => {
+                let sender_handle = client_handle;
                 let client_handle = match target {
                     TargetClient::Sender => client_handle,
                     TargetClient::Focused => match clients.focused_client() {
                         Some(handle) => handle,
                         None => return EditorControlFlow::Continue,
                     },
+                    TargetClient::FocusedOrSender => {
+                        clients.focused_client().unwrap_or(client_handle)
+                    }
                 };
 
+                // commands sent without an ui have their output sent back instead of
+                // replacing what the status bar shows to the interactive clients
+                let sender_has_ui = clients.get(sender_handle).has_ui();
+                let mut previous_message = self.string_pool.acquire();
+                let mut previous_kind = MessageKind::Info;
+                if !sender_has_ui {
+                    self.status_bar
+                        .swap_message(&mut previous_kind, &mut previous_message);
+                }
+
                 let mut command = self.string_pool.acquire_with(command);
                 let flow = CommandManager::eval(
                     self,
@@ -319,14 +419,102 @@ impl Editor {
                     &mut command,
                 );
                 self.string_pool.release(command);
+                self.trigger_event_handlers(platform, clients);
+
+                if !sender_has_ui {
+                    let (kind, message) = self.status_bar.message();
+                    if !message.is_empty() {
+                        let event = match kind {
+                            MessageKind::Info => ServerEvent::CommandOutput(message),
+                            MessageKind::Error => ServerEvent::CommandError(message),
+                        };
+                        let mut buf = platform.buf_pool.acquire();
+                        event.serialize(buf.write());
+                        platform.requests.enqueue(PlatformRequest::WriteToClient {
+                            handle: sender_handle,
+                            buf,
+                        });
+                    }
+                    self.status_bar
+                        .swap_message(&mut previous_kind, &mut previous_message);
+                }
+                self.string_pool.release(previous_message);
+
                 flow
             }
+            ClientEvent::StdinInput(target, bytes) => {
+                let buffer_handle = match clients.get(client_handle).stdin_buffer_handle {
+                    Some(handle) => handle,
+                    None => {
+                        let buffer = self.buffers.add_new();
+                        buffer.path.clear();
+                        buffer.path.push("<stdin>");
+                        buffer.capabilities = BufferCapabilities::log();
+                        let buffer_handle = buffer.handle();
+                        clients.get_mut(client_handle).stdin_buffer_handle = Some(buffer_handle);
+
+                        let view_client_handle = match target {
+                            TargetClient::Sender if clients.get(client_handle).has_ui() => {
+                                Some(client_handle)
+                            }
+                            _ => clients.focused_client(),
+                        };
+                        if let Some(handle) = view_client_handle {
+                            let buffer_view_handle =
+                                self.buffer_views.add_new(handle, buffer_handle);
+                            clients.get_mut(handle).set_buffer_view_handle(
+                                Some(buffer_view_handle),
+                                &self.buffer_views,
+                                &mut self.events,
+                            );
+                        }
+
+                        buffer_handle
+                    }
+                };
+
+                let client = clients.get_mut(client_handle);
+                let mut bytes_buf = std::mem::take(&mut client.stdin_utf8_tail);
+                bytes_buf.extend_from_slice(bytes);
+                let len = bytes_buf.len() - incomplete_utf8_tail_len(&bytes_buf);
+                client.stdin_utf8_tail.extend_from_slice(&bytes_buf[len..]);
+
+                let text = String::from_utf8_lossy(&bytes_buf[..len]);
+                let buffer = self.buffers.get_mut(buffer_handle);
+                let position = buffer.content().end();
+                buffer.insert_text(&mut self.word_database, position, &text, &mut self.events);
+
+                self.trigger_event_handlers(platform, clients);
+
+                if self.config.stdin_follow {
+                    let position = self.buffers.get(buffer_handle).content().end();
+                    for client in clients.iter() {
+                        let handle = match client.buffer_view_handle() {
+                            Some(handle) => handle,
+                            None => continue,
+                        };
+                        let buffer_view = self.buffer_views.get_mut(handle);
+                        if buffer_view.buffer_handle == buffer_handle {
+                            let mut cursors = buffer_view.cursors.mut_guard();
+                            cursors.clear();
+                            cursors.add(Cursor {
+                                anchor: position,
+                                position,
+                            });
+                        }
+                    }
+                }
+
+                EditorControlFlow::Continue
+            }
+            ClientEvent::Handshake(..) => EditorControlFlow::Continue,
         }
     }
 
     pub fn on_idle(&mut self, clients: &mut ClientManager, platform: &mut Platform) {
         self.events.enqueue(EditorEvent::Idle);
         self.trigger_event_handlers(platform, clients);
+        lsp::ClientManager::on_idle(self, platform, clients);
     }
 
     pub fn on_process_spawned(
@@ -337,10 +525,25 @@ impl Editor {
     ) {
         match tag {
             ProcessTag::Buffer(index) => self.buffers.on_process_spawned(platform, index, handle),
-            ProcessTag::FindFiles => (),
+            ProcessTag::FindFiles(_) => (),
+            ProcessTag::Formatter(index) => {
+                self.formatters.on_process_spawned(platform, index, handle)
+            }
+            ProcessTag::GitRepoState => (),
+            ProcessTag::Linter(index) => self.linters.on_process_spawned(platform, index, handle),
+            ProcessTag::Make => (),
+            ProcessTag::Dap => Debugger::on_process_spawned(self, platform, handle),
+            ProcessTag::OpenUrl => (),
             ProcessTag::Lsp(client_handle) => {
                 lsp::ClientManager::on_process_spawned(self, platform, client_handle, handle)
             }
+            ProcessTag::Plugin(plugin_handle) => {
+                RemotePluginCollection::on_process_spawned(self, platform, plugin_handle, handle)
+            }
+            ProcessTag::PluginInstall(_) => (),
+            ProcessTag::Spawn(index, stage) => self
+                .spawns
+                .on_process_spawned(platform, index, stage, handle),
         }
     }
 
@@ -358,14 +561,64 @@ impl Editor {
                 bytes,
                 &mut self.events,
             ),
-            ProcessTag::FindFiles => {
-                self.mode
-                    .picker_state
-                    .on_process_output(&mut self.picker, &self.read_line, bytes)
-            }
+            ProcessTag::FindFiles(token) => self.mode.picker_state.on_process_output(
+                &mut self.picker,
+                &self.read_line,
+                token,
+                bytes,
+            ),
+            ProcessTag::Formatter(index) => self.formatters.on_process_output(index, bytes),
+            ProcessTag::GitRepoState => self.git.on_process_output(bytes),
+            ProcessTag::Linter(index) => self.linters.on_process_output(index, bytes),
+            ProcessTag::Make => Make::on_process_output(self, bytes),
+            ProcessTag::Dap => Debugger::on_process_output(self, platform, clients, bytes),
+            ProcessTag::OpenUrl => (),
             ProcessTag::Lsp(client_handle) => {
                 lsp::ClientManager::on_process_output(self, platform, clients, client_handle, bytes)
             }
+            ProcessTag::Plugin(plugin_handle) => RemotePluginCollection::on_process_output(
+                self,
+                platform,
+                clients,
+                plugin_handle,
+                bytes,
+            ),
+            ProcessTag::PluginInstall(_) => (),
+            ProcessTag::Spawn(index, stage) => {
+                self.spawns.on_process_output(platform, index, stage, bytes)
+            }
+        }
+
+        self.trigger_event_handlers(platform, clients);
+    }
+
+    pub fn on_process_error_output(&mut self, tag: ProcessTag, bytes: &[u8]) {
+        match tag {
+            ProcessTag::Spawn(index, _) => self.spawns.on_process_error_output(index, bytes),
+            ProcessTag::PluginInstall(index) => {
+                self.plugin_manager.on_process_error_output(index, bytes)
+            }
+            _ => (),
+        }
+    }
+
+    pub fn on_job_finished(
+        &mut self,
+        platform: &mut Platform,
+        clients: &mut ClientManager,
+        tag: JobTag,
+        bytes: &[u8],
+    ) {
+        match tag {
+            JobTag::TodoWorkspace(token) => self.mode.picker_state.on_job_finished(
+                &mut self.picker,
+                &self.read_line,
+                token,
+                bytes,
+            ),
+            JobTag::LspWatchedFiles(client_handle) => {
+                lsp::ClientManager::on_watched_files_scanned(self, platform, client_handle, bytes)
+            }
         }
 
         self.trigger_event_handlers(platform, clients);
@@ -382,12 +635,30 @@ impl Editor {
                 self.buffers
                     .on_process_exit(&mut self.word_database, index, &mut self.events)
             }
-            ProcessTag::FindFiles => self
-                .mode
-                .picker_state
-                .on_process_exit(&mut self.picker, &self.read_line),
+            ProcessTag::FindFiles(token) => {
+                self.mode
+                    .picker_state
+                    .on_process_exit(&mut self.picker, &self.read_line, token)
+            }
+            ProcessTag::Formatter(index) => {
+                FormatterCollection::on_process_exit(self, platform, index)
+            }
+            ProcessTag::GitRepoState => self.git.on_process_exit(platform, &self.current_directory),
+            ProcessTag::Linter(index) => LinterCollection::on_process_exit(self, platform, index),
+            ProcessTag::Make => Make::on_process_exit(self, clients),
+            ProcessTag::Dap => Debugger::on_process_exit(self, platform, clients),
+            ProcessTag::OpenUrl => (),
             ProcessTag::Lsp(client_handle) => {
-                lsp::ClientManager::on_process_exit(self, client_handle)
+                lsp::ClientManager::on_process_exit(self, platform, client_handle)
+            }
+            ProcessTag::Plugin(plugin_handle) => {
+                RemotePluginCollection::on_process_exit(self, platform, plugin_handle)
+            }
+            ProcessTag::PluginInstall(index) => {
+                PluginManager::on_process_exit(self, platform, clients, index)
+            }
+            ProcessTag::Spawn(index, stage) => {
+                SpawnCollection::on_process_exit(self, platform, clients, index, stage)
             }
         }
 
@@ -403,33 +674,137 @@ impl Editor {
             }
 
             lsp::ClientManager::on_editor_events(self, platform);
+            plugin::on_editor_events(self, platform, clients);
 
             let mut events = EditorEventIter::new();
             while let Some(event) = events.next(&self.events) {
                 match *event {
-                    EditorEvent::Idle => (),
+                    EditorEvent::Idle => {
+                        self.git.refresh(platform, &self.current_directory);
+                        self.buffers.index_pending_words(&mut self.word_database);
+                        LinterCollection::on_idle(self, platform);
+                    }
                     EditorEvent::BufferRead { handle } => {
                         let buffer = self.buffers.get_mut(handle);
                         buffer.refresh_syntax(&self.syntaxes);
                         self.buffer_views.on_buffer_load(buffer);
+                        self.linters.on_buffer_change(platform, handle);
+                        self.decorations
+                            .clear(Some(handle), url::URL_DECORATION_NAMESPACE);
+                        url::refresh_decorations(
+                            &mut self.decorations,
+                            handle,
+                            buffer.content(),
+                            0..buffer.content().line_count(),
+                        );
+
+                        if git::is_commit_message_path(&buffer.path)
+                            && !buffer
+                                .content()
+                                .lines()
+                                .any(|l| git::is_scissors_line(l.as_str()))
+                        {
+                            let mut text = self.string_pool.acquire();
+                            let end = buffer.content().end();
+                            if end.column_byte_index > 0 {
+                                text.push('\n');
+                            }
+                            let len = text.len();
+                            if git::write_staged_diff_as_comment(&self.current_directory, &mut text)
+                                .is_ok()
+                                && text.len() > len
+                            {
+                                buffer.insert_text(
+                                    &mut self.word_database,
+                                    end,
+                                    &text,
+                                    &mut self.events,
+                                );
+                                buffer.commit_edits();
+                            }
+                            self.string_pool.release(text);
+                        }
                     }
                     EditorEvent::BufferInsertText { handle, range, .. } => {
                         self.buffer_views.on_buffer_insert_text(handle, range);
+                        for client in clients.iter_mut() {
+                            client.on_buffer_insert_text(&self.buffer_views, handle, range);
+                        }
+                        self.decorations.on_buffer_insert_text(handle, range);
+                        self.signs.on_buffer_insert_text(handle, range);
+                        self.linters.on_buffer_change(platform, handle);
+                        url::refresh_decorations(
+                            &mut self.decorations,
+                            handle,
+                            self.buffers.get(handle).content(),
+                            range.from.line_index as usize..range.to.line_index as usize + 1,
+                        );
                     }
                     EditorEvent::BufferDeleteText { handle, range } => {
                         self.buffer_views.on_buffer_delete_text(handle, range);
+                        for client in clients.iter_mut() {
+                            client.on_buffer_delete_text(&self.buffer_views, handle, range);
+                        }
+                        self.decorations.on_buffer_delete_text(handle, range);
+                        self.signs.on_buffer_delete_text(handle, range);
+                        self.linters.on_buffer_change(platform, handle);
+                        let line_index = range.from.line_index as usize;
+                        url::refresh_decorations(
+                            &mut self.decorations,
+                            handle,
+                            self.buffers.get(handle).content(),
+                            line_index..line_index + 1,
+                        );
                     }
                     EditorEvent::BufferWrite { handle, new_path } => {
                         if new_path {
                             self.buffers.get_mut(handle).refresh_syntax(&self.syntaxes);
+                            self.linters.on_buffer_change(platform, handle);
+                        }
+                        self.git.refresh(platform, &self.current_directory);
+                        FormatterCollection::on_buffer_write(self, platform, handle);
+                        for client in clients.iter_mut() {
+                            if client.wait_buffer_handle == Some(handle) {
+                                client.wait_buffer_saved = true;
+                            }
                         }
                     }
                     EditorEvent::BufferClose { handle } => {
-                        self.buffers
-                            .remove_from_editor_event_handler(handle, &mut self.word_database);
+                        self.decorations.on_buffer_close(handle);
+                        self.signs.on_buffer_close(handle);
+                        self.formatters.on_buffer_close(handle);
+                        self.linters.on_buffer_close(handle);
+                        self.make.on_buffer_close(handle);
                         for client in clients.iter_mut() {
                             client.on_buffer_close(self, handle);
+
+                            if client.wait_buffer_handle == Some(handle) {
+                                client.wait_buffer_handle = None;
+                                let handle = client.handle();
+                                if !client.wait_buffer_saved {
+                                    let mut buf = platform.buf_pool.acquire();
+                                    ServerEvent::CommandError("buffer closed without saving")
+                                        .serialize(buf.write());
+                                    platform
+                                        .requests
+                                        .enqueue(PlatformRequest::WriteToClient { handle, buf });
+                                }
+                                platform
+                                    .requests
+                                    .enqueue(PlatformRequest::CloseClient { handle });
+                            }
                         }
+                        let position = self
+                            .buffer_views
+                            .iter()
+                            .find(|v| v.buffer_handle == handle)
+                            .map(|v| v.cursors.main_cursor().position)
+                            .unwrap_or(BufferPosition::zero());
+                        self.buffers.remove_from_editor_event_handler(
+                            handle,
+                            position,
+                            &mut self.word_database,
+                        );
                         self.buffer_views.remove_buffer_views(handle);
                     }
                     EditorEvent::FixCursors { handle, cursors } => {
@@ -443,6 +818,9 @@ impl Editor {
                     EditorEvent::BufferViewLostFocus { handle } => {
                         let buffer_view = self.buffer_views.get(handle);
                         let buffer_handle = buffer_view.buffer_handle;
+                        clients
+                            .get_mut(buffer_view.client_handle)
+                            .alternate_buffer_handle = Some(buffer_handle);
                         let buffer = self.buffers.get(buffer_handle);
                         let should_close = buffer.capabilities.auto_close && !buffer.needs_save();
                         let any_view = !clients
@@ -455,8 +833,29 @@ impl Editor {
                             self.buffers.defer_remove(buffer_handle, &mut self.events);
                         }
                     }
+                    EditorEvent::BufferProcessExit { .. } => (),
+                    EditorEvent::ModeChange { .. } => (),
+                    EditorEvent::ClientFocus { .. } => (),
                 }
             }
         }
     }
 }
+
+// length of a utf8 sequence at the end of `bytes` that was cut short and may still be completed
+fn incomplete_utf8_tail_len(bytes: &[u8]) -> usize {
+    for len in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - len];
+        if byte & 0b1100_0000 == 0b1000_0000 {
+            continue;
+        }
+        let sequence_len = match byte {
+            0b1111_0000.. => 4,
+            0b1110_0000.. => 3,
+            0b1100_0000.. => 2,
+            _ => 1,
+        };
+        return if sequence_len > len { len } else { 0 };
+    }
+    0
+}
@@ -1,10 +1,23 @@
-use std::{fmt, process::Command};
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    process::Command,
+    str::FromStr,
+};
 
 use crate::{
-    client::ClientManager,
+    buffer::BufferCapabilities,
+    buffer_position::{BufferPosition, BufferRange},
+    buffer_view::BufferViewHandle,
+    client::{ClientHandle, ClientManager},
     command::{CommandManager, CommandTokenizer},
     editor::{BufferedKeys, Editor, EditorControlFlow, KeysIterator},
+    lsp,
     platform::{Key, Platform},
+    profile::{begin_startup_item, end_startup_item},
     word_database::{WordIter, WordKind},
 };
 
@@ -15,10 +28,29 @@ pub enum ReadLinePoll {
     Canceled,
 }
 
+const READ_LINE_HISTORY_CAPACITY: usize = 10;
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReadLineCompletionSource {
+    #[default]
+    None,
+    Words,
+    Custom,
+}
+struct ReadLineHistory {
+    prompt: String,
+    entries: VecDeque<String>,
+}
+
 #[derive(Default)]
 pub struct ReadLine {
     prompt: String,
     input: String,
+    completion_source: ReadLineCompletionSource,
+    completion_entries: Vec<String>,
+    histories: Vec<ReadLineHistory>,
+    history_enabled: bool,
+    history_index: usize,
 }
 impl ReadLine {
     pub fn prompt(&self) -> &str {
@@ -28,6 +60,9 @@ impl ReadLine {
     pub fn set_prompt(&mut self, prompt: &str) {
         self.prompt.clear();
         self.prompt.push_str(prompt);
+        self.completion_source = ReadLineCompletionSource::None;
+        self.completion_entries.clear();
+        self.history_enabled = false;
     }
 
     pub fn input(&self) -> &str {
@@ -38,6 +73,78 @@ impl ReadLine {
         &mut self.input
     }
 
+    pub fn completion_source(&self) -> ReadLineCompletionSource {
+        self.completion_source
+    }
+
+    pub fn set_completion_source(&mut self, source: ReadLineCompletionSource) {
+        self.completion_source = source;
+    }
+
+    pub fn completion_entries(&self) -> impl Iterator<Item = &str> {
+        self.completion_entries.iter().map(String::as_str)
+    }
+
+    pub fn add_completion_entry(&mut self, entry: &str) {
+        self.completion_entries.push(entry.into());
+    }
+
+    pub fn enable_history(&mut self) {
+        self.history_enabled = true;
+        self.history_index = self.history().map(|h| h.entries.len()).unwrap_or(0);
+    }
+
+    fn history(&self) -> Option<&ReadLineHistory> {
+        self.histories.iter().find(|h| h.prompt == self.prompt)
+    }
+
+    fn navigate_history(&mut self, offset: isize) {
+        if !self.history_enabled {
+            return;
+        }
+        let history = match self.histories.iter().find(|h| h.prompt == self.prompt) {
+            Some(history) => history,
+            None => return,
+        };
+
+        let index = self.history_index as isize + offset;
+        self.history_index = index.clamp(0, history.entries.len() as _) as _;
+        self.input.clear();
+        if let Some(entry) = history.entries.get(self.history_index) {
+            self.input.push_str(entry);
+        }
+    }
+
+    fn add_to_history(&mut self) {
+        if !self.history_enabled || self.input.is_empty() {
+            return;
+        }
+
+        let index = match self.histories.iter().position(|h| h.prompt == self.prompt) {
+            Some(index) => index,
+            None => {
+                self.histories.push(ReadLineHistory {
+                    prompt: self.prompt.clone(),
+                    entries: VecDeque::with_capacity(READ_LINE_HISTORY_CAPACITY),
+                });
+                self.histories.len() - 1
+            }
+        };
+        let entries = &mut self.histories[index].entries;
+        if entries.back() == Some(&self.input) {
+            return;
+        }
+
+        let mut entry = if entries.len() == READ_LINE_HISTORY_CAPACITY {
+            entries.pop_front().unwrap()
+        } else {
+            String::new()
+        };
+        entry.clear();
+        entry.push_str(&self.input);
+        entries.push_back(entry);
+    }
+
     pub fn poll(
         &mut self,
         platform: &mut Platform,
@@ -47,7 +154,18 @@ impl ReadLine {
     ) -> ReadLinePoll {
         match keys_iter.next(buffered_keys) {
             Key::Esc | Key::Ctrl('c') => ReadLinePoll::Canceled,
-            Key::Enter | Key::Ctrl('m') => ReadLinePoll::Submitted,
+            Key::Enter | Key::Ctrl('m') => {
+                self.add_to_history();
+                ReadLinePoll::Submitted
+            }
+            Key::Up => {
+                self.navigate_history(-1);
+                ReadLinePoll::Pending
+            }
+            Key::Down => {
+                self.navigate_history(1);
+                ReadLinePoll::Pending
+            }
             Key::Home | Key::Ctrl('u') => {
                 self.input.clear();
                 ReadLinePoll::Pending
@@ -83,6 +201,8 @@ impl ReadLine {
     }
 }
 
+const MESSAGE_HISTORY_CAPACITY: usize = 100;
+
 #[derive(Debug, Clone, Copy)]
 pub enum MessageKind {
     Info,
@@ -92,12 +212,14 @@ pub enum MessageKind {
 pub struct StatusBar {
     kind: MessageKind,
     message: String,
+    history: VecDeque<(MessageKind, String)>,
 }
 impl StatusBar {
     pub fn new() -> Self {
         Self {
             kind: MessageKind::Info,
             message: String::new(),
+            history: VecDeque::new(),
         }
     }
 
@@ -105,15 +227,50 @@ impl StatusBar {
         (self.kind, &self.message)
     }
 
+    pub fn history(&self) -> impl Iterator<Item = (MessageKind, &str)> {
+        let current = if self.message.is_empty() {
+            None
+        } else {
+            Some((self.kind, &self.message[..]))
+        };
+        self.history
+            .iter()
+            .map(|(kind, message)| (*kind, &message[..]))
+            .chain(current)
+    }
+
     pub fn clear(&mut self) {
+        self.push_message_to_history();
         self.message.clear();
     }
 
+    pub fn swap_message(&mut self, kind: &mut MessageKind, message: &mut String) {
+        std::mem::swap(&mut self.kind, kind);
+        std::mem::swap(&mut self.message, message);
+    }
+
     pub fn write(&mut self, kind: MessageKind) -> EditorOutputWrite {
+        self.push_message_to_history();
         self.kind = kind;
         self.message.clear();
         EditorOutputWrite(&mut self.message)
     }
+
+    fn push_message_to_history(&mut self) {
+        if self.message.is_empty() {
+            return;
+        }
+
+        let mut entry = if self.history.len() == MESSAGE_HISTORY_CAPACITY {
+            self.history.pop_front().unwrap()
+        } else {
+            (self.kind, String::new())
+        };
+        entry.0 = self.kind;
+        entry.1.clear();
+        entry.1.push_str(&self.message);
+        self.history.push_back(entry);
+    }
 }
 pub struct EditorOutputWrite<'a>(&'a mut String);
 impl<'a> EditorOutputWrite<'a> {
@@ -126,6 +283,112 @@ impl<'a> EditorOutputWrite<'a> {
     }
 }
 
+const LOG_CAPACITY: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warning,
+    Info,
+    Debug,
+}
+impl LogLevel {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Info => "info",
+            Self::Debug => "debug",
+        }
+    }
+}
+impl FromStr for LogLevel {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "warning" => Ok(Self::Warning),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Logger {
+    entries: VecDeque<(LogLevel, String)>,
+    file_path: String,
+    file: Option<io::BufWriter<File>>,
+}
+impl Logger {
+    pub fn entries(&self) -> impl Iterator<Item = (LogLevel, &str)> {
+        self.entries
+            .iter()
+            .map(|(level, message)| (*level, &message[..]))
+    }
+
+    pub fn file_path(&self) -> Option<&str> {
+        if self.file.is_some() {
+            Some(&self.file_path)
+        } else {
+            None
+        }
+    }
+
+    pub fn set_file_path(&mut self, path: &str) -> io::Result<()> {
+        self.file_path.clear();
+        self.file = None;
+        if path.is_empty() {
+            return Ok(());
+        }
+
+        let mut file = io::BufWriter::new(File::create(path)?);
+        for (level, message) in &self.entries {
+            writeln!(file, "{}: {}", level.name(), message)?;
+        }
+        file.flush()?;
+
+        self.file_path.push_str(path);
+        self.file = Some(file);
+        Ok(())
+    }
+
+    pub fn write(&mut self, level: LogLevel) -> LogWrite<'_> {
+        let mut entry = if self.entries.len() == LOG_CAPACITY {
+            self.entries.pop_front().unwrap()
+        } else {
+            (level, String::new())
+        };
+        entry.0 = level;
+        entry.1.clear();
+        self.entries.push_back(entry);
+        LogWrite(self)
+    }
+}
+pub struct LogWrite<'a>(&'a mut Logger);
+impl<'a> LogWrite<'a> {
+    pub fn str(&mut self, message: &str) {
+        if let Some((_, entry)) = self.0.entries.back_mut() {
+            entry.push_str(message);
+        }
+    }
+
+    pub fn fmt(&mut self, args: fmt::Arguments) {
+        if let Some((_, entry)) = self.0.entries.back_mut() {
+            let _ = fmt::write(entry, args);
+        }
+    }
+}
+impl<'a> Drop for LogWrite<'a> {
+    fn drop(&mut self) {
+        if let (Some(file), Some((level, message))) = (&mut self.0.file, self.0.entries.back()) {
+            let _ = writeln!(file, "{}: {}", level.name(), message);
+            let _ = file.flush();
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct StringPool {
     pool: Vec<String>,
@@ -167,16 +430,64 @@ pub const fn hash_bytes(mut bytes: &[u8]) -> u64 {
     hash
 }
 
+pub fn is_valid_env_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => (),
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 pub fn parse_process_command(command: &str) -> Option<Command> {
     let mut tokenizer = CommandTokenizer(command);
-    let name = tokenizer.next()?;
+    let mut vars = Vec::new();
+    let name = loop {
+        let token = tokenizer.next()?;
+        match token.split_once('=') {
+            Some((key, value)) if is_valid_env_var_name(key) => vars.push((key, value)),
+            _ => break token,
+        }
+    };
+
     let mut command = Command::new(name);
+    command.envs(vars);
     for arg in tokenizer {
         command.arg(arg);
     }
     Some(command)
 }
 
+// splits a process command at each unquoted `|` token
+pub struct ProcessPipelineIter<'a>(Option<&'a str>);
+impl<'a> ProcessPipelineIter<'a> {
+    pub fn new(command: &'a str) -> Self {
+        Self(Some(command))
+    }
+}
+impl<'a> Iterator for ProcessPipelineIter<'a> {
+    type Item = &'a str;
+    fn next(&mut self) -> Option<Self::Item> {
+        let command = self.0?;
+        let mut tokens = CommandTokenizer(command);
+        loop {
+            let rest = tokens.0.trim_start_matches(&[' ', '\t'][..]);
+            match tokens.next() {
+                Some(token) if token == "|" && token.as_ptr() == rest.as_ptr() => {
+                    let end = rest.as_ptr() as usize - command.as_ptr() as usize;
+                    self.0 = Some(tokens.0);
+                    return Some(&command[..end]);
+                }
+                Some(_) => (),
+                None => {
+                    self.0 = None;
+                    return Some(command);
+                }
+            }
+        }
+    }
+}
+
 pub fn load_config(
     editor: &mut Editor,
     platform: &mut Platform,
@@ -184,12 +495,40 @@ pub fn load_config(
     config_name: &str,
     config_content: &str,
 ) -> EditorControlFlow {
-    for (line_index, line) in config_content.lines().enumerate() {
+    let mut syntax_profile_index = None;
+    let mut lines = config_content.lines().enumerate();
+    while let Some((line_index, line)) = lines.next() {
         if line.is_empty() || line.starts_with('#') {
             continue;
         }
 
         let mut command = editor.string_pool.acquire_with(line);
+        while CommandTokenizer::has_unfinished_balanced_token(&command) {
+            match lines.next() {
+                Some((_, line)) => {
+                    command.push('\n');
+                    command.push_str(line);
+                }
+                None => break,
+            }
+        }
+
+        if editor.startup_profile.is_some() {
+            let mut tokens = CommandTokenizer(&command);
+            match tokens.next() {
+                Some("syntax") => {
+                    end_startup_item(&mut editor.startup_profile, syntax_profile_index);
+                    let glob = tokens.next().unwrap_or("");
+                    let name = format!("syntax {}", glob);
+                    syntax_profile_index = begin_startup_item(&mut editor.startup_profile, &name);
+                }
+                Some(name) if name.starts_with("syntax-") => (),
+                _ => {
+                    end_startup_item(&mut editor.startup_profile, syntax_profile_index.take());
+                }
+            }
+        }
+
         let result = CommandManager::try_eval(editor, platform, clients, None, &mut command);
         editor.string_pool.release(command);
 
@@ -214,5 +553,239 @@ pub fn load_config(
         }
     }
 
+    end_startup_item(&mut editor.startup_profile, syntax_profile_index);
     EditorControlFlow::Continue
 }
+
+pub const BUFFER_LIST_PATH: &str = "buffers.list";
+
+pub fn show_text_in_buffer(
+    editor: &mut Editor,
+    clients: &mut ClientManager,
+    client_handle: ClientHandle,
+    path: &Path,
+    text: &str,
+) -> Option<BufferViewHandle> {
+    let buffer_view_handle =
+        match editor.buffer_view_handle_from_path(client_handle, path, BufferCapabilities::log()) {
+            Ok(handle) => handle,
+            Err(error) => {
+                editor
+                    .status_bar
+                    .write(MessageKind::Error)
+                    .fmt(format_args!("{}", error));
+                return None;
+            }
+        };
+
+    let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+    let buffer = editor.buffers.get_mut(buffer_handle);
+    let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+    buffer.delete_range(&mut editor.word_database, range, &mut editor.events);
+    buffer.insert_text(
+        &mut editor.word_database,
+        BufferPosition::zero(),
+        text,
+        &mut editor.events,
+    );
+
+    clients.get_mut(client_handle).set_buffer_view_handle(
+        Some(buffer_view_handle),
+        &editor.buffer_views,
+        &mut editor.events,
+    );
+    Some(buffer_view_handle)
+}
+
+// renames the file and updates open buffers, after lsp servers had a chance to react
+pub fn rename_file(
+    editor: &mut Editor,
+    platform: &mut Platform,
+    old_path: &Path,
+    new_path: &Path,
+) -> io::Result<()> {
+    if let Some(parent) = new_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(old_path, new_path)?;
+
+    let renamed_buffers = editor.buffers.rename_paths(
+        &editor.current_directory,
+        old_path,
+        new_path,
+        &editor.syntaxes,
+    );
+    lsp::ClientManager::did_rename_file(editor, platform, old_path, new_path, &renamed_buffers);
+    if editor.file_tree.is_visible() {
+        editor.file_tree.refresh();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_line_history() {
+        fn submit(read_line: &mut ReadLine, input: &str) {
+            read_line.input.clear();
+            read_line.input.push_str(input);
+            read_line.add_to_history();
+        }
+
+        let mut read_line = ReadLine::default();
+        read_line.set_prompt("search:");
+        submit(&mut read_line, "ignored");
+        read_line.enable_history();
+        submit(&mut read_line, "first");
+        submit(&mut read_line, "second");
+        submit(&mut read_line, "second");
+
+        read_line.set_prompt("other:");
+        read_line.enable_history();
+        submit(&mut read_line, "other");
+
+        read_line.set_prompt("search:");
+        read_line.enable_history();
+        read_line.input.clear();
+        read_line.navigate_history(-1);
+        assert_eq!("second", read_line.input());
+        read_line.navigate_history(-1);
+        assert_eq!("first", read_line.input());
+        read_line.navigate_history(-1);
+        assert_eq!("first", read_line.input());
+        read_line.navigate_history(1);
+        assert_eq!("second", read_line.input());
+        read_line.navigate_history(1);
+        assert_eq!("", read_line.input());
+
+        for i in 0..READ_LINE_HISTORY_CAPACITY + 2 {
+            submit(&mut read_line, &i.to_string());
+        }
+        let history = read_line.history().unwrap();
+        assert_eq!(READ_LINE_HISTORY_CAPACITY, history.entries.len());
+        assert_eq!("2", history.entries[0]);
+    }
+
+    #[test]
+    fn process_pipeline_splitting() {
+        fn stages(command: &str) -> Vec<&str> {
+            ProcessPipelineIter::new(command).map(str::trim).collect()
+        }
+
+        assert_eq!(vec![""], stages(""));
+        assert_eq!(vec!["ls -la"], stages("ls -la"));
+        assert_eq!(
+            vec!["ls", "sort -r", "head"],
+            stages("ls | sort -r\t|\thead")
+        );
+        assert_eq!(vec!["echo '|' a|b [[ | ]]"], stages("echo '|' a|b [[ | ]]"));
+        assert_eq!(vec!["ls", ""], stages("ls |"));
+        assert_eq!(vec!["", "ls"], stages("| ls"));
+    }
+
+    #[test]
+    fn process_command_parsing() {
+        fn envs(command: &Command) -> Vec<(&str, &str)> {
+            command
+                .get_envs()
+                .map(|(k, v)| (k.to_str().unwrap(), v.and_then(|v| v.to_str()).unwrap()))
+                .collect()
+        }
+
+        assert!(parse_process_command("").is_none());
+        assert!(parse_process_command("A=1 B=2").is_none());
+
+        let command = parse_process_command("cargo build --release").unwrap();
+        assert_eq!("cargo", command.get_program());
+        assert_eq!(2, command.get_args().count());
+        assert!(envs(&command).is_empty());
+
+        let command = parse_process_command("RUST_LOG=debug A= 'B=x y' cargo run").unwrap();
+        assert_eq!("cargo", command.get_program());
+        assert_eq!(
+            vec![("A", ""), ("B", "x y"), ("RUST_LOG", "debug")],
+            envs(&command)
+        );
+
+        let command = parse_process_command("1A=x git log --format=%h").unwrap();
+        assert_eq!("1A=x", command.get_program());
+        assert!(envs(&command).is_empty());
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(vec!["git", "log", "--format=%h"], args);
+    }
+
+    #[test]
+    fn status_bar_history() {
+        fn collect(status_bar: &StatusBar) -> Vec<&str> {
+            status_bar.history().map(|(_, message)| message).collect()
+        }
+
+        let mut status_bar = StatusBar::new();
+        assert!(collect(&status_bar).is_empty());
+
+        status_bar.write(MessageKind::Info).str("first");
+        assert_eq!(vec!["first"], collect(&status_bar));
+        status_bar.write(MessageKind::Error).str("second");
+        status_bar.clear();
+        status_bar.clear();
+        assert_eq!(vec!["first", "second"], collect(&status_bar));
+        assert!(matches!(
+            status_bar.history().nth(1),
+            Some((MessageKind::Error, _))
+        ));
+
+        for i in 0..MESSAGE_HISTORY_CAPACITY {
+            status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("{}", i));
+        }
+        status_bar.clear();
+        let history = collect(&status_bar);
+        assert_eq!(MESSAGE_HISTORY_CAPACITY, history.len());
+        assert_eq!("0", history[0]);
+        assert_eq!("99", history[MESSAGE_HISTORY_CAPACITY - 1]);
+    }
+
+    #[test]
+    fn default_config_loads() {
+        let mut editor = Editor::new(std::path::PathBuf::new());
+        let mut platform = Platform::default();
+        let mut clients = ClientManager::default();
+        let source = include_str!("../rc/default_config.pp");
+        load_config(
+            &mut editor,
+            &mut platform,
+            &mut clients,
+            "default_config.pp",
+            source,
+        );
+
+        let errors: Vec<_> = editor
+            .status_bar
+            .history()
+            .filter(|(kind, _)| matches!(kind, MessageKind::Error))
+            .map(|(_, message)| message)
+            .collect();
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn logger_ring_buffer() {
+        let mut logger = Logger::default();
+        for i in 0..LOG_CAPACITY + 2 {
+            logger.write(LogLevel::Info).fmt(format_args!("{}", i));
+        }
+        logger.write(LogLevel::Error).str("last");
+
+        let mut entries = logger.entries();
+        assert_eq!(Some((LogLevel::Info, "3")), entries.next());
+        assert_eq!(Some((LogLevel::Error, "last")), logger.entries().last());
+        assert_eq!(LOG_CAPACITY, logger.entries().count());
+
+        assert!(LogLevel::Error < LogLevel::Warning);
+        assert_eq!(Ok(LogLevel::Debug), "debug".parse());
+        assert!("trace".parse::<LogLevel>().is_err());
+    }
+}
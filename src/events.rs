@@ -6,8 +6,10 @@ use crate::{
     buffer_view::BufferViewHandle,
     client::ClientHandle,
     cursor::Cursor,
+    mode::ModeKind,
     platform::Key,
     serialization::{DeserializeError, Deserializer, Serialize, Serializer},
+    theme::Color,
 };
 
 #[derive(Clone, Copy)]
@@ -60,6 +62,17 @@ pub enum EditorEvent {
     BufferViewLostFocus {
         handle: BufferViewHandle,
     },
+    BufferProcessExit {
+        handle: BufferHandle,
+    },
+    ModeChange {
+        kind: ModeKind,
+        previous: ModeKind,
+        client: ClientHandle,
+    },
+    ClientFocus {
+        handle: ClientHandle,
+    },
 }
 
 #[derive(Default)]
@@ -210,16 +223,46 @@ fn parse_key(chars: &mut Chars) -> Result<Key, KeyParseError> {
         Ok(())
     }
 
+    fn parse_number(chars: &mut impl Iterator<Item = char>) -> Result<u8, KeyParseError> {
+        let c = next(chars)?;
+        let mut n = match c.to_digit(10) {
+            Some(d) => d,
+            None => return Err(KeyParseError::InvalidCharacter(c)),
+        };
+        loop {
+            let c = next(chars)?;
+            match c.to_digit(10) {
+                Some(d) if n * 10 + d <= u8::MAX as _ => n = n * 10 + d,
+                None if c == '>' => return Ok(n as _),
+                _ => return Err(KeyParseError::InvalidCharacter(c)),
+            }
+        }
+    }
+
+    fn parse_modified_char(chars: &mut impl Iterator<Item = char>) -> Result<char, KeyParseError> {
+        let c = next(chars)?;
+        if c.is_ascii_alphanumeric() {
+            consume(chars, '>')?;
+            Ok(c)
+        } else {
+            Err(KeyParseError::InvalidCharacter(c))
+        }
+    }
+
     match next(chars)? {
         '<' => match next(chars)? {
             'b' => {
                 consume_str(chars, "ackspace>")?;
                 Ok(Key::Backspace)
             }
-            's' => {
-                consume_str(chars, "pace>")?;
-                Ok(Key::Char(' '))
-            }
+            's' => match next(chars)? {
+                'p' => {
+                    consume_str(chars, "ace>")?;
+                    Ok(Key::Char(' '))
+                }
+                '-' => Ok(Key::Super(parse_modified_char(chars)?)),
+                c => Err(KeyParseError::InvalidCharacter(c)),
+            },
             'e' => match next(chars)? {
                 'n' => match next(chars)? {
                     't' => {
@@ -256,10 +299,14 @@ fn parse_key(chars: &mut Chars) -> Result<Key, KeyParseError> {
                 consume_str(chars, "reater>")?;
                 Ok(Key::Char('>'))
             }
-            'r' => {
-                consume_str(chars, "ight>")?;
-                Ok(Key::Right)
-            }
+            'r' => match next(chars)? {
+                'i' => {
+                    consume_str(chars, "ght>")?;
+                    Ok(Key::Right)
+                }
+                '-' => Ok(Key::Release(parse_modified_char(chars)?)),
+                c => Err(KeyParseError::InvalidCharacter(c)),
+            },
             'u' => {
                 consume_str(chars, "p>")?;
                 Ok(Key::Up)
@@ -275,9 +322,21 @@ fn parse_key(chars: &mut Chars) -> Result<Key, KeyParseError> {
                 }
                 c => Err(KeyParseError::InvalidCharacter(c)),
             },
-            'h' => {
-                consume_str(chars, "ome>")?;
-                Ok(Key::Home)
+            'h' => match next(chars)? {
+                'o' => {
+                    consume_str(chars, "me>")?;
+                    Ok(Key::Home)
+                }
+                '-' => Ok(Key::Hyper(parse_modified_char(chars)?)),
+                c => Err(KeyParseError::InvalidCharacter(c)),
+            },
+            'i' => {
+                consume_str(chars, "nsert>")?;
+                Ok(Key::Insert)
+            }
+            'm' => {
+                consume_str(chars, "ouse")?;
+                Ok(Key::Mouse(parse_number(chars)?))
             }
             'p' => {
                 consume_str(chars, "age")?;
@@ -297,45 +356,14 @@ fn parse_key(chars: &mut Chars) -> Result<Key, KeyParseError> {
                 consume_str(chars, "ab>")?;
                 Ok(Key::Tab)
             }
-            'f' => {
-                let c = next(chars)?;
-                match c.to_digit(10) {
-                    Some(d0) => {
-                        let c = next(chars)?;
-                        match c.to_digit(10) {
-                            Some(d1) => {
-                                consume(chars, '>')?;
-                                let n = d0 * 10 + d1;
-                                Ok(Key::F(n as _))
-                            }
-                            None => match c {
-                                '>' => Ok(Key::F(d0 as _)),
-                                _ => Err(KeyParseError::InvalidCharacter(c)),
-                            },
-                        }
-                    }
-                    None => Err(KeyParseError::InvalidCharacter(c)),
-                }
-            }
+            'f' => Ok(Key::F(parse_number(chars)?)),
             'c' => {
                 consume(chars, '-')?;
-                let c = next(chars)?;
-                if c.is_ascii_alphanumeric() {
-                    consume(chars, '>')?;
-                    Ok(Key::Ctrl(c))
-                } else {
-                    Err(KeyParseError::InvalidCharacter(c))
-                }
+                Ok(Key::Ctrl(parse_modified_char(chars)?))
             }
             'a' => {
                 consume(chars, '-')?;
-                let c = next(chars)?;
-                if c.is_ascii_alphanumeric() {
-                    consume(chars, '>')?;
-                    Ok(Key::Alt(c))
-                } else {
-                    Err(KeyParseError::InvalidCharacter(c))
-                }
+                Ok(Key::Alt(parse_modified_char(chars)?))
             }
             c => Err(KeyParseError::InvalidCharacter(c)),
         },
@@ -359,6 +387,7 @@ impl fmt::Display for Key {
             Key::PageUp => f.write_str("<pageup>"),
             Key::PageDown => f.write_str("<pagedown>"),
             Key::Tab => f.write_str("<tab>"),
+            Key::Insert => f.write_str("<insert>"),
             Key::Delete => f.write_str("<delete>"),
             Key::F(n) => write!(f, "<f{}>", n),
             Key::Char(' ') => f.write_str("<space>"),
@@ -367,6 +396,10 @@ impl fmt::Display for Key {
             Key::Char(c) => write!(f, "{}", c),
             Key::Ctrl(c) => write!(f, "<c-{}>", c),
             Key::Alt(c) => write!(f, "<a-{}>", c),
+            Key::Super(c) => write!(f, "<s-{}>", c),
+            Key::Hyper(c) => write!(f, "<h-{}>", c),
+            Key::Release(c) => write!(f, "<r-{}>", c),
+            Key::Mouse(n) => write!(f, "<mouse{}>", n),
             Key::Esc => f.write_str("<esc>"),
         }
     }
@@ -407,6 +440,23 @@ where
             c.serialize(serializer);
         }
         Key::Esc => 17u8.serialize(serializer),
+        Key::Insert => 18u8.serialize(serializer),
+        Key::Super(c) => {
+            19u8.serialize(serializer);
+            c.serialize(serializer);
+        }
+        Key::Hyper(c) => {
+            20u8.serialize(serializer);
+            c.serialize(serializer);
+        }
+        Key::Release(c) => {
+            21u8.serialize(serializer);
+            c.serialize(serializer);
+        }
+        Key::Mouse(n) => {
+            22u8.serialize(serializer);
+            n.serialize(serializer);
+        }
     }
 }
 
@@ -446,15 +496,46 @@ where
             Ok(Key::Alt(c))
         }
         17 => Ok(Key::Esc),
+        18 => Ok(Key::Insert),
+        19 => {
+            let c = Serialize::deserialize(deserializer)?;
+            Ok(Key::Super(c))
+        }
+        20 => {
+            let c = Serialize::deserialize(deserializer)?;
+            Ok(Key::Hyper(c))
+        }
+        21 => {
+            let c = Serialize::deserialize(deserializer)?;
+            Ok(Key::Release(c))
+        }
+        22 => {
+            let n = Serialize::deserialize(deserializer)?;
+            Ok(Key::Mouse(n))
+        }
         _ => Err(DeserializeError::InvalidData),
     }
 }
 
+pub const PROTOCOL_VERSION: u32 = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Hidden,
+    Block,
+    Underline,
+    Bar,
+}
+
 pub enum ServerEvent<'a> {
     Display(&'a [u8]),
     Suspend,
     CommandOutput(&'a str),
     Request(&'a str),
+    CommandError(&'a str),
+    Handshake(u32),
+    CompressedDisplay(&'a [u8]),
+    CursorStyle(CursorShape, Color),
 }
 impl<'a> ServerEvent<'a> {
     pub const fn display_header_len() -> usize {
@@ -487,6 +568,29 @@ impl<'de> Serialize<'de> for ServerEvent<'de> {
                 3u8.serialize(serializer);
                 request.serialize(serializer);
             }
+            Self::CommandError(error) => {
+                4u8.serialize(serializer);
+                error.serialize(serializer);
+            }
+            Self::Handshake(version) => {
+                5u8.serialize(serializer);
+                version.serialize(serializer);
+            }
+            Self::CompressedDisplay(display) => {
+                6u8.serialize(serializer);
+                display.serialize(serializer);
+            }
+            Self::CursorStyle(shape, color) => {
+                7u8.serialize(serializer);
+                let shape: u8 = match shape {
+                    CursorShape::Hidden => 0,
+                    CursorShape::Block => 1,
+                    CursorShape::Underline => 2,
+                    CursorShape::Bar => 3,
+                };
+                shape.serialize(serializer);
+                color.into_u32().serialize(serializer);
+            }
         }
     }
 
@@ -509,6 +613,29 @@ impl<'de> Serialize<'de> for ServerEvent<'de> {
                 let request = Serialize::deserialize(deserializer)?;
                 Ok(Self::Request(request))
             }
+            4 => {
+                let error = Serialize::deserialize(deserializer)?;
+                Ok(Self::CommandError(error))
+            }
+            5 => {
+                let version = Serialize::deserialize(deserializer)?;
+                Ok(Self::Handshake(version))
+            }
+            6 => {
+                let display = Serialize::deserialize(deserializer)?;
+                Ok(Self::CompressedDisplay(display))
+            }
+            7 => {
+                let shape = match u8::deserialize(deserializer)? {
+                    0 => CursorShape::Hidden,
+                    1 => CursorShape::Block,
+                    2 => CursorShape::Underline,
+                    3 => CursorShape::Bar,
+                    _ => return Err(DeserializeError::InvalidData),
+                };
+                let color = u32::deserialize(deserializer)?;
+                Ok(Self::CursorStyle(shape, Color::from_u32(color)))
+            }
             _ => Err(DeserializeError::InvalidData),
         }
     }
@@ -518,6 +645,7 @@ impl<'de> Serialize<'de> for ServerEvent<'de> {
 pub enum TargetClient {
     Sender,
     Focused,
+    FocusedOrSender,
 }
 impl<'de> Serialize<'de> for TargetClient {
     fn serialize<S>(&self, serializer: &mut S)
@@ -527,6 +655,7 @@ impl<'de> Serialize<'de> for TargetClient {
         match self {
             Self::Sender => 0u8.serialize(serializer),
             Self::Focused => 1u8.serialize(serializer),
+            Self::FocusedOrSender => 2u8.serialize(serializer),
         }
     }
 
@@ -538,6 +667,7 @@ impl<'de> Serialize<'de> for TargetClient {
         match discriminant {
             0 => Ok(Self::Sender),
             1 => Ok(Self::Focused),
+            2 => Ok(Self::FocusedOrSender),
             _ => Err(DeserializeError::InvalidData),
         }
     }
@@ -547,6 +677,8 @@ pub enum ClientEvent<'a> {
     Key(TargetClient, Key),
     Resize(u16, u16),
     Command(TargetClient, &'a str),
+    StdinInput(TargetClient, &'a [u8]),
+    Handshake(u32, bool),
 }
 impl<'de> Serialize<'de> for ClientEvent<'de> {
     fn serialize<S>(&self, serializer: &mut S)
@@ -569,6 +701,16 @@ impl<'de> Serialize<'de> for ClientEvent<'de> {
                 target.serialize(serializer);
                 command.serialize(serializer);
             }
+            Self::StdinInput(target, bytes) => {
+                3u8.serialize(serializer);
+                target.serialize(serializer);
+                bytes.serialize(serializer);
+            }
+            Self::Handshake(version, compress_display) => {
+                4u8.serialize(serializer);
+                version.serialize(serializer);
+                (*compress_display as u8).serialize(serializer);
+            }
         }
     }
 
@@ -593,6 +735,20 @@ impl<'de> Serialize<'de> for ClientEvent<'de> {
                 let command = Serialize::deserialize(deserializer)?;
                 Ok(Self::Command(target, command))
             }
+            3 => {
+                let target = Serialize::deserialize(deserializer)?;
+                let bytes = Serialize::deserialize(deserializer)?;
+                Ok(Self::StdinInput(target, bytes))
+            }
+            4 => {
+                let version = Serialize::deserialize(deserializer)?;
+                let compress_display = match u8::deserialize(deserializer)? {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(DeserializeError::InvalidData),
+                };
+                Ok(Self::Handshake(version, compress_display))
+            }
             _ => Err(DeserializeError::InvalidData),
         }
     }
@@ -674,10 +830,21 @@ mod tests {
         assert_eq!(Key::Delete, parse_key(&mut "<delete>".chars()).unwrap());
         assert_eq!(Key::Esc, parse_key(&mut "<esc>".chars()).unwrap());
 
-        for n in 1..=99 {
+        assert_eq!(Key::Insert, parse_key(&mut "<insert>".chars()).unwrap());
+
+        for n in 1..=255 {
             let s = format!("<f{}>", n);
             assert_eq!(Key::F(n as _), parse_key(&mut s.chars()).unwrap());
         }
+        assert!(parse_key(&mut "<f256>".chars()).is_err());
+        assert!(parse_key(&mut "<f>".chars()).is_err());
+
+        assert_eq!(Key::Super('a'), parse_key(&mut "<s-a>".chars()).unwrap());
+        assert_eq!(Key::Hyper('x'), parse_key(&mut "<h-x>".chars()).unwrap());
+        assert_eq!(Key::Release('q'), parse_key(&mut "<r-q>".chars()).unwrap());
+        assert_eq!(Key::Mouse(1), parse_key(&mut "<mouse1>".chars()).unwrap());
+        assert_eq!(Key::Mouse(12), parse_key(&mut "<mouse12>".chars()).unwrap());
+        assert!(parse_key(&mut "<s-$>".chars()).is_err());
 
         assert_eq!(Key::Ctrl('z'), parse_key(&mut "<c-z>".chars()).unwrap());
         assert_eq!(Key::Ctrl('0'), parse_key(&mut "<c-0>".chars()).unwrap());
@@ -750,6 +917,55 @@ mod tests {
         assert_key_serialization(Key::Alt('9'));
         assert_key_serialization(Key::Alt('$'));
         assert_key_serialization(Key::Esc);
+        assert_key_serialization(Key::Insert);
+        assert_key_serialization(Key::F(255));
+        assert_key_serialization(Key::Super('a'));
+        assert_key_serialization(Key::Hyper('Z'));
+        assert_key_serialization(Key::Release('0'));
+        assert_key_serialization(Key::Mouse(3));
+    }
+
+    #[test]
+    fn key_notation_roundtrip() {
+        let keys = [
+            Key::Backspace,
+            Key::Enter,
+            Key::Left,
+            Key::Right,
+            Key::Up,
+            Key::Down,
+            Key::Home,
+            Key::End,
+            Key::PageUp,
+            Key::PageDown,
+            Key::Tab,
+            Key::Insert,
+            Key::Delete,
+            Key::F(1),
+            Key::F(24),
+            Key::F(255),
+            Key::Char('a'),
+            Key::Char(' '),
+            Key::Char('<'),
+            Key::Char('>'),
+            Key::Ctrl('w'),
+            Key::Alt('9'),
+            Key::Super('s'),
+            Key::Hyper('h'),
+            Key::Release('r'),
+            Key::Mouse(1),
+            Key::Mouse(255),
+            Key::Esc,
+        ];
+
+        let mut notation = String::new();
+        for key in &keys {
+            use fmt::Write;
+            let _ = write!(notation, "{}", key);
+        }
+
+        let parsed: Vec<_> = KeyParser::new(&notation).map(Result::unwrap).collect();
+        assert_eq!(&keys[..], &parsed[..]);
     }
 
     #[test]
@@ -798,4 +1014,41 @@ mod tests {
         assert_eq!(0, receiver.bufs[client_handle.into_index()].len());
         assert_eq!(EVENT_COUNT, event_count);
     }
+
+    #[test]
+    fn handshake_serialization() {
+        let mut bytes = Vec::new();
+        ClientEvent::Handshake(PROTOCOL_VERSION, true).serialize(&mut bytes);
+        ServerEvent::Handshake(PROTOCOL_VERSION).serialize(&mut bytes);
+
+        let mut slice = &bytes[..];
+        assert!(matches!(
+            ClientEvent::deserialize(&mut slice),
+            Ok(ClientEvent::Handshake(PROTOCOL_VERSION, true))
+        ));
+        assert!(matches!(
+            ServerEvent::deserialize(&mut slice),
+            Ok(ServerEvent::Handshake(PROTOCOL_VERSION))
+        ));
+        assert!(slice.is_empty());
+    }
+
+    #[test]
+    fn cursor_style_serialization() {
+        let color = Color::from_u32(0x123456);
+        let mut bytes = Vec::new();
+        ServerEvent::CursorStyle(CursorShape::Bar, color).serialize(&mut bytes);
+        ServerEvent::CursorStyle(CursorShape::Hidden, color).serialize(&mut bytes);
+
+        let mut slice = &bytes[..];
+        assert!(matches!(
+            ServerEvent::deserialize(&mut slice),
+            Ok(ServerEvent::CursorStyle(CursorShape::Bar, c)) if c == color
+        ));
+        assert!(matches!(
+            ServerEvent::deserialize(&mut slice),
+            Ok(ServerEvent::CursorStyle(CursorShape::Hidden, _))
+        ));
+        assert!(slice.is_empty());
+    }
 }
@@ -0,0 +1,358 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub struct FileTreeEntry {
+    pub path: PathBuf,
+    pub depth: usize,
+    pub is_dir: bool,
+    pub expanded: bool,
+}
+
+impl FileTreeEntry {
+    pub fn name(&self) -> &str {
+        self.path.file_name().and_then(|n| n.to_str()).unwrap_or("")
+    }
+}
+
+fn read_children(
+    path: &Path,
+    depth: usize,
+    expanded: &[PathBuf],
+    entries: &mut Vec<FileTreeEntry>,
+) {
+    let read_dir = match fs::read_dir(path) {
+        Ok(read_dir) => read_dir,
+        Err(_) => return,
+    };
+
+    let mut children: Vec<_> = read_dir
+        .flatten()
+        .filter(|e| e.file_name() != ".git")
+        .map(|e| {
+            let is_dir = e.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            (e.path(), is_dir)
+        })
+        .collect();
+    children.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    for (path, is_dir) in children {
+        let is_expanded = is_dir && expanded.contains(&path);
+        entries.push(FileTreeEntry {
+            path,
+            depth,
+            is_dir,
+            expanded: is_expanded,
+        });
+        if is_expanded {
+            let path = entries[entries.len() - 1].path.clone();
+            read_children(&path, depth + 1, expanded, entries);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct FileTree {
+    visible: bool,
+    root: PathBuf,
+    entries: Vec<FileTreeEntry>,
+    selected_index: usize,
+    scroll: usize,
+}
+
+impl FileTree {
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn show(&mut self, root: &Path) {
+        self.visible = true;
+        if self.root != root {
+            self.root.clear();
+            self.root.push(root);
+            self.entries.clear();
+            self.selected_index = 0;
+            self.scroll = 0;
+        }
+        self.refresh();
+    }
+
+    pub fn hide(&mut self) {
+        self.visible = false;
+    }
+
+    pub fn width(&self, config_width: u8, viewport_width: u16) -> u16 {
+        if self.visible {
+            (config_width as u16 + 1).min(viewport_width / 2)
+        } else {
+            0
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn entries(&self) -> &[FileTreeEntry] {
+        &self.entries
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected_index
+    }
+
+    pub fn selected_entry(&self) -> Option<&FileTreeEntry> {
+        self.entries.get(self.selected_index)
+    }
+
+    pub fn scroll(&self) -> usize {
+        self.scroll
+    }
+
+    pub fn update_scroll(&mut self, height: usize) {
+        if self.selected_index < self.scroll {
+            self.scroll = self.selected_index;
+        } else if height > 0 && self.selected_index >= self.scroll + height {
+            self.scroll = self.selected_index + 1 - height;
+        }
+    }
+
+    pub fn refresh(&mut self) {
+        let expanded: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|e| e.expanded)
+            .map(|e| e.path.clone())
+            .collect();
+        let selected_path = self.selected_entry().map(|e| e.path.clone());
+
+        self.entries.clear();
+        read_children(&self.root, 0, &expanded, &mut self.entries);
+
+        self.selected_index = selected_path
+            .and_then(|p| self.entries.iter().position(|e| e.path == p))
+            .unwrap_or_else(|| {
+                self.selected_index
+                    .min(self.entries.len().saturating_sub(1))
+            });
+    }
+
+    pub fn move_selection(&mut self, offset: isize) {
+        let last_index = self.entries.len().saturating_sub(1) as isize;
+        let index = (self.selected_index as isize + offset)
+            .max(0)
+            .min(last_index);
+        self.selected_index = index as _;
+    }
+
+    pub fn expand(&mut self, index: usize) {
+        let entry = match self.entries.get_mut(index) {
+            Some(entry) if entry.is_dir && !entry.expanded => entry,
+            _ => return,
+        };
+        entry.expanded = true;
+
+        let path = entry.path.clone();
+        let mut children = Vec::new();
+        read_children(&path, entry.depth + 1, &[], &mut children);
+        self.entries.splice(index + 1..index + 1, children);
+    }
+
+    pub fn collapse(&mut self, index: usize) {
+        let entry = match self.entries.get_mut(index) {
+            Some(entry) if entry.expanded => entry,
+            _ => return,
+        };
+        entry.expanded = false;
+
+        let depth = entry.depth;
+        let end_index = self.entries[index + 1..]
+            .iter()
+            .position(|e| e.depth <= depth)
+            .map(|i| index + 1 + i)
+            .unwrap_or(self.entries.len());
+        self.entries.drain(index + 1..end_index);
+        if self.selected_index > index && self.selected_index < end_index {
+            self.selected_index = index;
+        } else if self.selected_index >= end_index {
+            self.selected_index -= end_index - index - 1;
+        }
+    }
+
+    pub fn toggle_expanded(&mut self, index: usize) {
+        match self.entries.get(index) {
+            Some(entry) if entry.expanded => self.collapse(index),
+            Some(_) => self.expand(index),
+            None => (),
+        }
+    }
+
+    pub fn select_parent(&mut self) {
+        let depth = match self.selected_entry() {
+            Some(entry) => entry.depth,
+            None => return,
+        };
+        if let Some(index) = self.entries[..self.selected_index]
+            .iter()
+            .rposition(|e| e.depth < depth)
+        {
+            self.selected_index = index;
+        }
+    }
+
+    pub fn reveal(&mut self, path: &Path) -> bool {
+        let relative_path = match path.strip_prefix(&self.root) {
+            Ok(path) => path,
+            Err(_) if path.is_relative() => path,
+            Err(_) => return false,
+        };
+
+        let mut current_path = self.root.clone();
+        let mut index = None;
+        for component in relative_path.components() {
+            if let Some(index) = index {
+                self.expand(index);
+            }
+            current_path.push(component);
+            let start_index = index.map(|i| i + 1).unwrap_or(0);
+            match self.entries[start_index..]
+                .iter()
+                .position(|e| e.path == current_path)
+            {
+                Some(i) => index = Some(start_index + i),
+                None => return false,
+            }
+        }
+
+        match index {
+            Some(index) => {
+                self.selected_index = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn selected_directory(&self) -> &Path {
+        match self.selected_entry() {
+            Some(entry) if entry.is_dir => &entry.path,
+            Some(entry) => entry.path.parent().unwrap_or(&self.root),
+            None => &self.root,
+        }
+    }
+
+    pub fn create(&mut self, name: &str) -> io::Result<PathBuf> {
+        let path = self.selected_directory().join(name);
+        if name.ends_with('/') || name.ends_with(std::path::MAIN_SEPARATOR) {
+            fs::create_dir_all(&path)?;
+        } else {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+        }
+
+        self.refresh();
+        self.reveal(&path);
+        Ok(path)
+    }
+
+    pub fn rename_selected(&mut self, new_path: &Path) -> io::Result<(PathBuf, PathBuf)> {
+        let old_path = match self.selected_entry() {
+            Some(entry) => entry.path.clone(),
+            None => return Err(io::Error::from(io::ErrorKind::NotFound)),
+        };
+        let new_path = self.root.join(new_path);
+        if let Some(parent) = new_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&old_path, &new_path)?;
+
+        self.refresh();
+        self.reveal(&new_path);
+        Ok((old_path, new_path))
+    }
+
+    pub fn delete_selected(&mut self) -> io::Result<PathBuf> {
+        let (path, is_dir) = match self.selected_entry() {
+            Some(entry) => (entry.path.clone(), entry.is_dir),
+            None => return Err(io::Error::from(io::ErrorKind::NotFound)),
+        };
+        if is_dir {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+
+        self.refresh();
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(tree: &FileTree) -> Vec<String> {
+        tree.entries()
+            .iter()
+            .map(|e| format!("{}{}", "  ".repeat(e.depth), e.name()))
+            .collect()
+    }
+
+    #[test]
+    fn file_tree_operations() {
+        let root = std::env::temp_dir().join(format!("pepper-file-tree-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join("src/nested/mod.rs"), "").unwrap();
+        fs::write(root.join("README.md"), "").unwrap();
+
+        let mut tree = FileTree::default();
+        tree.show(&root);
+        assert_eq!(vec!["src", "README.md"], names(&tree));
+
+        assert!(tree.reveal(&root.join("src/nested/mod.rs")));
+        assert_eq!(
+            vec!["src", "  nested", "    mod.rs", "  main.rs", "README.md"],
+            names(&tree)
+        );
+        assert_eq!(2, tree.selected_index());
+
+        tree.select_parent();
+        assert_eq!(1, tree.selected_index());
+        let path = tree.create("lib.rs").unwrap();
+        assert_eq!(root.join("src/nested/lib.rs"), path);
+        assert_eq!("lib.rs", tree.selected_entry().unwrap().name());
+
+        tree.rename_selected(Path::new("src/lib.rs")).unwrap();
+        assert_eq!(
+            vec![
+                "src",
+                "  nested",
+                "    mod.rs",
+                "  lib.rs",
+                "  main.rs",
+                "README.md"
+            ],
+            names(&tree)
+        );
+        assert_eq!(3, tree.selected_index());
+
+        tree.collapse(0);
+        assert_eq!(vec!["src", "README.md"], names(&tree));
+        assert_eq!(0, tree.selected_index());
+
+        tree.move_selection(1);
+        tree.delete_selected().unwrap();
+        assert_eq!(vec!["src"], names(&tree));
+        assert!(!root.join("README.md").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
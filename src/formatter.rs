@@ -0,0 +1,358 @@
+use std::process::Stdio;
+
+use crate::{
+    buffer::{Buffer, BufferHandle},
+    buffer_position::{BufferPosition, BufferRange},
+    editor::Editor,
+    editor_utils::{parse_process_command, MessageKind},
+    events::EditorEventQueue,
+    git::diff_lines,
+    glob::{Glob, InvalidGlobError},
+    platform::{Platform, PlatformRequest, PooledBuf, ProcessHandle, ProcessTag},
+    word_database::WordDatabase,
+};
+
+struct Formatter {
+    glob: Glob,
+    command: String,
+}
+
+struct FormatJob {
+    alive: bool,
+    discarded: bool,
+    buffer_handle: BufferHandle,
+    input: Option<PooledBuf>,
+    input_text: String,
+    output: Vec<u8>,
+    save_after: bool,
+}
+
+#[derive(Default)]
+pub struct FormatterCollection {
+    formatters: Vec<Formatter>,
+    format_on_save_globs: Vec<Glob>,
+    jobs: Vec<FormatJob>,
+    save_skip: Option<BufferHandle>,
+}
+
+impl FormatterCollection {
+    pub fn add(&mut self, glob: &str, command: &str) -> Result<(), InvalidGlobError> {
+        let mut formatter_glob = Glob::default();
+        formatter_glob.compile(glob)?;
+        self.formatters.push(Formatter {
+            glob: formatter_glob,
+            command: command.into(),
+        });
+        Ok(())
+    }
+
+    pub fn add_format_on_save_glob(&mut self, glob: &str) -> Result<(), InvalidGlobError> {
+        let mut save_glob = Glob::default();
+        save_glob.compile(glob)?;
+        self.format_on_save_globs.push(save_glob);
+        Ok(())
+    }
+
+    pub fn formats_on_save(&self, path: &str) -> bool {
+        self.format_on_save_globs.iter().any(|g| g.matches(path))
+    }
+
+    fn find_command(&self, path: &str) -> Option<&str> {
+        self.formatters
+            .iter()
+            .rev()
+            .find(|f| f.glob.matches(path))
+            .map(|f| f.command.as_str())
+    }
+
+    pub fn on_buffer_write(editor: &mut Editor, platform: &mut Platform, handle: BufferHandle) {
+        if editor.formatters.save_skip == Some(handle) {
+            editor.formatters.save_skip = None;
+            return;
+        }
+
+        let path = editor.buffers.get(handle).path.to_str().unwrap_or("");
+        if editor.formatters.formats_on_save(path) {
+            Self::format(editor, platform, handle, true);
+        }
+    }
+
+    pub fn on_buffer_close(&mut self, handle: BufferHandle) {
+        for job in &mut self.jobs {
+            if job.alive && job.buffer_handle == handle {
+                job.discarded = true;
+            }
+        }
+    }
+
+    pub fn format(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        save_after: bool,
+    ) -> bool {
+        let buffer = editor.buffers.get(buffer_handle);
+        let path = buffer.path.to_str().unwrap_or("");
+        let command = match editor.formatters.find_command(path) {
+            Some(command) => command,
+            None => return false,
+        };
+        let mut command = match parse_process_command(command) {
+            Some(command) => command,
+            None => return false,
+        };
+
+        let formatters = &mut editor.formatters;
+        if formatters
+            .jobs
+            .iter()
+            .any(|j| j.alive && !j.discarded && j.buffer_handle == buffer_handle)
+        {
+            return true;
+        }
+
+        let index = match formatters.jobs.iter().position(|j| !j.alive) {
+            Some(index) => index,
+            None => {
+                formatters.jobs.push(FormatJob {
+                    alive: false,
+                    discarded: false,
+                    buffer_handle,
+                    input: None,
+                    input_text: String::new(),
+                    output: Vec::new(),
+                    save_after,
+                });
+                formatters.jobs.len() - 1
+            }
+        };
+
+        let job = &mut formatters.jobs[index];
+        job.alive = true;
+        job.discarded = false;
+        job.buffer_handle = buffer_handle;
+        job.save_after = save_after;
+        job.output.clear();
+
+        job.input_text.clear();
+        for line in buffer.content().lines() {
+            job.input_text.push_str(line.as_str());
+            job.input_text.push('\n');
+        }
+        let mut buf = platform.buf_pool.acquire();
+        buf.write().extend_from_slice(job.input_text.as_bytes());
+        if let Some(buf) = job.input.replace(buf) {
+            platform.buf_pool.release(buf);
+        }
+
+        command.current_dir(&editor.current_directory);
+        command.stdin(Stdio::piped());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::null());
+
+        platform.requests.enqueue(PlatformRequest::SpawnProcess {
+            tag: ProcessTag::Formatter(index),
+            command,
+            buf_len: 4 * 1024,
+        });
+        true
+    }
+
+    pub fn on_process_spawned(
+        &mut self,
+        platform: &mut Platform,
+        index: usize,
+        handle: ProcessHandle,
+    ) {
+        if let Some(buf) = self.jobs[index].input.take() {
+            platform
+                .requests
+                .enqueue(PlatformRequest::WriteToProcess { handle, buf });
+            platform
+                .requests
+                .enqueue(PlatformRequest::CloseProcessInput { handle });
+        }
+    }
+
+    pub fn on_process_output(&mut self, index: usize, bytes: &[u8]) {
+        self.jobs[index].output.extend_from_slice(bytes);
+    }
+
+    pub fn on_process_exit(editor: &mut Editor, platform: &mut Platform, index: usize) {
+        let job = &mut editor.formatters.jobs[index];
+        job.alive = false;
+        if let Some(buf) = job.input.take() {
+            platform.buf_pool.release(buf);
+        }
+        if job.discarded {
+            return;
+        }
+
+        let buffer_handle = job.buffer_handle;
+        let save_after = job.save_after;
+        let buffer = editor.buffers.get_mut(buffer_handle);
+
+        let input_lines = job.input_text.strip_suffix('\n').unwrap_or(&job.input_text);
+        let content = buffer.content();
+        let unchanged = content.line_count() == input_lines.split('\n').count()
+            && content
+                .lines()
+                .zip(input_lines.split('\n'))
+                .all(|(a, b)| a.as_str() == b);
+        if !unchanged {
+            editor
+                .status_bar
+                .write(MessageKind::Error)
+                .str("buffer changed while formatting");
+            return;
+        }
+
+        let output = match std::str::from_utf8(&job.output) {
+            Ok(output) => output,
+            Err(_) => {
+                editor
+                    .status_bar
+                    .write(MessageKind::Error)
+                    .str("formatter output is not valid utf-8");
+                return;
+            }
+        };
+        if output.is_empty() && !input_lines.is_empty() {
+            editor
+                .status_bar
+                .write(MessageKind::Error)
+                .str("formatter failed");
+            return;
+        }
+
+        let old_lines: Vec<_> = input_lines.split('\n').collect();
+        let new_lines: Vec<_> = output
+            .strip_suffix('\n')
+            .unwrap_or(output)
+            .split('\n')
+            .map(|l| l.strip_suffix('\r').unwrap_or(l))
+            .collect();
+        apply_diff(
+            buffer,
+            &mut editor.word_database,
+            &old_lines,
+            &new_lines,
+            &mut editor.events,
+        );
+
+        if save_after {
+            editor.formatters.save_skip = Some(buffer_handle);
+            if let Err(error) = buffer.write_to_file(None, &mut editor.events) {
+                editor.formatters.save_skip = None;
+                editor
+                    .status_bar
+                    .write(MessageKind::Error)
+                    .fmt(format_args!("{}", error));
+            }
+        }
+    }
+}
+
+pub fn apply_diff(
+    buffer: &mut Buffer,
+    word_database: &mut WordDatabase,
+    old_lines: &[&str],
+    new_lines: &[&str],
+    events: &mut EditorEventQueue,
+) -> bool {
+    let mut hunks = Vec::new();
+    diff_lines(old_lines, new_lines, &mut hunks);
+    if hunks.is_empty() {
+        return false;
+    }
+
+    buffer.commit_edits();
+    let mut text = String::new();
+    for hunk in hunks.iter().rev() {
+        let lines = &new_lines[hunk.new_lines.clone()];
+        text.clear();
+
+        let (from, to) = if hunk.old_lines.end < old_lines.len() {
+            for line in lines {
+                text.push_str(line);
+                text.push('\n');
+            }
+            (
+                BufferPosition::line_col(hunk.old_lines.start as _, 0),
+                BufferPosition::line_col(hunk.old_lines.end as _, 0),
+            )
+        } else if hunk.old_lines.start > 0 {
+            for line in lines {
+                text.push('\n');
+                text.push_str(line);
+            }
+            let line_index = hunk.old_lines.start - 1;
+            (
+                BufferPosition::line_col(line_index as _, old_lines[line_index].len() as _),
+                buffer.content().end(),
+            )
+        } else {
+            for (i, line) in lines.iter().enumerate() {
+                if i > 0 {
+                    text.push('\n');
+                }
+                text.push_str(line);
+            }
+            (BufferPosition::zero(), buffer.content().end())
+        };
+
+        if from != to {
+            buffer.delete_range(word_database, BufferRange::between(from, to), events);
+        }
+        buffer.insert_text(word_database, from, &text, events);
+    }
+    buffer.commit_edits();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::{BufferCapabilities, BufferCollection};
+
+    fn format(old: &str, new: &str) -> String {
+        let mut word_database = WordDatabase::new();
+        let mut events = EditorEventQueue::default();
+
+        let mut buffers = BufferCollection::default();
+        let buffer = buffers.add_new();
+        buffer.capabilities = BufferCapabilities::text();
+        buffer.insert_text(&mut word_database, BufferPosition::zero(), old, &mut events);
+
+        let old_lines: Vec<_> = old.split('\n').collect();
+        let new_lines: Vec<_> = new.split('\n').collect();
+        apply_diff(
+            buffer,
+            &mut word_database,
+            &old_lines,
+            &new_lines,
+            &mut events,
+        );
+        buffer.content().to_string()
+    }
+
+    #[test]
+    fn apply_formatter_diff() {
+        let cases = [
+            ("a\nb\nc", "a\nb\nc"),
+            ("a\nb\nc", "a\nB\nc"),
+            ("a\nb\nc", "A\nb\nC"),
+            ("a\nb\nc", "x\ny"),
+            ("a\nb\nc", "a\nb\nc\nd\ne"),
+            ("a\nb\nc\nd", "a\nd"),
+            ("a\nb\nc", "b\nc"),
+            ("a\nb\nc", "a"),
+            ("a\nb", "a\nx\nb\ny"),
+            ("", "fn main() {}"),
+            ("fn main(){}", ""),
+        ];
+        for &(old, new) in &cases {
+            assert_eq!(new, format(old, new), "formatting {:?}", old);
+        }
+    }
+}
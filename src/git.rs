@@ -0,0 +1,664 @@
+use std::{
+    fmt, io,
+    ops::Range,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::platform::{Platform, PlatformRequest, ProcessTag};
+
+pub enum GitError {
+    CouldNotRun,
+    Failed(String),
+}
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::CouldNotRun => f.write_str("could not run git"),
+            Self::Failed(message) => f.write_str(message.trim_end()),
+        }
+    }
+}
+
+pub fn run(
+    current_directory: &Path,
+    args: &[&str],
+    stdin: Option<&str>,
+    output: &mut String,
+) -> Result<(), GitError> {
+    let mut command = Command::new("git");
+    command.current_dir(current_directory);
+    command.args(args);
+    command.stdin(match stdin {
+        Some(_) => Stdio::piped(),
+        None => Stdio::null(),
+    });
+    command.stdout(Stdio::piped());
+    command.stderr(Stdio::piped());
+
+    let mut child = command.spawn().map_err(|_| GitError::CouldNotRun)?;
+    if let (Some(text), Some(mut child_stdin)) = (stdin, child.stdin.take()) {
+        use io::Write;
+        let _ = child_stdin.write_all(text.as_bytes());
+    }
+    let result = child
+        .wait_with_output()
+        .map_err(|_| GitError::CouldNotRun)?;
+
+    if result.status.success() {
+        output.push_str(&String::from_utf8_lossy(&result.stdout));
+        Ok(())
+    } else {
+        Err(GitError::Failed(
+            String::from_utf8_lossy(&result.stderr).into_owned(),
+        ))
+    }
+}
+
+pub fn blame_line_commit(line: &str) -> Option<&str> {
+    let hash = line.split(' ').next()?.trim_start_matches('^');
+    if hash.is_empty() || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        None
+    } else {
+        Some(hash)
+    }
+}
+
+pub fn is_uncommitted(hash: &str) -> bool {
+    hash.bytes().all(|b| b == b'0')
+}
+
+pub fn status_entry_path(entry: &str) -> Option<&str> {
+    let path = entry.get(3..)?;
+    let path = match path.find(" -> ") {
+        Some(i) => &path[i + " -> ".len()..],
+        None => path,
+    };
+    let path = path
+        .strip_prefix('"')
+        .and_then(|p| p.strip_suffix('"'))
+        .unwrap_or(path);
+    if path.is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+pub fn is_untracked_status(entry: &str) -> bool {
+    entry.starts_with("??")
+}
+
+pub fn show_index_file(
+    current_directory: &Path,
+    path: &Path,
+    output: &mut String,
+) -> Result<(), GitError> {
+    let path = current_directory.join(path);
+    let (directory, name) = match (path.parent(), path.file_name().and_then(|n| n.to_str())) {
+        (Some(directory), Some(name)) => (directory, name),
+        _ => return Err(GitError::Failed(String::from("invalid path"))),
+    };
+    let object = format!(":./{}", name);
+    run(directory, &["show", &object], None, output)
+}
+
+pub fn is_commit_message_path(path: &Path) -> bool {
+    matches!(
+        path.file_name().and_then(|n| n.to_str()),
+        Some("COMMIT_EDITMSG" | "MERGE_MSG")
+    )
+}
+
+pub fn is_scissors_line(line: &str) -> bool {
+    line.starts_with("# ------------------------ >8 ------------------------")
+}
+
+pub fn write_staged_diff_as_comment(
+    current_directory: &Path,
+    output: &mut String,
+) -> Result<(), GitError> {
+    let mut diff = String::new();
+    run(
+        current_directory,
+        &["diff", "--cached", "--no-color", "--no-ext-diff"],
+        None,
+        &mut diff,
+    )?;
+
+    if !diff.is_empty() {
+        output.push_str("#\n# staged changes:\n");
+        for line in diff.lines() {
+            output.push_str("# ");
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct RepoState {
+    branch: String,
+    ahead: u32,
+    behind: u32,
+    dirty: bool,
+    output: Vec<u8>,
+    waiting_for_process: bool,
+    needs_refresh: bool,
+}
+impl RepoState {
+    pub fn branch(&self) -> &str {
+        &self.branch
+    }
+
+    pub fn ahead(&self) -> u32 {
+        self.ahead
+    }
+
+    pub fn behind(&self) -> u32 {
+        self.behind
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn refresh(&mut self, platform: &mut Platform, current_directory: &Path) {
+        if self.waiting_for_process {
+            self.needs_refresh = true;
+            return;
+        }
+
+        let mut command = Command::new("git");
+        command.current_dir(current_directory);
+        command.args(["status", "--porcelain=v2", "--branch"]);
+        command.stdin(Stdio::null());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::null());
+
+        self.output.clear();
+        self.waiting_for_process = true;
+        self.needs_refresh = false;
+        platform.requests.enqueue(PlatformRequest::SpawnProcess {
+            tag: ProcessTag::GitRepoState,
+            command,
+            buf_len: 4 * 1024,
+        });
+    }
+
+    pub fn on_process_output(&mut self, bytes: &[u8]) {
+        self.output.extend_from_slice(bytes);
+    }
+
+    pub fn on_process_exit(&mut self, platform: &mut Platform, current_directory: &Path) {
+        self.waiting_for_process = false;
+        let output = std::mem::take(&mut self.output);
+        self.parse_status(&String::from_utf8_lossy(&output));
+        self.output = output;
+
+        if self.needs_refresh {
+            self.refresh(platform, current_directory);
+        }
+    }
+
+    fn parse_status(&mut self, status: &str) {
+        self.branch.clear();
+        self.ahead = 0;
+        self.behind = 0;
+        self.dirty = false;
+
+        for line in status.lines() {
+            if let Some(head) = line.strip_prefix("# branch.head ") {
+                self.branch.push_str(head);
+            } else if let Some(ab) = line.strip_prefix("# branch.ab ") {
+                for count in ab.split(' ') {
+                    if let Some(ahead) = count.strip_prefix('+') {
+                        self.ahead = ahead.parse().unwrap_or(0);
+                    } else if let Some(behind) = count.strip_prefix('-') {
+                        self.behind = behind.parse().unwrap_or(0);
+                    }
+                }
+            } else if !line.starts_with('#') && !line.is_empty() {
+                self.dirty = true;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_lines: Range<usize>,
+    pub new_lines: Range<usize>,
+}
+
+pub fn diff_lines(old: &[&str], new: &[&str], hunks: &mut Vec<Hunk>) {
+    hunks.clear();
+
+    let prefix_len = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &old[prefix_len..];
+    let new = &new[prefix_len..];
+    let suffix_len = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &old[..old.len() - suffix_len];
+    let new = &new[..new.len() - suffix_len];
+
+    let mut old_changed = vec![false; old.len()];
+    let mut new_changed = vec![false; new.len()];
+
+    let max = (old.len() + new.len()) as isize;
+    let offset = max + 1;
+    let mut v = vec![0; 2 * max as usize + 3];
+    let mut trace = Vec::new();
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) {
+                v[i + 1]
+            } else {
+                v[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < old.len() as isize
+                && y < new.len() as isize
+                && old[x as usize] == new[y as usize]
+            {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= old.len() as isize && y >= new.len() as isize {
+                break 'search;
+            }
+        }
+    }
+
+    let mut x = old.len() as isize;
+    let mut y = new.len() as isize;
+    for (d, v) in trace.iter().enumerate().skip(1).rev() {
+        let d = d as isize;
+        let k = x - y;
+        let previous_k =
+            if k == -d || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize]) {
+                k + 1
+            } else {
+                k - 1
+            };
+        let previous_x = v[(previous_k + offset) as usize];
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+        }
+        if x == previous_x {
+            new_changed[previous_y as usize] = true;
+        } else {
+            old_changed[previous_x as usize] = true;
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+
+    let mut old_index = 0;
+    let mut new_index = 0;
+    loop {
+        while old_index < old.len()
+            && new_index < new.len()
+            && !old_changed[old_index]
+            && !new_changed[new_index]
+        {
+            old_index += 1;
+            new_index += 1;
+        }
+        if old_index == old.len() && new_index == new.len() {
+            break;
+        }
+
+        let old_start = old_index;
+        let new_start = new_index;
+        while old_index < old.len() && old_changed[old_index] {
+            old_index += 1;
+        }
+        while new_index < new.len() && new_changed[new_index] {
+            new_index += 1;
+        }
+        hunks.push(Hunk {
+            old_lines: prefix_len + old_start..prefix_len + old_index,
+            new_lines: prefix_len + new_start..prefix_len + new_index,
+        });
+    }
+}
+
+pub fn write_unified_diff(
+    old: &[&str],
+    new: &[&str],
+    hunks: &[Hunk],
+    context_len: usize,
+    output: &mut String,
+) {
+    use fmt::Write;
+
+    let mut hunks = hunks;
+    while let Some(first) = hunks.first() {
+        let mut group_len = 1;
+        while group_len < hunks.len()
+            && hunks[group_len].old_lines.start - hunks[group_len - 1].old_lines.end
+                <= 2 * context_len
+        {
+            group_len += 1;
+        }
+        let (group, rest) = hunks.split_at(group_len);
+        hunks = rest;
+        let last = &group[group_len - 1];
+
+        let old_from = first.old_lines.start.saturating_sub(context_len);
+        let old_to = (last.old_lines.end + context_len).min(old.len());
+        let new_from = first.new_lines.start - (first.old_lines.start - old_from);
+        let new_to = last.new_lines.end + (old_to - last.old_lines.end);
+        let _ = writeln!(
+            output,
+            "@@ -{},{} +{},{} @@",
+            old_from + 1,
+            old_to - old_from,
+            new_from + 1,
+            new_to - new_from,
+        );
+
+        let mut old_index = old_from;
+        for hunk in group {
+            for line in &old[old_index..hunk.old_lines.start] {
+                let _ = writeln!(output, " {}", line);
+            }
+            for line in &old[hunk.old_lines.clone()] {
+                let _ = writeln!(output, "-{}", line);
+            }
+            for line in &new[hunk.new_lines.clone()] {
+                let _ = writeln!(output, "+{}", line);
+            }
+            old_index = hunk.old_lines.end;
+        }
+        for line in &old[old_index..old_to] {
+            let _ = writeln!(output, " {}", line);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictMarker {
+    Ours,
+    Base,
+    Separator,
+    Theirs,
+}
+impl ConflictMarker {
+    pub fn parse(line: &str) -> Option<Self> {
+        fn is_marker(line: &str, prefix: &str) -> bool {
+            line.strip_prefix(prefix)
+                .map(|rest| rest.is_empty() || rest.starts_with(' '))
+                .unwrap_or(false)
+        }
+
+        if is_marker(line, "<<<<<<<") {
+            Some(Self::Ours)
+        } else if is_marker(line, "|||||||") {
+            Some(Self::Base)
+        } else if line == "=======" {
+            Some(Self::Separator)
+        } else if is_marker(line, ">>>>>>>") {
+            Some(Self::Theirs)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictSide {
+    None,
+    Ours,
+    Base,
+    Theirs,
+}
+impl ConflictSide {
+    pub fn after(self, marker: Option<ConflictMarker>) -> Self {
+        match marker {
+            Some(ConflictMarker::Ours) => Self::Ours,
+            Some(ConflictMarker::Base) => Self::Base,
+            Some(ConflictMarker::Separator) => Self::Theirs,
+            Some(ConflictMarker::Theirs) => Self::None,
+            None => self,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Conflict {
+    pub start: usize,
+    pub ours: Range<usize>,
+    pub theirs: Range<usize>,
+    pub end: usize,
+}
+
+pub fn find_conflict_at(lines: &[&str], line_index: usize) -> Option<Conflict> {
+    let mut start = None;
+    for i in (0..=line_index.min(lines.len().checked_sub(1)?)).rev() {
+        match ConflictMarker::parse(lines[i]) {
+            Some(ConflictMarker::Ours) => {
+                start = Some(i);
+                break;
+            }
+            Some(ConflictMarker::Theirs) if i != line_index => return None,
+            _ => (),
+        }
+    }
+    let start = start?;
+
+    let mut ours_end = None;
+    let mut separator = None;
+    for (i, &line) in lines.iter().enumerate().skip(start + 1) {
+        match ConflictMarker::parse(line) {
+            Some(ConflictMarker::Ours) => return None,
+            Some(ConflictMarker::Base) => {
+                ours_end.get_or_insert(i);
+            }
+            Some(ConflictMarker::Separator) => {
+                ours_end.get_or_insert(i);
+                separator = Some(i);
+            }
+            Some(ConflictMarker::Theirs) => {
+                let separator = separator?;
+                if i < line_index {
+                    return None;
+                }
+                return Some(Conflict {
+                    start,
+                    ours: start + 1..ours_end?,
+                    theirs: separator + 1..i,
+                    end: i,
+                });
+            }
+            None => (),
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blame_line_commit_hash() {
+        assert_eq!(
+            Some("1a2b3c4d"),
+            blame_line_commit("1a2b3c4d (author 2021-05-01  1) fn main() {")
+        );
+        assert_eq!(
+            Some("1a2b3c4d"),
+            blame_line_commit("^1a2b3c4d (author 2021-05-01  1) fn main() {")
+        );
+        assert_eq!(None, blame_line_commit("fn main() {"));
+        assert_eq!(None, blame_line_commit(""));
+        assert!(is_uncommitted("00000000"));
+        assert!(!is_uncommitted("1a2b3c4d"));
+    }
+
+    #[test]
+    fn status_entry() {
+        assert_eq!(Some("src/main.rs"), status_entry_path(" M src/main.rs"));
+        assert_eq!(Some("src/main.rs"), status_entry_path("M  src/main.rs"));
+        assert_eq!(Some("new.rs"), status_entry_path("R  old.rs -> new.rs"));
+        assert_eq!(Some("a file.rs"), status_entry_path("?? \"a file.rs\""));
+        assert_eq!(None, status_entry_path("??"));
+        assert!(is_untracked_status("?? new.rs"));
+        assert!(!is_untracked_status(" M src/main.rs"));
+    }
+
+    #[test]
+    fn diff_lines_hunks() {
+        fn diff(old: &str, new: &str) -> Vec<Hunk> {
+            let old: Vec<_> = old.lines().collect();
+            let new: Vec<_> = new.lines().collect();
+            let mut hunks = Vec::new();
+            diff_lines(&old, &new, &mut hunks);
+            hunks
+        }
+        fn hunk(old_lines: Range<usize>, new_lines: Range<usize>) -> Hunk {
+            Hunk {
+                old_lines,
+                new_lines,
+            }
+        }
+
+        assert_eq!(Vec::<Hunk>::new(), diff("", ""));
+        assert_eq!(Vec::<Hunk>::new(), diff("a\nb\nc", "a\nb\nc"));
+        assert_eq!(vec![hunk(0..0, 0..2)], diff("", "a\nb"));
+        assert_eq!(vec![hunk(0..2, 0..0)], diff("a\nb", ""));
+        assert_eq!(vec![hunk(1..2, 1..2)], diff("a\nb\nc", "a\nx\nc"));
+        assert_eq!(vec![hunk(1..1, 1..2)], diff("a\nc", "a\nb\nc"));
+        assert_eq!(vec![hunk(1..2, 1..1)], diff("a\nb\nc", "a\nc"));
+        assert_eq!(
+            vec![hunk(0..1, 0..0), hunk(3..3, 2..3), hunk(4..5, 4..5)],
+            diff("a\nb\nc\nd\ne", "b\nc\nx\nd\ny"),
+        );
+    }
+
+    #[test]
+    fn conflicts() {
+        let lines = [
+            "a",
+            "<<<<<<< HEAD",
+            "ours",
+            "=======",
+            "theirs",
+            ">>>>>>> branch",
+            "b",
+            "<<<<<<< HEAD",
+            "ours",
+            "||||||| base",
+            "base",
+            "=======",
+            ">>>>>>> branch",
+        ];
+
+        assert_eq!(None, find_conflict_at(&lines, 0));
+        assert_eq!(None, find_conflict_at(&lines, 6));
+        assert_eq!(None, find_conflict_at(&lines, 100));
+        let conflict = Conflict {
+            start: 1,
+            ours: 2..3,
+            theirs: 4..5,
+            end: 5,
+        };
+        assert_eq!(Some(&conflict), find_conflict_at(&lines, 1).as_ref());
+        assert_eq!(Some(&conflict), find_conflict_at(&lines, 3).as_ref());
+        assert_eq!(Some(&conflict), find_conflict_at(&lines, 5).as_ref());
+        let conflict = Conflict {
+            start: 7,
+            ours: 8..9,
+            theirs: 12..12,
+            end: 12,
+        };
+        assert_eq!(Some(&conflict), find_conflict_at(&lines, 10).as_ref());
+
+        let mut side = ConflictSide::None;
+        let sides: Vec<_> = lines
+            .iter()
+            .map(|l| {
+                side = side.after(ConflictMarker::parse(l));
+                side
+            })
+            .collect();
+        assert_eq!(ConflictSide::None, sides[0]);
+        assert_eq!(ConflictSide::Ours, sides[2]);
+        assert_eq!(ConflictSide::Theirs, sides[4]);
+        assert_eq!(ConflictSide::None, sides[5]);
+        assert_eq!(ConflictSide::Base, sides[10]);
+    }
+
+    #[test]
+    fn commit_message() {
+        assert!(is_commit_message_path(Path::new(".git/COMMIT_EDITMSG")));
+        assert!(is_commit_message_path(Path::new("/repo/.git/MERGE_MSG")));
+        assert!(!is_commit_message_path(Path::new("src/COMMIT_EDITMSG.rs")));
+        assert!(is_scissors_line(
+            "# ------------------------ >8 ------------------------"
+        ));
+        assert!(!is_scissors_line("# Please enter the commit message"));
+    }
+
+    #[test]
+    fn repo_state_status() {
+        let mut state = RepoState::default();
+        state.parse_status(
+            "# branch.oid 1a2b3c4d\n# branch.head main\n# branch.upstream origin/main\n# branch.ab +2 -1\n",
+        );
+        assert_eq!("main", state.branch());
+        assert_eq!(2, state.ahead());
+        assert_eq!(1, state.behind());
+        assert!(!state.is_dirty());
+
+        state.parse_status(
+            "# branch.head feature\n1 .M N... 100644 100644 100644 a b src/main.rs\n",
+        );
+        assert_eq!("feature", state.branch());
+        assert_eq!(0, state.ahead());
+        assert_eq!(0, state.behind());
+        assert!(state.is_dirty());
+
+        state.parse_status("");
+        assert_eq!("", state.branch());
+    }
+
+    #[test]
+    fn unified_diff() {
+        let old: Vec<_> = "a\nb\nc\nd\ne\nf\ng\nh".lines().collect();
+        let new: Vec<_> = "a\nB\nc\nd\ne\nf\ng\nh\ni".lines().collect();
+        let mut hunks = Vec::new();
+        diff_lines(&old, &new, &mut hunks);
+
+        let mut output = String::new();
+        write_unified_diff(&old, &new, &hunks, 1, &mut output);
+        assert_eq!(
+            "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n@@ -8,1 +8,2 @@\n h\n+i\n",
+            output
+        );
+
+        output.clear();
+        write_unified_diff(&old, &new, &hunks, 3, &mut output);
+        assert_eq!(
+            "@@ -1,8 +1,9 @@\n a\n-b\n+B\n c\n d\n e\n f\n g\n h\n+i\n",
+            output
+        );
+    }
+}
@@ -14,19 +14,21 @@ impl From<TryFromIntError> for InvalidGlobError {
     }
 }
 
+#[derive(Clone, Copy)]
 pub enum Op {
     Slice { from: u16, to: u16 },
     Separator,
     Skip { count: u16 },
     Many,
     ManyComponents,
+    Everything,
     AnyWithinRanges { from: u16, to: u16 },
     ExceptWithinRanges { from: u16, to: u16 },
     SubPatternGroup { len: u16 },
     SubPattern { len: u16 },
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Glob {
     pub texts: String,
     pub ops: Vec<Op>,
@@ -51,7 +53,8 @@ impl Glob {
         &mut self,
         mut pattern: Chars<'a>,
     ) -> Result<Chars<'a>, InvalidGlobError> {
-        let mut start_ops_index = self.ops.len();
+        let begin_ops_index = self.ops.len();
+        let mut start_ops_index = begin_ops_index;
         loop {
             let previous_state = pattern.clone();
             match pattern.next() {
@@ -64,16 +67,17 @@ impl Glob {
                     let previous_state = pattern.clone();
                     match pattern.next() {
                         Some('*') => {
-                            match self.ops.last() {
-                                None | Some(Op::Separator) => (),
-                                _ => return Err(InvalidGlobError),
+                            if self.ops.len() != begin_ops_index
+                                && !matches!(self.ops.last(), Some(Op::Separator))
+                            {
+                                return Err(InvalidGlobError);
                             }
 
                             let previous_state = pattern.clone();
                             match pattern.next() {
-                                None => {
+                                None | Some('}' | ',') => {
                                     pattern = previous_state;
-                                    self.ops.push(Op::ManyComponents);
+                                    self.ops.push(Op::Everything);
                                 }
                                 Some('/') => self.ops.push(Op::ManyComponents),
                                 _ => return Err(InvalidGlobError),
@@ -190,6 +194,83 @@ impl Glob {
     }
 }
 
+struct IgnorePattern {
+    glob: Glob,
+    negated: bool,
+    dir_only: bool,
+}
+
+#[derive(Default)]
+pub struct IgnoreList {
+    patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreList {
+    pub fn clear(&mut self) {
+        self.patterns.clear();
+    }
+
+    pub fn add(&mut self, pattern: &str) -> Result<(), InvalidGlobError> {
+        let pattern = pattern.trim_end();
+        if pattern.is_empty() || pattern.starts_with('#') {
+            return Ok(());
+        }
+
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern.strip_prefix('\\').unwrap_or(pattern)),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        if pattern.is_empty() {
+            return Err(InvalidGlobError);
+        }
+
+        let mut glob = Glob::default();
+        match pattern.strip_prefix('/') {
+            Some(pattern) => glob.compile(pattern)?,
+            None if pattern.contains('/') => glob.compile(pattern)?,
+            None => glob.compile(&format!("**/{}", pattern))?,
+        }
+
+        self.patterns.push(IgnorePattern {
+            glob,
+            negated,
+            dir_only,
+        });
+        Ok(())
+    }
+
+    pub fn add_lines(&mut self, text: &str) -> Result<(), InvalidGlobError> {
+        for line in text.lines() {
+            self.add(line)?;
+        }
+        Ok(())
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        for pattern in self.patterns.iter().rev() {
+            if (is_dir || !pattern.dir_only) && pattern.glob.matches(path) {
+                return !pattern.negated;
+            }
+        }
+        false
+    }
+
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        let path = path.strip_prefix("./").unwrap_or(path);
+        let path = path.trim_end_matches(std::path::is_separator);
+        for (i, c) in path.char_indices() {
+            if std::path::is_separator(c) && self.matches(&path[..i], true) {
+                return true;
+            }
+        }
+        self.matches(path, is_dir)
+    }
+}
+
 enum Continuation<'this, 'ops> {
     None,
     Next(&'ops [Op], &'this Continuation<'this, 'ops>),
@@ -252,6 +333,14 @@ fn matches_recursive<'data, 'cont>(
                     return false;
                 }
             },
+            Op::Everything => loop {
+                if matches_recursive(ops, texts, path.clone(), continuation) {
+                    return true;
+                }
+                if path.next().is_none() {
+                    return false;
+                }
+            },
             &Op::AnyWithinRanges { from, to } => {
                 let c = match path.next() {
                     Some(c) => c,
@@ -328,6 +417,10 @@ mod tests {
         assert!(glob.compile("a/**/c").is_ok());
         assert!(glob.compile("a/**c").is_err());
         assert!(glob.compile("a**/c").is_err());
+        assert!(glob.compile("{**/a,b/**}").is_ok());
+        assert!(glob.compile("{a**}").is_err());
+        assert!(glob.compile("{a,b}**").is_err());
+        assert!(glob.compile("{a,{b,c}").is_err());
 
         assert!(glob.compile("a{b,c}d").is_ok());
         assert!(glob.compile("a*{b,c}d").is_ok());
@@ -389,6 +482,9 @@ mod tests {
         assert_glob(&mut glob, false, "**/c", "a/bc");
         assert_glob(&mut glob, true, "**/c", "ab/c");
         assert_glob(&mut glob, true, "**/c", "a/b/c");
+        assert_glob(&mut glob, true, "**", "a/b/c");
+        assert_glob(&mut glob, true, "a/**", "a/b/c");
+        assert_glob(&mut glob, false, "a/**", "b/c");
 
         assert_glob(&mut glob, true, "a{b,c}d", "abd");
         assert_glob(&mut glob, true, "a{b,c}d", "acd");
@@ -421,5 +517,72 @@ mod tests {
         assert_glob(&mut glob, true, "**/*.{é,ç}", "m/n/p.ç");
         assert_glob(&mut glob, false, "**/*.{é,ç}", "p.e");
         assert_glob(&mut glob, false, "**/*.{é,ç}", "p.c");
+
+        assert_glob(&mut glob, true, "src/{foo,bar}/**/*.rs", "src/foo/a.rs");
+        assert_glob(&mut glob, true, "src/{foo,bar}/**/*.rs", "src/bar/b/c.rs");
+        assert_glob(&mut glob, false, "src/{foo,bar}/**/*.rs", "src/baz/a.rs");
+        assert_glob(&mut glob, false, "src/{foo,bar}/**/*.rs", "src/foo/a.txt");
+
+        assert_glob(&mut glob, true, "a{b,{c,d}e}f", "abf");
+        assert_glob(&mut glob, true, "a{b,{c,d}e}f", "acef");
+        assert_glob(&mut glob, true, "a{b,{c,d}e}f", "adef");
+        assert_glob(&mut glob, false, "a{b,{c,d}e}f", "acf");
+        assert_glob(&mut glob, false, "a{b,{c,d}e}f", "abef");
+        assert_glob(&mut glob, true, "{a{b,c},d}", "ab");
+        assert_glob(&mut glob, true, "{a{b,c},d}", "d");
+        assert_glob(&mut glob, false, "{a{b,c},d}", "a");
+
+        assert_glob(&mut glob, true, "{,a}b", "b");
+        assert_glob(&mut glob, true, "{,a}b", "ab");
+        assert_glob(&mut glob, true, "{a/b,c}/d", "a/b/d");
+        assert_glob(&mut glob, true, "{a/b,c}/d", "c/d");
+        assert_glob(&mut glob, true, "{**/*.rs,*.toml}", "src/main.rs");
+        assert_glob(&mut glob, true, "{**/*.rs,*.toml}", "Cargo.toml");
+        assert_glob(&mut glob, false, "{**/*.rs,*.toml}", "src/Cargo.toml");
+        assert_glob(&mut glob, true, "{src/**,tests}/*.rs", "src/a/b.rs");
+        assert_glob(&mut glob, true, "{src/**,tests}/*.rs", "tests/b.rs");
+        assert_glob(&mut glob, true, "a/{b/**,c}", "a/b/x/y");
+        assert_glob(&mut glob, true, "a/{b/**,c}", "a/c");
+    }
+
+    #[test]
+    fn ignore_list() {
+        let mut ignore = IgnoreList::default();
+        assert!(ignore.add("a/").is_ok());
+        assert!(ignore.add("/").is_err());
+        assert!(ignore.add("!").is_err());
+
+        let mut ignore = IgnoreList::default();
+        let gitignore = "
+# comment
+target/
+*.log
+!keep.log
+/build
+docs/*.html
+\\!bang
+";
+        assert!(ignore.add_lines(gitignore).is_ok());
+
+        assert!(!ignore.is_ignored("src/main.rs", false));
+        assert!(ignore.is_ignored("target", true));
+        assert!(!ignore.is_ignored("target", false));
+        assert!(ignore.is_ignored("target/debug/pepper", false));
+        assert!(ignore.is_ignored("sub/target/file", false));
+
+        assert!(ignore.is_ignored("a.log", false));
+        assert!(ignore.is_ignored("logs/b.log", false));
+        assert!(!ignore.is_ignored("keep.log", false));
+        assert!(!ignore.is_ignored("logs/keep.log", false));
+
+        assert!(ignore.is_ignored("build", true));
+        assert!(ignore.is_ignored("./build/out.o", false));
+        assert!(!ignore.is_ignored("src/build/out.o", false));
+
+        assert!(ignore.is_ignored("docs/index.html", false));
+        assert!(!ignore.is_ignored("docs/api/index.html", false));
+        assert!(!ignore.is_ignored("other/docs/index.html", false));
+
+        assert!(ignore.is_ignored("!bang", false));
     }
 }
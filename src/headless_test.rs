@@ -0,0 +1,322 @@
+use std::{fmt, fs, io::Write, mem, process::Child};
+
+use crate::{
+    application::ServerApplication,
+    client::ClientHandle,
+    command::CommandTokenizer,
+    editor_utils::MessageKind,
+    events::{ClientEvent, KeyParser, TargetClient, PROTOCOL_VERSION},
+    platform::{Key, PlatformEvent, PlatformRequest, ProcessHandle, ProcessTag},
+    serialization::Serialize,
+    Args,
+};
+
+const VIEWPORT_WIDTH: u16 = 80;
+const VIEWPORT_HEIGHT: u16 = 24;
+
+enum TestError {
+    InvalidDirective,
+    WrongArgumentCount,
+    InvalidKeys,
+    ServerQuit,
+    NoBuffer,
+    BufferMismatch(String),
+    StatusMismatch(String),
+}
+impl fmt::Display for TestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidDirective => f.write_str(
+                "invalid directive. expected one of: keys, command, assert-buffer, assert-status",
+            ),
+            Self::WrongArgumentCount => f.write_str("directive expects exactly one argument"),
+            Self::InvalidKeys => f.write_str("invalid keys"),
+            Self::ServerQuit => f.write_str("server quit before the script ended"),
+            Self::NoBuffer => f.write_str("client has no buffer open"),
+            Self::BufferMismatch(found) => {
+                f.write_str("buffer content mismatch. found:\n")?;
+                f.write_str(found)
+            }
+            Self::StatusMismatch(found) => {
+                f.write_str("status message mismatch. found:\n")?;
+                f.write_str(found)
+            }
+        }
+    }
+}
+
+struct HeadlessServer {
+    application: ServerApplication,
+    handle: ClientHandle,
+    events: Vec<PlatformEvent>,
+    next_process_handle: u8,
+    processes: Vec<(ProcessHandle, ProcessTag, Child)>,
+    running: bool,
+}
+impl HeadlessServer {
+    fn send(&mut self, event: ClientEvent) -> Result<(), TestError> {
+        let mut buf = self.application.platform.buf_pool.acquire();
+        event.serialize(buf.write());
+        self.events.push(PlatformEvent::ConnectionOutput {
+            handle: self.handle,
+            buf,
+        });
+        self.pump()
+    }
+
+    fn pump(&mut self) -> Result<(), TestError> {
+        while self.running && !self.events.is_empty() {
+            self.application.update(self.events.drain(..));
+            self.application
+                .platform
+                .take_finished_jobs(&mut self.events);
+
+            let requests: Vec<_> = self.application.platform.requests.drain().collect();
+            for request in requests {
+                match request {
+                    PlatformRequest::Quit | PlatformRequest::CloseClient { .. } => {
+                        self.running = false;
+                    }
+                    PlatformRequest::Redraw => (),
+                    PlatformRequest::WriteToClient { buf, .. } => {
+                        self.application.platform.buf_pool.release(buf)
+                    }
+                    PlatformRequest::SpawnProcess {
+                        tag, mut command, ..
+                    } => {
+                        let child = match command.spawn() {
+                            Ok(child) => child,
+                            Err(_) => {
+                                self.events.push(PlatformEvent::ProcessExit { tag });
+                                continue;
+                            }
+                        };
+
+                        let handle = ProcessHandle(self.next_process_handle);
+                        self.next_process_handle = self.next_process_handle.wrapping_add(1);
+                        self.events
+                            .push(PlatformEvent::ProcessSpawned { tag, handle });
+
+                        // processes with piped stdin only run once their input is closed
+                        if child.stdin.is_some() {
+                            self.processes.push((handle, tag, child));
+                        } else {
+                            self.finish_process(tag, child);
+                        }
+                    }
+                    PlatformRequest::WriteToProcess { handle, buf } => {
+                        if let Some((_, _, child)) =
+                            self.processes.iter_mut().find(|(h, _, _)| h.0 == handle.0)
+                        {
+                            if let Some(stdin) = &mut child.stdin {
+                                let _ = stdin.write_all(buf.as_bytes());
+                            }
+                        }
+                        self.application.platform.buf_pool.release(buf)
+                    }
+                    PlatformRequest::CloseProcessInput { handle } => {
+                        if let Some(i) = self.processes.iter().position(|(h, _, _)| h.0 == handle.0)
+                        {
+                            let (_, tag, child) = self.processes.swap_remove(i);
+                            self.finish_process(tag, child);
+                        }
+                    }
+                    PlatformRequest::KillProcess { .. } => (),
+                }
+            }
+        }
+
+        if self.running {
+            Ok(())
+        } else {
+            Err(TestError::ServerQuit)
+        }
+    }
+
+    fn finish_process(&mut self, tag: ProcessTag, mut child: Child) {
+        drop(child.stdin.take());
+        let output = match child.wait_with_output() {
+            Ok(output) => output,
+            Err(_) => {
+                self.events.push(PlatformEvent::ProcessExit { tag });
+                return;
+            }
+        };
+
+        let buf_pool = &mut self.application.platform.buf_pool;
+        if !output.stdout.is_empty() {
+            let mut buf = buf_pool.acquire();
+            buf.write().extend_from_slice(&output.stdout);
+            self.events.push(PlatformEvent::ProcessOutput { tag, buf });
+        }
+        if !output.stderr.is_empty() {
+            let mut buf = buf_pool.acquire();
+            buf.write().extend_from_slice(&output.stderr);
+            self.events
+                .push(PlatformEvent::ProcessErrorOutput { tag, buf });
+        }
+        self.events.push(PlatformEvent::ProcessExit { tag });
+    }
+
+    fn run_directive(&mut self, line: &str) -> Result<(), TestError> {
+        let mut tokens = CommandTokenizer(line);
+        let directive = tokens.next().ok_or(TestError::InvalidDirective)?;
+        let arg = match (tokens.next(), tokens.next()) {
+            (Some(arg), None) => arg,
+            _ => return Err(TestError::WrongArgumentCount),
+        };
+
+        match directive {
+            "keys" => {
+                for key in KeyParser::new(arg) {
+                    let key = key.map_err(|_| TestError::InvalidKeys)?;
+                    self.send(ClientEvent::Key(TargetClient::Sender, key))?;
+                }
+                Ok(())
+            }
+            "command" => self.send(ClientEvent::Command(TargetClient::Sender, arg)),
+            "assert-buffer" => {
+                let editor = &self.application.editor;
+                let buffer_view_handle = self
+                    .application
+                    .clients
+                    .get(self.handle)
+                    .buffer_view_handle()
+                    .ok_or(TestError::NoBuffer)?;
+                let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+                let content = editor.buffers.get(buffer_handle).content().to_string();
+                if content == arg {
+                    Ok(())
+                } else {
+                    Err(TestError::BufferMismatch(content))
+                }
+            }
+            "assert-status" => {
+                let (kind, message) = self.application.editor.status_bar.message();
+                if message == arg {
+                    Ok(())
+                } else {
+                    let kind = match kind {
+                        MessageKind::Info => "info",
+                        MessageKind::Error => "error",
+                    };
+                    Err(TestError::StatusMismatch(format!("[{}] {}", kind, message)))
+                }
+            }
+            _ => Err(TestError::InvalidDirective),
+        }
+    }
+}
+
+pub fn run(args: Args, script_path: &str) -> bool {
+    match fs::read_to_string(script_path) {
+        Ok(script) => run_script(args, script_path, &script),
+        Err(error) => {
+            eprintln!("could not read test script '{}': {}", script_path, error);
+            false
+        }
+    }
+}
+
+fn run_script(mut args: Args, script_path: &str, script: &str) -> bool {
+    let files = mem::take(&mut args.files);
+    let commands = mem::take(&mut args.commands);
+    let application = match ServerApplication::new(args) {
+        Some(application) => application,
+        None => {
+            eprintln!("{}: server quit while loading configs", script_path);
+            return false;
+        }
+    };
+
+    let handle = ClientHandle::from_index(0).unwrap();
+    let mut server = HeadlessServer {
+        application,
+        handle,
+        events: vec![PlatformEvent::ConnectionOpen { handle }],
+        next_process_handle: 0,
+        processes: Vec::new(),
+        running: true,
+    };
+
+    let mut startup_commands = Vec::new();
+    for path in &files {
+        startup_commands.push(format!("open \"{}\"", path));
+    }
+    startup_commands.extend(commands);
+
+    let startup_result = server
+        .send(ClientEvent::Handshake(PROTOCOL_VERSION, false))
+        .and_then(|_| server.send(ClientEvent::Resize(VIEWPORT_WIDTH, VIEWPORT_HEIGHT)))
+        .and_then(|_| server.send(ClientEvent::Key(TargetClient::Sender, Key::None)))
+        .and_then(|_| {
+            startup_commands
+                .iter()
+                .try_for_each(|c| server.send(ClientEvent::Command(TargetClient::Sender, c)))
+        });
+    if let Err(error) = startup_result {
+        eprintln!("{}: {}", script_path, error);
+        return false;
+    }
+
+    let mut assertion_count = 0;
+    let mut lines = script.lines().enumerate();
+    while let Some((line_index, line)) = lines.next() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let mut directive = String::from(line);
+        while CommandTokenizer::has_unfinished_balanced_token(&directive) {
+            match lines.next() {
+                Some((_, line)) => {
+                    directive.push('\n');
+                    directive.push_str(line);
+                }
+                None => break,
+            }
+        }
+
+        if directive.trim_start().starts_with("assert-") {
+            assertion_count += 1;
+        }
+
+        if let Err(error) = server.run_directive(&directive) {
+            eprintln!("{}:{}: {}", script_path, line_index + 1, error);
+            return false;
+        }
+    }
+
+    println!("{}: ok ({} assertions)", script_path, assertion_count);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_directives() {
+        let script = r#"
+# comment
+command "open scratch.txt"
+keys "ihello<esc>"
+assert-buffer "hello"
+keys "oworld<esc>"
+assert-buffer [[hello
+world]]
+command "does-not-exist"
+assert-status "no such command"
+"#;
+        assert!(run_script(Args::default(), "test", script));
+
+        assert!(!run_script(Args::default(), "test", "assert-buffer \"\""));
+        assert!(!run_script(Args::default(), "test", "keys"));
+        assert!(!run_script(
+            Args::default(),
+            "test",
+            "invalid-directive arg"
+        ));
+        assert!(!run_script(Args::default(), "test", "command quit"));
+    }
+}
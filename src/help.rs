@@ -16,6 +16,10 @@ static HELP_SOURCES: &[(&str, &str)] = &[
         "help://config_recipes.md",
         include_str!("../rc/config_recipes.md"),
     ),
+    (
+        "help://plugin_protocol.md",
+        include_str!("../rc/plugin_protocol.md"),
+    ),
     ("help://help.md", include_str!("../rc/help.md")),
 ];
 
@@ -55,3 +59,81 @@ pub fn search(keyword: &str) -> Option<(&'static Path, usize)> {
     }
     last_match
 }
+
+#[derive(Clone, Copy)]
+pub struct CommandHelp {
+    pub usage: &'static str,
+    pub summary: &'static str,
+    flags: &'static str,
+}
+impl CommandHelp {
+    pub fn flags(&self) -> impl Iterator<Item = &'static str> {
+        self.flags.lines().filter_map(|line| {
+            let flag = line.trim_start().strip_prefix("- `")?;
+            let end = flag.find('`')?;
+            Some(&flag[..end])
+        })
+    }
+}
+
+pub fn command_help(command_name: &str) -> Option<CommandHelp> {
+    let source = HELP_SOURCES[0].1;
+    let mut lines = source.lines();
+    lines.find(|line| {
+        line.strip_prefix("## `").and_then(|l| l.strip_suffix('`')) == Some(command_name)
+    })?;
+
+    let mut help = CommandHelp {
+        usage: "",
+        summary: "",
+        flags: "",
+    };
+    while let Some(line) = lines.next() {
+        if line.starts_with('#') {
+            break;
+        }
+        if let Some(usage) = line.strip_prefix("- usage") {
+            if help.usage.is_empty() {
+                help.usage = usage.trim_start_matches(':').trim().trim_matches('`');
+            }
+        } else if line == "- flags:" {
+            let start = lines.clone().next().map(|l| l.as_ptr() as usize);
+            let mut end = start;
+            while let Some(flag) = lines.clone().next() {
+                if !flag.starts_with("  ") {
+                    break;
+                }
+                end = Some(flag.as_ptr() as usize + flag.len());
+                lines.next();
+            }
+            if let (Some(start), Some(end)) = (start, end) {
+                let offset = source.as_ptr() as usize;
+                help.flags = &source[start - offset..end - offset];
+            }
+        } else if help.summary.is_empty() && !line.is_empty() && !line.starts_with("- ") {
+            help.summary = line;
+        }
+    }
+    Some(help)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_help_from_reference() {
+        let help = command_help("open").unwrap();
+        assert_eq!("open [<flags>] <path>", help.usage);
+        assert_eq!("Opens a buffer up for editting.", help.summary);
+        let flags: Vec<_> = help.flags().collect();
+        assert_eq!("-line=<number>", flags[0]);
+        assert_eq!("-auto-close", flags[flags.len() - 1]);
+
+        let help = command_help("quit").unwrap();
+        assert!(help.usage.starts_with("quit"));
+        assert_eq!(0, help.flags().count());
+
+        assert!(command_help("not-a-command").is_none());
+    }
+}
@@ -1,4 +1,7 @@
-use std::ops::Range;
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
 
 use crate::buffer_position::{BufferPosition, BufferRange};
 
@@ -46,6 +49,7 @@ pub struct History {
     pub edits: Vec<EditInternal>,
     group_ranges: Vec<Range<usize>>,
     state: HistoryState,
+    last_edit_instant: Option<Instant>,
 }
 
 impl History {
@@ -55,6 +59,7 @@ impl History {
             edits: Vec::new(),
             group_ranges: Vec::new(),
             state: HistoryState::IterIndex { group_index: 0 },
+            last_edit_instant: None,
         }
     }
 
@@ -63,9 +68,12 @@ impl History {
         self.edits.clear();
         self.group_ranges.clear();
         self.state = HistoryState::IterIndex { group_index: 0 };
+        self.last_edit_instant = None;
     }
 
     pub fn add_edit(&mut self, edit: Edit) {
+        self.last_edit_instant = Some(Instant::now());
+
         let current_group_start = match self.state {
             HistoryState::IterIndex { group_index } => {
                 let edit_index = match self.group_ranges.get(group_index) {
@@ -372,6 +380,13 @@ impl History {
         }
     }
 
+    pub fn commit_edits_after_pause(&mut self, pause: Duration) {
+        match self.last_edit_instant {
+            Some(instant) if instant.elapsed() >= pause => self.commit_edits(),
+            _ => (),
+        }
+    }
+
     pub fn undo_edits(
         &mut self,
     ) -> impl Clone + ExactSizeIterator<Item = Edit> + DoubleEndedIterator<Item = Edit> {
@@ -453,6 +468,35 @@ mod tests {
         assert_eq!(0, history.redo_edits().count());
     }
 
+    #[test]
+    fn commit_edits_after_pause() {
+        let mut history = History::new();
+        history.commit_edits_after_pause(Duration::ZERO);
+        assert_eq!(0, history.undo_edits().count());
+
+        history.add_edit(Edit {
+            kind: EditKind::Insert,
+            range: buffer_range((0, 0), (0, 1)),
+            text: "a",
+        });
+        history.commit_edits_after_pause(Duration::from_secs(60));
+        history.add_edit(Edit {
+            kind: EditKind::Insert,
+            range: buffer_range((0, 2), (0, 3)),
+            text: "b",
+        });
+        history.commit_edits_after_pause(Duration::ZERO);
+        history.add_edit(Edit {
+            kind: EditKind::Insert,
+            range: buffer_range((1, 0), (1, 1)),
+            text: "c",
+        });
+
+        assert_eq!(1, history.undo_edits().count());
+        assert_eq!(2, history.undo_edits().count());
+        assert_eq!(0, history.undo_edits().count());
+    }
+
     #[test]
     fn edit_grouping() {
         let mut history = History::new();
@@ -6,6 +6,8 @@ use crate::{
     platform::Key,
 };
 
+pub const LEADER_KEY: &str = "<leader>";
+
 pub enum MatchResult<'a> {
     None,
     Prefix,
@@ -33,7 +35,30 @@ struct KeyMap {
 
 #[derive(Default)]
 pub struct KeyMapCollection {
-    maps: [Vec<KeyMap>; 5],
+    maps: [Vec<KeyMap>; ModeKind::ALL.len()],
+}
+
+fn parse_keys(text: &str, leader: &str) -> Result<Vec<Key>, KeyParseAllError> {
+    let mut keys = Vec::new();
+    let mut parse = |text| {
+        for key in KeyParser::new(text) {
+            match key {
+                Ok(key) => keys.push(key),
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    };
+
+    let mut rest = text;
+    while let Some(index) = rest.find(LEADER_KEY) {
+        parse(&rest[..index])?;
+        parse(leader)?;
+        rest = &rest[index + LEADER_KEY.len()..];
+    }
+    parse(rest)?;
+
+    Ok(keys)
 }
 
 impl KeyMapCollection {
@@ -42,21 +67,11 @@ impl KeyMapCollection {
         mode_kind: ModeKind,
         from: &str,
         to: &str,
+        leader: &str,
     ) -> Result<(), ParseKeyMapError> {
-        fn parse_keys(text: &str) -> Result<Vec<Key>, KeyParseAllError> {
-            let mut keys = Vec::new();
-            for key in KeyParser::new(text) {
-                match key {
-                    Ok(key) => keys.push(key),
-                    Err(error) => return Err(error),
-                }
-            }
-            Ok(keys)
-        }
-
         let map = KeyMap {
-            from: parse_keys(from).map_err(ParseKeyMapError::From)?,
-            to: parse_keys(to).map_err(ParseKeyMapError::To)?,
+            from: parse_keys(from, leader).map_err(ParseKeyMapError::From)?,
+            to: parse_keys(to, leader).map_err(ParseKeyMapError::To)?,
         };
 
         let maps = &mut self.maps[mode_kind as usize];
@@ -71,6 +86,25 @@ impl KeyMapCollection {
         Ok(())
     }
 
+    pub fn parse_and_unmap(
+        &mut self,
+        mode_kind: ModeKind,
+        from: &str,
+        leader: &str,
+    ) -> Result<bool, ParseKeyMapError> {
+        let from = parse_keys(from, leader).map_err(ParseKeyMapError::From)?;
+        let maps = &mut self.maps[mode_kind as usize];
+        let len = maps.len();
+        maps.retain(|m| m.from != from);
+        Ok(maps.len() != len)
+    }
+
+    pub fn iter(&self, mode_kind: ModeKind) -> impl Iterator<Item = (&[Key], &[Key])> {
+        self.maps[mode_kind as usize]
+            .iter()
+            .map(|m| (&m.from[..], &m.to[..]))
+    }
+
     pub fn matches<'a>(&'a self, mode_kind: ModeKind, keys: &[Key]) -> MatchResult<'a> {
         let maps = &self.maps[mode_kind as usize];
 
@@ -91,3 +125,60 @@ impl KeyMapCollection {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_with_leader() {
+        let mut keymaps = KeyMapCollection::default();
+        assert!(keymaps
+            .parse_and_map(
+                ModeKind::Normal,
+                "<leader>gs",
+                "<leader><leader>",
+                "<space>"
+            )
+            .is_ok());
+        assert!(keymaps
+            .parse_and_map(ModeKind::Insert, "<leader>", "x", ",")
+            .is_ok());
+
+        let space = Key::Char(' ');
+        match keymaps.matches(ModeKind::Normal, &[space, Key::Char('g'), Key::Char('s')]) {
+            MatchResult::ReplaceWith(keys) => assert_eq!(&[space, space], keys),
+            _ => panic!("keymap did not match"),
+        }
+        assert!(matches!(
+            keymaps.matches(ModeKind::Insert, &[Key::Char(',')]),
+            MatchResult::ReplaceWith(_)
+        ));
+        assert!(keymaps
+            .parse_and_map(ModeKind::Normal, "<leader", "x", " ")
+            .is_err());
+    }
+
+    #[test]
+    fn unmap() {
+        let mut keymaps = KeyMapCollection::default();
+        assert!(keymaps
+            .parse_and_map(ModeKind::Normal, "ab", "c", "")
+            .is_ok());
+        assert!(keymaps
+            .parse_and_map(ModeKind::Normal, "<c-x>", "d", "")
+            .is_ok());
+        assert_eq!(2, keymaps.iter(ModeKind::Normal).count());
+
+        assert!(!keymaps.parse_and_unmap(ModeKind::Insert, "ab", "").unwrap());
+        assert!(keymaps.parse_and_unmap(ModeKind::Normal, "ab", "").unwrap());
+        assert!(!keymaps.parse_and_unmap(ModeKind::Normal, "ab", "").unwrap());
+        assert!(matches!(
+            keymaps.matches(ModeKind::Normal, &[Key::Char('a')]),
+            MatchResult::None
+        ));
+
+        let maps: Vec<_> = keymaps.iter(ModeKind::Normal).collect();
+        assert_eq!(vec![(&[Key::Ctrl('x')][..], &[Key::Char('d')][..])], maps);
+    }
+}
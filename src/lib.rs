@@ -1,31 +1,52 @@
+use std::time::{Duration, Instant};
+
 pub mod application;
 pub mod buffer;
 pub mod buffer_position;
 pub mod buffer_view;
+pub mod change_list;
 pub mod client;
 pub mod command;
+pub mod compression;
 pub mod config;
 pub mod cursor;
+pub mod dap;
+pub mod decoration;
 pub mod editor;
 pub mod editor_utils;
 pub mod events;
+pub mod file_tree;
+pub mod formatter;
+pub mod git;
 pub mod glob;
+pub mod headless_test;
 pub mod help;
 pub mod history;
 pub mod json;
 pub mod keymap;
+pub mod linter;
 pub mod lsp;
+pub mod make;
 pub mod mode;
+pub mod modeline;
 pub mod navigation_history;
 pub mod pattern;
 pub mod picker;
 pub mod platform;
+pub mod plugin;
+pub mod profile;
 pub mod register;
+pub mod script;
 pub mod serialization;
+pub mod sign;
+pub mod spawn;
 pub mod syntax;
 pub mod theme;
+pub mod todo;
 pub mod ui;
+pub mod url;
 pub mod word_database;
+pub mod workspace;
 
 pub struct ArgsConfig {
     pub path: String,
@@ -39,10 +60,20 @@ pub struct Args {
     pub print_session: bool,
     pub as_focused_client: bool,
     pub quit: bool,
+    pub send: Vec<String>,
+    pub commands: Vec<String>,
+    pub wait: bool,
+    pub compress: bool,
+    pub record_input: Option<String>,
+    pub replay_input: Option<String>,
+    pub headless_test: Option<String>,
     pub server: bool,
     pub configs: Vec<ArgsConfig>,
     pub no_default_config: bool,
+    pub startup_profile: bool,
+    pub stdin: bool,
     pub files: Vec<String>,
+    pub parse_duration: Duration,
 }
 
 fn print_version() {
@@ -55,10 +86,13 @@ fn print_help() {
     print_version();
     println!("{}", env!("CARGO_PKG_DESCRIPTION"));
     println!();
-    println!("usage: pepper [<options...>] [<files...>]");
+    println!("usage: pepper [<options...>] [<files...>] [+<command>...]");
     println!();
     println!("  files: file paths to open as a buffer (clients only)");
     println!("         you can append ':<line>[,<column>]' to open it at that position");
+    println!("         if it's '-', stdin is read into a buffer and, if stdout is not a terminal,");
+    println!("         that buffer is written to stdout on exit (unix only)");
+    println!("  +<command>: same as `--command <command>`");
     println!();
     println!("options:");
     println!();
@@ -68,12 +102,23 @@ fn print_help() {
     println!("  --print-session          prints the computed session name and quits");
     println!("  --as-focused-client      sends events as if it was the currently focused client");
     println!("  --quit                   sends a `quit` event on start");
+    println!("  --send                   sends a command to the focused client, prints its output and quits (repeatable)");
+    println!("                           if the command is '-', commands are read from stdin, one per line");
+    println!("  --command                executes a command after the files are opened (repeatable) (clients only)");
+    println!("  --wait                   opens the files in the focused client and waits until the last one is closed");
+    println!("                           exits with an error if it was not saved");
+    println!("  --compress               asks the server to compress large display updates (useful over slow connections)");
+    println!("  --record-input           records every event sent to the server, with timestamps, to a file at path (clients only)");
+    println!("  --replay-input           replays events from a file made with `--record-input` instead of opening files and commands (clients only)");
+    println!("  --headless-test          runs a test script against an in-process server without a terminal and quits");
+    println!("                           exits with an error if any of its assertions fail");
     println!("  --server                 only run as server");
     println!("  -c, --config             sources config file at path (repeatable) (server only)");
     println!("  --try-config             like `--config` but suppresses the 'file not found' error (repeatable)");
     println!(
         "  --no-default-config      does not source the default config included in the editor"
     );
+    println!("  --startup-profile        shows how long each startup step took (server only)");
 }
 
 impl Args {
@@ -90,6 +135,7 @@ impl Args {
             }
         }
 
+        let start = Instant::now();
         let mut args = std::env::args_os();
         args.next();
 
@@ -120,6 +166,43 @@ impl Args {
                 "--print-session" => parsed.print_session = true,
                 "--as-focused-client" => parsed.as_focused_client = true,
                 "--quit" => parsed.quit = true,
+                "--send" => match args.next() {
+                    Some(arg) => {
+                        let arg = arg_to_str(&arg);
+                        parsed.send.push(arg.into());
+                    }
+                    None => error(format_args!("expected command after {}", arg)),
+                },
+                "--command" => match args.next() {
+                    Some(arg) => {
+                        let arg = arg_to_str(&arg);
+                        parsed.commands.push(arg.into());
+                    }
+                    None => error(format_args!("expected command after {}", arg)),
+                },
+                "--wait" => parsed.wait = true,
+                "--compress" => parsed.compress = true,
+                "--record-input" => match args.next() {
+                    Some(arg) => {
+                        let arg = arg_to_str(&arg);
+                        parsed.record_input = Some(arg.into());
+                    }
+                    None => error(format_args!("expected record path after {}", arg)),
+                },
+                "--replay-input" => match args.next() {
+                    Some(arg) => {
+                        let arg = arg_to_str(&arg);
+                        parsed.replay_input = Some(arg.into());
+                    }
+                    None => error(format_args!("expected record path after {}", arg)),
+                },
+                "--headless-test" => match args.next() {
+                    Some(arg) => {
+                        let arg = arg_to_str(&arg);
+                        parsed.headless_test = Some(arg.into());
+                    }
+                    None => error(format_args!("expected script path after {}", arg)),
+                },
                 "--server" => parsed.server = true,
                 "-c" | "--config" => match args.next() {
                     Some(arg) => {
@@ -142,6 +225,8 @@ impl Args {
                     None => error(format_args!("expected config path after {}", arg)),
                 },
                 "--no-default-config" => parsed.no_default_config = true,
+                "-" => parsed.stdin = true,
+                "--startup-profile" => parsed.startup_profile = true,
                 "--" => {
                     while let Some(arg) = args.next() {
                         let arg = arg_to_str(&arg);
@@ -149,7 +234,9 @@ impl Args {
                     }
                 }
                 _ => {
-                    if arg.starts_with('-') {
+                    if let Some(command) = arg.strip_prefix('+') {
+                        parsed.commands.push(command.into());
+                    } else if arg.starts_with('-') {
                         error(format_args!("invalid option '{}'", arg));
                     } else {
                         parsed.files.push(arg.into());
@@ -158,6 +245,7 @@ impl Args {
             }
         }
 
+        parsed.parse_duration = start.elapsed();
         parsed
     }
 }
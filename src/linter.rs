@@ -0,0 +1,393 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use crate::{
+    buffer::{BufferContent, BufferHandle},
+    buffer_position::{BufferPosition, BufferRange},
+    editor::Editor,
+    editor_utils::{parse_process_command, MessageKind},
+    glob::{Glob, InvalidGlobError},
+    lsp::Diagnostic,
+    make::{match_error_format, DEFAULT_ERROR_FORMATS},
+    platform::{Platform, PlatformRequest, PooledBuf, ProcessHandle, ProcessTag},
+};
+
+struct Linter {
+    glob: Glob,
+    command: String,
+    format: Option<String>,
+}
+
+struct LintJob {
+    alive: bool,
+    discarded: bool,
+    buffer_handle: BufferHandle,
+    linter_index: usize,
+    input: Option<PooledBuf>,
+    output: Vec<u8>,
+}
+
+struct BufferDiagnostics {
+    path: PathBuf,
+    buffer_handle: BufferHandle,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Default)]
+pub struct LinterCollection {
+    linters: Vec<Linter>,
+    jobs: Vec<LintJob>,
+    pending_buffers: Vec<BufferHandle>,
+    buffer_diagnostics: Vec<BufferDiagnostics>,
+}
+
+impl LinterCollection {
+    pub fn add(
+        &mut self,
+        glob: &str,
+        command: &str,
+        format: Option<&str>,
+    ) -> Result<(), InvalidGlobError> {
+        let mut linter_glob = Glob::default();
+        linter_glob.compile(glob)?;
+        self.linters.push(Linter {
+            glob: linter_glob,
+            command: command.into(),
+            format: format.map(String::from),
+        });
+        Ok(())
+    }
+
+    fn find_linter(&self, path: &str) -> Option<usize> {
+        self.linters.iter().rposition(|l| l.glob.matches(path))
+    }
+
+    pub fn buffer_diagnostics(&self, buffer_handle: BufferHandle) -> &[Diagnostic] {
+        for diagnostics in &self.buffer_diagnostics {
+            if diagnostics.buffer_handle == buffer_handle {
+                return &diagnostics.diagnostics;
+            }
+        }
+        &[]
+    }
+
+    pub fn iter(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = (&Path, Option<BufferHandle>, &[Diagnostic])> {
+        self.buffer_diagnostics
+            .iter()
+            .map(|d| (d.path.as_path(), Some(d.buffer_handle), &d.diagnostics[..]))
+    }
+
+    pub fn on_buffer_change(&mut self, platform: &mut Platform, handle: BufferHandle) {
+        if self.linters.is_empty() {
+            return;
+        }
+        if !self.pending_buffers.contains(&handle) {
+            self.pending_buffers.push(handle);
+        }
+        // makes sure an idle event will come after the edits stop
+        platform.requests.enqueue(PlatformRequest::Redraw);
+    }
+
+    pub fn on_buffer_close(&mut self, handle: BufferHandle) {
+        self.pending_buffers.retain(|&h| h != handle);
+        self.buffer_diagnostics
+            .retain(|d| d.buffer_handle != handle);
+        for job in &mut self.jobs {
+            if job.alive && job.buffer_handle == handle {
+                job.discarded = true;
+            }
+        }
+    }
+
+    pub fn on_idle(editor: &mut Editor, platform: &mut Platform) {
+        let mut i = 0;
+        while i < editor.linters.pending_buffers.len() {
+            let handle = editor.linters.pending_buffers[i];
+            if editor.linters.is_linting(handle) {
+                i += 1;
+                continue;
+            }
+
+            editor.linters.pending_buffers.swap_remove(i);
+            Self::lint(editor, platform, handle);
+        }
+    }
+
+    fn is_linting(&self, handle: BufferHandle) -> bool {
+        self.jobs
+            .iter()
+            .any(|j| j.alive && !j.discarded && j.buffer_handle == handle)
+    }
+
+    pub fn lint(editor: &mut Editor, platform: &mut Platform, buffer_handle: BufferHandle) -> bool {
+        let buffer = editor.buffers.get(buffer_handle);
+        let path = buffer.path.to_str().unwrap_or("");
+        let linters = &mut editor.linters;
+        let linter_index = match linters.find_linter(path) {
+            Some(index) => index,
+            None => return false,
+        };
+        if linters.is_linting(buffer_handle) {
+            linters.on_buffer_change(platform, buffer_handle);
+            return true;
+        }
+        let mut command = match parse_process_command(&linters.linters[linter_index].command) {
+            Some(command) => command,
+            None => return false,
+        };
+
+        let index = match linters.jobs.iter().position(|j| !j.alive) {
+            Some(index) => index,
+            None => {
+                linters.jobs.push(LintJob {
+                    alive: false,
+                    discarded: false,
+                    buffer_handle,
+                    linter_index,
+                    input: None,
+                    output: Vec::new(),
+                });
+                linters.jobs.len() - 1
+            }
+        };
+
+        let job = &mut linters.jobs[index];
+        job.alive = true;
+        job.discarded = false;
+        job.buffer_handle = buffer_handle;
+        job.linter_index = linter_index;
+        job.output.clear();
+
+        let mut buf = platform.buf_pool.acquire();
+        let write = buf.write();
+        for line in buffer.content().lines() {
+            write.extend_from_slice(line.as_str().as_bytes());
+            write.push(b'\n');
+        }
+        if let Some(buf) = job.input.replace(buf) {
+            platform.buf_pool.release(buf);
+        }
+
+        command.current_dir(editor.workspace_root(Some(buffer_handle)));
+        command.stdin(Stdio::piped());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::null());
+
+        platform.requests.enqueue(PlatformRequest::SpawnProcess {
+            tag: ProcessTag::Linter(index),
+            command,
+            buf_len: 4 * 1024,
+        });
+        true
+    }
+
+    pub fn on_process_spawned(
+        &mut self,
+        platform: &mut Platform,
+        index: usize,
+        handle: ProcessHandle,
+    ) {
+        if let Some(buf) = self.jobs[index].input.take() {
+            platform
+                .requests
+                .enqueue(PlatformRequest::WriteToProcess { handle, buf });
+            platform
+                .requests
+                .enqueue(PlatformRequest::CloseProcessInput { handle });
+        }
+    }
+
+    pub fn on_process_output(&mut self, index: usize, bytes: &[u8]) {
+        self.jobs[index].output.extend_from_slice(bytes);
+    }
+
+    pub fn on_process_exit(editor: &mut Editor, platform: &mut Platform, index: usize) {
+        let linters = &mut editor.linters;
+        let job = &mut linters.jobs[index];
+        job.alive = false;
+        if let Some(buf) = job.input.take() {
+            platform.buf_pool.release(buf);
+        }
+        let buffer_handle = job.buffer_handle;
+        if job.discarded {
+            return;
+        }
+        if linters.pending_buffers.contains(&buffer_handle) {
+            platform.requests.enqueue(PlatformRequest::Redraw);
+            return;
+        }
+
+        let buffer = editor.buffers.get(buffer_handle);
+        let diagnostics_index = match linters
+            .buffer_diagnostics
+            .iter()
+            .position(|d| d.buffer_handle == buffer_handle)
+        {
+            Some(index) => index,
+            None => {
+                linters.buffer_diagnostics.push(BufferDiagnostics {
+                    path: PathBuf::new(),
+                    buffer_handle,
+                    diagnostics: Vec::new(),
+                });
+                linters.buffer_diagnostics.len() - 1
+            }
+        };
+        let buffer_diagnostics = &mut linters.buffer_diagnostics[diagnostics_index];
+        buffer_diagnostics.path.clear();
+        buffer_diagnostics.path.push(&buffer.path);
+        buffer_diagnostics.diagnostics.clear();
+
+        let job = &linters.jobs[index];
+        let output = String::from_utf8_lossy(&job.output);
+        let format = linters.linters[job.linter_index].format.as_deref();
+        let buffer_path = buffer.path.to_str().unwrap_or("");
+        parse_lint_output(
+            &output,
+            format,
+            buffer_path,
+            buffer.content(),
+            &mut buffer_diagnostics.diagnostics,
+        );
+
+        let count = buffer_diagnostics.diagnostics.len();
+        if count == 0 {
+            linters.buffer_diagnostics.swap_remove(diagnostics_index);
+        } else {
+            editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("lint: {} diagnostics", count));
+        }
+    }
+}
+
+fn is_same_path(buffer_path: &str, path: &str) -> bool {
+    let path = path.trim();
+    let path = path.strip_prefix("./").unwrap_or(path);
+    matches!(path, "-" | "stdin" | "<stdin>")
+        || buffer_path.ends_with(path)
+        || path.ends_with(buffer_path)
+}
+
+fn diagnostic_range(content: &BufferContent, position: BufferPosition) -> BufferRange {
+    let position = content.position_from_char_column(position);
+    let line = content.line_at(position.line_index as _).as_str();
+    let column = position.column_byte_index as usize;
+
+    let word_len = line[column..]
+        .find(char::is_whitespace)
+        .unwrap_or(line.len() - column);
+    let (from, to) = if word_len > 0 {
+        (column, column + word_len)
+    } else if column < line.len() {
+        let len = line[column..]
+            .chars()
+            .next()
+            .map(char::len_utf8)
+            .unwrap_or(0);
+        (column, column + len)
+    } else {
+        let indentation_len = line.len() - line.trim_start().len();
+        (indentation_len.min(column), line.len())
+    };
+
+    BufferRange::between(
+        BufferPosition::line_col(position.line_index, from as _),
+        BufferPosition::line_col(position.line_index, to as _),
+    )
+}
+
+pub fn parse_lint_output(
+    output: &str,
+    format: Option<&str>,
+    buffer_path: &str,
+    content: &BufferContent,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut previous_line = "";
+    for line in output.lines() {
+        let result = match format {
+            Some(format) => match_error_format(format, line),
+            None => DEFAULT_ERROR_FORMATS
+                .iter()
+                .find_map(|f| match_error_format(f, line)),
+        };
+        let result = match result {
+            Some(result) => result,
+            None => {
+                previous_line = line.trim();
+                continue;
+            }
+        };
+        if !is_same_path(buffer_path, result.path)
+            || result.position.line_index as usize >= content.line_count()
+        {
+            continue;
+        }
+
+        diagnostics.push(Diagnostic {
+            message: result.message.unwrap_or(previous_line).into(),
+            range: diagnostic_range(content, result.position),
+            data: Vec::new(),
+        });
+    }
+
+    diagnostics.sort_by_key(|d| d.range.from);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lint_output_parsing() {
+        let mut content = BufferContent::new();
+        content.insert_text(BufferPosition::zero(), "fn main() {\n    let x = 0;\n}\n");
+
+        let output = concat!(
+            "src/main.rs:2:9: warning: unused variable `x`\n",
+            "other.rs:1:1: error: not this file\n",
+            "src/main.rs:9:1: error: out of bounds\n",
+            "src/main.rs:1:12: note: line end\n",
+            "summary without position\n",
+        );
+        let mut diagnostics = Vec::new();
+        parse_lint_output(output, None, "src/main.rs", &content, &mut diagnostics);
+
+        assert_eq!(2, diagnostics.len());
+        assert_eq!("note: line end", diagnostics[0].message);
+        assert_eq!(
+            BufferRange::between(
+                BufferPosition::line_col(0, 0),
+                BufferPosition::line_col(0, 11),
+            ),
+            diagnostics[0].range
+        );
+        assert_eq!("warning: unused variable `x`", diagnostics[1].message);
+        assert_eq!(
+            BufferRange::between(
+                BufferPosition::line_col(1, 8),
+                BufferPosition::line_col(1, 9),
+            ),
+            diagnostics[1].range
+        );
+
+        diagnostics.clear();
+        let output = "In <stdin>\n<stdin>:2:5\n";
+        parse_lint_output(output, Some("%f:%l:%c"), "a.rs", &content, &mut diagnostics);
+        assert_eq!(1, diagnostics.len());
+        assert_eq!("In <stdin>", diagnostics[0].message);
+        assert_eq!(
+            BufferRange::between(
+                BufferPosition::line_col(1, 4),
+                BufferPosition::line_col(1, 7),
+            ),
+            diagnostics[0].range
+        );
+    }
+}
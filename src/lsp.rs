@@ -6,4 +6,9 @@ pub use client::Client;
 pub use client::ClientHandle;
 pub use client::ClientManager;
 pub use client::Diagnostic;
+pub use client::LspConfigError;
+pub use protocol::try_get_content_range;
+pub use protocol::Protocol;
+pub use protocol::ResponseError;
 pub use protocol::ServerEvent;
+pub use protocol::ServerRequest;
@@ -1,4 +1,7 @@
-use crate::json::{Json, JsonArray, JsonObject, JsonValue};
+use crate::{
+    json::{Json, JsonArray, JsonObject, JsonValue},
+    lsp::protocol::PositionEncoding,
+};
 
 pub fn client_capabilities(json: &mut Json) -> JsonValue {
     fn symbol_kind(json: &mut Json) -> JsonObject {
@@ -53,17 +56,35 @@ pub fn client_capabilities(json: &mut Json) -> JsonValue {
     {
         let mut workspace_capabilities = JsonObject::default();
         workspace_capabilities.set("applyEdit".into(), true.into(), json);
-        workspace_capabilities.set("workspaceFolders".into(), false.into(), json);
-        workspace_capabilities.set("configuration".into(), false.into(), json);
-
+        workspace_capabilities.set("workspaceFolders".into(), true.into(), json);
+        workspace_capabilities.set("configuration".into(), true.into(), json);
         workspace_capabilities.set(
-            "didChangeWatchedFiles".into(),
+            "didChangeConfiguration".into(),
             JsonObject::default().into(),
             json,
         );
 
+        {
+            let mut did_change_watched_files = JsonObject::default();
+            did_change_watched_files.set("dynamicRegistration".into(), true.into(), json);
+
+            workspace_capabilities.set(
+                "didChangeWatchedFiles".into(),
+                did_change_watched_files.into(),
+                json,
+            );
+        }
+
         workspace_capabilities.set("executeCommand".into(), JsonObject::default().into(), json);
 
+        {
+            let mut file_operations = JsonObject::default();
+            file_operations.set("willRename".into(), true.into(), json);
+            file_operations.set("didRename".into(), true.into(), json);
+
+            workspace_capabilities.set("fileOperations".into(), file_operations.into(), json);
+        }
+
         {
             let mut workspace_edit_capabilities = JsonObject::default();
             workspace_edit_capabilities.set("documentChanges".into(), true.into(), json);
@@ -120,7 +141,7 @@ pub fn client_capabilities(json: &mut Json) -> JsonValue {
 
             {
                 let mut completion_item = JsonObject::default();
-                completion_item.set("snippetSupport".into(), false.into(), json);
+                completion_item.set("snippetSupport".into(), true.into(), json);
                 completion_item.set("commitCharactersSupport".into(), false.into(), json);
 
                 let mut documentation_formats = JsonArray::default();
@@ -135,6 +156,13 @@ pub fn client_capabilities(json: &mut Json) -> JsonValue {
                 completion_item.set("preselectSupport".into(), false.into(), json);
                 completion_item.set("tagSupport".into(), tag_support(json).into(), json);
 
+                let mut resolve_support = JsonObject::default();
+                let mut properties = JsonArray::default();
+                properties.push("detail".into(), json);
+                properties.push("documentation".into(), json);
+                resolve_support.set("properties".into(), properties.into(), json);
+                completion_item.set("resolveSupport".into(), resolve_support.into(), json);
+
                 completion.set("completionItem".into(), completion_item.into(), json);
             }
 
@@ -289,6 +317,7 @@ pub fn client_capabilities(json: &mut Json) -> JsonValue {
             );
         }
 
+        text_document_capabilities.set("inlayHint".into(), JsonObject::default().into(), json);
         text_document_capabilities.set("selectionRange".into(), JsonObject::default().into(), json);
 
         capabilities.set(
@@ -317,5 +346,17 @@ pub fn client_capabilities(json: &mut Json) -> JsonValue {
         capabilities.set("window".into(), window_capabilities.into(), json);
     }
 
+    {
+        let mut general_capabilities = JsonObject::default();
+
+        let mut position_encodings = JsonArray::default();
+        for encoding in &PositionEncoding::SUPPORTED {
+            position_encodings.push(encoding.name().into(), json);
+        }
+        general_capabilities.set("positionEncodings".into(), position_encodings.into(), json);
+
+        capabilities.set("general".into(), general_capabilities.into(), json);
+    }
+
     capabilities.into()
 }
@@ -1,39 +1,46 @@
 use std::{
+    cmp::Ordering,
+    collections::VecDeque,
     fmt,
-    fs::File,
+    fs::{self, File},
     io,
     ops::Range,
     path::{Path, PathBuf},
     process::{self, Command, Stdio},
     str::FromStr,
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
 
 use crate::{
     buffer::{BufferCapabilities, BufferContent, BufferHandle},
-    buffer_position::{BufferPosition, BufferRange},
+    buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
     buffer_view::BufferViewHandle,
     client,
+    config::LspTrace,
     cursor::Cursor,
     editor::Editor,
-    editor_utils::{hash_bytes, parse_process_command, MessageKind, StatusBar},
+    editor_utils::{self, hash_bytes, parse_process_command, LogLevel, MessageKind, StatusBar},
     events::{EditorEvent, EditorEventIter},
-    glob::{Glob, InvalidGlobError},
+    glob::{Glob, IgnoreList, InvalidGlobError},
     json::{
         FromJson, Json, JsonArray, JsonConvertError, JsonInteger, JsonObject, JsonString, JsonValue,
     },
     lsp::{
         capabilities,
         protocol::{
-            self, DocumentCodeAction, DocumentCompletionItem, DocumentDiagnostic, DocumentLocation,
+            self, completion_item_kind_name, strip_snippet_placeholders, DocumentCodeAction,
+            DocumentCompletionItem, DocumentDiagnostic, DocumentInlayHint, DocumentLocation,
             DocumentPosition, DocumentRange, DocumentSymbolInformation, PendingRequestColection,
-            Protocol, ProtocolError, ResponseError, ServerEvent, ServerNotification, ServerRequest,
-            ServerResponse, TextEdit, Uri, WorkspaceEdit,
+            PositionEncoding, Protocol, ProtocolError, RequestId, ResponseError, ServerEvent,
+            ServerNotification, ServerRequest, ServerResponse, SymbolKind, TextEdit, Uri,
+            WorkspaceEdit,
         },
     },
     mode::{picker, read_line, ModeContext, ModeKind},
     navigation_history::NavigationHistory,
     picker::Picker,
-    platform::{Platform, PlatformRequest, ProcessHandle, ProcessTag},
+    platform::{Job, JobTag, Platform, PlatformRequest, ProcessHandle, ProcessTag},
     word_database::{WordIndicesIter, WordKind},
 };
 
@@ -80,6 +87,24 @@ impl<'json> FromJson<'json> for TriggerCharactersCapability {
     }
 }
 
+#[derive(Default)]
+struct FileOperationsCapability {
+    pub will_rename: bool,
+    pub did_rename: bool,
+}
+impl<'json> FromJson<'json> for FileOperationsCapability {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        let file_operations = value.get("fileOperations", json);
+        Ok(Self {
+            will_rename: matches!(
+                file_operations.clone().get("willRename", json),
+                JsonValue::Object(_)
+            ),
+            did_rename: matches!(file_operations.get("didRename", json), JsonValue::Object(_)),
+        })
+    }
+}
+
 #[derive(Default)]
 struct RenameCapability {
     pub on: bool,
@@ -192,21 +217,45 @@ impl<'json> FromJson<'json> for TextDocumentSyncCapability {
     }
 }
 
+#[derive(Default)]
+struct CompletionCapability {
+    pub on: bool,
+    pub trigger_characters: String,
+    pub resolve_provider: bool,
+}
+impl<'json> FromJson<'json> for CompletionCapability {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        let trigger_characters = TriggerCharactersCapability::from_json(value.clone(), json)?;
+        let resolve_provider =
+            matches!(value.get("resolveProvider", json), JsonValue::Boolean(true));
+        Ok(Self {
+            on: trigger_characters.on,
+            trigger_characters: trigger_characters.trigger_characters,
+            resolve_provider,
+        })
+    }
+}
+
 #[derive(Default)]
 struct ServerCapabilities {
     text_document_sync: TextDocumentSyncCapability,
-    completion_provider: TriggerCharactersCapability,
+    completion_provider: CompletionCapability,
     hover_provider: GenericCapability,
     signature_help_provider: TriggerCharactersCapability,
     declaration_provider: GenericCapability,
     definition_provider: GenericCapability,
+    type_definition_provider: GenericCapability,
     implementation_provider: GenericCapability,
     references_provider: GenericCapability,
     document_symbol_provider: GenericCapability,
     code_action_provider: GenericCapability,
     document_formatting_provider: GenericCapability,
+    document_range_formatting_provider: GenericCapability,
     rename_provider: RenameCapability,
     workspace_symbol_provider: GenericCapability,
+    inlay_hint_provider: GenericCapability,
+    file_operations: FileOperationsCapability,
+    position_encoding: PositionEncoding,
 }
 impl<'json> FromJson<'json> for ServerCapabilities {
     fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
@@ -227,6 +276,9 @@ impl<'json> FromJson<'json> for ServerCapabilities {
                 "definitionProvider" => {
                     this.definition_provider = FromJson::from_json(value, json)?
                 }
+                "typeDefinitionProvider" => {
+                    this.type_definition_provider = FromJson::from_json(value, json)?
+                }
                 "implementationProvider" => {
                     this.implementation_provider = FromJson::from_json(value, json)?
                 }
@@ -242,10 +294,16 @@ impl<'json> FromJson<'json> for ServerCapabilities {
                 "documentFormattingProvider" => {
                     this.document_formatting_provider = FromJson::from_json(value, json)?
                 }
+                "documentRangeFormattingProvider" => {
+                    this.document_range_formatting_provider = FromJson::from_json(value, json)?
+                }
                 "renameProvider" => this.rename_provider = FromJson::from_json(value, json)?,
                 "workspaceSymbolProvider" => {
                     this.workspace_symbol_provider = FromJson::from_json(value, json)?
                 }
+                "inlayHintProvider" => this.inlay_hint_provider = FromJson::from_json(value, json)?,
+                "workspace" => this.file_operations = FromJson::from_json(value, json)?,
+                "positionEncoding" => this.position_encoding = FromJson::from_json(value, json)?,
                 _ => (),
             }
         }
@@ -259,7 +317,12 @@ pub struct Diagnostic {
     pub data: Vec<u8>,
 }
 impl Diagnostic {
-    pub fn as_document_diagnostic(&self, json: &mut Json) -> DocumentDiagnostic {
+    pub fn as_document_diagnostic(
+        &self,
+        content: &BufferContent,
+        encoding: PositionEncoding,
+        json: &mut Json,
+    ) -> DocumentDiagnostic {
         let mut reader = io::Cursor::new(&self.data);
         let data = match json.read(&mut reader) {
             Ok(value) => value,
@@ -267,7 +330,7 @@ impl Diagnostic {
         };
         DocumentDiagnostic {
             message: json.create_string(&self.message),
-            range: self.range.into(),
+            range: DocumentRange::from_buffer_range(self.range, content, encoding),
             data,
         }
     }
@@ -280,9 +343,15 @@ struct BufferDiagnosticCollection {
     len: usize,
 }
 impl BufferDiagnosticCollection {
-    pub fn add(&mut self, diagnostic: DocumentDiagnostic, json: &Json) {
+    pub fn add(
+        &mut self,
+        diagnostic: DocumentDiagnostic,
+        content: Option<&BufferContent>,
+        encoding: PositionEncoding,
+        json: &Json,
+    ) {
         let message = diagnostic.message.as_str(json);
-        let range = diagnostic.range.into();
+        let range = diagnostic.range.to_buffer_range(content, encoding);
 
         if self.len < self.diagnostics.len() {
             let diagnostic = &mut self.diagnostics[self.len];
@@ -511,6 +580,275 @@ impl DiagnosticCollection {
     }
 }
 
+const WATCH_KIND_CREATE: u8 = 1;
+const WATCH_KIND_CHANGE: u8 = 2;
+const WATCH_KIND_DELETE: u8 = 4;
+const WATCHED_FILES_SCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
+struct FileWatcher {
+    glob: Glob,
+    kind: u8,
+}
+
+struct WatchedFile {
+    path: String,
+    modified: u128,
+}
+
+#[derive(Default)]
+struct WatchedFiles {
+    watchers: Vec<FileWatcher>,
+    files: Vec<WatchedFile>,
+    has_files: bool,
+    scanning: bool,
+    discard_scan: bool,
+}
+impl WatchedFiles {
+    fn watch_kind(&self, path: &str) -> u8 {
+        watch_kind(&self.watchers, path)
+    }
+
+    fn on_watchers_changed(&mut self) {
+        self.files.clear();
+        self.has_files = false;
+        self.discard_scan = self.scanning;
+    }
+
+    // waits for `delay` and then outputs a `<modified nanos>\t<relative path>` line
+    // for each watched file under `root`
+    fn scan_job(&self, root: PathBuf, find_file_ignore: &str, delay: Duration) -> Job {
+        let watchers = self.watchers.clone();
+        let mut ignore = IgnoreList::default();
+        if let Ok(gitignore) = fs::read_to_string(root.join(".gitignore")) {
+            let _ = ignore.add_lines(&gitignore);
+        }
+        for pattern in find_file_ignore.split_whitespace() {
+            let _ = ignore.add(pattern);
+        }
+
+        fn scan(
+            root: &Path,
+            dir: &Path,
+            watchers: &[FileWatcher],
+            ignore: &IgnoreList,
+            output: &mut Vec<u8>,
+        ) {
+            use io::Write;
+
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => return,
+            };
+            for entry in entries.flatten() {
+                if entry.file_name().to_string_lossy().starts_with('.') {
+                    continue;
+                }
+                let path = entry.path();
+                let file_type = match entry.file_type() {
+                    Ok(file_type) => file_type,
+                    Err(_) => continue,
+                };
+                let relative_path = match path.strip_prefix(root).ok().and_then(Path::to_str) {
+                    Some(path) if !path.contains('\n') => path,
+                    _ => continue,
+                };
+                let is_dir = file_type.is_dir();
+                if ignore.is_ignored(relative_path, is_dir) {
+                    continue;
+                }
+                if is_dir {
+                    scan(root, &path, watchers, ignore, output);
+                    continue;
+                }
+
+                if watch_kind(watchers, relative_path) == 0 {
+                    continue;
+                }
+                let modified = match entry.metadata().and_then(|m| m.modified()) {
+                    Ok(modified) => modified,
+                    Err(_) => continue,
+                };
+                let modified = modified
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|d| d.as_nanos())
+                    .unwrap_or(0);
+                let _ = writeln!(output, "{}\t{}", modified, relative_path);
+            }
+        }
+
+        Box::new(move |output| {
+            thread::sleep(delay);
+            scan(&root, &root, &watchers, &ignore, output);
+        })
+    }
+}
+
+fn watch_kind(watchers: &[FileWatcher], path: &str) -> u8 {
+    watchers
+        .iter()
+        .filter(|w| w.glob.matches(path))
+        .fold(0, |kind, w| kind | w.kind)
+}
+
+const LOG_CAPACITY: usize = 256;
+
+struct Log {
+    trace: LspTrace,
+    entries: VecDeque<String>,
+    scratch: Vec<u8>,
+    file_path: String,
+    file: Option<io::BufWriter<File>>,
+}
+impl Log {
+    pub fn new(file_path: Option<String>) -> Self {
+        let (file_path, file) = match file_path {
+            Some(path) => match File::create(&path) {
+                Ok(file) => (path, Some(io::BufWriter::new(file))),
+                Err(_) => (String::new(), None),
+            },
+            None => (String::new(), None),
+        };
+        Self {
+            trace: LspTrace::Messages,
+            entries: VecDeque::new(),
+            scratch: Vec::new(),
+            file_path,
+            file,
+        }
+    }
+
+    pub fn write<F>(&mut self, json: &Json, body: Option<&JsonValue>, header: F)
+    where
+        F: FnOnce(&mut Vec<u8>, &Json),
+    {
+        use io::Write;
+
+        if self.trace == LspTrace::Off && self.file.is_none() {
+            return;
+        }
+
+        self.scratch.clear();
+        header(&mut self.scratch, json);
+        let header_len = self.scratch.len();
+        if let Some(body) = body {
+            if self.trace == LspTrace::Verbose || self.file.is_some() {
+                self.scratch.push(b'\n');
+                let _ = json.write(&mut self.scratch, body);
+            }
+        }
+
+        let entry_len = match self.trace {
+            LspTrace::Off => 0,
+            LspTrace::Messages => header_len,
+            LspTrace::Verbose => self.scratch.len(),
+        };
+        if entry_len > 0 {
+            let mut entry = if self.entries.len() < LOG_CAPACITY {
+                String::new()
+            } else {
+                let mut entry = self.entries.pop_front().unwrap_or_default();
+                entry.clear();
+                entry
+            };
+            entry.push_str(&String::from_utf8_lossy(&self.scratch[..entry_len]));
+            self.entries.push_back(entry);
+        }
+
+        if let Some(ref mut file) = self.file {
+            let _ = file.write_all(&self.scratch);
+            let _ = file.write_all(b"\n\n");
+            let _ = file.flush();
+        }
+    }
+}
+
+pub struct InlayHint {
+    pub position: BufferPosition,
+    pub label: String,
+}
+
+struct BufferInlayHintCollection {
+    buffer_handle: BufferHandle,
+    line_range: Range<BufferPositionIndex>,
+    dirty: bool,
+    hints: Vec<InlayHint>,
+    len: usize,
+}
+
+#[derive(Default)]
+pub struct InlayHintCollection {
+    buffer_hints: Vec<BufferInlayHintCollection>,
+    pending_request: Option<(BufferHandle, RequestId)>,
+}
+impl InlayHintCollection {
+    pub fn buffer_inlay_hints(&self, buffer_handle: BufferHandle) -> &[InlayHint] {
+        for hints in &self.buffer_hints {
+            if hints.buffer_handle == buffer_handle {
+                return &hints.hints[..hints.len];
+            }
+        }
+        &[]
+    }
+
+    fn needs_refresh(
+        &self,
+        buffer_handle: BufferHandle,
+        line_range: &Range<BufferPositionIndex>,
+    ) -> bool {
+        match self
+            .buffer_hints
+            .iter()
+            .find(|h| h.buffer_handle == buffer_handle)
+        {
+            Some(hints) => {
+                hints.dirty
+                    || line_range.start < hints.line_range.start
+                    || line_range.end > hints.line_range.end
+            }
+            None => true,
+        }
+    }
+
+    fn hints_for_buffer(&mut self, buffer_handle: BufferHandle) -> &mut BufferInlayHintCollection {
+        match self
+            .buffer_hints
+            .iter()
+            .position(|h| h.buffer_handle == buffer_handle)
+        {
+            Some(index) => &mut self.buffer_hints[index],
+            None => {
+                let end_index = self.buffer_hints.len();
+                self.buffer_hints.push(BufferInlayHintCollection {
+                    buffer_handle,
+                    line_range: 0..0,
+                    dirty: true,
+                    hints: Vec::new(),
+                    len: 0,
+                });
+                &mut self.buffer_hints[end_index]
+            }
+        }
+    }
+
+    fn set_dirty(&mut self, buffer_handle: Option<BufferHandle>) {
+        for hints in &mut self.buffer_hints {
+            if buffer_handle.is_none() || buffer_handle == Some(hints.buffer_handle) {
+                hints.dirty = true;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer_hints.clear();
+    }
+
+    fn on_close_buffer(&mut self, buffer_handle: BufferHandle) {
+        self.buffer_hints
+            .retain(|h| h.buffer_handle != buffer_handle);
+    }
+}
+
 enum RequestState {
     Idle,
     Definition {
@@ -519,6 +857,9 @@ enum RequestState {
     Declaration {
         client_handle: client::ClientHandle,
     },
+    TypeDefinition {
+        client_handle: client::ClientHandle,
+    },
     Implementation {
         client_handle: client::ClientHandle,
     },
@@ -533,9 +874,14 @@ enum RequestState {
         buffer_position: BufferPosition,
     },
     FinishRename {
+        client_handle: client::ClientHandle,
         buffer_handle: BufferHandle,
         buffer_position: BufferPosition,
     },
+    PendingRename {
+        client_handle: client::ClientHandle,
+    },
+    RenamePreview,
     CodeAction {
         client_handle: client::ClientHandle,
     },
@@ -553,16 +899,28 @@ enum RequestState {
     FinishWorkspaceSymbols,
     Formatting {
         buffer_handle: BufferHandle,
+        save_after: bool,
     },
     Completion {
         client_handle: client::ClientHandle,
         buffer_handle: BufferHandle,
     },
+    CompletionResolve,
 }
 impl RequestState {
     pub fn is_idle(&self) -> bool {
         matches!(self, RequestState::Idle)
     }
+
+    pub fn buffer_handle(&self) -> Option<BufferHandle> {
+        match self {
+            RequestState::Rename { buffer_handle, .. }
+            | RequestState::FinishRename { buffer_handle, .. }
+            | RequestState::Formatting { buffer_handle, .. }
+            | RequestState::Completion { buffer_handle, .. } => Some(*buffer_handle),
+            _ => None,
+        }
+    }
 }
 
 pub struct Client {
@@ -570,54 +928,74 @@ pub struct Client {
     protocol: Protocol,
     json: Json,
     root: PathBuf,
+    workspace_roots: Vec<PathBuf>,
     pending_requests: PendingRequestColection,
 
     initialized: bool,
+    server_name: String,
     server_capabilities: ServerCapabilities,
 
+    globs: Vec<Glob>,
     document_selectors: Vec<Glob>,
     versioned_buffers: VersionedBufferCollection,
     diagnostics: DiagnosticCollection,
+    inlay_hints: InlayHintCollection,
+    watched_files: WatchedFiles,
 
     temp_edits: Vec<(BufferRange, BufferRange)>,
 
     request_state: RequestState,
+    request_state_id: Option<RequestId>,
     request_raw_json: Vec<u8>,
+    completion_raw_json: Vec<u8>,
+    completion_item_ranges: Vec<Range<usize>>,
+    completion_position: DocumentPosition,
+    completion_trailing_replaced: bool,
+    format_on_save_skip: Option<BufferHandle>,
+    format_on_save_pending: Option<BufferHandle>,
 
-    log_file_path: String,
-    log_file: Option<io::BufWriter<File>>,
+    initialization_options: Vec<u8>,
+    settings: Vec<u8>,
+
+    log: Log,
 }
 
 impl Client {
     fn new(handle: ClientHandle, root: PathBuf, log_file_path: Option<String>) -> Self {
-        let (log_file_path, log_file) = match log_file_path {
-            Some(path) => match File::create(&path) {
-                Ok(file) => (path, Some(io::BufWriter::new(file))),
-                Err(_) => (String::new(), None),
-            },
-            None => (String::new(), None),
-        };
-
         Self {
             handle,
             protocol: Protocol::new(),
             json: Json::new(),
             root,
+            workspace_roots: Vec::new(),
             pending_requests: PendingRequestColection::default(),
 
             initialized: false,
+            server_name: String::new(),
             server_capabilities: ServerCapabilities::default(),
 
+            globs: Vec::new(),
             document_selectors: Vec::new(),
             versioned_buffers: VersionedBufferCollection::default(),
             diagnostics: DiagnosticCollection::default(),
+            inlay_hints: InlayHintCollection::default(),
+            watched_files: WatchedFiles::default(),
 
             request_state: RequestState::Idle,
+            request_state_id: None,
             request_raw_json: Vec::new(),
+            completion_raw_json: Vec::new(),
+            completion_item_ranges: Vec::new(),
+            completion_position: DocumentPosition::default(),
+            completion_trailing_replaced: false,
+            format_on_save_skip: None,
+            format_on_save_pending: None,
             temp_edits: Vec::new(),
 
-            log_file_path,
-            log_file,
+            initialization_options: Vec::new(),
+            settings: Vec::new(),
+
+            log: Log::new(log_file_path),
         }
     }
 
@@ -625,19 +1003,89 @@ impl Client {
         self.handle
     }
 
+    pub fn server_name(&self) -> &str {
+        &self.server_name
+    }
+
     pub fn handles_path(&self, path: &str) -> bool {
-        if self.document_selectors.is_empty() {
-            true
-        } else {
-            self.document_selectors.iter().any(|g| g.matches(path))
+        let matches_globs = self.globs.is_empty() || self.globs.iter().any(|g| g.matches(path));
+        let matches_selectors = self.document_selectors.is_empty()
+            || self.document_selectors.iter().any(|g| g.matches(path));
+        matches_globs && matches_selectors
+    }
+
+    fn handles_buffer(&self, editor: &Editor, buffer_handle: BufferHandle) -> bool {
+        let buffer = editor.buffers.get(buffer_handle);
+        if buffer.is_large_file() {
+            return false;
+        }
+        match buffer.path.to_str() {
+            Some(path) => self.handles_path(path),
+            None => false,
+        }
+    }
+
+    fn will_rename_file(
+        &mut self,
+        platform: &mut Platform,
+        old_path: &Path,
+        new_path: &Path,
+    ) -> bool {
+        if !self.server_capabilities.file_operations.will_rename {
+            return false;
+        }
+
+        let params = helper::file_rename_params(self, old_path, new_path);
+        self.request(platform, "workspace/willRenameFiles", params);
+        true
+    }
+
+    pub fn did_rename_file(
+        &mut self,
+        editor: &Editor,
+        platform: &mut Platform,
+        old_path: &Path,
+        new_path: &Path,
+        renamed_buffers: &[(BufferHandle, PathBuf)],
+    ) {
+        helper::send_pending_did_change(self, editor, platform);
+
+        for (handle, old_buffer_path) in renamed_buffers {
+            let handle = *handle;
+            if !editor.buffers.get(handle).capabilities.can_save {
+                continue;
+            }
+
+            if let Some(true) = old_buffer_path.to_str().map(|p| self.handles_path(p)) {
+                self.versioned_buffers.dispose(handle);
+                self.diagnostics.on_close_buffer(handle);
+                self.inlay_hints.on_close_buffer(handle);
+                helper::send_did_close_path(self, platform, old_buffer_path);
+            }
+            if self.handles_buffer(editor, handle) {
+                self.diagnostics.on_load_buffer(editor, handle, &self.root);
+                helper::send_did_open(self, editor, platform, handle);
+            }
+        }
+
+        if self.server_capabilities.file_operations.did_rename {
+            let params = helper::file_rename_params(self, old_path, new_path);
+            self.notify(platform, "workspace/didRenameFiles", params);
         }
     }
 
     pub fn log_file_path(&self) -> Option<&str> {
-        if self.log_file_path.is_empty() {
+        if self.log.file_path.is_empty() {
             None
         } else {
-            Some(&self.log_file_path)
+            Some(&self.log.file_path)
+        }
+    }
+
+    pub fn write_log(&self, text: &mut String) {
+        for entry in &self.log.entries {
+            text.push_str(entry);
+            text.push_str("\n\n");
         }
     }
 
@@ -645,6 +1093,14 @@ impl Client {
         &self.diagnostics
     }
 
+    pub fn inlay_hints(&self) -> &InlayHintCollection {
+        &self.inlay_hints
+    }
+
+    pub fn clear_inlay_hints(&mut self) {
+        self.inlay_hints.clear();
+    }
+
     pub fn signature_help_triggers(&self) -> &str {
         &self
             .server_capabilities
@@ -659,8 +1115,11 @@ impl Client {
             .trigger_characters
     }
 
-    pub fn cancel_current_request(&mut self) {
+    pub fn cancel_current_request(&mut self, platform: &mut Platform) {
         self.request_state = RequestState::Idle;
+        if let Some(id) = self.request_state_id.take() {
+            self.cancel_request(platform, id);
+        }
     }
 
     pub fn hover(
@@ -676,9 +1135,13 @@ impl Client {
 
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(
+            buffer_position,
+            buffer.content(),
+            self.server_capabilities.position_encoding,
+        );
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -704,9 +1167,13 @@ impl Client {
 
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(
+            buffer_position,
+            buffer.content(),
+            self.server_capabilities.position_encoding,
+        );
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -728,9 +1195,13 @@ impl Client {
     ) -> JsonObject {
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(
+            buffer_position,
+            buffer.content(),
+            self.server_capabilities.position_encoding,
+        );
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -757,7 +1228,7 @@ impl Client {
 
         let params = self.make_definition_params(editor, platform, buffer_handle, buffer_position);
         self.request_state = RequestState::Definition { client_handle };
-        self.request(platform, "textDocument/definition", params);
+        self.request_state_id = self.request(platform, "textDocument/definition", params);
     }
 
     pub fn declaration(
@@ -774,7 +1245,24 @@ impl Client {
 
         let params = self.make_definition_params(editor, platform, buffer_handle, buffer_position);
         self.request_state = RequestState::Declaration { client_handle };
-        self.request(platform, "textDocument/declaration", params);
+        self.request_state_id = self.request(platform, "textDocument/declaration", params);
+    }
+
+    pub fn type_definition(
+        &mut self,
+        editor: &Editor,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        buffer_position: BufferPosition,
+        client_handle: client::ClientHandle,
+    ) {
+        if !self.server_capabilities.type_definition_provider.0 || !self.request_state.is_idle() {
+            return;
+        }
+
+        let params = self.make_definition_params(editor, platform, buffer_handle, buffer_position);
+        self.request_state = RequestState::TypeDefinition { client_handle };
+        self.request_state_id = self.request(platform, "textDocument/typeDefinition", params);
     }
 
     pub fn implementation(
@@ -791,7 +1279,7 @@ impl Client {
 
         let params = self.make_definition_params(editor, platform, buffer_handle, buffer_position);
         self.request_state = RequestState::Implementation { client_handle };
-        self.request(platform, "textDocument/implementation", params);
+        self.request_state_id = self.request(platform, "textDocument/implementation", params);
     }
 
     pub fn references(
@@ -810,9 +1298,13 @@ impl Client {
 
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(
+            buffer_position,
+            buffer.content(),
+            self.server_capabilities.position_encoding,
+        );
 
         let mut context = JsonObject::default();
         context.set("includeDeclaration".into(), true.into(), &mut self.json);
@@ -831,7 +1323,7 @@ impl Client {
             context_len,
             auto_close_buffer,
         };
-        self.request(platform, "textDocument/references", params);
+        self.request_state_id = self.request(platform, "textDocument/references", params);
     }
 
     pub fn rename(
@@ -849,9 +1341,13 @@ impl Client {
 
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(
+            buffer_position,
+            buffer.content(),
+            self.server_capabilities.position_encoding,
+        );
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -867,9 +1363,10 @@ impl Client {
                 buffer_handle,
                 buffer_position,
             };
-            self.request(platform, "textDocument/prepareRename", params);
+            self.request_state_id = self.request(platform, "textDocument/prepareRename", params);
         } else {
             self.request_state = RequestState::FinishRename {
+                client_handle,
                 buffer_handle,
                 buffer_position,
             };
@@ -884,11 +1381,12 @@ impl Client {
     }
 
     pub fn finish_rename(&mut self, editor: &Editor, platform: &mut Platform) {
-        let (buffer_handle, buffer_position) = match self.request_state {
+        let (client_handle, buffer_handle, buffer_position) = match self.request_state {
             RequestState::FinishRename {
+                client_handle,
                 buffer_handle,
                 buffer_position,
-            } => (buffer_handle, buffer_position),
+            } => (client_handle, buffer_handle, buffer_position),
             _ => return,
         };
         self.request_state = RequestState::Idle;
@@ -898,9 +1396,13 @@ impl Client {
 
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(
+            buffer_position,
+            buffer.content(),
+            self.server_capabilities.position_encoding,
+        );
         let new_name = self.json.create_string(editor.read_line.input());
 
         let mut params = JsonObject::default();
@@ -912,7 +1414,34 @@ impl Client {
         );
         params.set("newName".into(), new_name.into(), &mut self.json);
 
-        self.request(platform, "textDocument/rename", params);
+        self.request_state = RequestState::PendingRename { client_handle };
+        self.request_state_id = self.request(platform, "textDocument/rename", params);
+    }
+
+    pub fn finish_rename_preview(&mut self, editor: &mut Editor, apply: bool) {
+        match self.request_state {
+            RequestState::RenamePreview => (),
+            _ => return,
+        }
+        self.request_state = RequestState::Idle;
+        if !apply {
+            return;
+        }
+
+        let mut reader = io::Cursor::new(&self.request_raw_json);
+        let edit = match self.json.read(&mut reader) {
+            Ok(edit) => edit,
+            Err(_) => return,
+        };
+        if let Ok(edit) = WorkspaceEdit::from_json(edit, &self.json) {
+            edit.apply(
+                editor,
+                &mut self.temp_edits,
+                &self.root,
+                self.server_capabilities.position_encoding,
+                &self.json,
+            );
+        }
     }
 
     pub fn code_action(
@@ -929,15 +1458,17 @@ impl Client {
 
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let encoding = self.server_capabilities.position_encoding;
 
         let mut diagnostics = JsonArray::default();
         for diagnostic in self.diagnostics.buffer_diagnostics(buffer_handle) {
             if diagnostic.range.from <= range.from && range.from < diagnostic.range.to
                 || diagnostic.range.from <= range.to && range.to < diagnostic.range.to
             {
-                let diagnostic = diagnostic.as_document_diagnostic(&mut self.json);
+                let diagnostic =
+                    diagnostic.as_document_diagnostic(buffer.content(), encoding, &mut self.json);
                 diagnostics.push(diagnostic.to_json_value(&mut self.json), &mut self.json);
             }
         }
@@ -949,16 +1480,22 @@ impl Client {
         params.set("textDocument".into(), text_document.into(), &mut self.json);
         params.set(
             "range".into(),
-            DocumentRange::from(range).to_json_value(&mut self.json),
+            DocumentRange::from_buffer_range(range, buffer.content(), encoding)
+                .to_json_value(&mut self.json),
             &mut self.json,
         );
         params.set("context".into(), context.into(), &mut self.json);
 
         self.request_state = RequestState::CodeAction { client_handle };
-        self.request(platform, "textDocument/codeAction", params);
+        self.request_state_id = self.request(platform, "textDocument/codeAction", params);
     }
 
-    pub fn finish_code_action(&mut self, editor: &mut Editor, index: usize) {
+    pub fn finish_code_action(
+        &mut self,
+        editor: &mut Editor,
+        platform: &mut Platform,
+        index: usize,
+    ) {
         match self.request_state {
             RequestState::FinishCodeAction => (),
             _ => return,
@@ -973,14 +1510,31 @@ impl Client {
             Ok(actions) => actions,
             Err(_) => return,
         };
-        if let Some(edit) = code_actions
+        let action = match code_actions
             .elements(&self.json)
             .filter_map(|a| DocumentCodeAction::from_json(a, &self.json).ok())
             .filter(|a| !a.disabled)
-            .map(|a| a.edit)
             .nth(index)
         {
-            edit.apply(editor, &mut self.temp_edits, &self.root, &self.json);
+            Some(action) => action,
+            None => return,
+        };
+
+        action.edit.apply(
+            editor,
+            &mut self.temp_edits,
+            &self.root,
+            self.server_capabilities.position_encoding,
+            &self.json,
+        );
+
+        if let Some(command) = action.command {
+            let mut params = JsonObject::default();
+            params.set("command".into(), command.command.into(), &mut self.json);
+            if !matches!(command.arguments, JsonValue::Null) {
+                params.set("arguments".into(), command.arguments, &mut self.json);
+            }
+            self.request(platform, "workspace/executeCommand", params);
         }
     }
 
@@ -1008,7 +1562,7 @@ impl Client {
             client_handle,
             buffer_view_handle,
         };
-        self.request(platform, "textDocument/documentSymbol", params);
+        self.request_state_id = self.request(platform, "textDocument/documentSymbol", params);
     }
 
     pub fn finish_document_symbols(
@@ -1036,20 +1590,20 @@ impl Client {
             Err(_) => return,
         };
 
-        fn find_symbol_position(
+        fn find_symbol_range(
             symbols: JsonArray,
             json: &Json,
             mut index: usize,
-        ) -> Result<DocumentPosition, usize> {
+        ) -> Result<DocumentRange, usize> {
             for symbol in symbols
                 .elements(json)
                 .filter_map(|s| DocumentSymbolInformation::from_json(s, json).ok())
             {
                 if index == 0 {
-                    return Ok(symbol.range.start);
+                    return Ok(symbol.range);
                 } else {
-                    match find_symbol_position(symbol.children.clone(), json, index - 1) {
-                        Ok(position) => return Ok(position),
+                    match find_symbol_range(symbol.children.clone(), json, index - 1) {
+                        Ok(range) => return Ok(range),
                         Err(i) => index = i,
                     }
                 }
@@ -1057,10 +1611,14 @@ impl Client {
             Err(index)
         }
 
-        if let Ok(position) = find_symbol_position(symbols, &self.json, index) {
+        if let Ok(range) = find_symbol_range(symbols, &self.json, index) {
+            let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+            let content = editor.buffers.get(buffer_handle).content();
+            let range =
+                range.to_buffer_range(Some(content), self.server_capabilities.position_encoding);
+
             NavigationHistory::save_snapshot(clients.get_mut(client_handle), &editor.buffer_views);
 
-            let position = position.into();
             let mut cursors = editor
                 .buffer_views
                 .get_mut(buffer_view_handle)
@@ -1068,8 +1626,8 @@ impl Client {
                 .mut_guard();
             cursors.clear();
             cursors.add(Cursor {
-                anchor: position,
-                position,
+                anchor: range.to,
+                position: range.from,
             });
         }
     }
@@ -1092,7 +1650,7 @@ impl Client {
         params.set("query".into(), query.into(), &mut self.json);
 
         self.request_state = RequestState::WorkspaceSymbols { client_handle };
-        self.request(platform, "workspace/symbol", params);
+        self.request_state_id = self.request(platform, "workspace/symbol", params);
     }
 
     pub fn finish_workspace_symbols(
@@ -1135,7 +1693,11 @@ impl Client {
                         &mut editor.events,
                     );
 
-                    let position = symbol.range.start.into();
+                    let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+                    let position = symbol.range.start.to_buffer_position(
+                        Some(editor.buffers.get(buffer_handle).content()),
+                        self.server_capabilities.position_encoding,
+                    );
                     let mut cursors = editor
                         .buffer_views
                         .get_mut(buffer_view_handle)
@@ -1161,7 +1723,17 @@ impl Client {
         platform: &mut Platform,
         buffer_handle: BufferHandle,
     ) {
-        if !self.server_capabilities.document_formatting_provider.0 || !self.request_state.is_idle()
+        self.request_formatting(editor, platform, buffer_handle, false);
+    }
+
+    fn request_formatting(
+        &mut self,
+        editor: &Editor,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        save_after: bool,
+    ) {
+        if !self.server_capabilities.document_formatting_provider.0 || !self.request_state.is_idle()
         {
             return;
         }
@@ -1170,26 +1742,112 @@ impl Client {
 
         let buffer_path = &editor.buffers.get(buffer_handle).path;
         let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let mut options = JsonObject::default();
-        options.set(
-            "tabSize".into(),
-            JsonValue::Integer(editor.config.tab_size.get() as _),
-            &mut self.json,
+        let options = helper::formatting_options(editor, buffer_handle, &mut self.json);
+
+        let mut params = JsonObject::default();
+        params.set("textDocument".into(), text_document.into(), &mut self.json);
+        params.set("options".into(), options.into(), &mut self.json);
+
+        self.request_state = RequestState::Formatting {
+            buffer_handle,
+            save_after,
+        };
+        self.request_state_id = self.request(platform, "textDocument/formatting", params);
+    }
+
+    pub fn range_formatting(
+        &mut self,
+        editor: &Editor,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        range: BufferRange,
+    ) {
+        if !self
+            .server_capabilities
+            .document_range_formatting_provider
+            .0
+            || !self.request_state.is_idle()
+        {
+            return;
+        }
+
+        helper::send_pending_did_change(self, editor, platform);
+
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let range = DocumentRange::from_buffer_range(
+            range,
+            buffer.content(),
+            self.server_capabilities.position_encoding,
         );
-        options.set(
-            "insertSpaces".into(),
-            (!editor.config.indent_with_tabs).into(),
+        let options = helper::formatting_options(editor, buffer_handle, &mut self.json);
+
+        let mut params = JsonObject::default();
+        params.set("textDocument".into(), text_document.into(), &mut self.json);
+        params.set(
+            "range".into(),
+            range.to_json_value(&mut self.json),
             &mut self.json,
         );
-        options.set("trimTrailingWhitespace".into(), true.into(), &mut self.json);
-        options.set("trimFinalNewlines".into(), true.into(), &mut self.json);
+        params.set("options".into(), options.into(), &mut self.json);
+
+        self.request_state = RequestState::Formatting {
+            buffer_handle,
+            save_after: false,
+        };
+        self.request_state_id = self.request(platform, "textDocument/rangeFormatting", params);
+    }
+
+    pub fn refresh_inlay_hints(
+        &mut self,
+        editor: &Editor,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        line_range: Range<BufferPositionIndex>,
+    ) {
+        if !self.server_capabilities.inlay_hint_provider.0
+            || self.inlay_hints.pending_request.is_some()
+            || !self.handles_buffer(editor, buffer_handle)
+            || !self.inlay_hints.needs_refresh(buffer_handle, &line_range)
+        {
+            return;
+        }
+
+        helper::send_pending_did_change(self, editor, platform);
+
+        let buffer = editor.buffers.get(buffer_handle);
+        let line_count = buffer.content().line_count() as BufferPositionIndex;
+        let margin = (line_range.end - line_range.start) / 2;
+        let line_range =
+            line_range.start.saturating_sub(margin)..line_count.min(line_range.end + margin);
+
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let range_end = if line_range.end < line_count {
+            BufferPosition::line_col(line_range.end, 0)
+        } else {
+            buffer.content().end()
+        };
+        let range = DocumentRange::from_buffer_range(
+            BufferRange::between(BufferPosition::line_col(line_range.start, 0), range_end),
+            buffer.content(),
+            self.server_capabilities.position_encoding,
+        );
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
-        params.set("options".into(), options.into(), &mut self.json);
+        params.set(
+            "range".into(),
+            range.to_json_value(&mut self.json),
+            &mut self.json,
+        );
 
-        self.request_state = RequestState::Formatting { buffer_handle };
-        self.request(platform, "textDocument/formatting", params);
+        let hints = self.inlay_hints.hints_for_buffer(buffer_handle);
+        hints.line_range = line_range;
+        hints.dirty = false;
+
+        if let Some(id) = self.request(platform, "textDocument/inlayHint", params) {
+            self.inlay_hints.pending_request = Some((buffer_handle, id));
+        }
     }
 
     pub fn completion(
@@ -1206,9 +1864,13 @@ impl Client {
 
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(
+            buffer_position,
+            buffer.content(),
+            self.server_capabilities.position_encoding,
+        );
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -1222,20 +1884,98 @@ impl Client {
             client_handle,
             buffer_handle,
         };
+        self.completion_position = position;
+        self.completion_trailing_replaced = false;
+
+        self.request_state_id = self.request(platform, "textDocument/completion", params);
+    }
+
+    // pushes the completion item text, shows its info and returns the exact range it should
+    // replace around `cursor_position` (when the server sent one)
+    pub fn completion_item(
+        &mut self,
+        editor: &mut Editor,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        cursor_position: BufferPosition,
+        index: usize,
+        text: &mut String,
+    ) -> Option<BufferRange> {
+        let item_range = self.completion_item_ranges.get(index)?.clone();
+        let mut reader = io::Cursor::new(&self.completion_raw_json[item_range]);
+        let item_value = self.json.read(&mut reader).ok()?;
+        let item = DocumentCompletionItem::from_json(item_value.clone(), &self.json).ok()?;
+
+        let item_text = item.text.as_str(&self.json);
+        if item.is_snippet {
+            strip_snippet_placeholders(item_text, text);
+        } else {
+            text.push_str(item_text);
+        }
+
+        let range = item.edit_range.and_then(|range| {
+            self.completion_edit_range(editor, buffer_handle, cursor_position, range)
+        });
+
+        helper::write_completion_item_info(editor, &item, &self.json);
+
+        if self
+            .server_capabilities
+            .completion_provider
+            .resolve_provider
+            && self.request_state.is_idle()
+        {
+            if let JsonValue::Object(item) = item_value {
+                self.request_state = RequestState::CompletionResolve;
+                self.request_state_id = self.request(platform, "completionItem/resolve", item);
+            }
+        }
+
+        range
+    }
+
+    fn completion_edit_range(
+        &mut self,
+        editor: &Editor,
+        buffer_handle: BufferHandle,
+        cursor_position: BufferPosition,
+        range: DocumentRange,
+    ) -> Option<BufferRange> {
+        let requested = self.completion_position;
+        if range.start.line != requested.line
+            || range.end.line != requested.line
+            || cursor_position.line_index != requested.line
+        {
+            return None;
+        }
+
+        let encoding = self.server_capabilities.position_encoding;
+        let content = editor.buffers.get(buffer_handle).content();
+        let line = content.line_at(cursor_position.line_index as _).as_str();
+        let cursor_column = cursor_position.column_byte_index as usize;
+
+        // text before the completion start is untouched since the request, but the text
+        // after the request position is only replaced the first time an item is applied
+        let from = encoding
+            .decode_column(line, range.start.character)
+            .min(cursor_column);
+        let mut to = cursor_column;
+        if !std::mem::replace(&mut self.completion_trailing_replaced, true) {
+            let trailing = range.end.character.saturating_sub(requested.character);
+            to += encoding.decode_column(&line[cursor_column..], trailing);
+        }
 
-        self.request(platform, "textDocument/completion", params);
+        Some(BufferRange::between(
+            BufferPosition::line_col(cursor_position.line_index, from as _),
+            BufferPosition::line_col(cursor_position.line_index, to as _),
+        ))
     }
 
-    fn write_to_log_file<F>(&mut self, writer: F)
+    fn write_to_log<F>(&mut self, body: Option<&JsonValue>, header: F)
     where
-        F: FnOnce(&mut io::BufWriter<File>, &mut Json),
+        F: FnOnce(&mut Vec<u8>, &Json),
     {
-        if let Some(ref mut buf) = self.log_file {
-            use io::Write;
-            writer(buf, &mut self.json);
-            let _ = buf.write_all(b"\n\n");
-            let _ = buf.flush();
-        }
+        self.log.write(&self.json, body, header);
     }
 
     fn on_request(
@@ -1244,16 +1984,11 @@ impl Client {
         clients: &mut client::ClientManager,
         request: ServerRequest,
     ) -> Result<JsonValue, ProtocolError> {
-        self.write_to_log_file(|buf, json| {
+        self.write_to_log(Some(&request.params), |buf, json| {
             use io::Write;
             let _ = write!(buf, "receive request\nid: ");
             let _ = json.write(buf, &request.id);
-            let _ = write!(
-                buf,
-                "\nmethod: '{}'\nparams:\n",
-                request.method.as_str(json)
-            );
-            let _ = json.write(buf, &request.params);
+            let _ = write!(buf, "\nmethod: '{}'\nparams:", request.method.as_str(json));
         });
 
         match request.method.as_str(&self.json) {
@@ -1321,6 +2056,39 @@ impl Client {
                                 self.document_selectors.push(glob);
                             }
                         }
+                        "workspace/didChangeWatchedFiles" => {
+                            let root = self.root.to_str().unwrap_or("");
+                            for watcher in registration
+                                .register_options
+                                .get("watchers", &self.json)
+                                .elements(&self.json)
+                            {
+                                let pattern = match watcher.clone().get("globPattern", &self.json) {
+                                    JsonValue::String(pattern) => pattern,
+                                    JsonValue::Object(pattern) => {
+                                        match pattern.get("pattern", &self.json) {
+                                            JsonValue::String(pattern) => pattern,
+                                            _ => continue,
+                                        }
+                                    }
+                                    _ => continue,
+                                };
+                                let pattern = pattern.as_str(&self.json);
+                                let pattern = match pattern.strip_prefix(root) {
+                                    Some(pattern) => pattern.trim_start_matches('/'),
+                                    None => pattern,
+                                };
+                                let kind = match watcher.get("kind", &self.json) {
+                                    JsonValue::Integer(kind) => kind as _,
+                                    _ => WATCH_KIND_CREATE | WATCH_KIND_CHANGE | WATCH_KIND_DELETE,
+                                };
+
+                                let mut glob = Glob::default();
+                                glob.compile(pattern)?;
+                                self.watched_files.watchers.push(FileWatcher { glob, kind });
+                            }
+                            self.watched_files.on_watchers_changed();
+                        }
                         _ => (),
                     }
                 }
@@ -1412,11 +2180,15 @@ impl Client {
                             }
                             if let Some(range) = params.selection {
                                 let buffer_view = editor.buffer_views.get_mut(buffer_view_handle);
+                                let range = range.to_buffer_range(
+                                    Some(editor.buffers.get(buffer_view.buffer_handle).content()),
+                                    self.server_capabilities.position_encoding,
+                                );
                                 let mut cursors = buffer_view.cursors.mut_guard();
                                 cursors.clear();
                                 cursors.add(Cursor {
-                                    anchor: range.start.into(),
-                                    position: range.end.into(),
+                                    anchor: range.from,
+                                    position: range.to,
                                 });
                             }
                             true
@@ -1437,6 +2209,56 @@ impl Client {
                 result.set("success".into(), success.into(), &mut self.json);
                 Ok(result.into())
             }
+            "workspace/configuration" => {
+                let settings = if self.settings.is_empty() {
+                    JsonValue::Null
+                } else {
+                    let mut reader = io::Cursor::new(&self.settings);
+                    self.json.read(&mut reader).unwrap_or(JsonValue::Null)
+                };
+
+                let mut result = JsonArray::default();
+                let items = match request.params.get("items", &self.json) {
+                    JsonValue::Array(items) => items,
+                    _ => JsonArray::default(),
+                };
+                let values: Vec<_> = items
+                    .elements(&self.json)
+                    .map(|item| {
+                        let mut value = settings.clone();
+                        if let JsonValue::String(section) = item.get("section", &self.json) {
+                            for key in section.as_str(&self.json).split('.') {
+                                value = value.get(key, &self.json);
+                            }
+                        }
+                        value
+                    })
+                    .collect();
+                for value in values {
+                    result.push(value, &mut self.json);
+                }
+                Ok(result.into())
+            }
+            "workspace/workspaceFolders" => Ok(helper::workspace_folders(self).into()),
+            "workspace/inlayHint/refresh" => {
+                self.inlay_hints.set_dirty(None);
+                Ok(JsonValue::Null)
+            }
+            "workspace/applyEdit" => {
+                let edit = request.params.get("edit", &self.json);
+                let edit = WorkspaceEdit::from_json(edit, &self.json)?;
+                edit.apply(
+                    editor,
+                    &mut self.temp_edits,
+                    &self.root,
+                    self.server_capabilities.position_encoding,
+                    &self.json,
+                );
+
+                let mut result = JsonObject::default();
+                result.set("applied".into(), true.into(), &mut self.json);
+                Ok(result.into())
+            }
             _ => Err(ProtocolError::MethodNotFound),
         }
     }
@@ -1446,14 +2268,13 @@ impl Client {
         editor: &mut Editor,
         notification: ServerNotification,
     ) -> Result<(), ProtocolError> {
-        self.write_to_log_file(|buf, json| {
+        self.write_to_log(Some(&notification.params), |buf, json| {
             use io::Write;
             let _ = write!(
                 buf,
-                "receive notification\nmethod: '{}'\nparams:\n",
+                "receive notification\nmethod: '{}'\nparams:",
                 notification.method.as_str(json)
             );
-            let _ = json.write(buf, &notification.params);
         });
 
         match notification.method.as_str(&self.json) {
@@ -1515,9 +2336,13 @@ impl Client {
                 let diagnostics = self
                     .diagnostics
                     .diagnostics_at_path(editor, &self.root, path);
+                let content = diagnostics
+                    .buffer_handle
+                    .map(|handle| editor.buffers.get(handle).content());
+                let encoding = self.server_capabilities.position_encoding;
                 for diagnostic in params.diagnostics.elements(&self.json) {
                     let diagnostic = DocumentDiagnostic::from_json(diagnostic, &self.json)?;
-                    diagnostics.add(diagnostic, &self.json);
+                    diagnostics.add(diagnostic, content, encoding, &self.json);
                 }
                 diagnostics.sort();
                 self.diagnostics.clear_empty();
@@ -1538,8 +2363,15 @@ impl Client {
             Some(method) => method,
             None => return Ok(()),
         };
+        if method == "workspace/willRenameFiles" {
+            editor.lsp.on_will_rename_response(self.handle);
+        }
 
-        self.write_to_log_file(|buf, json| {
+        let body = match &response.result {
+            Ok(result) => result.clone(),
+            Err(error) => error.data.clone(),
+        };
+        self.write_to_log(Some(&body), |buf, json| {
             use io::Write;
             let _ = write!(
                 buf,
@@ -1547,22 +2379,52 @@ impl Client {
                 response.id.0, method
             );
             match &response.result {
-                Ok(result) => {
-                    let _ = buf.write_all(b"result:\n");
-                    let _ = json.write(buf, result);
+                Ok(_) => {
+                    let _ = buf.write_all(b"result:");
                 }
                 Err(error) => {
                     let _ = write!(
                         buf,
-                        "error_code: {}\nerror_message: '{}'\nerror_data:\n",
+                        "error_code: {}\nerror_message: '{}'\nerror_data:",
                         error.code,
                         error.message.as_str(json)
                     );
-                    let _ = json.write(buf, &error.data);
                 }
             }
         });
 
+        if method == "textDocument/inlayHint" {
+            let buffer_handle = match self.inlay_hints.pending_request.take() {
+                Some((handle, id)) if id == response.id => handle,
+                _ => return Ok(()),
+            };
+            let hints = match response.result {
+                Ok(JsonValue::Array(hints)) => hints,
+                _ => return Ok(()),
+            };
+
+            let content = editor.buffers.get(buffer_handle).content();
+            let encoding = self.server_capabilities.position_encoding;
+            let buffer_hints = self.inlay_hints.hints_for_buffer(buffer_handle);
+            buffer_hints.len = 0;
+            for hint in hints.elements(&self.json) {
+                let hint = DocumentInlayHint::from_json(hint, &self.json)?;
+                if buffer_hints.len == buffer_hints.hints.len() {
+                    buffer_hints.hints.push(InlayHint {
+                        position: BufferPosition::zero(),
+                        label: String::new(),
+                    });
+                }
+                let inlay_hint = &mut buffer_hints.hints[buffer_hints.len];
+                inlay_hint.position = hint.position.to_buffer_position(Some(content), encoding);
+                inlay_hint.label.clear();
+                hint.write_label(&self.json, &mut inlay_hint.label);
+                buffer_hints.len += 1;
+            }
+            buffer_hints.hints[..buffer_hints.len].sort_by_key(|h| h.position);
+            return Ok(());
+        }
+
         let result = match response.result {
             Ok(result) => result,
             Err(error) => {
@@ -1601,15 +2463,33 @@ impl Client {
                         .fmt(format_args!("lsp server '{}' started", server_name)),
                 }
 
+                self.server_name.clear();
+                self.server_name.push_str(server_name);
                 self.initialized = true;
                 self.notify(platform, "initialized", JsonObject::default());
+                helper::send_did_change_configuration(self, platform);
 
                 for buffer in editor.buffers.iter() {
-                    helper::send_did_open(self, editor, platform, buffer.handle());
+                    if self.handles_buffer(editor, buffer.handle()) {
+                        helper::send_did_open(self, editor, platform, buffer.handle());
+                    }
                 }
 
                 Ok(())
             }
+            "workspace/willRenameFiles" => {
+                if let JsonValue::Object(_) = result {
+                    let edit = WorkspaceEdit::from_json(result, &self.json)?;
+                    edit.apply(
+                        editor,
+                        &mut self.temp_edits,
+                        &self.root,
+                        self.server_capabilities.position_encoding,
+                        &self.json,
+                    );
+                }
+                Ok(())
+            }
             "textDocument/hover" => {
                 let contents = result.get("contents", &self.json);
                 let info = helper::extract_markup_content(contents, &self.json);
@@ -1703,6 +2583,13 @@ impl Client {
                 };
                 self.goto_definition(editor, platform, clients, client_handle, result)
             }
+            "textDocument/typeDefinition" => {
+                let client_handle = match self.request_state {
+                    RequestState::TypeDefinition { client_handle } => client_handle,
+                    _ => return Ok(()),
+                };
+                self.goto_definition(editor, platform, clients, client_handle, result)
+            }
             "textDocument/implementation" => {
                 let client_handle = match self.request_state {
                     RequestState::Implementation { client_handle } => client_handle,
@@ -1735,9 +2622,12 @@ impl Client {
                         .find_with_path(&editor.current_directory, path)
                         .map(|h| editor.buffers.get(h))
                     {
-                        buffer
-                            .content()
-                            .append_range_text_to_string(location.range.into(), &mut buffer_name);
+                        let content = buffer.content();
+                        let range = location.range.to_buffer_range(
+                            Some(content),
+                            self.server_capabilities.position_encoding,
+                        );
+                        content.append_range_text_to_string(range, &mut buffer_name);
                         break;
                     }
                 }
@@ -1766,8 +2656,8 @@ impl Client {
                 let mut count = 0;
                 let mut context_buffer = BufferContent::new();
 
-                let buffer_view = editor.buffer_views.get(buffer_view_handle);
-                let buffer = editor.buffers.get_mut(buffer_view.buffer_handle);
+                let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+                let buffer = editor.buffers.get_mut(buffer_handle);
 
                 buffer.capabilities = BufferCapabilities::log();
                 buffer.capabilities.auto_close = auto_close_buffer;
@@ -1775,13 +2665,22 @@ impl Client {
                 let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
                 buffer.delete_range(&mut editor.word_database, range, &mut editor.events);
 
+                let mut sorted_locations: Vec<_> = locations
+                    .elements(&self.json)
+                    .filter_map(|l| DocumentLocation::from_json(l, &self.json).ok())
+                    .collect();
+                sorted_locations.sort_by(|a, b| {
+                    let a_uri = a.uri.as_str(&self.json);
+                    let b_uri = b.uri.as_str(&self.json);
+                    a_uri
+                        .cmp(b_uri)
+                        .then(a.range.start.line.cmp(&b.range.start.line))
+                        .then(a.range.start.character.cmp(&b.range.start.character))
+                });
+
                 let mut text = editor.string_pool.acquire();
                 let mut last_path = "";
-                for location in locations.elements(&self.json) {
-                    let location = match DocumentLocation::from_json(location, &self.json) {
-                        Ok(location) => location,
-                        Err(_) => continue,
-                    };
+                for location in sorted_locations {
                     let path = match Uri::parse(&self.root, location.uri.as_str(&self.json)) {
                         Ok(Uri::Path(path)) => path,
                         Err(_) => continue,
@@ -1791,32 +2690,59 @@ impl Client {
                         None => continue,
                     };
 
+                    let content = match editor
+                        .buffers
+                        .find_with_path(&editor.current_directory, Path::new(path))
+                    {
+                        Some(handle) => Some(editor.buffers.get(handle).content()),
+                        None if context_len > 0 => {
+                            if last_path != path {
+                                context_buffer.clear();
+                                if let Ok(file) = File::open(path) {
+                                    let mut reader = io::BufReader::new(file);
+                                    let _ = context_buffer.read(&mut reader);
+                                }
+                            }
+                            Some(&context_buffer)
+                        }
+                        None => None,
+                    };
+                    let column = match content {
+                        Some(content) => {
+                            let position = location.range.start.to_buffer_position(
+                                Some(content),
+                                self.server_capabilities.position_encoding,
+                            );
+                            content.char_column(position)
+                        }
+                        None => location.range.start.character as _,
+                    };
+
+                    if last_path != path {
+                        if count > 0 && context_len == 0 {
+                            text.push('\n');
+                        }
+                        text.push_str(path);
+                        text.push('\n');
+                    }
+
                     use fmt::Write;
-                    let position: BufferPosition = location.range.start.into();
                     let _ = writeln!(
                         text,
                         "{}:{},{}",
                         path,
-                        position.line_index + 1,
-                        position.column_byte_index + 1,
+                        location.range.start.line + 1,
+                        column + 1,
                     );
 
-                    if context_len > 0 {
-                        if last_path != path {
-                            context_buffer.clear();
-                            if let Ok(file) = File::open(path) {
-                                let mut reader = io::BufReader::new(file);
-                                let _ = context_buffer.read(&mut reader);
-                            }
-                        }
-
+                    if let Some(content) = content.filter(|_| context_len > 0) {
                         let surrounding_len = context_len - 1;
                         let start =
                             (location.range.start.line as usize).saturating_sub(surrounding_len);
                         let end = location.range.end.line as usize + surrounding_len;
                         let len = end - start + 1;
 
-                        for line in context_buffer
+                        for line in content
                             .lines()
                             .skip(start)
                             .take(len)
@@ -1828,6 +2754,7 @@ impl Client {
                         text.push('\n');
                     }
 
+                    let buffer = editor.buffers.get_mut(buffer_handle);
                     let position = buffer.content().end();
                     buffer.insert_text(
                         &mut editor.word_database,
@@ -1848,6 +2775,7 @@ impl Client {
                     let _ = writeln!(text, "{} references found\n", count);
                 }
 
+                let buffer = editor.buffers.get_mut(buffer_handle);
                 buffer.insert_text(
                     &mut editor.word_database,
                     BufferPosition::zero(),
@@ -1915,7 +2843,10 @@ impl Client {
 
                 let buffer = editor.buffers.get(buffer_handle);
 
-                let mut range = range.into();
+                let mut range = range.to_buffer_range(
+                    Some(buffer.content()),
+                    self.server_capabilities.position_encoding,
+                );
                 if let Some(true) = default_behaviour {
                     let word = buffer.content().word_at(buffer_position);
                     range = BufferRange::between(word.position, word.end_position());
@@ -1939,14 +2870,84 @@ impl Client {
                 editor.string_pool.release(input);
 
                 self.request_state = RequestState::FinishRename {
+                    client_handle,
                     buffer_handle,
                     buffer_position,
                 };
                 Ok(())
             }
             "textDocument/rename" => {
-                let edit = WorkspaceEdit::from_json(result, &self.json)?;
-                edit.apply(editor, &mut self.temp_edits, &self.root, &self.json);
+                let client_handle = match self.request_state {
+                    RequestState::PendingRename { client_handle } => client_handle,
+                    _ => return Ok(()),
+                };
+                self.request_state = RequestState::Idle;
+                let edit = WorkspaceEdit::from_json(result.clone(), &self.json)?;
+                if !editor.config.lsp_rename_preview {
+                    edit.apply(
+                        editor,
+                        &mut self.temp_edits,
+                        &self.root,
+                        self.server_capabilities.position_encoding,
+                        &self.json,
+                    );
+                    return Ok(());
+                }
+
+                let buffer_view_handle = match editor.buffer_view_handle_from_path(
+                    client_handle,
+                    Path::new("lsp.rename"),
+                    BufferCapabilities::log(),
+                ) {
+                    Ok(handle) => handle,
+                    Err(error) => {
+                        editor
+                            .status_bar
+                            .write(MessageKind::Error)
+                            .fmt(format_args!("{}", error));
+                        return Ok(());
+                    }
+                };
+
+                let mut text = editor.string_pool.acquire();
+                edit.write_preview(
+                    editor,
+                    &self.root,
+                    self.server_capabilities.position_encoding,
+                    &self.json,
+                    &mut text,
+                );
+
+                let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+                let buffer = editor.buffers.get_mut(buffer_handle);
+                let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+                buffer.delete_range(&mut editor.word_database, range, &mut editor.events);
+                buffer.insert_text(
+                    &mut editor.word_database,
+                    BufferPosition::zero(),
+                    &text,
+                    &mut editor.events,
+                );
+                editor.string_pool.release(text);
+
+                let client = clients.get_mut(client_handle);
+                client.set_buffer_view_handle(
+                    Some(buffer_view_handle),
+                    &editor.buffer_views,
+                    &mut editor.events,
+                );
+
+                self.request_state = RequestState::RenamePreview;
+                self.request_raw_json.clear();
+                let _ = self.json.write(&mut self.request_raw_json, &result);
+
+                let mut ctx = ModeContext {
+                    editor,
+                    platform,
+                    clients,
+                    client_handle,
+                };
+                read_line::lsp_rename_preview::enter_mode(&mut ctx, self.handle());
                 Ok(())
             }
             "textDocument/codeAction" => {
@@ -1961,6 +2962,7 @@ impl Client {
                 };
 
                 editor.picker.clear();
+                let mut count = 0;
                 for action in actions
                     .clone()
                     .elements(&self.json)
@@ -1970,6 +2972,14 @@ impl Client {
                     editor
                         .picker
                         .add_custom_entry(action.title.as_str(&self.json));
+                    count += 1;
+                }
+                if count == 0 {
+                    editor
+                        .status_bar
+                        .write(MessageKind::Info)
+                        .str("no code actions available");
+                    return Ok(());
                 }
 
                 let mut ctx = ModeContext {
@@ -2011,17 +3021,19 @@ impl Client {
                             unsafe { std::str::from_utf8_unchecked(&indent_buf[..indent_len]) };
 
                         let name = symbol.name.as_str(json);
+                        let kind = SymbolKind(symbol.kind);
                         match symbol.container_name {
                             Some(container_name) => {
                                 let container_name = container_name.as_str(json);
                                 picker.add_custom_entry_fmt(format_args!(
-                                    "{}{} ({})",
-                                    indent, name, container_name,
+                                    "{}{} ({}) [{}]",
+                                    indent, name, container_name, kind,
                                 ));
                             }
-                            None => {
-                                picker.add_custom_entry_fmt(format_args!("{}{}", indent, name,))
-                            }
+                            None => picker.add_custom_entry_fmt(format_args!(
+                                "{}{} [{}]",
+                                indent, name, kind
+                            )),
                         }
 
                         add_symbols(picker, depth + 1, symbol.children.clone(), json);
@@ -2087,9 +3099,12 @@ impl Client {
                 let _ = self.json.write(&mut self.request_raw_json, &symbols.into());
                 Ok(())
             }
-            "textDocument/formatting" => {
-                let buffer_handle = match self.request_state {
-                    RequestState::Formatting { buffer_handle } => buffer_handle,
+            "textDocument/formatting" | "textDocument/rangeFormatting" => {
+                let (buffer_handle, save_after) = match self.request_state {
+                    RequestState::Formatting {
+                        buffer_handle,
+                        save_after,
+                    } => (buffer_handle, save_after),
                     _ => return Ok(()),
                 };
                 self.request_state = RequestState::Idle;
@@ -2097,13 +3112,55 @@ impl Client {
                     JsonValue::Array(edits) => edits,
                     _ => return Ok(()),
                 };
+                if edits.clone().elements(&self.json).next().is_none() {
+                    return Ok(());
+                }
+
+                let mut cursors = Vec::new();
+                let mut view_cursors = Vec::new();
+                for handle in clients
+                    .iter()
+                    .filter_map(client::Client::buffer_view_handle)
+                {
+                    let buffer_view = editor.buffer_views.get(handle);
+                    if buffer_view.buffer_handle == buffer_handle {
+                        let from = cursors.len();
+                        cursors.extend_from_slice(&buffer_view.cursors[..]);
+                        view_cursors.push((handle, from..cursors.len()));
+                    }
+                }
+
                 TextEdit::apply_edits(
                     editor,
                     buffer_handle,
                     &mut self.temp_edits,
                     edits,
+                    self.server_capabilities.position_encoding,
                     &self.json,
                 );
+
+                let content = editor.buffers.get(buffer_handle).content();
+                for cursor in &mut cursors {
+                    cursor.anchor = content.saturate_position(cursor.anchor);
+                    cursor.position = content.saturate_position(cursor.position);
+                }
+                for (handle, range) in view_cursors {
+                    editor.events.enqueue_fix_cursors(handle, &cursors[range]);
+                }
+
+                if save_after {
+                    match editor
+                        .buffers
+                        .get_mut(buffer_handle)
+                        .write_to_file(None, &mut editor.events)
+                    {
+                        Ok(()) => self.format_on_save_skip = Some(buffer_handle),
+                        Err(error) => editor
+                            .status_bar
+                            .write(MessageKind::Error)
+                            .fmt(format_args!("{}", error)),
+                    }
+                }
                 Ok(())
             }
             "textDocument/completion" => {
@@ -2139,13 +3196,21 @@ impl Client {
                     _ => return Ok(()),
                 };
 
+                // items are stored one after the other so accepting one only reads it back
                 editor.picker.clear();
+                self.completion_raw_json.clear();
+                self.completion_item_ranges.clear();
                 for completion in completions.elements(&self.json) {
-                    if let Ok(completion) =
-                        DocumentCompletionItem::from_json(completion, &self.json)
+                    if let Ok(item) =
+                        DocumentCompletionItem::from_json(completion.clone(), &self.json)
                     {
-                        let text = completion.text.as_str(&self.json);
-                        editor.picker.add_custom_entry(text);
+                        let label = item.label.as_str(&self.json);
+                        editor.picker.add_custom_entry(label);
+
+                        let start = self.completion_raw_json.len();
+                        let _ = self.json.write(&mut self.completion_raw_json, &completion);
+                        self.completion_item_ranges
+                            .push(start..self.completion_raw_json.len());
                     }
                 }
 
@@ -2159,6 +3224,21 @@ impl Client {
                 editor.picker.filter(WordIndicesIter::empty(), filter);
                 Ok(())
             }
+            "completionItem/resolve" => {
+                match self.request_state {
+                    RequestState::CompletionResolve => (),
+                    _ => return Ok(()),
+                }
+                self.request_state = RequestState::Idle;
+
+                if editor.mode.kind() != ModeKind::Insert {
+                    return Ok(());
+                }
+
+                let item = DocumentCompletionItem::from_json(result, &self.json)?;
+                helper::write_completion_item_info(editor, &item, &self.json);
+                Ok(())
+            }
             _ => Ok(()),
         }
     }
@@ -2167,15 +3247,25 @@ impl Client {
         if !self.initialized {
             return;
         }
+        helper::scan_watched_files(self, editor, platform);
 
         let mut events = EditorEventIter::new();
         while let Some(event) = events.next(&editor.events) {
             match *event {
                 EditorEvent::Idle => {
                     helper::send_pending_did_change(self, editor, platform);
+                    if self.request_state.is_idle() {
+                        if let Some(handle) = self.format_on_save_pending.take() {
+                            if !editor.buffers.get(handle).needs_save() {
+                                self.request_formatting(editor, platform, handle, true);
+                            }
+                        }
+                    }
                 }
                 EditorEvent::BufferRead { handle } => {
-                    let handle = handle;
+                    if !self.handles_buffer(editor, handle) {
+                        continue;
+                    }
                     self.versioned_buffers.dispose(handle);
                     self.diagnostics.on_load_buffer(editor, handle, &self.root);
                     helper::send_did_open(self, editor, platform, handle);
@@ -2186,40 +3276,87 @@ impl Client {
                     text,
                     ..
                 } => {
+                    if !self.handles_buffer(editor, handle) {
+                        continue;
+                    }
                     let text = text.as_str(&editor.events);
                     let range = BufferRange::between(range.from, range.from);
                     self.versioned_buffers.add_edit(handle, range, text);
+                    self.inlay_hints.set_dirty(Some(handle));
                 }
                 EditorEvent::BufferDeleteText { handle, range, .. } => {
+                    if !self.handles_buffer(editor, handle) {
+                        continue;
+                    }
                     self.versioned_buffers.add_edit(handle, range, "");
+                    self.inlay_hints.set_dirty(Some(handle));
                 }
                 EditorEvent::BufferWrite { handle, .. } => {
+                    if !self.handles_buffer(editor, handle) {
+                        continue;
+                    }
                     self.diagnostics.on_save_buffer(editor, handle, &self.root);
                     helper::send_pending_did_change(self, editor, platform);
                     helper::send_did_save(self, editor, platform, handle);
+
+                    if self.format_on_save_skip == Some(handle) {
+                        self.format_on_save_skip = None;
+                    } else if let Some(path) = editor.buffers.get(handle).path.to_str() {
+                        if editor.lsp.formats_on_save(path) {
+                            // formatting is retried on idle if another request is in flight
+                            if self.request_state.is_idle() {
+                                self.request_formatting(editor, platform, handle, true);
+                            } else {
+                                self.format_on_save_pending = Some(handle);
+                            }
+                        }
+                    }
                 }
                 EditorEvent::BufferClose { handle } => {
+                    if !self.handles_buffer(editor, handle) {
+                        continue;
+                    }
                     self.versioned_buffers.dispose(handle);
                     self.diagnostics.on_close_buffer(handle);
+                    self.inlay_hints.on_close_buffer(handle);
+                    if self.format_on_save_pending == Some(handle) {
+                        self.format_on_save_pending = None;
+                    }
+                    if let Some((buffer_handle, id)) = self.inlay_hints.pending_request {
+                        if buffer_handle == handle {
+                            self.inlay_hints.pending_request = None;
+                            self.cancel_request(platform, id);
+                        }
+                    }
+                    if self.request_state.buffer_handle() == Some(handle) {
+                        self.cancel_current_request(platform);
+                    }
                     helper::send_pending_did_change(self, editor, platform);
                     helper::send_did_close(self, editor, platform, handle);
                 }
                 EditorEvent::FixCursors { .. } => (),
                 EditorEvent::BufferViewLostFocus { .. } => (),
+                EditorEvent::BufferProcessExit { .. } => (),
+                EditorEvent::ModeChange { .. } => (),
+                EditorEvent::ClientFocus { .. } => (),
             }
         }
     }
 
-    fn request(&mut self, platform: &mut Platform, method: &'static str, params: JsonObject) {
+    fn request(
+        &mut self,
+        platform: &mut Platform,
+        method: &'static str,
+        params: JsonObject,
+    ) -> Option<RequestId> {
         if !self.initialized {
-            return;
+            return None;
         }
 
         let params = params.into();
-        self.write_to_log_file(|buf, json| {
+        self.write_to_log(Some(&params), |buf, _| {
             use io::Write;
-            let _ = write!(buf, "send request\nmethod: '{}'\nparams:\n", method);
-            let _ = json.write(buf, &params);
+            let _ = write!(buf, "send request\nmethod: '{}'\nparams:", method);
         });
         let id = self
             .protocol
@@ -2227,6 +3364,17 @@ impl Client {
         self.json.clear();
 
         self.pending_requests.add(id, method);
+        Some(id)
+    }
+
+    fn cancel_request(&mut self, platform: &mut Platform, id: RequestId) {
+        if !self.pending_requests.cancel(id) {
+            return;
+        }
+
+        let mut params = JsonObject::default();
+        params.set("id".into(), id.into(), &mut self.json);
+        self.notify(platform, "$/cancelRequest", params);
     }
 
     fn respond(
@@ -2235,23 +3383,25 @@ impl Client {
         request_id: JsonValue,
         result: Result<JsonValue, ResponseError>,
     ) {
-        self.write_to_log_file(|buf, json| {
+        let body = match &result {
+            Ok(result) => result.clone(),
+            Err(error) => error.data.clone(),
+        };
+        self.write_to_log(Some(&body), |buf, json| {
             use io::Write;
             let _ = write!(buf, "send response\nid: ");
             let _ = json.write(buf, &request_id);
             match &result {
-                Ok(result) => {
-                    let _ = write!(buf, "\nresult:\n");
-                    let _ = json.write(buf, result);
+                Ok(_) => {
+                    let _ = write!(buf, "\nresult:");
                 }
                 Err(error) => {
                     let _ = write!(
                         buf,
-                        "\nerror.code: {}\nerror.message: {}\nerror.data:\n",
+                        "\nerror.code: {}\nerror.message: {}\nerror.data:",
                         error.code,
                         error.message.as_str(json)
                     );
-                    let _ = json.write(buf, &error.data);
                 }
             }
         });
@@ -2262,10 +3412,9 @@ impl Client {
 
     fn notify(&mut self, platform: &mut Platform, method: &'static str, params: JsonObject) {
         let params = params.into();
-        self.write_to_log_file(|buf, json| {
+        self.write_to_log(Some(&params), |buf, _| {
             use io::Write;
-            let _ = write!(buf, "send notification\nmethod: '{}'\nparams:\n", method);
-            let _ = json.write(buf, &params);
+            let _ = write!(buf, "send notification\nmethod: '{}'\nparams:", method);
         });
         self.protocol
             .notify(platform, &mut self.json, method, params);
@@ -2289,10 +3438,15 @@ impl Client {
         );
         params.set("clientInfo".into(), client_info.into(), &mut self.json);
 
-        let root = self
-            .json
-            .fmt_string(format_args!("{}", Uri::Path(&self.root)));
+        let root = self.workspace_roots.first().unwrap_or(&self.root);
+        let root = self.json.fmt_string(format_args!("{}", Uri::Path(root)));
         params.set("rootUri".into(), root.into(), &mut self.json);
+        let workspace_folders = helper::workspace_folders(self);
+        params.set(
+            "workspaceFolders".into(),
+            workspace_folders.into(),
+            &mut self.json,
+        );
 
         params.set(
             "capabilities".into(),
@@ -2300,6 +3454,13 @@ impl Client {
             &mut self.json,
         );
 
+        if !self.initialization_options.is_empty() {
+            let mut reader = io::Cursor::new(&self.initialization_options);
+            if let Ok(options) = self.json.read(&mut reader) {
+                params.set("initializationOptions".into(), options, &mut self.json);
+            }
+        }
+
         self.initialized = true;
         self.request(platform, "initialize", params);
         self.initialized = false;
@@ -2362,7 +3523,12 @@ impl Client {
                             &mut editor.events,
                         );
 
-                        let position = location.range.start.into();
+                        let buffer_handle =
+                            editor.buffer_views.get(buffer_view_handle).buffer_handle;
+                        let position = location.range.start.to_buffer_position(
+                            Some(editor.buffers.get(buffer_handle).content()),
+                            self.server_capabilities.position_encoding,
+                        );
                         let mut cursors = editor
                             .buffer_views
                             .get_mut(buffer_view_handle)
@@ -2395,22 +3561,27 @@ impl Client {
                         None => continue,
                     };
 
-                    let position: BufferPosition = location.range.start.into();
+                    let column = helper::location_char_column(
+                        editor,
+                        Path::new(path),
+                        location.range.start,
+                        self.server_capabilities.position_encoding,
+                    );
                     editor.picker.add_custom_entry_fmt(format_args!(
                         "{}:{},{}",
                         path,
-                        position.line_index + 1,
-                        position.column_byte_index + 1
+                        location.range.start.line + 1,
+                        column + 1
                     ));
-
-                    let mut ctx = ModeContext {
-                        editor,
-                        platform,
-                        clients,
-                        client_handle,
-                    };
-                    picker::lsp_definition::enter_mode(&mut ctx, self.handle());
                 }
+
+                let mut ctx = ModeContext {
+                    editor,
+                    platform,
+                    clients,
+                    client_handle,
+                };
+                picker::lsp_definition::enter_mode(&mut ctx, self.handle());
             }
             DefinitionLocation::Invalid => (),
         }
@@ -2427,6 +3598,21 @@ mod helper {
             .str(error.message.as_str(json));
     }
 
+    pub fn workspace_folders(client: &mut Client) -> JsonArray {
+        let mut folders = JsonArray::default();
+        for root in &client.workspace_roots {
+            let uri = client.json.fmt_string(format_args!("{}", Uri::Path(root)));
+            let name = root.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            let name = client.json.create_string(name);
+
+            let mut folder = JsonObject::default();
+            folder.set("uri".into(), uri.into(), &mut client.json);
+            folder.set("name".into(), name.into(), &mut client.json);
+            folders.push(folder.into(), &mut client.json);
+        }
+        folders
+    }
+
     pub fn text_document_with_id(root: &Path, path: &Path, json: &mut Json) -> JsonObject {
         let uri = if path.is_absolute() {
             json.fmt_string(format_args!("{}", Uri::Path(path)))
@@ -2441,6 +3627,69 @@ mod helper {
         id
     }
 
+    pub fn location_char_column(
+        editor: &Editor,
+        path: &Path,
+        position: DocumentPosition,
+        encoding: PositionEncoding,
+    ) -> usize {
+        match editor
+            .buffers
+            .find_with_path(&editor.current_directory, path)
+        {
+            Some(handle) => {
+                let content = editor.buffers.get(handle).content();
+                content.char_column(position.to_buffer_position(Some(content), encoding))
+            }
+            None => position.character as _,
+        }
+    }
+
+    pub fn write_completion_item_info(
+        editor: &mut Editor,
+        item: &DocumentCompletionItem,
+        json: &Json,
+    ) {
+        let kind = completion_item_kind_name(item.kind);
+        let label = item.label.as_str(json);
+        let detail = item.detail.as_str(json);
+        let documentation = extract_markup_content(item.documentation.clone(), json);
+
+        let mut write = editor.status_bar.write(MessageKind::Info);
+        if !kind.is_empty() {
+            write.fmt(format_args!("[{}] ", kind));
+        }
+        write.str(label);
+        if !detail.is_empty() {
+            write.fmt(format_args!(" {}", detail));
+        }
+        if !documentation.is_empty() {
+            write.fmt(format_args!("\n{}", documentation));
+        }
+    }
+
+    pub fn formatting_options(
+        editor: &Editor,
+        buffer_handle: BufferHandle,
+        json: &mut Json,
+    ) -> JsonObject {
+        let buffer = editor.buffers.get(buffer_handle);
+        let mut options = JsonObject::default();
+        options.set(
+            "tabSize".into(),
+            JsonValue::Integer(buffer.tab_size(&editor.config).get() as _),
+            json,
+        );
+        options.set(
+            "insertSpaces".into(),
+            (!buffer.indent_with_tabs(&editor.config)).into(),
+            json,
+        );
+        options.set("trimTrailingWhitespace".into(), true.into(), json);
+        options.set("trimFinalNewlines".into(), true.into(), json);
+        options
+    }
+
     pub fn extract_markup_content(content: JsonValue, json: &Json) -> &str {
         match content {
             JsonValue::String(s) => s.as_str(json),
@@ -2486,6 +3735,136 @@ mod helper {
         client.notify(platform, "textDocument/didOpen", params);
     }
 
+    // after the first scan, the next one is only scheduled once the previous one finishes
+    pub fn scan_watched_files(client: &mut Client, editor: &Editor, platform: &mut Platform) {
+        if client.watched_files.watchers.is_empty() || client.watched_files.scanning {
+            return;
+        }
+        let delay = if client.watched_files.has_files {
+            WATCHED_FILES_SCAN_INTERVAL
+        } else {
+            Duration::ZERO
+        };
+        let job = client.watched_files.scan_job(
+            client.root.clone(),
+            &editor.config.find_file_ignore,
+            delay,
+        );
+        client.watched_files.scanning = true;
+        platform
+            .jobs
+            .spawn(JobTag::LspWatchedFiles(client.handle), job);
+    }
+
+    pub fn send_did_change_watched_files(
+        client: &mut Client,
+        platform: &mut Platform,
+        scan_output: &[u8],
+    ) {
+        client.watched_files.scanning = false;
+        if std::mem::take(&mut client.watched_files.discard_scan) {
+            return;
+        }
+
+        let mut files = Vec::new();
+        for line in String::from_utf8_lossy(scan_output).lines() {
+            if let Some((modified, path)) = line.split_once('\t') {
+                files.push(WatchedFile {
+                    path: path.into(),
+                    modified: modified.parse().unwrap_or(0),
+                });
+            }
+        }
+        files.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+        let previous_files = std::mem::replace(&mut client.watched_files.files, files);
+        if !std::mem::replace(&mut client.watched_files.has_files, true) {
+            return;
+        }
+
+        const CREATED: i64 = 1;
+        const CHANGED: i64 = 2;
+        const DELETED: i64 = 3;
+
+        let mut changes = Vec::new();
+        let files = &client.watched_files.files;
+        let mut previous_index = 0;
+        let mut index = 0;
+        loop {
+            let change = match (previous_files.get(previous_index), files.get(index)) {
+                (None, None) => break,
+                (Some(previous), Some(file)) => match previous.path.cmp(&file.path) {
+                    Ordering::Less => {
+                        previous_index += 1;
+                        (&previous.path, DELETED, WATCH_KIND_DELETE)
+                    }
+                    Ordering::Greater => {
+                        index += 1;
+                        (&file.path, CREATED, WATCH_KIND_CREATE)
+                    }
+                    Ordering::Equal => {
+                        previous_index += 1;
+                        index += 1;
+                        if previous.modified == file.modified {
+                            continue;
+                        }
+                        (&file.path, CHANGED, WATCH_KIND_CHANGE)
+                    }
+                },
+                (Some(previous), None) => {
+                    previous_index += 1;
+                    (&previous.path, DELETED, WATCH_KIND_DELETE)
+                }
+                (None, Some(file)) => {
+                    index += 1;
+                    (&file.path, CREATED, WATCH_KIND_CREATE)
+                }
+            };
+
+            let (path, change_type, kind) = change;
+            if client.watched_files.watch_kind(path) & kind != 0 {
+                changes.push((client.root.join(path), change_type));
+            }
+        }
+
+        if changes.is_empty() {
+            return;
+        }
+
+        let mut json_changes = JsonArray::default();
+        for (path, change_type) in changes {
+            let uri = client.json.fmt_string(format_args!("{}", Uri::Path(&path)));
+            let mut change = JsonObject::default();
+            change.set("uri".into(), uri.into(), &mut client.json);
+            change.set(
+                "type".into(),
+                JsonValue::Integer(change_type),
+                &mut client.json,
+            );
+            json_changes.push(change.into(), &mut client.json);
+        }
+
+        let mut params = JsonObject::default();
+        params.set("changes".into(), json_changes.into(), &mut client.json);
+        client.notify(platform, "workspace/didChangeWatchedFiles", params);
+    }
+
+    pub fn send_did_change_configuration(client: &mut Client, platform: &mut Platform) {
+        if client.settings.is_empty() {
+            return;
+        }
+
+        let mut reader = io::Cursor::new(&client.settings);
+        let settings = match client.json.read(&mut reader) {
+            Ok(settings) => settings,
+            Err(_) => return,
+        };
+
+        let mut params = JsonObject::default();
+        params.set("settings".into(), settings, &mut client.json);
+        client.notify(platform, "workspace/didChangeConfiguration", params);
+    }
+
     pub fn send_pending_did_change(client: &mut Client, editor: &Editor, platform: &mut Platform) {
         let mut versioned_buffers = std::mem::take(&mut client.versioned_buffers);
         for (buffer_handle, versioned_buffer) in versioned_buffers.iter_pending_mut() {
@@ -2513,21 +3892,29 @@ mod helper {
                 &mut client.json,
             );
 
+            fn utf8_document_position(position: BufferPosition) -> DocumentPosition {
+                DocumentPosition {
+                    line: position.line_index,
+                    character: position.column_byte_index,
+                }
+            }
+
+            // pending edit positions refer to buffer contents that no longer exist,
+            // so they can only be sent as is when the server also counts bytes
             let mut content_changes = JsonArray::default();
             match client.server_capabilities.text_document_sync.change {
                 TextDocumentSyncKind::None => (),
-                TextDocumentSyncKind::Full => {
-                    let text = client.json.fmt_string(format_args!("{}", buffer.content()));
-                    let mut change_event = JsonObject::default();
-                    change_event.set("text".into(), text.into(), &mut client.json);
-                    content_changes.push(change_event.into(), &mut client.json);
-                }
-                TextDocumentSyncKind::Incremental => {
+                TextDocumentSyncKind::Incremental
+                    if client.server_capabilities.position_encoding == PositionEncoding::Utf8 =>
+                {
                     for edit in &versioned_buffer.pending_edits {
                         let mut change_event = JsonObject::default();
 
-                        let edit_range =
-                            DocumentRange::from(edit.buffer_range).to_json_value(&mut client.json);
+                        let edit_range = DocumentRange {
+                            start: utf8_document_position(edit.buffer_range.from),
+                            end: utf8_document_position(edit.buffer_range.to),
+                        };
+                        let edit_range = edit_range.to_json_value(&mut client.json);
                         change_event.set("range".into(), edit_range, &mut client.json);
 
                         let edit_text_range =
@@ -2539,6 +3926,12 @@ mod helper {
                         content_changes.push(change_event.into(), &mut client.json);
                     }
                 }
+                TextDocumentSyncKind::Full | TextDocumentSyncKind::Incremental => {
+                    let text = client.json.fmt_string(format_args!("{}", buffer.content()));
+                    let mut change_event = JsonObject::default();
+                    change_event.set("text".into(), text.into(), &mut client.json);
+                    content_changes.push(change_event.into(), &mut client.json);
+                }
             }
 
             params.set(
@@ -2599,7 +3992,15 @@ mod helper {
             return;
         }
 
-        let text_document = text_document_with_id(&client.root, &buffer.path, &mut client.json);
+        send_did_close_path(client, platform, &buffer.path);
+    }
+
+    pub fn send_did_close_path(client: &mut Client, platform: &mut Platform, path: &Path) {
+        if !client.server_capabilities.text_document_sync.open_close {
+            return;
+        }
+
+        let text_document = text_document_with_id(&client.root, path, &mut client.json);
         let mut params = JsonObject::default();
         params.set(
             "textDocument".into(),
@@ -2609,6 +4010,25 @@ mod helper {
 
         client.notify(platform, "textDocument/didClose", params);
     }
+
+    pub fn file_rename_params(client: &mut Client, old_path: &Path, new_path: &Path) -> JsonObject {
+        let old_uri = client
+            .json
+            .fmt_string(format_args!("{}", Uri::Path(old_path)));
+        let new_uri = client
+            .json
+            .fmt_string(format_args!("{}", Uri::Path(new_path)));
+
+        let mut file = JsonObject::default();
+        file.set("oldUri".into(), old_uri.into(), &mut client.json);
+        file.set("newUri".into(), new_uri.into(), &mut client.json);
+        let mut files = JsonArray::default();
+        files.push(file.into(), &mut client.json);
+
+        let mut params = JsonObject::default();
+        params.set("files".into(), files.into(), &mut client.json);
+        params
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -2628,13 +4048,35 @@ impl FromStr for ClientHandle {
     }
 }
 
+const MAX_RESTART_COUNT: u32 = 5;
+const WILL_RENAME_TIMEOUT: Duration = Duration::from_secs(2);
+const RESTART_RESET_DURATION: Duration = Duration::from_secs(60);
+
 struct ClientRecipe {
     glob_hash: u64,
     glob: Glob,
     command: String,
     root: PathBuf,
     log_file_path: String,
+    initialization_options: String,
+    settings: String,
     running_client: Option<ClientHandle>,
+    started_at: Instant,
+    restart_count: u32,
+    restart_at: Option<Instant>,
+}
+
+pub enum LspConfigError {
+    NoSuchRecipe,
+    InvalidJson,
+}
+impl fmt::Display for LspConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoSuchRecipe => f.write_str("no lsp recipe with this glob"),
+            Self::InvalidJson => f.write_str("invalid json"),
+        }
+    }
 }
 
 enum ClientEntry {
@@ -2657,9 +4099,19 @@ impl ClientEntry {
     }
 }
 
+// a file rename waiting for the `workspace/willRenameFiles` responses
+struct PendingFileRename {
+    old_path: PathBuf,
+    new_path: PathBuf,
+    waiting_clients: Vec<ClientHandle>,
+    timeout_at: Instant,
+}
+
 pub struct ClientManager {
     entries: Vec<ClientEntry>,
     recipes: Vec<ClientRecipe>,
+    format_on_save_globs: Vec<Glob>,
+    pending_renames: Vec<PendingFileRename>,
 }
 
 impl ClientManager {
@@ -2667,9 +4119,22 @@ impl ClientManager {
         Self {
             entries: Vec::new(),
             recipes: Vec::new(),
+            format_on_save_globs: Vec::new(),
+            pending_renames: Vec::new(),
         }
     }
 
+    pub fn add_format_on_save_glob(&mut self, glob: &str) -> Result<(), InvalidGlobError> {
+        let mut format_glob = Glob::default();
+        format_glob.compile(glob)?;
+        self.format_on_save_globs.push(format_glob);
+        Ok(())
+    }
+
+    pub fn formats_on_save(&self, path: &str) -> bool {
+        self.format_on_save_globs.iter().any(|g| g.matches(path))
+    }
+
     pub fn add_recipe(
         &mut self,
         glob: &str,
@@ -2691,6 +4156,8 @@ impl ClientManager {
                     recipe.log_file_path.push_str(name);
                 }
                 recipe.running_client = None;
+                recipe.restart_count = 0;
+                recipe.restart_at = None;
                 return Ok(());
             }
         }
@@ -2703,11 +4170,62 @@ impl ClientManager {
             command: command.into(),
             root: root.unwrap_or("").into(),
             log_file_path: log_file_path.unwrap_or("").into(),
+            initialization_options: String::new(),
+            settings: String::new(),
             running_client: None,
+            started_at: Instant::now(),
+            restart_count: 0,
+            restart_at: None,
         });
         Ok(())
     }
 
+    pub fn set_recipe_initialization_options(
+        &mut self,
+        glob: &str,
+        options: &str,
+    ) -> Result<(), LspConfigError> {
+        let recipe = self.find_recipe(glob, options)?;
+        recipe.initialization_options.clear();
+        recipe.initialization_options.push_str(options);
+        Ok(())
+    }
+
+    pub fn set_recipe_settings(
+        &mut self,
+        platform: &mut Platform,
+        glob: &str,
+        settings: &str,
+    ) -> Result<(), LspConfigError> {
+        let recipe = self.find_recipe(glob, settings)?;
+        recipe.settings.clear();
+        recipe.settings.push_str(settings);
+
+        if let Some(handle) = recipe.running_client {
+            if let ClientEntry::Occupied(client) = &mut self.entries[handle.0 as usize] {
+                client.settings.clear();
+                client.settings.extend_from_slice(settings.as_bytes());
+                if client.initialized {
+                    helper::send_did_change_configuration(client, platform);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn find_recipe(&mut self, glob: &str, json: &str) -> Result<&mut ClientRecipe, LspConfigError> {
+        let mut reader = io::Cursor::new(json.as_bytes());
+        if Json::new().read(&mut reader).is_err() {
+            return Err(LspConfigError::InvalidJson);
+        }
+
+        let glob_hash = hash_bytes(glob.as_bytes());
+        match self.recipes.iter_mut().find(|r| r.glob_hash == glob_hash) {
+            Some(recipe) => Ok(recipe),
+            None => Err(LspConfigError::NoSuchRecipe),
+        }
+    }
+
     pub fn start(
         &mut self,
         platform: &mut Platform,
@@ -2729,6 +4247,11 @@ impl ClientManager {
 
         let handle = find_vacant_entry(self);
 
+        platform.logger.write(LogLevel::Info).fmt(format_args!(
+            "starting lsp server '{}'",
+            command.get_program().to_string_lossy()
+        ));
+
         command
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
@@ -2766,6 +4289,9 @@ impl ClientManager {
         for i in 0..self.entries.len() {
             self.stop(platform, ClientHandle(i as _));
         }
+        for recipe in &mut self.recipes {
+            recipe.restart_at = None;
+        }
     }
 
     pub fn get(&self, handle: ClientHandle) -> Option<&Client> {
@@ -2785,6 +4311,88 @@ impl ClientManager {
         Some(result)
     }
 
+    // the rename only happens once every server that asked for `workspace/willRenameFiles`
+    // responded (and its edits were applied) or after a timeout
+    pub fn rename_file(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        old_path: PathBuf,
+        new_path: PathBuf,
+    ) -> io::Result<()> {
+        let mut waiting_clients = Vec::new();
+        for client in editor.lsp.clients_mut() {
+            if client.will_rename_file(platform, &old_path, &new_path) {
+                waiting_clients.push(client.handle());
+            }
+        }
+
+        if waiting_clients.is_empty() {
+            editor_utils::rename_file(editor, platform, &old_path, &new_path)
+        } else {
+            editor.lsp.pending_renames.push(PendingFileRename {
+                old_path,
+                new_path,
+                waiting_clients,
+                timeout_at: Instant::now() + WILL_RENAME_TIMEOUT,
+            });
+            Ok(())
+        }
+    }
+
+    fn on_will_rename_response(&mut self, handle: ClientHandle) {
+        for rename in &mut self.pending_renames {
+            if let Some(index) = rename.waiting_clients.iter().position(|&h| h == handle) {
+                rename.waiting_clients.remove(index);
+                break;
+            }
+        }
+    }
+
+    fn flush_pending_renames(editor: &mut Editor, platform: &mut Platform) {
+        let now = Instant::now();
+        let mut i = 0;
+        while i < editor.lsp.pending_renames.len() {
+            let rename = &editor.lsp.pending_renames[i];
+            if !rename.waiting_clients.is_empty() && now < rename.timeout_at {
+                i += 1;
+                continue;
+            }
+
+            let rename = editor.lsp.pending_renames.remove(i);
+            if let Err(error) =
+                editor_utils::rename_file(editor, platform, &rename.old_path, &rename.new_path)
+            {
+                editor
+                    .status_bar
+                    .write(MessageKind::Error)
+                    .fmt(format_args!(
+                        "could not rename '{}': {}",
+                        rename.old_path.display(),
+                        error
+                    ));
+            }
+        }
+
+        if !editor.lsp.pending_renames.is_empty() {
+            platform.requests.enqueue(PlatformRequest::Redraw);
+        }
+    }
+
+    pub fn did_rename_file(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        old_path: &Path,
+        new_path: &Path,
+        renamed_buffers: &[(BufferHandle, PathBuf)],
+    ) {
+        for i in 0..editor.lsp.entries.len() {
+            if let Some(mut client) = editor.lsp.entries[i].reserve_and_take() {
+                client.did_rename_file(editor, platform, old_path, new_path, renamed_buffers);
+                editor.lsp.entries[i] = ClientEntry::Occupied(client);
+            }
+        }
+    }
+
     pub fn clients(&self) -> impl DoubleEndedIterator<Item = &Client> {
         self.entries.iter().flat_map(|e| match e {
             ClientEntry::Occupied(client) => Some(client.as_ref()),
@@ -2792,6 +4400,13 @@ impl ClientManager {
         })
     }
 
+    pub fn clients_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut Client> {
+        self.entries.iter_mut().flat_map(|e| match e {
+            ClientEntry::Occupied(client) => Some(client.as_mut()),
+            _ => None,
+        })
+    }
+
     pub fn on_process_spawned(
         editor: &mut Editor,
         platform: &mut Platform,
@@ -2815,15 +4430,19 @@ impl ClientManager {
             Some(client) => client,
             None => return,
         };
+        client.log.trace = editor.config.lsp_trace;
 
         let mut events = client.protocol.parse_events(bytes);
         while let Some(event) = events.next(&mut client.protocol, &mut client.json) {
             match event {
                 ServerEvent::ParseError => {
-                    client.write_to_log_file(|buf, json| {
+                    platform
+                        .logger
+                        .write(LogLevel::Error)
+                        .str("could not parse message from lsp server");
+                    client.write_to_log(None, |buf, _| {
                         use io::Write;
-                        let _ = write!(buf, "send parse error\nrequest_id: ");
-                        let _ = json.write(buf, &JsonValue::Null);
+                        let _ = write!(buf, "send parse error\nrequest_id: null");
                     });
                     client.respond(platform, JsonValue::Null, Err(ResponseError::parse_error()));
                 }
@@ -2842,72 +4461,206 @@ impl ClientManager {
                     }
                 }
                 ServerEvent::Notification(notification) => {
-                    let _ = client.on_notification(editor, notification);
+                    if client.on_notification(editor, notification).is_err() {
+                        platform
+                            .logger
+                            .write(LogLevel::Debug)
+                            .str("could not handle lsp notification");
+                    }
                 }
                 ServerEvent::Response(response) => {
-                    let _ = client.on_response(editor, platform, clients, response);
+                    if client
+                        .on_response(editor, platform, clients, response)
+                        .is_err()
+                    {
+                        platform
+                            .logger
+                            .write(LogLevel::Debug)
+                            .str("could not handle lsp response");
+                    }
                 }
             }
         }
         events.finish(&mut client.protocol);
 
         editor.lsp.entries[handle.0 as usize] = ClientEntry::Occupied(client);
+        Self::flush_pending_renames(editor, platform);
     }
 
-    pub fn on_process_exit(editor: &mut Editor, handle: ClientHandle) {
+    pub fn on_watched_files_scanned(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        handle: ClientHandle,
+        scan_output: &[u8],
+    ) {
+        if let Some(mut client) = editor.lsp.entries[handle.0 as usize].reserve_and_take() {
+            helper::send_did_change_watched_files(&mut client, platform, scan_output);
+            helper::scan_watched_files(&mut client, editor, platform);
+            editor.lsp.entries[handle.0 as usize] = ClientEntry::Occupied(client);
+        }
+    }
+
+    pub fn on_process_exit(editor: &mut Editor, platform: &mut Platform, handle: ClientHandle) {
+        for rename in &mut editor.lsp.pending_renames {
+            rename.waiting_clients.retain(|&h| h != handle);
+        }
+        Self::flush_pending_renames(editor, platform);
+
         let index = handle.0 as usize;
         let mut entry = ClientEntry::Vacant;
         std::mem::swap(&mut entry, &mut editor.lsp.entries[index]);
-        if let ClientEntry::Occupied(mut client) = entry {
-            client.write_to_log_file(|buf, _| {
-                use io::Write;
-                let _ = write!(buf, "lsp server stopped");
-            });
-        }
+        let crashed = match entry {
+            ClientEntry::Occupied(mut client) => {
+                client.write_to_log(None, |buf, _| {
+                    use io::Write;
+                    let _ = write!(buf, "lsp server stopped");
+                });
+                true
+            }
+            _ => false,
+        };
 
         for recipe in &mut editor.lsp.recipes {
-            if recipe.running_client == Some(handle) {
-                recipe.running_client = None;
+            if recipe.running_client != Some(handle) {
+                continue;
+            }
+            recipe.running_client = None;
+            if !crashed {
+                continue;
+            }
+
+            if recipe.started_at.elapsed() >= RESTART_RESET_DURATION {
+                recipe.restart_count = 0;
+            }
+            if recipe.restart_count < MAX_RESTART_COUNT {
+                let delay = Duration::from_secs(1 << recipe.restart_count);
+                recipe.restart_count += 1;
+                recipe.restart_at = Some(Instant::now() + delay);
+                editor
+                    .status_bar
+                    .write(MessageKind::Error)
+                    .fmt(format_args!(
+                        "lsp server '{}' exited unexpectedly. restarting in {} seconds",
+                        recipe.command,
+                        delay.as_secs()
+                    ));
+                platform.logger.write(LogLevel::Warning).fmt(format_args!(
+                    "lsp server '{}' exited unexpectedly. restart {} of {}",
+                    recipe.command, recipe.restart_count, MAX_RESTART_COUNT
+                ));
+            } else {
+                editor
+                    .status_bar
+                    .write(MessageKind::Error)
+                    .fmt(format_args!(
+                        "lsp server '{}' exited unexpectedly too many times",
+                        recipe.command
+                    ));
+                platform.logger.write(LogLevel::Error).fmt(format_args!(
+                    "lsp server '{}' exited unexpectedly too many times",
+                    recipe.command
+                ));
             }
         }
     }
 
-    pub fn on_editor_events(editor: &mut Editor, platform: &mut Platform) {
-        let mut events = EditorEventIter::new();
-        while let Some(event) = events.next(&editor.events) {
-            if let EditorEvent::BufferRead { handle } = *event {
-                let buffer_path = match editor.buffers.get(handle).path.to_str() {
-                    Some(path) => path,
-                    None => continue,
-                };
-                let (index, recipe) = match editor
-                    .lsp
-                    .recipes
-                    .iter_mut()
-                    .enumerate()
-                    .find(|(_, r)| r.glob.matches(buffer_path))
-                {
-                    Some(recipe) => recipe,
-                    None => continue,
-                };
-                if recipe.running_client.is_some() {
-                    continue;
+    fn restart_crashed_recipes(editor: &mut Editor, platform: &mut Platform) {
+        let now = Instant::now();
+        let mut pending = false;
+        for index in 0..editor.lsp.recipes.len() {
+            let recipe = &editor.lsp.recipes[index];
+            match recipe.restart_at {
+                Some(instant) if instant <= now => {
+                    editor.lsp.recipes[index].restart_at = None;
+                    let workspace_roots = editor.workspace.roots().to_vec();
+                    editor.lsp.start_recipe(
+                        platform,
+                        &mut editor.status_bar,
+                        &editor.current_directory,
+                        workspace_roots,
+                        index,
+                    );
+                }
+                Some(_) => pending = true,
+                None => (),
+            }
+        }
+
+        if pending {
+            platform.requests.enqueue(PlatformRequest::Redraw);
+        }
+    }
+
+    pub fn on_idle(editor: &mut Editor, platform: &mut Platform, clients: &client::ClientManager) {
+        Self::restart_crashed_recipes(editor, platform);
+        Self::flush_pending_renames(editor, platform);
+
+        if !editor.config.lsp_inlay_hints {
+            return;
+        }
+
+        for client in clients.iter() {
+            let buffer_view_handle = match client.buffer_view_handle() {
+                Some(handle) => handle,
+                None => continue,
+            };
+            if !client.has_ui() {
+                continue;
+            }
+            let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+            let line_range =
+                client.scroll.1..client.scroll.1 + client.height as BufferPositionIndex;
+
+            for i in 0..editor.lsp.entries.len() {
+                if let Some(mut lsp_client) = editor.lsp.entries[i].reserve_and_take() {
+                    lsp_client.refresh_inlay_hints(
+                        editor,
+                        platform,
+                        buffer_handle,
+                        line_range.clone(),
+                    );
+                    editor.lsp.entries[i] = ClientEntry::Occupied(lsp_client);
                 }
+            }
+        }
+    }
+
+    fn start_recipe(
+        &mut self,
+        platform: &mut Platform,
+        status_bar: &mut StatusBar,
+        current_directory: &Path,
+        mut workspace_roots: Vec<PathBuf>,
+        index: usize,
+    ) {
+        let recipe = &self.recipes[index];
+        let root = if recipe.root.as_os_str().is_empty() {
+            current_directory.into()
+        } else {
+            workspace_roots = vec![recipe.root.clone()];
+            recipe.root.clone()
+        };
+
+        let shared_client = self.recipes.iter().find_map(|r| {
+            let client = self.get(r.running_client?)?;
+            if r.command == recipe.command && client.root == root {
+                Some(client.handle())
+            } else {
+                None
+            }
+        });
+        let (client_handle, started) = match shared_client {
+            Some(client_handle) => (client_handle, false),
+            None => {
                 let command = match parse_process_command(&recipe.command) {
                     Some(command) => command,
                     None => {
-                        editor
-                            .status_bar
+                        status_bar
                             .write(MessageKind::Error)
                             .fmt(format_args!("invalid lsp command '{}'", &recipe.command));
-                        continue;
+                        return;
                     }
                 };
-                let root = if recipe.root.as_os_str().is_empty() {
-                    editor.current_directory.clone()
-                } else {
-                    recipe.root.clone()
-                };
 
                 let log_file_path = if recipe.log_file_path.is_empty() {
                     None
@@ -2915,13 +4668,72 @@ impl ClientManager {
                     Some(recipe.log_file_path.clone())
                 };
 
-                let client_handle = editor.lsp.start(platform, command, root, log_file_path);
-                editor.lsp.recipes[index].running_client = Some(client_handle);
+                let handle = self.start(platform, command, root, log_file_path);
+                (handle, true)
+            }
+        };
+
+        let recipe = &mut self.recipes[index];
+        recipe.running_client = Some(client_handle);
+        recipe.started_at = Instant::now();
+        recipe.restart_at = None;
+        if let ClientEntry::Occupied(client) = &mut self.entries[client_handle.0 as usize] {
+            client.globs.push(recipe.glob.clone());
+            if started {
+                client.workspace_roots = workspace_roots;
+                client
+                    .initialization_options
+                    .extend_from_slice(recipe.initialization_options.as_bytes());
+                client
+                    .settings
+                    .extend_from_slice(recipe.settings.as_bytes());
+            }
+        }
+    }
+
+    pub fn on_editor_events(editor: &mut Editor, platform: &mut Platform) {
+        let mut events = EditorEventIter::new();
+        while let Some(event) = events.next(&editor.events) {
+            if let EditorEvent::BufferRead { handle } = *event {
+                let buffer = editor.buffers.get(handle);
+                if buffer.is_large_file() {
+                    continue;
+                }
+                let buffer_path = &buffer.path;
+                let buffer_path_str = match buffer_path.to_str() {
+                    Some(path) => path,
+                    None => continue,
+                };
+                for index in 0..editor.lsp.recipes.len() {
+                    let recipe = &editor.lsp.recipes[index];
+                    if recipe.running_client.is_some() || !recipe.glob.matches(buffer_path_str) {
+                        continue;
+                    }
+
+                    let root = editor
+                        .workspace
+                        .root_for(&editor.current_directory, buffer_path);
+                    let mut workspace_roots = vec![root];
+                    for root in editor.workspace.roots() {
+                        if !workspace_roots.contains(root) {
+                            workspace_roots.push(root.clone());
+                        }
+                    }
+
+                    editor.lsp.start_recipe(
+                        platform,
+                        &mut editor.status_bar,
+                        &editor.current_directory,
+                        workspace_roots,
+                        index,
+                    );
+                }
             }
         }
 
         for i in 0..editor.lsp.entries.len() {
             if let Some(mut client) = editor.lsp.entries[i].reserve_and_take() {
+                client.log.trace = editor.config.lsp_trace;
                 client.on_editor_events(editor, platform);
                 editor.lsp.entries[i] = ClientEntry::Occupied(client);
             }
@@ -6,8 +6,11 @@ use std::{
 };
 
 use crate::{
-    buffer::{BufferCapabilities, BufferHandle},
-    buffer_position::{BufferPosition, BufferRange},
+    buffer::{BufferCapabilities, BufferContent, BufferHandle},
+    buffer_position::{
+        byte_index_to_char_index, byte_index_to_utf16_index, char_index_to_byte_index,
+        floor_char_boundary, utf16_index_to_byte_index, BufferPosition, BufferRange,
+    },
     editor::Editor,
     editor_utils::MessageKind,
     glob::InvalidGlobError,
@@ -246,12 +249,93 @@ impl From<InvalidGlobError> for ProtocolError {
     }
 }
 
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    #[default]
+    Utf16,
+    Utf32,
+}
+impl PositionEncoding {
+    pub const SUPPORTED: [Self; 3] = [Self::Utf8, Self::Utf32, Self::Utf16];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16 => "utf-16",
+            Self::Utf32 => "utf-32",
+        }
+    }
+
+    pub fn encode_column(self, line: &str, column_byte_index: usize) -> u32 {
+        let character = match self {
+            Self::Utf8 => floor_char_boundary(line, column_byte_index),
+            Self::Utf16 => byte_index_to_utf16_index(line, column_byte_index),
+            Self::Utf32 => byte_index_to_char_index(line, column_byte_index),
+        };
+        character as _
+    }
+
+    pub fn decode_column(self, line: &str, character: u32) -> usize {
+        let character = character as usize;
+        match self {
+            Self::Utf8 => floor_char_boundary(line, character),
+            Self::Utf16 => utf16_index_to_byte_index(line, character),
+            Self::Utf32 => char_index_to_byte_index(line, character),
+        }
+    }
+}
+impl<'json> FromJson<'json> for PositionEncoding {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        let value = JsonString::from_json(value, json)?;
+        Self::SUPPORTED
+            .iter()
+            .copied()
+            .find(|e| e.name() == value.as_str(json))
+            .ok_or(JsonConvertError)
+    }
+}
+
 #[derive(Default, Clone, Copy)]
 pub struct DocumentPosition {
     pub line: u32,
     pub character: u32,
 }
 impl DocumentPosition {
+    pub fn from_buffer_position(
+        position: BufferPosition,
+        content: &BufferContent,
+        encoding: PositionEncoding,
+    ) -> Self {
+        let line_index = position.line_index as usize;
+        let character = if line_index < content.line_count() {
+            let line = content.line_at(line_index).as_str();
+            encoding.encode_column(line, position.column_byte_index as _)
+        } else {
+            position.column_byte_index
+        };
+        Self {
+            line: position.line_index,
+            character,
+        }
+    }
+
+    pub fn to_buffer_position(
+        self,
+        content: Option<&BufferContent>,
+        encoding: PositionEncoding,
+    ) -> BufferPosition {
+        let line_index = self.line as usize;
+        let column_byte_index = match content {
+            Some(content) if line_index < content.line_count() => {
+                let line = content.line_at(line_index).as_str();
+                encoding.decode_column(line, self.character) as _
+            }
+            _ => self.character,
+        };
+        BufferPosition::line_col(self.line, column_byte_index)
+    }
+
     pub fn to_json_value(self, json: &mut Json) -> JsonValue {
         let mut value = JsonObject::default();
         value.set("line".into(), JsonValue::Integer(self.line as _), json);
@@ -263,24 +347,6 @@ impl DocumentPosition {
         value.into()
     }
 }
-// TODO: handle utf8 to utf16
-impl From<BufferPosition> for DocumentPosition {
-    fn from(position: BufferPosition) -> Self {
-        Self {
-            line: position.line_index as _,
-            character: position.column_byte_index as _,
-        }
-    }
-}
-// TODO: handle utf16 to utf8
-impl From<DocumentPosition> for BufferPosition {
-    fn from(position: DocumentPosition) -> Self {
-        Self {
-            line_index: position.line as _,
-            column_byte_index: position.character as _,
-        }
-    }
-}
 impl<'json> FromJson<'json> for DocumentPosition {
     fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
         let value = match value {
@@ -305,6 +371,28 @@ pub struct DocumentRange {
     pub end: DocumentPosition,
 }
 impl DocumentRange {
+    pub fn from_buffer_range(
+        range: BufferRange,
+        content: &BufferContent,
+        encoding: PositionEncoding,
+    ) -> Self {
+        Self {
+            start: DocumentPosition::from_buffer_position(range.from, content, encoding),
+            end: DocumentPosition::from_buffer_position(range.to, content, encoding),
+        }
+    }
+
+    pub fn to_buffer_range(
+        self,
+        content: Option<&BufferContent>,
+        encoding: PositionEncoding,
+    ) -> BufferRange {
+        BufferRange::between(
+            self.start.to_buffer_position(content, encoding),
+            self.end.to_buffer_position(content, encoding),
+        )
+    }
+
     pub fn to_json_value(self, json: &mut Json) -> JsonValue {
         let mut value = JsonObject::default();
         value.set("start".into(), self.start.to_json_value(json), json);
@@ -312,19 +400,6 @@ impl DocumentRange {
         value.into()
     }
 }
-impl From<BufferRange> for DocumentRange {
-    fn from(range: BufferRange) -> Self {
-        Self {
-            start: range.from.into(),
-            end: range.to.into(),
-        }
-    }
-}
-impl From<DocumentRange> for BufferRange {
-    fn from(range: DocumentRange) -> Self {
-        BufferRange::between(range.start.into(), range.end.into())
-    }
-}
 impl<'json> FromJson<'json> for DocumentRange {
     fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
         let value = match value {
@@ -377,6 +452,7 @@ impl TextEdit {
         buffer_handle: BufferHandle,
         temp_edits: &mut Vec<(BufferRange, BufferRange)>,
         edits: JsonArray,
+        encoding: PositionEncoding,
         json: &Json,
     ) {
         let buffer = editor.buffers.get_mut(buffer_handle);
@@ -384,16 +460,23 @@ impl TextEdit {
         buffer.commit_edits();
         temp_edits.clear();
 
-        for edit in edits.elements(json) {
-            let edit = match TextEdit::from_json(edit, json) {
-                Ok(edit) => edit,
-                Err(_) => continue,
-            };
+        let parse_edits = |edits: JsonArray| {
+            edits
+                .elements(json)
+                .filter_map(move |edit| TextEdit::from_json(edit, json).ok())
+        };
+
+        // all edit ranges refer to the original content
+        for edit in parse_edits(edits.clone()) {
+            let range = edit.range.to_buffer_range(Some(buffer.content()), encoding);
+            temp_edits.push((range, range));
+        }
 
-            let mut delete_range: BufferRange = edit.range.into();
+        for (index, edit) in parse_edits(edits).enumerate() {
+            let mut delete_range = temp_edits[index].0;
             let text = edit.new_text.as_str(&json);
 
-            for (d, i) in temp_edits.iter() {
+            for (d, i) in temp_edits[..index].iter() {
                 delete_range.from = delete_range.from.delete(*d);
                 delete_range.to = delete_range.to.delete(*d);
 
@@ -409,7 +492,7 @@ impl TextEdit {
                 &mut editor.events,
             );
 
-            temp_edits.push((delete_range, insert_range));
+            temp_edits[index] = (delete_range, insert_range);
         }
         buffer.commit_edits();
     }
@@ -590,15 +673,172 @@ impl<'json> FromJson<'json> for WorkspaceEditChange {
 #[derive(Default)]
 pub struct WorkspaceEdit {
     document_changes: JsonArray,
+    changes: JsonObject,
 }
 impl WorkspaceEdit {
+    fn apply_document_edit(
+        editor: &mut Editor,
+        temp_edits: &mut Vec<(BufferRange, BufferRange)>,
+        path: &Path,
+        edits: JsonArray,
+        encoding: PositionEncoding,
+        json: &Json,
+    ) {
+        let buffer_handle = editor
+            .buffers
+            .find_with_path(&editor.current_directory, path);
+
+        let (is_temp, buffer_handle) = match buffer_handle {
+            Some(handle) => (false, handle),
+            None => {
+                let buffer = editor.buffers.add_new();
+                buffer.capabilities = BufferCapabilities::log();
+                buffer.capabilities.can_save = true;
+                buffer.path.clear();
+                buffer.path.push(path);
+                let _ = buffer.read_from_file(
+                    &mut editor.word_database,
+                    &editor.config,
+                    &mut editor.events,
+                );
+                (true, buffer.handle())
+            }
+        };
+
+        TextEdit::apply_edits(editor, buffer_handle, temp_edits, edits, encoding, json);
+
+        if is_temp {
+            let _ = editor
+                .buffers
+                .get_mut(buffer_handle)
+                .write_to_file(None, &mut editor.events);
+
+            editor
+                .buffers
+                .defer_remove(buffer_handle, &mut editor.events);
+        }
+    }
+
+    fn write_document_edit_preview(
+        editor: &Editor,
+        path: &Path,
+        edits: JsonArray,
+        encoding: PositionEncoding,
+        json: &Json,
+        text: &mut String,
+    ) {
+        use fmt::Write;
+
+        let mut file_content = BufferContent::new();
+        let content = match editor
+            .buffers
+            .find_with_path(&editor.current_directory, path)
+        {
+            Some(handle) => editor.buffers.get(handle).content(),
+            None => {
+                if let Ok(file) = fs::File::open(path) {
+                    let _ = file_content.read(&mut io::BufReader::new(file));
+                }
+                &file_content
+            }
+        };
+
+        for edit in edits.elements(json) {
+            let edit = match TextEdit::from_json(edit, json) {
+                Ok(edit) => edit,
+                Err(_) => continue,
+            };
+            let range = edit.range.to_buffer_range(Some(content), encoding);
+            let _ = write!(
+                text,
+                "{}:{},{} '",
+                path.display(),
+                range.from.line_index + 1,
+                content.char_column(range.from) + 1,
+            );
+            content.append_range_text_to_string(range, text);
+            let _ = writeln!(text, "' -> '{}'", edit.new_text.as_str(json));
+        }
+    }
+
+    pub fn write_preview(
+        &self,
+        editor: &Editor,
+        root: &Path,
+        encoding: PositionEncoding,
+        json: &Json,
+        text: &mut String,
+    ) {
+        use fmt::Write;
+
+        for change in self.document_changes.clone().elements(json) {
+            let change = match WorkspaceEditChange::from_json(change, json) {
+                Ok(change) => change,
+                Err(_) => continue,
+            };
+            match change {
+                WorkspaceEditChange::DocumentEdit(edit) => {
+                    if let Ok(Uri::Path(path)) = Uri::parse(root, edit.uri.as_str(json)) {
+                        Self::write_document_edit_preview(
+                            editor, path, edit.edits, encoding, json, text,
+                        );
+                    }
+                }
+                WorkspaceEditChange::CreateFile(op) => {
+                    if let Ok(Uri::Path(path)) = Uri::parse(root, op.uri.as_str(json)) {
+                        let _ = writeln!(text, "create file {}", path.display());
+                    }
+                }
+                WorkspaceEditChange::RenameFile(op) => {
+                    if let (Ok(Uri::Path(old_path)), Ok(Uri::Path(new_path))) = (
+                        Uri::parse(root, op.old_uri.as_str(json)),
+                        Uri::parse(root, op.new_uri.as_str(json)),
+                    ) {
+                        let _ = writeln!(
+                            text,
+                            "rename file {} to {}",
+                            old_path.display(),
+                            new_path.display()
+                        );
+                    }
+                }
+                WorkspaceEditChange::DeleteFile(op) => {
+                    if let Ok(Uri::Path(path)) = Uri::parse(root, op.uri.as_str(json)) {
+                        let _ = writeln!(text, "delete {}", path.display());
+                    }
+                }
+            }
+        }
+
+        for (uri, edits) in self.changes.clone().members(json) {
+            let edits = match edits {
+                JsonValue::Array(edits) => edits,
+                _ => continue,
+            };
+            if let Ok(Uri::Path(path)) = Uri::parse(root, uri) {
+                Self::write_document_edit_preview(editor, path, edits, encoding, json, text);
+            }
+        }
+    }
+
     pub fn apply(
         &self,
         editor: &mut Editor,
         temp_edits: &mut Vec<(BufferRange, BufferRange)>,
         root: &Path,
+        encoding: PositionEncoding,
         json: &Json,
     ) {
+        for (uri, edits) in self.changes.clone().members(json) {
+            let edits = match edits {
+                JsonValue::Array(edits) => edits,
+                _ => continue,
+            };
+            if let Ok(Uri::Path(path)) = Uri::parse(root, uri) {
+                Self::apply_document_edit(editor, temp_edits, path, edits, encoding, json);
+            }
+        }
+
         for change in self.document_changes.clone().elements(json) {
             let change = match WorkspaceEditChange::from_json(change, json) {
                 Ok(change) => change,
@@ -610,36 +850,7 @@ impl WorkspaceEdit {
                         Ok(Uri::Path(path)) => path,
                         Err(_) => return,
                     };
-                    let buffer_handle = editor
-                        .buffers
-                        .find_with_path(&editor.current_directory, path);
-
-                    let (is_temp, buffer_handle) = match buffer_handle {
-                        Some(handle) => (false, handle),
-                        None => {
-                            let buffer = editor.buffers.add_new();
-                            buffer.capabilities = BufferCapabilities::log();
-                            buffer.capabilities.can_save = true;
-                            buffer.path.clear();
-                            buffer.path.push(path);
-                            let _ = buffer
-                                .read_from_file(&mut editor.word_database, &mut editor.events);
-                            (true, buffer.handle())
-                        }
-                    };
-
-                    TextEdit::apply_edits(editor, buffer_handle, temp_edits, edit.edits, json);
-
-                    if is_temp {
-                        let _ = editor
-                            .buffers
-                            .get_mut(buffer_handle)
-                            .write_to_file(None, &mut editor.events);
-
-                        editor
-                            .buffers
-                            .defer_remove(buffer_handle, &mut editor.events);
-                    }
+                    Self::apply_document_edit(editor, temp_edits, path, edit.edits, encoding, json);
                 }
                 WorkspaceEditChange::CreateFile(op) => {
                     let path = match Uri::parse(&root, op.uri.as_str(json)) {
@@ -716,8 +927,13 @@ impl<'json> FromJson<'json> for WorkspaceEdit {
             _ => return Err(JsonConvertError),
         };
         let mut this = Self::default();
-        let changes = value.get("documentChanges", json);
-        this.document_changes = FromJson::from_json(changes, json)?;
+        for (key, value) in value.members(json) {
+            match key {
+                "documentChanges" => this.document_changes = FromJson::from_json(value, json)?,
+                "changes" => this.changes = FromJson::from_json(value, json)?,
+                _ => (),
+            }
+        }
         Ok(this)
     }
 }
@@ -756,23 +972,59 @@ impl<'json> FromJson<'json> for DocumentDiagnostic {
     }
 }
 
+#[derive(Default)]
+pub struct DocumentCommand {
+    pub command: JsonString,
+    pub arguments: JsonValue,
+}
+impl<'json> FromJson<'json> for DocumentCommand {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        let value = match value {
+            JsonValue::Object(value) => value,
+            _ => return Err(JsonConvertError),
+        };
+        let mut command = None;
+        let mut arguments = JsonValue::Null;
+        for (key, value) in value.members(json) {
+            match key {
+                "command" => command = Some(JsonString::from_json(value, json)?),
+                "arguments" => arguments = value,
+                _ => (),
+            }
+        }
+        match command {
+            Some(command) => Ok(Self { command, arguments }),
+            None => Err(JsonConvertError),
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct DocumentCodeAction {
     pub title: JsonString,
     pub edit: WorkspaceEdit,
+    pub command: Option<DocumentCommand>,
     pub disabled: bool,
 }
 impl<'json> FromJson<'json> for DocumentCodeAction {
     fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
-        let value = match value {
-            JsonValue::Object(value) => value,
+        let object = match value {
+            JsonValue::Object(object) => object,
             _ => return Err(JsonConvertError),
         };
         let mut this = Self::default();
-        for (key, value) in value.members(json) {
+        for (key, value) in object.clone().members(json) {
             match key {
                 "title" => this.title = JsonString::from_json(value, json)?,
                 "edit" => this.edit = WorkspaceEdit::from_json(value, json)?,
+                "command" => {
+                    // a plain `Command` has a string `command` field instead of an object
+                    let command = match value {
+                        JsonValue::String(_) => JsonValue::Object(object.clone()),
+                        value => value,
+                    };
+                    this.command = Some(DocumentCommand::from_json(command, json)?);
+                }
                 "disabled" => this.disabled = true,
                 _ => (),
             }
@@ -784,6 +1036,7 @@ impl<'json> FromJson<'json> for DocumentCodeAction {
 #[derive(Default)]
 pub struct DocumentSymbolInformation {
     pub name: JsonString,
+    pub kind: u32,
     pub uri: JsonString,
     pub range: DocumentRange,
     pub container_name: Option<JsonString>,
@@ -799,6 +1052,7 @@ impl<'json> FromJson<'json> for DocumentSymbolInformation {
         for (key, value) in value.members(json) {
             match key {
                 "name" => this.name = JsonString::from_json(value, json)?,
+                "kind" => this.kind = FromJson::from_json(value, json)?,
                 "location" => {
                     let location = DocumentLocation::from_json(value, json)?;
                     this.uri = location.uri;
@@ -814,9 +1068,63 @@ impl<'json> FromJson<'json> for DocumentSymbolInformation {
     }
 }
 
+#[derive(Default)]
+pub struct DocumentInlayHint {
+    pub position: DocumentPosition,
+    pub label: JsonValue,
+    pub padding_left: bool,
+    pub padding_right: bool,
+}
+impl DocumentInlayHint {
+    pub fn write_label(&self, json: &Json, text: &mut String) {
+        if self.padding_left {
+            text.push(' ');
+        }
+        match &self.label {
+            JsonValue::String(label) => text.push_str(label.as_str(json)),
+            JsonValue::Array(parts) => {
+                for part in parts.clone().elements(json) {
+                    if let JsonValue::String(value) = part.get("value", json) {
+                        text.push_str(value.as_str(json));
+                    }
+                }
+            }
+            _ => (),
+        }
+        if self.padding_right {
+            text.push(' ');
+        }
+    }
+}
+impl<'json> FromJson<'json> for DocumentInlayHint {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        let value = match value {
+            JsonValue::Object(value) => value,
+            _ => return Err(JsonConvertError),
+        };
+        let mut this = Self::default();
+        for (key, value) in value.members(json) {
+            match key {
+                "position" => this.position = DocumentPosition::from_json(value, json)?,
+                "label" => this.label = value,
+                "paddingLeft" => this.padding_left = matches!(value, JsonValue::Boolean(true)),
+                "paddingRight" => this.padding_right = matches!(value, JsonValue::Boolean(true)),
+                _ => (),
+            }
+        }
+        Ok(this)
+    }
+}
+
 #[derive(Default)]
 pub struct DocumentCompletionItem {
+    pub label: JsonString,
+    pub kind: u32,
+    pub detail: JsonString,
+    pub documentation: JsonValue,
     pub text: JsonString,
+    pub edit_range: Option<DocumentRange>,
+    pub is_snippet: bool,
 }
 impl<'json> FromJson<'json> for DocumentCompletionItem {
     fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
@@ -825,21 +1133,161 @@ impl<'json> FromJson<'json> for DocumentCompletionItem {
             _ => return Err(JsonConvertError),
         };
         let mut this = Self::default();
+        let mut insert_text = None;
+        let mut edit_text = None;
         for (key, value) in value.members(json) {
             match key {
-                "label" => this.text = JsonString::from_json(value, json)?,
-                "insertText" => {
-                    this.text = JsonString::from_json(value, json)?;
-                    break;
+                "label" => this.label = JsonString::from_json(value, json)?,
+                "kind" => this.kind = FromJson::from_json(value, json)?,
+                "detail" => this.detail = JsonString::from_json(value, json)?,
+                "documentation" => this.documentation = value,
+                "insertText" => insert_text = Some(JsonString::from_json(value, json)?),
+                "insertTextFormat" => {
+                    this.is_snippet = matches!(value, JsonValue::Integer(2));
+                }
+                "textEdit" => {
+                    if let JsonValue::Object(edit) = value {
+                        for (key, value) in edit.members(json) {
+                            match key {
+                                "newText" => edit_text = Some(JsonString::from_json(value, json)?),
+                                "range" | "insert" => {
+                                    this.edit_range = Some(DocumentRange::from_json(value, json)?)
+                                }
+                                _ => (),
+                            }
+                        }
+                    }
                 }
                 _ => (),
             }
         }
+        this.text = edit_text
+            .or(insert_text)
+            .unwrap_or_else(|| this.label.clone());
         Ok(this)
     }
 }
 
-fn try_get_content_range(buf: &[u8]) -> Option<Range<usize>> {
+pub struct SymbolKind(pub u32);
+impl fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // https://microsoft.github.io/language-server-protocol/specifications/specification-current/#symbolKind
+        let name = match self.0 {
+            1 => "file",
+            2 => "module",
+            3 => "namespace",
+            4 => "package",
+            5 => "class",
+            6 => "method",
+            7 => "property",
+            8 => "field",
+            9 => "constructor",
+            10 => "enum",
+            11 => "interface",
+            12 => "function",
+            13 => "variable",
+            14 => "constant",
+            15 => "string",
+            16 => "number",
+            17 => "boolean",
+            18 => "array",
+            19 => "object",
+            20 => "key",
+            21 => "null",
+            22 => "enum-member",
+            23 => "struct",
+            24 => "event",
+            25 => "operator",
+            26 => "type-parameter",
+            kind => return write!(f, "{}", kind),
+        };
+        f.write_str(name)
+    }
+}
+
+pub fn completion_item_kind_name(kind: u32) -> &'static str {
+    // https://microsoft.github.io/language-server-protocol/specifications/specification-current/#completionItemKind
+    match kind {
+        1 => "text",
+        2 => "method",
+        3 => "function",
+        4 => "constructor",
+        5 => "field",
+        6 => "variable",
+        7 => "class",
+        8 => "interface",
+        9 => "module",
+        10 => "property",
+        11 => "unit",
+        12 => "value",
+        13 => "enum",
+        14 => "keyword",
+        15 => "snippet",
+        16 => "color",
+        17 => "file",
+        18 => "reference",
+        19 => "folder",
+        20 => "enum-member",
+        21 => "constant",
+        22 => "struct",
+        23 => "event",
+        24 => "operator",
+        25 => "type-parameter",
+        _ => "",
+    }
+}
+
+pub fn strip_snippet_placeholders(snippet: &str, output: &mut String) {
+    fn parse(chars: &mut std::iter::Peekable<std::str::Chars>, output: &mut String, nested: bool) {
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    if let Some(c) = chars.next() {
+                        output.push(c);
+                    }
+                }
+                '}' if nested => return,
+                '$' => match chars.peek() {
+                    Some(c) if c.is_ascii_digit() => {
+                        while matches!(chars.peek(), Some(c) if c.is_ascii_digit()) {
+                            chars.next();
+                        }
+                    }
+                    Some('{') => {
+                        chars.next();
+                        while matches!(chars.peek(), Some(c) if c.is_ascii_alphanumeric() || *c == '_')
+                        {
+                            chars.next();
+                        }
+                        match chars.next() {
+                            Some(':') => parse(chars, output, true),
+                            Some('|') => {
+                                for c in &mut *chars {
+                                    if c == ',' || c == '|' {
+                                        break;
+                                    }
+                                    output.push(c);
+                                }
+                                for c in &mut *chars {
+                                    if c == '}' {
+                                        break;
+                                    }
+                                }
+                            }
+                            _ => (),
+                        }
+                    }
+                    _ => output.push(c),
+                },
+                _ => output.push(c),
+            }
+        }
+    }
+
+    parse(&mut snippet.chars().peekable(), output, false);
+}
+
+pub fn try_get_content_range(buf: &[u8]) -> Option<Range<usize>> {
     fn find_pattern_end(buf: &[u8], pattern: &[u8]) -> Option<usize> {
         let len = pattern.len();
         buf.windows(len).position(|w| w == pattern).map(|p| p + len)
@@ -965,6 +1413,11 @@ pub struct Protocol {
     read_buf: Vec<u8>,
     next_request_id: usize,
 }
+impl Default for Protocol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Protocol {
     pub fn new() -> Self {
@@ -1071,6 +1524,7 @@ impl Protocol {
 struct PendingRequest {
     id: RequestId,
     method: &'static str,
+    cancelled: bool,
 }
 
 #[derive(Default)]
@@ -1084,11 +1538,26 @@ impl PendingRequestColection {
             if request.id.0 == 0 {
                 request.id = id;
                 request.method = method;
+                request.cancelled = false;
                 return;
             }
         }
 
-        self.pending_requests.push(PendingRequest { id, method });
+        self.pending_requests.push(PendingRequest {
+            id,
+            method,
+            cancelled: false,
+        });
+    }
+
+    pub fn cancel(&mut self, id: RequestId) -> bool {
+        for request in &mut self.pending_requests {
+            if request.id == id && !request.cancelled {
+                request.cancelled = true;
+                return true;
+            }
+        }
+        false
     }
 
     pub fn take(&mut self, id: RequestId) -> Option<&'static str> {
@@ -1096,9 +1565,139 @@ impl PendingRequestColection {
             let request = &self.pending_requests[i];
             if request.id == id {
                 let request = self.pending_requests.swap_remove(i);
+                if request.cancelled {
+                    return None;
+                }
                 return Some(request.method);
             }
         }
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snippet_placeholders() {
+        fn strip(snippet: &str) -> String {
+            let mut output = String::new();
+            strip_snippet_placeholders(snippet, &mut output);
+            output
+        }
+
+        assert_eq!("", strip(""));
+        assert_eq!("abc", strip("abc"));
+        assert_eq!("foo()", strip("foo($0)"));
+        assert_eq!("foo(a, b)", strip("foo(${1:a}, ${2:b})$0"));
+        assert_eq!("foo(a, b)", strip("foo(${1:a${2:, b}})"));
+        assert_eq!("one", strip("${1|one,two|}"));
+        assert_eq!("$}", strip("\\$\\}"));
+        assert_eq!("a$", strip("a$"));
+    }
+
+    #[test]
+    fn position_encodings() {
+        let line = "a\u{e9}\u{1f600}b";
+        let columns = [(0, 0, 0, 0), (1, 1, 1, 1), (3, 3, 2, 2), (7, 7, 4, 3)];
+        for &(byte_index, utf8, utf16, utf32) in &columns {
+            assert_eq!(utf8, PositionEncoding::Utf8.encode_column(line, byte_index));
+            assert_eq!(
+                utf16,
+                PositionEncoding::Utf16.encode_column(line, byte_index)
+            );
+            assert_eq!(
+                utf32,
+                PositionEncoding::Utf32.encode_column(line, byte_index)
+            );
+            assert_eq!(byte_index, PositionEncoding::Utf8.decode_column(line, utf8));
+            assert_eq!(
+                byte_index,
+                PositionEncoding::Utf16.decode_column(line, utf16)
+            );
+            assert_eq!(
+                byte_index,
+                PositionEncoding::Utf32.decode_column(line, utf32)
+            );
+        }
+        assert_eq!(1, PositionEncoding::Utf8.decode_column(line, 2));
+        assert_eq!(8, PositionEncoding::Utf16.decode_column(line, 99));
+
+        let mut content = BufferContent::new();
+        content.insert_text(BufferPosition::zero(), "x\n\u{e9}\u{e9}y");
+        let position = BufferPosition::line_col(1, 4);
+        let document_position =
+            DocumentPosition::from_buffer_position(position, &content, PositionEncoding::Utf16);
+        assert_eq!(1, document_position.line);
+        assert_eq!(2, document_position.character);
+        assert_eq!(
+            position,
+            document_position.to_buffer_position(Some(&content), PositionEncoding::Utf16)
+        );
+        assert_eq!(
+            BufferPosition::line_col(1, 2),
+            document_position.to_buffer_position(None, PositionEncoding::Utf16)
+        );
+    }
+
+    #[test]
+    fn completion_item_edit_range() {
+        fn parse(item: &str) -> (String, Option<(u32, u32)>) {
+            let mut json = Json::new();
+            let value = json.read(&mut item.as_bytes()).unwrap();
+            let item = DocumentCompletionItem::from_json(value, &json)
+                .ok()
+                .unwrap();
+            let range = item
+                .edit_range
+                .map(|r| (r.start.character, r.end.character));
+            (item.text.as_str(&json).into(), range)
+        }
+
+        assert_eq!(("abc".into(), None), parse(r#"{"label":"abc"}"#));
+        assert_eq!(
+            ("->abc".into(), Some((3, 5))),
+            parse(
+                r#"{"label":"abc","textEdit":{"newText":"->abc","range":{"start":{"line":0,"character":3},"end":{"line":0,"character":5}}}}"#
+            )
+        );
+        assert_eq!(
+            ("abc".into(), Some((1, 2))),
+            parse(
+                r#"{"label":"abc","textEdit":{"newText":"abc","insert":{"start":{"line":0,"character":1},"end":{"line":0,"character":2}},"replace":{"start":{"line":0,"character":1},"end":{"line":0,"character":4}}}}"#
+            )
+        );
+    }
+
+    #[test]
+    fn document_symbol_selection_range() {
+        let mut json = Json::new();
+        let symbol = r#"{"name":"f","kind":12,"range":{"start":{"line":1,"character":0},"end":{"line":4,"character":1}},"selectionRange":{"start":{"line":1,"character":3},"end":{"line":1,"character":4}}}"#;
+        let value = json.read(&mut symbol.as_bytes()).unwrap();
+        let symbol = DocumentSymbolInformation::from_json(value, &json)
+            .ok()
+            .unwrap();
+        assert_eq!(1, symbol.range.start.line);
+        assert_eq!(3, symbol.range.start.character);
+        assert_eq!(4, symbol.range.end.character);
+
+        assert_eq!("function", SymbolKind(12).to_string());
+        assert_eq!("27", SymbolKind(27).to_string());
+    }
+
+    #[test]
+    fn pending_request_cancellation() {
+        let mut requests = PendingRequestColection::default();
+        requests.add(RequestId(1), "textDocument/definition");
+        requests.add(RequestId(2), "textDocument/hover");
+
+        assert!(requests.cancel(RequestId(1)));
+        assert!(!requests.cancel(RequestId(1)));
+        assert!(!requests.cancel(RequestId(3)));
+
+        assert_eq!(None, requests.take(RequestId(1)));
+        assert_eq!(Some("textDocument/hover"), requests.take(RequestId(2)));
+        assert_eq!(None, requests.take(RequestId(2)));
+    }
+}
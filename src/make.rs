@@ -0,0 +1,433 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    buffer::{BufferCapabilities, BufferHandle},
+    buffer_position::{BufferPosition, BufferRange},
+    client::{ClientHandle, ClientManager},
+    cursor::Cursor,
+    editor::Editor,
+    editor_utils::MessageKind,
+    glob::{Glob, InvalidGlobError},
+    navigation_history::NavigationHistory,
+    platform::{Platform, PlatformRequest, ProcessTag},
+};
+
+pub const MAKE_BUFFER_PATH: &str = "make.output";
+
+pub const DEFAULT_ERROR_FORMATS: &[&str] = &["%f:%l:%c: %m", "%f:%l: %m", "%f(%l,%c): %m"];
+
+struct ErrorFormat {
+    glob: Glob,
+    format: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ErrorFormatMatch<'a> {
+    pub path: &'a str,
+    pub position: BufferPosition,
+    pub message: Option<&'a str>,
+}
+
+fn next_literal(format: &str) -> &str {
+    match format.find('%') {
+        Some(i) => &format[..i],
+        None => format,
+    }
+}
+
+pub fn match_error_format<'a>(format: &str, line: &'a str) -> Option<ErrorFormatMatch<'a>> {
+    let mut format = format.trim_start();
+    let mut line = line.trim_start();
+
+    let mut path = None;
+    let mut line_number = None;
+    let mut column_number = None;
+    let mut message = None;
+
+    while !format.is_empty() {
+        let rest = match format.strip_prefix('%') {
+            Some(rest) => rest,
+            None => {
+                let literal = next_literal(format);
+                line = line.strip_prefix(literal)?;
+                format = &format[literal.len()..];
+                continue;
+            }
+        };
+
+        let mut chars = rest.chars();
+        let placeholder = chars.next()?;
+        format = chars.as_str();
+        match placeholder {
+            '%' => line = line.strip_prefix('%')?,
+            'l' | 'c' => {
+                let len = line
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(line.len());
+                let number: u32 = line[..len].parse().ok()?;
+                if placeholder == 'l' {
+                    line_number = Some(number);
+                } else {
+                    column_number = Some(number);
+                }
+                line = &line[len..];
+            }
+            'f' | 'm' => {
+                let literal = next_literal(format);
+                let len = if literal.is_empty() {
+                    line.len()
+                } else {
+                    let skip = if placeholder == 'f' {
+                        2.min(line.len())
+                    } else {
+                        0
+                    };
+                    skip + line.get(skip..)?.find(literal)?
+                };
+                let text = &line[..len];
+                if placeholder == 'f' {
+                    if text.trim().is_empty() {
+                        return None;
+                    }
+                    path = Some(text);
+                } else {
+                    message = Some(text.trim());
+                }
+                line = &line[len..];
+            }
+            _ => return None,
+        }
+    }
+
+    let line_index = line_number?.saturating_sub(1);
+    let column_index = column_number.unwrap_or(1).saturating_sub(1);
+    Some(ErrorFormatMatch {
+        path: path?,
+        position: BufferPosition::line_col(line_index as _, column_index as _),
+        message,
+    })
+}
+
+pub struct MakeResult {
+    pub path: PathBuf,
+    pub position: BufferPosition,
+    pub message: String,
+}
+
+#[derive(Default)]
+pub struct Make {
+    error_formats: Vec<ErrorFormat>,
+    active_formats: Vec<String>,
+    waiting_for_process: bool,
+    client_handle: Option<ClientHandle>,
+    buffer_handle: Option<BufferHandle>,
+    root: PathBuf,
+    output: Vec<u8>,
+    previous_line: String,
+    results: Vec<MakeResult>,
+    current_result: Option<usize>,
+}
+
+impl Make {
+    pub fn is_running(&self) -> bool {
+        self.waiting_for_process
+    }
+
+    pub fn results(&self) -> &[MakeResult] {
+        &self.results
+    }
+
+    pub fn add_error_format(&mut self, glob: &str, format: &str) -> Result<(), InvalidGlobError> {
+        let mut error_glob = Glob::default();
+        error_glob.compile(glob)?;
+        self.error_formats.push(ErrorFormat {
+            glob: error_glob,
+            format: format.into(),
+        });
+        Ok(())
+    }
+
+    pub fn on_buffer_close(&mut self, handle: BufferHandle) {
+        if self.buffer_handle == Some(handle) {
+            self.buffer_handle = None;
+        }
+    }
+
+    pub fn spawn(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        client_handle: Option<ClientHandle>,
+        current_buffer_handle: Option<BufferHandle>,
+        command: &str,
+    ) {
+        let root = editor.workspace_root(current_buffer_handle);
+        let glob_path = match current_buffer_handle {
+            Some(handle) => editor.buffers.get(handle).path.to_str().unwrap_or(""),
+            None => "",
+        };
+
+        let make = &mut editor.make;
+        make.active_formats.clear();
+        for error_format in &make.error_formats {
+            if error_format.glob.matches(glob_path) {
+                make.active_formats.push(error_format.format.clone());
+            }
+        }
+        for &format in DEFAULT_ERROR_FORMATS {
+            make.active_formats.push(format.into());
+        }
+
+        let buffer_handle = match editor
+            .buffers
+            .find_with_path(&editor.current_directory, Path::new(MAKE_BUFFER_PATH))
+        {
+            Some(handle) => handle,
+            None => {
+                let buffer = editor.buffers.add_new();
+                buffer.path.push(MAKE_BUFFER_PATH);
+                buffer.handle()
+            }
+        };
+        let buffer = editor.buffers.get_mut(buffer_handle);
+        buffer.capabilities = BufferCapabilities::log();
+        let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+        buffer.delete_range(&mut editor.word_database, range, &mut editor.events);
+
+        let mut process = shell_command(command);
+        process.current_dir(&root);
+        process.stdin(Stdio::null());
+        process.stdout(Stdio::piped());
+        process.stderr(Stdio::null());
+
+        let make = &mut editor.make;
+        make.waiting_for_process = true;
+        make.client_handle = client_handle;
+        make.buffer_handle = Some(buffer_handle);
+        make.root = root;
+        make.output.clear();
+        make.previous_line.clear();
+        make.results.clear();
+        make.current_result = None;
+
+        platform.requests.enqueue(PlatformRequest::SpawnProcess {
+            tag: ProcessTag::Make,
+            command: process,
+            buf_len: 4 * 1024,
+        });
+    }
+
+    pub fn on_process_output(editor: &mut Editor, bytes: &[u8]) {
+        editor.make.output.extend_from_slice(bytes);
+        let len = match editor.make.output.iter().rposition(|&b| b == b'\n') {
+            Some(i) => i + 1,
+            None => return,
+        };
+        Self::flush_output(editor, len);
+    }
+
+    pub fn on_process_exit(editor: &mut Editor, clients: &mut ClientManager) {
+        Self::flush_output(editor, editor.make.output.len());
+        editor.make.waiting_for_process = false;
+
+        let count = editor.make.results.len();
+        if count == 0 {
+            editor
+                .status_bar
+                .write(MessageKind::Info)
+                .str("make finished without errors");
+            return;
+        }
+
+        match editor.make.client_handle {
+            Some(client_handle) => Self::jump_to_result(editor, clients, client_handle, 0),
+            None => editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("make finished with {} errors", count)),
+        }
+    }
+
+    fn flush_output(editor: &mut Editor, len: usize) {
+        let output = std::mem::take(&mut editor.make.output);
+        let text = String::from_utf8_lossy(&output[..len]);
+
+        if let Some(buffer_handle) = editor.make.buffer_handle {
+            let buffer = editor.buffers.get_mut(buffer_handle);
+            let position = buffer.content().end();
+            buffer.insert_text(
+                &mut editor.word_database,
+                position,
+                &text,
+                &mut editor.events,
+            );
+        }
+
+        let make = &mut editor.make;
+        for line in text.lines() {
+            let result = make
+                .active_formats
+                .iter()
+                .find_map(|f| match_error_format(f, line));
+            match result {
+                Some(result) => {
+                    let message = result.message.unwrap_or(&make.previous_line);
+                    let result = MakeResult {
+                        path: make.root.join(result.path.trim()),
+                        position: result.position,
+                        message: message.into(),
+                    };
+                    make.results.push(result);
+                }
+                None => {
+                    make.previous_line.clear();
+                    make.previous_line.push_str(line.trim());
+                }
+            }
+        }
+
+        editor.make.output = output;
+        editor.make.output.drain(..len);
+    }
+
+    pub fn jump_to_result(
+        editor: &mut Editor,
+        clients: &mut ClientManager,
+        client_handle: ClientHandle,
+        index: usize,
+    ) {
+        let count = editor.make.results.len();
+        let result = match editor.make.results.get(index) {
+            Some(result) => result,
+            None => return,
+        };
+        editor.make.current_result = Some(index);
+
+        let path = result
+            .path
+            .strip_prefix(&editor.current_directory)
+            .unwrap_or(&result.path)
+            .to_path_buf();
+        let position = result.position;
+
+        NavigationHistory::save_snapshot(clients.get_mut(client_handle), &editor.buffer_views);
+        match editor.buffer_view_handle_from_path(client_handle, &path, BufferCapabilities::text())
+        {
+            Ok(buffer_view_handle) => {
+                let client = clients.get_mut(client_handle);
+                client.set_buffer_view_handle(
+                    Some(buffer_view_handle),
+                    &editor.buffer_views,
+                    &mut editor.events,
+                );
+
+                let buffer_view = editor.buffer_views.get_mut(buffer_view_handle);
+                let position = editor
+                    .buffers
+                    .get(buffer_view.buffer_handle)
+                    .content()
+                    .position_from_char_column(position);
+                let mut cursors = buffer_view.cursors.mut_guard();
+                cursors.clear();
+                cursors.add(Cursor {
+                    anchor: position,
+                    position,
+                });
+
+                let result = &editor.make.results[index];
+                editor.status_bar.write(MessageKind::Info).fmt(format_args!(
+                    "({}/{}) {}",
+                    index + 1,
+                    count,
+                    result.message
+                ));
+            }
+            Err(error) => editor
+                .status_bar
+                .write(MessageKind::Error)
+                .fmt(format_args!("{}", error)),
+        }
+    }
+
+    pub fn next_result_index(&self, forward: bool) -> Option<usize> {
+        let count = self.results.len();
+        if count == 0 {
+            return None;
+        }
+        let index = match (self.current_result, forward) {
+            (None, _) => 0,
+            (Some(index), true) => (index + 1) % count,
+            (Some(index), false) => (index + count - 1) % count,
+        };
+        Some(index)
+    }
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut process = Command::new("cmd");
+    process.arg("/C");
+    process.arg(format!("{} 2>&1", command));
+    process
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut process = Command::new("sh");
+    process.arg("-c");
+    process.arg(format!("{} 2>&1", command));
+    process
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error<'a>(
+        path: &'a str,
+        line: u32,
+        column: u32,
+        message: Option<&'a str>,
+    ) -> ErrorFormatMatch<'a> {
+        ErrorFormatMatch {
+            path,
+            position: BufferPosition::line_col(line as _, column as _),
+            message,
+        }
+    }
+
+    #[test]
+    fn error_format_matching() {
+        let format = "%f:%l:%c: %m";
+        assert_eq!(None, match_error_format(format, ""));
+        assert_eq!(None, match_error_format(format, "warning: unused variable"));
+        assert_eq!(None, match_error_format(format, "src/main.c:x:3: error"));
+        assert_eq!(
+            Some(error("src/main.c", 11, 2, Some("error: expected ';'"))),
+            match_error_format(format, "src/main.c:12:3: error: expected ';'")
+        );
+        assert_eq!(
+            Some(error("c:/code/main.c", 0, 0, Some("note"))),
+            match_error_format(format, "c:/code/main.c:1:1: note")
+        );
+
+        assert_eq!(
+            Some(error("src/lib.rs", 4, 8, None)),
+            match_error_format("--> %f:%l:%c", "   --> src/lib.rs:5:9")
+        );
+        assert_eq!(
+            Some(error("main.cs", 9, 4, Some("error CS1002"))),
+            match_error_format("%f(%l,%c): %m", "main.cs(10,5): error CS1002")
+        );
+        assert_eq!(
+            Some(error("a.py", 2, 0, Some("100% wrong"))),
+            match_error_format("%f:%l: %m", "a.py:3: 100% wrong")
+        );
+        assert_eq!(
+            Some(error("a.txt", 6, 0, Some("x"))),
+            match_error_format("%%%f %l %m", "%a.txt 7 x")
+        );
+    }
+}
@@ -1,13 +1,17 @@
 use crate::{
     client::{ClientHandle, ClientManager},
     editor::{Editor, EditorControlFlow, KeysIterator},
+    events::EditorEvent,
     platform::Platform,
 };
 
 mod command;
+mod debug;
+mod file_tree;
 mod insert;
 mod normal;
 pub mod picker;
+pub mod plugin;
 pub mod read_line;
 
 pub struct ModeContext<'a> {
@@ -30,6 +34,39 @@ pub enum ModeKind {
     Command,
     ReadLine,
     Picker,
+    Plugin,
+    FileTree,
+    Debug,
+}
+
+impl ModeKind {
+    pub const ALL: [Self; 8] = [
+        Self::Normal,
+        Self::Insert,
+        Self::Command,
+        Self::ReadLine,
+        Self::Picker,
+        Self::Plugin,
+        Self::FileTree,
+        Self::Debug,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Insert => "insert",
+            Self::Command => "command",
+            Self::ReadLine => "read-line",
+            Self::Picker => "picker",
+            Self::Plugin => "plugin",
+            Self::FileTree => "file-tree",
+            Self::Debug => "debug",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|k| k.name() == name)
+    }
 }
 
 impl Default for ModeKind {
@@ -47,6 +84,9 @@ pub struct Mode {
     pub command_state: command::State,
     pub read_line_state: read_line::State,
     pub picker_state: picker::State,
+    pub plugin_state: plugin::State,
+    pub file_tree_state: file_tree::State,
+    pub debug_state: debug::State,
 }
 
 impl Mode {
@@ -59,15 +99,24 @@ impl Mode {
             return;
         }
 
-        match ctx.editor.mode.kind {
+        let previous = ctx.editor.mode.kind;
+        match previous {
             ModeKind::Normal => normal::State::on_exit(ctx),
             ModeKind::Insert => insert::State::on_exit(ctx),
             ModeKind::Command => command::State::on_exit(ctx),
             ModeKind::ReadLine => read_line::State::on_exit(ctx),
             ModeKind::Picker => picker::State::on_exit(ctx),
+            ModeKind::Plugin => plugin::State::on_exit(ctx),
+            ModeKind::FileTree => file_tree::State::on_exit(ctx),
+            ModeKind::Debug => debug::State::on_exit(ctx),
         }
 
         ctx.editor.mode.kind = next;
+        ctx.editor.events.enqueue(EditorEvent::ModeChange {
+            kind: next,
+            previous,
+            client: ctx.client_handle,
+        });
 
         match ctx.editor.mode.kind {
             ModeKind::Normal => normal::State::on_enter(ctx),
@@ -75,6 +124,9 @@ impl Mode {
             ModeKind::Command => command::State::on_enter(ctx),
             ModeKind::ReadLine => read_line::State::on_enter(ctx),
             ModeKind::Picker => picker::State::on_enter(ctx),
+            ModeKind::Plugin => plugin::State::on_enter(ctx),
+            ModeKind::FileTree => file_tree::State::on_enter(ctx),
+            ModeKind::Debug => debug::State::on_enter(ctx),
         }
     }
 
@@ -88,6 +140,9 @@ impl Mode {
             ModeKind::Command => command::State::on_client_keys(ctx, keys),
             ModeKind::ReadLine => read_line::State::on_client_keys(ctx, keys),
             ModeKind::Picker => picker::State::on_client_keys(ctx, keys),
+            ModeKind::Plugin => plugin::State::on_client_keys(ctx, keys),
+            ModeKind::FileTree => file_tree::State::on_client_keys(ctx, keys),
+            ModeKind::Debug => debug::State::on_client_keys(ctx, keys),
         }
     }
 }
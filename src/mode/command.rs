@@ -4,6 +4,7 @@ use crate::{
     command::{CommandManager, CommandTokenizer, CompletionSource},
     editor::{EditorControlFlow, KeysIterator},
     editor_utils::{hash_bytes, ReadLinePoll},
+    help::{self, CommandHelp},
     mode::{Mode, ModeContext, ModeKind, ModeState},
     picker::Picker,
     platform::Key,
@@ -20,6 +21,12 @@ pub struct State {
     completion_index: usize,
     completion_source: CompletionSource,
     completion_path_hash: Option<u64>,
+    help: Option<CommandHelp>,
+}
+impl State {
+    pub fn help(&self) -> Option<CommandHelp> {
+        self.help
+    }
 }
 
 impl Default for State {
@@ -29,6 +36,7 @@ impl Default for State {
             completion_index: 0,
             completion_source: CompletionSource::Custom(&[]),
             completion_path_hash: None,
+            help: None,
         }
     }
 }
@@ -40,6 +48,7 @@ impl ModeState for State {
         state.completion_index = 0;
         state.completion_source = CompletionSource::Custom(&[]);
         state.completion_path_hash = None;
+        state.help = None;
 
         ctx.editor.read_line.set_prompt(":");
         ctx.editor.read_line.input_mut().clear();
@@ -138,6 +147,7 @@ fn update_autocomplete_entries(ctx: &mut ModeContext) {
             ctx.editor.picker.clear();
             state.completion_index = input.len();
             state.completion_source = CompletionSource::Custom(&[]);
+            state.help = None;
             if input.trim().is_empty() {
                 state.read_state =
                     ReadCommandState::NavigatingHistory(ctx.editor.commands.history_len());
@@ -147,6 +157,12 @@ fn update_autocomplete_entries(ctx: &mut ModeContext) {
     };
     let mut command_name = last_token.trim_end_matches('!');
 
+    let help_command_name = match ctx.editor.commands.aliases.find(command_name) {
+        Some(aliased) => CommandTokenizer(aliased).next().unwrap_or(""),
+        None => command_name,
+    };
+    state.help = help::command_help(help_command_name);
+
     if let ReadCommandState::NavigatingHistory(_) = state.read_state {
         state.read_state = ReadCommandState::TypingCommand;
     }
@@ -205,6 +221,9 @@ fn update_autocomplete_entries(ctx: &mut ModeContext) {
                 for command in ctx.editor.commands.builtin_commands() {
                     ctx.editor.picker.add_custom_entry(command.name);
                 }
+                for macro_command in ctx.editor.commands.macros.iter() {
+                    ctx.editor.picker.add_custom_entry(&macro_command.name);
+                }
             }
             CompletionSource::Buffers => {
                 for buffer in ctx.editor.buffers.iter() {
@@ -0,0 +1,104 @@
+use crate::{
+    buffer_view::{CursorMovement, CursorMovementKind},
+    dap::{Debugger, DebuggerError, StepKind},
+    editor::{EditorControlFlow, KeysIterator},
+    editor_utils::MessageKind,
+    mode::{Mode, ModeContext, ModeKind, ModeState},
+    platform::Key,
+};
+
+#[derive(Default)]
+pub struct State;
+
+impl ModeState for State {
+    fn on_enter(_: &mut ModeContext) {}
+
+    fn on_exit(_: &mut ModeContext) {}
+
+    fn on_client_keys(ctx: &mut ModeContext, keys: &mut KeysIterator) -> Option<EditorControlFlow> {
+        let result = match keys.next(&ctx.editor.buffered_keys) {
+            Key::Char('n') => Debugger::step(ctx.editor, ctx.platform, StepKind::Next),
+            Key::Char('s') | Key::Char('i') => {
+                Debugger::step(ctx.editor, ctx.platform, StepKind::StepIn)
+            }
+            Key::Char('o') => Debugger::step(ctx.editor, ctx.platform, StepKind::StepOut),
+            Key::Char('c') => Debugger::step(ctx.editor, ctx.platform, StepKind::Continue),
+            Key::Char('u') => select_frame(ctx, true),
+            Key::Char('d') => select_frame(ctx, false),
+            Key::Char('b') => {
+                toggle_breakpoint(ctx);
+                Ok(())
+            }
+            Key::Char('j') | Key::Down => {
+                move_cursors(ctx, CursorMovement::LinesForward(1));
+                Ok(())
+            }
+            Key::Char('k') | Key::Up => {
+                move_cursors(ctx, CursorMovement::LinesBackward(1));
+                Ok(())
+            }
+            Key::Char('x') => Debugger::stop(ctx.editor, ctx.platform),
+            Key::Char(':') => {
+                Mode::change_to(ctx, ModeKind::Command);
+                Ok(())
+            }
+            Key::Char('q') | Key::Esc => {
+                Mode::change_to(ctx, ModeKind::default());
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+
+        if let Err(error) = result {
+            report_error(ctx, error);
+        }
+        Some(EditorControlFlow::Continue)
+    }
+}
+
+fn report_error(ctx: &mut ModeContext, error: DebuggerError) {
+    ctx.editor
+        .status_bar
+        .write(MessageKind::Error)
+        .fmt(format_args!("{}", error));
+}
+
+fn select_frame(ctx: &mut ModeContext, up: bool) -> Result<(), DebuggerError> {
+    let current = match ctx.editor.debugger.current_frame() {
+        Some(index) => index,
+        None => return Err(DebuggerError::NotStopped),
+    };
+    let index = if up {
+        current + 1
+    } else {
+        match current.checked_sub(1) {
+            Some(index) => index,
+            None => return Err(DebuggerError::NoSuchFrame),
+        }
+    };
+    Debugger::select_frame(ctx.editor, ctx.platform, ctx.clients, index)
+}
+
+fn toggle_breakpoint(ctx: &mut ModeContext) {
+    let handle = match ctx.clients.get(ctx.client_handle).buffer_view_handle() {
+        Some(handle) => handle,
+        None => return,
+    };
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer_handle = buffer_view.buffer_handle;
+    let line_index = buffer_view.cursors.main_cursor().position.line_index;
+    Debugger::toggle_breakpoint(ctx.editor, ctx.platform, buffer_handle, line_index as _);
+}
+
+fn move_cursors(ctx: &mut ModeContext, movement: CursorMovement) {
+    let handle = match ctx.clients.get(ctx.client_handle).buffer_view_handle() {
+        Some(handle) => handle,
+        None => return,
+    };
+    ctx.editor.buffer_views.get_mut(handle).move_cursors(
+        &ctx.editor.buffers,
+        movement,
+        CursorMovementKind::PositionAndAnchor,
+        ctx.editor.config.tab_size,
+    );
+}
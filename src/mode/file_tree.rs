@@ -0,0 +1,80 @@
+use crate::{
+    buffer::BufferCapabilities,
+    editor::{EditorControlFlow, KeysIterator},
+    editor_utils::MessageKind,
+    mode::{read_line, Mode, ModeContext, ModeKind, ModeState},
+    platform::Key,
+};
+
+#[derive(Default)]
+pub struct State;
+
+impl ModeState for State {
+    fn on_enter(ctx: &mut ModeContext) {
+        let root = ctx.editor.current_directory.clone();
+        ctx.editor.file_tree.show(&root);
+    }
+
+    fn on_exit(_: &mut ModeContext) {}
+
+    fn on_client_keys(ctx: &mut ModeContext, keys: &mut KeysIterator) -> Option<EditorControlFlow> {
+        let tree = &mut ctx.editor.file_tree;
+        match keys.next(&ctx.editor.buffered_keys) {
+            Key::Char('j') | Key::Down => tree.move_selection(1),
+            Key::Char('k') | Key::Up => tree.move_selection(-1),
+            Key::Char('g') | Key::Home => tree.move_selection(-(tree.selected_index() as isize)),
+            Key::Char('G') | Key::End => tree.move_selection(tree.entries().len() as _),
+            Key::Char('l') | Key::Right => tree.expand(tree.selected_index()),
+            Key::Char('h') | Key::Left => match tree.selected_entry() {
+                Some(entry) if entry.expanded => tree.collapse(tree.selected_index()),
+                _ => tree.select_parent(),
+            },
+            Key::Char('R') => tree.refresh(),
+            Key::Enter => open_selected(ctx),
+            Key::Char('a') => read_line::file_tree::enter_create_mode(ctx),
+            Key::Char('r') => read_line::file_tree::enter_rename_mode(ctx),
+            Key::Char('d') => read_line::file_tree::enter_delete_mode(ctx),
+            Key::Char(':') => Mode::change_to(ctx, ModeKind::Command),
+            Key::Char('q') | Key::Esc => Mode::change_to(ctx, ModeKind::default()),
+            _ => (),
+        }
+
+        Some(EditorControlFlow::Continue)
+    }
+}
+
+fn open_selected(ctx: &mut ModeContext) {
+    let tree = &mut ctx.editor.file_tree;
+    let path = match tree.selected_entry() {
+        Some(entry) if entry.is_dir => {
+            tree.toggle_expanded(tree.selected_index());
+            return;
+        }
+        Some(entry) => entry.path.clone(),
+        None => return,
+    };
+    let path = path
+        .strip_prefix(&ctx.editor.current_directory)
+        .unwrap_or(&path);
+
+    match ctx.editor.buffer_view_handle_from_path(
+        ctx.client_handle,
+        path,
+        BufferCapabilities::text(),
+    ) {
+        Ok(handle) => {
+            let client = ctx.clients.get_mut(ctx.client_handle);
+            client.set_buffer_view_handle(
+                Some(handle),
+                &ctx.editor.buffer_views,
+                &mut ctx.editor.events,
+            );
+            Mode::change_to(ctx, ModeKind::default());
+        }
+        Err(error) => ctx
+            .editor
+            .status_bar
+            .write(MessageKind::Error)
+            .fmt(format_args!("{}", error)),
+    }
+}
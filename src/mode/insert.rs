@@ -1,20 +1,59 @@
-use std::{fmt::Write, path::Path};
+use std::{
+    fmt::Write,
+    fs,
+    path::{self, Path, PathBuf},
+    time::Duration,
+};
 
 use crate::{
-    buffer_position::BufferPosition,
+    buffer_position::{BufferPosition, BufferPositionIndex},
     buffer_view::{BufferViewHandle, CursorMovement, CursorMovementKind},
+    config::{CompletionTrigger, UndoGrouping},
     editor::{Editor, EditorControlFlow, KeysIterator},
+    editor_utils::MessageKind,
     lsp,
     mode::{Mode, ModeContext, ModeKind, ModeState},
+    picker::EntrySource,
     platform::Key,
     register::AUTO_MACRO_REGISTER,
     word_database::{WordIndicesIter, WordKind},
 };
 
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum CompletionSource {
+    #[default]
+    Auto,
+    Words,
+    Lsp,
+    Paths,
+}
+impl CompletionSource {
+    fn next(self) -> Self {
+        match self {
+            Self::Auto => Self::Words,
+            Self::Words => Self::Lsp,
+            Self::Lsp => Self::Paths,
+            Self::Paths => Self::Auto,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Words => "words",
+            Self::Lsp => "lsp",
+            Self::Paths => "paths",
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct State {
     lsp_client_handle: Option<lsp::ClientHandle>,
     completion_positions: Vec<BufferPosition>,
+    completion_source: CompletionSource,
+    completion_requested: bool,
+    path_completion_dir: Option<PathBuf>,
 }
 
 impl State {
@@ -44,6 +83,7 @@ impl State {
 impl ModeState for State {
     fn on_enter(ctx: &mut ModeContext) {
         cancel_completion(ctx.editor);
+        ctx.editor.mode.insert_state.completion_source = CompletionSource::Auto;
     }
 
     fn on_exit(ctx: &mut ModeContext) {
@@ -63,13 +103,21 @@ impl ModeState for State {
         let register = ctx.editor.registers.get_mut(AUTO_MACRO_REGISTER);
         let _ = write!(register, "{}", key);
 
+        if let UndoGrouping::AfterPauseMillis(millis) = ctx.editor.config.undo_grouping {
+            let buffer_handle = ctx.editor.buffer_views.get(handle).buffer_handle;
+            ctx.editor
+                .buffers
+                .get_mut(buffer_handle)
+                .commit_edits_after_pause(Duration::from_millis(millis as _));
+        }
+
         match key {
             Key::Esc | Key::Ctrl('c') => {
                 let buffer_view = ctx.editor.buffer_views.get(handle);
-                ctx.editor
-                    .buffers
-                    .get_mut(buffer_view.buffer_handle)
-                    .commit_edits();
+                let position = buffer_view.cursors.main_cursor().position;
+                let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+                buffer.commit_edits();
+                buffer.change_list.set_last_insert_position(position);
                 Mode::change_to(ctx, ModeKind::default());
                 return Some(EditorControlFlow::Continue);
             }
@@ -115,10 +163,12 @@ impl ModeState for State {
             }
             Key::Tab => {
                 static SPACES_BUF: &[u8; u8::MAX as usize] = &[b' '; u8::MAX as usize];
-                let text = if ctx.editor.config.indent_with_tabs {
+                let buffer_handle = ctx.editor.buffer_views.get(handle).buffer_handle;
+                let buffer = ctx.editor.buffers.get(buffer_handle);
+                let text = if buffer.indent_with_tabs(&ctx.editor.config) {
                     "\t"
                 } else {
-                    let len = ctx.editor.config.tab_size.get() as usize;
+                    let len = buffer.tab_size(&ctx.editor.config).get() as usize;
                     unsafe { std::str::from_utf8_unchecked(&SPACES_BUF[..len]) }
                 };
 
@@ -215,12 +265,32 @@ impl ModeState for State {
                     &mut ctx.editor.events,
                 );
             }
-            Key::Ctrl('n') => {
-                apply_completion(ctx, handle, 1);
-                return Some(EditorControlFlow::Continue);
+            Key::Ctrl('f') => {
+                cancel_completion(ctx.editor);
+                let state = &mut ctx.editor.mode.insert_state;
+                state.completion_source = CompletionSource::Paths;
+                state.completion_requested = true;
+            }
+            Key::Ctrl('o') => {
+                cancel_completion(ctx.editor);
+                let state = &mut ctx.editor.mode.insert_state;
+                state.completion_source = state.completion_source.next();
+                state.completion_requested = true;
+                let source_name = state.completion_source.name();
+                ctx.editor
+                    .status_bar
+                    .write(MessageKind::Info)
+                    .fmt(format_args!("completion source: {}", source_name));
             }
-            Key::Ctrl('p') => {
-                apply_completion(ctx, handle, -1);
+            Key::Ctrl('n') | Key::Ctrl('p') => {
+                let cursor_movement = if key == Key::Ctrl('n') { 1 } else { -1 };
+                let state = &mut ctx.editor.mode.insert_state;
+                if state.completion_positions.is_empty() {
+                    state.completion_requested = true;
+                    update_completions(ctx, handle);
+                } else {
+                    apply_completion(ctx, handle, cursor_movement);
+                }
                 return Some(EditorControlFlow::Continue);
             }
             _ => return Some(EditorControlFlow::Continue),
@@ -234,10 +304,130 @@ impl ModeState for State {
 
 fn cancel_completion(editor: &mut Editor) {
     editor.picker.clear();
-    editor.mode.insert_state.completion_positions.clear();
+    let state = &mut editor.mode.insert_state;
+    state.completion_positions.clear();
+    state.completion_requested = false;
+    state.path_completion_dir = None;
+}
+
+// returns the path-like text at the end of `line` split at its last path separator
+fn split_path_before(line: &str) -> (&str, &str) {
+    let start = match line.char_indices().rev().find(|&(_, c)| {
+        c.is_whitespace()
+            || matches!(
+                c,
+                '"' | '\'' | '`' | '(' | ')' | '[' | ']' | '{' | '}' | '<' | '>' | ',' | ';' | '='
+            )
+    }) {
+        Some((i, c)) => i + c.len_utf8(),
+        None => 0,
+    };
+    let path = &line[start..];
+    match path.rfind(path::is_separator) {
+        Some(i) => path.split_at(i + 1),
+        None => ("", path),
+    }
+}
+
+fn update_path_completions(
+    ctx: &mut ModeContext,
+    buffer_view_handle: BufferViewHandle,
+    requested: bool,
+) -> bool {
+    let state = &mut ctx.editor.mode.insert_state;
+    if state.path_completion_dir.is_none()
+        && !requested
+        && ctx.editor.config.completion_trigger == CompletionTrigger::Manual
+    {
+        return false;
+    }
+
+    let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+    let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
+    let content = buffer.content();
+
+    let line_prefix = |position: BufferPosition| {
+        let line = content.line_at(position.line_index as _).as_str();
+        &line[..position.column_byte_index as usize]
+    };
+
+    let main_cursor_position = buffer_view.cursors.main_cursor().position;
+    let (dir, file) = split_path_before(line_prefix(main_cursor_position));
+    if dir.is_empty() && state.completion_source != CompletionSource::Paths {
+        return false;
+    }
+
+    let dir = Path::new(dir);
+    let dir = if dir.is_absolute() {
+        dir.into()
+    } else {
+        let buffer_path = ctx.editor.current_directory.join(&buffer.path);
+        match buffer_path.parent().map(|p| p.join(dir)) {
+            Some(buffer_dir) if buffer_dir.is_dir() => buffer_dir,
+            _ => ctx
+                .editor
+                .workspace
+                .root_for(&ctx.editor.current_directory, &buffer.path)
+                .join(dir),
+        }
+    };
+    if !dir.is_dir() {
+        return false;
+    }
+
+    if state.path_completion_dir.as_ref() != Some(&dir) {
+        ctx.editor.picker.clear();
+        if let Ok(entries) = fs::read_dir(&dir) {
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let name = match name.to_str() {
+                    Some(name) => name,
+                    None => continue,
+                };
+                match entry.file_type() {
+                    Ok(file_type) if file_type.is_dir() => ctx
+                        .editor
+                        .picker
+                        .add_custom_entry_fmt(format_args!("{}/", name)),
+                    _ => ctx.editor.picker.add_custom_entry(name),
+                }
+            }
+        }
+        state.path_completion_dir = Some(dir);
+    }
+
+    state.completion_positions.clear();
+    for cursor in &buffer_view.cursors[..] {
+        let (_, file) = split_path_before(line_prefix(cursor.position));
+        let mut position = cursor.position;
+        position.column_byte_index -= file.len() as BufferPositionIndex;
+        state.completion_positions.push(position);
+    }
+
+    ctx.editor.picker.filter(WordIndicesIter::empty(), file);
+    if ctx.editor.picker.cursor().is_none() {
+        ctx.editor.picker.move_cursor(0);
+    }
+    true
 }
 
 fn update_completions(ctx: &mut ModeContext, buffer_view_handle: BufferViewHandle) {
+    let state = &mut ctx.editor.mode.insert_state;
+    let requested = std::mem::take(&mut state.completion_requested);
+    let source = state.completion_source;
+
+    if matches!(source, CompletionSource::Auto | CompletionSource::Paths)
+        && update_path_completions(ctx, buffer_view_handle, requested)
+    {
+        return;
+    }
+    if ctx.editor.mode.insert_state.path_completion_dir.is_some() {
+        cancel_completion(ctx.editor);
+    }
+    if source == CompletionSource::Paths {
+        return;
+    }
+
     let state = &mut ctx.editor.mode.insert_state;
     let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
     let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
@@ -246,10 +436,22 @@ fn update_completions(ctx: &mut ModeContext, buffer_view_handle: BufferViewHandl
     let main_cursor_position = buffer_view.cursors.main_cursor().position;
     let word = content.word_at(content.position_before(main_cursor_position));
 
-    let lsp_client_handle = state.get_lsp_client_handle(&ctx.editor.lsp, &buffer.path);
+    let lsp_client_handle = match source {
+        CompletionSource::Words => None,
+        _ => state.get_lsp_client_handle(&ctx.editor.lsp, &buffer.path),
+    };
+    if source == CompletionSource::Lsp && lsp_client_handle.is_none() {
+        return cancel_completion(ctx.editor);
+    }
 
     let mut force_trigger_completion = false;
     if let Some(last_char) = word.text.chars().next_back() {
+        let syntax = ctx.editor.syntaxes.get(buffer.syntax_handle());
+        if word.kind != WordKind::Identifier && syntax.completion_triggers().contains(last_char) {
+            force_trigger_completion = true;
+            state.completion_positions.clear();
+        }
+
         let lsp = &ctx.editor.lsp;
         if let Some(client) = lsp_client_handle.and_then(|h| lsp.get(h)) {
             if client.signature_help_triggers().contains(last_char) {
@@ -279,10 +481,12 @@ fn update_completions(ctx: &mut ModeContext, buffer_view_handle: BufferViewHandl
             position
         }
         None => {
-            if !force_trigger_completion
-                && (word.kind != WordKind::Identifier
-                    || word.text.len() < ctx.editor.config.completion_min_len as _)
-            {
+            let should_trigger = requested
+                || (ctx.editor.config.completion_trigger == CompletionTrigger::Auto
+                    && (force_trigger_completion
+                        || (word.kind == WordKind::Identifier
+                            && word.text.len() >= ctx.editor.config.completion_min_len as _)));
+            if !should_trigger {
                 return cancel_completion(ctx.editor);
             }
 
@@ -322,19 +526,20 @@ fn update_completions(ctx: &mut ModeContext, buffer_view_handle: BufferViewHandl
         return cancel_completion(ctx.editor);
     }
 
-    match ctx.editor.mode.insert_state.lsp_client_handle {
-        Some(_) => ctx
-            .editor
-            .picker
-            .filter(WordIndicesIter::empty(), word.text),
+    let filter = match word.kind {
+        WordKind::Identifier => word.text,
+        _ => "",
+    };
+    match lsp_client_handle {
+        Some(_) => ctx.editor.picker.filter(WordIndicesIter::empty(), filter),
         None => {
             ctx.editor
                 .picker
-                .filter(ctx.editor.word_database.word_indices(), word.text);
+                .filter(ctx.editor.word_database.word_indices(), filter);
             if ctx.editor.picker.cursor().is_none() {
                 ctx.editor.picker.move_cursor(0);
             }
-            if ctx.editor.picker.len() == 1 {
+            if !filter.is_empty() && ctx.editor.picker.len() == 1 {
                 ctx.editor.picker.clear();
             }
         }
@@ -349,8 +554,8 @@ fn apply_completion(
     let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
 
     ctx.editor.picker.move_cursor(cursor_movement);
-    let entry = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
-        Some((_, entry)) => entry,
+    let (source, entry) = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
+        Some(entry) => entry,
         None => {
             let buffer_handle = buffer_view.buffer_handle;
             let buffer = ctx.editor.buffers.get(buffer_handle);
@@ -383,13 +588,55 @@ fn apply_completion(
         }
     };
 
-    let completion = ctx.editor.string_pool.acquire_with(entry);
+    let mut completion = ctx.editor.string_pool.acquire_with(entry);
+    let mut main_range = None;
+    let state = &ctx.editor.mode.insert_state;
+    if let (EntrySource::Custom(index), Some(lsp_client_handle), None) =
+        (source, state.lsp_client_handle, &state.path_completion_dir)
+    {
+        let platform = &mut *ctx.platform;
+        let buffer_handle = buffer_view.buffer_handle;
+        let cursor_position = buffer_view.cursors.main_cursor().position;
+        lsp::ClientManager::access(ctx.editor, lsp_client_handle, |e, c| {
+            let mut text = e.string_pool.acquire();
+            main_range = c.completion_item(
+                e,
+                platform,
+                buffer_handle,
+                cursor_position,
+                index,
+                &mut text,
+            );
+            if !text.is_empty() {
+                std::mem::swap(&mut completion, &mut text);
+            }
+            e.string_pool.release(text);
+        });
+    }
+
+    let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
     buffer_view.apply_completion(
         &mut ctx.editor.buffers,
         &mut ctx.editor.word_database,
         &completion,
         &ctx.editor.mode.insert_state.completion_positions,
+        main_range,
         &mut ctx.editor.events,
     );
     ctx.editor.string_pool.release(completion);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_before_cursor() {
+        assert_eq!(("", ""), split_path_before(""));
+        assert_eq!(("", "word"), split_path_before("some word"));
+        assert_eq!(("src/", ""), split_path_before("src/"));
+        assert_eq!(("src/mode/", "ins"), split_path_before("open src/mode/ins"));
+        assert_eq!(("/usr/", "bin"), split_path_before("x = \"/usr/bin"));
+        assert_eq!(("./", "a"), split_path_before("include(./a"));
+    }
+}
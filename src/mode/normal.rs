@@ -2,13 +2,14 @@ use std::{cmp::Ordering, fmt::Write, path::Path};
 
 use crate::{
     buffer::{
-        find_path_and_position_at, parse_path_and_position, BufferCapabilities, BufferContent,
+        find_path_and_position_at, parse_path_and_position, reindent_text, BufferCapabilities,
+        BufferContent,
     },
     buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
     buffer_view::{BufferViewHandle, CursorMovement, CursorMovementKind},
     cursor::{Cursor, CursorCollection},
     editor::{Editor, EditorControlFlow, KeysIterator},
-    editor_utils::{hash_bytes, MessageKind},
+    editor_utils::{hash_bytes, MessageKind, BUFFER_LIST_PATH},
     help::HELP_PREFIX,
     lsp,
     mode::{picker, read_line, Mode, ModeContext, ModeKind, ModeState},
@@ -90,18 +91,26 @@ impl State {
                 state.movement_kind,
                 ctx.editor.config.tab_size,
             ),
-            Key::Char('j') => ctx.editor.buffer_views.get_mut(handle).move_cursors(
-                &ctx.editor.buffers,
-                CursorMovement::LinesForward(state.count.max(1) as _),
-                state.movement_kind,
-                ctx.editor.config.tab_size,
-            ),
-            Key::Char('k') => ctx.editor.buffer_views.get_mut(handle).move_cursors(
-                &ctx.editor.buffers,
-                CursorMovement::LinesBackward(state.count.max(1) as _),
-                state.movement_kind,
-                ctx.editor.config.tab_size,
-            ),
+            Key::Char('j') => {
+                let movement = vertical_movement(ctx, handle, true);
+                let state = &ctx.editor.mode.normal_state;
+                ctx.editor.buffer_views.get_mut(handle).move_cursors(
+                    &ctx.editor.buffers,
+                    movement,
+                    state.movement_kind,
+                    ctx.editor.config.tab_size,
+                );
+            }
+            Key::Char('k') => {
+                let movement = vertical_movement(ctx, handle, false);
+                let state = &ctx.editor.mode.normal_state;
+                ctx.editor.buffer_views.get_mut(handle).move_cursors(
+                    &ctx.editor.buffers,
+                    movement,
+                    state.movement_kind,
+                    ctx.editor.config.tab_size,
+                );
+            }
             Key::Char('l') => ctx.editor.buffer_views.get_mut(handle).move_cursors(
                 &ctx.editor.buffers,
                 CursorMovement::ColumnsForward(state.count.max(1) as _),
@@ -438,121 +447,29 @@ impl State {
                             }
                         }
                     }
-                    Key::Char('f') => {
-                        let buffer_handle = buffer_view.buffer_handle;
-
-                        let mut len = 0;
-                        let mut ranges = [BufferRange::zero(); CursorCollection::capacity()];
-                        for cursor in &buffer_view.cursors[..] {
-                            ranges[len] = cursor.to_range();
-                            len += 1;
-                        }
-
-                        let mut jumped = false;
-                        let mut path_buf = ctx.editor.string_pool.acquire();
-                        let mut error_buf = ctx.editor.string_pool.acquire();
-                        let fallback_line_index = state.count.saturating_sub(1) as _;
-
-                        for range in &ranges[..len] {
-                            let line_index = range.from.line_index;
-                            if range.to.line_index != line_index {
-                                continue;
-                            }
-
-                            let buffer = ctx.editor.buffers.get(buffer_handle);
-                            let line = buffer.content().line_at(line_index as _).as_str();
-
-                            let from = range.from.column_byte_index;
-                            let to = range.to.column_byte_index;
-
-                            let (path, position) = if from < to {
-                                parse_path_and_position(&line[from as usize..to as usize])
-                            } else {
-                                find_path_and_position_at(line, from as _)
-                            };
-                            let position = match position {
-                                Some(position) => position,
-                                None => BufferPosition::line_col(fallback_line_index, 0),
-                            };
-
-                            path_buf.clear();
-                            if Path::new(path).is_relative() {
-                                if buffer.path.starts_with(HELP_PREFIX) {
-                                    path_buf.push_str(HELP_PREFIX);
-                                } else if let Some(parent) =
-                                    buffer.path.parent().and_then(Path::to_str)
-                                {
-                                    if !parent.is_empty() {
-                                        path_buf.push_str(parent);
-                                        path_buf.push('/');
-                                    }
-                                }
-                            }
-                            path_buf.push_str(path);
-
-                            let path = Path::new(&path_buf);
-                            if !path.starts_with(HELP_PREFIX) && !path.is_file() {
-                                ctx.editor
-                                    .status_bar
-                                    .write(MessageKind::Error)
-                                    .fmt(format_args!("file {:?} does not exist", path));
-                                continue;
-                            }
-
-                            match ctx.editor.buffer_view_handle_from_path(
-                                ctx.client_handle,
-                                path,
-                                BufferCapabilities::text(),
-                            ) {
-                                Ok(buffer_view_handle) => {
-                                    if jumped {
-                                        continue;
-                                    }
-                                    jumped = true;
-
-                                    ctx.editor.mode.normal_state.movement_kind =
-                                        CursorMovementKind::PositionAndAnchor;
-                                    let client = ctx.clients.get_mut(ctx.client_handle);
-                                    client.set_buffer_view_handle(
-                                        Some(buffer_view_handle),
-                                        &ctx.editor.buffer_views,
-                                        &mut ctx.editor.events,
-                                    );
-
-                                    let buffer_view =
-                                        ctx.editor.buffer_views.get_mut(buffer_view_handle);
-                                    let position = ctx
-                                        .editor
-                                        .buffers
-                                        .get(buffer_view.buffer_handle)
-                                        .content()
-                                        .saturate_position(position);
-                                    let mut cursors = buffer_view.cursors.mut_guard();
-                                    cursors.clear();
-                                    cursors.add(Cursor {
-                                        anchor: position,
-                                        position,
-                                    });
-                                }
-                                Err(error) => {
-                                    if !error_buf.is_empty() {
-                                        error_buf.push('\n');
-                                    }
-                                    let _ = write!(error_buf, "{}", error);
-                                }
-                            }
-                        }
-
-                        if !error_buf.is_empty() {
-                            ctx.editor
-                                .status_bar
-                                .write(MessageKind::Error)
-                                .str(&error_buf);
+                    Key::Char(c @ (';' | ',' | '.')) => {
+                        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+                        let position = match c {
+                            ';' => buffer.change_list.older(),
+                            ',' => buffer.change_list.newer(),
+                            _ => buffer.change_list.last_insert_position(),
+                        };
+                        if let Some(position) = position {
+                            let position = buffer.content().saturate_position(position);
+                            NavigationHistory::save_snapshot(
+                                ctx.clients.get_mut(ctx.client_handle),
+                                &ctx.editor.buffer_views,
+                            );
+                            let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+                            let mut cursors = buffer_view.cursors.mut_guard();
+                            cursors.clear();
+                            cursors.add(Cursor {
+                                anchor: position,
+                                position,
+                            });
                         }
-
-                        ctx.editor.string_pool.release(path_buf);
-                        ctx.editor.string_pool.release(error_buf);
                     }
+                    Key::Char('f') => goto_file(ctx, handle),
                     _ => (),
                 }
             }
@@ -574,6 +491,10 @@ impl State {
                     }
                     _ => (),
                 },
+                Key::Char(c @ ('p' | 'P')) => {
+                    paste_lines_from_clipboard(ctx, handle, false, c == 'p');
+                    return Some(EditorControlFlow::Continue);
+                }
                 _ => (),
             },
             Key::Char(']') => match keys.next(&ctx.editor.buffered_keys) {
@@ -594,6 +515,10 @@ impl State {
                     }
                     _ => (),
                 },
+                Key::Char(c @ ('p' | 'P')) => {
+                    paste_lines_from_clipboard(ctx, handle, true, c == 'p');
+                    return Some(EditorControlFlow::Continue);
+                }
                 _ => (),
             },
             Key::Char('{') => {
@@ -775,6 +700,12 @@ impl State {
             }
             Key::Char('d') => {
                 let buffer_view = ctx.editor.buffer_views.get(handle);
+                let mut text = ctx.editor.string_pool.acquire();
+                let mut text_ranges = [(0, 0); CursorCollection::capacity()];
+                buffer_view.append_selection_text(&ctx.editor.buffers, &mut text, &mut text_ranges);
+                ctx.editor.yank_history.push(&text);
+                ctx.editor.string_pool.release(text);
+
                 buffer_view.delete_text_in_cursor_ranges(
                     &mut ctx.editor.buffers,
                     &mut ctx.editor.word_database,
@@ -819,7 +750,9 @@ impl State {
                                 Some((i, c @ '\t')) => i + c.len_utf8(),
                                 Some((i, c @ ' ')) => {
                                     match chars
-                                        .take(ctx.editor.config.tab_size.get() as usize - 1)
+                                        .take(
+                                            buffer.tab_size(&ctx.editor.config).get() as usize - 1,
+                                        )
                                         .take_while(|(_, c)| *c == ' ')
                                         .last()
                                     {
@@ -847,13 +780,15 @@ impl State {
                 return Some(EditorControlFlow::Continue);
             }
             Key::Char('>') => {
-                let cursor_count = ctx.editor.buffer_views.get(handle).cursors[..].len();
+                let buffer_view = ctx.editor.buffer_views.get(handle);
+                let cursor_count = buffer_view.cursors[..].len();
+                let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
 
-                let extender = if ctx.editor.config.indent_with_tabs {
+                let extender = if buffer.indent_with_tabs(&ctx.editor.config) {
                     let count = state.count.max(1) as _;
                     std::iter::repeat('\t').take(count)
                 } else {
-                    let tab_size = ctx.editor.config.tab_size.get() as usize;
+                    let tab_size = buffer.tab_size(&ctx.editor.config).get() as usize;
                     let count = state.count.max(1) as usize * tab_size;
                     std::iter::repeat(' ').take(count)
                 };
@@ -1050,6 +985,7 @@ impl State {
                 _ => (),
             },
             Key::Char('s') => read_line::search::enter_mode(ctx),
+            Key::Enter => focus_listed_buffer(ctx, handle),
             Key::Char('m') => match keys.next(&ctx.editor.buffered_keys) {
                 Key::None => return None,
                 Key::Char(c) => {
@@ -1062,7 +998,7 @@ impl State {
                         if let Some(path) = buffer.path.to_str() {
                             let position = buffer_view.cursors.main_cursor().position;
                             let line = position.line_index + 1;
-                            let column = position.column_byte_index + 1;
+                            let column = buffer.content().char_column(position) + 1;
                             let _ = write!(register, "{}:{},{}", path, line, column);
                         }
 
@@ -1096,8 +1032,14 @@ impl State {
                                 );
 
                                 if let Some(position) = position {
-                                    let mut cursors =
-                                        ctx.editor.buffer_views.get_mut(handle).cursors.mut_guard();
+                                    let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+                                    let position = ctx
+                                        .editor
+                                        .buffers
+                                        .get(buffer_view.buffer_handle)
+                                        .content()
+                                        .position_from_char_column(position);
+                                    let mut cursors = buffer_view.cursors.mut_guard();
                                     cursors.clear();
                                     cursors.add(Cursor {
                                         anchor: position,
@@ -1228,11 +1170,14 @@ impl ModeState for State {
             let buffer_view = ctx.editor.buffer_views.get(handle);
             let main_position = buffer_view.cursors.main_cursor().position;
 
-            for client in ctx.editor.lsp.clients() {
-                let diagnostics = client
-                    .diagnostics()
-                    .buffer_diagnostics(buffer_view.buffer_handle);
-
+            let buffer_handle = buffer_view.buffer_handle;
+            let lsp_diagnostics = ctx
+                .editor
+                .lsp
+                .clients()
+                .map(|c| c.diagnostics().buffer_diagnostics(buffer_handle));
+            let lint_diagnostics = ctx.editor.linters.buffer_diagnostics(buffer_handle);
+            for diagnostics in lsp_diagnostics.chain(std::iter::once(lint_diagnostics)) {
                 if let Ok(index) = diagnostics.binary_search_by(|d| {
                     let range = d.range;
                     if range.to < main_position {
@@ -1400,6 +1345,33 @@ impl ModeState for State {
     }
 }
 
+fn vertical_movement(
+    ctx: &ModeContext,
+    buffer_view_handle: BufferViewHandle,
+    forward: bool,
+) -> CursorMovement {
+    let count = ctx.editor.mode.normal_state.count.max(1) as usize;
+    let buffer_handle = ctx
+        .editor
+        .buffer_views
+        .get(buffer_view_handle)
+        .buffer_handle;
+    let client = ctx.clients.get(ctx.client_handle);
+    let soft_wrap = ctx
+        .editor
+        .buffers
+        .get(buffer_handle)
+        .soft_wrap(client.display_config(ctx.editor));
+    let width = client.buffer_view_width(ctx.editor) as usize;
+
+    match (forward, soft_wrap && width > 0) {
+        (true, false) => CursorMovement::LinesForward(count),
+        (false, false) => CursorMovement::LinesBackward(count),
+        (true, true) => CursorMovement::RowsForward { count, width },
+        (false, true) => CursorMovement::RowsBackward { count, width },
+    }
+}
+
 fn copy_text(ctx: &mut ModeContext, buffer_view_handle: BufferViewHandle, text: &mut String) {
     let state = &mut ctx.editor.mode.normal_state;
     let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
@@ -1407,6 +1379,7 @@ fn copy_text(ctx: &mut ModeContext, buffer_view_handle: BufferViewHandle, text:
     let text_ranges_len =
         buffer_view.append_selection_text(&ctx.editor.buffers, text, &mut text_ranges);
     if !text.is_empty() {
+        ctx.editor.yank_history.push(text);
         state.last_copy_hash = hash_bytes(text.as_bytes());
         state.last_copy_ranges.clear();
         state
@@ -1416,7 +1389,7 @@ fn copy_text(ctx: &mut ModeContext, buffer_view_handle: BufferViewHandle, text:
     state.movement_kind = CursorMovementKind::PositionAndAnchor;
 }
 
-fn paste_text(ctx: &mut ModeContext, buffer_view_handle: BufferViewHandle, text: &str) {
+pub fn paste_text(ctx: &mut ModeContext, buffer_view_handle: BufferViewHandle, text: &str) {
     let state = &mut ctx.editor.mode.normal_state;
     let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
     buffer_view.delete_text_in_cursor_ranges(
@@ -1461,6 +1434,97 @@ fn paste_text(ctx: &mut ModeContext, buffer_view_handle: BufferViewHandle, text:
         .commit_edits();
 }
 
+fn paste_lines_from_clipboard(
+    ctx: &mut ModeContext,
+    buffer_view_handle: BufferViewHandle,
+    after: bool,
+    reindent: bool,
+) {
+    let mut text = ctx.editor.string_pool.acquire();
+    ctx.platform.read_from_clipboard(&mut text);
+    let lines = text.strip_suffix('\n').unwrap_or(&text);
+    if lines.is_empty() {
+        ctx.editor.string_pool.release(text);
+        return;
+    }
+    let pasted_line_count = lines.split('\n').count() as BufferPositionIndex;
+
+    let state = &mut ctx.editor.mode.normal_state;
+    state.movement_kind = CursorMovementKind::PositionAndAnchor;
+    state.is_recording_auto_macro = false;
+
+    let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+    let mut line_indexes: Vec<_> = buffer_view.cursors[..]
+        .iter()
+        .map(|c| {
+            let range = c.to_range();
+            if after {
+                range.to.line_index
+            } else {
+                range.from.line_index
+            }
+        })
+        .collect();
+    line_indexes.dedup();
+
+    let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+    let tab_size = buffer.tab_size(&ctx.editor.config);
+    let indent_with_tabs = buffer.indent_with_tabs(&ctx.editor.config);
+
+    let mut pasted = ctx.editor.string_pool.acquire();
+    for &line_index in line_indexes.iter().rev() {
+        let line = buffer.content().line_at(line_index as _).as_str();
+        let indentation = &line[..line.len() - line.trim_start().len()];
+
+        pasted.clear();
+        if after {
+            pasted.push('\n');
+        }
+        if reindent {
+            reindent_text(lines, indentation, tab_size, indent_with_tabs, &mut pasted);
+        } else {
+            pasted.push_str(lines);
+        }
+        if !after {
+            pasted.push('\n');
+        }
+
+        let position = if after {
+            BufferPosition::line_col(line_index, line.len() as _)
+        } else {
+            BufferPosition::line_col(line_index, 0)
+        };
+        buffer.insert_text(
+            &mut ctx.editor.word_database,
+            position,
+            &pasted,
+            &mut ctx.editor.events,
+        );
+    }
+    buffer.commit_edits();
+    ctx.editor.string_pool.release(pasted);
+    ctx.editor.string_pool.release(text);
+
+    ctx.editor.trigger_event_handlers(ctx.platform, ctx.clients);
+
+    let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+    let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle).content();
+    let mut cursors = buffer_view.cursors.mut_guard();
+    cursors.clear();
+    for (i, &line_index) in line_indexes.iter().enumerate() {
+        let line_index = line_index
+            + i as BufferPositionIndex * pasted_line_count
+            + after as BufferPositionIndex;
+        let line = buffer.line_at(line_index as _).as_str();
+        let position =
+            BufferPosition::line_col(line_index, (line.len() - line.trim_start().len()) as _);
+        cursors.add(Cursor {
+            anchor: position,
+            position,
+        });
+    }
+}
+
 fn find_char(ctx: &mut ModeContext, forward: bool) {
     let state = &ctx.editor.mode.normal_state;
     let skip;
@@ -1708,7 +1772,8 @@ fn move_to_diagnostic(ctx: &mut ModeContext, forward: bool) {
         ctx.editor
             .lsp
             .clients()
-            .flat_map(|c| c.diagnostics().iter()),
+            .flat_map(|c| c.diagnostics().iter())
+            .chain(ctx.editor.linters.iter()),
         forward,
     );
     let mut next_diagnostic = None;
@@ -1758,7 +1823,8 @@ fn move_to_diagnostic(ctx: &mut ModeContext, forward: bool) {
             ctx.editor
                 .lsp
                 .clients()
-                .flat_map(|c| c.diagnostics().iter()),
+                .flat_map(|c| c.diagnostics().iter())
+                .chain(ctx.editor.linters.iter()),
             forward,
         );
         next_diagnostic = iter
@@ -1819,3 +1885,182 @@ fn move_to_diagnostic(ctx: &mut ModeContext, forward: bool) {
     });
 }
 
+fn focus_listed_buffer(ctx: &mut ModeContext, buffer_view_handle: BufferViewHandle) {
+    let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+    let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
+    if buffer.path != Path::new(BUFFER_LIST_PATH) {
+        return;
+    }
+
+    let line_index = buffer_view.cursors.main_cursor().position.line_index;
+    let line = buffer.content().line_at(line_index as _).as_str();
+    let buffer_handle = match line.split_whitespace().next().and_then(|h| h.parse().ok()) {
+        Some(index) => match ctx.editor.buffers.iter().find(|b| b.handle().0 == index) {
+            Some(buffer) => buffer.handle(),
+            None => {
+                ctx.editor
+                    .status_bar
+                    .write(MessageKind::Error)
+                    .fmt(format_args!("buffer {} is no longer open", index));
+                return;
+            }
+        },
+        None => return,
+    };
+
+    let buffer_view_handle = ctx
+        .editor
+        .buffer_views
+        .buffer_view_handle_from_buffer_handle(ctx.client_handle, buffer_handle);
+    ctx.clients
+        .get_mut(ctx.client_handle)
+        .set_buffer_view_handle(
+            Some(buffer_view_handle),
+            &ctx.editor.buffer_views,
+            &mut ctx.editor.events,
+        );
+}
+
+fn goto_file(ctx: &mut ModeContext, buffer_view_handle: BufferViewHandle) {
+    let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+    let buffer_handle = buffer_view.buffer_handle;
+
+    let mut len = 0;
+    let mut ranges = [BufferRange::zero(); CursorCollection::capacity()];
+    for cursor in &buffer_view.cursors[..] {
+        ranges[len] = cursor.to_range();
+        len += 1;
+    }
+
+    let workspace_root = ctx.editor.workspace_root(Some(buffer_handle));
+    let count = ctx.editor.mode.normal_state.count;
+    let fallback_position = BufferPosition::line_col(count.saturating_sub(1) as _, 0);
+
+    let mut jumped = false;
+    let mut candidates = Vec::new();
+    let mut error_buf = ctx.editor.string_pool.acquire();
+    ctx.editor.picker.clear();
+
+    for range in &ranges[..len] {
+        let line_index = range.from.line_index;
+        if range.to.line_index != line_index {
+            continue;
+        }
+
+        let buffer = ctx.editor.buffers.get(buffer_handle);
+        let line = buffer.content().line_at(line_index as _).as_str();
+
+        let from = range.from.column_byte_index;
+        let to = range.to.column_byte_index;
+
+        let (path, position) = if from < to {
+            parse_path_and_position(&line[from as usize..to as usize])
+        } else {
+            find_path_and_position_at(line, from as _)
+        };
+        let position = position.unwrap_or(fallback_position);
+
+        candidates.clear();
+        let current_directory = &ctx.editor.current_directory;
+        if buffer.path.starts_with(HELP_PREFIX) && Path::new(path).is_relative() {
+            let mut help_path = HELP_PREFIX.to_string();
+            help_path.push_str(path);
+            candidates.push(help_path.into());
+        } else {
+            let buffer_directory = buffer.path.parent().unwrap_or(Path::new(""));
+            for directory in [
+                current_directory.join(buffer_directory),
+                workspace_root.clone(),
+                current_directory.clone(),
+            ] {
+                let candidate = directory.join(path);
+                if candidate.is_file() && !candidates.contains(&candidate) {
+                    candidates.push(candidate);
+                }
+            }
+        }
+
+        match &candidates[..] {
+            [] => {
+                if !error_buf.is_empty() {
+                    error_buf.push('\n');
+                }
+                let _ = write!(error_buf, "file {:?} does not exist", path);
+            }
+            [candidate] => {
+                let path = candidate
+                    .strip_prefix(current_directory)
+                    .unwrap_or(candidate)
+                    .to_path_buf();
+                match ctx.editor.buffer_view_handle_from_path(
+                    ctx.client_handle,
+                    &path,
+                    BufferCapabilities::text(),
+                ) {
+                    Ok(buffer_view_handle) => {
+                        if jumped {
+                            continue;
+                        }
+                        jumped = true;
+
+                        ctx.editor.mode.normal_state.movement_kind =
+                            CursorMovementKind::PositionAndAnchor;
+                        let client = ctx.clients.get_mut(ctx.client_handle);
+                        client.set_buffer_view_handle(
+                            Some(buffer_view_handle),
+                            &ctx.editor.buffer_views,
+                            &mut ctx.editor.events,
+                        );
+
+                        let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+                        let position = ctx
+                            .editor
+                            .buffers
+                            .get(buffer_view.buffer_handle)
+                            .content()
+                            .position_from_char_column(position);
+                        let mut cursors = buffer_view.cursors.mut_guard();
+                        cursors.clear();
+                        cursors.add(Cursor {
+                            anchor: position,
+                            position,
+                        });
+                    }
+                    Err(error) => {
+                        if !error_buf.is_empty() {
+                            error_buf.push('\n');
+                        }
+                        let _ = write!(error_buf, "{}", error);
+                    }
+                }
+            }
+            candidates => {
+                for candidate in candidates {
+                    let path = candidate
+                        .strip_prefix(current_directory)
+                        .unwrap_or(candidate);
+                    if let Some(path) = path.to_str() {
+                        ctx.editor.picker.add_custom_entry_fmt(format_args!(
+                            "{}:{},{}",
+                            path,
+                            position.line_index + 1,
+                            position.column_byte_index + 1,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    if !error_buf.is_empty() {
+        ctx.editor
+            .status_bar
+            .write(MessageKind::Error)
+            .str(&error_buf);
+    }
+    ctx.editor.string_pool.release(error_buf);
+
+    if !jumped {
+        picker::goto_file::enter_mode(ctx);
+    }
+}
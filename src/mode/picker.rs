@@ -1,4 +1,7 @@
-use std::{path::Path, process::Stdio};
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+};
 
 use crate::{
     buffer::{parse_path_and_position, BufferCapabilities},
@@ -6,10 +9,14 @@ use crate::{
     cursor::Cursor,
     editor::{EditorControlFlow, KeysIterator},
     editor_utils::{parse_process_command, MessageKind, ReadLine, ReadLinePoll},
+    git,
+    glob::IgnoreList,
+    json::JsonInteger,
     lsp,
     mode::{Mode, ModeContext, ModeKind, ModeState},
     picker::{EntrySource, Picker},
-    platform::{Key, PlatformRequest, ProcessTag},
+    platform::{CancellationSource, CancellationToken, JobTag, Key, PlatformRequest, ProcessTag},
+    plugin::remote::{RemotePluginCollection, RemotePluginHandle},
     word_database::WordIndicesIter,
 };
 
@@ -18,12 +25,29 @@ pub struct State {
         fn(ctx: &mut ModeContext, &mut KeysIterator, ReadLinePoll) -> Option<EditorControlFlow>,
     find_file_waiting_for_process: bool,
     find_file_buf: Vec<u8>,
+    find_file_ignore: IgnoreList,
+    find_file_root: PathBuf,
+    cancellation: CancellationSource,
     lsp_client_handle: Option<lsp::ClientHandle>,
+    remote_plugin_request: Option<(RemotePluginHandle, JsonInteger)>,
 }
 
 impl State {
-    pub fn on_process_output(&mut self, picker: &mut Picker, read_line: &ReadLine, bytes: &[u8]) {
-        if !self.find_file_waiting_for_process {
+    fn begin_request(&mut self) -> CancellationToken {
+        self.cancellation.cancel();
+        self.find_file_waiting_for_process = true;
+        self.find_file_buf.clear();
+        self.cancellation.token()
+    }
+
+    pub fn on_process_output(
+        &mut self,
+        picker: &mut Picker,
+        read_line: &ReadLine,
+        token: CancellationToken,
+        bytes: &[u8],
+    ) {
+        if !self.find_file_waiting_for_process || self.cancellation.is_cancelled(token) {
             return;
         }
 
@@ -42,7 +66,9 @@ impl State {
                         continue;
                     }
                     if let Ok(line) = std::str::from_utf8(line) {
-                        filtered_entry_adder.add(line);
+                        if !self.find_file_ignore.is_ignored(line, false) {
+                            filtered_entry_adder.add(line);
+                        }
                     }
                 }
             }
@@ -51,8 +77,24 @@ impl State {
         picker.move_cursor(0);
     }
 
-    pub fn on_process_exit(&mut self, picker: &mut Picker, read_line: &ReadLine) {
-        if !self.find_file_waiting_for_process {
+    pub fn on_job_finished(
+        &mut self,
+        picker: &mut Picker,
+        read_line: &ReadLine,
+        token: CancellationToken,
+        bytes: &[u8],
+    ) {
+        self.on_process_output(picker, read_line, token, bytes);
+        self.on_process_exit(picker, read_line, token);
+    }
+
+    pub fn on_process_exit(
+        &mut self,
+        picker: &mut Picker,
+        read_line: &ReadLine,
+        token: CancellationToken,
+    ) {
+        if !self.find_file_waiting_for_process || self.cancellation.is_cancelled(token) {
             return;
         }
 
@@ -65,7 +107,9 @@ impl State {
                     continue;
                 }
                 if let Ok(line) = std::str::from_utf8(line) {
-                    filtered_entry_adder.add(line);
+                    if !self.find_file_ignore.is_ignored(line, false) {
+                        filtered_entry_adder.add(line);
+                    }
                 }
             }
         }
@@ -81,7 +125,11 @@ impl Default for State {
             on_client_keys: |_, _, _| Some(EditorControlFlow::Continue),
             find_file_waiting_for_process: false,
             find_file_buf: Vec::new(),
+            find_file_ignore: IgnoreList::default(),
+            find_file_root: PathBuf::new(),
+            cancellation: CancellationSource::default(),
             lsp_client_handle: None,
+            remote_plugin_request: None,
         }
     }
 }
@@ -93,6 +141,18 @@ impl ModeState for State {
 
     fn on_exit(ctx: &mut ModeContext) {
         ctx.editor.mode.picker_state.find_file_waiting_for_process = false;
+        ctx.editor.mode.picker_state.cancellation.cancel();
+        if let Some((handle, request_id)) =
+            ctx.editor.mode.picker_state.remote_plugin_request.take()
+        {
+            RemotePluginCollection::respond_pick(
+                ctx.editor,
+                ctx.platform,
+                handle,
+                request_id,
+                None,
+            );
+        }
         ctx.editor.read_line.input_mut().clear();
         ctx.editor.picker.clear();
     }
@@ -150,6 +210,55 @@ impl ModeState for State {
     }
 }
 
+fn open_current_entry_at_position(ctx: &mut ModeContext) {
+    let entry = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
+        Some((_, entry)) => entry,
+        None => return,
+    };
+
+    let (path, position) = parse_path_and_position(entry);
+    let position = match position {
+        Some(position) => position,
+        None => BufferPosition::zero(),
+    };
+
+    let path = ctx.editor.string_pool.acquire_with(path);
+    match ctx.editor.buffer_view_handle_from_path(
+        ctx.client_handle,
+        Path::new(&path),
+        BufferCapabilities::text(),
+    ) {
+        Ok(buffer_view_handle) => {
+            let client = ctx.clients.get_mut(ctx.client_handle);
+            client.set_buffer_view_handle(
+                Some(buffer_view_handle),
+                &ctx.editor.buffer_views,
+                &mut ctx.editor.events,
+            );
+
+            let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+            let position = ctx
+                .editor
+                .buffers
+                .get(buffer_view.buffer_handle)
+                .content()
+                .position_from_char_column(position);
+            let mut cursors = buffer_view.cursors.mut_guard();
+            cursors.clear();
+            cursors.add(Cursor {
+                anchor: position,
+                position,
+            });
+        }
+        Err(error) => ctx
+            .editor
+            .status_bar
+            .write(MessageKind::Error)
+            .fmt(format_args!("{}", error)),
+    }
+    ctx.editor.string_pool.release(path);
+}
+
 pub mod opened_buffers {
     use super::*;
 
@@ -222,8 +331,6 @@ pub mod opened_buffers {
 pub mod find_file {
     use super::*;
 
-    use std::path::Path;
-
     pub fn enter_mode(ctx: &mut ModeContext, command: &str) {
         fn on_client_keys(
             ctx: &mut ModeContext,
@@ -247,10 +354,10 @@ pub mod find_file {
                 }
             };
 
-            let path = ctx.editor.string_pool.acquire_with(path);
+            let path = ctx.editor.mode.picker_state.find_file_root.join(path);
             match ctx.editor.buffer_view_handle_from_path(
                 ctx.client_handle,
-                Path::new(&path),
+                &path,
                 BufferCapabilities::text(),
             ) {
                 Ok(buffer_view_handle) => {
@@ -267,7 +374,6 @@ pub mod find_file {
                     .write(MessageKind::Error)
                     .fmt(format_args!("{}", error)),
             }
-            ctx.editor.string_pool.release(path);
 
             Mode::change_to(ctx, ModeKind::default());
             Some(EditorControlFlow::Continue)
@@ -276,7 +382,22 @@ pub mod find_file {
         ctx.editor.read_line.set_prompt("open:");
         ctx.editor.picker.clear();
 
-        let command = match parse_process_command(command) {
+        let ignore = &mut ctx.editor.mode.picker_state.find_file_ignore;
+        ignore.clear();
+        for pattern in ctx.editor.config.find_file_ignore.split_whitespace() {
+            if ignore.add(pattern).is_err() {
+                ctx.editor
+                    .status_bar
+                    .write(MessageKind::Error)
+                    .fmt(format_args!(
+                        "invalid find file ignore pattern '{}'",
+                        pattern
+                    ));
+                return;
+            }
+        }
+
+        let mut command = match parse_process_command(command) {
             Some(mut command) => {
                 command.stdin(Stdio::null());
                 command.stdout(Stdio::piped());
@@ -293,11 +414,20 @@ pub mod find_file {
             }
         };
 
-        ctx.editor.mode.picker_state.find_file_waiting_for_process = true;
+        let buffer_handle = ctx
+            .clients
+            .get(ctx.client_handle)
+            .buffer_view_handle()
+            .map(|h| ctx.editor.buffer_views.get(h).buffer_handle);
+        let root = ctx.editor.workspace_root(buffer_handle);
+        command.current_dir(&root);
+        ctx.editor.mode.picker_state.find_file_root = root;
+
+        let token = ctx.editor.mode.picker_state.begin_request();
         ctx.platform
             .requests
             .enqueue(PlatformRequest::SpawnProcess {
-                tag: ProcessTag::FindFiles,
+                tag: ProcessTag::FindFiles(token),
                 command,
                 buf_len: 4 * 1024,
             });
@@ -307,27 +437,65 @@ pub mod find_file {
     }
 }
 
-pub mod lsp_definition {
+pub mod git_status {
     use super::*;
 
-    pub fn enter_mode(ctx: &mut ModeContext, client_handle: lsp::ClientHandle) {
+    use std::{path::Path, process::Command};
+
+    pub fn enter_mode(ctx: &mut ModeContext) {
         fn on_client_keys(
             ctx: &mut ModeContext,
-            _: &mut KeysIterator,
+            keys: &mut KeysIterator,
             poll: ReadLinePoll,
         ) -> Option<EditorControlFlow> {
             match poll {
-                ReadLinePoll::Pending => Some(EditorControlFlow::Continue),
+                ReadLinePoll::Pending => {
+                    keys.index = keys.index.saturating_sub(1);
+                    let key = keys.next(&ctx.editor.buffered_keys);
+                    let entry = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
+                        Some((_, entry)) => entry,
+                        None => return Some(EditorControlFlow::Continue),
+                    };
+                    let args: &[&str] = match (key, git::is_untracked_status(entry)) {
+                        (Key::Ctrl('s'), _) => &["add", "--"],
+                        (Key::Ctrl('r'), false) => &["restore", "--staged", "--"],
+                        (Key::Ctrl('x'), false) => &["restore", "--"],
+                        (Key::Ctrl('x'), true) => &["clean", "-f", "--"],
+                        _ => return Some(EditorControlFlow::Continue),
+                    };
+                    let path = match git::status_entry_path(entry) {
+                        Some(path) => ctx.editor.string_pool.acquire_with(path),
+                        None => return Some(EditorControlFlow::Continue),
+                    };
+
+                    let mut command_args = Vec::with_capacity(args.len() + 1);
+                    command_args.extend_from_slice(args);
+                    command_args.push(&path[..]);
+                    let mut output = ctx.editor.string_pool.acquire();
+                    if let Err(error) = git::run(
+                        &ctx.editor.current_directory,
+                        &command_args,
+                        None,
+                        &mut output,
+                    ) {
+                        ctx.editor
+                            .status_bar
+                            .write(MessageKind::Error)
+                            .fmt(format_args!("{}", error));
+                    }
+                    ctx.editor.string_pool.release(output);
+                    ctx.editor.string_pool.release(path);
+
+                    refresh(ctx);
+                    Some(EditorControlFlow::Continue)
+                }
                 ReadLinePoll::Submitted => {
-                    if let Some((_, entry)) =
-                        ctx.editor.picker.current_entry(&ctx.editor.word_database)
+                    if let Some(path) = ctx
+                        .editor
+                        .picker
+                        .current_entry(&ctx.editor.word_database)
+                        .and_then(|(_, entry)| git::status_entry_path(entry))
                     {
-                        let (path, position) = parse_path_and_position(entry);
-                        let position = match position {
-                            Some(position) => position,
-                            None => BufferPosition::zero(),
-                        };
-
                         let path = ctx.editor.string_pool.acquire_with(path);
                         match ctx.editor.buffer_view_handle_from_path(
                             ctx.client_handle,
@@ -341,18 +509,6 @@ pub mod lsp_definition {
                                     &ctx.editor.buffer_views,
                                     &mut ctx.editor.events,
                                 );
-
-                                let mut cursors = ctx
-                                    .editor
-                                    .buffer_views
-                                    .get_mut(buffer_view_handle)
-                                    .cursors
-                                    .mut_guard();
-                                cursors.clear();
-                                cursors.add(Cursor {
-                                    anchor: position,
-                                    position,
-                                });
                             }
                             Err(error) => ctx
                                 .editor
@@ -372,6 +528,233 @@ pub mod lsp_definition {
             }
         }
 
+        ctx.editor.read_line.set_prompt("git status:");
+        ctx.editor.mode.picker_state.on_client_keys = on_client_keys;
+        Mode::change_to(ctx, ModeKind::Picker);
+        refresh(ctx);
+    }
+
+    fn refresh(ctx: &mut ModeContext) {
+        ctx.editor.picker.clear();
+
+        let mut command = Command::new("git");
+        command.current_dir(&ctx.editor.current_directory);
+        command.args(["status", "--porcelain", "--untracked-files=all"]);
+        command.stdin(Stdio::null());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::null());
+
+        let token = ctx.editor.mode.picker_state.begin_request();
+        ctx.platform
+            .requests
+            .enqueue(PlatformRequest::SpawnProcess {
+                tag: ProcessTag::FindFiles(token),
+                command,
+                buf_len: 4 * 1024,
+            });
+    }
+}
+
+pub mod goto_file {
+    use super::*;
+
+    pub fn enter_mode(ctx: &mut ModeContext) {
+        fn on_client_keys(
+            ctx: &mut ModeContext,
+            _: &mut KeysIterator,
+            poll: ReadLinePoll,
+        ) -> Option<EditorControlFlow> {
+            match poll {
+                ReadLinePoll::Pending => return Some(EditorControlFlow::Continue),
+                ReadLinePoll::Submitted => open_current_entry_at_position(ctx),
+                ReadLinePoll::Canceled => (),
+            }
+
+            Mode::change_to(ctx, ModeKind::default());
+            Some(EditorControlFlow::Continue)
+        }
+
+        ctx.editor.read_line.set_prompt("goto file:");
+        ctx.editor.picker.filter(WordIndicesIter::empty(), "");
+        ctx.editor.picker.move_cursor(0);
+
+        if ctx.editor.picker.len() > 0 {
+            ctx.editor.mode.picker_state.on_client_keys = on_client_keys;
+            Mode::change_to(ctx, ModeKind::Picker);
+        }
+    }
+}
+
+pub mod todo {
+    use super::*;
+
+    pub fn enter_mode(ctx: &mut ModeContext, workspace: Option<(PathBuf, IgnoreList)>) {
+        fn on_client_keys(
+            ctx: &mut ModeContext,
+            _: &mut KeysIterator,
+            poll: ReadLinePoll,
+        ) -> Option<EditorControlFlow> {
+            match poll {
+                ReadLinePoll::Pending => return Some(EditorControlFlow::Continue),
+                ReadLinePoll::Submitted => open_current_entry_at_position(ctx),
+                ReadLinePoll::Canceled => (),
+            }
+
+            Mode::change_to(ctx, ModeKind::default());
+            Some(EditorControlFlow::Continue)
+        }
+
+        ctx.editor.read_line.set_prompt("todo:");
+        ctx.editor.mode.picker_state.on_client_keys = on_client_keys;
+        Mode::change_to(ctx, ModeKind::Picker);
+
+        ctx.editor.picker.clear();
+        let is_workspace = workspace.is_some();
+        match workspace {
+            Some((root, ignore)) => {
+                crate::todo::add_buffer_entries(ctx.editor, Some(&root));
+                let job = crate::todo::workspace_job(ctx.editor, root, ignore);
+
+                let state = &mut ctx.editor.mode.picker_state;
+                state.find_file_ignore.clear();
+                let token = state.begin_request();
+                ctx.platform.jobs.spawn(JobTag::TodoWorkspace(token), job);
+            }
+            None => crate::todo::add_buffer_entries(ctx.editor, None),
+        }
+        ctx.editor.picker.filter(WordIndicesIter::empty(), "");
+        ctx.editor.picker.move_cursor(0);
+
+        if !is_workspace && ctx.editor.picker.len() == 0 {
+            Mode::change_to(ctx, ModeKind::default());
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .str("no todos found");
+        }
+    }
+}
+
+pub mod yank_history {
+    use super::*;
+
+    use crate::mode::normal;
+
+    fn entry_index(entry: &str) -> Option<usize> {
+        let (index, _) = entry.split_once(':')?;
+        let index: usize = index.parse().ok()?;
+        index.checked_sub(1)
+    }
+
+    fn preview_current_entry(ctx: &mut ModeContext) {
+        let index = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
+            Some((_, entry)) => entry_index(entry),
+            None => None,
+        };
+        let history = &ctx.editor.yank_history;
+        if let Some(text) = index.and_then(|i| history.get(i)) {
+            if text.trim_end_matches('\n').contains('\n') {
+                ctx.editor.status_bar.write(MessageKind::Info).str(text);
+            }
+        }
+    }
+
+    pub fn enter_mode(ctx: &mut ModeContext) {
+        fn on_client_keys(
+            ctx: &mut ModeContext,
+            _: &mut KeysIterator,
+            poll: ReadLinePoll,
+        ) -> Option<EditorControlFlow> {
+            match poll {
+                ReadLinePoll::Pending => {
+                    preview_current_entry(ctx);
+                    return Some(EditorControlFlow::Continue);
+                }
+                ReadLinePoll::Submitted => (),
+                ReadLinePoll::Canceled => {
+                    Mode::change_to(ctx, ModeKind::default());
+                    return Some(EditorControlFlow::Continue);
+                }
+            }
+
+            let index = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
+                Some((_, entry)) => entry_index(entry),
+                None => None,
+            };
+            Mode::change_to(ctx, ModeKind::default());
+
+            let history = &ctx.editor.yank_history;
+            let text = match index.and_then(|i| history.get(i)) {
+                Some(text) => ctx.editor.string_pool.acquire_with(text),
+                None => return Some(EditorControlFlow::Continue),
+            };
+            if let Some(handle) = ctx.clients.get(ctx.client_handle).buffer_view_handle() {
+                normal::paste_text(ctx, handle, &text);
+            }
+            ctx.editor.string_pool.release(text);
+            Some(EditorControlFlow::Continue)
+        }
+
+        ctx.editor.read_line.set_prompt("yank:");
+        ctx.editor.picker.clear();
+
+        for (i, text) in ctx.editor.yank_history.iter().enumerate() {
+            let mut lines = text.trim_end_matches('\n').lines();
+            let first_line = lines.next().unwrap_or("").trim();
+            match lines.count() {
+                0 => ctx.editor.picker.add_custom_entry_fmt(format_args!(
+                    "{}: {}",
+                    i + 1,
+                    first_line
+                )),
+                n => ctx.editor.picker.add_custom_entry_fmt(format_args!(
+                    "{}: {} (+{} lines)",
+                    i + 1,
+                    first_line,
+                    n
+                )),
+            }
+        }
+
+        ctx.editor.picker.filter(WordIndicesIter::empty(), "");
+        ctx.editor.picker.move_cursor(0);
+
+        if ctx.editor.picker.len() > 0 {
+            ctx.editor.mode.picker_state.on_client_keys = on_client_keys;
+            Mode::change_to(ctx, ModeKind::Picker);
+            preview_current_entry(ctx);
+        } else {
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Error)
+                .str("yank history is empty");
+        }
+    }
+}
+
+pub mod lsp_definition {
+    use super::*;
+
+    pub fn enter_mode(ctx: &mut ModeContext, client_handle: lsp::ClientHandle) {
+        fn on_client_keys(
+            ctx: &mut ModeContext,
+            _: &mut KeysIterator,
+            poll: ReadLinePoll,
+        ) -> Option<EditorControlFlow> {
+            match poll {
+                ReadLinePoll::Pending => Some(EditorControlFlow::Continue),
+                ReadLinePoll::Submitted => {
+                    open_current_entry_at_position(ctx);
+                    Mode::change_to(ctx, ModeKind::default());
+                    Some(EditorControlFlow::Continue)
+                }
+                ReadLinePoll::Canceled => {
+                    Mode::change_to(ctx, ModeKind::default());
+                    Some(EditorControlFlow::Continue)
+                }
+            }
+        }
+
         ctx.editor.read_line.set_prompt("definition:");
         ctx.editor.picker.filter(WordIndicesIter::empty(), "");
         ctx.editor.picker.move_cursor(0);
@@ -403,8 +786,9 @@ pub mod lsp_code_action {
                             Some((EntrySource::Custom(i), _)) => i,
                             _ => 0,
                         };
+                        let platform = &mut *ctx.platform;
                         lsp::ClientManager::access(ctx.editor, handle, |e, c| {
-                            c.finish_code_action(e, index);
+                            c.finish_code_action(e, platform, index);
                         });
                     }
                     Mode::change_to(ctx, ModeKind::default());
@@ -412,8 +796,9 @@ pub mod lsp_code_action {
                 }
                 ReadLinePoll::Canceled => {
                     if let Some(handle) = ctx.editor.mode.picker_state.lsp_client_handle {
+                        let platform = &mut *ctx.platform;
                         lsp::ClientManager::access(ctx.editor, handle, |_, c| {
-                            c.cancel_current_request();
+                            c.cancel_current_request(platform);
                         });
                     }
                     Mode::change_to(ctx, ModeKind::default());
@@ -432,8 +817,9 @@ pub mod lsp_code_action {
             state.lsp_client_handle = Some(client_handle);
             Mode::change_to(ctx, ModeKind::Picker);
         } else {
+            let platform = &mut *ctx.platform;
             lsp::ClientManager::access(ctx.editor, client_handle, |_, c| {
-                c.cancel_current_request();
+                c.cancel_current_request(platform);
             });
         }
     }
@@ -468,8 +854,9 @@ pub mod lsp_document_symbol {
                 }
                 ReadLinePoll::Canceled => {
                     if let Some(handle) = ctx.editor.mode.picker_state.lsp_client_handle {
+                        let platform = &mut *ctx.platform;
                         lsp::ClientManager::access(ctx.editor, handle, |_, c| {
-                            c.cancel_current_request();
+                            c.cancel_current_request(platform);
                         });
                     }
                     Mode::change_to(ctx, ModeKind::default());
@@ -488,8 +875,9 @@ pub mod lsp_document_symbol {
             state.lsp_client_handle = Some(client_handle);
             Mode::change_to(ctx, ModeKind::Picker);
         } else {
+            let platform = &mut *ctx.platform;
             lsp::ClientManager::access(ctx.editor, client_handle, |_, c| {
-                c.cancel_current_request();
+                c.cancel_current_request(platform);
             });
         }
     }
@@ -524,8 +912,9 @@ pub mod lsp_workspace_symbol {
                 }
                 ReadLinePoll::Canceled => {
                     if let Some(handle) = ctx.editor.mode.picker_state.lsp_client_handle {
+                        let platform = &mut *ctx.platform;
                         lsp::ClientManager::access(ctx.editor, handle, |_, c| {
-                            c.cancel_current_request();
+                            c.cancel_current_request(platform);
                         });
                     }
                     Mode::change_to(ctx, ModeKind::default());
@@ -544,9 +933,78 @@ pub mod lsp_workspace_symbol {
             state.lsp_client_handle = Some(client_handle);
             Mode::change_to(ctx, ModeKind::Picker);
         } else {
+            let platform = &mut *ctx.platform;
             lsp::ClientManager::access(ctx.editor, client_handle, |_, c| {
-                c.cancel_current_request();
+                c.cancel_current_request(platform);
             });
         }
     }
 }
+
+pub mod remote_plugin {
+    use super::*;
+
+    pub fn enter_mode(
+        ctx: &mut ModeContext,
+        handle: RemotePluginHandle,
+        request_id: JsonInteger,
+        prompt: &str,
+    ) {
+        fn on_client_keys(
+            ctx: &mut ModeContext,
+            _: &mut KeysIterator,
+            poll: ReadLinePoll,
+        ) -> Option<EditorControlFlow> {
+            match poll {
+                ReadLinePoll::Pending => return Some(EditorControlFlow::Continue),
+                ReadLinePoll::Submitted => (),
+                ReadLinePoll::Canceled => {
+                    Mode::change_to(ctx, ModeKind::default());
+                    return Some(EditorControlFlow::Continue);
+                }
+            }
+
+            let request = ctx.editor.mode.picker_state.remote_plugin_request.take();
+            if let Some((handle, request_id)) = request {
+                let entry = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
+                    Some((_, entry)) => Some(ctx.editor.string_pool.acquire_with(entry)),
+                    None => None,
+                };
+                RemotePluginCollection::respond_pick(
+                    ctx.editor,
+                    ctx.platform,
+                    handle,
+                    request_id,
+                    entry.as_deref(),
+                );
+                if let Some(entry) = entry {
+                    ctx.editor.string_pool.release(entry);
+                }
+            }
+
+            Mode::change_to(ctx, ModeKind::default());
+            Some(EditorControlFlow::Continue)
+        }
+
+        if let Some((handle, request_id)) =
+            ctx.editor.mode.picker_state.remote_plugin_request.take()
+        {
+            RemotePluginCollection::respond_pick(
+                ctx.editor,
+                ctx.platform,
+                handle,
+                request_id,
+                None,
+            );
+        }
+
+        ctx.editor.read_line.set_prompt(prompt);
+        ctx.editor.picker.filter(WordIndicesIter::empty(), "");
+        ctx.editor.picker.move_cursor(0);
+
+        let state = &mut ctx.editor.mode.picker_state;
+        state.on_client_keys = on_client_keys;
+        state.remote_plugin_request = Some((handle, request_id));
+        Mode::change_to(ctx, ModeKind::Picker);
+    }
+}
@@ -0,0 +1,25 @@
+use crate::{
+    editor::{EditorControlFlow, KeysIterator},
+    mode::{ModeContext, ModeState},
+    plugin,
+};
+
+#[derive(Default)]
+pub struct State {
+    pub name: String,
+}
+
+impl ModeState for State {
+    fn on_enter(ctx: &mut ModeContext) {
+        plugin::on_mode_enter(ctx);
+    }
+
+    fn on_exit(ctx: &mut ModeContext) {
+        plugin::on_mode_exit(ctx);
+    }
+
+    fn on_client_keys(ctx: &mut ModeContext, keys: &mut KeysIterator) -> Option<EditorControlFlow> {
+        let key = keys.next(&ctx.editor.buffered_keys);
+        plugin::on_mode_key(ctx, key)
+    }
+}
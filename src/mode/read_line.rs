@@ -3,11 +3,12 @@ use crate::{
     buffer_view::CursorMovementKind,
     cursor::{Cursor, CursorCollection},
     editor::{EditorControlFlow, KeysIterator},
-    editor_utils::{parse_process_command, MessageKind, ReadLinePoll},
+    editor_utils::{parse_process_command, MessageKind, ReadLineCompletionSource, ReadLinePoll},
     lsp,
     mode::{Mode, ModeContext, ModeKind, ModeState},
     pattern::Pattern,
-    platform::PooledBuf,
+    platform::{Key, PooledBuf},
+    word_database::WordIndicesIter,
 };
 
 pub struct State {
@@ -15,6 +16,7 @@ pub struct State {
         fn(&mut ModeContext, &mut KeysIterator, ReadLinePoll) -> Option<EditorControlFlow>,
     previous_position: BufferPosition,
     lsp_client_handle: Option<lsp::ClientHandle>,
+    completion_index: Option<usize>,
 }
 
 impl Default for State {
@@ -23,6 +25,7 @@ impl Default for State {
             on_client_keys: |_, _, _| Some(EditorControlFlow::Continue),
             previous_position: BufferPosition::zero(),
             lsp_client_handle: None,
+            completion_index: None,
         }
     }
 }
@@ -30,10 +33,12 @@ impl Default for State {
 impl ModeState for State {
     fn on_enter(ctx: &mut ModeContext) {
         ctx.editor.read_line.input_mut().clear();
+        ctx.editor.mode.read_line_state.completion_index = None;
     }
 
     fn on_exit(ctx: &mut ModeContext) {
         ctx.editor.read_line.input_mut().clear();
+        cancel_completion(ctx);
     }
 
     fn on_client_keys(ctx: &mut ModeContext, keys: &mut KeysIterator) -> Option<EditorControlFlow> {
@@ -43,11 +48,80 @@ impl ModeState for State {
             &ctx.editor.buffered_keys,
             keys,
         );
+        if let ReadLinePoll::Pending = poll {
+            keys.index = keys.index.saturating_sub(1);
+            match keys.next(&ctx.editor.buffered_keys) {
+                Key::Tab | Key::Ctrl('n') => apply_completion(ctx, 1),
+                Key::Ctrl('p') => apply_completion(ctx, -1),
+                Key::Up | Key::Down => cancel_completion(ctx),
+                _ => update_completions(ctx),
+            }
+        }
+
         let func = ctx.editor.mode.read_line_state.on_client_keys;
         func(ctx, keys, poll)
     }
 }
 
+fn completion_token_index(input: &str) -> usize {
+    input.trim_end_matches(|c: char| !c.is_whitespace()).len()
+}
+
+fn cancel_completion(ctx: &mut ModeContext) {
+    ctx.editor.mode.read_line_state.completion_index = None;
+    ctx.editor.picker.clear();
+}
+
+fn filter_completions(ctx: &mut ModeContext, index: usize) {
+    let read_line = &ctx.editor.read_line;
+    let pattern = &read_line.input()[index..];
+    let word_indices = match read_line.completion_source() {
+        ReadLineCompletionSource::None => return,
+        ReadLineCompletionSource::Words => ctx.editor.word_database.word_indices(),
+        ReadLineCompletionSource::Custom => WordIndicesIter::empty(),
+    };
+    ctx.editor.picker.filter(word_indices, pattern);
+}
+
+fn apply_completion(ctx: &mut ModeContext, cursor_movement: isize) {
+    let index = match ctx.editor.mode.read_line_state.completion_index {
+        Some(index) => index,
+        None => {
+            let index = completion_token_index(ctx.editor.read_line.input());
+            ctx.editor.picker.clear();
+            for entry in ctx.editor.read_line.completion_entries() {
+                ctx.editor.picker.add_custom_entry(entry);
+            }
+            filter_completions(ctx, index);
+            ctx.editor.mode.read_line_state.completion_index = Some(index);
+            index
+        }
+    };
+
+    ctx.editor.picker.move_cursor(cursor_movement);
+    if let Some((_, entry)) = ctx.editor.picker.current_entry(&ctx.editor.word_database) {
+        let input = ctx.editor.read_line.input_mut();
+        input.truncate(index);
+        input.push_str(entry);
+    }
+}
+
+fn update_completions(ctx: &mut ModeContext) {
+    let index = match ctx.editor.mode.read_line_state.completion_index {
+        Some(index) => index,
+        None => return,
+    };
+
+    let input = ctx.editor.read_line.input();
+    if index > input.len() || completion_token_index(input) != index {
+        cancel_completion(ctx);
+        return;
+    }
+
+    ctx.editor.picker.clear_cursor();
+    filter_completions(ctx, index);
+}
+
 pub mod search {
     use super::*;
 
@@ -100,6 +174,10 @@ pub mod search {
 
         save_current_position(ctx);
         ctx.editor.read_line.set_prompt("search:");
+        ctx.editor.read_line.enable_history();
+        ctx.editor
+            .read_line
+            .set_completion_source(ReadLineCompletionSource::Words);
         update_search(ctx);
 
         ctx.editor.mode.read_line_state.on_client_keys = on_client_keys;
@@ -174,6 +252,10 @@ pub mod filter_cursors {
 
     pub fn enter_filter_mode(ctx: &mut ModeContext) {
         ctx.editor.read_line.set_prompt("filter:");
+        ctx.editor.read_line.enable_history();
+        ctx.editor
+            .read_line
+            .set_completion_source(ReadLineCompletionSource::Words);
         ctx.editor.mode.read_line_state.on_client_keys = |ctx, _, poll| {
             on_submitted(ctx, poll, |ctx| {
                 on_event_impl(ctx, true);
@@ -185,6 +267,10 @@ pub mod filter_cursors {
 
     pub fn enter_except_mode(ctx: &mut ModeContext) {
         ctx.editor.read_line.set_prompt("except:");
+        ctx.editor.read_line.enable_history();
+        ctx.editor
+            .read_line
+            .set_completion_source(ReadLineCompletionSource::Words);
         ctx.editor.mode.read_line_state.on_client_keys = |ctx, _, poll| {
             on_submitted(ctx, poll, |ctx| {
                 on_event_impl(ctx, false);
@@ -315,6 +401,7 @@ pub mod split_cursors {
         }
 
         ctx.editor.read_line.set_prompt("split-by:");
+        ctx.editor.read_line.enable_history();
         ctx.editor.mode.read_line_state.on_client_keys = |ctx, _, poll| {
             on_submitted(ctx, poll, |ctx| {
                 on_event_impl(ctx, add_matches);
@@ -372,6 +459,7 @@ pub mod split_cursors {
         }
 
         ctx.editor.read_line.set_prompt("split-on:");
+        ctx.editor.read_line.enable_history();
         ctx.editor.mode.read_line_state.on_client_keys = |ctx, _, poll| {
             on_submitted(ctx, poll, |ctx| {
                 on_event_impl(ctx, add_matches);
@@ -534,6 +622,7 @@ pub mod goto {
 
         save_current_position(ctx);
         ctx.editor.read_line.set_prompt("goto-line:");
+        ctx.editor.read_line.enable_history();
         ctx.editor.mode.read_line_state.on_client_keys = on_client_keys;
         Mode::change_to(ctx, ModeKind::ReadLine);
     }
@@ -563,6 +652,7 @@ pub mod process {
         }
 
         ctx.editor.read_line.set_prompt("replace-with-output:");
+        ctx.editor.read_line.enable_history();
         ctx.editor.mode.read_line_state.on_client_keys = on_client_keys;
         Mode::change_to(ctx, ModeKind::ReadLine);
     }
@@ -588,6 +678,7 @@ pub mod process {
         }
 
         ctx.editor.read_line.set_prompt("insert-from-output:");
+        ctx.editor.read_line.enable_history();
         ctx.editor.mode.read_line_state.on_client_keys = on_client_keys;
         Mode::change_to(ctx, ModeKind::ReadLine);
     }
@@ -668,8 +759,9 @@ pub mod lsp_rename {
                 }
                 ReadLinePoll::Canceled => {
                     if let Some(handle) = ctx.editor.mode.read_line_state.lsp_client_handle {
+                        let platform = &mut *ctx.platform;
                         lsp::ClientManager::access(ctx.editor, handle, |_, c| {
-                            c.cancel_current_request();
+                            c.cancel_current_request(platform);
                         });
                     }
                     Mode::change_to(ctx, ModeKind::default());
@@ -679,6 +771,9 @@ pub mod lsp_rename {
         }
 
         ctx.editor.read_line.set_prompt("rename:");
+        ctx.editor
+            .read_line
+            .set_completion_source(ReadLineCompletionSource::Words);
         let state = &mut ctx.editor.mode.read_line_state;
         state.on_client_keys = on_client_keys;
         state.lsp_client_handle = Some(client_handle);
@@ -687,6 +782,191 @@ pub mod lsp_rename {
     }
 }
 
+pub mod lsp_rename_preview {
+    use super::*;
+
+    pub fn enter_mode(ctx: &mut ModeContext, client_handle: lsp::ClientHandle) {
+        fn on_client_keys(
+            ctx: &mut ModeContext,
+            _: &mut KeysIterator,
+            poll: ReadLinePoll,
+        ) -> Option<EditorControlFlow> {
+            let apply = match poll {
+                ReadLinePoll::Pending => return Some(EditorControlFlow::Continue),
+                ReadLinePoll::Submitted => {
+                    matches!(ctx.editor.read_line.input().trim(), "y" | "yes")
+                }
+                ReadLinePoll::Canceled => false,
+            };
+
+            if let Some(handle) = ctx.editor.mode.read_line_state.lsp_client_handle {
+                lsp::ClientManager::access(ctx.editor, handle, |e, c| {
+                    c.finish_rename_preview(e, apply);
+                });
+            }
+            Mode::change_to(ctx, ModeKind::default());
+            Some(EditorControlFlow::Continue)
+        }
+
+        ctx.editor.read_line.set_prompt("apply rename? [y/n]:");
+        let state = &mut ctx.editor.mode.read_line_state;
+        state.on_client_keys = on_client_keys;
+        state.lsp_client_handle = Some(client_handle);
+        Mode::change_to(ctx, ModeKind::ReadLine);
+    }
+}
+
+pub mod file_tree {
+    use super::*;
+
+    use std::path::Path;
+
+    pub fn enter_create_mode(ctx: &mut ModeContext) {
+        fn on_client_keys(
+            ctx: &mut ModeContext,
+            _: &mut KeysIterator,
+            poll: ReadLinePoll,
+        ) -> Option<EditorControlFlow> {
+            match poll {
+                ReadLinePoll::Pending => return Some(EditorControlFlow::Continue),
+                ReadLinePoll::Submitted => {
+                    let name = ctx.editor.read_line.input().trim();
+                    if !name.is_empty() {
+                        if let Err(error) = ctx.editor.file_tree.create(name) {
+                            ctx.editor
+                                .status_bar
+                                .write(MessageKind::Error)
+                                .fmt(format_args!("could not create '{}': {}", name, error));
+                        }
+                    }
+                }
+                ReadLinePoll::Canceled => (),
+            }
+
+            Mode::change_to(ctx, ModeKind::FileTree);
+            Some(EditorControlFlow::Continue)
+        }
+
+        ctx.editor.read_line.set_prompt("create:");
+        ctx.editor.read_line.enable_history();
+        ctx.editor.mode.read_line_state.on_client_keys = on_client_keys;
+        Mode::change_to(ctx, ModeKind::ReadLine);
+    }
+
+    pub fn enter_rename_mode(ctx: &mut ModeContext) {
+        fn on_client_keys(
+            ctx: &mut ModeContext,
+            _: &mut KeysIterator,
+            poll: ReadLinePoll,
+        ) -> Option<EditorControlFlow> {
+            match poll {
+                ReadLinePoll::Pending => return Some(EditorControlFlow::Continue),
+                ReadLinePoll::Submitted => {
+                    let new_path = Path::new(ctx.editor.read_line.input().trim());
+                    match ctx.editor.file_tree.rename_selected(new_path) {
+                        Ok((old_path, new_path)) => {
+                            let editor = &mut *ctx.editor;
+                            let renamed_buffers = editor.buffers.rename_paths(
+                                &editor.current_directory,
+                                &old_path,
+                                &new_path,
+                                &editor.syntaxes,
+                            );
+                            lsp::ClientManager::did_rename_file(
+                                ctx.editor,
+                                ctx.platform,
+                                &old_path,
+                                &new_path,
+                                &renamed_buffers,
+                            );
+                        }
+                        Err(error) => ctx
+                            .editor
+                            .status_bar
+                            .write(MessageKind::Error)
+                            .fmt(format_args!("could not rename: {}", error)),
+                    }
+                }
+                ReadLinePoll::Canceled => (),
+            }
+
+            Mode::change_to(ctx, ModeKind::FileTree);
+            Some(EditorControlFlow::Continue)
+        }
+
+        let tree = &ctx.editor.file_tree;
+        let path = match tree.selected_entry() {
+            Some(entry) => entry.path.strip_prefix(tree.root()).unwrap_or(&entry.path),
+            None => return,
+        };
+        let path = ctx
+            .editor
+            .string_pool
+            .acquire_with(path.to_str().unwrap_or(""));
+
+        ctx.editor.read_line.set_prompt("rename:");
+        ctx.editor
+            .read_line
+            .set_completion_source(ReadLineCompletionSource::Custom);
+        let tree = &ctx.editor.file_tree;
+        for entry in tree.entries().iter().filter(|e| e.is_dir) {
+            let dir = entry.path.strip_prefix(tree.root()).unwrap_or(&entry.path);
+            if let Some(dir) = dir.to_str() {
+                let mut completion = ctx.editor.string_pool.acquire_with(dir);
+                completion.push('/');
+                ctx.editor.read_line.add_completion_entry(&completion);
+                ctx.editor.string_pool.release(completion);
+            }
+        }
+        ctx.editor.mode.read_line_state.on_client_keys = on_client_keys;
+        Mode::change_to(ctx, ModeKind::ReadLine);
+        ctx.editor.read_line.input_mut().push_str(&path);
+        ctx.editor.string_pool.release(path);
+    }
+
+    pub fn enter_delete_mode(ctx: &mut ModeContext) {
+        fn on_client_keys(
+            ctx: &mut ModeContext,
+            _: &mut KeysIterator,
+            poll: ReadLinePoll,
+        ) -> Option<EditorControlFlow> {
+            let delete = match poll {
+                ReadLinePoll::Pending => return Some(EditorControlFlow::Continue),
+                ReadLinePoll::Submitted => {
+                    matches!(ctx.editor.read_line.input().trim(), "y" | "yes")
+                }
+                ReadLinePoll::Canceled => false,
+            };
+
+            if delete {
+                if let Err(error) = ctx.editor.file_tree.delete_selected() {
+                    ctx.editor
+                        .status_bar
+                        .write(MessageKind::Error)
+                        .fmt(format_args!("could not delete: {}", error));
+                }
+            }
+
+            Mode::change_to(ctx, ModeKind::FileTree);
+            Some(EditorControlFlow::Continue)
+        }
+
+        let name = match ctx.editor.file_tree.selected_entry() {
+            Some(entry) => entry.name(),
+            None => return,
+        };
+        let mut prompt = ctx.editor.string_pool.acquire();
+        prompt.push_str("delete '");
+        prompt.push_str(name);
+        prompt.push_str("'? [y/n]:");
+        ctx.editor.read_line.set_prompt(&prompt);
+        ctx.editor.string_pool.release(prompt);
+
+        ctx.editor.mode.read_line_state.on_client_keys = on_client_keys;
+        Mode::change_to(ctx, ModeKind::ReadLine);
+    }
+}
+
 fn save_current_position(ctx: &mut ModeContext) {
     let buffer_view_handle = match ctx.clients.get(ctx.client_handle).buffer_view_handle() {
         Some(handle) => handle,
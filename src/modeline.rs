@@ -0,0 +1,182 @@
+use std::num::NonZeroU8;
+
+use crate::buffer::BufferContent;
+
+const MODELINE_SEARCH_LINE_COUNT: usize = 5;
+
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct Modeline<'a> {
+    pub filetype: Option<&'a str>,
+    pub tab_size: Option<NonZeroU8>,
+    pub indent_with_tabs: Option<bool>,
+}
+
+impl<'a> Modeline<'a> {
+    pub fn find(content: &'a BufferContent) -> Option<Self> {
+        let line_count = content.line_count();
+        let head = 0..line_count.min(MODELINE_SEARCH_LINE_COUNT);
+        let tail = line_count
+            .saturating_sub(MODELINE_SEARCH_LINE_COUNT)
+            .max(MODELINE_SEARCH_LINE_COUNT)..line_count;
+        head.chain(tail)
+            .find_map(|i| Self::parse(content.line_at(i).as_str()))
+    }
+
+    pub fn parse(line: &'a str) -> Option<Self> {
+        let modeline = Self::parse_any(line)?;
+        if modeline == Self::default() {
+            None
+        } else {
+            Some(modeline)
+        }
+    }
+
+    fn parse_any(line: &'a str) -> Option<Self> {
+        if let Some(start) = line.find("-*-") {
+            let rest = &line[start + 3..];
+            let options = &rest[..rest.find("-*-")?];
+            return Some(Self::parse_emacs(options));
+        }
+
+        let start = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+            let index = line.find(marker)?;
+            let preceded_by_blank = line[..index]
+                .chars()
+                .next_back()
+                .map(char::is_whitespace)
+                .unwrap_or(true);
+            if preceded_by_blank {
+                Some(index + marker.len())
+            } else {
+                None
+            }
+        })?;
+        Some(Self::parse_vim(&line[start..]))
+    }
+
+    fn parse_emacs(options: &'a str) -> Self {
+        let mut modeline = Self::default();
+        if !options.contains(':') {
+            let mode = options.trim();
+            if !mode.is_empty() {
+                modeline.filetype = Some(mode);
+            }
+            return modeline;
+        }
+
+        for option in options.split(';') {
+            let (key, value) = match option.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            match key {
+                "mode" if !value.is_empty() => modeline.filetype = Some(value),
+                "tab-width" => modeline.tab_size = value.parse().ok(),
+                "indent-tabs-mode" => modeline.indent_with_tabs = Some(value != "nil"),
+                _ => (),
+            }
+        }
+        modeline
+    }
+
+    fn parse_vim(options: &'a str) -> Self {
+        let options = options.trim_start();
+        let options = options
+            .strip_prefix("set ")
+            .or_else(|| options.strip_prefix("se "))
+            .unwrap_or(options);
+
+        let mut modeline = Self::default();
+        let mut shift_width = None;
+        for option in options.split(|c: char| c == ':' || c.is_whitespace()) {
+            match option.split_once('=') {
+                Some(("ft" | "filetype" | "syn" | "syntax", value)) if !value.is_empty() => {
+                    modeline.filetype = Some(value)
+                }
+                Some(("ts" | "tabstop", value)) => modeline.tab_size = value.parse().ok(),
+                Some(("sw" | "shiftwidth" | "sts" | "softtabstop", value)) => {
+                    shift_width = value.parse().ok()
+                }
+                Some(_) => (),
+                None => match option {
+                    "et" | "expandtab" => modeline.indent_with_tabs = Some(false),
+                    "noet" | "noexpandtab" => modeline.indent_with_tabs = Some(true),
+                    _ => (),
+                },
+            }
+        }
+
+        if modeline.indent_with_tabs == Some(false) || modeline.tab_size.is_none() {
+            modeline.tab_size = shift_width.or(modeline.tab_size);
+        }
+        modeline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::buffer_position::BufferPosition;
+
+    #[test]
+    fn parse_modelines() {
+        fn parse(line: &str) -> Option<(Option<&str>, Option<u8>, Option<bool>)> {
+            let modeline = Modeline::parse(line)?;
+            Some((
+                modeline.filetype,
+                modeline.tab_size.map(NonZeroU8::get),
+                modeline.indent_with_tabs,
+            ))
+        }
+
+        assert_eq!(None, parse("fn main() {}"));
+        assert_eq!(None, parse("nvim: ts=2"));
+        assert_eq!(None, parse("# -*- coding: utf-8 -*-"));
+        assert_eq!(None, parse("see the ex: command"));
+        assert_eq!(Some((None, Some(4), None)), parse("# vim: set ts=4 sw=4"));
+        assert_eq!(
+            Some((Some("python"), Some(2), Some(false))),
+            parse("# vim: set ft=python ts=8 sw=2 et:")
+        );
+        assert_eq!(
+            Some((Some("c"), Some(8), Some(true))),
+            parse("/* vi:syntax=c:ts=8:noet */")
+        );
+        assert_eq!(
+            Some((Some("rust"), None, None)),
+            parse("// -*- mode: rust -*-")
+        );
+        assert_eq!(
+            Some((Some("python"), None, None)),
+            parse("# -*- python -*-")
+        );
+        assert_eq!(
+            Some((Some("c"), Some(3), Some(false))),
+            parse("/* -*- mode: c; tab-width: 3; indent-tabs-mode: nil -*- */")
+        );
+    }
+
+    #[test]
+    fn find_modeline_in_content() {
+        fn find_filetype(text: &str) -> Option<String> {
+            let mut content = BufferContent::new();
+            content.insert_text(BufferPosition::zero(), text);
+            let filetype = Modeline::find(&content)?.filetype;
+            filetype.map(String::from)
+        }
+
+        assert_eq!(
+            Some("python"),
+            find_filetype("# vim: ft=python\n2\n3").as_deref()
+        );
+        assert_eq!(
+            Some("python"),
+            find_filetype("1\n2\n3\n4\n5\n6\n7\n# vim: ft=python").as_deref()
+        );
+        assert_eq!(
+            None,
+            find_filetype("1\n2\n3\n4\n5\n# vim: ft=python\n7\n8\n9\n10\n11")
+        );
+    }
+}
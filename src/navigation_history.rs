@@ -1,10 +1,13 @@
+use std::path::PathBuf;
+
 use crate::{
-    buffer::BufferHandle,
+    buffer::{Buffer, BufferCapabilities, BufferHandle},
     buffer_position::BufferPosition,
     buffer_view::{BufferView, BufferViewCollection},
     client::Client,
     cursor::Cursor,
     editor::Editor,
+    editor_utils::MessageKind,
 };
 
 #[derive(Clone, Copy)]
@@ -13,9 +16,9 @@ pub enum NavigationMovement {
     Backward,
 }
 
-#[derive(Clone)]
 struct NavigationHistorySnapshot {
-    pub buffer_handle: BufferHandle,
+    pub buffer_handle: Option<BufferHandle>,
+    pub closed_buffer_path: PathBuf,
     pub position: BufferPosition,
 }
 
@@ -53,14 +56,15 @@ impl NavigationHistory {
         if this
             .snapshots
             .last()
-            .map(|s| s.buffer_handle == buffer_handle && s.position == position)
+            .map(|s| s.buffer_handle == Some(buffer_handle) && s.position == position)
             .unwrap_or(false)
         {
             return;
         }
 
         this.snapshots.push(NavigationHistorySnapshot {
-            buffer_handle,
+            buffer_handle: Some(buffer_handle),
+            closed_buffer_path: PathBuf::new(),
             position,
         });
         this.current_snapshot_index = this.snapshots.len() as _;
@@ -95,18 +99,67 @@ impl NavigationHistory {
             }
         }
 
-        let snapshot = &client.navigation_history.snapshots
-            [client.navigation_history.current_snapshot_index as usize];
+        let index = client.navigation_history.current_snapshot_index as usize;
+        if !Self::reopen_snapshot_buffer(client, editor, index) {
+            return;
+        }
+        Self::apply_snapshot(client, editor, index);
+    }
+
+    fn reopen_snapshot_buffer(client: &mut Client, editor: &mut Editor, index: usize) -> bool {
+        let this = &mut client.navigation_history;
+        if this.snapshots[index].buffer_handle.is_some() {
+            return true;
+        }
+
+        let path = std::mem::take(&mut this.snapshots[index].closed_buffer_path);
+        match editor.buffer_view_handle_from_path(
+            client.handle(),
+            &path,
+            BufferCapabilities::text(),
+        ) {
+            Ok(buffer_view_handle) => {
+                let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+                for snapshot in &mut client.navigation_history.snapshots {
+                    if snapshot.buffer_handle.is_none() && snapshot.closed_buffer_path == path {
+                        snapshot.buffer_handle = Some(buffer_handle);
+                        snapshot.closed_buffer_path = PathBuf::new();
+                    }
+                }
+                client.navigation_history.snapshots[index].buffer_handle = Some(buffer_handle);
+                true
+            }
+            Err(error) => {
+                editor
+                    .status_bar
+                    .write(MessageKind::Error)
+                    .fmt(format_args!(
+                        "could not reopen '{}': {}",
+                        path.display(),
+                        error
+                    ));
+                client.navigation_history.remove_snapshot(index);
+                false
+            }
+        }
+    }
+
+    fn apply_snapshot(client: &mut Client, editor: &mut Editor, index: usize) {
+        let snapshot = &client.navigation_history.snapshots[index];
+        let buffer_handle = match snapshot.buffer_handle {
+            Some(handle) => handle,
+            None => return,
+        };
 
         let position = editor
             .buffers
-            .get(snapshot.buffer_handle)
+            .get(buffer_handle)
             .content()
             .saturate_position(snapshot.position);
 
         let buffer_view_handle = editor
             .buffer_views
-            .buffer_view_handle_from_buffer_handle(client.handle(), snapshot.buffer_handle);
+            .buffer_view_handle_from_buffer_handle(client.handle(), buffer_handle);
 
         let mut cursors = editor
             .buffer_views
@@ -133,7 +186,7 @@ impl NavigationHistory {
                 .snapshots
                 .get(client.navigation_history.current_snapshot_index as usize)
             {
-                if current_snapshot.buffer_handle == buffer_view.buffer_handle {
+                if current_snapshot.buffer_handle == Some(buffer_view.buffer_handle) {
                     return;
                 }
             }
@@ -142,7 +195,8 @@ impl NavigationHistory {
                 .navigation_history
                 .snapshots
                 .push(NavigationHistorySnapshot {
-                    buffer_handle: buffer_view.buffer_handle,
+                    buffer_handle: Some(buffer_view.buffer_handle),
+                    closed_buffer_path: PathBuf::new(),
                     position: buffer_view.cursors.main_cursor().position,
                 });
         }
@@ -158,10 +212,14 @@ impl NavigationHistory {
         let current_buffer_handle = current_buffer_view.map(|v| v.buffer_handle);
 
         for (i, snapshot) in client.navigation_history.snapshots.iter().enumerate().rev() {
-            if current_buffer_handle != Some(snapshot.buffer_handle) {
+            let buffer_handle = match snapshot.buffer_handle {
+                Some(handle) => handle,
+                None => continue,
+            };
+            if current_buffer_handle != Some(buffer_handle) {
                 let buffer_view_handle = editor
                     .buffer_views
-                    .buffer_view_handle_from_buffer_handle(client.handle(), snapshot.buffer_handle);
+                    .buffer_view_handle_from_buffer_handle(client.handle(), buffer_handle);
                 client.set_buffer_view_handle_no_history(
                     Some(buffer_view_handle),
                     &mut editor.events,
@@ -173,15 +231,41 @@ impl NavigationHistory {
         }
     }
 
-    pub fn remove_snapshots_with_buffer_handle(&mut self, buffer_handle: BufferHandle) {
+    pub fn move_to_last_open_buffer(client: &mut Client, editor: &mut Editor) {
+        let this = &mut client.navigation_history;
+        let end_index = this.snapshots.len().min(this.current_snapshot_index as _);
+        if let Some(index) = (0..end_index)
+            .rev()
+            .find(|&i| this.snapshots[i].buffer_handle.is_some())
+        {
+            this.current_snapshot_index = index as _;
+            Self::apply_snapshot(client, editor, index);
+        }
+    }
+
+    fn remove_snapshot(&mut self, index: usize) {
+        self.snapshots.remove(index);
+        if self.current_snapshot_index > 0 && index <= self.current_snapshot_index as _ {
+            self.current_snapshot_index -= 1;
+        }
+    }
+
+    pub fn on_buffer_close(&mut self, buffer: &Buffer) {
+        let buffer_handle = buffer.handle();
+        let can_reopen = buffer.capabilities.can_save && !buffer.path.as_os_str().is_empty();
+
         for i in (0..self.snapshots.len()).rev() {
-            let snapshot = self.snapshots[i].clone();
-            if snapshot.buffer_handle == buffer_handle {
-                self.snapshots.remove(i);
+            let snapshot = &mut self.snapshots[i];
+            if snapshot.buffer_handle != Some(buffer_handle) {
+                continue;
+            }
 
-                if self.current_snapshot_index > 0 && i <= self.current_snapshot_index as _ {
-                    self.current_snapshot_index -= 1;
-                }
+            if can_reopen {
+                snapshot.buffer_handle = None;
+                snapshot.closed_buffer_path.clear();
+                snapshot.closed_buffer_path.push(&buffer.path);
+            } else {
+                self.remove_snapshot(i);
             }
         }
     }
@@ -310,4 +394,40 @@ mod tests {
 
         assert_eq!(3, client.navigation_history.snapshots.len());
     }
+
+    #[test]
+    fn move_back_to_closed_buffer() {
+        let (mut editor, mut client) = setup();
+
+        let path = std::env::temp_dir().join("pepper_navigation_history_test.txt");
+        std::fs::write(&path, "content").unwrap();
+        let buffer = editor.buffers.get_mut(BufferHandle(1));
+        buffer.path = path.clone();
+        buffer.capabilities = BufferCapabilities::text();
+
+        client
+            .navigation_history
+            .on_buffer_close(editor.buffers.get(BufferHandle(0)));
+        client
+            .navigation_history
+            .on_buffer_close(editor.buffers.get(BufferHandle(1)));
+        editor.buffers.remove_from_editor_event_handler(
+            BufferHandle(1),
+            BufferPosition::zero(),
+            &mut editor.word_database,
+        );
+        assert_eq!(1, client.navigation_history.snapshots.len());
+        assert_eq!(1, client.navigation_history.current_snapshot_index);
+
+        NavigationHistory::move_in_history(&mut client, &mut editor, NavigationMovement::Backward);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(0, client.navigation_history.current_snapshot_index);
+        let buffer_view = editor
+            .buffer_views
+            .get(client.buffer_view_handle().unwrap());
+        let buffer = editor.buffers.get(buffer_view.buffer_handle);
+        assert_eq!(path, buffer.path);
+        assert_eq!("content", buffer.content().line_at(0).as_str());
+    }
 }
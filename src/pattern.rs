@@ -15,6 +15,7 @@ pub enum PatternError {
     Unescaped(char),
     EmptyGroup,
     GroupWithElementsOfDifferentSize,
+    RepeatWithEmptyElement,
     PatternTooLong,
 }
 impl fmt::Display for PatternError {
@@ -28,6 +29,9 @@ impl fmt::Display for PatternError {
             Self::GroupWithElementsOfDifferentSize => {
                 write!(f, "pattern group has elements of different size")
             }
+            Self::RepeatWithEmptyElement => {
+                write!(f, "pattern repeat has elements that match empty text")
+            }
             Self::PatternTooLong => write!(f, "pattern is too long"),
         }
     }
@@ -390,6 +394,63 @@ impl Pattern {
     }
 }
 
+// compiles `pattern` through every compile entry point and matches the result against `text`
+// from each char boundary, panicking if a match ends out of bounds or off a char boundary.
+// it's fully deterministic so fuzzers and property tests can reproduce failures from inputs alone
+pub fn check_match_invariants(pattern: &str, text: &str) {
+    fn check(pattern: &Pattern, text: &str) {
+        for (index, _) in text
+            .char_indices()
+            .chain(std::iter::once((text.len(), ' ')))
+        {
+            let mut state = None;
+            loop {
+                let result = match state {
+                    Some(state) => pattern.matches_with_state(text, index, state),
+                    None => pattern.matches(text, index),
+                };
+                match result {
+                    MatchResult::Ok(end) => {
+                        assert!(index <= end && end <= text.len());
+                        assert!(text.is_char_boundary(end));
+                        break;
+                    }
+                    MatchResult::Err => break,
+                    MatchResult::Pending(pending) => {
+                        if state == Some(pending) {
+                            break;
+                        }
+                        state = Some(pending);
+                    }
+                }
+            }
+        }
+
+        let mut previous_end = 0;
+        for range in pattern.match_indices(text, pattern.search_anchor()) {
+            assert!(previous_end <= range.start && range.start < range.end);
+            assert!(range.end <= text.len());
+            assert!(text.is_char_boundary(range.start) && text.is_char_boundary(range.end));
+            previous_end = range.end;
+        }
+    }
+
+    let mut p = Pattern::new();
+    if p.compile(pattern).is_ok() {
+        check(&p, text);
+        p.ignore_case();
+        check(&p, text);
+    }
+    for prefix in &["f/", "F/", "p/", "P/", ""] {
+        let mut searcher = String::with_capacity(prefix.len() + pattern.len());
+        searcher.push_str(prefix);
+        searcher.push_str(pattern);
+        if p.compile_searcher(&searcher).is_ok() {
+            check(&p, text);
+        }
+    }
+}
+
 impl fmt::Debug for Pattern {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut op_digit_count = 0;
@@ -719,8 +780,14 @@ impl<'a> PatternCompiler<'a> {
                     self.parse_expr(JumpFrom::Beginning(end_jump), JumpFrom::End(Jump(0)))?;
                     has_cancel_pattern = true;
                 }
-                _ => {
-                    self.parse_expr(JumpFrom::Beginning(start_jump), JumpFrom::End(Jump(0)))?;
+                c => {
+                    let len =
+                        self.parse_expr(JumpFrom::Beginning(start_jump), JumpFrom::End(Jump(0)))?;
+                    // an element that consumes nothing would repeat forever.
+                    // the ending anchor is fine as matching stops (or becomes pending) there
+                    if len.0 == 0 && c != '$' {
+                        return Err(PatternError::RepeatWithEmptyElement);
+                    }
                 }
             }
         }
@@ -985,7 +1052,7 @@ impl<'a> PatternCompiler<'a> {
             }
         }
 
-        let fix = (len - 1) as _;
+        let fix = (to - from) as _;
         fix_jump(&mut self.start_jump, index, fix);
 
         for op in self.ops.iter_mut() {
@@ -1549,6 +1616,11 @@ mod tests {
         assert_eq!(MatchResult::Ok(1), p.matches("a", 0));
         assert_eq!(MatchResult::Ok('ç'.len_utf8()), p.matches("ç", 0));
         assert_eq!(MatchResult::Ok('é'.len_utf8()), p.matches("é", 0));
+
+        let p = new_pattern("çç|a");
+        assert_eq!(MatchResult::Ok(2 * 'ç'.len_utf8()), p.matches("çç", 0));
+        assert_eq!(MatchResult::Ok(1), p.matches("a", 0));
+        assert_eq!(MatchResult::Err, p.matches("x", 0));
     }
 
     #[test]
@@ -1601,5 +1673,45 @@ mod tests {
             try_new_pattern("a|"),
             Err(PatternError::UnexpectedEndOfPattern)
         ));
+        assert!(matches!(
+            try_new_pattern("{^}"),
+            Err(PatternError::RepeatWithEmptyElement)
+        ));
+        assert!(matches!(
+            try_new_pattern("{()}"),
+            Err(PatternError::RepeatWithEmptyElement)
+        ));
+    }
+
+    #[test]
+    fn match_invariants() {
+        const PATTERN_CHARS: &[char] = &[
+            'a', 'b', 'A', 'ç', '.', '%', 'a', 'd', 'w', 'b', '^', '$', '!', '(', ')', '[', ']',
+            '{', '}', '|',
+        ];
+        const TEXT_CHARS: &[char] = &['a', 'b', 'A', 'B', '1', ' ', '_', 'ç', 'é'];
+
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut next_random = move |max: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % max as u64) as usize
+        };
+
+        let mut pattern = String::new();
+        let mut text = String::new();
+        for _ in 0..10000 {
+            pattern.clear();
+            for _ in 0..next_random(12) {
+                pattern.push(PATTERN_CHARS[next_random(PATTERN_CHARS.len())]);
+            }
+            text.clear();
+            for _ in 0..next_random(16) {
+                text.push(TEXT_CHARS[next_random(TEXT_CHARS.len())]);
+            }
+
+            check_match_invariants(&pattern, &text);
+        }
     }
 }
@@ -1,6 +1,6 @@
 use std::fmt;
 
-use crate::word_database::{WordDatabase, WordIndicesIter};
+use crate::word_database::{is_sub_word_start, WordDatabase, WordIndicesIter};
 
 #[derive(Clone, Copy)]
 pub enum EntrySource {
@@ -124,12 +124,12 @@ impl Picker {
     pub fn filter(&mut self, word_indices: WordIndicesIter, pattern: &str) {
         self.filtered_entries.clear();
 
-        for (i, word) in word_indices {
+        for (i, word, count) in word_indices {
             let score = self.fuzzy_matcher.score(word, pattern);
             if score != 0 {
                 self.filtered_entries.push(FilteredEntry {
                     source: EntrySource::WordDatabase(i),
-                    score,
+                    score: score + word_frequency_score(count),
                 });
             }
         }
@@ -219,6 +219,11 @@ impl<'picker, 'pattern> Drop for AddCustomFilteredEntryGuard<'picker, 'pattern>
 const FIRST_CHAR_SCORE: u32 = 1;
 const WORD_BOUNDARY_MATCH_SCORE: u32 = 2;
 const CONSECUTIVE_MATCH_SCORE: u32 = 3;
+const MAX_WORD_FREQUENCY_SCORE: u32 = 2;
+
+fn word_frequency_score(count: usize) -> u32 {
+    (count.max(1).ilog2() / 2).min(MAX_WORD_FREQUENCY_SCORE)
+}
 
 struct FuzzyMatch {
     rest_index: u32,
@@ -254,10 +259,13 @@ impl FuzzyMatcher {
                         } else if !text_char.is_ascii_alphanumeric() {
                             (true, 0)
                         } else {
-                            let is_word_boundary = (!previous_text_char.is_ascii_alphanumeric()
-                                && text_char.is_ascii_alphanumeric())
-                                || (previous_text_char.is_ascii_lowercase()
-                                    && text_char.is_ascii_uppercase());
+                            let rest_index = previous_match.rest_index as usize + i;
+                            let next_text_char = text[rest_index + text_char.len_utf8()..]
+                                .chars()
+                                .next()
+                                .unwrap_or('\0');
+                            let is_word_boundary =
+                                is_sub_word_start(previous_text_char, text_char, next_text_char);
                             (is_word_boundary, WORD_BOUNDARY_MATCH_SCORE)
                         };
 
@@ -356,6 +364,19 @@ mod tests {
             fuzzy_matcher.score("xyz-abc", "a")
         );
 
+        assert_eq!(
+            FIRST_CHAR_SCORE + WORD_BOUNDARY_MATCH_SCORE * 3,
+            fuzzy_matcher.score("fooBarBaz", "fbb")
+        );
+        assert_eq!(
+            FIRST_CHAR_SCORE + WORD_BOUNDARY_MATCH_SCORE * 2,
+            fuzzy_matcher.score("HTTPServer", "hs")
+        );
+        assert_eq!(
+            FIRST_CHAR_SCORE + WORD_BOUNDARY_MATCH_SCORE * 2 + CONSECUTIVE_MATCH_SCORE,
+            fuzzy_matcher.score("utf8Len", "u8l")
+        );
+
         let repetition_count = 100;
         let big_repetitive_text = "a".repeat(repetition_count);
         assert_eq!(
@@ -366,4 +387,22 @@ mod tests {
             fuzzy_matcher.score(&big_repetitive_text, &big_repetitive_text),
         );
     }
+
+    #[test]
+    fn word_frequency_ranking() {
+        assert_eq!(0, word_frequency_score(1));
+        assert_eq!(1, word_frequency_score(4));
+        assert_eq!(MAX_WORD_FREQUENCY_SCORE, word_frequency_score(10000));
+
+        let mut words = WordDatabase::new();
+        words.add("fooBarRare");
+        for _ in 0..16 {
+            words.add("fooBarCommon");
+        }
+
+        let mut picker = Picker::default();
+        picker.filter(words.word_indices(), "fb");
+        let entries: Vec<_> = picker.entries(&words).collect();
+        assert_eq!(vec!["fooBarCommon", "fooBarRare"], entries);
+    }
 }
@@ -2,9 +2,16 @@ use std::{
     io,
     mem::ManuallyDrop,
     process::{Command, Stdio},
+    sync::{mpsc, Arc, Mutex},
+    thread,
 };
 
-use crate::{client::ClientHandle, editor_utils::parse_process_command, lsp};
+use crate::{
+    client::ClientHandle,
+    editor_utils::{parse_process_command, Logger},
+    lsp,
+    plugin::remote::RemotePluginHandle,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Key {
@@ -20,11 +27,16 @@ pub enum Key {
     PageUp,
     PageDown,
     Tab,
+    Insert,
     Delete,
     F(u8),
     Char(char),
     Ctrl(char),
     Alt(char),
+    Super(char),
+    Hyper(char),
+    Release(char),
+    Mouse(u8),
     Esc,
 }
 
@@ -48,9 +60,17 @@ pub enum PlatformEvent {
         tag: ProcessTag,
         buf: PooledBuf,
     },
+    ProcessErrorOutput {
+        tag: ProcessTag,
+        buf: PooledBuf,
+    },
     ProcessExit {
         tag: ProcessTag,
     },
+    JobFinished {
+        tag: JobTag,
+        buf: PooledBuf,
+    },
 }
 
 pub enum PlatformRequest {
@@ -83,13 +103,131 @@ pub enum PlatformRequest {
 #[derive(Clone, Copy)]
 pub enum ProcessTag {
     Buffer(usize),
-    FindFiles,
+    FindFiles(CancellationToken),
+    Formatter(usize),
+    GitRepoState,
+    Linter(usize),
+    Make,
+    Dap,
+    OpenUrl,
     Lsp(lsp::ClientHandle),
+    Plugin(RemotePluginHandle),
+    PluginInstall(usize),
+    Spawn(usize, u8),
 }
 
 #[derive(Clone, Copy)]
 pub struct ProcessHandle(pub u8);
 
+#[derive(Clone, Copy)]
+pub enum JobTag {
+    TodoWorkspace(CancellationToken),
+    LspWatchedFiles(lsp::ClientHandle),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct CancellationToken(u32);
+
+#[derive(Default)]
+pub struct CancellationSource {
+    generation: u32,
+}
+impl CancellationSource {
+    pub fn token(&self) -> CancellationToken {
+        CancellationToken(self.generation)
+    }
+
+    pub fn cancel(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn is_cancelled(&self, token: CancellationToken) -> bool {
+        token.0 != self.generation
+    }
+}
+
+pub type Job = Box<dyn 'static + Send + FnOnce(&mut Vec<u8>)>;
+
+const MAX_JOB_WORKER_COUNT: usize = 4;
+
+struct JobWorkers {
+    job_sender: mpsc::Sender<(JobTag, Job)>,
+    finished_receiver: mpsc::Receiver<(JobTag, Vec<u8>)>,
+}
+impl JobWorkers {
+    pub fn start(waker: Option<Arc<dyn Send + Sync + Fn()>>) -> Self {
+        let (job_sender, job_receiver) = mpsc::channel::<(JobTag, Job)>();
+        let (finished_sender, finished_receiver) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let worker_count = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(MAX_JOB_WORKER_COUNT);
+        for _ in 0..worker_count {
+            let job_receiver = job_receiver.clone();
+            let finished_sender = finished_sender.clone();
+            let waker = waker.clone();
+            thread::spawn(move || loop {
+                let received = match job_receiver.lock() {
+                    Ok(receiver) => receiver.recv(),
+                    Err(_) => return,
+                };
+                let (tag, job) = match received {
+                    Ok(job) => job,
+                    Err(_) => return,
+                };
+
+                let mut output = Vec::new();
+                job(&mut output);
+                if finished_sender.send((tag, output)).is_err() {
+                    return;
+                }
+                if let Some(waker) = &waker {
+                    waker();
+                }
+            });
+        }
+
+        Self {
+            job_sender,
+            finished_receiver,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct JobPool {
+    waker: Option<Arc<dyn Send + Sync + Fn()>>,
+    workers: Option<JobWorkers>,
+}
+impl JobPool {
+    pub fn set_waker<F>(&mut self, waker: F)
+    where
+        F: 'static + Send + Sync + Fn(),
+    {
+        self.waker = Some(Arc::new(waker));
+    }
+
+    pub fn spawn(&mut self, tag: JobTag, job: Job) {
+        let waker = &self.waker;
+        let workers = self
+            .workers
+            .get_or_insert_with(|| JobWorkers::start(waker.clone()));
+        let _ = workers.job_sender.send((tag, job));
+    }
+
+    pub fn take_finished(&mut self, buf_pool: &mut BufPool, events: &mut Vec<PlatformEvent>) {
+        if let Some(workers) = &self.workers {
+            for (tag, output) in workers.finished_receiver.try_iter() {
+                let mut buf = buf_pool.acquire();
+                buf.write().extend_from_slice(&output);
+                events.push(PlatformEvent::JobFinished { tag, buf });
+            }
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct PlatformRequestCollection {
     pending_requests: Vec<PlatformRequest>,
@@ -112,6 +250,8 @@ pub struct Platform {
     write_to_clipboard: Option<fn(&str)>,
 
     pub buf_pool: BufPool,
+    pub jobs: JobPool,
+    pub logger: Logger,
 
     internal_clipboard: String,
     pub copy_command: String,
@@ -127,6 +267,10 @@ impl Platform {
         self.write_to_clipboard = Some(write_to_clipboard);
     }
 
+    pub fn take_finished_jobs(&mut self, events: &mut Vec<PlatformEvent>) {
+        self.jobs.take_finished(&mut self.buf_pool, events);
+    }
+
     pub fn read_from_clipboard(&self, text: &mut String) {
         if let Some(mut command) = parse_process_command(&self.paste_command) {
             command.stdin(Stdio::null());
@@ -145,6 +289,23 @@ impl Platform {
         }
     }
 
+    pub fn open_url(&mut self, open_url_command: &str, url: &str) {
+        let mut command = match parse_process_command(open_url_command) {
+            Some(command) => command,
+            None => default_open_url_command(),
+        };
+        command.arg(url);
+        command.stdin(Stdio::null());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::null());
+
+        self.requests.enqueue(PlatformRequest::SpawnProcess {
+            tag: ProcessTag::OpenUrl,
+            command,
+            buf_len: 256,
+        });
+    }
+
     pub fn write_to_clipboard(&mut self, text: &str) {
         if let Some(mut command) = parse_process_command(&self.copy_command) {
             command.stdin(Stdio::piped());
@@ -166,6 +327,23 @@ impl Platform {
     }
 }
 
+#[cfg(windows)]
+fn default_open_url_command() -> Command {
+    let mut command = Command::new("rundll32");
+    command.arg("url.dll,FileProtocolHandler");
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn default_open_url_command() -> Command {
+    Command::new("open")
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn default_open_url_command() -> Command {
+    Command::new("xdg-open")
+}
+
 pub struct PooledBuf(Vec<u8>);
 impl PooledBuf {
     pub fn as_bytes(&self) -> &[u8] {
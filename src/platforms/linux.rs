@@ -10,6 +10,7 @@ use std::{
 use pepper::{
     application::{ClientApplication, ServerApplication},
     client::ClientHandle,
+    editor_utils::LogLevel,
     platform::{Key, PlatformEvent, PlatformRequest, ProcessHandle},
     Args,
 };
@@ -17,7 +18,7 @@ use pepper::{
 mod unix_utils;
 use unix_utils::{
     get_terminal_size, is_pipped, parse_terminal_keys, read, read_from_connection, run,
-    suspend_process, Process, RawMode,
+    suspend_process, JobWaker, Process, RawMode, StdinFilter,
 };
 
 const MAX_CLIENT_COUNT: usize = 20;
@@ -159,9 +160,15 @@ fn run_server(args: Args, listener: UnixListener) {
     const CLIENTS_LAST_INDEX: usize = CLIENTS_START_INDEX + MAX_CLIENT_COUNT - 1;
     const PROCESSES_START_INDEX: usize = CLIENTS_LAST_INDEX + 1;
     const PROCESSES_LAST_INDEX: usize = PROCESSES_START_INDEX + MAX_PROCESS_COUNT - 1;
+    const PROCESS_ERRORS_START_INDEX: usize = PROCESSES_LAST_INDEX + 1;
+    const PROCESS_ERRORS_LAST_INDEX: usize = PROCESS_ERRORS_START_INDEX + MAX_PROCESS_COUNT - 1;
+    const JOBS_INDEX: usize = PROCESS_ERRORS_LAST_INDEX + 1;
+
+    let job_waker = JobWaker::new(&mut application.platform.jobs);
 
     let epoll = Epoll::new();
     epoll.add(listener.as_raw_fd(), 0);
+    epoll.add(job_waker.as_raw_fd(), JOBS_INDEX);
     let mut epoll_events = EpollEvents::new();
 
     loop {
@@ -222,13 +229,43 @@ fn run_server(args: Args, listener: UnixListener) {
                                 if let Some(fd) = process.try_as_raw_fd() {
                                     epoll.remove(fd);
                                 }
-                                process.kill();
-                                processes[index] = None;
-                                events.push(PlatformEvent::ProcessExit { tag });
+                                process.close_output();
+                                if !process.has_output() {
+                                    process.kill();
+                                    processes[index] = None;
+                                    events.push(PlatformEvent::ProcessExit { tag });
+                                }
+                            }
+                        }
+                    }
+                }
+                PROCESS_ERRORS_START_INDEX..=PROCESS_ERRORS_LAST_INDEX => {
+                    let index = event_index - PROCESS_ERRORS_START_INDEX;
+                    if let Some(ref mut process) = processes[index] {
+                        let tag = process.tag();
+                        match process.read_error(&mut application.platform.buf_pool) {
+                            Ok(None) => (),
+                            Ok(Some(buf)) => {
+                                events.push(PlatformEvent::ProcessErrorOutput { tag, buf })
+                            }
+                            Err(()) => {
+                                if let Some(fd) = process.try_error_as_raw_fd() {
+                                    epoll.remove(fd);
+                                }
+                                process.close_error_output();
+                                if !process.has_output() {
+                                    process.kill();
+                                    processes[index] = None;
+                                    events.push(PlatformEvent::ProcessExit { tag });
+                                }
                             }
                         }
                     }
                 }
+                JOBS_INDEX => {
+                    job_waker.clear();
+                    application.platform.take_finished_jobs(&mut events);
+                }
                 _ => unreachable!(),
             }
         }
@@ -278,14 +315,26 @@ fn run_server(args: Args, listener: UnixListener) {
                         }
 
                         let handle = ProcessHandle(i as _);
-                        if let Ok(child) = command.spawn() {
-                            let process = Process::new(child, tag, buf_len);
-                            if let Some(fd) = process.try_as_raw_fd() {
-                                epoll.add(fd, PROCESSES_START_INDEX + i);
+                        match command.spawn() {
+                            Ok(child) => {
+                                let process = Process::new(child, tag, buf_len);
+                                if let Some(fd) = process.try_as_raw_fd() {
+                                    epoll.add(fd, PROCESSES_START_INDEX + i);
+                                }
+                                if let Some(fd) = process.try_error_as_raw_fd() {
+                                    epoll.add(fd, PROCESS_ERRORS_START_INDEX + i);
+                                }
+                                *p = Some(process);
+                                events.push(PlatformEvent::ProcessSpawned { tag, handle });
+                                spawned = true;
                             }
-                            *p = Some(process);
-                            events.push(PlatformEvent::ProcessSpawned { tag, handle });
-                            spawned = true;
+                            Err(error) => application.platform.logger.write(LogLevel::Error).fmt(
+                                format_args!(
+                                    "could not spawn process '{}': {}",
+                                    command.get_program().to_string_lossy(),
+                                    error
+                                ),
+                            ),
                         }
                         break;
                     }
@@ -300,6 +349,9 @@ fn run_server(args: Args, listener: UnixListener) {
                             if let Some(fd) = process.try_as_raw_fd() {
                                 epoll.remove(fd);
                             }
+                            if let Some(fd) = process.try_error_as_raw_fd() {
+                                epoll.remove(fd);
+                            }
                             let tag = process.tag();
                             process.kill();
                             processes[index] = None;
@@ -319,6 +371,9 @@ fn run_server(args: Args, listener: UnixListener) {
                         if let Some(fd) = process.try_as_raw_fd() {
                             epoll.remove(fd);
                         }
+                        if let Some(fd) = process.try_error_as_raw_fd() {
+                            epoll.remove(fd);
+                        }
                         let tag = process.tag();
                         process.kill();
                         processes[index] = None;
@@ -334,9 +389,10 @@ fn run_server(args: Args, listener: UnixListener) {
     }
 }
 
-fn run_client(args: Args, mut connection: UnixStream) {
+fn run_client(mut args: Args, mut connection: UnixStream) {
     use io::{Read, Write};
 
+    let stdin_filter = StdinFilter::new(&mut args);
     let is_pipped = is_pipped();
     let mut application = ClientApplication::new(is_pipped);
     let bytes = application.init(args);
@@ -423,6 +479,9 @@ fn run_client(args: Args, mut connection: UnixStream) {
             if connection.write_all(bytes).is_err() {
                 break;
             }
+            if application.has_protocol_error() {
+                break 'main_loop;
+            }
             if suspend {
                 suspend_process(&mut application, &mut raw_mode);
             }
@@ -430,4 +489,13 @@ fn run_client(args: Args, mut connection: UnixStream) {
     }
 
     drop(raw_mode);
+
+    if let Some(stdin_filter) = stdin_filter {
+        stdin_filter.finish();
+    }
+
+    if application.has_command_errors() {
+        drop(application);
+        std::process::exit(1);
+    }
 }
@@ -2,10 +2,10 @@ use std::{
     env, fs, io,
     os::unix::{
         ffi::OsStrExt,
-        io::{AsRawFd, RawFd},
+        io::{AsRawFd, FromRawFd, RawFd},
         net::{UnixListener, UnixStream},
     },
-    path::Path,
+    path::{Path, PathBuf},
     process::Child,
     time::Duration,
 };
@@ -13,12 +13,17 @@ use std::{
 use pepper::{
     application::ClientApplication,
     editor_utils::hash_bytes,
-    platform::{BufPool, Key, PooledBuf, ProcessTag},
+    headless_test,
+    platform::{BufPool, JobPool, Key, PooledBuf, ProcessTag},
     Args,
 };
 
 pub fn run(server_fn: fn(Args, UnixListener), client_fn: fn(Args, UnixStream)) {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    if let Some(script_path) = args.headless_test.take() {
+        let success = headless_test::run(args, &script_path);
+        std::process::exit(if success { 0 } else { 1 });
+    }
 
     let mut session_path = String::new();
     session_path.push_str("/tmp/");
@@ -70,6 +75,9 @@ pub fn run(server_fn: fn(Args, UnixListener), client_fn: fn(Args, UnixStream)) {
             Err(_) => match unsafe { libc::fork() } {
                 -1 => panic!("could not start server"),
                 0 => {
+                    if args.stdin {
+                        redirect_stdio_to_null();
+                    }
                     server_fn(args, start_server(session_path));
                     let _ = fs::remove_file(session_path);
                 }
@@ -87,6 +95,19 @@ pub fn run(server_fn: fn(Args, UnixListener), client_fn: fn(Args, UnixStream)) {
     }
 }
 
+fn redirect_stdio_to_null() {
+    if let Ok(null) = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")
+    {
+        unsafe {
+            libc::dup2(null.as_raw_fd(), libc::STDIN_FILENO);
+            libc::dup2(null.as_raw_fd(), libc::STDOUT_FILENO);
+        }
+    }
+}
+
 pub struct RawMode {
     original: libc::termios,
 }
@@ -131,6 +152,69 @@ pub fn is_pipped() -> bool {
     unsafe { libc::isatty(libc::STDIN_FILENO) == 0 }
 }
 
+pub struct StdinFilter {
+    path: PathBuf,
+    output: Option<fs::File>,
+}
+impl StdinFilter {
+    pub fn new(args: &mut Args) -> Option<Self> {
+        use io::Read;
+
+        if !args.stdin || !is_pipped() {
+            return None;
+        }
+
+        let mut content = Vec::new();
+        if io::stdin().read_to_end(&mut content).is_err() {
+            panic!("could not read from stdin");
+        }
+
+        let mut path = env::temp_dir();
+        path.push(format!(
+            "{}-stdin-{}",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        if fs::write(&path, &content).is_err() {
+            panic!("could not write stdin to {:?}", path);
+        }
+
+        let tty = match fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+        {
+            Ok(tty) => tty,
+            Err(_) => panic!("could not open terminal"),
+        };
+
+        let output = unsafe {
+            libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO);
+            if libc::isatty(libc::STDOUT_FILENO) == 0 {
+                let output = libc::dup(libc::STDOUT_FILENO);
+                libc::dup2(tty.as_raw_fd(), libc::STDOUT_FILENO);
+                Some(fs::File::from_raw_fd(output))
+            } else {
+                None
+            }
+        };
+
+        args.files.push(path.to_string_lossy().into());
+        Some(Self { path, output })
+    }
+
+    pub fn finish(self) {
+        use io::Write;
+
+        if let Some(mut output) = self.output {
+            if let Ok(content) = fs::read(&self.path) {
+                let _ = output.write_all(&content);
+            }
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
 pub fn read(fd: RawFd, buf: &mut [u8]) -> Result<usize, ()> {
     let len = unsafe { libc::read(fd, buf.as_mut_ptr() as _, buf.len() as _) };
     if len >= 0 {
@@ -160,6 +244,35 @@ pub fn read_from_connection(
     }
 }
 
+pub struct JobWaker(UnixStream);
+impl JobWaker {
+    pub fn new(jobs: &mut JobPool) -> Self {
+        let (reader, writer) = UnixStream::pair().expect("could not create job waker");
+        reader
+            .set_nonblocking(true)
+            .expect("could not create job waker");
+        writer
+            .set_nonblocking(true)
+            .expect("could not create job waker");
+        jobs.set_waker(move || {
+            use io::Write;
+            let _ = (&writer).write(&[0]);
+        });
+        Self(reader)
+    }
+
+    pub fn clear(&self) {
+        use io::Read;
+        let mut buf = [0; 64];
+        while let Ok(1..) = (&self.0).read(&mut buf) {}
+    }
+}
+impl AsRawFd for JobWaker {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
 pub struct Process {
     alive: bool,
     child: Child,
@@ -184,13 +297,27 @@ impl Process {
         self.child.stdout.as_ref().map(|s| s.as_raw_fd())
     }
 
-    pub fn read(&mut self, buf_pool: &mut BufPool) -> Result<Option<PooledBuf>, ()> {
-        use io::Read;
-        match self.child.stdout {
-            Some(ref mut stdout) => {
+    pub fn try_error_as_raw_fd(&self) -> Option<RawFd> {
+        self.child.stderr.as_ref().map(|s| s.as_raw_fd())
+    }
+
+    pub fn has_output(&self) -> bool {
+        self.child.stdout.is_some() || self.child.stderr.is_some()
+    }
+
+    fn read_pipe<R>(
+        pipe: &mut Option<R>,
+        buf_len: usize,
+        buf_pool: &mut BufPool,
+    ) -> Result<Option<PooledBuf>, ()>
+    where
+        R: io::Read,
+    {
+        match pipe {
+            Some(ref mut pipe) => {
                 let mut buf = buf_pool.acquire();
-                let write = buf.write_with_len(self.buf_len);
-                match stdout.read(write) {
+                let write = buf.write_with_len(buf_len);
+                match pipe.read(write) {
                     Ok(0) | Err(_) => {
                         buf_pool.release(buf);
                         Err(())
@@ -205,6 +332,22 @@ impl Process {
         }
     }
 
+    pub fn read(&mut self, buf_pool: &mut BufPool) -> Result<Option<PooledBuf>, ()> {
+        Self::read_pipe(&mut self.child.stdout, self.buf_len, buf_pool)
+    }
+
+    pub fn read_error(&mut self, buf_pool: &mut BufPool) -> Result<Option<PooledBuf>, ()> {
+        Self::read_pipe(&mut self.child.stderr, self.buf_len, buf_pool)
+    }
+
+    pub fn close_output(&mut self) {
+        self.child.stdout = None;
+    }
+
+    pub fn close_error_output(&mut self) {
+        self.child.stderr = None;
+    }
+
     pub fn write(&mut self, buf: &[u8]) -> bool {
         use io::Write;
         match self.child.stdin {
@@ -282,7 +425,19 @@ pub fn parse_terminal_keys(mut buf: &[u8], backspace_code: u8, keys: &mut Vec<Ke
             | &[0x1b, b'[', b'8', b'~', ref rest @ ..]
             | &[0x1b, b'[', b'F', ref rest @ ..]
             | &[0x1b, b'O', b'F', ref rest @ ..] => (Key::End, rest),
+            &[0x1b, b'[', b'2', b'~', ref rest @ ..] => (Key::Insert, rest),
             &[0x1b, b'[', b'3', b'~', ref rest @ ..] => (Key::Delete, rest),
+            &[0x1b, b'O', b @ b'P'..=b'S', ref rest @ ..] => (Key::F(b - b'P' + 1), rest),
+            &[0x1b, b'[', b'1', b'5', b'~', ref rest @ ..] => (Key::F(5), rest),
+            &[0x1b, b'[', b'1', b @ b'7'..=b'9', b'~', ref rest @ ..] => {
+                (Key::F(b - b'7' + 6), rest)
+            }
+            &[0x1b, b'[', b'2', b @ b'0'..=b'1', b'~', ref rest @ ..] => {
+                (Key::F(b - b'0' + 9), rest)
+            }
+            &[0x1b, b'[', b'2', b @ b'3'..=b'4', b'~', ref rest @ ..] => {
+                (Key::F(b - b'3' + 11), rest)
+            }
             &[0x1b, ref rest @ ..] => (Key::Esc, rest),
             &[0x8, ref rest @ ..] => (Key::Backspace, rest),
             &[b'\r', ref rest @ ..] => (Key::Enter, rest),
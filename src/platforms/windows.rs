@@ -30,7 +30,7 @@ use winapi::{
             CreateProcessW, GetCurrentProcessId, PROCESS_INFORMATION, STARTUPINFOW,
         },
         stringapiset::{MultiByteToWideChar, WideCharToMultiByte},
-        synchapi::{CreateEventW, SetEvent, Sleep, WaitForMultipleObjects},
+        synchapi::{CreateEventW, ResetEvent, SetEvent, Sleep, WaitForMultipleObjects},
         sysinfoapi::GetSystemDirectoryW,
         winbase::{
             GlobalAlloc, GlobalFree, GlobalLock, GlobalUnlock, FILE_FLAG_OVERLAPPED,
@@ -51,7 +51,7 @@ use winapi::{
         winuser::{
             CloseClipboard, EmptyClipboard, GetClipboardData, OpenClipboard, SetClipboardData,
             CF_UNICODETEXT, VK_BACK, VK_DELETE, VK_DOWN, VK_END, VK_ESCAPE, VK_F1, VK_F24, VK_HOME,
-            VK_LEFT, VK_NEXT, VK_PRIOR, VK_RETURN, VK_RIGHT, VK_SPACE, VK_TAB, VK_UP,
+            VK_INSERT, VK_LEFT, VK_NEXT, VK_PRIOR, VK_RETURN, VK_RIGHT, VK_SPACE, VK_TAB, VK_UP,
         },
     },
 };
@@ -59,7 +59,8 @@ use winapi::{
 use pepper::{
     application::{ClientApplication, ServerApplication},
     client::ClientHandle,
-    editor_utils::hash_bytes,
+    editor_utils::{hash_bytes, LogLevel},
+    headless_test,
     platform::{
         BufPool, Key, PlatformEvent, PlatformRequest, PooledBuf, ProcessHandle, ProcessTag,
     },
@@ -67,8 +68,8 @@ use pepper::{
 };
 
 const MAX_CLIENT_COUNT: usize = 20;
-const MAX_PROCESS_COUNT: usize = 43;
-const MAX_EVENT_COUNT: usize = 1 + MAX_CLIENT_COUNT + MAX_PROCESS_COUNT;
+const MAX_PROCESS_COUNT: usize = 21;
+const MAX_EVENT_COUNT: usize = 2 + MAX_CLIENT_COUNT + 2 * MAX_PROCESS_COUNT;
 const _ASSERT_MAX_EVENT_COUNT_IS_MAX_WAIT_OBJECTS: [(); MAXIMUM_WAIT_OBJECTS as _] =
     [(); MAX_EVENT_COUNT];
 
@@ -142,7 +143,11 @@ pub fn try_launching_debugger() {
 }
 
 pub fn main() {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    if let Some(script_path) = args.headless_test.take() {
+        let success = headless_test::run(args, &script_path);
+        std::process::exit(if success { 0 } else { 1 });
+    }
 
     let mut pipe_path = Vec::new();
     let mut hash_buf = [0u8; 16];
@@ -576,6 +581,12 @@ impl Event {
     pub fn notify(&self) {
         set_event(self.0);
     }
+
+    pub fn reset(&self) {
+        if unsafe { ResetEvent(self.0) } == FALSE {
+            panic!("could not reset event");
+        }
+    }
 }
 impl Drop for Event {
     fn drop(&mut self) {
@@ -823,26 +834,34 @@ struct AsyncProcess {
     child: Child,
     tag: ProcessTag,
     pub stdout: Option<ProcessPipe>,
+    pub stderr: Option<ProcessPipe>,
 }
 impl AsyncProcess {
     pub fn new(mut child: Child, tag: ProcessTag, buf_len: usize) -> Self {
-        let stdout = child
-            .stdout
-            .take()
-            .map(IntoRawHandle::into_raw_handle)
-            .map(|h| {
-                let reader = AsyncReader::new(Handle(h as _));
-                ProcessPipe::new(reader, buf_len)
-            });
+        fn to_pipe<H>(handle: H, buf_len: usize) -> ProcessPipe
+        where
+            H: IntoRawHandle,
+        {
+            let reader = AsyncReader::new(Handle(handle.into_raw_handle() as _));
+            ProcessPipe::new(reader, buf_len)
+        }
+
+        let stdout = child.stdout.take().map(|h| to_pipe(h, buf_len));
+        let stderr = child.stderr.take().map(|h| to_pipe(h, buf_len));
 
         Self {
             alive: true,
             child,
             tag,
             stdout,
+            stderr,
         }
     }
 
+    pub fn has_output(&self) -> bool {
+        self.stdout.is_some() || self.stderr.is_some()
+    }
+
     pub fn write(&mut self, buf: &[u8]) -> bool {
         use io::Write;
         match &mut self.child.stdin {
@@ -859,6 +878,9 @@ impl AsyncProcess {
         if let Some(buf) = self.stdout.take().and_then(|p| p.current_buf) {
             buf_pool.release(buf);
         }
+        if let Some(buf) = self.stderr.take().and_then(|p| p.current_buf) {
+            buf_pool.release(buf);
+        }
     }
 
     pub fn kill(&mut self) {
@@ -868,6 +890,7 @@ impl AsyncProcess {
 
         self.alive = false;
         self.stdout = None;
+        self.stderr = None;
         let _ = self.child.kill();
         let _ = self.child.wait();
     }
@@ -883,6 +906,8 @@ enum EventSource {
     ConnectionListener,
     Connection(usize),
     Process(usize),
+    ProcessError(usize),
+    Jobs,
 }
 struct EventListener {
     wait_handles: [HANDLE; MAX_EVENT_COUNT],
@@ -932,6 +957,17 @@ fn run_server(args: Args, pipe_path: &[u16]) {
         .platform
         .set_clipboard_api(read_from_clipboard, write_to_clipboard);
 
+    struct JobsEventHandle(HANDLE);
+    unsafe impl Send for JobsEventHandle {}
+    unsafe impl Sync for JobsEventHandle {}
+
+    let jobs_event = Event::manual();
+    let jobs_event_handle = JobsEventHandle(jobs_event.handle());
+    application
+        .platform
+        .jobs
+        .set_waker(move || set_event(jobs_event_handle.0));
+
     let mut client_connections: [Option<ConnectionToClient>; MAX_CLIENT_COUNT] = Default::default();
 
     const NONE_ASYNC_PROCESS: Option<AsyncProcess> = None;
@@ -942,6 +978,7 @@ fn run_server(args: Args, pipe_path: &[u16]) {
 
     loop {
         event_listener.track(listener.event(), EventSource::ConnectionListener);
+        event_listener.track(&jobs_event, EventSource::Jobs);
         for (i, connection) in client_connections.iter().enumerate() {
             if let Some(connection) = connection {
                 event_listener.track(connection.event(), EventSource::Connection(i));
@@ -952,6 +989,9 @@ fn run_server(args: Args, pipe_path: &[u16]) {
                 if let Some(stdout) = &process.stdout {
                     event_listener.track(stdout.event(), EventSource::Process(i));
                 }
+                if let Some(stderr) = &process.stderr {
+                    event_listener.track(stderr.event(), EventSource::ProcessError(i));
+                }
             }
         }
 
@@ -1022,10 +1062,21 @@ fn run_server(args: Args, pipe_path: &[u16]) {
                                 }
 
                                 let handle = ProcessHandle(i as _);
-                                if let Ok(child) = command.spawn() {
-                                    *p = Some(AsyncProcess::new(child, tag, buf_len));
-                                    events.push(PlatformEvent::ProcessSpawned { tag, handle });
-                                    spawned = true;
+                                match command.spawn() {
+                                    Ok(child) => {
+                                        *p = Some(AsyncProcess::new(child, tag, buf_len));
+                                        events.push(PlatformEvent::ProcessSpawned { tag, handle });
+                                        spawned = true;
+                                    }
+                                    Err(error) => {
+                                        application.platform.logger.write(LogLevel::Error).fmt(
+                                            format_args!(
+                                                "could not spawn process '{}': {}",
+                                                command.get_program().to_string_lossy(),
+                                                error
+                                            ),
+                                        )
+                                    }
                                 }
                                 break;
                             }
@@ -1110,14 +1161,41 @@ fn run_server(args: Args, pipe_path: &[u16]) {
                             Ok(Some(buf)) => events.push(PlatformEvent::ProcessOutput { tag, buf }),
                             Err(()) => {
                                 process.stdout = None;
-                                process.kill();
-                                processes[i] = None;
-                                events.push(PlatformEvent::ProcessExit { tag });
+                                if !process.has_output() {
+                                    process.kill();
+                                    processes[i] = None;
+                                    events.push(PlatformEvent::ProcessExit { tag });
+                                }
                             }
                         }
                     }
                 }
             }
+            EventSource::ProcessError(i) => {
+                if let Some(process) = &mut processes[i] {
+                    if let Some(pipe) = &mut process.stderr {
+                        let tag = process.tag;
+                        match pipe.read_async(&mut application.platform.buf_pool) {
+                            Ok(None) => (),
+                            Ok(Some(buf)) => {
+                                events.push(PlatformEvent::ProcessErrorOutput { tag, buf })
+                            }
+                            Err(()) => {
+                                process.stderr = None;
+                                if !process.has_output() {
+                                    process.kill();
+                                    processes[i] = None;
+                                    events.push(PlatformEvent::ProcessExit { tag });
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            EventSource::Jobs => {
+                jobs_event.reset();
+                application.platform.take_finished_jobs(&mut events);
+            }
         }
     }
 }
@@ -1298,13 +1376,18 @@ fn run_client(args: Args, pipe_path: &[u16], input_handle: Handle, output_handle
         }
 
         let (_, bytes) = application.update(resize, &keys, stdin_bytes, server_bytes);
-        if !connection.write(bytes) {
+        if !connection.write(bytes) || application.has_protocol_error() {
             break;
         }
     }
 
     drop(console_input_mode);
     drop(console_output_mode);
+
+    if application.has_command_errors() {
+        drop(application);
+        std::process::exit(1);
+    }
 }
 
 fn parse_console_events(
@@ -1339,6 +1422,7 @@ fn parse_console_events(
                     VK_PRIOR => Key::PageUp,
                     VK_NEXT => Key::PageDown,
                     VK_TAB => Key::Tab,
+                    VK_INSERT => Key::Insert,
                     VK_DELETE => Key::Delete,
                     VK_F1..=VK_F24 => Key::F((keycode - VK_F1 + 1) as _),
                     VK_ESCAPE => Key::Esc,
@@ -0,0 +1,679 @@
+use std::{
+    fmt::{self, Write},
+    mem,
+};
+
+use crate::{
+    buffer::BufferHandle,
+    buffer_position::{BufferPosition, BufferRange},
+    client::{ClientHandle, ClientManager},
+    command::CommandManager,
+    editor::{Editor, EditorControlFlow},
+    editor_utils::MessageKind,
+    events::{EditorEvent, EditorEventIter},
+    mode::{Mode, ModeContext, ModeKind},
+    platform::{Key, Platform},
+    script::{ScriptError, ScriptHost, Value},
+    theme::Color,
+};
+
+pub mod manager;
+pub mod remote;
+
+const DEFAULT_PANEL_HEIGHT: u16 = 10;
+
+pub static HOST_FUNCTION_NAMES: &[&str] = &[
+    "decorate",
+    "clear_decorations",
+    "sign",
+    "remove_sign",
+    "clear_signs",
+    "workspace_root",
+    "workspace_roots",
+];
+
+pub static EVENT_NAMES: &[&str] = &[
+    "idle",
+    "buffer-read",
+    "buffer-insert-text",
+    "buffer-delete-text",
+    "buffer-write",
+    "buffer-close",
+    "buffer-view-lost-focus",
+    "process-exit",
+    "mode-change",
+    "client-focus",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PanelPosition {
+    Bottom,
+    Overlay,
+}
+
+pub struct PanelLine {
+    pub text: String,
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+}
+
+pub struct Panel {
+    pub name: String,
+    pub position: PanelPosition,
+    pub height: u16,
+    pub mode: String,
+    pub lines: Vec<PanelLine>,
+}
+
+#[derive(Default)]
+pub struct StatusSegments {
+    text: String,
+}
+impl StatusSegments {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[derive(Default)]
+pub struct PanelCollection {
+    panels: Vec<Panel>,
+}
+impl PanelCollection {
+    pub fn iter(&self) -> impl Iterator<Item = &Panel> {
+        self.panels.iter()
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Panel> {
+        self.panels.iter().find(|p| p.name == name)
+    }
+
+    pub fn bottom_height(&self, viewport_height: u16) -> u16 {
+        let height = self
+            .panels
+            .iter()
+            .filter(|p| p.position == PanelPosition::Bottom)
+            .map(|p| p.height)
+            .sum::<u16>();
+        height.min(viewport_height / 2)
+    }
+}
+
+struct EditorScriptHost<'state> {
+    editor: &'state mut Editor,
+    platform: &'state mut Platform,
+    clients: &'state mut ClientManager,
+    client_handle: Option<ClientHandle>,
+    output: String,
+    flow: EditorControlFlow,
+}
+impl<'state> ScriptHost for EditorScriptHost<'state> {
+    fn print(&mut self, message: &str) {
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        self.output.push_str(message);
+    }
+
+    fn command(&mut self, command: &str) -> Result<(), String> {
+        let mut command = self.editor.string_pool.acquire_with(command);
+        let result = CommandManager::try_eval(
+            self.editor,
+            self.platform,
+            self.clients,
+            self.client_handle,
+            &mut command,
+        );
+        self.editor.string_pool.release(command);
+        match result {
+            Ok(EditorControlFlow::Continue) => Ok(()),
+            Ok(flow) => {
+                self.flow = flow;
+                Ok(())
+            }
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    fn is_event(&self, name: &str) -> bool {
+        EVENT_NAMES.contains(&name)
+    }
+
+    fn functions(&self) -> &'static [&'static str] {
+        HOST_FUNCTION_NAMES
+    }
+
+    fn call(&mut self, function: &'static str, args: &[Value]) -> Result<Value, String> {
+        let arg = |index: usize| args.get(index).cloned().unwrap_or(Value::Nil);
+        let buffer_arg = |editor: &Editor, index: usize| match arg(index).as_number() {
+            Some(n) if n >= 0.0 => {
+                let handle = BufferHandle(n as _);
+                if editor.buffers.iter().any(|b| b.handle() == handle) {
+                    Ok(handle)
+                } else {
+                    Err(String::from("no such buffer"))
+                }
+            }
+            _ => Err(format!("expected a buffer as argument {}", index + 1)),
+        };
+        let namespace_arg = |index: usize| match arg(index) {
+            Value::String(namespace) => Ok(namespace),
+            _ => Err(format!("expected a namespace as argument {}", index + 1)),
+        };
+
+        match function {
+            "decorate" => {
+                let buffer_handle = buffer_arg(self.editor, 0)?;
+                let namespace = namespace_arg(1)?;
+                let decoration = arg(2);
+
+                let position = |line: &str, column: &str| {
+                    let line = decoration.field(line).as_number().unwrap_or(0.0);
+                    let column = decoration.field(column).as_number().unwrap_or(0.0);
+                    BufferPosition::line_col(line.max(0.0) as _, column.max(0.0) as _)
+                };
+                let from = position("from_line", "from_column");
+                let to = match decoration.field("to_line") {
+                    Value::Nil => from,
+                    _ => position("to_line", "to_column"),
+                };
+                let color = match decoration.field("color").as_str() {
+                    Some(name) => match self.editor.theme.color_from_name(name) {
+                        Some(color) => Some(*color),
+                        None => return Err(format!("no such color '{}'", name)),
+                    },
+                    None => None,
+                };
+                let underlined = matches!(decoration.field("underline"), Value::Bool(true));
+                let text = decoration.field("text");
+                let text = text.as_str().unwrap_or("");
+
+                self.editor.decorations.add(
+                    buffer_handle,
+                    &namespace,
+                    BufferRange::between(from, to),
+                    color,
+                    underlined,
+                    text,
+                );
+                Ok(Value::Nil)
+            }
+            "clear_decorations" => {
+                let buffer_handle = match arg(0) {
+                    Value::Nil => None,
+                    _ => Some(buffer_arg(self.editor, 0)?),
+                };
+                let namespace = namespace_arg(1)?;
+                self.editor.decorations.clear(buffer_handle, &namespace);
+                Ok(Value::Nil)
+            }
+            "sign" => {
+                let buffer_handle = buffer_arg(self.editor, 0)?;
+                let namespace = namespace_arg(1)?;
+                let line = arg(2).as_number().unwrap_or(0.0).max(0.0) as _;
+                let name = arg(3);
+                let name = name.as_str().unwrap_or("");
+                if self.editor.signs.add(buffer_handle, &namespace, line, name) {
+                    Ok(Value::Nil)
+                } else {
+                    Err(format!("no such sign '{}'", name))
+                }
+            }
+            "remove_sign" => {
+                let buffer_handle = buffer_arg(self.editor, 0)?;
+                let namespace = namespace_arg(1)?;
+                let line = arg(2).as_number().unwrap_or(0.0).max(0.0) as _;
+                self.editor.signs.remove(buffer_handle, &namespace, line);
+                Ok(Value::Nil)
+            }
+            "clear_signs" => {
+                let buffer_handle = match arg(0) {
+                    Value::Nil => None,
+                    _ => Some(buffer_arg(self.editor, 0)?),
+                };
+                let namespace = namespace_arg(1)?;
+                self.editor.signs.clear(buffer_handle, &namespace);
+                Ok(Value::Nil)
+            }
+            "workspace_root" => {
+                let buffer_handle = match arg(0) {
+                    Value::Nil => None,
+                    _ => Some(buffer_arg(self.editor, 0)?),
+                };
+                let root = self.editor.workspace_root(buffer_handle);
+                Ok(Value::string(root.to_str().unwrap_or("")))
+            }
+            "workspace_roots" => {
+                let roots = self
+                    .editor
+                    .workspace
+                    .roots()
+                    .iter()
+                    .map(|r| Value::string(r.to_str().unwrap_or("")))
+                    .collect();
+                Ok(Value::list(roots))
+            }
+            _ => Err(format!("no such function '{}'", function)),
+        }
+    }
+}
+
+pub fn eval_script(
+    editor: &mut Editor,
+    platform: &mut Platform,
+    clients: &mut ClientManager,
+    client_handle: Option<ClientHandle>,
+    source: &str,
+) -> Result<EditorControlFlow, ScriptError> {
+    let engine = editor.script.clone();
+    let mut host = EditorScriptHost {
+        editor,
+        platform,
+        clients,
+        client_handle,
+        output: String::new(),
+        flow: EditorControlFlow::Continue,
+    };
+    let result = engine.eval(source, &mut host);
+    let EditorScriptHost { output, flow, .. } = host;
+
+    if !output.is_empty() {
+        editor.status_bar.write(MessageKind::Info).str(&output);
+    }
+    result.map(|_| flow)
+}
+
+pub fn on_editor_events(editor: &mut Editor, platform: &mut Platform, clients: &mut ClientManager) {
+    remote::RemotePluginCollection::on_editor_events(editor, platform);
+
+    let engine = editor.script.clone();
+    let client_handle = clients.focused_client();
+
+    let mut events = EditorEventIter::new();
+    while let Some(event) = events.next(&editor.events) {
+        let name = event_name(event);
+        if !engine.has_hooks(name) {
+            continue;
+        }
+        let arg = Value::table(event_fields(editor, event));
+
+        let mut host = EditorScriptHost {
+            editor,
+            platform,
+            clients,
+            client_handle,
+            output: String::new(),
+            flow: EditorControlFlow::Continue,
+        };
+        let result = engine.trigger(name, arg, &mut host);
+        let output = host.output;
+
+        match result {
+            Ok(()) => {
+                if !output.is_empty() {
+                    editor.status_bar.write(MessageKind::Info).str(&output);
+                }
+            }
+            Err(error) => editor
+                .status_bar
+                .write(MessageKind::Error)
+                .fmt(format_args!("{} hook: {}", name, error)),
+        }
+    }
+}
+
+pub fn toggle_panel(editor: &mut Editor, name: &str) -> bool {
+    let panels = &mut editor.panels.panels;
+    if let Some(index) = panels.iter().position(|p| p.name == name) {
+        panels.remove(index);
+        return true;
+    }
+    if editor.script.definition("panel", name).is_none() {
+        return false;
+    }
+    panels.push(Panel {
+        name: name.into(),
+        position: PanelPosition::Bottom,
+        height: 0,
+        mode: String::new(),
+        lines: Vec::new(),
+    });
+    true
+}
+
+pub fn on_pre_render(editor: &mut Editor, platform: &mut Platform, clients: &mut ClientManager) {
+    render_panels(editor, platform, clients);
+    render_status_segments(editor, platform, clients);
+}
+
+fn render_status_segments(
+    editor: &mut Editor,
+    platform: &mut Platform,
+    clients: &mut ClientManager,
+) {
+    let engine = editor.script.clone();
+    let mut segments = engine.definitions("status-segment");
+    let mut text = mem::take(&mut editor.status_segments.text);
+    text.clear();
+    if segments.is_empty() {
+        editor.status_segments.text = text;
+        return;
+    }
+
+    if !editor.config.status_segments.is_empty() {
+        let mut arranged = Vec::with_capacity(segments.len());
+        for name in editor.config.status_segments.split_whitespace() {
+            if let Some(index) = segments.iter().position(|(n, _)| &**n == name) {
+                arranged.push(segments.swap_remove(index));
+            }
+        }
+        segments = arranged;
+    }
+
+    let client_handle = clients.focused_client();
+    for (name, function) in segments {
+        let mut host = EditorScriptHost {
+            editor,
+            platform,
+            clients,
+            client_handle,
+            output: String::new(),
+            flow: EditorControlFlow::Continue,
+        };
+        let result = engine.call(function, Vec::new(), &mut host);
+        let output = host.output;
+
+        match result {
+            Ok(Value::Nil) => (),
+            Ok(value) => {
+                let value = value.to_string();
+                if value.is_empty() {
+                    continue;
+                }
+                if !text.is_empty() {
+                    text.push(' ');
+                }
+                text.extend(value.chars().map(|c| if c.is_control() { ' ' } else { c }));
+            }
+            Err(error) => editor
+                .status_bar
+                .write(MessageKind::Error)
+                .fmt(format_args!("{} status segment: {}", name, error)),
+        }
+        if !output.is_empty() {
+            editor.status_bar.write(MessageKind::Info).str(&output);
+        }
+    }
+
+    editor.status_segments.text = text;
+}
+
+fn render_panels(editor: &mut Editor, platform: &mut Platform, clients: &mut ClientManager) {
+    if editor.panels.panels.is_empty() {
+        return;
+    }
+
+    let engine = editor.script.clone();
+    let client_handle = clients.focused_client();
+
+    let mut i = 0;
+    while i < editor.panels.panels.len() {
+        let definition = match engine.definition("panel", &editor.panels.panels[i].name) {
+            Some(definition) => definition,
+            None => {
+                editor.panels.panels.remove(i);
+                continue;
+            }
+        };
+
+        let mut host = EditorScriptHost {
+            editor,
+            platform,
+            clients,
+            client_handle,
+            output: String::new(),
+            flow: EditorControlFlow::Continue,
+        };
+        let result = engine.call(definition.field("render"), Vec::new(), &mut host);
+        let output = host.output;
+
+        let lines = match result {
+            Ok(lines) => lines.array(),
+            Err(error) => {
+                report_script_result(editor, Err(error), &output);
+                Vec::new()
+            }
+        };
+
+        let theme = &mut editor.theme;
+        let mut color = |value: Value| {
+            let name = value.as_str()?;
+            theme.color_from_name(name).map(|c| *c)
+        };
+
+        let panel = &mut editor.panels.panels[i];
+        panel.position = match definition.field("position").as_str() {
+            Some("overlay") => PanelPosition::Overlay,
+            _ => PanelPosition::Bottom,
+        };
+        panel.height = match definition.field("size").as_number() {
+            Some(size) => size.max(1.0) as _,
+            None => DEFAULT_PANEL_HEIGHT,
+        };
+        panel.mode.clear();
+        if let Some(mode) = definition.field("mode").as_str() {
+            panel.mode.push_str(mode);
+        }
+        panel.lines.clear();
+        for line in lines {
+            let line = match line {
+                Value::Table(_) => PanelLine {
+                    text: line.field("text").to_string(),
+                    foreground: color(line.field("color")),
+                    background: color(line.field("background")),
+                },
+                line => PanelLine {
+                    text: line.to_string(),
+                    foreground: None,
+                    background: None,
+                },
+            };
+            panel.lines.push(line);
+        }
+
+        i += 1;
+    }
+}
+
+pub fn enter_mode(ctx: &mut ModeContext, name: &str) -> bool {
+    if ctx.editor.script.definition("mode", name).is_none() {
+        return false;
+    }
+    if ctx.editor.mode.kind() == ModeKind::Plugin {
+        Mode::change_to(ctx, ModeKind::default());
+    }
+    let mode_name = &mut ctx.editor.mode.plugin_state.name;
+    mode_name.clear();
+    mode_name.push_str(name);
+    Mode::change_to(ctx, ModeKind::Plugin);
+    true
+}
+
+pub fn on_mode_enter(ctx: &mut ModeContext) {
+    call_mode_callback(ctx, "on_enter");
+}
+
+pub fn on_mode_exit(ctx: &mut ModeContext) {
+    call_mode_callback(ctx, "on_exit");
+}
+
+fn call_mode_callback(ctx: &mut ModeContext, callback: &str) {
+    let engine = ctx.editor.script.clone();
+    let function = match engine.definition("mode", &ctx.editor.mode.plugin_state.name) {
+        Some(mode) => mode.field(callback),
+        None => return,
+    };
+    if let Value::Nil = function {
+        return;
+    }
+
+    let mut host = EditorScriptHost {
+        editor: ctx.editor,
+        platform: ctx.platform,
+        clients: ctx.clients,
+        client_handle: Some(ctx.client_handle),
+        output: String::new(),
+        flow: EditorControlFlow::Continue,
+    };
+    let result = engine.call(function, Vec::new(), &mut host);
+    let output = host.output;
+    report_script_result(ctx.editor, result.map(|_| ()), &output);
+}
+
+pub fn on_mode_key(ctx: &mut ModeContext, key: Key) -> Option<EditorControlFlow> {
+    let engine = ctx.editor.script.clone();
+    let mode = match engine.definition("mode", &ctx.editor.mode.plugin_state.name) {
+        Some(mode) => mode,
+        None => {
+            Mode::change_to(ctx, ModeKind::default());
+            return Some(EditorControlFlow::Continue);
+        }
+    };
+
+    let mut key_name = ctx.editor.string_pool.acquire();
+    let _ = write!(key_name, "{}", key);
+    let handler = match mode.field("keys").field(&key_name) {
+        Value::Nil => mode.field("on_key"),
+        handler => handler,
+    };
+
+    let mut host = EditorScriptHost {
+        editor: ctx.editor,
+        platform: ctx.platform,
+        clients: ctx.clients,
+        client_handle: Some(ctx.client_handle),
+        output: String::new(),
+        flow: EditorControlFlow::Continue,
+    };
+    let is_unhandled = matches!(handler, Value::Nil);
+    let result = match handler {
+        Value::Nil => Ok(()),
+        Value::String(command) => host.command(&command),
+        function => engine
+            .call(function, vec![Value::string(&key_name)], &mut host)
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+    };
+    let EditorScriptHost { output, flow, .. } = host;
+
+    ctx.editor.string_pool.release(key_name);
+    report_script_result(ctx.editor, result, &output);
+
+    if is_unhandled && key == Key::Esc {
+        Mode::change_to(ctx, ModeKind::default());
+    }
+    Some(flow)
+}
+
+fn report_script_result<E>(editor: &mut Editor, result: Result<(), E>, output: &str)
+where
+    E: fmt::Display,
+{
+    match result {
+        Ok(()) => {
+            if !output.is_empty() {
+                editor.status_bar.write(MessageKind::Info).str(output);
+            }
+        }
+        Err(error) => editor
+            .status_bar
+            .write(MessageKind::Error)
+            .fmt(format_args!("{}", error)),
+    }
+}
+
+fn event_name(event: &EditorEvent) -> &'static str {
+    match event {
+        EditorEvent::Idle => "idle",
+        EditorEvent::BufferRead { .. } => "buffer-read",
+        EditorEvent::BufferInsertText { .. } => "buffer-insert-text",
+        EditorEvent::BufferDeleteText { .. } => "buffer-delete-text",
+        EditorEvent::BufferWrite { .. } => "buffer-write",
+        EditorEvent::BufferClose { .. } => "buffer-close",
+        EditorEvent::FixCursors { .. } => "",
+        EditorEvent::BufferViewLostFocus { .. } => "buffer-view-lost-focus",
+        EditorEvent::BufferProcessExit { .. } => "process-exit",
+        EditorEvent::ModeChange { .. } => "mode-change",
+        EditorEvent::ClientFocus { .. } => "client-focus",
+    }
+}
+
+fn event_fields(editor: &Editor, event: &EditorEvent) -> Vec<(&'static str, Value)> {
+    fn buffer_fields(editor: &Editor, handle: BufferHandle) -> Vec<(&'static str, Value)> {
+        let path = editor.buffers.get(handle).path.to_str().unwrap_or("");
+        vec![
+            ("buffer", Value::Number(handle.0 as _)),
+            ("path", Value::string(path)),
+        ]
+    }
+
+    fn range_fields(fields: &mut Vec<(&'static str, Value)>, range: BufferRange) {
+        fields.push(("from_line", Value::Number(range.from.line_index as _)));
+        fields.push((
+            "from_column",
+            Value::Number(range.from.column_byte_index as _),
+        ));
+        fields.push(("to_line", Value::Number(range.to.line_index as _)));
+        fields.push(("to_column", Value::Number(range.to.column_byte_index as _)));
+    }
+
+    match *event {
+        EditorEvent::Idle | EditorEvent::FixCursors { .. } => Vec::new(),
+        EditorEvent::BufferRead { handle }
+        | EditorEvent::BufferClose { handle }
+        | EditorEvent::BufferProcessExit { handle } => buffer_fields(editor, handle),
+        EditorEvent::BufferInsertText {
+            handle,
+            range,
+            text,
+        } => {
+            let mut fields = buffer_fields(editor, handle);
+            range_fields(&mut fields, range);
+            fields.push(("text", Value::string(text.as_str(&editor.events))));
+            fields
+        }
+        EditorEvent::BufferDeleteText { handle, range } => {
+            let mut fields = buffer_fields(editor, handle);
+            range_fields(&mut fields, range);
+            fields
+        }
+        EditorEvent::BufferWrite { handle, new_path } => {
+            let mut fields = buffer_fields(editor, handle);
+            fields.push(("new_path", Value::Bool(new_path)));
+            fields
+        }
+        EditorEvent::BufferViewLostFocus { handle } => {
+            let buffer_handle = editor.buffer_views.get(handle).buffer_handle;
+            buffer_fields(editor, buffer_handle)
+        }
+        EditorEvent::ModeChange {
+            kind,
+            previous,
+            client,
+        } => {
+            let mut fields = vec![
+                ("mode", Value::string(kind.name())),
+                ("previous_mode", Value::string(previous.name())),
+                ("client", Value::Number(client.into_index() as _)),
+            ];
+            if let ModeKind::Plugin = kind {
+                let name = &editor.mode.plugin_state.name;
+                fields.push(("name", Value::string(name)));
+            }
+            fields
+        }
+        EditorEvent::ClientFocus { handle } => {
+            vec![("client", Value::Number(handle.into_index() as _))]
+        }
+    }
+}
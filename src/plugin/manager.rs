@@ -0,0 +1,381 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    client::ClientManager,
+    editor::{Editor, EditorControlFlow},
+    editor_utils::{load_config, MessageKind},
+    git::{self, GitError},
+    platform::{Platform, PlatformRequest, ProcessTag},
+    profile::{begin_startup_item, end_startup_item},
+};
+
+pub const PLUGIN_ENTRY_FILE_NAME: &str = "plugin.pp";
+const DISABLED_MARKER_FILE_NAME: &str = ".disabled";
+const VERSION_FILE_NAME: &str = "version";
+
+pub enum PluginManagerError {
+    NoPluginDirectory,
+    NoSuchPlugin,
+    AlreadyInstalled,
+    InvalidSource,
+    IoError(io::Error),
+    GitError(GitError),
+}
+impl fmt::Display for PluginManagerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoPluginDirectory => f.write_str("config 'plugin_directory' is not set"),
+            Self::NoSuchPlugin => f.write_str("no such plugin"),
+            Self::AlreadyInstalled => f.write_str("plugin already installed"),
+            Self::InvalidSource => f.write_str("invalid plugin source"),
+            Self::IoError(error) => error.fmt(f),
+            Self::GitError(error) => error.fmt(f),
+        }
+    }
+}
+
+pub struct PluginEntry {
+    pub name: String,
+    pub enabled: bool,
+    pub version: String,
+}
+
+struct PluginInstall {
+    alive: bool,
+    name: String,
+    path: PathBuf,
+    error_output: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct PluginManager {
+    loaded: Vec<String>,
+    loading_directory: Option<PathBuf>,
+    installs: Vec<PluginInstall>,
+}
+impl PluginManager {
+    pub fn is_loaded(&self, name: &str) -> bool {
+        self.loaded.iter().any(|n| n == name)
+    }
+
+    pub fn loading_directory(&self) -> Option<&Path> {
+        self.loading_directory.as_deref()
+    }
+
+    pub fn on_process_error_output(&mut self, index: usize, bytes: &[u8]) {
+        self.installs[index].error_output.extend_from_slice(bytes);
+    }
+
+    pub fn on_process_exit(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        clients: &mut ClientManager,
+        index: usize,
+    ) {
+        let install = &mut editor.plugin_manager.installs[index];
+        install.alive = false;
+        let name = std::mem::take(&mut install.name);
+        let path = std::mem::take(&mut install.path);
+        let error_output = std::mem::take(&mut install.error_output);
+
+        if !path.join(PLUGIN_ENTRY_FILE_NAME).is_file() {
+            let _ = fs::remove_dir_all(&path);
+            let error_output = String::from_utf8_lossy(&error_output);
+            let error = error_output
+                .trim()
+                .lines()
+                .last()
+                .unwrap_or("invalid plugin source");
+            editor
+                .status_bar
+                .write(MessageKind::Error)
+                .fmt(format_args!(
+                    "could not install plugin '{}': {}",
+                    name, error
+                ));
+            return;
+        }
+
+        if let Err(error) = finish_install(editor, platform, clients, &name, &path) {
+            editor
+                .status_bar
+                .write(MessageKind::Error)
+                .fmt(format_args!("plugin '{}': {}", name, error));
+        }
+    }
+}
+
+fn plugin_directory(editor: &Editor) -> Result<PathBuf, PluginManagerError> {
+    if editor.config.plugin_directory.is_empty() {
+        return Err(PluginManagerError::NoPluginDirectory);
+    }
+    Ok(editor
+        .current_directory
+        .join(&editor.config.plugin_directory))
+}
+
+fn find_plugin(editor: &Editor, name: &str) -> Result<PathBuf, PluginManagerError> {
+    let path = plugin_directory(editor)?.join(name);
+    if name.is_empty() || name.contains(['/', '\\']) || !path.is_dir() {
+        return Err(PluginManagerError::NoSuchPlugin);
+    }
+    Ok(path)
+}
+
+pub fn plugin_version(path: &Path) -> String {
+    let mut output = String::new();
+    if path.join(".git").exists()
+        && git::run(path, &["rev-parse", "--short", "HEAD"], None, &mut output).is_ok()
+    {
+        output.truncate(output.trim_end().len());
+        return output;
+    }
+
+    match fs::read_to_string(path.join(VERSION_FILE_NAME)) {
+        Ok(version) => version.trim().into(),
+        Err(_) => String::new(),
+    }
+}
+
+pub fn list(editor: &Editor) -> Result<Vec<PluginEntry>, PluginManagerError> {
+    let directory = plugin_directory(editor)?;
+    let mut entries = Vec::new();
+    let dir_entries = match fs::read_dir(directory) {
+        Ok(dir_entries) => dir_entries,
+        Err(_) => return Ok(entries),
+    };
+    for entry in dir_entries.flatten() {
+        let path = entry.path();
+        if !path.join(PLUGIN_ENTRY_FILE_NAME).is_file() {
+            continue;
+        }
+        let name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name.into(),
+            None => continue,
+        };
+        entries.push(PluginEntry {
+            name,
+            enabled: !path.join(DISABLED_MARKER_FILE_NAME).exists(),
+            version: plugin_version(&path),
+        });
+    }
+    entries.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+pub fn load(
+    editor: &mut Editor,
+    platform: &mut Platform,
+    clients: &mut ClientManager,
+    name: &str,
+) -> Result<EditorControlFlow, PluginManagerError> {
+    let path = find_plugin(editor, name)?;
+    if editor.plugin_manager.is_loaded(name) {
+        return Ok(EditorControlFlow::Continue);
+    }
+
+    let entry_path = path.join(PLUGIN_ENTRY_FILE_NAME);
+    let source = match fs::read_to_string(&entry_path) {
+        Ok(source) => source,
+        Err(_) => return Err(PluginManagerError::NoSuchPlugin),
+    };
+    let entry_path = entry_path.to_string_lossy();
+
+    editor.plugin_manager.loaded.push(name.into());
+    let previous_directory = editor.plugin_manager.loading_directory.replace(path);
+    let flow = load_config(editor, platform, clients, &entry_path, &source);
+    editor.plugin_manager.loading_directory = previous_directory;
+    Ok(flow)
+}
+
+pub fn load_all(
+    editor: &mut Editor,
+    platform: &mut Platform,
+    clients: &mut ClientManager,
+) -> EditorControlFlow {
+    if editor.config.plugin_directory.is_empty() {
+        return EditorControlFlow::Continue;
+    }
+
+    let entries = match list(editor) {
+        Ok(entries) => entries,
+        Err(_) => return EditorControlFlow::Continue,
+    };
+    for entry in entries {
+        if !entry.enabled {
+            continue;
+        }
+        let profile_index = begin_startup_item(&mut editor.startup_profile, &entry.name);
+        let result = load(editor, platform, clients, &entry.name);
+        end_startup_item(&mut editor.startup_profile, profile_index);
+        match result {
+            Ok(EditorControlFlow::Continue) => (),
+            Ok(flow) => return flow,
+            Err(error) => editor
+                .status_bar
+                .write(MessageKind::Error)
+                .fmt(format_args!("plugin '{}': {}", entry.name, error)),
+        }
+    }
+    EditorControlFlow::Continue
+}
+
+pub fn unload(editor: &mut Editor, platform: &mut Platform, name: &str) {
+    editor.plugin_manager.loaded.retain(|n| n != name);
+    if let Some(handle) = editor.remote_plugins.find(name) {
+        editor.remote_plugins.stop(platform, handle);
+    }
+}
+
+pub fn set_enabled(
+    editor: &mut Editor,
+    platform: &mut Platform,
+    clients: &mut ClientManager,
+    name: &str,
+    enabled: bool,
+) -> Result<EditorControlFlow, PluginManagerError> {
+    let path = find_plugin(editor, name)?;
+    let marker_path = path.join(DISABLED_MARKER_FILE_NAME);
+    if enabled {
+        let _ = fs::remove_file(marker_path);
+        load(editor, platform, clients, name)
+    } else {
+        fs::write(marker_path, b"").map_err(PluginManagerError::IoError)?;
+        unload(editor, platform, name);
+        Ok(EditorControlFlow::Continue)
+    }
+}
+
+pub fn source_plugin_name(source: &str) -> Option<&str> {
+    let source = source.trim_end_matches(['/', '\\']);
+    let name = match source.rfind(['/', '\\', ':']) {
+        Some(i) => &source[i + 1..],
+        None => source,
+    };
+    let name = name.strip_suffix(".git").unwrap_or(name);
+    if name.is_empty() || name == "." || name == ".." {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn copy_directory(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_directory(&path, &target)?;
+        } else {
+            fs::copy(&path, &target)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn install(
+    editor: &mut Editor,
+    platform: &mut Platform,
+    clients: &mut ClientManager,
+    source: &str,
+) -> Result<EditorControlFlow, PluginManagerError> {
+    let directory = plugin_directory(editor)?;
+    let name = source_plugin_name(source).ok_or(PluginManagerError::InvalidSource)?;
+    let path = directory.join(name);
+    if path.exists() {
+        return Err(PluginManagerError::AlreadyInstalled);
+    }
+    fs::create_dir_all(&directory).map_err(PluginManagerError::IoError)?;
+
+    let source_path = editor.current_directory.join(source);
+    if source_path.is_dir() {
+        copy_directory(&source_path, &path).map_err(PluginManagerError::IoError)?;
+        if !path.join(PLUGIN_ENTRY_FILE_NAME).is_file() {
+            let _ = fs::remove_dir_all(&path);
+            return Err(PluginManagerError::InvalidSource);
+        }
+        return finish_install(editor, platform, clients, name, &path);
+    }
+
+    // cloning can take a while so it happens in a process and the install finishes when it exits
+    let mut command = Command::new("git");
+    command.current_dir(&directory);
+    command.args(["clone", "--depth", "1", source, name]);
+    command.stdin(Stdio::null());
+    command.stdout(Stdio::null());
+    command.stderr(Stdio::piped());
+
+    let installs = &mut editor.plugin_manager.installs;
+    let index = match installs.iter().position(|i| !i.alive) {
+        Some(index) => index,
+        None => {
+            installs.push(PluginInstall {
+                alive: false,
+                name: String::new(),
+                path: PathBuf::new(),
+                error_output: Vec::new(),
+            });
+            installs.len() - 1
+        }
+    };
+    let install = &mut installs[index];
+    install.alive = true;
+    install.name.clear();
+    install.name.push_str(name);
+    install.path = path;
+    install.error_output.clear();
+
+    platform.requests.enqueue(PlatformRequest::SpawnProcess {
+        tag: ProcessTag::PluginInstall(index),
+        command,
+        buf_len: 4 * 1024,
+    });
+    editor
+        .status_bar
+        .write(MessageKind::Info)
+        .fmt(format_args!("installing plugin '{}'...", name));
+    Ok(EditorControlFlow::Continue)
+}
+
+fn finish_install(
+    editor: &mut Editor,
+    platform: &mut Platform,
+    clients: &mut ClientManager,
+    name: &str,
+    path: &Path,
+) -> Result<EditorControlFlow, PluginManagerError> {
+    let version = plugin_version(path);
+    editor
+        .status_bar
+        .write(MessageKind::Info)
+        .fmt(format_args!("installed plugin '{}' {}", name, version));
+    load(editor, platform, clients, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plugin_name_from_source() {
+        assert_eq!(Some("plugin"), source_plugin_name("plugin"));
+        assert_eq!(Some("plugin"), source_plugin_name("path/to/plugin/"));
+        assert_eq!(Some("plugin"), source_plugin_name("C:\\path\\plugin"));
+        assert_eq!(
+            Some("pepper-todo"),
+            source_plugin_name("https://github.com/user/pepper-todo.git")
+        );
+        assert_eq!(
+            Some("plugin"),
+            source_plugin_name("git@github.com:user/plugin.git")
+        );
+        assert_eq!(None, source_plugin_name(""));
+        assert_eq!(None, source_plugin_name("path/.."));
+    }
+}
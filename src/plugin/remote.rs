@@ -0,0 +1,483 @@
+use std::process::{Command, Stdio};
+
+use crate::{
+    client::ClientManager,
+    command::{CommandArgs, CommandManager},
+    editor::Editor,
+    editor_utils::{LogLevel, MessageKind},
+    events::EditorEventIter,
+    json::{FromJson, Json, JsonArray, JsonInteger, JsonKey, JsonObject, JsonValue},
+    lsp::{Protocol, ResponseError, ServerEvent, ServerRequest},
+    mode::{picker, ModeContext},
+    platform::{Platform, PlatformRequest, ProcessHandle, ProcessTag},
+    script::Value,
+};
+
+use super::{event_fields, event_name, EVENT_NAMES};
+
+const BUFFER_LEN: usize = 4 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RemotePluginHandle(u8);
+
+pub struct RemotePlugin {
+    name: String,
+    protocol: Protocol,
+    json: Json,
+    events: Vec<&'static str>,
+}
+impl RemotePlugin {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn notify(&mut self, platform: &mut Platform, method: &'static str, params: JsonObject) {
+        self.protocol
+            .notify(platform, &mut self.json, method, params.into());
+    }
+
+    fn respond(
+        &mut self,
+        platform: &mut Platform,
+        request_id: JsonValue,
+        result: Result<JsonValue, ResponseError>,
+    ) {
+        self.protocol
+            .respond(platform, &mut self.json, request_id, result);
+    }
+
+    fn error(&mut self, message: &str) -> ResponseError {
+        ResponseError {
+            code: -32603,
+            message: JsonKey::String(self.json.create_string(message)),
+            data: JsonValue::Null,
+        }
+    }
+
+    fn on_request(
+        &mut self,
+        editor: &mut Editor,
+        platform: &mut Platform,
+        clients: &mut ClientManager,
+        handle: RemotePluginHandle,
+        request: ServerRequest,
+    ) -> Option<Result<JsonValue, ResponseError>> {
+        let params = request.params;
+        match request.method.as_str(&self.json) {
+            "command" => {
+                let command = params.get("command", &self.json);
+                let command: &str = match FromJson::from_json(command, &self.json) {
+                    Ok(command) => command,
+                    Err(_) => return Some(Err(ResponseError::parse_error())),
+                };
+                let client_handle = clients.focused_client();
+                let mut command = editor.string_pool.acquire_with(command);
+                let result = CommandManager::try_eval(
+                    editor,
+                    platform,
+                    clients,
+                    client_handle,
+                    &mut command,
+                );
+                editor.string_pool.release(command);
+                match result {
+                    Ok(_) => Some(Ok(JsonValue::Null)),
+                    Err(error) => Some(Err(self.error(&error.to_string()))),
+                }
+            }
+            "pick" => {
+                let request_id = match request.id {
+                    JsonValue::Integer(id) => id,
+                    _ => return Some(Err(ResponseError::parse_error())),
+                };
+                let client_handle = match clients.focused_client() {
+                    Some(handle) => handle,
+                    None => return Some(Ok(JsonValue::Null)),
+                };
+                let prompt: &str =
+                    FromJson::from_json(params.clone().get("prompt", &self.json), &self.json)
+                        .unwrap_or("");
+                let entries: JsonArray =
+                    match FromJson::from_json(params.get("entries", &self.json), &self.json) {
+                        Ok(entries) => entries,
+                        Err(_) => return Some(Err(ResponseError::parse_error())),
+                    };
+
+                editor.picker.clear();
+                for entry in entries.elements(&self.json) {
+                    if let Ok(entry) = <&str>::from_json(entry, &self.json) {
+                        editor.picker.add_custom_entry(entry);
+                    }
+                }
+                let mut ctx = ModeContext {
+                    editor,
+                    platform,
+                    clients,
+                    client_handle,
+                };
+                picker::remote_plugin::enter_mode(&mut ctx, handle, request_id, prompt);
+                None
+            }
+            _ => Some(Err(ResponseError::method_not_found())),
+        }
+    }
+
+    fn on_notification(&mut self, editor: &mut Editor, method: &str, params: JsonValue) {
+        match method {
+            "register-command" => {
+                let name: &str =
+                    match FromJson::from_json(params.get("name", &self.json), &self.json) {
+                        Ok(name) => name,
+                        Err(_) => return,
+                    };
+                let mut command = editor.string_pool.acquire();
+                command.push_str("plugin-command ");
+                command.push_str(&self.name);
+                command.push(' ');
+                command.push_str(name);
+                editor.commands.aliases.add(name, &command);
+                editor.string_pool.release(command);
+            }
+            "subscribe" => {
+                let event: &str =
+                    match FromJson::from_json(params.get("event", &self.json), &self.json) {
+                        Ok(event) => event,
+                        Err(_) => return,
+                    };
+                match EVENT_NAMES.iter().find(|&&e| e == event) {
+                    Some(&event) => {
+                        if !self.events.contains(&event) {
+                            self.events.push(event);
+                        }
+                    }
+                    None => editor
+                        .status_bar
+                        .write(MessageKind::Error)
+                        .fmt(format_args!(
+                            "plugin '{}': no such event '{}'",
+                            self.name, event
+                        )),
+                }
+            }
+            "print" => {
+                if let Ok(message) =
+                    <&str>::from_json(params.get("message", &self.json), &self.json)
+                {
+                    editor.status_bar.write(MessageKind::Info).str(message);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+enum RemotePluginEntry {
+    Vacant,
+    Reserved,
+    Occupied(Box<RemotePlugin>),
+    Stopping,
+}
+impl RemotePluginEntry {
+    fn reserve_and_take(&mut self) -> Option<Box<RemotePlugin>> {
+        let mut entry = Self::Reserved;
+        std::mem::swap(self, &mut entry);
+        match entry {
+            Self::Vacant => {
+                *self = Self::Vacant;
+                None
+            }
+            Self::Reserved => None,
+            Self::Occupied(plugin) => Some(plugin),
+            Self::Stopping => {
+                *self = Self::Stopping;
+                None
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RemotePluginCollection {
+    entries: Vec<RemotePluginEntry>,
+}
+impl RemotePluginCollection {
+    pub fn start(
+        &mut self,
+        platform: &mut Platform,
+        name: &str,
+        mut command: Command,
+    ) -> RemotePluginHandle {
+        if let Some(handle) = self.find(name) {
+            self.stop(platform, handle);
+        }
+
+        let index = match self
+            .entries
+            .iter()
+            .position(|e| matches!(e, RemotePluginEntry::Vacant))
+        {
+            Some(index) => index,
+            None => {
+                self.entries.push(RemotePluginEntry::Vacant);
+                self.entries.len() - 1
+            }
+        };
+        let handle = RemotePluginHandle(index as _);
+
+        platform
+            .logger
+            .write(LogLevel::Info)
+            .fmt(format_args!("starting plugin '{}'", name));
+
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        platform.requests.enqueue(PlatformRequest::SpawnProcess {
+            tag: ProcessTag::Plugin(handle),
+            command,
+            buf_len: BUFFER_LEN,
+        });
+
+        self.entries[index] = RemotePluginEntry::Occupied(Box::new(RemotePlugin {
+            name: name.into(),
+            protocol: Protocol::new(),
+            json: Json::new(),
+            events: Vec::new(),
+        }));
+        handle
+    }
+
+    pub fn stop(&mut self, platform: &mut Platform, handle: RemotePluginHandle) {
+        let entry = &mut self.entries[handle.0 as usize];
+        match entry {
+            RemotePluginEntry::Occupied(plugin) => {
+                plugin.notify(platform, "exit", JsonObject::default());
+                if let Some(process_handle) = plugin.protocol.process_handle() {
+                    platform.requests.enqueue(PlatformRequest::KillProcess {
+                        handle: process_handle,
+                    });
+                }
+            }
+            RemotePluginEntry::Reserved => (),
+            _ => return,
+        }
+        // the entry is only reused after its process exits
+        *entry = RemotePluginEntry::Stopping;
+    }
+
+    pub fn find(&self, name: &str) -> Option<RemotePluginHandle> {
+        self.entries
+            .iter()
+            .position(|e| match e {
+                RemotePluginEntry::Occupied(plugin) => plugin.name == name,
+                _ => false,
+            })
+            .map(|i| RemotePluginHandle(i as _))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RemotePlugin> {
+        self.entries.iter().flat_map(|e| match e {
+            RemotePluginEntry::Occupied(plugin) => Some(plugin.as_ref()),
+            _ => None,
+        })
+    }
+
+    fn put_back(
+        &mut self,
+        platform: &mut Platform,
+        handle: RemotePluginHandle,
+        mut plugin: Box<RemotePlugin>,
+    ) {
+        let entry = &mut self.entries[handle.0 as usize];
+        if let RemotePluginEntry::Reserved = entry {
+            *entry = RemotePluginEntry::Occupied(plugin);
+            return;
+        }
+
+        // plugin was stopped while handling its own messages
+        plugin.notify(platform, "exit", JsonObject::default());
+        if let Some(process_handle) = plugin.protocol.process_handle() {
+            platform.requests.enqueue(PlatformRequest::KillProcess {
+                handle: process_handle,
+            });
+        }
+    }
+
+    pub fn on_process_spawned(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        handle: RemotePluginHandle,
+        process_handle: ProcessHandle,
+    ) {
+        let plugin = match &mut editor.remote_plugins.entries[handle.0 as usize] {
+            RemotePluginEntry::Occupied(plugin) => plugin,
+            RemotePluginEntry::Stopping => {
+                platform.requests.enqueue(PlatformRequest::KillProcess {
+                    handle: process_handle,
+                });
+                return;
+            }
+            _ => return,
+        };
+
+        plugin.protocol.set_process_handle(process_handle);
+
+        plugin.json.clear();
+        let mut params = JsonObject::default();
+        let name = plugin.json.create_string(&plugin.name);
+        params.set("name".into(), name.into(), &mut plugin.json);
+        params.set(
+            "pid".into(),
+            JsonValue::Integer(std::process::id() as _),
+            &mut plugin.json,
+        );
+        plugin.notify(platform, "initialize", params);
+    }
+
+    pub fn on_process_output(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        clients: &mut ClientManager,
+        handle: RemotePluginHandle,
+        bytes: &[u8],
+    ) {
+        let mut plugin = match editor.remote_plugins.entries[handle.0 as usize].reserve_and_take() {
+            Some(plugin) => plugin,
+            None => return,
+        };
+
+        plugin.json.clear();
+        let mut events = plugin.protocol.parse_events(bytes);
+        while let Some(event) = events.next(&mut plugin.protocol, &mut plugin.json) {
+            match event {
+                ServerEvent::ParseError => {
+                    platform.logger.write(LogLevel::Error).fmt(format_args!(
+                        "could not parse message from plugin '{}'",
+                        plugin.name
+                    ));
+                    plugin.respond(platform, JsonValue::Null, Err(ResponseError::parse_error()))
+                }
+                ServerEvent::Request(request) => {
+                    let id = request.id.clone();
+                    let result = plugin.on_request(editor, platform, clients, handle, request);
+                    if let Some(result) = result {
+                        plugin.respond(platform, id, result);
+                    }
+                }
+                ServerEvent::Notification(notification) => {
+                    let method = editor
+                        .string_pool
+                        .acquire_with(notification.method.as_str(&plugin.json));
+                    plugin.on_notification(editor, &method, notification.params);
+                    editor.string_pool.release(method);
+                }
+                ServerEvent::Response(_) => (),
+            }
+        }
+        events.finish(&mut plugin.protocol);
+
+        editor.remote_plugins.put_back(platform, handle, plugin);
+    }
+
+    pub fn on_process_exit(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        handle: RemotePluginHandle,
+    ) {
+        let entry = &mut editor.remote_plugins.entries[handle.0 as usize];
+        if let RemotePluginEntry::Occupied(plugin) = entry {
+            editor
+                .status_bar
+                .write(MessageKind::Error)
+                .fmt(format_args!("plugin '{}' exited", plugin.name));
+            platform
+                .logger
+                .write(LogLevel::Warning)
+                .fmt(format_args!("plugin '{}' exited unexpectedly", plugin.name));
+        }
+        *entry = RemotePluginEntry::Vacant;
+    }
+
+    pub fn on_editor_events(editor: &mut Editor, platform: &mut Platform) {
+        for i in 0..editor.remote_plugins.entries.len() {
+            let mut plugin = match editor.remote_plugins.entries[i].reserve_and_take() {
+                Some(plugin) => plugin,
+                None => continue,
+            };
+
+            if !plugin.events.is_empty() {
+                plugin.json.clear();
+                let mut events = EditorEventIter::new();
+                while let Some(event) = events.next(&editor.events) {
+                    let name = event_name(event);
+                    if !plugin.events.contains(&name) {
+                        continue;
+                    }
+
+                    let json = &mut plugin.json;
+                    let mut params = JsonObject::default();
+                    params.set("event".into(), name.into(), json);
+                    for (key, value) in event_fields(editor, event) {
+                        let value = match value {
+                            Value::Bool(b) => b.into(),
+                            Value::Number(n) => JsonValue::Integer(n as _),
+                            Value::String(s) => json.create_string(&s).into(),
+                            _ => JsonValue::Null,
+                        };
+                        params.set(key.into(), value, json);
+                    }
+                    plugin.notify(platform, "event", params);
+                }
+            }
+
+            editor
+                .remote_plugins
+                .put_back(platform, RemotePluginHandle(i as _), plugin);
+        }
+    }
+
+    pub fn send_command(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        handle: RemotePluginHandle,
+        name: &str,
+        args: &mut CommandArgs,
+    ) {
+        if let RemotePluginEntry::Occupied(plugin) =
+            &mut editor.remote_plugins.entries[handle.0 as usize]
+        {
+            plugin.json.clear();
+            let json = &mut plugin.json;
+            let mut params = JsonObject::default();
+            let name = json.create_string(name);
+            params.set("name".into(), name.into(), json);
+            let mut command_args = JsonArray::default();
+            while let Some(arg) = args.try_next() {
+                let arg = json.create_string(arg);
+                command_args.push(arg.into(), json);
+            }
+            params.set("args".into(), command_args.into(), json);
+            plugin.notify(platform, "command", params);
+        }
+    }
+
+    pub fn respond_pick(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        handle: RemotePluginHandle,
+        request_id: JsonInteger,
+        entry: Option<&str>,
+    ) {
+        if let RemotePluginEntry::Occupied(plugin) =
+            &mut editor.remote_plugins.entries[handle.0 as usize]
+        {
+            plugin.json.clear();
+            let result = match entry {
+                Some(entry) => plugin.json.create_string(entry).into(),
+                None => JsonValue::Null,
+            };
+            plugin.respond(platform, JsonValue::Integer(request_id), Ok(result));
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::client::ClientHandle;
+
+struct StartupProfileEntry {
+    name: String,
+    depth: usize,
+    start: Instant,
+    duration: Duration,
+}
+
+pub struct StartupProfile {
+    entries: Vec<StartupProfileEntry>,
+    open_count: usize,
+    start: Instant,
+}
+impl Default for StartupProfile {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            open_count: 0,
+            start: Instant::now(),
+        }
+    }
+}
+impl StartupProfile {
+    pub fn add(&mut self, name: &str, duration: Duration) {
+        self.entries.push(StartupProfileEntry {
+            name: name.into(),
+            depth: self.open_count,
+            start: Instant::now(),
+            duration,
+        });
+    }
+
+    pub fn begin(&mut self, name: &str) -> usize {
+        let index = self.entries.len();
+        self.add(name, Duration::ZERO);
+        self.open_count += 1;
+        index
+    }
+
+    pub fn end(&mut self, index: usize) {
+        let entry = &mut self.entries[index];
+        entry.duration = entry.start.elapsed();
+        self.open_count = self.open_count.saturating_sub(1);
+    }
+}
+
+pub fn begin_startup_item(profile: &mut Option<StartupProfile>, name: &str) -> Option<usize> {
+    profile.as_mut().map(|p| p.begin(name))
+}
+
+pub fn end_startup_item(profile: &mut Option<StartupProfile>, index: Option<usize>) {
+    if let (Some(profile), Some(index)) = (profile, index) {
+        profile.end(index);
+    }
+}
+
+impl fmt::Display for StartupProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("startup profile:")?;
+        for entry in &self.entries {
+            let millis = entry.duration.as_secs_f64() * 1000.0;
+            write!(f, "\n{:>10.3}ms ", millis)?;
+            for _ in 0..entry.depth {
+                f.write_str("  ")?;
+            }
+            f.write_str(&entry.name)?;
+        }
+        let millis = self.start.elapsed().as_secs_f64() * 1000.0;
+        write!(f, "\n{:>10.3}ms total", millis)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum FrameStage {
+    Events,
+    Highlighting,
+    Rendering,
+    Serialization,
+}
+impl FrameStage {
+    const COUNT: usize = 4;
+    const ALL: [Self; Self::COUNT] = [
+        Self::Events,
+        Self::Highlighting,
+        Self::Rendering,
+        Self::Serialization,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Events => "events",
+            Self::Highlighting => "highlighting",
+            Self::Rendering => "rendering",
+            Self::Serialization => "serialization",
+        }
+    }
+}
+
+const SLOWEST_FRAME_COUNT: usize = 10;
+
+#[derive(Default)]
+pub struct FrameProfiler {
+    client_handle: Option<ClientHandle>,
+    recording_until: Option<Instant>,
+    frames: Vec<[Duration; FrameStage::COUNT]>,
+    current_frame: [Duration; FrameStage::COUNT],
+}
+impl FrameProfiler {
+    pub fn start(&mut self, client_handle: ClientHandle, duration: Duration) {
+        self.client_handle = Some(client_handle);
+        self.recording_until = Some(Instant::now() + duration);
+        self.frames.clear();
+        self.current_frame = Default::default();
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording_until.is_some()
+    }
+
+    pub fn record(&mut self, stage: FrameStage, duration: Duration) {
+        if self.is_recording() {
+            self.current_frame[stage as usize] += duration;
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        if self.is_recording() {
+            self.frames.push(self.current_frame);
+            self.current_frame = Default::default();
+        }
+    }
+
+    pub fn poll_report(&mut self, report: &mut String) -> Option<ClientHandle> {
+        match self.recording_until {
+            Some(instant) if Instant::now() >= instant => (),
+            _ => return None,
+        }
+        self.recording_until = None;
+
+        use fmt::Write;
+        fn millis(duration: Duration) -> f64 {
+            duration.as_secs_f64() * 1000.0
+        }
+
+        let _ = writeln!(report, "frames: {}", self.frames.len());
+        let _ = writeln!(
+            report,
+            "\n{:<14}{:>12}{:>12}{:>12}",
+            "stage", "total ms", "average ms", "max ms"
+        );
+        for &stage in &FrameStage::ALL {
+            let durations = self.frames.iter().map(|f| f[stage as usize]);
+            let total: Duration = durations.clone().sum();
+            let max = durations.max().unwrap_or_default();
+            let average = total / self.frames.len().max(1) as u32;
+            let _ = writeln!(
+                report,
+                "{:<14}{:>12.3}{:>12.3}{:>12.3}",
+                stage.name(),
+                millis(total),
+                millis(average),
+                millis(max)
+            );
+        }
+
+        let mut slowest: Vec<_> = self.frames.iter().enumerate().collect();
+        slowest.sort_unstable_by_key(|(_, f)| std::cmp::Reverse(f.iter().sum::<Duration>()));
+        slowest.truncate(SLOWEST_FRAME_COUNT);
+
+        let _ = write!(report, "\nslowest frames:\n{:<8}", "frame");
+        for &stage in &FrameStage::ALL {
+            let _ = write!(report, "{:>14}", stage.name());
+        }
+        report.push('\n');
+        for (index, frame) in slowest {
+            let _ = write!(report, "{:<8}", index);
+            for duration in frame {
+                let _ = write!(report, "{:>14.3}", millis(*duration));
+            }
+            report.push('\n');
+        }
+
+        self.frames.clear();
+        self.client_handle.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn startup_profile_report() {
+        let mut profile = StartupProfile::default();
+        profile.add("parse args", Duration::from_micros(1500));
+        let config = profile.begin("config.pp");
+        let syntax = profile.begin("syntax **/*.rs");
+        profile.end(syntax);
+        profile.end(config);
+        profile.add("plugins", Duration::from_millis(2));
+
+        let report = profile.to_string();
+        let mut lines = report.lines();
+        assert_eq!(Some("startup profile:"), lines.next());
+        assert_eq!(Some("     1.500ms parse args"), lines.next());
+        assert!(lines.next().unwrap().ends_with("ms config.pp"));
+        assert!(lines.next().unwrap().ends_with("ms   syntax **/*.rs"));
+        assert_eq!(Some("     2.000ms plugins"), lines.next());
+        assert!(lines.next().unwrap().ends_with("ms total"));
+        assert_eq!(None, lines.next());
+    }
+
+    #[test]
+    fn frame_profiler_report() {
+        let client_handle = ClientHandle::from_index(0).unwrap();
+        let mut profiler = FrameProfiler::default();
+        let mut report = String::new();
+
+        profiler.record(FrameStage::Events, Duration::from_millis(1));
+        profiler.end_frame();
+        assert!(profiler.frames.is_empty());
+        assert!(profiler.poll_report(&mut report).is_none());
+
+        profiler.start(client_handle, Duration::from_secs(60));
+        profiler.record(FrameStage::Rendering, Duration::from_millis(3));
+        profiler.end_frame();
+        profiler.end_frame();
+        assert!(profiler.poll_report(&mut report).is_none());
+        assert_eq!(2, profiler.frames.len());
+
+        profiler.recording_until = Some(Instant::now());
+        assert!(profiler.poll_report(&mut report).is_some());
+        assert!(!profiler.is_recording());
+        assert!(report.starts_with("frames: 2\n"));
+        assert!(report.contains("\nhighlighting "));
+        assert!(report.contains("\nrendering            3.000       1.500       3.000\n"));
+        assert!(report.contains("\nslowest frames:\n"));
+    }
+}
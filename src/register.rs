@@ -1,3 +1,5 @@
+use std::collections::VecDeque;
+
 pub static SEARCH_REGISTER: RegisterKey = RegisterKey::from_char_unchecked('s');
 pub static AUTO_MACRO_REGISTER: RegisterKey = RegisterKey::from_char_unchecked('a');
 
@@ -46,3 +48,59 @@ impl RegisterCollection {
         &mut self.registers[key.0 as usize]
     }
 }
+
+const YANK_HISTORY_CAPACITY: usize = 32;
+
+#[derive(Default)]
+pub struct YankHistory {
+    entries: VecDeque<String>,
+}
+
+impl YankHistory {
+    pub fn push(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        let entry = match self.entries.iter().position(|e| e == text) {
+            Some(index) => self.entries.remove(index),
+            None if self.entries.len() == YANK_HISTORY_CAPACITY => self.entries.pop_back(),
+            None => None,
+        };
+        let mut entry = entry.unwrap_or_default();
+        entry.clear();
+        entry.push_str(text);
+        self.entries.push_front(entry);
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yank_history_order() {
+        let mut history = YankHistory::default();
+        history.push("a");
+        history.push("");
+        history.push("b");
+        history.push("a");
+        assert_eq!(vec!["a", "b"], history.iter().collect::<Vec<_>>());
+
+        for i in 0..YANK_HISTORY_CAPACITY {
+            history.push(&i.to_string());
+        }
+        assert_eq!(YANK_HISTORY_CAPACITY, history.iter().count());
+        assert_eq!(Some("31"), history.get(0));
+        assert_eq!(Some("0"), history.get(YANK_HISTORY_CAPACITY - 1));
+        assert_eq!(None, history.get(YANK_HISTORY_CAPACITY));
+    }
+}
@@ -0,0 +1,1870 @@
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+const MAX_CALL_DEPTH: usize = 128;
+const MAX_STEP_COUNT: usize = 10_000_000;
+const MAX_NESTING_DEPTH: usize = 200;
+
+pub trait ScriptHost {
+    fn print(&mut self, message: &str);
+    fn command(&mut self, command: &str) -> Result<(), String>;
+    fn is_event(&self, name: &str) -> bool;
+
+    fn functions(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn call(&mut self, function: &'static str, args: &[Value]) -> Result<Value, String> {
+        let _ = args;
+        Err(format!("no such function '{}'", function))
+    }
+}
+
+pub struct ScriptError {
+    pub line: u32,
+    pub message: String,
+}
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "script error at line {}: {}", self.line, self.message)
+    }
+}
+
+fn error<T>(line: u32, message: impl Into<String>) -> Result<T, ScriptError> {
+    Err(ScriptError {
+        line,
+        message: message.into(),
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Builtin {
+    Print,
+    Command,
+    On,
+    Mode,
+    Panel,
+    StatusSegment,
+    Provide,
+    Service,
+    Send,
+    Error,
+    Type,
+    ToString,
+    ToNumber,
+    Pairs,
+    IPairs,
+    TableInsert,
+    TableRemove,
+    TableConcat,
+    StringLen,
+    StringSub,
+    StringUpper,
+    StringLower,
+    StringFind,
+    MathFloor,
+    MathAbs,
+    MathMin,
+    MathMax,
+    Host(&'static str),
+}
+
+#[derive(Default)]
+pub struct Table {
+    array: Vec<Value>,
+    fields: HashMap<Rc<str>, Value>,
+}
+impl Table {
+    fn get(&self, key: &Value) -> Value {
+        match key {
+            Value::Number(n) if n.fract() == 0.0 && *n >= 1.0 => self
+                .array
+                .get(*n as usize - 1)
+                .cloned()
+                .unwrap_or(Value::Nil),
+            Value::String(s) => self.fields.get(s).cloned().unwrap_or(Value::Nil),
+            _ => Value::Nil,
+        }
+    }
+
+    fn set(&mut self, key: Value, value: Value, line: u32) -> Result<(), ScriptError> {
+        match key {
+            Value::Number(n) if n.fract() == 0.0 && n >= 1.0 => {
+                let index = n as usize - 1;
+                if index < self.array.len() {
+                    if let Value::Nil = value {
+                        if index == self.array.len() - 1 {
+                            self.array.pop();
+                            return Ok(());
+                        }
+                    }
+                    self.array[index] = value;
+                    Ok(())
+                } else if index == self.array.len() {
+                    self.array.push(value);
+                    Ok(())
+                } else {
+                    error(line, "table index out of range")
+                }
+            }
+            Value::String(s) => {
+                match value {
+                    Value::Nil => self.fields.remove(&s),
+                    value => self.fields.insert(s, value),
+                };
+                Ok(())
+            }
+            _ => error(line, "invalid table key"),
+        }
+    }
+
+    fn entries(&self) -> Vec<(Value, Value)> {
+        let mut entries: Vec<_> = self
+            .array
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (Value::Number((i + 1) as _), v.clone()))
+            .collect();
+        let mut fields: Vec<_> = self.fields.iter().collect();
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+        entries.extend(
+            fields
+                .into_iter()
+                .map(|(k, v)| (Value::String(k.clone()), v.clone())),
+        );
+        entries
+    }
+}
+
+pub struct Function {
+    params: Vec<Rc<str>>,
+    body: Vec<Stmt>,
+}
+
+#[derive(Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    Table(Rc<RefCell<Table>>),
+    Function(Rc<Function>),
+    Builtin(Builtin),
+}
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Nil => "nil",
+            Self::Bool(_) => "boolean",
+            Self::Number(_) => "number",
+            Self::String(_) => "string",
+            Self::Table(_) => "table",
+            Self::Function(_) | Self::Builtin(_) => "function",
+        }
+    }
+
+    fn is_truthy(&self) -> bool {
+        !matches!(self, Self::Nil | Self::Bool(false))
+    }
+
+    fn equals(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Nil, Self::Nil) => true,
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Number(a), Self::Number(b)) => a == b,
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Table(a), Self::Table(b)) => Rc::ptr_eq(a, b),
+            (Self::Function(a), Self::Function(b)) => Rc::ptr_eq(a, b),
+            (Self::Builtin(a), Self::Builtin(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    pub fn string(s: &str) -> Self {
+        Self::String(s.into())
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Self::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn array(&self) -> Vec<Value> {
+        match self {
+            Self::Table(table) => table.borrow().array.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Self {
+        match self {
+            Self::Table(table) => table
+                .borrow()
+                .fields
+                .get(name)
+                .cloned()
+                .unwrap_or(Self::Nil),
+            _ => Self::Nil,
+        }
+    }
+
+    pub fn fields(&self) -> Vec<(Rc<str>, Value)> {
+        match self {
+            Self::Table(table) => {
+                let mut fields: Vec<_> = table
+                    .borrow()
+                    .fields
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect();
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                fields
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn table(fields: Vec<(&str, Value)>) -> Self {
+        let mut table = Table::default();
+        for (name, value) in fields {
+            table.fields.insert(name.into(), value);
+        }
+        Self::Table(Rc::new(RefCell::new(table)))
+    }
+
+    pub fn list(values: Vec<Value>) -> Self {
+        let table = Table {
+            array: values,
+            fields: HashMap::new(),
+        };
+        Self::Table(Rc::new(RefCell::new(table)))
+    }
+}
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Nil => f.write_str("nil"),
+            Self::Bool(b) => write!(f, "{}", b),
+            Self::Number(n) => {
+                if n.fract() == 0.0 && n.abs() < 1e15 {
+                    write!(f, "{}", *n as i64)
+                } else {
+                    write!(f, "{}", n)
+                }
+            }
+            Self::String(s) => f.write_str(s),
+            Self::Table(t) => write!(f, "table: {:p}", Rc::as_ptr(t)),
+            Self::Function(function) => write!(f, "function: {:p}", Rc::as_ptr(function)),
+            Self::Builtin(builtin) => write!(f, "builtin: {:?}", builtin),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(Rc<str>),
+    Number(f64),
+    String(Rc<str>),
+    Symbol(&'static str),
+    End,
+}
+
+static KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in", "local",
+    "nil", "not", "or", "return", "then", "true", "while",
+];
+static SYMBOLS: &[&str] = &[
+    "==", "~=", "<=", ">=", "..", "+", "-", "*", "/", "%", "#", "<", ">", "=", "(", ")", "{", "}",
+    "[", "]", ";", ",", ".",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, u32)>, ScriptError> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut rest = source;
+
+    loop {
+        let trimmed = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() && c != '\n');
+        rest = trimmed;
+
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => break,
+        };
+
+        if c == '\n' {
+            line += 1;
+            rest = &rest[1..];
+        } else if rest.starts_with("--") {
+            rest = match rest.find('\n') {
+                Some(i) => &rest[i..],
+                None => "",
+            };
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            match rest[..len].parse() {
+                Ok(n) => tokens.push((Token::Number(n), line)),
+                Err(_) => return error(line, "invalid number"),
+            }
+            rest = &rest[len..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let name = &rest[..len];
+            match KEYWORDS.iter().find(|&&k| k == name) {
+                Some(keyword) => tokens.push((Token::Symbol(keyword), line)),
+                None => tokens.push((Token::Name(name.into()), line)),
+            }
+            rest = &rest[len..];
+        } else if c == '"' || c == '\'' {
+            let mut text = String::new();
+            let mut chars = rest[1..].chars();
+            loop {
+                match chars.next() {
+                    Some('\\') => match chars.next() {
+                        Some('n') => text.push('\n'),
+                        Some('t') => text.push('\t'),
+                        Some(c) => text.push(c),
+                        None => return error(line, "unfinished string"),
+                    },
+                    Some(d) if d == c => break,
+                    Some('\n') | None => return error(line, "unfinished string"),
+                    Some(c) => text.push(c),
+                }
+            }
+            tokens.push((Token::String(text.into()), line));
+            rest = chars.as_str();
+        } else {
+            match SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+                Some(symbol) => {
+                    tokens.push((Token::Symbol(symbol), line));
+                    rest = &rest[symbol.len()..];
+                }
+                None => return error(line, format!("unexpected character '{}'", c)),
+            }
+        }
+    }
+
+    tokens.push((Token::End, line));
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Concat,
+    Equals,
+    NotEquals,
+    Less,
+    LessEquals,
+    Greater,
+    GreaterEquals,
+    And,
+    Or,
+}
+
+#[derive(Clone, Copy)]
+enum UnaryOp {
+    Negate,
+    Not,
+    Len,
+}
+
+enum Expr {
+    Literal(Value),
+    Name(Rc<str>),
+    Index(Box<Expr>, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    Function(Rc<Function>),
+    Table(Vec<(Option<Expr>, Expr)>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+enum StmtKind {
+    Local(Rc<str>, Option<Expr>),
+    Assign(Expr, Expr),
+    Call(Expr),
+    If(Vec<(Expr, Vec<Stmt>)>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    NumericFor(Rc<str>, Expr, Expr, Option<Expr>, Vec<Stmt>),
+    GenericFor(Rc<str>, Option<Rc<str>>, Expr, Vec<Stmt>),
+    Return(Option<Expr>),
+    Break,
+}
+
+struct Stmt {
+    line: u32,
+    kind: StmtKind,
+}
+
+struct Parser {
+    tokens: Vec<(Token, u32)>,
+    index: usize,
+    depth: usize,
+}
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index].0
+    }
+
+    fn line(&self) -> u32 {
+        self.tokens[self.index].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.index].0.clone();
+        if self.index < self.tokens.len() - 1 {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn accept(&mut self, symbol: &str) -> bool {
+        match self.peek() {
+            Token::Symbol(s) if *s == symbol => {
+                self.next();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ScriptError> {
+        if self.accept(symbol) {
+            Ok(())
+        } else {
+            error(self.line(), format!("expected '{}'", symbol))
+        }
+    }
+
+    fn expect_name(&mut self) -> Result<Rc<str>, ScriptError> {
+        match self.next() {
+            Token::Name(name) => Ok(name),
+            _ => error(self.line(), "expected name"),
+        }
+    }
+
+    fn is_block_end(&self) -> bool {
+        matches!(
+            self.peek(),
+            Token::End | Token::Symbol("end" | "else" | "elseif")
+        )
+    }
+
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ScriptError>,
+    ) -> Result<T, ScriptError> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return error(self.line(), "script is nested too deeply");
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_block(&mut self) -> Result<Vec<Stmt>, ScriptError> {
+        self.nested(Self::parse_stmts)
+    }
+
+    fn parse_stmts(&mut self) -> Result<Vec<Stmt>, ScriptError> {
+        let mut stmts = Vec::new();
+        while !self.is_block_end() {
+            if self.accept(";") {
+                continue;
+            }
+            let stmt = self.parse_stmt()?;
+            let is_last = matches!(stmt.kind, StmtKind::Return(_) | StmtKind::Break);
+            stmts.push(stmt);
+            if is_last {
+                self.accept(";");
+                if !self.is_block_end() {
+                    return error(self.line(), "expected block end");
+                }
+            }
+        }
+        Ok(stmts)
+    }
+
+    fn parse_stmt(&mut self) -> Result<Stmt, ScriptError> {
+        let line = self.line();
+        let kind = match self.peek() {
+            Token::Symbol("local") => {
+                self.next();
+                if self.accept("function") {
+                    let name = self.expect_name()?;
+                    let function = self.parse_function_body()?;
+                    StmtKind::Local(name, Some(Expr::Function(function)))
+                } else {
+                    let name = self.expect_name()?;
+                    let value = if self.accept("=") {
+                        Some(self.parse_expr()?)
+                    } else {
+                        None
+                    };
+                    StmtKind::Local(name, value)
+                }
+            }
+            Token::Symbol("function") => {
+                self.next();
+                let mut target = Expr::Name(self.expect_name()?);
+                while self.accept(".") {
+                    let key = self.expect_name()?;
+                    target = Expr::Index(
+                        Box::new(target),
+                        Box::new(Expr::Literal(Value::String(key))),
+                    );
+                }
+                let function = self.parse_function_body()?;
+                StmtKind::Assign(target, Expr::Function(function))
+            }
+            Token::Symbol("if") => {
+                self.next();
+                let mut branches = Vec::new();
+                let mut else_block = Vec::new();
+                loop {
+                    let condition = self.parse_expr()?;
+                    self.expect("then")?;
+                    let block = self.parse_block()?;
+                    branches.push((condition, block));
+                    if self.accept("elseif") {
+                        continue;
+                    }
+                    if self.accept("else") {
+                        else_block = self.parse_block()?;
+                    }
+                    self.expect("end")?;
+                    break;
+                }
+                StmtKind::If(branches, else_block)
+            }
+            Token::Symbol("while") => {
+                self.next();
+                let condition = self.parse_expr()?;
+                self.expect("do")?;
+                let block = self.parse_block()?;
+                self.expect("end")?;
+                StmtKind::While(condition, block)
+            }
+            Token::Symbol("for") => {
+                self.next();
+                let name = self.expect_name()?;
+                if self.accept("=") {
+                    let from = self.parse_expr()?;
+                    self.expect(",")?;
+                    let to = self.parse_expr()?;
+                    let step = if self.accept(",") {
+                        Some(self.parse_expr()?)
+                    } else {
+                        None
+                    };
+                    self.expect("do")?;
+                    let block = self.parse_block()?;
+                    self.expect("end")?;
+                    StmtKind::NumericFor(name, from, to, step, block)
+                } else {
+                    let value_name = if self.accept(",") {
+                        Some(self.expect_name()?)
+                    } else {
+                        None
+                    };
+                    self.expect("in")?;
+                    let table = self.parse_expr()?;
+                    self.expect("do")?;
+                    let block = self.parse_block()?;
+                    self.expect("end")?;
+                    StmtKind::GenericFor(name, value_name, table, block)
+                }
+            }
+            Token::Symbol("do") => {
+                self.next();
+                let block = self.parse_block()?;
+                self.expect("end")?;
+                StmtKind::If(vec![(Expr::Literal(Value::Bool(true)), block)], Vec::new())
+            }
+            Token::Symbol("return") => {
+                self.next();
+                if self.is_block_end() || matches!(self.peek(), Token::Symbol(";")) {
+                    StmtKind::Return(None)
+                } else {
+                    StmtKind::Return(Some(self.parse_expr()?))
+                }
+            }
+            Token::Symbol("break") => {
+                self.next();
+                StmtKind::Break
+            }
+            _ => {
+                let expr = self.parse_suffixed_expr()?;
+                if self.accept("=") {
+                    if !matches!(expr, Expr::Name(_) | Expr::Index(..)) {
+                        return error(line, "can not assign to expression");
+                    }
+                    StmtKind::Assign(expr, self.parse_expr()?)
+                } else if let Expr::Call(..) = expr {
+                    StmtKind::Call(expr)
+                } else {
+                    return error(line, "expected statement");
+                }
+            }
+        };
+        Ok(Stmt { line, kind })
+    }
+
+    fn parse_function_body(&mut self) -> Result<Rc<Function>, ScriptError> {
+        self.expect("(")?;
+        let mut params = Vec::new();
+        if !self.accept(")") {
+            loop {
+                params.push(self.expect_name()?);
+                if self.accept(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        let body = self.parse_block()?;
+        self.expect("end")?;
+        Ok(Rc::new(Function { params, body }))
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, ScriptError> {
+        self.parse_binary_expr(0)
+    }
+
+    fn parse_binary_expr(&mut self, min_precedence: u8) -> Result<Expr, ScriptError> {
+        self.nested(|p| p.parse_binary_expr_nested(min_precedence))
+    }
+
+    fn parse_binary_expr_nested(&mut self, min_precedence: u8) -> Result<Expr, ScriptError> {
+        let mut left = match self.peek() {
+            Token::Symbol("not") => {
+                self.next();
+                Expr::Unary(UnaryOp::Not, Box::new(self.parse_binary_expr(7)?))
+            }
+            Token::Symbol("-") => {
+                self.next();
+                Expr::Unary(UnaryOp::Negate, Box::new(self.parse_binary_expr(7)?))
+            }
+            Token::Symbol("#") => {
+                self.next();
+                Expr::Unary(UnaryOp::Len, Box::new(self.parse_binary_expr(7)?))
+            }
+            _ => self.parse_simple_expr()?,
+        };
+
+        // chained operators nest their left operands just as deep
+        let mut chain_depth = self.depth;
+        loop {
+            let (op, precedence, right_associative) = match self.peek() {
+                Token::Symbol("or") => (BinaryOp::Or, 1, false),
+                Token::Symbol("and") => (BinaryOp::And, 2, false),
+                Token::Symbol("==") => (BinaryOp::Equals, 3, false),
+                Token::Symbol("~=") => (BinaryOp::NotEquals, 3, false),
+                Token::Symbol("<") => (BinaryOp::Less, 3, false),
+                Token::Symbol("<=") => (BinaryOp::LessEquals, 3, false),
+                Token::Symbol(">") => (BinaryOp::Greater, 3, false),
+                Token::Symbol(">=") => (BinaryOp::GreaterEquals, 3, false),
+                Token::Symbol("..") => (BinaryOp::Concat, 4, true),
+                Token::Symbol("+") => (BinaryOp::Add, 5, false),
+                Token::Symbol("-") => (BinaryOp::Sub, 5, false),
+                Token::Symbol("*") => (BinaryOp::Mul, 6, false),
+                Token::Symbol("/") => (BinaryOp::Div, 6, false),
+                Token::Symbol("%") => (BinaryOp::Mod, 6, false),
+                _ => break,
+            };
+            if precedence <= min_precedence {
+                break;
+            }
+            chain_depth += 1;
+            if chain_depth > MAX_NESTING_DEPTH {
+                return error(self.line(), "script is nested too deeply");
+            }
+            self.next();
+            let next_precedence = if right_associative {
+                precedence - 1
+            } else {
+                precedence
+            };
+            let right = self.parse_binary_expr(next_precedence)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn parse_simple_expr(&mut self) -> Result<Expr, ScriptError> {
+        let expr = match self.peek() {
+            Token::Number(n) => Expr::Literal(Value::Number(*n)),
+            Token::String(s) => Expr::Literal(Value::String(s.clone())),
+            Token::Symbol("nil") => Expr::Literal(Value::Nil),
+            Token::Symbol("true") => Expr::Literal(Value::Bool(true)),
+            Token::Symbol("false") => Expr::Literal(Value::Bool(false)),
+            Token::Symbol("function") => {
+                self.next();
+                return Ok(Expr::Function(self.parse_function_body()?));
+            }
+            Token::Symbol("{") => return self.parse_table(),
+            _ => return self.parse_suffixed_expr(),
+        };
+        self.next();
+        Ok(expr)
+    }
+
+    fn parse_table(&mut self) -> Result<Expr, ScriptError> {
+        self.expect("{")?;
+        let mut entries = Vec::new();
+        while !self.accept("}") {
+            let entry = if self.accept("[") {
+                let key = self.parse_expr()?;
+                self.expect("]")?;
+                self.expect("=")?;
+                (Some(key), self.parse_expr()?)
+            } else {
+                let next = match self.tokens.get(self.index + 1) {
+                    Some((token, _)) => token,
+                    None => return error(self.line(), "expected '}'"),
+                };
+                match (self.peek().clone(), next) {
+                    (Token::Name(name), Token::Symbol("=")) => {
+                        self.next();
+                        self.next();
+                        (Some(Expr::Literal(Value::String(name))), self.parse_expr()?)
+                    }
+                    _ => (None, self.parse_expr()?),
+                }
+            };
+            entries.push(entry);
+            if !self.accept(",") && !self.accept(";") {
+                self.expect("}")?;
+                break;
+            }
+        }
+        Ok(Expr::Table(entries))
+    }
+
+    fn parse_suffixed_expr(&mut self) -> Result<Expr, ScriptError> {
+        let mut expr = match self.next() {
+            Token::Name(name) => Expr::Name(name),
+            Token::Symbol("(") => {
+                let expr = self.parse_expr()?;
+                self.expect(")")?;
+                expr
+            }
+            _ => return error(self.line(), "unexpected symbol"),
+        };
+
+        loop {
+            if self.accept(".") {
+                let key = self.expect_name()?;
+                expr = Expr::Index(Box::new(expr), Box::new(Expr::Literal(Value::String(key))));
+            } else if self.accept("[") {
+                let key = self.parse_expr()?;
+                self.expect("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(key));
+            } else if self.accept("(") {
+                let mut args = Vec::new();
+                if !self.accept(")") {
+                    loop {
+                        args.push(self.parse_expr()?);
+                        if self.accept(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                expr = Expr::Call(Box::new(expr), args);
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+}
+
+enum Flow {
+    Normal,
+    Break,
+    Return(Value),
+}
+
+struct Hook {
+    event: Rc<str>,
+    function: Value,
+}
+
+struct Definition {
+    kind: &'static str,
+    name: Rc<str>,
+    value: Value,
+}
+
+#[derive(Default)]
+struct EngineState {
+    globals: HashMap<Rc<str>, Value>,
+    hooks: Vec<Hook>,
+    definitions: Vec<Definition>,
+}
+
+struct Interpreter<'host> {
+    host: &'host mut dyn ScriptHost,
+    state: &'host RefCell<EngineState>,
+    locals: Vec<(Rc<str>, Value)>,
+    frame_start: usize,
+    call_depth: usize,
+    step_count: usize,
+    line: u32,
+}
+impl<'host> Interpreter<'host> {
+    fn exec_block(&mut self, block: &[Stmt]) -> Result<Flow, ScriptError> {
+        let locals_len = self.locals.len();
+        let mut flow = Flow::Normal;
+        for stmt in block {
+            flow = self.exec_stmt(stmt)?;
+            if !matches!(flow, Flow::Normal) {
+                break;
+            }
+        }
+        self.locals.truncate(locals_len);
+        Ok(flow)
+    }
+
+    fn exec_stmt(&mut self, stmt: &Stmt) -> Result<Flow, ScriptError> {
+        self.line = stmt.line;
+        self.step_count += 1;
+        if self.step_count > MAX_STEP_COUNT {
+            return error(self.line, "script took too long to run");
+        }
+
+        match &stmt.kind {
+            StmtKind::Local(name, value) => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Nil,
+                };
+                self.locals.push((name.clone(), value));
+            }
+            StmtKind::Assign(target, value) => {
+                let value = self.eval(value)?;
+                match target {
+                    Expr::Name(name) => self.set_variable(name, value),
+                    Expr::Index(table, key) => {
+                        let table = self.eval(table)?;
+                        let key = self.eval(key)?;
+                        match table {
+                            Value::Table(table) => table.borrow_mut().set(key, value, self.line)?,
+                            value => {
+                                return error(
+                                    self.line,
+                                    format!("can not index a {} value", value.type_name()),
+                                )
+                            }
+                        }
+                    }
+                    _ => return error(self.line, "can not assign to expression"),
+                }
+            }
+            StmtKind::Call(call) => {
+                self.eval(call)?;
+            }
+            StmtKind::If(branches, else_block) => {
+                for (condition, block) in branches {
+                    if self.eval(condition)?.is_truthy() {
+                        return self.exec_block(block);
+                    }
+                }
+                return self.exec_block(else_block);
+            }
+            StmtKind::While(condition, block) => {
+                while self.eval(condition)?.is_truthy() {
+                    match self.exec_block(block)? {
+                        Flow::Normal => (),
+                        Flow::Break => break,
+                        flow => return Ok(flow),
+                    }
+                    self.line = stmt.line;
+                    self.step_count += 1;
+                    if self.step_count > MAX_STEP_COUNT {
+                        return error(self.line, "script took too long to run");
+                    }
+                }
+            }
+            StmtKind::NumericFor(name, from, to, step, block) => {
+                let from = self.eval_number(from)?;
+                let to = self.eval_number(to)?;
+                let step = match step {
+                    Some(step) => self.eval_number(step)?,
+                    None => 1.0,
+                };
+                if step == 0.0 {
+                    return error(self.line, "for loop step can not be zero");
+                }
+
+                let mut i = from;
+                while (step > 0.0 && i <= to) || (step < 0.0 && i >= to) {
+                    self.locals.push((name.clone(), Value::Number(i)));
+                    let flow = self.exec_block(block)?;
+                    self.locals.pop();
+                    match flow {
+                        Flow::Normal => (),
+                        Flow::Break => break,
+                        flow => return Ok(flow),
+                    }
+                    i += step;
+                    self.line = stmt.line;
+                    self.step_count += 1;
+                    if self.step_count > MAX_STEP_COUNT {
+                        return error(self.line, "script took too long to run");
+                    }
+                }
+            }
+            StmtKind::GenericFor(key_name, value_name, table, block) => {
+                let entries = match self.eval(table)? {
+                    Value::Table(table) => table.borrow().entries(),
+                    value => {
+                        return error(
+                            self.line,
+                            format!("can not iterate a {} value", value.type_name()),
+                        )
+                    }
+                };
+                for (key, value) in entries {
+                    self.locals.push((key_name.clone(), key));
+                    if let Some(value_name) = value_name {
+                        self.locals.push((value_name.clone(), value));
+                    }
+                    let flow = self.exec_block(block)?;
+                    self.locals.pop();
+                    if value_name.is_some() {
+                        self.locals.pop();
+                    }
+                    match flow {
+                        Flow::Normal => (),
+                        Flow::Break => break,
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Nil,
+                };
+                return Ok(Flow::Return(value));
+            }
+            StmtKind::Break => return Ok(Flow::Break),
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    fn get_variable(&self, name: &str) -> Value {
+        for (local_name, value) in self.locals[self.frame_start..].iter().rev() {
+            if &**local_name == name {
+                return value.clone();
+            }
+        }
+        let state = self.state.borrow();
+        state.globals.get(name).cloned().unwrap_or(Value::Nil)
+    }
+
+    fn set_variable(&mut self, name: &Rc<str>, value: Value) {
+        for (local_name, local_value) in self.locals[self.frame_start..].iter_mut().rev() {
+            if local_name == name {
+                *local_value = value;
+                return;
+            }
+        }
+        self.state.borrow_mut().globals.insert(name.clone(), value);
+    }
+
+    fn eval_number(&mut self, expr: &Expr) -> Result<f64, ScriptError> {
+        match self.eval(expr)? {
+            Value::Number(n) => Ok(n),
+            value => error(
+                self.line,
+                format!("expected a number, got a {} value", value.type_name()),
+            ),
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value, ScriptError> {
+        match expr {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Name(name) => Ok(self.get_variable(name)),
+            Expr::Index(table, key) => {
+                let table = self.eval(table)?;
+                let key = self.eval(key)?;
+                match table {
+                    Value::Table(table) => Ok(table.borrow().get(&key)),
+                    value => error(
+                        self.line,
+                        format!("can not index a {} value", value.type_name()),
+                    ),
+                }
+            }
+            Expr::Call(function, args) => {
+                let function = self.eval(function)?;
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(self.eval(arg)?);
+                }
+                self.call(function, values)
+            }
+            Expr::Function(function) => Ok(Value::Function(function.clone())),
+            Expr::Table(entries) => {
+                let mut table = Table::default();
+                for (key, value) in entries {
+                    let key = match key {
+                        Some(key) => self.eval(key)?,
+                        None => Value::Number((table.array.len() + 1) as _),
+                    };
+                    let value = self.eval(value)?;
+                    table.set(key, value, self.line)?;
+                }
+                Ok(Value::Table(Rc::new(RefCell::new(table))))
+            }
+            Expr::Unary(op, expr) => {
+                let value = self.eval(expr)?;
+                match (op, value) {
+                    (UnaryOp::Not, value) => Ok(Value::Bool(!value.is_truthy())),
+                    (UnaryOp::Negate, Value::Number(n)) => Ok(Value::Number(-n)),
+                    (UnaryOp::Len, Value::String(s)) => Ok(Value::Number(s.len() as _)),
+                    (UnaryOp::Len, Value::Table(t)) => {
+                        Ok(Value::Number(t.borrow().array.len() as _))
+                    }
+                    (_, value) => error(
+                        self.line,
+                        format!("invalid operation on a {} value", value.type_name()),
+                    ),
+                }
+            }
+            Expr::Binary(BinaryOp::And, left, right) => {
+                let left = self.eval(left)?;
+                if left.is_truthy() {
+                    self.eval(right)
+                } else {
+                    Ok(left)
+                }
+            }
+            Expr::Binary(BinaryOp::Or, left, right) => {
+                let left = self.eval(left)?;
+                if left.is_truthy() {
+                    Ok(left)
+                } else {
+                    self.eval(right)
+                }
+            }
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                self.binary(*op, left, right)
+            }
+        }
+    }
+
+    fn binary(&mut self, op: BinaryOp, left: Value, right: Value) -> Result<Value, ScriptError> {
+        let value = match (op, &left, &right) {
+            (BinaryOp::Equals, _, _) => Value::Bool(left.equals(&right)),
+            (BinaryOp::NotEquals, _, _) => Value::Bool(!left.equals(&right)),
+            (BinaryOp::Concat, Value::String(_) | Value::Number(_), _)
+                if matches!(right, Value::String(_) | Value::Number(_)) =>
+            {
+                Value::String(format!("{}{}", left, right).into())
+            }
+            (_, Value::Number(a), Value::Number(b)) => {
+                let (a, b) = (*a, *b);
+                match op {
+                    BinaryOp::Add => Value::Number(a + b),
+                    BinaryOp::Sub => Value::Number(a - b),
+                    BinaryOp::Mul => Value::Number(a * b),
+                    BinaryOp::Div => Value::Number(a / b),
+                    BinaryOp::Mod => Value::Number(a - (a / b).floor() * b),
+                    BinaryOp::Less => Value::Bool(a < b),
+                    BinaryOp::LessEquals => Value::Bool(a <= b),
+                    BinaryOp::Greater => Value::Bool(a > b),
+                    BinaryOp::GreaterEquals => Value::Bool(a >= b),
+                    _ => return self.invalid_binary(&left, &right),
+                }
+            }
+            (_, Value::String(a), Value::String(b)) => match op {
+                BinaryOp::Less => Value::Bool(a < b),
+                BinaryOp::LessEquals => Value::Bool(a <= b),
+                BinaryOp::Greater => Value::Bool(a > b),
+                BinaryOp::GreaterEquals => Value::Bool(a >= b),
+                _ => return self.invalid_binary(&left, &right),
+            },
+            _ => return self.invalid_binary(&left, &right),
+        };
+        Ok(value)
+    }
+
+    fn invalid_binary(&self, left: &Value, right: &Value) -> Result<Value, ScriptError> {
+        error(
+            self.line,
+            format!(
+                "invalid operation between a {} and a {} value",
+                left.type_name(),
+                right.type_name()
+            ),
+        )
+    }
+
+    fn call(&mut self, function: Value, args: Vec<Value>) -> Result<Value, ScriptError> {
+        let function = match function {
+            Value::Function(function) => function,
+            Value::Builtin(builtin) => return self.call_builtin(builtin, args),
+            value => {
+                return error(
+                    self.line,
+                    format!("can not call a {} value", value.type_name()),
+                )
+            }
+        };
+
+        if self.call_depth >= MAX_CALL_DEPTH {
+            return error(self.line, "stack overflow");
+        }
+
+        let previous_frame_start = self.frame_start;
+        let previous_line = self.line;
+        self.frame_start = self.locals.len();
+        self.call_depth += 1;
+
+        let mut args = args.into_iter();
+        for param in &function.params {
+            let value = args.next().unwrap_or(Value::Nil);
+            self.locals.push((param.clone(), value));
+        }
+
+        let result = self.exec_block(&function.body);
+
+        self.locals.truncate(self.frame_start);
+        self.frame_start = previous_frame_start;
+        self.call_depth -= 1;
+
+        match result? {
+            Flow::Return(value) => {
+                self.line = previous_line;
+                Ok(value)
+            }
+            Flow::Break => error(self.line, "break outside loop"),
+            Flow::Normal => {
+                self.line = previous_line;
+                Ok(Value::Nil)
+            }
+        }
+    }
+
+    fn call_builtin(&mut self, builtin: Builtin, args: Vec<Value>) -> Result<Value, ScriptError> {
+        let line = self.line;
+        let arg = |index: usize| args.get(index).cloned().unwrap_or(Value::Nil);
+        let number_arg = |index: usize| match arg(index) {
+            Value::Number(n) => Ok(n),
+            value => error(
+                line,
+                format!(
+                    "expected a number as argument {}, got a {} value",
+                    index + 1,
+                    value.type_name()
+                ),
+            ),
+        };
+        let string_arg = |index: usize| match arg(index) {
+            Value::String(s) => Ok(s),
+            Value::Number(n) => Ok(Value::Number(n).to_string().into()),
+            value => error(
+                line,
+                format!(
+                    "expected a string as argument {}, got a {} value",
+                    index + 1,
+                    value.type_name()
+                ),
+            ),
+        };
+        let table_arg = |index: usize| match arg(index) {
+            Value::Table(t) => Ok(t),
+            value => error(
+                line,
+                format!(
+                    "expected a table as argument {}, got a {} value",
+                    index + 1,
+                    value.type_name()
+                ),
+            ),
+        };
+
+        let value = match builtin {
+            Builtin::Print => {
+                let mut message = String::new();
+                for (i, value) in args.iter().enumerate() {
+                    if i > 0 {
+                        message.push(' ');
+                    }
+                    message.push_str(&value.to_string());
+                }
+                self.host.print(&message);
+                Value::Nil
+            }
+            Builtin::Command => {
+                let command = string_arg(0)?;
+                if let Err(message) = self.host.command(&command) {
+                    return error(line, message);
+                }
+                Value::Nil
+            }
+            Builtin::On => {
+                let event = string_arg(0)?;
+                if !self.host.is_event(&event) {
+                    return error(line, format!("no such event '{}'", event));
+                }
+                let function = arg(1);
+                if !matches!(function, Value::Function(_) | Value::Builtin(_)) {
+                    return error(line, "expected a function as argument 2");
+                }
+                self.state.borrow_mut().hooks.push(Hook { event, function });
+                Value::Nil
+            }
+            Builtin::Host(function) => match self.host.call(function, &args) {
+                Ok(value) => value,
+                Err(message) => return error(line, message),
+            },
+            Builtin::Mode | Builtin::Panel | Builtin::StatusSegment | Builtin::Provide => {
+                let name = string_arg(0)?;
+                let (kind, value) = match builtin {
+                    Builtin::Mode => ("mode", Value::Table(table_arg(1)?)),
+                    Builtin::Panel => ("panel", Value::Table(table_arg(1)?)),
+                    Builtin::StatusSegment => match arg(1) {
+                        value @ Value::Function(_) => ("status-segment", value),
+                        _ => return error(line, "expected a function as argument 2"),
+                    },
+                    _ => match arg(1) {
+                        Value::Nil => return error(line, "expected a service as argument 2"),
+                        value => ("service", value),
+                    },
+                };
+                let mut state = self.state.borrow_mut();
+                let definitions = &mut state.definitions;
+                match definitions
+                    .iter_mut()
+                    .find(|d| d.kind == kind && d.name == name)
+                {
+                    Some(definition) => definition.value = value,
+                    None => definitions.push(Definition { kind, name, value }),
+                }
+                Value::Nil
+            }
+            Builtin::Service => {
+                let name = string_arg(0)?;
+                let state = self.state.borrow();
+                state
+                    .definitions
+                    .iter()
+                    .find(|d| d.kind == "service" && d.name == name)
+                    .map(|d| d.value.clone())
+                    .unwrap_or(Value::Nil)
+            }
+            Builtin::Send => {
+                let name = string_arg(0)?;
+                let service = self
+                    .state
+                    .borrow()
+                    .definitions
+                    .iter()
+                    .find(|d| d.kind == "service" && d.name == name)
+                    .map(|d| d.value.clone());
+                let endpoint = match service {
+                    Some(Value::Table(table)) => Value::Table(table).field("on_message"),
+                    Some(value) => value,
+                    None => return error(line, format!("no such service '{}'", name)),
+                };
+                if let Value::Nil = endpoint {
+                    return error(
+                        line,
+                        format!("service '{}' does not receive messages", name),
+                    );
+                }
+                return self.call(endpoint, args.into_iter().skip(1).collect());
+            }
+            Builtin::Error => return error(line, arg(0).to_string()),
+            Builtin::Type => Value::string(arg(0).type_name()),
+            Builtin::ToString => Value::String(arg(0).to_string().into()),
+            Builtin::ToNumber => match arg(0) {
+                Value::Number(n) => Value::Number(n),
+                Value::String(s) => match s.trim().parse() {
+                    Ok(n) => Value::Number(n),
+                    Err(_) => Value::Nil,
+                },
+                _ => Value::Nil,
+            },
+            Builtin::Pairs => Value::Table(table_arg(0)?),
+            Builtin::IPairs => {
+                let table = table_arg(0)?;
+                let array = table.borrow().array.clone();
+                Value::Table(Rc::new(RefCell::new(Table {
+                    array,
+                    fields: HashMap::new(),
+                })))
+            }
+            Builtin::TableInsert => {
+                let table = table_arg(0)?;
+                table.borrow_mut().array.push(arg(1));
+                Value::Nil
+            }
+            Builtin::TableRemove => {
+                let table = table_arg(0)?;
+                let value = table.borrow_mut().array.pop();
+                value.unwrap_or(Value::Nil)
+            }
+            Builtin::TableConcat => {
+                let table = table_arg(0)?;
+                let separator = match arg(1) {
+                    Value::Nil => "".into(),
+                    _ => string_arg(1)?,
+                };
+                let mut text = String::new();
+                for (i, value) in table.borrow().array.iter().enumerate() {
+                    if i > 0 {
+                        text.push_str(&separator);
+                    }
+                    text.push_str(&value.to_string());
+                }
+                Value::String(text.into())
+            }
+            Builtin::StringLen => Value::Number(string_arg(0)?.len() as _),
+            Builtin::StringSub => {
+                let s = string_arg(0)?;
+                let len = s.len() as f64;
+                let from = number_arg(1)?;
+                let to = match arg(2) {
+                    Value::Nil => len,
+                    _ => number_arg(2)?,
+                };
+                let from = if from < 0.0 { len + from + 1.0 } else { from };
+                let to = if to < 0.0 { len + to + 1.0 } else { to };
+                let from = (from.max(1.0) - 1.0) as usize;
+                let to = to.min(len).max(0.0) as usize;
+                match s.get(from..to) {
+                    Some(sub) if from < to => Value::string(sub),
+                    _ => Value::string(""),
+                }
+            }
+            Builtin::StringUpper => Value::String(string_arg(0)?.to_uppercase().into()),
+            Builtin::StringLower => Value::String(string_arg(0)?.to_lowercase().into()),
+            Builtin::StringFind => {
+                let s = string_arg(0)?;
+                let pattern = string_arg(1)?;
+                match s.find(&*pattern) {
+                    Some(i) => Value::Number((i + 1) as _),
+                    None => Value::Nil,
+                }
+            }
+            Builtin::MathFloor => Value::Number(number_arg(0)?.floor()),
+            Builtin::MathAbs => Value::Number(number_arg(0)?.abs()),
+            Builtin::MathMin => Value::Number(number_arg(0)?.min(number_arg(1)?)),
+            Builtin::MathMax => Value::Number(number_arg(0)?.max(number_arg(1)?)),
+        };
+        Ok(value)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ScriptEngine {
+    state: Rc<RefCell<EngineState>>,
+}
+impl ScriptEngine {
+    fn load_library(globals: &mut HashMap<Rc<str>, Value>, host_functions: &[&'static str]) {
+        fn library(functions: &[(&str, Builtin)]) -> Value {
+            let mut table = Table::default();
+            for &(name, builtin) in functions {
+                table.fields.insert(name.into(), Value::Builtin(builtin));
+            }
+            Value::Table(Rc::new(RefCell::new(table)))
+        }
+
+        for &(name, builtin) in &[
+            ("print", Builtin::Print),
+            ("command", Builtin::Command),
+            ("on", Builtin::On),
+            ("mode", Builtin::Mode),
+            ("panel", Builtin::Panel),
+            ("status_segment", Builtin::StatusSegment),
+            ("provide", Builtin::Provide),
+            ("service", Builtin::Service),
+            ("send", Builtin::Send),
+            ("error", Builtin::Error),
+            ("type", Builtin::Type),
+            ("tostring", Builtin::ToString),
+            ("tonumber", Builtin::ToNumber),
+            ("pairs", Builtin::Pairs),
+            ("ipairs", Builtin::IPairs),
+        ] {
+            globals.insert(name.into(), Value::Builtin(builtin));
+        }
+        globals.insert(
+            "table".into(),
+            library(&[
+                ("insert", Builtin::TableInsert),
+                ("remove", Builtin::TableRemove),
+                ("concat", Builtin::TableConcat),
+            ]),
+        );
+        globals.insert(
+            "string".into(),
+            library(&[
+                ("len", Builtin::StringLen),
+                ("sub", Builtin::StringSub),
+                ("upper", Builtin::StringUpper),
+                ("lower", Builtin::StringLower),
+                ("find", Builtin::StringFind),
+            ]),
+        );
+        globals.insert(
+            "math".into(),
+            library(&[
+                ("floor", Builtin::MathFloor),
+                ("abs", Builtin::MathAbs),
+                ("min", Builtin::MathMin),
+                ("max", Builtin::MathMax),
+            ]),
+        );
+
+        let mut editor = Table::default();
+        for &name in host_functions {
+            editor
+                .fields
+                .insert(name.into(), Value::Builtin(Builtin::Host(name)));
+        }
+        globals.insert("editor".into(), Value::Table(Rc::new(RefCell::new(editor))));
+    }
+
+    fn interpreter<'host>(&'host self, host: &'host mut dyn ScriptHost) -> Interpreter<'host> {
+        {
+            let mut state = self.state.borrow_mut();
+            if state.globals.is_empty() {
+                Self::load_library(&mut state.globals, host.functions());
+            }
+        }
+        Interpreter {
+            host,
+            state: &self.state,
+            locals: Vec::new(),
+            frame_start: 0,
+            call_depth: 0,
+            step_count: 0,
+            line: 1,
+        }
+    }
+
+    pub fn eval(&self, source: &str, host: &mut dyn ScriptHost) -> Result<Value, ScriptError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            index: 0,
+            depth: 0,
+        };
+        let block = parser.parse_block()?;
+        if !matches!(parser.peek(), Token::End) {
+            return error(parser.line(), "unexpected symbol");
+        }
+
+        let mut interpreter = self.interpreter(host);
+        match interpreter.exec_block(&block)? {
+            Flow::Return(value) => Ok(value),
+            Flow::Normal => Ok(Value::Nil),
+            Flow::Break => error(interpreter.line, "break outside loop"),
+        }
+    }
+
+    pub fn call(
+        &self,
+        function: Value,
+        args: Vec<Value>,
+        host: &mut dyn ScriptHost,
+    ) -> Result<Value, ScriptError> {
+        self.interpreter(host).call(function, args)
+    }
+
+    pub fn has_hooks(&self, event: &str) -> bool {
+        self.state.borrow().hooks.iter().any(|h| &*h.event == event)
+    }
+
+    pub fn trigger(
+        &self,
+        event: &str,
+        arg: Value,
+        host: &mut dyn ScriptHost,
+    ) -> Result<(), ScriptError> {
+        let functions: Vec<_> = self
+            .state
+            .borrow()
+            .hooks
+            .iter()
+            .filter(|h| &*h.event == event)
+            .map(|h| h.function.clone())
+            .collect();
+        let mut interpreter = self.interpreter(host);
+        for function in functions {
+            interpreter.call(function, vec![arg.clone()])?;
+        }
+        Ok(())
+    }
+
+    pub fn definition(&self, kind: &str, name: &str) -> Option<Value> {
+        let state = self.state.borrow();
+        let definition = state
+            .definitions
+            .iter()
+            .find(|d| d.kind == kind && &*d.name == name)?;
+        Some(definition.value.clone())
+    }
+
+    pub fn definitions(&self, kind: &str) -> Vec<(Rc<str>, Value)> {
+        let state = self.state.borrow();
+        state
+            .definitions
+            .iter()
+            .filter(|d| d.kind == kind)
+            .map(|d| (d.name.clone(), d.value.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestHost {
+        output: String,
+        commands: Vec<String>,
+    }
+    impl ScriptHost for TestHost {
+        fn print(&mut self, message: &str) {
+            self.output.push_str(message);
+            self.output.push('\n');
+        }
+
+        fn command(&mut self, command: &str) -> Result<(), String> {
+            if command == "fail" {
+                return Err("command failed".into());
+            }
+            self.commands.push(command.into());
+            Ok(())
+        }
+
+        fn is_event(&self, name: &str) -> bool {
+            name == "event"
+        }
+
+        fn functions(&self) -> &'static [&'static str] {
+            &["sum"]
+        }
+
+        fn call(&mut self, function: &'static str, args: &[Value]) -> Result<Value, String> {
+            match function {
+                "sum" => Ok(Value::Number(
+                    args.iter().filter_map(Value::as_number).sum(),
+                )),
+                _ => Err("unknown function".into()),
+            }
+        }
+    }
+
+    fn eval(source: &str) -> String {
+        let engine = ScriptEngine::default();
+        let mut host = TestHost::default();
+        match engine.eval(source, &mut host) {
+            Ok(value) => value.to_string(),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!("3", eval("return 1 + 2"));
+        assert_eq!("7", eval("return 1 + 2 * 3"));
+        assert_eq!("9", eval("return (1 + 2) * 3"));
+        assert_eq!("1", eval("return 7 % 3"));
+        assert_eq!("2.5", eval("return 5 / 2"));
+        assert_eq!("-4", eval("return -2 * 2"));
+        assert_eq!("true", eval("return 1 < 2 and 2 <= 2"));
+        assert_eq!("false", eval("return not (1 ~= 2)"));
+        assert_eq!("b", eval("return nil or false or 'b'"));
+        assert_eq!("a1.5", eval("return 'a' .. 1.5"));
+        assert_eq!("abc", eval("return 'a' .. 'b' .. 'c'"));
+        assert_eq!("3", eval("return #'abc'"));
+        assert_eq!("line\n", eval("return \"line\\n\""));
+    }
+
+    #[test]
+    fn statements() {
+        assert_eq!(
+            "10",
+            eval("local x = 0 for i = 1, 4 do x = x + i end return x")
+        );
+        assert_eq!(
+            "6",
+            eval("local x = 0 for i = 3, 1, -1 do x = x + i end return x")
+        );
+        assert_eq!(
+            "3",
+            eval("local i = 0 while true do i = i + 1 if i == 3 then break end end return i")
+        );
+        assert_eq!(
+            "small",
+            eval("local x = 2 if x > 5 then return 'big' elseif x > 1 then return 'small' else return 'tiny' end")
+        );
+        assert_eq!("2", eval("x = 1 do local x = 5 end x = x + 1 return x"));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            "120",
+            eval("function fact(n) if n <= 1 then return 1 end return n * fact(n - 1) end return fact(5)")
+        );
+        assert_eq!(
+            "5",
+            eval("local add = function(a, b) return a + b end return add(2, 3)")
+        );
+        assert_eq!("nil", eval("local function f() end return f()"));
+        assert_eq!(
+            "script error at line 1: stack overflow",
+            eval("function f() return f() end f()")
+        );
+    }
+
+    #[test]
+    fn tables() {
+        assert_eq!("3", eval("local t = {1, 2, 3} return #t"));
+        assert_eq!("2", eval("local t = {1, 2, 3} return t[2]"));
+        assert_eq!("v", eval("local t = {k = 'v'} return t.k"));
+        assert_eq!("v", eval("local t = {['k'] = 'v'} return t['k']"));
+        assert_eq!(
+            "1,2,3",
+            eval("local t = {} table.insert(t, 1) t[2] = 2 t[#t + 1] = 3 return table.concat(t, ',')")
+        );
+        assert_eq!(
+            "1a2bxy",
+            eval("local t = {'a', 'b', y = 'y', x = 'x'} local s = '' for k, v in t do if type(k) == 'number' then s = s .. k .. v else s = s .. v end end return s")
+        );
+        assert_eq!("3", eval("local t = {a = {b = {c = 3}}} return t.a.b.c"));
+        assert_eq!(
+            "script error at line 1: table index out of range",
+            eval("local t = {} t[3] = 1")
+        );
+    }
+
+    #[test]
+    fn library() {
+        assert_eq!("bc", eval("return string.sub('abcd', 2, 3)"));
+        assert_eq!("cd", eval("return string.sub('abcd', -2)"));
+        assert_eq!("ABC", eval("return string.upper('abc')"));
+        assert_eq!("3", eval("return string.find('abcd', 'cd')"));
+        assert_eq!("nil", eval("return tonumber('x')"));
+        assert_eq!("12", eval("return tonumber(' 12 ')"));
+        assert_eq!("3", eval("return math.floor(3.7)"));
+        assert_eq!("2", eval("return math.max(1, 2)"));
+    }
+
+    #[test]
+    fn host() {
+        let engine = ScriptEngine::default();
+        let mut host = TestHost::default();
+        assert!(engine
+            .eval(
+                "print('a', 1, nil) command('open ' .. 'file.txt')",
+                &mut host
+            )
+            .is_ok());
+        assert_eq!("a 1 nil\n", host.output);
+        assert_eq!(vec![String::from("open file.txt")], host.commands);
+
+        match engine.eval("\n\ncommand('fail')", &mut host) {
+            Ok(_) => panic!("command should fail"),
+            Err(error) => assert_eq!("script error at line 3: command failed", error.to_string()),
+        }
+
+        assert!(engine.eval("counter = 1", &mut host).is_ok());
+        match engine.eval("return counter + 1", &mut host) {
+            Ok(value) => assert_eq!("2", value.to_string()),
+            Err(_) => panic!("globals should persist between evaluations"),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            "script error at line 2: expected 'end'",
+            eval("if true then\n")
+        );
+        assert_eq!(
+            "script error at line 1: invalid operation between a number and a string value",
+            eval("return 1 + 'a'")
+        );
+        assert_eq!(
+            "script error at line 1: can not call a nil value",
+            eval("undefined()")
+        );
+        assert_eq!(
+            "script error at line 1: script took too long to run",
+            eval("while true do end")
+        );
+        assert_eq!(
+            "script error at line 1: script took too long to run",
+            eval("for i = 1, 1/0 do end")
+        );
+        assert_eq!(
+            "script error at line 1: script took too long to run",
+            eval("for i = 9007199254740992, 9007199254740994 do end")
+        );
+        assert_eq!("script error at line 1: boom", eval("error('boom')"));
+        assert_eq!("script error at line 1: expected '}'", eval("x = {"));
+        assert_eq!("script error at line 1: expected '}'", eval("x = {1,"));
+        assert_eq!(
+            "script error at line 1: script is nested too deeply",
+            eval(&format!("x = {}1", "(".repeat(60000)))
+        );
+        assert_eq!(
+            "script error at line 1: script is nested too deeply",
+            eval(&format!("x = 1{}", " + 1".repeat(60000)))
+        );
+        assert_eq!(
+            "script error at line 1: script is nested too deeply",
+            eval(&"if true then ".repeat(60000))
+        );
+    }
+
+    #[test]
+    fn hooks() {
+        let engine = ScriptEngine::default();
+        let mut host = TestHost::default();
+        assert!(engine
+            .eval(
+                "on('event', function(e) command('first ' .. e.value) end) on('event', function(e) print(e.value * 2) end)",
+                &mut host
+            )
+            .is_ok());
+        assert!(engine.has_hooks("event"));
+        assert!(!engine.has_hooks("other"));
+
+        let arg = Value::table(vec![("value", Value::Number(2.0))]);
+        assert!(engine.trigger("event", arg, &mut host).is_ok());
+        assert_eq!(vec![String::from("first 2")], host.commands);
+        assert_eq!("4\n", host.output);
+
+        match engine.eval("on('other', print)", &mut host) {
+            Ok(_) => panic!("should not register hook for unknown event"),
+            Err(error) => assert_eq!(
+                "script error at line 1: no such event 'other'",
+                error.to_string()
+            ),
+        }
+    }
+
+    #[test]
+    fn modes() {
+        let engine = ScriptEngine::default();
+        let mut host = TestHost::default();
+        assert!(engine.definition("mode", "tree").is_none());
+        assert!(engine
+            .eval("mode('tree', {keys = {j = 'cursor-down'}})", &mut host)
+            .is_ok());
+        let mode = engine.definition("mode", "tree").unwrap();
+        assert_eq!("cursor-down", mode.field("keys").field("j").to_string());
+        assert_eq!("nil", mode.field("on_enter").to_string());
+    }
+
+    #[test]
+    fn host_functions() {
+        assert_eq!("6", eval("return editor.sum(1, 2, 3)"));
+        assert_eq!("function", eval("return type(editor.sum)"));
+        assert_eq!("nil", eval("return editor.other"));
+    }
+
+    #[test]
+    fn definitions() {
+        let engine = ScriptEngine::default();
+        let mut host = TestHost::default();
+        assert!(engine
+            .eval(
+                "status_segment('b', function() return 'x' end) status_segment('a', function() end) panel('p', {})",
+                &mut host
+            )
+            .is_ok());
+        let names: Vec<_> = engine
+            .definitions("status-segment")
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(vec![Rc::from("b"), Rc::from("a")], names);
+        assert!(engine.definition("panel", "p").is_some());
+        assert!(engine.definition("panel", "b").is_none());
+        assert!(engine.eval("status_segment('c', {})", &mut host).is_err());
+    }
+
+    #[test]
+    fn table_fields() {
+        let engine = ScriptEngine::default();
+        let mut host = TestHost::default();
+        let value = match engine.eval("return { b = 'x', a = 1, 'array' }", &mut host) {
+            Ok(value) => value,
+            Err(_) => panic!("table should evaluate"),
+        };
+        let fields: Vec<_> = value
+            .fields()
+            .into_iter()
+            .map(|(name, value)| (name, value.to_string()))
+            .collect();
+        assert_eq!(
+            vec![
+                (Rc::from("a"), String::from("1")),
+                (Rc::from("b"), String::from("x")),
+            ],
+            fields
+        );
+        assert!(Value::Nil.fields().is_empty());
+    }
+
+    #[test]
+    fn services() {
+        let engine = ScriptEngine::default();
+        let mut host = TestHost::default();
+        let source = r#"
+provide("math2", { double = function(x) return x * 2 end })
+messages = {}
+provide("log", function(message) table.insert(messages, message) return #messages end)
+provide("counter", {
+    count = 0,
+    on_message = function(n) return n + 1 end,
+})
+send("log", "a")
+return service("math2").double(4) .. "," .. send("log", "b") .. "," .. send("counter", 2) .. "," .. type(service("none"))
+"#;
+        match engine.eval(source, &mut host) {
+            Ok(value) => assert_eq!("8,2,3,nil", value.to_string()),
+            Err(error) => panic!("{}", error),
+        }
+        assert!(engine.eval("send('none', 1)", &mut host).is_err());
+        assert!(engine.eval("send('math2', 1)", &mut host).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{
+    buffer::BufferHandle,
+    buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
+};
+
+pub struct SignDefinition {
+    pub name: String,
+    pub glyph: char,
+    pub color: String,
+}
+
+struct Sign {
+    line_index: BufferPositionIndex,
+    definition: u32,
+    namespace: u32,
+}
+
+struct BufferSigns {
+    buffer_handle: BufferHandle,
+    signs: Vec<Sign>,
+}
+
+#[derive(Default)]
+pub struct SignCollection {
+    definitions: Vec<SignDefinition>,
+    namespaces: Vec<String>,
+    buffers: Vec<BufferSigns>,
+}
+
+impl SignCollection {
+    fn namespace_index(&mut self, namespace: &str) -> u32 {
+        match self.namespaces.iter().position(|n| n == namespace) {
+            Some(index) => index as _,
+            None => {
+                self.namespaces.push(namespace.into());
+                (self.namespaces.len() - 1) as _
+            }
+        }
+    }
+
+    fn buffer_mut(&mut self, buffer_handle: BufferHandle) -> Option<&mut BufferSigns> {
+        self.buffers
+            .iter_mut()
+            .find(|b| b.buffer_handle == buffer_handle)
+    }
+
+    pub fn define(&mut self, name: &str, glyph: char, color: &str) {
+        let definition = SignDefinition {
+            name: name.into(),
+            glyph,
+            color: color.into(),
+        };
+        match self.definitions.iter_mut().find(|d| d.name == name) {
+            Some(d) => *d = definition,
+            None => self.definitions.push(definition),
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        buffer_handle: BufferHandle,
+        namespace: &str,
+        line_index: BufferPositionIndex,
+        name: &str,
+    ) -> bool {
+        let definition = match self.definitions.iter().position(|d| d.name == name) {
+            Some(index) => index as _,
+            None => return false,
+        };
+        let namespace = self.namespace_index(namespace);
+        let index = match self
+            .buffers
+            .iter()
+            .position(|b| b.buffer_handle == buffer_handle)
+        {
+            Some(index) => index,
+            None => {
+                self.buffers.push(BufferSigns {
+                    buffer_handle,
+                    signs: Vec::new(),
+                });
+                self.buffers.len() - 1
+            }
+        };
+
+        let signs = &mut self.buffers[index].signs;
+        signs.retain(|s| s.namespace != namespace || s.line_index != line_index);
+        signs.push(Sign {
+            line_index,
+            definition,
+            namespace,
+        });
+        true
+    }
+
+    pub fn remove(
+        &mut self,
+        buffer_handle: BufferHandle,
+        namespace: &str,
+        line_index: BufferPositionIndex,
+    ) {
+        let namespace = match self.namespaces.iter().position(|n| n == namespace) {
+            Some(index) => index as u32,
+            None => return,
+        };
+        if let Some(buffer) = self.buffer_mut(buffer_handle) {
+            buffer
+                .signs
+                .retain(|s| s.namespace != namespace || s.line_index != line_index);
+        }
+    }
+
+    pub fn clear(&mut self, buffer_handle: Option<BufferHandle>, namespace: &str) {
+        let namespace = match self.namespaces.iter().position(|n| n == namespace) {
+            Some(index) => index as u32,
+            None => return,
+        };
+        for buffer in &mut self.buffers {
+            if buffer_handle
+                .map(|h| h == buffer.buffer_handle)
+                .unwrap_or(true)
+            {
+                buffer.signs.retain(|s| s.namespace != namespace);
+            }
+        }
+    }
+
+    pub fn has_signs(&self, buffer_handle: BufferHandle) -> bool {
+        self.buffers
+            .iter()
+            .any(|b| b.buffer_handle == buffer_handle && !b.signs.is_empty())
+    }
+
+    // the most recently placed sign on that line
+    pub fn line_sign(
+        &self,
+        buffer_handle: BufferHandle,
+        line_index: BufferPositionIndex,
+    ) -> Option<&SignDefinition> {
+        let buffer = self
+            .buffers
+            .iter()
+            .find(|b| b.buffer_handle == buffer_handle)?;
+        let sign = buffer
+            .signs
+            .iter()
+            .rev()
+            .find(|s| s.line_index == line_index)?;
+        self.definitions.get(sign.definition as usize)
+    }
+
+    pub fn on_buffer_insert_text(&mut self, buffer_handle: BufferHandle, range: BufferRange) {
+        if let Some(buffer) = self.buffer_mut(buffer_handle) {
+            for sign in &mut buffer.signs {
+                let position = BufferPosition::line_col(sign.line_index, 0).insert(range);
+                sign.line_index = position.line_index;
+            }
+        }
+    }
+
+    pub fn on_buffer_delete_text(&mut self, buffer_handle: BufferHandle, range: BufferRange) {
+        if let Some(buffer) = self.buffer_mut(buffer_handle) {
+            for sign in &mut buffer.signs {
+                let position = BufferPosition::line_col(sign.line_index, 0).delete(range);
+                sign.line_index = position.line_index;
+            }
+        }
+    }
+
+    pub fn on_buffer_close(&mut self, buffer_handle: BufferHandle) {
+        self.buffers.retain(|b| b.buffer_handle != buffer_handle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(from_line: u32, to_line: u32) -> BufferRange {
+        BufferRange::between(
+            BufferPosition::line_col(from_line as _, 0),
+            BufferPosition::line_col(to_line as _, 0),
+        )
+    }
+
+    #[test]
+    fn add_remove_and_follow_edits() {
+        let buffer = BufferHandle(0);
+        let mut signs = SignCollection::default();
+        signs.define("breakpoint", 'B', "gutter_sign");
+        signs.define("added", '+', "diff_added_background");
+
+        assert!(!signs.add(buffer, "debug", 1, "nothing"));
+        assert!(!signs.has_signs(buffer));
+
+        assert!(signs.add(buffer, "debug", 1, "breakpoint"));
+        assert!(signs.add(buffer, "git", 1, "added"));
+        assert!(signs.add(buffer, "git", 4, "added"));
+        assert!(signs.has_signs(buffer));
+
+        let glyph = |signs: &SignCollection, line| signs.line_sign(buffer, line).map(|d| d.glyph);
+        assert_eq!(Some('+'), glyph(&signs, 1));
+        assert_eq!(None, glyph(&signs, 2));
+
+        signs.remove(buffer, "git", 1);
+        assert_eq!(Some('B'), glyph(&signs, 1));
+
+        signs.on_buffer_insert_text(buffer, range(0, 2));
+        assert_eq!(Some('B'), glyph(&signs, 3));
+        assert_eq!(Some('+'), glyph(&signs, 6));
+        signs.on_buffer_delete_text(buffer, range(2, 4));
+        assert_eq!(Some('B'), glyph(&signs, 2));
+        assert_eq!(Some('+'), glyph(&signs, 4));
+
+        signs.clear(None, "debug");
+        assert_eq!(None, glyph(&signs, 2));
+        signs.on_buffer_close(buffer);
+        assert!(!signs.has_signs(buffer));
+    }
+}
@@ -0,0 +1,271 @@
+use std::{path::Path, process::Stdio};
+
+use crate::{
+    client::{ClientHandle, ClientManager},
+    command::{expand_params, CommandError, CommandManager},
+    editor::Editor,
+    editor_utils::{parse_process_command, MessageKind, ProcessPipelineIter},
+    platform::{Platform, PlatformRequest, ProcessHandle, ProcessTag},
+};
+
+const OUTPUT_PARAMS: &[&str] = &["output"];
+
+pub struct SpawnOptions<'a> {
+    pub stderr_to_output: bool,
+    pub on_output: Option<&'a str>,
+    pub on_error: Option<&'a str>,
+}
+
+struct SpawnStage {
+    handle: Option<ProcessHandle>,
+    pending_input: Vec<u8>,
+    input_closed: bool,
+}
+
+struct SpawnJob {
+    alive: bool,
+    client_handle: Option<ClientHandle>,
+    stderr_to_output: bool,
+    on_output: String,
+    on_error: String,
+    stages: Vec<SpawnStage>,
+    running_stages: usize,
+    output: Vec<u8>,
+    error_output: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct SpawnCollection {
+    jobs: Vec<SpawnJob>,
+}
+
+impl SpawnCollection {
+    pub fn spawn(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        client_handle: Option<ClientHandle>,
+        command: &str,
+        options: SpawnOptions,
+    ) -> bool {
+        if ProcessPipelineIter::new(command).any(|c| parse_process_command(c).is_none())
+            || ProcessPipelineIter::new(command).count() > u8::MAX as usize
+        {
+            return false;
+        }
+
+        let spawns = &mut editor.spawns;
+        let index = match spawns.jobs.iter().position(|j| !j.alive) {
+            Some(index) => index,
+            None => {
+                spawns.jobs.push(SpawnJob {
+                    alive: false,
+                    client_handle: None,
+                    stderr_to_output: false,
+                    on_output: String::new(),
+                    on_error: String::new(),
+                    stages: Vec::new(),
+                    running_stages: 0,
+                    output: Vec::new(),
+                    error_output: Vec::new(),
+                });
+                spawns.jobs.len() - 1
+            }
+        };
+
+        let job = &mut spawns.jobs[index];
+        job.alive = true;
+        job.client_handle = client_handle;
+        job.stderr_to_output = options.stderr_to_output;
+        job.on_output.clear();
+        job.on_output.push_str(options.on_output.unwrap_or(""));
+        job.on_error.clear();
+        job.on_error.push_str(options.on_error.unwrap_or(""));
+        job.stages.clear();
+        job.output.clear();
+        job.error_output.clear();
+
+        // every stage runs at the same time and each one's output is forwarded
+        // to the next one's input as soon as it arrives
+        for (stage, stage_command) in ProcessPipelineIter::new(command).enumerate() {
+            job.stages.push(SpawnStage {
+                handle: None,
+                pending_input: Vec::new(),
+                input_closed: false,
+            });
+            spawn_stage(
+                platform,
+                &editor.current_directory,
+                index,
+                stage as _,
+                stage_command,
+                job,
+            );
+        }
+        job.running_stages = job.stages.len();
+        true
+    }
+
+    pub fn on_process_spawned(
+        &mut self,
+        platform: &mut Platform,
+        index: usize,
+        stage: u8,
+        handle: ProcessHandle,
+    ) {
+        let stage = &mut self.jobs[index].stages[stage as usize];
+        stage.handle = Some(handle);
+        if !stage.pending_input.is_empty() {
+            let mut buf = platform.buf_pool.acquire();
+            buf.write().extend_from_slice(&stage.pending_input);
+            stage.pending_input.clear();
+            platform
+                .requests
+                .enqueue(PlatformRequest::WriteToProcess { handle, buf });
+        }
+        if stage.input_closed {
+            platform
+                .requests
+                .enqueue(PlatformRequest::CloseProcessInput { handle });
+        }
+    }
+
+    pub fn on_process_output(
+        &mut self,
+        platform: &mut Platform,
+        index: usize,
+        stage: u8,
+        bytes: &[u8],
+    ) {
+        let job = &mut self.jobs[index];
+        let next_stage = match job.stages.get_mut(stage as usize + 1) {
+            Some(next_stage) => next_stage,
+            None => {
+                job.output.extend_from_slice(bytes);
+                return;
+            }
+        };
+
+        match next_stage.handle {
+            Some(handle) => {
+                let mut buf = platform.buf_pool.acquire();
+                buf.write().extend_from_slice(bytes);
+                platform
+                    .requests
+                    .enqueue(PlatformRequest::WriteToProcess { handle, buf });
+            }
+            None => next_stage.pending_input.extend_from_slice(bytes),
+        }
+    }
+
+    pub fn on_process_error_output(&mut self, index: usize, bytes: &[u8]) {
+        let job = &mut self.jobs[index];
+        if job.stderr_to_output {
+            job.output.extend_from_slice(bytes);
+        } else {
+            job.error_output.extend_from_slice(bytes);
+        }
+    }
+
+    pub fn on_process_exit(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        clients: &mut ClientManager,
+        index: usize,
+        stage: u8,
+    ) {
+        let job = &mut editor.spawns.jobs[index];
+        if let Some(next_stage) = job.stages.get_mut(stage as usize + 1) {
+            next_stage.input_closed = true;
+            if let Some(handle) = next_stage.handle {
+                platform
+                    .requests
+                    .enqueue(PlatformRequest::CloseProcessInput { handle });
+            }
+        }
+
+        job.running_stages = job.running_stages.saturating_sub(1);
+        if job.running_stages > 0 {
+            return;
+        }
+
+        job.alive = false;
+        let client_handle = job.client_handle.filter(|&h| clients.try_get(h).is_some());
+
+        let mut commands = editor.string_pool.acquire();
+        let result = expand_callback(&job.on_output, &job.output, &mut commands).and_then(|_| {
+            if job.error_output.is_empty() {
+                Ok(())
+            } else {
+                commands.push('\n');
+                expand_callback(&job.on_error, &job.error_output, &mut commands)
+            }
+        });
+        let result = match result {
+            Ok(()) => {
+                CommandManager::eval_commands(editor, platform, clients, client_handle, &commands)
+                    .map(|_| ())
+            }
+            Err(error) => Err(error),
+        };
+        editor.string_pool.release(commands);
+
+        if let Err(error) = result {
+            editor
+                .status_bar
+                .write(MessageKind::Error)
+                .fmt(format_args!("{}", error));
+        }
+    }
+}
+
+fn spawn_stage(
+    platform: &mut Platform,
+    current_directory: &Path,
+    index: usize,
+    stage: u8,
+    stage_command: &str,
+    job: &SpawnJob,
+) {
+    let mut command = match parse_process_command(stage_command) {
+        Some(command) => command,
+        None => return,
+    };
+
+    command.current_dir(current_directory);
+    if stage > 0 {
+        command.stdin(Stdio::piped());
+    } else {
+        command.stdin(Stdio::null());
+    }
+    command.stdout(Stdio::piped());
+    if job.stderr_to_output || !job.on_error.is_empty() {
+        command.stderr(Stdio::piped());
+    } else {
+        command.stderr(Stdio::null());
+    }
+
+    platform.requests.enqueue(PlatformRequest::SpawnProcess {
+        tag: ProcessTag::Spawn(index, stage),
+        command,
+        buf_len: 4 * 1024,
+    });
+}
+
+fn expand_callback(
+    callback: &str,
+    output: &[u8],
+    commands: &mut String,
+) -> Result<(), CommandError> {
+    if callback.is_empty() {
+        return Ok(());
+    }
+
+    let output = match std::str::from_utf8(output) {
+        Ok(output) => output,
+        Err(_) => return Err(CommandError::InvalidProcessOutput),
+    };
+    let output = output.strip_suffix('\n').unwrap_or(output);
+    let output = output.strip_suffix('\r').unwrap_or(output);
+
+    expand_params(callback, OUTPUT_PARAMS, &[Some(output)], commands)
+}
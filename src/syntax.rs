@@ -5,6 +5,7 @@ use crate::{
     buffer_position::{BufferPositionIndex, BufferRange},
     editor_utils::hash_bytes,
     glob::{Glob, InvalidGlobError},
+    modeline::Modeline,
     pattern::{MatchResult, Pattern, PatternError, PatternState},
 };
 
@@ -74,7 +75,9 @@ impl Default for LineParseState {
 pub struct Syntax {
     glob_hash: u64,
     glob: Glob,
+    glob_pattern: String,
     rules: [Pattern; 7],
+    completion_triggers: String,
 }
 
 impl Syntax {
@@ -84,6 +87,7 @@ impl Syntax {
         Self {
             glob_hash: 0,
             glob: Glob::default(),
+            glob_pattern: String::new(),
             rules: [
                 Pattern::new(),
                 Pattern::new(),
@@ -93,6 +97,7 @@ impl Syntax {
                 Pattern::new(),
                 text_pattern,
             ],
+            completion_triggers: String::new(),
         }
     }
 
@@ -100,17 +105,33 @@ impl Syntax {
         for r in &mut self.rules {
             r.clear();
         }
+        self.completion_triggers.clear();
     }
 
     fn set_glob(&mut self, glob: &str, glob_hash: u64) -> Result<(), InvalidGlobError> {
         self.glob_hash = glob_hash;
+        self.glob_pattern.clear();
+        self.glob_pattern.push_str(glob);
         self.glob.compile(glob)
     }
 
+    pub fn glob_pattern(&self) -> &str {
+        &self.glob_pattern
+    }
+
     pub fn set_rule(&mut self, kind: TokenKind, pattern: &str) -> Result<(), PatternError> {
         self.rules[kind as usize].compile(pattern)
     }
 
+    pub fn completion_triggers(&self) -> &str {
+        &self.completion_triggers
+    }
+
+    pub fn set_completion_triggers(&mut self, triggers: &str) {
+        self.completion_triggers.clear();
+        self.completion_triggers.push_str(triggers);
+    }
+
     fn parse_line(
         &self,
         line: &str,
@@ -217,12 +238,60 @@ impl Syntax {
     }
 }
 
-#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct SyntaxHandle(u32);
 
+enum SyntaxDetector {
+    Shebang(String),
+    ContentPrefix(String),
+    Modeline(String),
+    Path(Glob),
+}
+
+struct SyntaxDetection {
+    detector: SyntaxDetector,
+    path: String,
+}
+
+fn shebang_interpreter(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = words.find(|w| !w.starts_with('-') && !w.contains('='))?;
+    }
+    Some(interpreter)
+}
+
+fn doctype_path(first_text: &str) -> Option<&'static str> {
+    fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+        let text = text.as_bytes();
+        text.len() >= prefix.len() && text[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+    }
+
+    if starts_with_ignore_case(first_text, "<!doctype html")
+        || starts_with_ignore_case(first_text, "<html")
+    {
+        Some("detect.html")
+    } else if starts_with_ignore_case(first_text, "<?xml")
+        || starts_with_ignore_case(first_text, "<!doctype")
+    {
+        Some("detect.xml")
+    } else {
+        None
+    }
+}
+
+fn interpreter_matches(interpreter: &str, name: &str) -> bool {
+    match interpreter.strip_prefix(name) {
+        Some(version) => version.chars().all(|c| c.is_ascii_digit() || c == '.'),
+        None => false,
+    }
+}
+
 pub struct SyntaxCollection {
     syntaxes: Vec<Syntax>,
     current_syntax_index: u32,
+    detections: Vec<SyntaxDetection>,
 }
 
 impl SyntaxCollection {
@@ -230,9 +299,85 @@ impl SyntaxCollection {
         Self {
             syntaxes: vec![Syntax::new()],
             current_syntax_index: 0,
+            detections: Vec::new(),
         }
     }
 
+    pub fn add_detection(&mut self, detector: &str, path: &str) -> Result<(), InvalidGlobError> {
+        let detector = if let Some(interpreter) = detector.strip_prefix("#!") {
+            SyntaxDetector::Shebang(interpreter.into())
+        } else if let Some(prefix) = detector.strip_prefix('^') {
+            SyntaxDetector::ContentPrefix(prefix.into())
+        } else if let Some(name) = detector.strip_prefix('=') {
+            SyntaxDetector::Modeline(name.into())
+        } else {
+            let mut glob = Glob::default();
+            glob.compile(detector)?;
+            SyntaxDetector::Path(glob)
+        };
+
+        self.detections.push(SyntaxDetection {
+            detector,
+            path: path.into(),
+        });
+        Ok(())
+    }
+
+    pub fn detect_handle(&self, path: &str, content: &BufferContent) -> Option<SyntaxHandle> {
+        let modeline = Modeline::find(content).and_then(|m| m.filetype);
+        if let Some(name) = modeline {
+            if let Some(handle) = self.detect_handle_from_modeline(name) {
+                return Some(handle);
+            }
+        }
+
+        if let Some(handle) = self.find_handle_by_path(path) {
+            return Some(handle);
+        }
+
+        let interpreter = shebang_interpreter(content.line_at(0).as_str());
+        let first_text = content
+            .lines()
+            .map(|l| l.as_str().trim_start())
+            .find(|l| !l.is_empty())
+            .unwrap_or("");
+
+        for detection in self.detections.iter().rev() {
+            let matches = match &detection.detector {
+                SyntaxDetector::Shebang(name) => match interpreter {
+                    Some(interpreter) => interpreter_matches(interpreter, name),
+                    None => false,
+                },
+                SyntaxDetector::ContentPrefix(prefix) => first_text.starts_with(&prefix[..]),
+                SyntaxDetector::Modeline(_) => false,
+                SyntaxDetector::Path(glob) => glob.matches(path),
+            };
+            if matches {
+                if let Some(handle) = self.find_handle_by_path(&detection.path) {
+                    return Some(handle);
+                }
+            }
+        }
+
+        doctype_path(first_text).and_then(|path| self.find_handle_by_path(path))
+    }
+
+    fn detect_handle_from_modeline(&self, name: &str) -> Option<SyntaxHandle> {
+        for detection in self.detections.iter().rev() {
+            if let SyntaxDetector::Modeline(n) = &detection.detector {
+                if n == name {
+                    if let Some(handle) = self.find_handle_by_path(&detection.path) {
+                        return Some(handle);
+                    }
+                }
+            }
+        }
+
+        let mut path = String::from("detect.");
+        path.push_str(name);
+        self.find_handle_by_path(&path)
+    }
+
     pub fn find_handle_by_path(&self, path: &str) -> Option<SyntaxHandle> {
         let mut iter = self.syntaxes.iter().enumerate();
         iter.next();
@@ -461,6 +606,118 @@ mod tests {
         assert_eq!(slice, &line[token.from as usize..token.to as usize]);
     }
 
+    #[test]
+    fn syntax_detection() {
+        fn detect(syntaxes: &SyntaxCollection, path: &str, text: &str) -> SyntaxHandle {
+            let mut content = BufferContent::new();
+            content.insert_text(BufferPosition::zero(), text);
+            syntaxes.detect_handle(path, &content).unwrap_or_default()
+        }
+
+        let mut syntaxes = SyntaxCollection::new();
+        syntaxes.set_current_from_glob("**/*.py").unwrap();
+        syntaxes.set_current_from_glob("**/*.mk").unwrap();
+        syntaxes.set_current_from_glob("**/*.json").unwrap();
+        let python = SyntaxHandle(1);
+        let make = SyntaxHandle(2);
+        let json = SyntaxHandle(3);
+
+        syntaxes.add_detection("#!python", "detect.py").unwrap();
+        syntaxes.add_detection("**/Makefile", "detect.mk").unwrap();
+        syntaxes.add_detection("^{", "detect.json").unwrap();
+        syntaxes
+            .add_detection("#!unknown", "detect.unknown")
+            .unwrap();
+
+        assert_eq!(python, detect(&syntaxes, "src/main.py", ""));
+        assert_eq!(python, detect(&syntaxes, "script", "#!/usr/bin/python\n"));
+        assert_eq!(
+            python,
+            detect(&syntaxes, "script", "#!/usr/bin/env python3.11 -u")
+        );
+        assert_eq!(
+            python,
+            detect(
+                &syntaxes,
+                "script",
+                "#!/usr/bin/env -S PYTHONPATH=. python3"
+            )
+        );
+        assert_eq!(
+            SyntaxHandle(0),
+            detect(&syntaxes, "script", "#!/bin/pythonic")
+        );
+        assert_eq!(
+            SyntaxHandle(0),
+            detect(&syntaxes, "script", "#!/bin/unknown")
+        );
+        assert_eq!(make, detect(&syntaxes, "project/Makefile", "all:"));
+        assert_eq!(json, detect(&syntaxes, "data", "\n  { \"a\": 1 }"));
+        assert_eq!(SyntaxHandle(0), detect(&syntaxes, "data", "a {"));
+
+        syntaxes.add_detection("^{", "detect.py").unwrap();
+        assert_eq!(python, detect(&syntaxes, "data", "{"));
+    }
+
+    #[test]
+    fn syntax_detection_modelines_and_doctypes() {
+        fn detect(syntaxes: &SyntaxCollection, path: &str, text: &str) -> SyntaxHandle {
+            let mut content = BufferContent::new();
+            content.insert_text(BufferPosition::zero(), text);
+            syntaxes.detect_handle(path, &content).unwrap_or_default()
+        }
+
+        let mut syntaxes = SyntaxCollection::new();
+        syntaxes.set_current_from_glob("**/*.py").unwrap();
+        syntaxes.set_current_from_glob("**/*.{html,xml}").unwrap();
+        syntaxes.set_current_from_glob("**/*.sh").unwrap();
+        let python = SyntaxHandle(1);
+        let markup = SyntaxHandle(2);
+        let shell = SyntaxHandle(3);
+
+        syntaxes.add_detection("=python", "detect.py").unwrap();
+
+        assert_eq!(
+            python,
+            detect(&syntaxes, "notes.txt", "# vim: set ft=python:")
+        );
+        assert_eq!(
+            python,
+            detect(&syntaxes, "notes.txt", "# vim:ts=4:syntax=python")
+        );
+        assert_eq!(
+            shell,
+            detect(&syntaxes, "script", "# -*- mode: sh; coding: utf-8 -*-")
+        );
+        assert_eq!(python, detect(&syntaxes, "script", "# -*- python -*-"));
+        assert_eq!(
+            python,
+            detect(
+                &syntaxes,
+                "main.sh",
+                "1\n2\n3\n4\n5\n6\n7\n# vim: ft=python"
+            )
+        );
+        assert_eq!(
+            SyntaxHandle(0),
+            detect(
+                &syntaxes,
+                "data",
+                "1\n2\n3\n4\n5\n# vim: ft=python\n7\n8\n9\n10\n11"
+            )
+        );
+        assert_eq!(
+            SyntaxHandle(0),
+            detect(&syntaxes, "data", "nvim: ft=python")
+        );
+        assert_eq!(shell, detect(&syntaxes, "main.sh", "# vim: ft=unknown"));
+
+        assert_eq!(markup, detect(&syntaxes, "page", "<!DOCTYPE html>\n<html>"));
+        assert_eq!(markup, detect(&syntaxes, "page", "  <HTML lang=\"en\">"));
+        assert_eq!(markup, detect(&syntaxes, "data", "<?xml version=\"1.0\"?>"));
+        assert_eq!(SyntaxHandle(0), detect(&syntaxes, "data", "<root/>"));
+    }
+
     #[test]
     fn no_syntax() {
         let syntax = Syntax::new();
@@ -33,6 +33,13 @@ macro_rules! theme_colors {
                     _ => None,
                 }
             }
+
+            pub fn color(&self, name: &str) -> Option<Color> {
+                match name {
+                    $(stringify!($color) => Some(self.$color),)*
+                    _ => None,
+                }
+            }
         }
     }
 }
@@ -45,8 +52,17 @@ theme_colors! {
     select_cursor,
     insert_cursor,
     inactive_cursor,
+    other_client_cursor,
+    other_client_selection,
     statusbar_active_background,
     statusbar_inactive_background,
+    diff_removed_background,
+    diff_added_background,
+    gutter_background,
+    gutter_line_number,
+    gutter_active_line_number,
+    gutter_sign,
+    debug_line_background,
 
     token_whitespace,
     token_text,
@@ -73,8 +89,17 @@ pub fn gruvbox_theme() -> Theme {
         insert_cursor: Color::from_u32(0xfabd2f),
         select_cursor: Color::from_u32(0x458588),
         inactive_cursor: Color::from_u32(0x504945),
+        other_client_cursor: Color::from_u32(0xb16286),
+        other_client_selection: Color::from_u32(0x3c3836),
         statusbar_active_background: Color::from_u32(0x504945),
         statusbar_inactive_background: Color::from_u32(0x282828),
+        diff_removed_background: Color::from_u32(0x3c1f1e),
+        diff_added_background: Color::from_u32(0x32361a),
+        gutter_background: Color::from_u32(0x1d2021),
+        gutter_line_number: Color::from_u32(0x504945),
+        gutter_active_line_number: Color::from_u32(0xfabd2f),
+        gutter_sign: Color::from_u32(0xfb4934),
+        debug_line_background: Color::from_u32(0x3c3a1e),
 
         token_whitespace: Color::from_u32(0x504945),
         token_text: Color::from_u32(0xebdbb2),
@@ -0,0 +1,177 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{editor::Editor, glob::IgnoreList, picker::Picker, platform::Job};
+
+const MAX_FILE_LEN: u64 = 1024 * 1024;
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+pub fn find_keyword(line: &str, keywords: &str) -> Option<usize> {
+    let mut found = None;
+    for keyword in keywords.split_whitespace() {
+        for (i, _) in line.match_indices(keyword) {
+            let before = line[..i].chars().next_back();
+            let after = line[i + keyword.len()..].chars().next();
+            if !before.map(is_word_char).unwrap_or(false)
+                && !after.map(is_word_char).unwrap_or(false)
+            {
+                if found.map(|f| i < f).unwrap_or(true) {
+                    found = Some(i);
+                }
+                break;
+            }
+        }
+    }
+    found
+}
+
+fn for_each_todo<'a, I, F>(lines: I, keywords: &str, mut f: F)
+where
+    I: Iterator<Item = &'a str>,
+    F: FnMut(usize, usize, &'a str),
+{
+    for (line_index, line) in lines.enumerate() {
+        if let Some(index) = find_keyword(line, keywords) {
+            let column = line[..index].chars().count();
+            f(line_index + 1, column + 1, line[index..].trim_end());
+        }
+    }
+}
+
+pub fn add_entries<'a, I>(picker: &mut Picker, path: &str, lines: I, keywords: &str) -> usize
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut count = 0;
+    for_each_todo(lines, keywords, |line, column, text| {
+        picker.add_custom_entry_fmt(format_args!("{}:{},{} {}", path, line, column, text));
+        count += 1;
+    });
+    count
+}
+
+pub fn add_buffer_entries(editor: &mut Editor, root: Option<&Path>) {
+    let current_directory = &editor.current_directory;
+    let mut buffers: Vec<_> = editor
+        .buffers
+        .iter()
+        .filter(|b| b.capabilities.can_save && !b.path.as_os_str().is_empty())
+        .filter(|b| match root {
+            Some(root) => current_directory.join(&b.path).starts_with(root),
+            None => true,
+        })
+        .collect();
+    buffers.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+    for buffer in buffers {
+        add_entries(
+            &mut editor.picker,
+            buffer.path.to_str().unwrap_or(""),
+            buffer.content().lines().map(|l| l.as_str()),
+            &editor.config.todo_keywords,
+        );
+    }
+}
+
+pub fn workspace_job(editor: &Editor, root: PathBuf, ignore: IgnoreList) -> Job {
+    let current_directory = editor.current_directory.clone();
+    let keywords = editor.config.todo_keywords.clone();
+    let open_paths: Vec<_> = editor
+        .buffers
+        .iter()
+        .map(|b| current_directory.join(&b.path))
+        .collect();
+
+    Box::new(move |output| {
+        let mut paths = Vec::new();
+        workspace_files(&root, &ignore, &mut paths);
+
+        let mut text = String::new();
+        for path in paths {
+            if open_paths.contains(&path) {
+                continue;
+            }
+            text.clear();
+            if File::open(&path)
+                .and_then(|mut f| f.read_to_string(&mut text))
+                .is_err()
+            {
+                continue;
+            }
+
+            let path = path.strip_prefix(&current_directory).unwrap_or(&path);
+            let path = path.to_str().unwrap_or("");
+            for_each_todo(text.lines(), &keywords, |line, column, text| {
+                let _ = writeln!(output, "{}:{},{} {}", path, line, column, text);
+            });
+        }
+    })
+}
+
+pub fn workspace_files(root: &Path, ignore: &IgnoreList, files: &mut Vec<PathBuf>) {
+    fn walk(root: &Path, directory: &Path, ignore: &IgnoreList, files: &mut Vec<PathBuf>) {
+        let read_dir = match fs::read_dir(directory) {
+            Ok(read_dir) => read_dir,
+            Err(_) => return,
+        };
+
+        let mut entries: Vec<_> = read_dir
+            .flatten()
+            .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+            .collect();
+        entries.sort_unstable_by_key(|e| e.file_name());
+
+        for entry in entries {
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(_) => continue,
+            };
+            let path = entry.path();
+            let is_dir = file_type.is_dir();
+            let relative_path = path.strip_prefix(root).unwrap_or(&path);
+            if ignore.is_ignored(relative_path.to_str().unwrap_or(""), is_dir) {
+                continue;
+            }
+
+            if is_dir {
+                walk(root, &path, ignore, files);
+            } else if file_type.is_file()
+                && entry
+                    .metadata()
+                    .map(|m| m.len() <= MAX_FILE_LEN)
+                    .unwrap_or(false)
+            {
+                files.push(path);
+            }
+        }
+    }
+
+    walk(root, root, ignore, files);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyword_finding() {
+        let keywords = "TODO FIXME NOTE";
+        assert_eq!(None, find_keyword("", keywords));
+        assert_eq!(None, find_keyword("nothing to do", keywords));
+        assert_eq!(None, find_keyword("TODOS and NOTES", keywords));
+        assert_eq!(None, find_keyword("MY_TODO", keywords));
+        assert_eq!(Some(3), find_keyword("// TODO: fix", keywords));
+        assert_eq!(
+            Some(2),
+            find_keyword("# FIXME(someone) then TODO", keywords)
+        );
+        assert_eq!(Some(7), find_keyword("NOTES, NOTE", keywords));
+        assert_eq!(Some(0), find_keyword("HACK here", "HACK"));
+    }
+}
@@ -1,12 +1,18 @@
 use std::{io, iter};
 
 use crate::{
-    buffer_position::{BufferPosition, BufferRange},
+    buffer::BufferHandle,
+    buffer_position::{char_display_len, BufferPosition, BufferRange},
     buffer_view::{BufferViewHandle, CursorMovementKind},
     client::ClientManager,
+    config::{ClientDisplayConfig, LineNumbers},
+    cursor::Cursor,
     editor::Editor,
     editor_utils::MessageKind,
+    events::CursorShape,
+    git::{ConflictMarker, ConflictSide},
     mode::ModeKind,
+    plugin::{Panel, PanelLine, PanelPosition},
     syntax::{Token, TokenKind},
     theme::Color,
 };
@@ -15,12 +21,14 @@ pub static ENTER_ALTERNATE_BUFFER_CODE: &[u8] = b"\x1b[?1049h";
 pub static EXIT_ALTERNATE_BUFFER_CODE: &[u8] = b"\x1b[?1049l";
 pub static HIDE_CURSOR_CODE: &[u8] = b"\x1b[?25l";
 pub static SHOW_CURSOR_CODE: &[u8] = b"\x1b[?25h";
+pub static RESET_CURSOR_STYLE_CODE: &[u8] = b"\x1b[0 q\x1b]112\x07";
 pub static RESET_STYLE_CODE: &[u8] = b"\x1b[0;49m";
 pub static MODE_256_COLORS_CODE: &[u8] = b"\x1b[=19h";
 pub static BEGIN_TITLE_CODE: &[u8] = b"\x1b]0;";
 pub static END_TITLE_CODE: &[u8] = b"\x07";
 
 static TOO_LONG_PREFIX: &[u8] = b"...";
+const OTHER_CLIENT_CURSORS_CAPACITY: usize = 8;
 
 pub fn clear_line(buf: &mut Vec<u8>) {
     buf.extend_from_slice(b"\x1b[2K");
@@ -39,6 +47,11 @@ pub fn move_cursor_to_next_line(buf: &mut Vec<u8>) {
     buf.extend_from_slice(b"\x1b[1E");
 }
 
+pub fn move_cursor_down_and_back(buf: &mut Vec<u8>, count: usize) {
+    use io::Write;
+    let _ = write!(buf, "\x1b[1B\x1b[{}D", count);
+}
+
 pub fn move_cursor_up(buf: &mut Vec<u8>, count: usize) {
     use io::Write;
     let _ = write!(buf, "\x1b[{}A", count);
@@ -54,6 +67,17 @@ pub fn set_foreground_color(buf: &mut Vec<u8>, color: Color) {
     let _ = write!(buf, "\x1b[38;2;{};{};{}m", color.0, color.1, color.2);
 }
 
+pub fn set_cursor_style(buf: &mut Vec<u8>, shape: CursorShape, color: Color) {
+    use io::Write;
+    let shape = match shape {
+        CursorShape::Hidden => return,
+        CursorShape::Block => 2,
+        CursorShape::Underline => 4,
+        CursorShape::Bar => 6,
+    };
+    let _ = write!(buf, "\x1b[{} q\x1b]12;#{:06x}\x07", shape, color.into_u32());
+}
+
 pub fn set_underlined(buf: &mut Vec<u8>) {
     buf.extend_from_slice(b"\x1b[4m");
 }
@@ -62,8 +86,105 @@ pub fn set_not_underlined(buf: &mut Vec<u8>) {
     buf.extend_from_slice(b"\x1b[24m");
 }
 
+const SIGN_COLUMN_WIDTH: u16 = 2;
+
+pub fn gutter_width(
+    editor: &Editor,
+    display_config: ClientDisplayConfig,
+    buffer_handle: BufferHandle,
+    view_width: u16,
+) -> u16 {
+    let sign_width = if editor.signs.has_signs(buffer_handle) {
+        SIGN_COLUMN_WIDTH
+    } else {
+        0
+    };
+    let number_width = match display_config.line_numbers() {
+        LineNumbers::None => 0,
+        _ => {
+            let mut digits = 1;
+            let mut n = editor.buffers.get(buffer_handle).content().line_count();
+            while n >= 10 {
+                n /= 10;
+                digits += 1;
+            }
+            digits + 1
+        }
+    };
+    (sign_width + number_width).min(view_width / 2)
+}
+
+fn draw_gutter(
+    ctx: &RenderContext,
+    buf: &mut Vec<u8>,
+    buffer_handle: BufferHandle,
+    width: u16,
+    line: Option<(usize, usize)>,
+) {
+    if width == 0 {
+        return;
+    }
+
+    use io::Write;
+    set_background_color(buf, ctx.editor.theme.gutter_background);
+
+    let mut number_width = width as usize;
+    if ctx.editor.signs.has_signs(buffer_handle) {
+        number_width = number_width.saturating_sub(SIGN_COLUMN_WIDTH as _);
+        let sign = line
+            .and_then(|(line_index, _)| ctx.editor.signs.line_sign(buffer_handle, line_index as _));
+        match sign {
+            Some(sign) => {
+                let color = ctx
+                    .editor
+                    .theme
+                    .color(&sign.color)
+                    .unwrap_or(ctx.editor.theme.gutter_sign);
+                set_foreground_color(buf, color);
+                let _ = write!(buf, "{} ", sign.glyph);
+            }
+            None => buf.extend_from_slice(b"  "),
+        }
+    }
+    if number_width == 0 {
+        return;
+    }
+
+    let number = match line {
+        Some((line_index, active_line_index)) => {
+            let is_active = line_index == active_line_index;
+            let color = if is_active {
+                ctx.editor.theme.gutter_active_line_number
+            } else {
+                ctx.editor.theme.gutter_line_number
+            };
+            set_foreground_color(buf, color);
+
+            let distance = line_index.abs_diff(active_line_index);
+            match ctx.display_config.line_numbers() {
+                LineNumbers::None => None,
+                LineNumbers::Absolute => Some(line_index + 1),
+                LineNumbers::Relative => Some(distance),
+                LineNumbers::Hybrid if is_active => Some(line_index + 1),
+                LineNumbers::Hybrid => Some(distance),
+            }
+        }
+        None => None,
+    };
+
+    match number {
+        Some(number) => {
+            let _ = write!(buf, "{:>width$} ", number, width = number_width - 1);
+        }
+        None => {
+            let _ = write!(buf, "{:width$}", "", width = number_width);
+        }
+    }
+}
+
 pub struct RenderContext<'a> {
     pub editor: &'a Editor,
+    pub display_config: ClientDisplayConfig<'a>,
     pub clients: &'a ClientManager,
     pub viewport_size: (u16, u16),
     pub scroll: (u32, u32),
@@ -89,14 +210,14 @@ fn draw_empty_view(ctx: &RenderContext, buf: &mut Vec<u8>) {
     let height = ctx.viewport_size.1 as usize - 1;
     let draw_height = ctx.draw_height as usize;
 
-    let margin_top = (height.saturating_sub(message_lines.len())) / 2;
-    let margin_bottom = draw_height - margin_top - message_lines.len();
+    let margin_top = (height.saturating_sub(message_lines.len()) / 2)
+        .min(draw_height.saturating_sub(message_lines.len()));
+    let margin_bottom = draw_height.saturating_sub(margin_top + message_lines.len());
 
     let mut visual_empty = [0; 4];
     let visual_empty = ctx
-        .editor
-        .config
-        .visual_empty
+        .display_config
+        .visual_empty()
         .encode_utf8(&mut visual_empty)
         .as_bytes();
 
@@ -124,33 +245,77 @@ fn draw_empty_view(ctx: &RenderContext, buf: &mut Vec<u8>) {
     }
 }
 
+// returns the style of the terminal cursor, which is left at the main cursor when visible
 pub fn render(
     ctx: &RenderContext,
     buffer_view_handle: Option<BufferViewHandle>,
     buf: &mut Vec<u8>,
-) {
-    draw_buffer_view(ctx, buffer_view_handle, buf);
+) -> (CursorShape, Color) {
+    let file_tree_width = ctx
+        .editor
+        .file_tree
+        .width(ctx.editor.config.file_tree_width, ctx.viewport_size.0);
+    let main_cursor_cell = if file_tree_width > 0 {
+        let buffer_view_ctx = RenderContext {
+            viewport_size: (ctx.viewport_size.0 - file_tree_width, ctx.viewport_size.1),
+            ..*ctx
+        };
+        let cell = draw_buffer_view(&buffer_view_ctx, buffer_view_handle, buf);
+        draw_file_tree(ctx, file_tree_width as _, buf);
+        cell
+    } else {
+        draw_buffer_view(ctx, buffer_view_handle, buf)
+    };
+    draw_panels(ctx, buf);
     draw_picker(ctx, buf);
     draw_statusbar(ctx, buffer_view_handle, buf);
+    draw_overlay_panels(ctx, buf);
+
+    let (shape, color) = terminal_cursor_style(ctx);
+    match main_cursor_cell {
+        Some((x, y)) if shape != CursorShape::Hidden => {
+            move_cursor_to(buf, y + 1, x + 1);
+            (shape, color)
+        }
+        _ => (CursorShape::Hidden, color),
+    }
 }
 
+fn terminal_cursor_style(ctx: &RenderContext) -> (CursorShape, Color) {
+    let theme = &ctx.editor.theme;
+    if !ctx.display_config.terminal_cursor() || !ctx.has_focus {
+        return (CursorShape::Hidden, theme.inactive_cursor);
+    }
+    match ctx.editor.mode.kind() {
+        ModeKind::Insert => (CursorShape::Bar, theme.insert_cursor),
+        ModeKind::Normal => match ctx.editor.mode.normal_state.movement_kind {
+            CursorMovementKind::PositionAndAnchor => (CursorShape::Block, theme.normal_cursor),
+            CursorMovementKind::PositionOnly => (CursorShape::Underline, theme.select_cursor),
+        },
+        _ => (CursorShape::Hidden, theme.inactive_cursor),
+    }
+}
+
+// returns the screen cell of the main cursor if it was drawn
 fn draw_buffer_view(
     ctx: &RenderContext,
     buffer_view_handle: Option<BufferViewHandle>,
     buf: &mut Vec<u8>,
-) {
+) -> Option<(usize, usize)> {
     let buffer_view_handle = match buffer_view_handle {
         Some(handle) => handle,
         None => {
             draw_empty_view(ctx, buf);
-            return;
+            return None;
         }
     };
 
     let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
     let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
     let cursors = &buffer_view.cursors[..];
-    let active_line_index = buffer_view.cursors.main_cursor().position.line_index as usize;
+    let main_cursor_position = buffer_view.cursors.main_cursor().position;
+    let active_line_index = main_cursor_position.line_index as usize;
+    let mut main_cursor_cell = None;
 
     let cursor_color = if ctx.has_focus {
         match ctx.editor.mode.kind() {
@@ -166,8 +331,31 @@ fn draw_buffer_view(
 
     let cursors_end_index = cursors.len().saturating_sub(1);
 
+    let mut other_client_cursors = [Cursor::zero(); OTHER_CLIENT_CURSORS_CAPACITY];
+    let mut other_client_cursors_len = 0;
+    for client in ctx.clients.iter() {
+        if other_client_cursors_len == other_client_cursors.len() {
+            break;
+        }
+        let handle = match client.buffer_view_handle() {
+            Some(handle) => handle,
+            None => continue,
+        };
+        if handle == buffer_view_handle {
+            continue;
+        }
+        let other_buffer_view = ctx.editor.buffer_views.get(handle);
+        if other_buffer_view.buffer_handle == buffer_view.buffer_handle {
+            other_client_cursors[other_client_cursors_len] =
+                *other_buffer_view.cursors.main_cursor();
+            other_client_cursors_len += 1;
+        }
+    }
+    let other_client_cursors = &other_client_cursors[..other_client_cursors_len];
+
     let buffer_content = buffer.content();
     let highlighted_buffer = buffer.highlighted();
+    let is_large_file = buffer.is_large_file();
     let search_ranges = buffer.search_ranges();
     let search_ranges_end_index = search_ranges.len().saturating_sub(1);
 
@@ -178,10 +366,45 @@ fn draw_buffer_view(
             break;
         }
     }
+    if diagnostics.is_empty() {
+        diagnostics = ctx.editor.linters.buffer_diagnostics(buffer.handle());
+    }
     let diagnostics = diagnostics;
     let diagnostics_end_index = diagnostics.len().saturating_sub(1);
 
-    let display_position_offset = BufferPosition::line_col(ctx.scroll.1 as _, ctx.scroll.0 as _);
+    let mut inlay_hints: &[_] = &[];
+    if ctx.editor.config.lsp_inlay_hints {
+        for client in ctx.editor.lsp.clients() {
+            inlay_hints = client.inlay_hints().buffer_inlay_hints(buffer.handle());
+            if !inlay_hints.is_empty() {
+                break;
+            }
+        }
+    }
+    let inlay_hints = inlay_hints;
+
+    let decorations = ctx.editor.decorations.buffer_decorations(buffer.handle());
+    let mut line_decorations = Vec::new();
+
+    let mut conflict_side = ConflictSide::None.after(
+        buffer_content
+            .lines()
+            .take(ctx.scroll.1 as _)
+            .rev()
+            .find_map(|l| ConflictMarker::parse(l.as_str())),
+    );
+
+    let tab_size = buffer.tab_size(&ctx.editor.config);
+    let soft_wrap = buffer.soft_wrap(ctx.display_config);
+    let gutter_width = gutter_width(
+        ctx.editor,
+        ctx.display_config,
+        buffer.handle(),
+        ctx.viewport_size.0,
+    );
+    let text_width = (ctx.viewport_size.0 - gutter_width) as usize;
+    let scroll_x = if soft_wrap { 0 } else { ctx.scroll.0 };
+    let display_position_offset = BufferPosition::line_col(ctx.scroll.1 as _, scroll_x as _);
 
     let mut current_cursor_index = cursors.len();
     let mut current_cursor_position = BufferPosition::zero();
@@ -216,6 +439,11 @@ fn draw_buffer_view(
         }
     }
 
+    let mut current_inlay_hint_index = inlay_hints
+        .iter()
+        .position(|h| display_position_offset <= h.position)
+        .unwrap_or(inlay_hints.len());
+
     move_cursor_to(buf, 0, 0);
     set_background_color(buf, ctx.editor.theme.background);
     set_not_underlined(buf);
@@ -224,38 +452,34 @@ fn draw_buffer_view(
 
     let mut visual_empty = [0; 4];
     let visual_empty = ctx
-        .editor
-        .config
-        .visual_empty
+        .display_config
+        .visual_empty()
         .encode_utf8(&mut visual_empty)
         .as_bytes();
 
     let mut visual_space = [0; 4];
     let visual_space = ctx
-        .editor
-        .config
-        .visual_space
+        .display_config
+        .visual_space()
         .encode_utf8(&mut visual_space)
         .as_bytes();
 
     let mut visual_tab_first = [0; 4];
     let visual_tab_first = ctx
-        .editor
-        .config
-        .visual_tab_first
+        .display_config
+        .visual_tab_first()
         .encode_utf8(&mut visual_tab_first)
         .as_bytes();
 
     let mut visual_tab_repeat = [0; 4];
     let visual_tab_repeat = ctx
-        .editor
-        .config
-        .visual_tab_repeat
+        .display_config
+        .visual_tab_repeat()
         .encode_utf8(&mut visual_tab_repeat)
         .as_bytes();
 
     let mut lines_drawn_count = 0;
-    for (line_index, line) in buffer_content
+    'lines: for (line_index, line) in buffer_content
         .lines()
         .enumerate()
         .skip(ctx.scroll.1 as _)
@@ -267,34 +491,129 @@ fn draw_buffer_view(
             Selection(TokenKind),
             Highlight,
             Cursor,
+            OtherClientSelection(TokenKind),
+            OtherClientCursor,
+            InlayHint,
+            Decoration(TokenKind, Color),
+            LineWrap,
         }
 
+        if lines_drawn_count >= ctx.draw_height {
+            break;
+        }
         lines_drawn_count += 1;
 
         let line = line.as_str();
         let mut draw_state = DrawState::Token(TokenKind::Text);
-        let mut was_inside_diagnostic_range = false;
+        let mut was_underlined = false;
         let mut x = 0;
         let mut last_line_token = Token::default();
-        let mut line_tokens = highlighted_buffer.line_tokens(line_index).iter();
+        let mut line_tokens = if is_large_file {
+            [].iter()
+        } else {
+            highlighted_buffer.line_tokens(line_index).iter()
+        };
+
+        let conflict_marker = ConflictMarker::parse(line);
+        let line_conflict_side = match conflict_marker {
+            Some(ConflictMarker::Theirs) => ConflictSide::Theirs,
+            _ => conflict_side.after(conflict_marker),
+        };
+        conflict_side = conflict_side.after(conflict_marker);
 
         let background_color = if line_index == active_line_index as _ {
             ctx.editor.theme.active_line_background
         } else {
-            ctx.editor.theme.background
+            match line_conflict_side {
+                ConflictSide::Ours => ctx.editor.theme.diff_removed_background,
+                ConflictSide::Theirs => ctx.editor.theme.diff_added_background,
+                ConflictSide::None | ConflictSide::Base => ctx.editor.theme.background,
+            }
         };
 
+        draw_gutter(
+            ctx,
+            buf,
+            buffer.handle(),
+            gutter_width,
+            Some((line_index, active_line_index)),
+        );
         set_background_color(buf, background_color);
         set_foreground_color(buf, ctx.editor.theme.token_text);
 
+        line_decorations.clear();
+        line_decorations.extend(decorations.iter().filter(|d| {
+            d.range.from.line_index as usize <= line_index
+                && line_index <= d.range.to.line_index as usize
+        }));
+
         for (char_index, c) in line.char_indices().chain(iter::once((line.len(), '\n'))) {
-            if char_index < ctx.scroll.0 as _ {
+            if char_index < scroll_x as _ {
                 continue;
             }
 
+            if soft_wrap && x > 0 && x + char_display_len(c, tab_size) > text_width {
+                if was_underlined {
+                    was_underlined = false;
+                    set_not_underlined(buf);
+                }
+                draw_state = DrawState::LineWrap;
+                set_background_color(buf, background_color);
+                if x < text_width {
+                    clear_until_new_line(buf);
+                }
+                move_cursor_to_next_line(buf);
+
+                if lines_drawn_count >= ctx.draw_height {
+                    break 'lines;
+                }
+                lines_drawn_count += 1;
+                draw_gutter(ctx, buf, buffer.handle(), gutter_width, None);
+                set_background_color(buf, background_color);
+                x = 0;
+            }
+
             let buf_len = buf.len();
+            let previous_x = x;
             let char_position = BufferPosition::line_col(line_index as _, char_index as _);
 
+            while current_inlay_hint_index < inlay_hints.len() {
+                let hint = &inlay_hints[current_inlay_hint_index];
+                if char_position < hint.position {
+                    break;
+                }
+                current_inlay_hint_index += 1;
+                if hint.position < char_position {
+                    continue;
+                }
+
+                if was_underlined {
+                    was_underlined = false;
+                    set_not_underlined(buf);
+                }
+                draw_state = DrawState::InlayHint;
+                set_background_color(buf, background_color);
+                set_foreground_color(buf, ctx.editor.theme.token_comment);
+
+                // leave room for the hinted char so hints never push text out of view
+                let hint_width = text_width.saturating_sub(char_display_len(c, tab_size));
+                for hint_char in hint.label.chars() {
+                    let len = char_display_len(hint_char, tab_size);
+                    if x + len > hint_width {
+                        break;
+                    }
+                    x += len;
+                    match hint_char {
+                        '\t' | '\n' | '\r' => {
+                            for _ in 0..len {
+                                buf.push(b' ');
+                            }
+                        }
+                        _ => buf.extend_from_slice(hint_char.encode_utf8(&mut char_buf).as_bytes()),
+                    }
+                }
+            }
+
             let token_kind = if c.is_ascii_whitespace() {
                 TokenKind::Whitespace
             } else {
@@ -340,6 +659,15 @@ fn draw_buffer_view(
             let inside_search_range = current_search_range.from <= char_position
                 && char_position < current_search_range.to;
 
+            let mut on_other_client_cursor = false;
+            let mut inside_other_client_cursor_range = false;
+            for cursor in other_client_cursors {
+                let range = cursor.to_range();
+                on_other_client_cursor |= char_position == cursor.position;
+                inside_other_client_cursor_range |=
+                    range.from <= char_position && char_position < range.to;
+            }
+
             while current_diagnostic_range.to < char_position
                 && current_diagnostic_index < diagnostics_end_index
             {
@@ -349,9 +677,18 @@ fn draw_buffer_view(
             let inside_diagnostic_range = current_diagnostic_range.from <= char_position
                 && char_position < current_diagnostic_range.to;
 
-            if inside_diagnostic_range != was_inside_diagnostic_range {
-                was_inside_diagnostic_range = inside_diagnostic_range;
-                if inside_diagnostic_range {
+            let decoration_color = line_decorations.iter().rev().find_map(|d| {
+                let inside = d.range.from <= char_position && char_position < d.range.to;
+                d.color.filter(|_| inside)
+            });
+            let underlined = inside_diagnostic_range
+                || line_decorations.iter().any(|d| {
+                    d.underlined && d.range.from <= char_position && char_position < d.range.to
+                });
+
+            if underlined != was_underlined {
+                was_underlined = underlined;
+                if underlined {
                     set_underlined(buf);
                 } else {
                     set_not_underlined(buf);
@@ -370,19 +707,37 @@ fn draw_buffer_view(
                     set_background_color(buf, text_color);
                     set_foreground_color(buf, background_color);
                 }
+            } else if on_other_client_cursor {
+                if draw_state != DrawState::OtherClientCursor {
+                    draw_state = DrawState::OtherClientCursor;
+                    set_background_color(buf, ctx.editor.theme.other_client_cursor);
+                    set_foreground_color(buf, text_color);
+                }
+            } else if inside_other_client_cursor_range {
+                if draw_state != DrawState::OtherClientSelection(token_kind) {
+                    draw_state = DrawState::OtherClientSelection(token_kind);
+                    set_background_color(buf, ctx.editor.theme.other_client_selection);
+                    set_foreground_color(buf, text_color);
+                }
             } else if inside_search_range {
                 if draw_state != DrawState::Highlight {
                     draw_state = DrawState::Highlight;
                     set_background_color(buf, ctx.editor.theme.highlight);
                     set_foreground_color(buf, background_color);
                 }
+            } else if let Some(color) = decoration_color {
+                if draw_state != DrawState::Decoration(token_kind, color) {
+                    draw_state = DrawState::Decoration(token_kind, color);
+                    set_background_color(buf, color);
+                    set_foreground_color(buf, text_color);
+                }
             } else if draw_state != DrawState::Token(token_kind) {
                 draw_state = DrawState::Token(token_kind);
                 set_background_color(buf, background_color);
                 set_foreground_color(buf, text_color);
             }
 
-            let previous_x = x;
+            let char_x = x;
             match c {
                 '\n' => {
                     x += 1;
@@ -393,7 +748,7 @@ fn draw_buffer_view(
                     buf.extend_from_slice(visual_space);
                 }
                 '\t' => {
-                    let tab_size = ctx.editor.config.tab_size.get() as usize;
+                    let tab_size = tab_size.get() as usize;
                     x += tab_size;
 
                     buf.extend_from_slice(visual_tab_first);
@@ -407,16 +762,41 @@ fn draw_buffer_view(
                 }
             }
 
-            if x > ctx.viewport_size.0 as _ {
+            if x > text_width {
                 x = previous_x;
                 buf.truncate(buf_len);
                 break;
             }
+
+            if char_position == main_cursor_position {
+                let y = lines_drawn_count as usize - 1;
+                main_cursor_cell = Some((gutter_width as usize + char_x, y));
+            }
         }
 
         set_background_color(buf, background_color);
 
-        if x < ctx.viewport_size.0 as _ {
+        for decoration in &line_decorations {
+            if decoration.text.is_empty() || decoration.range.from.line_index as usize != line_index
+            {
+                continue;
+            }
+
+            let color = decoration.color.unwrap_or(ctx.editor.theme.token_comment);
+            set_foreground_color(buf, color);
+            for c in iter::once(' ').chain(decoration.text.chars()) {
+                if x >= text_width {
+                    break;
+                }
+                x += 1;
+                match c {
+                    '\t' | '\n' | '\r' => buf.push(b' '),
+                    c => buf.extend_from_slice(c.encode_utf8(&mut char_buf).as_bytes()),
+                }
+            }
+        }
+
+        if x < text_width {
             clear_until_new_line(buf);
         }
 
@@ -432,6 +812,199 @@ fn draw_buffer_view(
         clear_until_new_line(buf);
         move_cursor_to_next_line(buf);
     }
+
+    main_cursor_cell
+}
+
+fn draw_file_tree(ctx: &RenderContext, width: usize, buf: &mut Vec<u8>) {
+    let theme = &ctx.editor.theme;
+    let tree = &ctx.editor.file_tree;
+    let has_focus = ctx.has_focus && ctx.editor.mode.kind() == ModeKind::FileTree;
+    let x = ctx.viewport_size.0 as usize - width + 1;
+    let height = ctx.draw_height as usize;
+    if height == 0 {
+        return;
+    }
+
+    let header_background = if has_focus {
+        theme.statusbar_active_background
+    } else {
+        theme.statusbar_inactive_background
+    };
+    let root_name = tree
+        .root()
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("");
+
+    move_cursor_to(buf, 1, x);
+    set_background_color(buf, theme.statusbar_inactive_background);
+    buf.push(b' ');
+    draw_panel_line(
+        ctx,
+        root_name,
+        theme.token_text,
+        header_background,
+        width - 1,
+        buf,
+    );
+
+    let mut text = String::new();
+    let mut entries = tree.entries().iter().enumerate().skip(tree.scroll());
+    for y in 1..height {
+        move_cursor_to(buf, y + 1, x);
+        set_background_color(buf, theme.statusbar_inactive_background);
+        buf.push(b' ');
+
+        text.clear();
+        let (foreground, background) = match entries.next() {
+            Some((i, entry)) => {
+                for _ in 0..entry.depth {
+                    text.push_str("  ");
+                }
+                if entry.is_dir {
+                    text.push_str(if entry.expanded { "- " } else { "+ " });
+                } else {
+                    text.push_str("  ");
+                }
+                text.push_str(entry.name());
+
+                let foreground = if entry.is_dir {
+                    theme.token_keyword
+                } else {
+                    theme.token_text
+                };
+                let background = if i != tree.selected_index() {
+                    theme.background
+                } else if has_focus {
+                    theme.statusbar_active_background
+                } else {
+                    theme.active_line_background
+                };
+                (foreground, background)
+            }
+            None => (theme.token_text, theme.background),
+        };
+        draw_panel_line(ctx, &text, foreground, background, width - 1, buf);
+    }
+
+    move_cursor_to(buf, height + 1, 1);
+}
+
+fn draw_panel_line(
+    ctx: &RenderContext,
+    text: &str,
+    foreground: Color,
+    background: Color,
+    width: usize,
+    buf: &mut Vec<u8>,
+) {
+    set_background_color(buf, background);
+    set_foreground_color(buf, foreground);
+
+    let mut char_buf = [0; std::mem::size_of::<char>()];
+    let mut x = 0;
+    for c in text.chars() {
+        if x >= width {
+            break;
+        }
+        match c {
+            '\t' => buf.push(b' '),
+            c => buf.extend_from_slice(c.encode_utf8(&mut char_buf).as_bytes()),
+        }
+        x += 1;
+    }
+    for _ in x..width {
+        buf.push(b' ');
+    }
+    set_background_color(buf, ctx.editor.theme.background);
+}
+
+fn draw_panel(ctx: &RenderContext, panel: &Panel, height: usize, width: usize, buf: &mut Vec<u8>) {
+    let theme = &ctx.editor.theme;
+    let has_focus = ctx.has_focus
+        && ctx.editor.mode.kind() == ModeKind::Plugin
+        && !panel.mode.is_empty()
+        && ctx.editor.mode.plugin_state.name == panel.mode;
+    let header_background = if has_focus {
+        theme.statusbar_active_background
+    } else {
+        theme.statusbar_inactive_background
+    };
+    draw_panel_line(
+        ctx,
+        &panel.name,
+        theme.token_text,
+        header_background,
+        width,
+        buf,
+    );
+
+    let empty_line = PanelLine {
+        text: String::new(),
+        foreground: None,
+        background: None,
+    };
+    for i in 1..height {
+        match panel.position {
+            PanelPosition::Bottom => move_cursor_to_next_line(buf),
+            PanelPosition::Overlay => move_cursor_down_and_back(buf, width),
+        }
+        let line = panel.lines.get(i - 1).unwrap_or(&empty_line);
+        draw_panel_line(
+            ctx,
+            &line.text,
+            line.foreground.unwrap_or(theme.token_text),
+            line.background.unwrap_or(theme.background),
+            width,
+            buf,
+        );
+    }
+    if panel.position == PanelPosition::Bottom {
+        move_cursor_to_next_line(buf);
+    }
+}
+
+fn draw_panels(ctx: &RenderContext, buf: &mut Vec<u8>) {
+    let width = ctx.viewport_size.0 as usize;
+    let mut height_left = ctx.editor.panels.bottom_height(ctx.viewport_size.1) as usize;
+    for panel in ctx.editor.panels.iter() {
+        if panel.position != PanelPosition::Bottom || height_left == 0 {
+            continue;
+        }
+        let height = (panel.height as usize).min(height_left);
+        height_left -= height;
+        draw_panel(ctx, panel, height, width, buf);
+    }
+}
+
+fn draw_overlay_panels(ctx: &RenderContext, buf: &mut Vec<u8>) {
+    let viewport_width = ctx.viewport_size.0 as usize;
+    let viewport_height = ctx.viewport_size.1 as usize;
+
+    let mut y = 1;
+    for panel in ctx.editor.panels.iter() {
+        if panel.position != PanelPosition::Overlay {
+            continue;
+        }
+
+        let height = (panel.height as usize).min(viewport_height.saturating_sub(y));
+        if height == 0 {
+            break;
+        }
+        let width = panel
+            .lines
+            .iter()
+            .map(|l| l.text.chars().count())
+            .chain(iter::once(panel.name.chars().count()))
+            .max()
+            .unwrap_or(0)
+            .min(viewport_width / 2);
+
+        move_cursor_to(buf, y, viewport_width - width + 1);
+        draw_panel(ctx, panel, height, width, buf);
+        y += height;
+    }
 }
 
 fn draw_picker(ctx: &RenderContext, buf: &mut Vec<u8>) {
@@ -514,7 +1087,9 @@ fn draw_statusbar(
 ) {
     let view_name;
     let needs_save;
+    let large_file;
     let main_cursor_position;
+    let main_cursor_column;
     let search_ranges;
 
     match buffer_view_handle {
@@ -524,13 +1099,17 @@ fn draw_statusbar(
 
             view_name = buffer.path.to_str().unwrap_or("");
             needs_save = buffer.needs_save();
+            large_file = buffer.is_large_file();
             main_cursor_position = buffer_view.cursors.main_cursor().position;
+            main_cursor_column = buffer.content().char_column(main_cursor_position);
             search_ranges = buffer.search_ranges();
         }
         None => {
             view_name = "";
             needs_save = false;
+            large_file = false;
             main_cursor_position = BufferPosition::zero();
+            main_cursor_column = 0;
             search_ranges = &[];
         }
     }
@@ -578,6 +1157,17 @@ fn draw_statusbar(
                 buf.extend_from_slice(text);
                 Some(text.len())
             }
+            ModeKind::Debug if message_is_empty => {
+                let text = b"-- DEBUG --";
+                buf.extend_from_slice(text);
+                Some(text.len())
+            }
+            ModeKind::Plugin if message_is_empty => {
+                let name = ctx.editor.mode.plugin_state.name.to_uppercase();
+                let previous_len = buf.len();
+                let _ = write!(buf, "-- {} --", name);
+                Some(buf.len() - previous_len)
+            }
             ModeKind::Command | ModeKind::Picker | ModeKind::ReadLine => {
                 let read_line = &ctx.editor.read_line;
 
@@ -590,6 +1180,40 @@ fn draw_statusbar(
                 set_background_color(buf, cursor_color);
                 buf.push(b' ');
                 set_background_color(buf, background_active_color);
+
+                let help = match ctx.editor.mode.kind() {
+                    ModeKind::Command => ctx.editor.mode.command_state.help(),
+                    _ => None,
+                };
+                if let Some(help) = help {
+                    let used_width =
+                        read_line.prompt().chars().count() + read_line.input().chars().count() + 1;
+                    let mut available_width =
+                        (ctx.viewport_size.0 as usize).saturating_sub(used_width);
+                    set_foreground_color(buf, ctx.editor.theme.token_comment);
+                    let mut write_help = |text: &str| {
+                        for c in text.chars() {
+                            if available_width == 0 {
+                                break;
+                            }
+                            available_width -= 1;
+                            let mut char_buf = [0; std::mem::size_of::<char>()];
+                            buf.extend_from_slice(c.encode_utf8(&mut char_buf).as_bytes());
+                        }
+                    };
+
+                    write_help("  ");
+                    write_help(help.usage);
+                    if !help.summary.is_empty() {
+                        write_help("  ");
+                        write_help(help.summary);
+                    }
+                    for flag in help.flags() {
+                        write_help(" ");
+                        write_help(flag);
+                    }
+                    set_foreground_color(buf, foreground_color);
+                }
                 None
             }
             _ => {
@@ -679,6 +1303,31 @@ fn draw_statusbar(
                 let _ = write!(buf, "{}", key);
             }
             buf.push(b' ');
+
+            let git = &ctx.editor.git;
+            if !git.branch().is_empty() {
+                buf.extend_from_slice(git.branch().as_bytes());
+                if git.ahead() > 0 {
+                    let _ = write!(buf, " +{}", git.ahead());
+                }
+                if git.behind() > 0 {
+                    let _ = write!(buf, " -{}", git.behind());
+                }
+                if git.is_dirty() {
+                    buf.extend_from_slice(b" ~");
+                }
+                buf.push(b' ');
+            }
+
+            let segments = ctx.editor.status_segments.text();
+            if !segments.is_empty() {
+                buf.extend_from_slice(segments.as_bytes());
+                buf.push(b' ');
+            }
+        }
+
+        if large_file {
+            buf.extend_from_slice(b"[large] ");
         }
 
         if needs_save {
@@ -693,7 +1342,7 @@ fn draw_statusbar(
 
         if !view_name.is_empty() {
             let line_number = main_cursor_position.line_index + 1;
-            let column_number = main_cursor_position.column_byte_index + 1;
+            let column_number = main_cursor_column + 1;
             let _ = write!(buf, ":{},{}", line_number, column_number);
         }
         buf.push(b' ');
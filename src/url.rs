@@ -0,0 +1,153 @@
+use std::ops::Range;
+
+use crate::{
+    buffer::{BufferContent, BufferHandle},
+    buffer_position::{BufferPosition, BufferRange},
+    decoration::DecorationCollection,
+};
+
+pub const URL_DECORATION_NAMESPACE: &str = "url";
+
+const SCHEMES: &[&str] = &["http", "https", "ftp", "file"];
+
+fn is_scheme_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')
+}
+
+fn is_url_char(c: char) -> bool {
+    !c.is_whitespace()
+        && !matches!(
+            c,
+            '<' | '>' | '"' | '\'' | '`' | '{' | '}' | '|' | '\\' | '^'
+        )
+}
+
+fn trim_url_end(url: &str) -> usize {
+    let mut end = url.len();
+    loop {
+        let trimmed = &url[..end];
+        match trimmed.chars().next_back() {
+            Some('.' | ',' | ';' | ':' | '!' | '?') => end -= 1,
+            Some(c @ (')' | ']')) => {
+                let open = if c == ')' { '(' } else { '[' };
+                if trimmed.matches(open).count() < trimmed.matches(c).count() {
+                    end -= 1;
+                } else {
+                    break;
+                }
+            }
+            _ => break,
+        }
+    }
+    end
+}
+
+pub struct Urls<'a> {
+    line: &'a str,
+    index: usize,
+}
+impl<'a> Iterator for Urls<'a> {
+    type Item = Range<usize>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let separator_index = self.index + self.line[self.index..].find("://")?;
+            let rest_index = separator_index + 3;
+
+            let scheme_index = self.line[..separator_index]
+                .rfind(|c| !is_scheme_char(c))
+                .map(|i| i + 1)
+                .unwrap_or(0)
+                .max(self.index);
+            let scheme = &self.line[scheme_index..separator_index];
+
+            let rest = &self.line[rest_index..];
+            let rest_len = rest.find(|c| !is_url_char(c)).unwrap_or(rest.len());
+            let end = rest_index + trim_url_end(&rest[..rest_len]);
+            self.index = rest_index + rest_len;
+
+            if end > rest_index && SCHEMES.iter().any(|s| s.eq_ignore_ascii_case(scheme)) {
+                return Some(scheme_index..end);
+            }
+        }
+    }
+}
+
+pub fn find_urls(line: &str) -> Urls<'_> {
+    Urls { line, index: 0 }
+}
+
+pub fn find_url_at(line: &str, index: usize) -> Option<&str> {
+    find_urls(line)
+        .find(|range| range.start <= index && index < range.end)
+        .map(|range| &line[range])
+}
+
+pub fn refresh_decorations(
+    decorations: &mut DecorationCollection,
+    buffer_handle: BufferHandle,
+    content: &BufferContent,
+    lines: Range<usize>,
+) {
+    let lines = lines.start..lines.end.min(content.line_count());
+    decorations.clear_lines(
+        buffer_handle,
+        URL_DECORATION_NAMESPACE,
+        lines.start as _,
+        lines.end as _,
+    );
+
+    for line_index in lines {
+        let line = content.line_at(line_index).as_str();
+        for range in find_urls(line) {
+            let range = BufferRange::between(
+                BufferPosition::line_col(line_index as _, range.start as _),
+                BufferPosition::line_col(line_index as _, range.end as _),
+            );
+            decorations.add(
+                buffer_handle,
+                URL_DECORATION_NAMESPACE,
+                range,
+                None,
+                true,
+                "",
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(line: &str) -> Vec<&str> {
+        find_urls(line).map(|range| &line[range]).collect()
+    }
+
+    #[test]
+    fn url_detection() {
+        assert!(urls("").is_empty());
+        assert!(urls("no urls here: ://").is_empty());
+        assert!(urls("ssh://host and http://").is_empty());
+
+        assert_eq!(vec!["https://example.com"], urls("https://example.com"));
+        assert_eq!(
+            vec!["http://a.com/x?y=1&z=2#w", "FILE:///tmp/file.txt"],
+            urls("see http://a.com/x?y=1&z=2#w, or FILE:///tmp/file.txt.")
+        );
+        assert_eq!(
+            vec!["https://en.wikipedia.org/wiki/Rust_(programming_language)"],
+            urls("(https://en.wikipedia.org/wiki/Rust_(programming_language))")
+        );
+        assert_eq!(
+            vec!["https://example.com/path"],
+            urls("<a href=\"https://example.com/path\">")
+        );
+        assert_eq!(vec!["http://b.org"], urls("xhttp://a.org http://b.org"));
+
+        let line = "open https://example.com now";
+        assert_eq!(None, find_url_at(line, 4));
+        assert_eq!(Some("https://example.com"), find_url_at(line, 5));
+        assert_eq!(Some("https://example.com"), find_url_at(line, 23));
+        assert_eq!(None, find_url_at(line, 24));
+    }
+}
@@ -75,6 +75,51 @@ impl<'a> DoubleEndedIterator for WordIter<'a> {
     }
 }
 
+pub fn is_sub_word_start(previous: char, current: char, next: char) -> bool {
+    if !current.is_ascii_alphanumeric() {
+        return false;
+    }
+    if !previous.is_ascii_alphanumeric() {
+        return true;
+    }
+
+    (previous.is_ascii_lowercase() && current.is_ascii_uppercase())
+        || (previous.is_ascii_uppercase()
+            && current.is_ascii_uppercase()
+            && next.is_ascii_lowercase())
+        || (previous.is_ascii_alphabetic() && current.is_ascii_digit())
+        || (previous.is_ascii_digit() && current.is_ascii_alphabetic())
+}
+
+#[derive(Clone)]
+pub struct SubWordIter<'a>(pub &'a str);
+impl<'a> Iterator for SubWordIter<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.0.find(|c: char| c.is_ascii_alphanumeric())?;
+        let text = &self.0[start..];
+
+        let mut chars = text.chars();
+        let mut previous = chars.next()?;
+        let mut end = previous.len_utf8();
+        let mut current = chars.next().unwrap_or('\0');
+        while current.is_ascii_alphanumeric() {
+            let next = chars.clone().next().unwrap_or('\0');
+            if is_sub_word_start(previous, current, next) {
+                break;
+            }
+            end += current.len_utf8();
+            previous = current;
+            current = chars.next().unwrap_or('\0');
+        }
+
+        let (word, rest) = text.split_at(end);
+        self.0 = rest;
+        Some(word)
+    }
+}
+
 #[derive(Default)]
 struct Word {
     text: String,
@@ -129,14 +174,15 @@ impl<'a> WordIndicesIter<'a> {
     }
 }
 impl<'a> Iterator for WordIndicesIter<'a> {
-    type Item = (usize, &'a str);
+    type Item = (usize, &'a str, usize);
     fn next(&mut self) -> Option<Self::Item> {
         while self.next_index < self.words.len() {
             let index = self.next_index;
             self.next_index += 1;
 
-            if self.words[index].count > 0 {
-                return Some((index, &self.words[index].text));
+            let word = &self.words[index];
+            if word.count > 0 {
+                return Some((index, &word.text, word.count));
             }
         }
 
@@ -265,6 +311,23 @@ mod tests {
         assert_eq!(None, iter.next());
     }
 
+    #[test]
+    fn sub_word_iter() {
+        fn sub_words(word: &str) -> Vec<&str> {
+            SubWordIter(word).collect()
+        }
+
+        assert!(sub_words("").is_empty());
+        assert!(sub_words("__").is_empty());
+        assert_eq!(vec!["word"], sub_words("word"));
+        assert_eq!(vec!["foo", "Bar", "Baz"], sub_words("fooBarBaz"));
+        assert_eq!(vec!["Foo", "Bar"], sub_words("FooBar"));
+        assert_eq!(vec!["foo", "bar", "baz"], sub_words("_foo_bar__baz_"));
+        assert_eq!(vec!["HTTP", "Server"], sub_words("HTTPServer"));
+        assert_eq!(vec!["MAX", "LEN"], sub_words("MAX_LEN"));
+        assert_eq!(vec!["utf", "8", "Len"], sub_words("utf8Len"));
+    }
+
     #[test]
     fn word_database_insert_remove() {
         fn unique_word_count(word_database: &WordDatabase) -> usize {
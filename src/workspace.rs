@@ -0,0 +1,131 @@
+use std::path::{Path, PathBuf};
+
+use crate::glob::{Glob, InvalidGlobError};
+
+const DEFAULT_ROOT_MARKERS: &[&str] = &[".git", ".pepper"];
+
+struct RootMarkers {
+    glob: Glob,
+    markers: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct Workspace {
+    roots: Vec<PathBuf>,
+    markers: Vec<RootMarkers>,
+}
+
+impl Workspace {
+    pub fn new(current_directory: &Path) -> Self {
+        let mut workspace = Self::default();
+        let root = workspace
+            .detect_root(current_directory, "")
+            .unwrap_or(current_directory)
+            .to_path_buf();
+        workspace.roots.push(root);
+        workspace
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    pub fn add_root(&mut self, path: &Path) -> bool {
+        if self.roots.iter().any(|r| r == path) {
+            return false;
+        }
+        self.roots.push(path.into());
+        true
+    }
+
+    pub fn remove_root(&mut self, path: &Path) -> bool {
+        let len = self.roots.len();
+        self.roots.retain(|r| r != path);
+        self.roots.len() != len
+    }
+
+    pub fn add_markers(&mut self, glob: &str, markers: &str) -> Result<(), InvalidGlobError> {
+        let mut root_glob = Glob::default();
+        root_glob.compile(glob)?;
+        self.markers.push(RootMarkers {
+            glob: root_glob,
+            markers: markers.split_whitespace().map(String::from).collect(),
+        });
+        Ok(())
+    }
+
+    fn detect_root<'a>(&self, path: &'a Path, glob_path: &str) -> Option<&'a Path> {
+        let language_markers = self
+            .markers
+            .iter()
+            .filter(|m| m.glob.matches(glob_path))
+            .flat_map(|m| m.markers.iter().map(String::as_str));
+        let markers: Vec<_> = language_markers
+            .chain(DEFAULT_ROOT_MARKERS.iter().copied())
+            .collect();
+
+        path.ancestors()
+            .find(|dir| markers.iter().any(|m| dir.join(m).exists()))
+    }
+
+    pub fn root_for(&self, current_directory: &Path, path: &Path) -> PathBuf {
+        let absolute_path = current_directory.join(path);
+        let glob_path = path.to_str().unwrap_or("");
+        if let Some(root) = self.detect_root(&absolute_path, glob_path) {
+            return root.into();
+        }
+
+        self.roots
+            .iter()
+            .filter(|r| absolute_path.starts_with(r))
+            .max_by_key(|r| r.as_os_str().len())
+            .or_else(|| self.roots.first())
+            .cloned()
+            .unwrap_or_else(|| current_directory.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn root_detection() {
+        let base = std::env::temp_dir().join(format!("pepper-workspace-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(base.join("project/.git")).unwrap();
+        fs::create_dir_all(base.join("project/crates/lib/src")).unwrap();
+        fs::create_dir_all(base.join("other/src")).unwrap();
+        fs::write(base.join("project/crates/lib/Cargo.toml"), "").unwrap();
+
+        let project = base.join("project");
+        let mut workspace = Workspace::new(&project.join("crates"));
+        assert_eq!(std::slice::from_ref(&project), workspace.roots());
+
+        let lib_file = Path::new("crates/lib/src/lib.rs");
+        assert_eq!(project, workspace.root_for(&project, lib_file));
+        workspace.add_markers("**/*.rs", "Cargo.toml").unwrap();
+        assert_eq!(
+            project.join("crates/lib"),
+            workspace.root_for(&project, lib_file)
+        );
+        assert_eq!(
+            project,
+            workspace.root_for(&project, Path::new("crates/lib/README.md"))
+        );
+
+        let other = base.join("other");
+        let other_file = other.join("src/main.rs");
+        if !base.ancestors().any(|dir| dir.join(".git").exists()) {
+            assert_eq!(project, workspace.root_for(&project, &other_file));
+            assert!(workspace.add_root(&other));
+            assert!(!workspace.add_root(&other));
+            assert_eq!(other, workspace.root_for(&project, &other_file));
+            assert!(workspace.remove_root(&other));
+            assert!(!workspace.remove_root(&other));
+        }
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}